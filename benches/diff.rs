@@ -0,0 +1,103 @@
+//! Benchmarks for the diff code paths that grow with the size of a change
+//!
+//! Run with `cargo bench --bench diff`.
+
+use committor::diff::{get_staged_changes_from_repo, sanitize_diff};
+use committor::prompt::{sanitize_diff_for_prompt, PromptBudget, PromptBuilder};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use git2::{Repository, Signature};
+use std::fs;
+use std::hint::black_box;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// A diff of `lines` lines mixing ordinary code, long lines and lines that look sensitive
+fn synthetic_diff(lines: usize) -> String {
+    let mut diff = String::from("diff --git a/src/lib.rs b/src/lib.rs\n@@ -1,1 +1,1 @@\n");
+    for i in 0..lines {
+        match i % 10 {
+            0 => diff.push_str(&format!("+let api_key = \"value{i}\";\n")),
+            1 => diff.push_str(&format!("+// {}\n", "long comment ".repeat(30))),
+            2 => diff.push_str(&format!("-fn removed_{i}() {{}}\n")),
+            _ => diff.push_str(&format!("+    let value_{i} = compute({i});\n")),
+        }
+    }
+    diff
+}
+
+fn bench_sanitize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sanitize");
+    let unbounded = PromptBuilder::new().budget(PromptBudget {
+        max_lines: usize::MAX,
+        max_line_length: 150,
+    });
+
+    for lines in [1_000, 10_000, 100_000] {
+        let diff = synthetic_diff(lines);
+        group.throughput(Throughput::Bytes(diff.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::new("prompt_default", lines),
+            &diff,
+            |b, diff| b.iter(|| sanitize_diff_for_prompt(black_box(diff))),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("prompt_unbounded", lines),
+            &diff,
+            |b, diff| b.iter(|| unbounded.sanitize(black_box(diff))),
+        );
+        group.bench_with_input(BenchmarkId::new("diff", lines), &diff, |b, diff| {
+            b.iter(|| sanitize_diff(black_box(diff)))
+        });
+    }
+    group.finish();
+}
+
+/// A repository with `files` committed files, each then changed and staged
+fn repo_with_staged_changes(files: usize) -> (TempDir, Repository) {
+    let dir = TempDir::new().unwrap();
+    let repo = Repository::init(dir.path()).unwrap();
+    write_files(dir.path(), files, "original");
+    let signature = Signature::now("Bench", "bench@example.com").unwrap();
+    let tree_id = stage_all(&repo);
+    {
+        let tree = repo.find_tree(tree_id).unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "initial", &tree, &[])
+            .unwrap();
+    }
+
+    write_files(dir.path(), files, "changed");
+    stage_all(&repo);
+    (dir, repo)
+}
+
+fn write_files(root: &Path, files: usize, content: &str) {
+    for i in 0..files {
+        let body: String = (0..50)
+            .map(|line| format!("{content} {i} {line}\n"))
+            .collect();
+        fs::write(root.join(format!("file_{i}.txt")), body).unwrap();
+    }
+}
+
+fn stage_all(repo: &Repository) -> git2::Oid {
+    let mut index = repo.index().unwrap();
+    index
+        .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+        .unwrap();
+    index.write().unwrap();
+    index.write_tree().unwrap()
+}
+
+fn bench_staged_changes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("staged_changes");
+    for files in [10, 100, 1_000] {
+        let (_dir, repo) = repo_with_staged_changes(files);
+        group.bench_with_input(BenchmarkId::from_parameter(files), &repo, |b, repo| {
+            b.iter(|| get_staged_changes_from_repo(black_box(repo)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_sanitize, bench_staged_changes);
+criterion_main!(benches);
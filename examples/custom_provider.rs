@@ -0,0 +1,124 @@
+//! Plugging your own provider into Committor
+//!
+//! Anything implementing `AIProvider` can generate messages: a client for another
+//! service, a wrapper adding logging or caching around a built-in provider, or a
+//! test double. This example wraps a canned provider, so it runs without a model:
+//!
+//! ```sh
+//! cargo run --example custom_provider
+//! ```
+
+use anyhow::Result;
+use async_trait::async_trait;
+use committor::providers::AIProvider;
+use committor::settings::Settings;
+use committor::{diff, Committor, Config};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Diff used when nothing is staged, so the example always has something to describe
+const SAMPLE_DIFF: &str = "diff --git a/src/auth.rs b/src/auth.rs
+--- a/src/auth.rs
++++ b/src/auth.rs
+@@ -1,3 +1,7 @@
++pub fn validate_token(token: &str) -> bool {
++    !token.is_empty() && token.len() <= 4096
++}
++
+ pub fn login(user: &str) {
+";
+
+/// Answers with scripted responses in turn, the way a test double would
+struct CannedProvider {
+    responses: Vec<&'static str>,
+    next: AtomicUsize,
+}
+
+#[async_trait]
+impl AIProvider for CannedProvider {
+    async fn generate_message(&self, _prompt: &str) -> Result<String> {
+        let index = self.next.fetch_add(1, Ordering::SeqCst);
+        Ok(self.responses[index % self.responses.len()].to_string())
+    }
+
+    fn provider_name(&self) -> &'static str {
+        "Canned"
+    }
+}
+
+/// Wraps another provider, counting requests and the size of the prompts sent
+struct CountingProvider<P> {
+    inner: P,
+    requests: AtomicUsize,
+    prompt_bytes: AtomicUsize,
+}
+
+#[async_trait]
+impl<P: AIProvider> AIProvider for CountingProvider<P> {
+    async fn generate_message(&self, prompt: &str) -> Result<String> {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+        self.prompt_bytes.fetch_add(prompt.len(), Ordering::SeqCst);
+        self.inner.generate_message(prompt).await
+    }
+
+    fn provider_name(&self) -> &'static str {
+        self.inner.provider_name()
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    println!("🧩 Committor with a custom provider");
+    println!("===================================");
+
+    let provider = Arc::new(CountingProvider {
+        inner: CannedProvider {
+            responses: vec![
+                "feat(auth): add token validation",
+                // Not a conventional commit, so the rules reject it and another is requested
+                "Added a token check",
+                "fix(auth): reject oversized tokens",
+                "feat(auth): validate tokens before login",
+            ],
+            next: AtomicUsize::new(0),
+        },
+        requests: AtomicUsize::new(0),
+        prompt_bytes: AtomicUsize::new(0),
+    });
+
+    // The configured provider stands in for the custom one in the privacy and policy
+    // checks; a local Ollama URL tells them nothing leaves the machine
+    let config = Config::with_ollama(
+        "http://localhost:11434".to_string(),
+        "canned".to_string(),
+        3,
+        false,
+        false,
+    )
+    .with_settings(Settings::discover().unwrap_or_default());
+    let committor = Committor::with_provider(config, provider.clone())?;
+
+    let staged = diff::has_staged_changes().unwrap_or(false);
+    let diff = if staged {
+        committor.get_staged_diff()?
+    } else {
+        println!("📝 Nothing staged, using a sample diff");
+        SAMPLE_DIFF.to_string()
+    };
+
+    let messages = committor.generate_commit_messages(&diff).await?;
+    println!(
+        "✅ Generated {} commit messages, best first:",
+        messages.len()
+    );
+    for (i, message) in messages.iter().enumerate() {
+        println!("   {}. {}", i + 1, message);
+    }
+    println!(
+        "📊 {} requests, {} prompt bytes",
+        provider.requests.load(Ordering::SeqCst),
+        provider.prompt_bytes.load(Ordering::SeqCst)
+    );
+
+    Ok(())
+}
@@ -0,0 +1,71 @@
+//! A prepare-commit-msg hook built on the library
+//!
+//! `committor install-hook` covers the usual case; this is the same flow for tools
+//! that embed their own hook. Git passes the message file and the commit source:
+//!
+//! ```sh
+//! cargo run --example hook_mode -- .git/COMMIT_EDITMSG
+//! ```
+//!
+//! A hook that exits non-zero aborts the commit, so every failure is reported on
+//! stderr and the hook still exits successfully, leaving the message as it was.
+
+use anyhow::{Context, Result};
+use committor::hooks;
+use committor::settings::Settings;
+use committor::trailers::MessageCleanup;
+use committor::{diff, Committor, Config};
+use std::env;
+use std::fs;
+use std::time::Duration;
+
+#[tokio::main]
+async fn main() {
+    let mut args = env::args().skip(1);
+    let Some(file) = args.next() else {
+        eprintln!("Usage: hook_mode <message-file> [source]");
+        return;
+    };
+    let source = args.next();
+
+    if let Err(e) = add_suggestions(&file, source.as_deref()).await {
+        eprintln!("committor: no suggestions: {e:#}");
+    }
+}
+
+/// Add generated suggestions to the message file, or leave it alone when there are none
+async fn add_suggestions(file: &str, source: Option<&str>) -> Result<()> {
+    let settings = Settings::discover()?;
+    // Git already wrote the message for merges, squashes, `-m` and the like
+    if !settings.hooks.suggests_for(source) || !diff::has_staged_changes()? {
+        return Ok(());
+    }
+
+    let content = fs::read_to_string(file).with_context(|| format!("Failed to read {file}"))?;
+
+    let budget = Duration::from_secs(settings.hooks.timeout);
+    let config = config_from_env()?.with_settings(settings);
+    let committor = Committor::new(config)?;
+    let diff = committor.get_staged_diff()?;
+    let suggestions = tokio::time::timeout(budget, committor.generate_commit_messages(&diff))
+        .await
+        .with_context(|| format!("Nothing generated within {}s", budget.as_secs()))??;
+
+    let cleanup = MessageCleanup::from_git(&content);
+    let merged = hooks::merge_suggestions(&content, &suggestions, &cleanup);
+    fs::write(file, merged).with_context(|| format!("Failed to write {file}"))
+}
+
+/// Use OpenAI when an API key is set, otherwise a local Ollama
+fn config_from_env() -> Result<Config> {
+    if env::var("OPENAI_API_KEY").is_ok() {
+        return Config::new();
+    }
+    Ok(Config::with_ollama(
+        env::var("OLLAMA_BASE_URL").unwrap_or_else(|_| "http://localhost:11434".to_string()),
+        env::var("OLLAMA_MODEL").unwrap_or_else(|_| "llama2:7b".to_string()),
+        3,
+        false,
+        false,
+    ))
+}
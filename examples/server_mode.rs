@@ -0,0 +1,64 @@
+//! A pre-receive hook that checks every pushed commit message on the server
+//!
+//! `committor hook pre-receive` covers the usual case; this is the same check for
+//! servers that run their own hook. Git passes the ref updates on stdin, and a
+//! non-zero exit rejects the push. The rules come from the repository's settings,
+//! and `[hooks] pre_receive = "warn"` reports violations without rejecting:
+//!
+//! ```sh
+//! printf '%s %s refs/heads/main\n' "$(git rev-parse HEAD~3)" "$(git rev-parse HEAD)" |
+//!     cargo run --example server_mode
+//! ```
+
+use anyhow::{bail, Context, Result};
+use committor::hooks::{self, EnforcementMode};
+use committor::settings::Settings;
+use std::io::Read;
+
+fn main() -> Result<()> {
+    let settings = Settings::discover()?;
+
+    let mut input = String::new();
+    std::io::stdin()
+        .read_to_string(&mut input)
+        .context("Failed to read ref updates from stdin")?;
+    let updates = hooks::parse_pre_receive_input(&input)?;
+
+    // Receive hooks see pushed objects through GIT_DIR and quarantine directories
+    let repo = git2::Repository::open_from_env().context("Not in a git repository")?;
+    let results = hooks::lint_ref_updates(
+        &repo,
+        &updates,
+        &settings.rules,
+        &settings.trailers.required,
+    )?;
+
+    let failures: Vec<_> = results
+        .iter()
+        .filter(|result| !result.violations.is_empty())
+        .collect();
+    for failure in &failures {
+        eprintln!(
+            "{} on {}: {}",
+            &failure.commit_id[..failure.commit_id.len().min(10)],
+            failure.refname,
+            failure.subject
+        );
+        for violation in &failure.violations {
+            eprintln!("    - {violation}");
+        }
+    }
+
+    println!(
+        "{} of {} pushed commit(s) follow the commit message rules",
+        results.len() - failures.len(),
+        results.len()
+    );
+    if !failures.is_empty() && settings.hooks.pre_receive == EnforcementMode::Enforce {
+        bail!(
+            "Push rejected: {} commit(s) break the rules",
+            failures.len()
+        );
+    }
+    Ok(())
+}
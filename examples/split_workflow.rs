@@ -0,0 +1,59 @@
+//! Splitting the staged changes into several commits
+//!
+//! The provider groups the staged hunks into a plan of commits, which is printed
+//! as a tree. Pass `--apply` to create the commits; HEAD and the index are rolled
+//! back if any of them fails:
+//!
+//! ```sh
+//! cargo run --example split_workflow -- --apply
+//! ```
+
+use anyhow::Result;
+use committor::settings::Settings;
+use committor::{diff, split, Committor, Config};
+use std::env;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    println!("✂️  Committor split workflow");
+    println!("===========================");
+
+    if !diff::has_staged_changes()? {
+        println!("⚠️  No staged changes found");
+        println!("   Stage some changes first: git add <files>");
+        return Ok(());
+    }
+
+    let config = config_from_env()?.with_settings(Settings::discover()?);
+    let committor = Committor::new(config)?;
+
+    let hunks = committor.get_staged_hunks()?;
+    println!("📝 Planning commits for {} staged hunks...", hunks.len());
+    let plan = committor.plan_split(&hunks).await?;
+    println!("{}", split::render_plan_tree(&plan, &hunks));
+
+    if !env::args().any(|arg| arg == "--apply") {
+        println!("💡 Run with --apply to create these {} commits", plan.len());
+        return Ok(());
+    }
+
+    // The original HEAD is backed up first; `committor restore` undoes the split
+    committor.execute_split(&plan, &hunks)?;
+    println!("✅ Created {} commits", plan.len());
+
+    Ok(())
+}
+
+/// Use OpenAI when an API key is set, otherwise a local Ollama
+fn config_from_env() -> Result<Config> {
+    if env::var("OPENAI_API_KEY").is_ok() {
+        return Config::new();
+    }
+    Ok(Config::with_ollama(
+        env::var("OLLAMA_BASE_URL").unwrap_or_else(|_| "http://localhost:11434".to_string()),
+        env::var("OLLAMA_MODEL").unwrap_or_else(|_| "llama2:7b".to_string()),
+        3,
+        false,
+        false,
+    ))
+}
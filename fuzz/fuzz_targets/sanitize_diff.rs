@@ -0,0 +1,40 @@
+//! Arbitrary bytes through diff sanitization and the diff parsing paths
+//!
+//! Diffs hold whatever the repository does: any encoding, lines of any length,
+//! multi-byte characters wherever a length limit falls. None of this may panic.
+
+#![no_main]
+
+use committor::anonymize::Anonymizer;
+use committor::diff::{diff_paths, replace_file_patches, sanitize_diff};
+use committor::prompt::{sanitize_diff_for_prompt, PromptBudget, PromptBuilder};
+use committor::vendored::{detect_vendored, vendored_patch_replacements};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // The first two bytes pick a small budget, so line limits land mid-character often
+    let (budget, diff) = match data {
+        [lines, length, rest @ ..] => (
+            PromptBudget {
+                max_lines: usize::from(*lines),
+                max_line_length: usize::from(*length),
+            },
+            rest,
+        ),
+        _ => (PromptBudget::default(), data),
+    };
+    let diff = String::from_utf8_lossy(diff);
+
+    sanitize_diff(&diff);
+    sanitize_diff_for_prompt(&diff);
+    PromptBuilder::new().budget(budget).commit_prompt(&diff);
+
+    let paths = diff_paths(&diff);
+    assert!(paths.len() <= diff.matches("diff --git ").count());
+    let vendored = detect_vendored(&diff);
+    replace_file_patches(&diff, &vendored_patch_replacements(&vendored));
+
+    let mut anonymizer = Anonymizer::new();
+    let anonymized = anonymizer.anonymize_diff(&diff);
+    anonymizer.restore(&anonymized);
+});
@@ -0,0 +1,98 @@
+//! Running the model on another machine, for air-gapped setups
+//!
+//! `export-prompt` writes the commit prompt to a file to carry across to the network
+//! segment where the model runs, and `import-response` reads its replies back. What
+//! is needed in between, the diff the prompt describes and the values the privacy
+//! filter replaced with placeholders, is kept in the git directory and never exported.
+
+use crate::batch::diff_hash;
+use crate::paths::git_state_path;
+use crate::privacy::PrivacyFilter;
+use crate::prompt::PROMPT_VERSION;
+use crate::state::write_atomic;
+use anyhow::{Context, Result};
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// Name of the file recording the last exported prompt, kept in the git directory
+pub const EXPORT_FILE_NAME: &str = "committor-exported-prompt.json";
+
+/// What reading back the replies to an exported prompt needs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedPrompt {
+    pub diff_hash: String,
+    pub prompt_version: u32,
+    /// Undoes the placeholders in the prompt on the replies
+    pub filter: PrivacyFilter,
+}
+
+/// Get the path of the exported prompt record for a repository
+pub fn export_path_from_repo(repo: &Repository) -> PathBuf {
+    git_state_path(repo, EXPORT_FILE_NAME)
+}
+
+/// Record that a prompt was exported for a diff, replacing any earlier export
+pub fn save_export_from_repo(repo: &Repository, diff: &str, filter: &PrivacyFilter) -> Result<()> {
+    let exported = ExportedPrompt {
+        diff_hash: diff_hash(diff)?,
+        prompt_version: PROMPT_VERSION,
+        filter: filter.clone(),
+    };
+    let path = export_path_from_repo(repo);
+    write_atomic(&path, serde_json::to_string_pretty(&exported)?)
+        .with_context(|| format!("Failed to save the exported prompt to {}", path.display()))
+}
+
+/// Get the filter of the prompt exported for exactly this diff by the current prompts
+pub fn load_export_from_repo(repo: &Repository, diff: &str) -> Result<PrivacyFilter> {
+    let path = export_path_from_repo(repo);
+    let Ok(content) = fs::read_to_string(&path) else {
+        return Err(anyhow::anyhow!(
+            "No prompt was exported. Run `committor export-prompt` first."
+        ));
+    };
+
+    let exported: ExportedPrompt = serde_json::from_str(&content)
+        .with_context(|| format!("Invalid exported prompt in {}", path.display()))?;
+    if exported.diff_hash != diff_hash(diff)? {
+        return Err(anyhow::anyhow!(
+            "The staged changes are not the ones the prompt was exported for. Run `committor export-prompt` again."
+        ));
+    }
+    if exported.prompt_version != PROMPT_VERSION {
+        return Err(anyhow::anyhow!(
+            "The prompt was exported by another version of committor. Run `committor export-prompt` again."
+        ));
+    }
+    Ok(exported.filter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::privacy::PrivacyLevel;
+
+    #[test]
+    fn test_export_round_trip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let repo = Repository::init(dir.path())?;
+        let diff = "+connect(\"vault.prod.internal\");\n";
+        assert!(load_export_from_repo(&repo, diff).is_err());
+
+        let mut filter = PrivacyFilter::new(PrivacyLevel::Redacted);
+        let outgoing = filter.diff(diff).unwrap();
+        assert!(outgoing.contains("<STRING_1>"));
+        save_export_from_repo(&repo, diff, &filter)?;
+
+        let loaded = load_export_from_repo(&repo, diff)?;
+        assert_eq!(
+            loaded.restore("fix: connect to <STRING_1>"),
+            "fix: connect to vault.prod.internal"
+        );
+        let changed = load_export_from_repo(&repo, "+connect();\n").unwrap_err();
+        assert!(changed.to_string().contains("export-prompt"));
+        Ok(())
+    }
+}
@@ -0,0 +1,203 @@
+//! Anonymization of diff content before it is sent to a provider
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// The values that are replaced with placeholders
+struct Patterns {
+    double_quoted: Regex,
+    single_quoted: Regex,
+    email: Regex,
+    ip: Regex,
+    url_host: Regex,
+    hostname: Regex,
+}
+
+/// The placeholder patterns, compiled once
+fn patterns() -> &'static Patterns {
+    static PATTERNS: OnceLock<Patterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| Patterns {
+        double_quoted: Regex::new(r#""(?:[^"\\\n]|\\.)+""#).unwrap(),
+        single_quoted: Regex::new(r#"(^|[\s(=,:\[{])'((?:[^'\\\n]|\\.){2,})'"#).unwrap(),
+        email: Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}").unwrap(),
+        ip: Regex::new(r"\b(?:\d{1,3}\.){3}\d{1,3}\b").unwrap(),
+        url_host: Regex::new(r"\b((?:https?|wss?|ftp|ssh)://)([A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*)")
+            .unwrap(),
+        hostname: Regex::new(
+            r"\b(?:[A-Za-z0-9-]+\.){2,}(?:com|net|org|io|dev|cloud|internal|local|corp|lan)\b",
+        )
+        .unwrap(),
+    })
+}
+
+/// Replaces literals, emails, hostnames and IPs with placeholders, remembering the originals
+///
+/// The same value always gets the same placeholder, so the model can still see that
+/// two lines refer to the same thing. File paths and hunk headers are left alone.
+/// Only the originals are serialized: enough to restore a reply read back later.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Anonymizer {
+    #[serde(skip)]
+    placeholders: HashMap<String, String>,
+    originals: Vec<(String, String)>,
+    #[serde(skip)]
+    counts: HashMap<&'static str, usize>,
+}
+
+impl Anonymizer {
+    /// Create an anonymizer with an empty mapping
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Anonymize the added, removed and context lines of a diff
+    pub fn anonymize_diff(&mut self, diff: &str) -> String {
+        let mut output = String::with_capacity(diff.len());
+        for line in diff.lines() {
+            let is_content = (line.starts_with('+') && !line.starts_with("+++"))
+                || (line.starts_with('-') && !line.starts_with("---"))
+                || line.starts_with(' ');
+
+            if is_content {
+                let (marker, content) = line.split_at(1);
+                output.push_str(marker);
+                output.push_str(&self.anonymize_text(content));
+            } else {
+                output.push_str(line);
+            }
+            output.push('\n');
+        }
+        output
+    }
+
+    /// Anonymize a piece of text
+    pub fn anonymize_text(&mut self, text: &str) -> String {
+        let patterns = patterns();
+        let text = patterns
+            .double_quoted
+            .replace_all(text, |captures: &regex::Captures| {
+                format!("\"{}\"", self.placeholder("STRING", &captures[0]))
+            })
+            .into_owned();
+        let text = patterns
+            .single_quoted
+            .replace_all(&text, |captures: &regex::Captures| {
+                let literal = format!("'{}'", &captures[2]);
+                format!("{}'{}'", &captures[1], self.placeholder("STRING", &literal))
+            })
+            .into_owned();
+        let text = patterns
+            .email
+            .replace_all(&text, |captures: &regex::Captures| {
+                self.placeholder("EMAIL", &captures[0])
+            })
+            .into_owned();
+        let text = patterns
+            .url_host
+            .replace_all(&text, |captures: &regex::Captures| {
+                format!("{}{}", &captures[1], self.placeholder("HOST", &captures[2]))
+            })
+            .into_owned();
+        let text = patterns
+            .hostname
+            .replace_all(&text, |captures: &regex::Captures| {
+                self.placeholder("HOST", &captures[0])
+            })
+            .into_owned();
+        patterns
+            .ip
+            .replace_all(&text, |captures: &regex::Captures| {
+                self.placeholder("IP", &captures[0])
+            })
+            .into_owned()
+    }
+
+    /// Put the original values back in place of any placeholders in generated text
+    pub fn restore(&self, text: &str) -> String {
+        let mut restored = text.to_string();
+        // Longest placeholders first so `<STRING_10>` isn't clobbered by `<STRING_1>`
+        let mut originals: Vec<&(String, String)> = self.originals.iter().collect();
+        originals.sort_by_key(|(placeholder, _)| std::cmp::Reverse(placeholder.len()));
+
+        for (placeholder, original) in originals {
+            // String placeholders were quoted in the diff, so restore the bare value
+            let original = original
+                .strip_prefix(['"', '\''])
+                .and_then(|o| o.strip_suffix(['"', '\'']))
+                .unwrap_or(original);
+            restored = restored.replace(placeholder.as_str(), original);
+        }
+        restored
+    }
+
+    /// Check whether anything has been anonymized
+    pub fn is_empty(&self) -> bool {
+        self.originals.is_empty()
+    }
+
+    /// Get the placeholder for a value, allocating the next one of its kind if it's new
+    fn placeholder(&mut self, kind: &'static str, value: &str) -> String {
+        if let Some(placeholder) = self.placeholders.get(value) {
+            return placeholder.clone();
+        }
+
+        let count = self.counts.entry(kind).or_insert(0);
+        *count += 1;
+        let placeholder = format!("<{kind}_{count}>");
+        self.placeholders
+            .insert(value.to_string(), placeholder.clone());
+        self.originals
+            .push((placeholder.clone(), value.to_string()));
+        placeholder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_anonymize_diff() {
+        let diff = [
+            "diff --git a/src/mail.rs b/src/mail.rs",
+            "--- a/src/mail.rs",
+            "+++ b/src/mail.rs",
+            "@@ -1,2 +1,3 @@",
+            "-const HOST: &str = \"smtp.corp.example.com\";",
+            "+const HOST: &str = \"smtp2.corp.example.com\";",
+            "+// contact ops@example.com or https://status.internal.acme.io at 10.0.0.12",
+            " fn send<'a>(to: &'a str) {}",
+        ]
+        .join("\n");
+
+        let mut anonymizer = Anonymizer::new();
+        let anonymized = anonymizer.anonymize_diff(&diff);
+
+        assert!(anonymized.contains("diff --git a/src/mail.rs b/src/mail.rs"));
+        assert!(anonymized.contains("-const HOST: &str = \"<STRING_1>\";"));
+        assert!(anonymized.contains("+const HOST: &str = \"<STRING_2>\";"));
+        assert!(anonymized.contains("+// contact <EMAIL_1> or https://<HOST_1> at <IP_1>"));
+        assert!(anonymized.contains(" fn send<'a>(to: &'a str) {}"));
+        assert!(!anonymized.contains("example.com"));
+        assert!(!anonymized.contains("10.0.0.12"));
+    }
+
+    #[test]
+    fn test_same_value_same_placeholder() {
+        let mut anonymizer = Anonymizer::new();
+        let text = anonymizer.anonymize_text("send('alerts', 'alerts', 'ops')");
+        assert_eq!(text, "send('<STRING_1>', '<STRING_1>', '<STRING_2>')");
+    }
+
+    #[test]
+    fn test_restore() {
+        let mut anonymizer = Anonymizer::new();
+        anonymizer.anonymize_text("connect(\"db.prod.internal\") to 192.168.1.4");
+        assert_eq!(
+            anonymizer.restore("fix(db): use <STRING_1> instead of <IP_1>"),
+            "fix(db): use db.prod.internal instead of 192.168.1.4"
+        );
+    }
+}
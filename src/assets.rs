@@ -0,0 +1,380 @@
+//! Metadata for staged binary files, whose contents can't be diffed
+//!
+//! A binary patch is only `Binary files differ`, so the model has nothing but
+//! the path to go on. Kinds, counts, sizes and image dimensions let it write
+//! `feat(assets): add 3 onboarding illustrations` instead of `add files`.
+
+use crate::diff::{get_staged_changes_from_repo, LARGE_FILE_BYTES};
+use crate::types::DiffChangeType;
+use anyhow::{Context, Result};
+use git2::Repository;
+use std::fmt;
+use std::path::Path;
+
+/// Most files named individually in a group of similar binary files
+const MAX_NAMED_FILES: usize = 5;
+
+/// A binary file among the staged changes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BinaryAsset {
+    pub path: String,
+    pub change_type: DiffChangeType,
+    /// What the file holds, e.g. `PNG image` or `font`
+    pub kind: &'static str,
+    /// Size in bytes, of the staged file or of the deleted one
+    pub size: usize,
+    /// Width and height of images in pixels
+    pub dimensions: Option<(u32, u32)>,
+}
+
+impl BinaryAsset {
+    /// Describe a file from its path and contents
+    pub fn new(path: &str, change_type: DiffChangeType, content: &[u8]) -> Self {
+        Self {
+            path: path.to_string(),
+            change_type,
+            kind: file_kind(path, content),
+            size: content.len(),
+            dimensions: image_dimensions(content),
+        }
+    }
+
+    /// Describe a file too large to read from its path and size alone
+    pub fn large(path: &str, change_type: DiffChangeType, size: usize) -> Self {
+        Self {
+            path: path.to_string(),
+            change_type,
+            kind: file_kind(path, &[]),
+            size,
+            dimensions: None,
+        }
+    }
+
+    fn file_name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+
+    fn directory(&self) -> &str {
+        self.path.rsplit_once('/').map_or(".", |(dir, _)| dir)
+    }
+}
+
+impl fmt::Display for BinaryAsset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}", self.file_name(), format_size(self.size))?;
+        if let Some((width, height)) = self.dimensions {
+            write!(f, ", {width}x{height}")?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Get the binary files staged in the repository in the current directory
+pub fn binary_assets() -> Result<Vec<BinaryAsset>> {
+    let repo = Repository::open(".").context("Not in a git repository")?;
+    binary_assets_from_repo(&repo)
+}
+
+/// Get the binary files staged in a specific repository
+///
+/// Deleted files are described from HEAD; files whose contents aren't available locally are left out.
+/// Files larger than [`LARGE_FILE_BYTES`] aren't diffed either, so they are included from
+/// their size without being read.
+pub fn binary_assets_from_repo(repo: &Repository) -> Result<Vec<BinaryAsset>> {
+    let index = repo.index()?;
+    let odb = repo.odb()?;
+    let head_tree = repo.head().ok().and_then(|head| head.peel_to_tree().ok());
+
+    let mut assets = Vec::new();
+    for change in get_staged_changes_from_repo(repo)? {
+        let path = Path::new(&change.file_path);
+        let id = match change.change_type {
+            DiffChangeType::Deleted => head_tree
+                .as_ref()
+                .and_then(|tree| tree.get_path(path).ok())
+                .map(|entry| entry.id()),
+            _ => index.get_path(path, 0).map(|entry| entry.id),
+        };
+        let Some(id) = id else {
+            continue;
+        };
+        if let Ok((size, _)) = odb.read_header(id) {
+            if size as u64 > LARGE_FILE_BYTES {
+                assets.push(BinaryAsset::large(
+                    &change.file_path,
+                    change.change_type,
+                    size,
+                ));
+                continue;
+            }
+        }
+        let Ok(blob) = repo.find_blob(id) else {
+            continue;
+        };
+        if blob.is_binary() {
+            assets.push(BinaryAsset::new(
+                &change.file_path,
+                change.change_type,
+                blob.content(),
+            ));
+        }
+    }
+    Ok(assets)
+}
+
+/// Group binary files by change, kind and directory, one line per group
+///
+/// For example `added 3 PNG images in assets/onboarding: step1.png (48.0 KiB, 1200x800), ...`.
+pub fn summarize_assets(assets: &[BinaryAsset]) -> Vec<String> {
+    let mut groups: Vec<Vec<&BinaryAsset>> = Vec::new();
+    for asset in assets {
+        let group = groups.iter_mut().find(|group| {
+            let first = group[0];
+            first.change_type == asset.change_type
+                && first.kind == asset.kind
+                && first.directory() == asset.directory()
+        });
+        match group {
+            Some(group) => group.push(asset),
+            None => groups.push(vec![asset]),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|group| {
+            let first = group[0];
+            let count = group.len();
+            let kind = if count == 1 {
+                format!("a {}", first.kind)
+            } else {
+                format!("{count} {}s", first.kind)
+            };
+            let mut files: Vec<String> = group
+                .iter()
+                .take(MAX_NAMED_FILES)
+                .map(ToString::to_string)
+                .collect();
+            if count > MAX_NAMED_FILES {
+                files.push(format!("and {} more", count - MAX_NAMED_FILES));
+            }
+            let mut line = format!(
+                "{} {kind} in {}: {}",
+                first.change_type,
+                first.directory(),
+                files.join(", ")
+            );
+            if count > 1 {
+                let total: usize = group.iter().map(|asset| asset.size).sum();
+                line.push_str(&format!("; {} in total", format_size(total)));
+            }
+            line
+        })
+        .collect()
+}
+
+/// Name what a binary file holds from its signature, or failing that its extension
+fn file_kind(path: &str, content: &[u8]) -> &'static str {
+    let signatures: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "PNG image"),
+        (b"\xff\xd8\xff", "JPEG image"),
+        (b"GIF8", "GIF image"),
+        (b"BM", "BMP image"),
+        (b"%PDF", "PDF document"),
+        (b"wOFF", "font"),
+        (b"wOF2", "font"),
+        (b"PK\x03\x04", "archive"),
+    ];
+    if let Some((_, kind)) = signatures
+        .iter()
+        .find(|(signature, _)| content.starts_with(signature))
+    {
+        return kind;
+    }
+    if content.starts_with(b"RIFF") && content.get(8..12) == Some(b"WEBP") {
+        return "WebP image";
+    }
+
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "ttf" | "otf" | "woff" | "woff2" | "eot" => "font",
+        "ico" | "icns" => "icon",
+        "mp3" | "wav" | "ogg" | "flac" | "m4a" => "audio file",
+        "mp4" | "webm" | "mov" | "avi" | "mkv" => "video",
+        "zip" | "gz" | "tgz" | "jar" | "7z" => "archive",
+        _ => "binary file",
+    }
+}
+
+/// Read the width and height from a PNG, GIF, JPEG, WebP or BMP header
+pub fn image_dimensions(content: &[u8]) -> Option<(u32, u32)> {
+    let be16 = |at: usize| {
+        Some(u16::from_be_bytes(
+            content.get(at..at + 2)?.try_into().ok()?,
+        ))
+    };
+    let le16 = |at: usize| {
+        Some(u16::from_le_bytes(
+            content.get(at..at + 2)?.try_into().ok()?,
+        ))
+    };
+    let be32 = |at: usize| {
+        Some(u32::from_be_bytes(
+            content.get(at..at + 4)?.try_into().ok()?,
+        ))
+    };
+    let le24 = |at: usize| {
+        let bytes = content.get(at..at + 3)?;
+        Some(u32::from(bytes[0]) | u32::from(bytes[1]) << 8 | u32::from(bytes[2]) << 16)
+    };
+
+    if content.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Some((be32(16)?, be32(20)?));
+    }
+    if content.starts_with(b"GIF8") {
+        return Some((u32::from(le16(6)?), u32::from(le16(8)?)));
+    }
+    if content.starts_with(b"BM") {
+        let width = i32::from_le_bytes(content.get(18..22)?.try_into().ok()?);
+        let height = i32::from_le_bytes(content.get(22..26)?.try_into().ok()?);
+        return Some((width.unsigned_abs(), height.unsigned_abs()));
+    }
+    if content.starts_with(b"RIFF") && content.get(8..12) == Some(b"WEBP") {
+        return match content.get(12..16)? {
+            b"VP8 " => Some((u32::from(le16(26)? & 0x3fff), u32::from(le16(28)? & 0x3fff))),
+            b"VP8L" => {
+                let bits = u32::from_le_bytes(content.get(21..25)?.try_into().ok()?);
+                Some((1 + (bits & 0x3fff), 1 + ((bits >> 14) & 0x3fff)))
+            }
+            b"VP8X" => Some((1 + le24(24)?, 1 + le24(27)?)),
+            _ => None,
+        };
+    }
+    if content.starts_with(b"\xff\xd8") {
+        // Walk the segments to the start-of-frame marker, which holds the size
+        let mut at = 2;
+        while at + 9 < content.len() {
+            if content[at] != 0xff {
+                return None;
+            }
+            let marker = content[at + 1];
+            if marker == 0xff {
+                at += 1;
+                continue;
+            }
+            if matches!(marker, 0xc0..=0xcf) && !matches!(marker, 0xc4 | 0xc8 | 0xcc) {
+                return Some((u32::from(be16(at + 7)?), u32::from(be16(at + 5)?)));
+            }
+            at += 2 + usize::from(be16(at + 2)?);
+        }
+    }
+    None
+}
+
+/// Format a byte count the way file managers do, e.g. `48.0 KiB`
+pub fn format_size(bytes: usize) -> String {
+    const UNITS: &[&str] = &["KiB", "MiB", "GiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut content = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        content.extend(width.to_be_bytes());
+        content.extend(height.to_be_bytes());
+        content.extend([8, 6, 0, 0, 0]);
+        content.resize(2048, 0);
+        content
+    }
+
+    #[test]
+    fn test_image_dimensions() {
+        assert_eq!(image_dimensions(&png(1200, 800)), Some((1200, 800)));
+        assert_eq!(
+            image_dimensions(b"GIF89a\x40\x01\xf0\x00\x00\x00"),
+            Some((320, 240))
+        );
+
+        let jpeg = [
+            0xff, 0xd8, // start of image
+            0xff, 0xe0, 0x00, 0x04, 0x00, 0x00, // APP0 segment
+            0xff, 0xc0, 0x00, 0x11, 0x08, 0x02, 0x58, 0x03, 0x20, 0x03, // start of frame
+        ];
+        assert_eq!(image_dimensions(&jpeg), Some((800, 600)));
+        assert_eq!(image_dimensions(b"\0\x01\x02"), None);
+    }
+
+    #[test]
+    fn test_summarize_assets() {
+        let assets: Vec<BinaryAsset> = ["step1.png", "step2.png", "step3.png"]
+            .iter()
+            .map(|name| {
+                BinaryAsset::new(
+                    &format!("assets/onboarding/{name}"),
+                    DiffChangeType::Added,
+                    &png(1200, 800),
+                )
+            })
+            .chain([BinaryAsset::new(
+                "fonts/Inter.woff2",
+                DiffChangeType::Modified,
+                b"wOF2\0\0",
+            )])
+            .collect();
+
+        assert_eq!(
+            summarize_assets(&assets),
+            vec![
+                "added 3 PNG images in assets/onboarding: \
+                 step1.png (2.0 KiB, 1200x800), step2.png (2.0 KiB, 1200x800), \
+                 step3.png (2.0 KiB, 1200x800); 6.0 KiB in total",
+                "modified a font in fonts: Inter.woff2 (6 B)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_binary_assets_from_repo() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let repo = Repository::init(dir.path())?;
+        std::fs::write(dir.path().join("README.md"), "# Logo\n")?;
+        let mut index = repo.index()?;
+        index.add_path(Path::new("README.md"))?;
+        index.write()?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+        let signature = git2::Signature::now("Test User", "test@example.com")?;
+        repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])?;
+
+        std::fs::write(
+            dir.path().join("README.md"),
+            "# Logo\n\n![logo](logo.png)\n",
+        )?;
+        std::fs::write(dir.path().join("logo.png"), png(64, 64))?;
+        index.add_path(Path::new("README.md"))?;
+        index.add_path(Path::new("logo.png"))?;
+        index.write()?;
+
+        let assets = binary_assets_from_repo(&repo)?;
+        assert_eq!(assets.len(), 1);
+        assert_eq!(assets[0].kind, "PNG image");
+        assert_eq!(assets[0].dimensions, Some((64, 64)));
+        Ok(())
+    }
+}
@@ -0,0 +1,253 @@
+//! Audit log of the requests sent to providers that run off this machine
+
+use crate::paths::git_state_path;
+use crate::privacy::PrivacyLevel;
+use crate::prompt::{PROMPT_VERSION, REMOVED_LINE_NOTE};
+use crate::providers::AIProvider;
+use crate::report::DateTime;
+use crate::state::append_line;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name of the audit log file, kept in the git directory
+pub const AUDIT_FILE_NAME: &str = "committor-audit.jsonl";
+
+/// Audit behaviour read from the `[audit]` settings section
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditSettings {
+    /// Record every request sent to a provider that isn't on this machine
+    pub enabled: bool,
+    /// Where to write the log instead of the git directory
+    pub path: Option<PathBuf>,
+}
+
+/// What was sent to a provider in one request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// When the request was sent, as an RFC 3339 UTC timestamp
+    pub timestamp: String,
+    pub provider: String,
+    pub model: String,
+    /// `completion` or `embedding`
+    pub operation: String,
+    /// Privacy level the request was built under
+    pub privacy: PrivacyLevel,
+    /// Version of the prompt templates, for completions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_version: Option<u32>,
+    /// Values replaced with placeholders plus lines removed as sensitive
+    pub redactions: usize,
+    /// Size of the text sent, in bytes
+    pub bytes: usize,
+}
+
+impl AuditRecord {
+    /// Describe a request that is about to be sent
+    pub fn new(
+        provider: &str,
+        model: &str,
+        operation: &str,
+        privacy: PrivacyLevel,
+        text: &str,
+    ) -> Self {
+        Self {
+            timestamp: format_timestamp(now()),
+            provider: provider.to_string(),
+            model: model.to_string(),
+            operation: operation.to_string(),
+            privacy,
+            prompt_version: (operation == "completion").then_some(PROMPT_VERSION),
+            redactions: count_redactions(text),
+            bytes: text.len(),
+        }
+    }
+}
+
+/// Append a record to the audit log, creating it if needed
+pub fn append_record(path: &Path, record: &AuditRecord) -> Result<()> {
+    append_line(path, &serde_json::to_string(record)?)
+        .with_context(|| format!("Failed to write audit log {}", path.display()))
+}
+
+/// Read every record in the audit log, oldest first
+pub fn read_records(path: &Path) -> Result<Vec<AuditRecord>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read audit log {}", path.display()))?;
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).context("Invalid audit log record"))
+        .collect()
+}
+
+/// Get the audit log path for the current repository
+pub fn audit_path(settings: &AuditSettings) -> Result<PathBuf> {
+    let repo = Repository::discover(".")?;
+    Ok(audit_path_from_repo(&repo, settings))
+}
+
+/// Get the audit log path for a repository, honouring a configured path
+pub fn audit_path_from_repo(repo: &Repository, settings: &AuditSettings) -> PathBuf {
+    settings
+        .path
+        .clone()
+        .unwrap_or_else(|| git_state_path(repo, AUDIT_FILE_NAME))
+}
+
+/// Count the distinct placeholders and removed sensitive lines in outgoing text
+pub fn count_redactions(text: &str) -> usize {
+    let placeholder_regex = regex::Regex::new(r"<(?:STRING|EMAIL|HOST|IP)_\d+>").unwrap();
+    let placeholders: HashSet<&str> = placeholder_regex
+        .find_iter(text)
+        .map(|m| m.as_str())
+        .collect();
+
+    placeholders.len() + text.matches(REMOVED_LINE_NOTE).count()
+}
+
+/// Provider wrapper that records each request in the audit log before sending it
+pub struct AuditingProvider {
+    inner: Box<dyn AIProvider>,
+    model: String,
+    privacy: PrivacyLevel,
+    path: PathBuf,
+}
+
+impl AuditingProvider {
+    /// Wrap a provider, logging its requests to `path`
+    pub fn new(
+        inner: Box<dyn AIProvider>,
+        model: String,
+        privacy: PrivacyLevel,
+        path: PathBuf,
+    ) -> Self {
+        Self {
+            inner,
+            model,
+            privacy,
+            path,
+        }
+    }
+
+    /// Record a request, refusing to send it if the record can't be written
+    fn record(&self, model: &str, operation: &str, text: &str) -> Result<()> {
+        let record = AuditRecord::new(
+            self.inner.provider_name(),
+            model,
+            operation,
+            self.privacy,
+            text,
+        );
+        append_record(&self.path, &record)
+    }
+}
+
+#[async_trait]
+impl AIProvider for AuditingProvider {
+    async fn generate_message(&self, prompt: &str) -> Result<String> {
+        self.record(&self.model, "completion", prompt)?;
+        self.inner.generate_message(prompt).await
+    }
+
+    fn provider_name(&self) -> &'static str {
+        self.inner.provider_name()
+    }
+
+    async fn embed(&self, model: &str, text: &str) -> Result<Vec<f64>> {
+        self.record(model, "embedding", text)?;
+        self.inner.embed(model, text).await
+    }
+}
+
+/// Get the current time in seconds since the Unix epoch
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Format seconds since the Unix epoch as an RFC 3339 UTC timestamp
+pub fn format_timestamp(seconds: u64) -> String {
+    DateTime::from_timestamp(seconds).to_rfc3339()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FixedProvider;
+
+    #[async_trait]
+    impl AIProvider for FixedProvider {
+        async fn generate_message(&self, _prompt: &str) -> Result<String> {
+            Ok("feat: add login".to_string())
+        }
+
+        fn provider_name(&self) -> &'static str {
+            "OpenAI"
+        }
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_timestamp(1_760_531_696), "2025-10-15T12:34:56Z");
+    }
+
+    #[test]
+    fn test_count_redactions() {
+        let text = format!(
+            "+connect(\"<HOST_1>\", \"<EMAIL_1>\")\n+retry(\"<HOST_1>\")\n{REMOVED_LINE_NOTE}\n"
+        );
+        assert_eq!(count_redactions(&text), 3);
+        assert_eq!(count_redactions("+fn main() {}"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_auditing_provider_records_requests() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(AUDIT_FILE_NAME);
+        let provider = AuditingProvider::new(
+            Box::new(FixedProvider),
+            "gpt-4".to_string(),
+            PrivacyLevel::Redacted,
+            path.clone(),
+        );
+
+        let prompt = "## Git Diff:\n+send(\"<EMAIL_1>\")\n";
+        assert_eq!(
+            provider.generate_message(prompt).await.unwrap(),
+            "feat: add login"
+        );
+        assert!(provider
+            .embed("text-embedding-3-small", "diff")
+            .await
+            .is_err());
+
+        let records = read_records(&path).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].provider, "OpenAI");
+        assert_eq!(records[0].model, "gpt-4");
+        assert_eq!(records[0].operation, "completion");
+        assert_eq!(records[0].privacy, PrivacyLevel::Redacted);
+        assert_eq!(records[0].redactions, 1);
+        assert_eq!(records[0].bytes, prompt.len());
+        assert_eq!(records[1].model, "text-embedding-3-small");
+        assert_eq!(records[0].prompt_version, Some(PROMPT_VERSION));
+        assert_eq!(records[1].operation, "embedding");
+        assert_eq!(records[1].prompt_version, None);
+    }
+}
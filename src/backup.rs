@@ -0,0 +1,211 @@
+//! Safety refs kept before committor changes history
+//!
+//! Commands that replace commits on the current branch (`split`, `rewrite` and
+//! `restore` itself) first point `refs/committor/backup/<timestamp>` at HEAD, all
+//! through this module, so `committor restore` can put the branch back however
+//! the command ended. Only the newest backups are kept.
+
+use crate::audit::format_timestamp;
+use crate::git::git_output;
+use anyhow::Result;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Namespace of the backup refs, each named after the Unix time it was taken
+pub const BACKUP_REF_PREFIX: &str = "refs/committor/backup/";
+
+/// How many backups are kept; taking another drops the oldest
+pub const MAX_BACKUPS: usize = 20;
+
+/// A backup ref and the commit it keeps
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backup {
+    /// Full ref name, e.g. `refs/committor/backup/1760531696`
+    pub name: String,
+    pub commit: String,
+    /// Subject of the commit it keeps
+    pub subject: String,
+    /// When it was taken, in seconds since the Unix epoch
+    pub created: u64,
+}
+
+impl Backup {
+    /// Get the name after the prefix, which `restore` accepts
+    pub fn short_name(&self) -> &str {
+        self.name
+            .strip_prefix(BACKUP_REF_PREFIX)
+            .unwrap_or(&self.name)
+    }
+
+    /// Get when it was taken as an RFC 3339 UTC timestamp
+    pub fn created_at(&self) -> String {
+        format_timestamp(self.created)
+    }
+}
+
+/// Point a new backup ref at HEAD in the current repository
+pub fn create_backup() -> Result<Option<Backup>> {
+    create_backup_in(Path::new("."))
+}
+
+/// Point a new backup ref at HEAD, `None` on an unborn branch with nothing to keep
+pub fn create_backup_in(dir: &Path) -> Result<Option<Backup>> {
+    let Ok(commit) = git_output(dir, &["rev-parse", "--verify", "-q", "HEAD"]) else {
+        return Ok(None);
+    };
+    let subject = git_output(dir, &["log", "-1", "--format=%s", &commit])?;
+    let created = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+
+    // Two backups in the same second get a counter
+    let existing = list_backups_in(dir)?;
+    let mut name = format!("{BACKUP_REF_PREFIX}{created}");
+    let mut counter = 1;
+    while existing.iter().any(|backup| backup.name == name) {
+        counter += 1;
+        name = format!("{BACKUP_REF_PREFIX}{created}-{counter}");
+    }
+    git_output(dir, &["update-ref", &name, &commit, ""])?;
+
+    for old in existing.iter().skip(MAX_BACKUPS - 1) {
+        drop_backup_in(dir, old)?;
+    }
+    Ok(Some(Backup {
+        name,
+        commit,
+        subject,
+        created,
+    }))
+}
+
+/// Remove a backup the command no longer needs, e.g. after rolling its own changes back
+pub fn drop_backup_in(dir: &Path, backup: &Backup) -> Result<()> {
+    git_output(dir, &["update-ref", "-d", &backup.name])?;
+    Ok(())
+}
+
+/// List the backups in the current repository, newest first
+pub fn list_backups() -> Result<Vec<Backup>> {
+    list_backups_in(Path::new("."))
+}
+
+/// List the backups in a repository, newest first
+pub fn list_backups_in(dir: &Path) -> Result<Vec<Backup>> {
+    let output = git_output(
+        dir,
+        &[
+            "for-each-ref",
+            "--format=%(refname) %(objectname) %(subject)",
+            BACKUP_REF_PREFIX,
+        ],
+    )?;
+
+    let mut backups: Vec<(u64, u32, Backup)> = output
+        .lines()
+        .filter_map(|line| {
+            let (name, rest) = line.split_once(' ')?;
+            let (commit, subject) = rest.split_once(' ').unwrap_or((rest, ""));
+            let stamp = name.strip_prefix(BACKUP_REF_PREFIX)?;
+            let (seconds, counter) = stamp.split_once('-').unwrap_or((stamp, "1"));
+            let created = seconds.parse().ok()?;
+            let backup = Backup {
+                name: name.to_string(),
+                commit: commit.to_string(),
+                subject: subject.to_string(),
+                created,
+            };
+            Some((created, counter.parse().ok()?, backup))
+        })
+        .collect();
+    backups.sort_by_key(|(created, counter, _)| std::cmp::Reverse((*created, *counter)));
+    Ok(backups.into_iter().map(|(_, _, backup)| backup).collect())
+}
+
+/// Find a backup by its full or short name, or the newest when no name is given
+pub fn find_backup<'a>(backups: &'a [Backup], name: Option<&str>) -> Option<&'a Backup> {
+    match name {
+        Some(name) => backups
+            .iter()
+            .find(|backup| backup.name == name || backup.short_name() == name),
+        None => backups.first(),
+    }
+}
+
+/// Move the current branch back to a backup in the current repository
+pub fn restore_backup(backup: &Backup) -> Result<Option<Backup>> {
+    restore_backup_in(Path::new("."), backup)
+}
+
+/// Move the current branch back to a backup, first backing up where it is now
+///
+/// Uses `git reset --keep`, so uncommitted changes survive and the reset is refused
+/// if it would overwrite them. Returns the backup of the HEAD that was replaced.
+pub fn restore_backup_in(dir: &Path, backup: &Backup) -> Result<Option<Backup>> {
+    let current = create_backup_in(dir)?;
+    if let Err(e) = git_output(dir, &["reset", "-q", "--keep", &backup.commit]) {
+        if let Some(current) = &current {
+            drop_backup_in(dir, current)?;
+        }
+        return Err(e.context(format!("Failed to restore {}", backup.short_name())));
+    }
+    Ok(current)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fixtures::{commit, git, init_repo};
+    use std::fs;
+
+    #[test]
+    fn test_create_and_restore_backups() {
+        let temp_dir = init_repo();
+        let dir = temp_dir.path();
+        assert_eq!(create_backup_in(dir).unwrap(), None);
+
+        let first = commit(dir, "a.txt", "a", "feat: add a");
+        let backup = create_backup_in(dir).unwrap().unwrap();
+        assert_eq!(backup.commit, first);
+        assert_eq!(backup.subject, "feat: add a");
+        let second = commit(dir, "b.txt", "b", "feat: add b");
+        let again = create_backup_in(dir).unwrap().unwrap();
+        assert_ne!(again.name, backup.name);
+
+        let backups = list_backups_in(dir).unwrap();
+        assert_eq!(backups, vec![again.clone(), backup.clone()]);
+        assert_eq!(find_backup(&backups, None), Some(&again));
+        assert_eq!(
+            find_backup(&backups, Some(backup.short_name())),
+            Some(&backup)
+        );
+        assert_eq!(find_backup(&backups, Some("0")), None);
+
+        // Restoring keeps uncommitted changes and backs up the HEAD it replaces
+        fs::write(dir.join("a.txt"), "edited").unwrap();
+        let replaced = restore_backup_in(dir, &backup).unwrap().unwrap();
+        assert_eq!(replaced.commit, second);
+        assert_eq!(git(dir, &["rev-parse", "HEAD"]), first);
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "edited");
+        assert_eq!(list_backups_in(dir).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_only_the_newest_backups_are_kept() {
+        let temp_dir = init_repo();
+        let dir = temp_dir.path();
+        let head = commit(dir, "a.txt", "a", "feat: add a");
+        for stamp in 1..=MAX_BACKUPS {
+            git(
+                dir,
+                &["update-ref", &format!("{BACKUP_REF_PREFIX}{stamp}"), &head],
+            );
+        }
+
+        create_backup_in(dir).unwrap();
+        let backups = list_backups_in(dir).unwrap();
+        assert_eq!(backups.len(), MAX_BACKUPS);
+        assert_eq!(backups.last().unwrap().short_name(), "2");
+    }
+}
@@ -0,0 +1,258 @@
+//! Conventional commit compliance of the recent history, as a README badge
+//!
+//! `committor badge` counts how many of the last commits have a conventional
+//! subject and writes either a shields.io endpoint file, for
+//! `https://img.shields.io/endpoint?url=…`, or a self-contained SVG to commit
+//! next to the README. Merge commits are skipped, since git writes their subjects.
+
+use crate::validate::parse_header;
+use anyhow::Result;
+use git2::Repository;
+use serde::Serialize;
+use std::path::Path;
+
+/// Label shown on the left of the badge unless another is given
+pub const DEFAULT_LABEL: &str = "conventional commits";
+
+/// What the badge is written as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BadgeFormat {
+    /// A shields.io endpoint file
+    Json,
+    /// A badge image
+    Svg,
+}
+
+impl BadgeFormat {
+    /// Pick the format from a file's extension, JSON unless it is `.svg`
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("svg") => BadgeFormat::Svg,
+            _ => BadgeFormat::Json,
+        }
+    }
+}
+
+/// How many of the recent commits have a conventional subject
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Compliance {
+    pub conventional: usize,
+    pub total: usize,
+}
+
+impl Compliance {
+    /// Get the share of conventional commits as a whole percentage, or `None` without commits
+    pub fn percent(&self) -> Option<usize> {
+        (self.total > 0).then(|| self.conventional * 100 / self.total)
+    }
+
+    /// Get the text on the right of the badge, e.g. `87%`
+    pub fn message(&self) -> String {
+        match self.percent() {
+            Some(percent) => format!("{percent}%"),
+            None => "no commits".to_string(),
+        }
+    }
+
+    /// Get the shields.io color for the percentage
+    pub fn color(&self) -> &'static str {
+        match self.percent() {
+            None => "lightgrey",
+            Some(90..) => "brightgreen",
+            Some(75..) => "green",
+            Some(50..) => "yellow",
+            Some(25..) => "orange",
+            Some(_) => "red",
+        }
+    }
+}
+
+/// Measure the compliance of the last `last` commits of the current repository
+pub fn compliance(last: usize) -> Result<Compliance> {
+    let repo = Repository::discover(".")?;
+    compliance_from_repo(&repo, last)
+}
+
+/// Measure the compliance of the last `last` commits reachable from HEAD
+pub fn compliance_from_repo(repo: &Repository, last: usize) -> Result<Compliance> {
+    let mut compliance = Compliance {
+        conventional: 0,
+        total: 0,
+    };
+    // Nothing is committed yet
+    let Ok(head) = repo.head().and_then(|head| head.peel_to_commit()) else {
+        return Ok(compliance);
+    };
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push(head.id())?;
+    for oid in revwalk {
+        if compliance.total == last {
+            break;
+        }
+        let commit = repo.find_commit(oid?)?;
+        if commit.parent_count() > 1 {
+            continue;
+        }
+        compliance.total += 1;
+        if parse_header(commit.summary().unwrap_or_default()).is_ok() {
+            compliance.conventional += 1;
+        }
+    }
+    Ok(compliance)
+}
+
+/// The fields of a shields.io endpoint file
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Endpoint<'a> {
+    schema_version: u8,
+    label: &'a str,
+    message: String,
+    color: &'static str,
+}
+
+/// Write the badge in the given format
+pub fn render(compliance: &Compliance, label: &str, format: BadgeFormat) -> Result<String> {
+    match format {
+        BadgeFormat::Json => endpoint_json(compliance, label),
+        BadgeFormat::Svg => Ok(svg(compliance, label)),
+    }
+}
+
+/// Write a shields.io endpoint file
+pub fn endpoint_json(compliance: &Compliance, label: &str) -> Result<String> {
+    let endpoint = Endpoint {
+        schema_version: 1,
+        label,
+        message: compliance.message(),
+        color: compliance.color(),
+    };
+    Ok(format!("{}\n", serde_json::to_string_pretty(&endpoint)?))
+}
+
+/// Hex codes of the shields.io named colors the badge uses
+fn hex_color(color: &str) -> &'static str {
+    match color {
+        "brightgreen" => "#4c1",
+        "green" => "#97ca00",
+        "yellow" => "#dfb317",
+        "orange" => "#fe7d37",
+        "red" => "#e05d44",
+        _ => "#9f9f9f",
+    }
+}
+
+/// Approximate width of text in 11px Verdana, as shields.io lays it out
+fn text_width(text: &str) -> usize {
+    text.chars().count() * 7 + 10
+}
+
+/// Write a flat badge image in the style of shields.io
+pub fn svg(compliance: &Compliance, label: &str) -> String {
+    let message = compliance.message();
+    let color = hex_color(compliance.color());
+    let label_width = text_width(label);
+    let message_width = text_width(&message);
+    let label = escape_xml(label);
+    let message = escape_xml(&message);
+    let width = label_width + message_width;
+    let label_x = label_width * 5;
+    let message_x = (label_width * 2 + message_width) * 5;
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {message}">
+  <title>{label}: {message}</title>
+  <linearGradient id="s" x2="0" y2="100%">
+    <stop offset="0" stop-color="#bbb" stop-opacity=".1"/>
+    <stop offset="1" stop-opacity=".1"/>
+  </linearGradient>
+  <clipPath id="r"><rect width="{width}" height="20" rx="3" fill="#fff"/></clipPath>
+  <g clip-path="url(#r)">
+    <rect width="{label_width}" height="20" fill="#555"/>
+    <rect x="{label_width}" width="{message_width}" height="20" fill="{color}"/>
+    <rect width="{width}" height="20" fill="url(#s)"/>
+  </g>
+  <g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="110">
+    <text x="{label_x}" y="140" transform="scale(.1)">{label}</text>
+    <text x="{message_x}" y="140" transform="scale(.1)">{message}</text>
+  </g>
+</svg>
+"##
+    )
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(repo: &Repository, message: &str) {
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let signature = git2::Signature::now("Test User", "test@example.com").unwrap();
+        let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+        let parents: Vec<_> = parent.iter().collect();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_compliance_badge() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let repo = Repository::init(dir.path())?;
+        let empty = compliance_from_repo(&repo, 10)?;
+        assert_eq!(empty.message(), "no commits");
+        assert_eq!(empty.color(), "lightgrey");
+
+        commit(&repo, "Initial commit");
+        commit(&repo, "feat: add login");
+        commit(&repo, "fix(auth): handle expired tokens");
+        commit(&repo, "docs: describe login");
+        let compliance = compliance_from_repo(&repo, 10)?;
+        assert_eq!(
+            compliance,
+            Compliance {
+                conventional: 3,
+                total: 4
+            }
+        );
+        assert_eq!(compliance.message(), "75%");
+        assert_eq!(compliance.color(), "green");
+        assert_eq!(compliance_from_repo(&repo, 3)?.message(), "100%");
+
+        let json: serde_json::Value =
+            serde_json::from_str(&render(&compliance, DEFAULT_LABEL, BadgeFormat::Json)?)?;
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "schemaVersion": 1,
+                "label": "conventional commits",
+                "message": "75%",
+                "color": "green"
+            })
+        );
+
+        let svg = render(&compliance, "commits & co", BadgeFormat::Svg)?;
+        assert!(svg.contains("aria-label=\"commits &amp; co: 75%\""));
+        assert!(svg.contains("fill=\"#97ca00\""));
+        assert_eq!(
+            BadgeFormat::from_path(Path::new("badge.SVG")),
+            BadgeFormat::Svg
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,152 @@
+//! The branch a feature branch is compared against, read from the remote
+//!
+//! Repositories name their default branch `main`, `master`, `trunk` or `develop`,
+//! so assuming `main` sends `gate` and `rewrite` to the wrong base. The remote's
+//! own `HEAD` says which it is. Asking the remote is a network round trip, so the
+//! answer is cached where git keeps it, in `refs/remotes/<remote>/HEAD`.
+
+use crate::git::git_output;
+use crate::types::CommittorError;
+use anyhow::Result;
+use std::path::Path;
+use std::process::Command;
+
+/// Remote whose default branch is used
+pub const DEFAULT_REMOTE: &str = "origin";
+
+/// Find the default branch of `origin` for the repository in the current directory
+pub fn default_base_branch() -> Result<String> {
+    default_base_branch_in(Path::new("."), DEFAULT_REMOTE)
+}
+
+/// Find a remote's default branch, e.g. `origin/main`
+///
+/// Reads `refs/remotes/<remote>/HEAD` if clone or `git remote set-head` recorded it,
+/// otherwise asks the remote and records the answer. Without a reachable remote, the
+/// first of `<remote>/main`, `<remote>/master`, `main` and `master` that exists is used.
+pub fn default_base_branch_in(dir: &Path, remote: &str) -> Result<String> {
+    let head_ref = format!("refs/remotes/{remote}/HEAD");
+    if let Ok(branch) = git_output(dir, &["symbolic-ref", "-q", "--short", &head_ref]) {
+        return Ok(branch);
+    }
+
+    if let Some(name) = remote_head(dir, remote) {
+        let tracking = format!("refs/remotes/{remote}/{name}");
+        // Only cache a branch that has been fetched, so the cached ref always resolves
+        if git_output(dir, &["rev-parse", "--verify", "-q", &tracking]).is_ok() {
+            git_output(dir, &["symbolic-ref", &head_ref, &tracking])?;
+        }
+        return Ok(format!("{remote}/{name}"));
+    }
+
+    let candidates = [
+        format!("{remote}/main"),
+        format!("{remote}/master"),
+        "main".to_string(),
+        "master".to_string(),
+    ];
+    candidates
+        .into_iter()
+        .find(|branch| {
+            git_output(
+                dir,
+                &[
+                    "rev-parse",
+                    "--verify",
+                    "-q",
+                    &format!("{branch}^{{commit}}"),
+                ],
+            )
+            .is_ok()
+        })
+        .ok_or_else(|| {
+            CommittorError::GitError(format!(
+                "Could not find the default branch of {remote}; pass --base"
+            ))
+            .into()
+        })
+}
+
+/// Ask the remote which branch its HEAD points at, `None` if it can't be reached
+fn remote_head(dir: &Path, remote: &str) -> Option<String> {
+    let output = Command::new("git")
+        .args(["ls-remote", "--symref", remote, "HEAD"])
+        .current_dir(dir)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .env("GIT_ASKPASS", "")
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_symref(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Get the branch from `git ls-remote --symref <remote> HEAD` output
+pub fn parse_symref(output: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        let (target, name) = line.strip_prefix("ref: ")?.split_once('\t')?;
+        if name != "HEAD" {
+            return None;
+        }
+        target.strip_prefix("refs/heads/").map(str::to_string)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fixtures::{commit, git, init_repo_in};
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_symref() {
+        assert_eq!(
+            parse_symref("ref: refs/heads/trunk\tHEAD\n1234abcd\tHEAD\n").as_deref(),
+            Some("trunk")
+        );
+        assert_eq!(parse_symref("1234abcd\tHEAD\n"), None);
+    }
+
+    #[test]
+    fn test_default_base_branch_from_remote() {
+        let temp_dir = TempDir::new().unwrap();
+        let upstream = temp_dir.path().join("upstream");
+        let clone = temp_dir.path().join("clone");
+        fs::create_dir(&upstream).unwrap();
+        init_repo_in(&upstream);
+        git(&upstream, &["checkout", "-q", "-b", "trunk"]);
+        commit(&upstream, "a.txt", "a", "chore: initial");
+        git(
+            temp_dir.path(),
+            &["clone", "-q", upstream.to_str().unwrap(), "clone"],
+        );
+
+        // Recorded by clone
+        assert_eq!(
+            default_base_branch_in(&clone, "origin").unwrap(),
+            "origin/trunk"
+        );
+
+        // Asked of the remote, then cached
+        git(&clone, &["remote", "set-head", "origin", "-d"]);
+        assert_eq!(
+            default_base_branch_in(&clone, "origin").unwrap(),
+            "origin/trunk"
+        );
+        assert_eq!(
+            git(
+                &clone,
+                &["symbolic-ref", "--short", "refs/remotes/origin/HEAD"]
+            ),
+            "origin/trunk"
+        );
+
+        // Without a reachable remote, a conventional name is used
+        git(&clone, &["remote", "set-head", "origin", "-d"]);
+        git(&clone, &["remote", "set-url", "origin", "/nonexistent"]);
+        git(&clone, &["branch", "-q", "master"]);
+        assert_eq!(default_base_branch_in(&clone, "origin").unwrap(), "master");
+    }
+}
@@ -0,0 +1,91 @@
+//! The last batch of generated messages, kept so a later run can commit from it
+
+use crate::paths::git_state_path;
+use crate::prompt::PROMPT_VERSION;
+use crate::state::write_atomic;
+use anyhow::{Context, Result};
+use git2::{ObjectType, Oid, Repository};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// Name of the saved batch file, kept in the git directory
+pub const LAST_BATCH_FILE_NAME: &str = "committor-last-messages.json";
+
+/// Messages generated for a diff, identified by the diff's hash and the prompt version
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LastBatch {
+    pub diff_hash: String,
+    #[serde(default)]
+    pub prompt_version: u32,
+    pub messages: Vec<String>,
+}
+
+/// Hash a diff the way git hashes a blob, so the key is stable across versions
+pub fn diff_hash(diff: &str) -> Result<String> {
+    Ok(Oid::hash_object(ObjectType::Blob, diff.as_bytes())?.to_string())
+}
+
+/// Get the path of the saved batch for a repository
+pub fn last_batch_path_from_repo(repo: &Repository) -> PathBuf {
+    git_state_path(repo, LAST_BATCH_FILE_NAME)
+}
+
+/// Save the messages generated for a diff, replacing any earlier batch
+pub fn save_last_batch_from_repo(repo: &Repository, diff: &str, messages: &[String]) -> Result<()> {
+    let batch = LastBatch {
+        diff_hash: diff_hash(diff)?,
+        prompt_version: PROMPT_VERSION,
+        messages: messages.to_vec(),
+    };
+    let path = last_batch_path_from_repo(repo);
+    write_atomic(&path, serde_json::to_string_pretty(&batch)?)
+        .with_context(|| format!("Failed to save messages to {}", path.display()))
+}
+
+/// Load the saved messages if they were generated for this exact diff by the current prompts
+pub fn load_last_batch_from_repo(repo: &Repository, diff: &str) -> Result<Option<Vec<String>>> {
+    let path = last_batch_path_from_repo(repo);
+    let Ok(content) = fs::read_to_string(&path) else {
+        return Ok(None);
+    };
+
+    let batch: LastBatch = serde_json::from_str(&content)
+        .with_context(|| format!("Invalid saved messages in {}", path.display()))?;
+    if batch.diff_hash != diff_hash(diff)? || batch.prompt_version != PROMPT_VERSION {
+        return Ok(None);
+    }
+    Ok(Some(batch.messages))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_last_batch_round_trip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let repo = Repository::init(dir.path())?;
+        let messages = vec![
+            "feat: add login".to_string(),
+            "feat(auth): add login".to_string(),
+        ];
+
+        assert_eq!(load_last_batch_from_repo(&repo, "+login")?, None);
+
+        save_last_batch_from_repo(&repo, "+login", &messages)?;
+        assert_eq!(load_last_batch_from_repo(&repo, "+login")?, Some(messages));
+        assert_eq!(load_last_batch_from_repo(&repo, "+logout")?, None);
+
+        // Suggestions from older prompt templates are not reused
+        let path = last_batch_path_from_repo(&repo);
+        let stale = fs::read_to_string(&path)?.replace(
+            &format!("\"prompt_version\": {PROMPT_VERSION}"),
+            "\"prompt_version\": 0",
+        );
+        fs::write(&path, stale)?;
+        assert_eq!(load_last_batch_from_repo(&repo, "+login")?, None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,339 @@
+//! Layout of message bodies: wrapping, bullet markers and markdown clean-up
+//!
+//! Git shows bodies as plain text, so markdown a model adds (headings, bold,
+//! code fences, links) shows up literally. Bodies are reflowed to 72 columns
+//! the way `git log` expects, with every bullet written as `- `.
+
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Column bodies are wrapped at
+pub const BODY_WIDTH: usize = 72;
+
+/// Format everything after the subject line, leaving the subject as it is
+pub fn format_message_body(message: &str) -> String {
+    let message = message.trim();
+    let Some((subject, body)) = message.split_once('\n') else {
+        return message.to_string();
+    };
+
+    let body = format_body(body);
+    if body.is_empty() {
+        subject.trim_end().to_string()
+    } else {
+        format!("{}\n\n{body}", subject.trim_end())
+    }
+}
+
+/// Wrap a body at [`BODY_WIDTH`], write bullets as `- ` and strip markdown
+///
+/// Code blocks, indented lines and a closing block of trailers are kept as they are.
+pub fn format_body(body: &str) -> String {
+    let lines: Vec<&str> = body.lines().map(str::trim_end).collect();
+    let (content, trailers) = split_trailers(&lines);
+
+    let mut layout = Layout::default();
+    let mut in_fence = false;
+    for line in content {
+        if line.trim_start().starts_with("```") {
+            layout.flush();
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence || line.starts_with("    ") || line.starts_with('\t') {
+            layout.verbatim(line);
+        } else if line.trim().is_empty() {
+            layout.blank();
+        } else if let Some((marker, text)) = list_item(line) {
+            layout.flush();
+            layout.item = Some((marker, strip_markdown(text)));
+        } else {
+            layout.text(&strip_markdown(line.trim()));
+        }
+    }
+    layout.flush();
+
+    if !trailers.is_empty() {
+        layout.blank();
+        layout
+            .lines
+            .extend(trailers.iter().map(|line| line.to_string()));
+    }
+    layout.finish()
+}
+
+/// Lines of output being built, with the paragraph or list item still being collected
+#[derive(Default)]
+struct Layout {
+    lines: Vec<String>,
+    paragraph: Vec<String>,
+    item: Option<(String, String)>,
+}
+
+impl Layout {
+    /// Add prose, continuing the current list item if there is one
+    fn text(&mut self, text: &str) {
+        match &mut self.item {
+            Some((_, item)) => {
+                item.push(' ');
+                item.push_str(text);
+            }
+            None => self.paragraph.push(text.to_string()),
+        }
+    }
+
+    fn verbatim(&mut self, line: &str) {
+        self.flush();
+        self.lines.push(line.to_string());
+    }
+
+    fn blank(&mut self) {
+        self.flush();
+        if self.lines.last().is_some_and(|line| !line.is_empty()) {
+            self.lines.push(String::new());
+        }
+    }
+
+    /// Wrap the collected paragraph or list item into lines
+    fn flush(&mut self) {
+        if !self.paragraph.is_empty() {
+            let paragraph = self.paragraph.join(" ");
+            wrap(&paragraph, "", "", &mut self.lines);
+            self.paragraph.clear();
+        }
+        if let Some((marker, text)) = self.item.take() {
+            let hanging = " ".repeat(marker.chars().count());
+            wrap(&text, &marker, &hanging, &mut self.lines);
+        }
+    }
+
+    fn finish(mut self) -> String {
+        while self.lines.last().is_some_and(String::is_empty) {
+            self.lines.pop();
+        }
+        self.lines.join("\n")
+    }
+}
+
+/// Split off a final paragraph made only of `Key: value` trailers
+fn split_trailers<'a>(lines: &'a [&'a str]) -> (&'a [&'a str], &'a [&'a str]) {
+    static TRAILER: OnceLock<Regex> = OnceLock::new();
+    let trailer_regex = TRAILER.get_or_init(|| Regex::new(r"^[A-Za-z][A-Za-z0-9-]*: \S").unwrap());
+    let end = lines
+        .iter()
+        .rposition(|line| !line.trim().is_empty())
+        .map_or(0, |last| last + 1);
+    let start = lines[..end]
+        .iter()
+        .rposition(|line| line.trim().is_empty())
+        .map_or(0, |blank| blank + 1);
+
+    let block = &lines[start..end];
+    // A body that is nothing but one `Word: text` line is prose, not a trailer
+    if start > 0 && !block.is_empty() && block.iter().all(|line| trailer_regex.is_match(line)) {
+        (&lines[..start], block)
+    } else {
+        (lines, &[])
+    }
+}
+
+/// Split a list item into its normalized marker, e.g. `- ` or `  2. `, and its text
+fn list_item(line: &str) -> Option<(String, &str)> {
+    static ITEM: OnceLock<Regex> = OnceLock::new();
+    let item_regex =
+        ITEM.get_or_init(|| Regex::new(r"^(\s*)(?:([-*+•–])|(\d+)[.)])(?:\s+(\S.*))?$").unwrap());
+    let captures = item_regex.captures(line)?;
+    let indent = &captures[1];
+    // A marker alone on its line still starts an item; its text is on the lines below
+    let text = captures.get(4).map_or("", |text| text.as_str());
+
+    let marker = match captures.get(3) {
+        Some(number) => format!("{indent}{}. ", number.as_str()),
+        None => format!("{indent}- "),
+    };
+    Some((marker, text))
+}
+
+/// Remove markdown that git shows literally: headings, bold and link syntax
+fn strip_markdown(text: &str) -> String {
+    static HEADING: OnceLock<Regex> = OnceLock::new();
+    static BOLD: OnceLock<Regex> = OnceLock::new();
+    static LINK: OnceLock<Regex> = OnceLock::new();
+    let heading_regex = HEADING.get_or_init(|| Regex::new(r"^#{1,6}\s+").unwrap());
+    let bold_regex = BOLD.get_or_init(|| Regex::new(r"\*\*([^*]+)\*\*").unwrap());
+    let link_regex = LINK.get_or_init(|| Regex::new(r"\[([^\]]+)\]\(([^)\s]+)\)").unwrap());
+
+    let text = heading_regex.replace(text, "");
+    let text = bold_regex.replace_all(&text, "$1");
+    link_regex.replace_all(&text, "$1 ($2)").into_owned()
+}
+
+/// Greedily wrap words at [`BODY_WIDTH`]; words longer than a line get a line of their own
+///
+/// Chinese, Japanese and Korean text has no spaces to break at, so it breaks
+/// between characters, each taking two columns.
+fn wrap(text: &str, first_prefix: &str, rest_prefix: &str, lines: &mut Vec<String>) {
+    let mut line = first_prefix.to_string();
+    let mut line_width = display_width(first_prefix);
+    let mut has_words = false;
+
+    for word in text.split_whitespace() {
+        let word_width = display_width(word);
+        let separator = usize::from(has_words);
+        if line_width + separator + word_width <= BODY_WIDTH || !word.chars().any(is_wide) {
+            // A line that began with a marker would read as a list item the next time
+            if has_words && line_width + 1 + word_width > BODY_WIDTH && !is_marker(word) {
+                lines.push(line);
+                line = rest_prefix.to_string();
+                line_width = display_width(rest_prefix);
+                has_words = false;
+            }
+            if has_words {
+                line.push(' ');
+                line_width += 1;
+            }
+            line.push_str(word);
+            line_width += word_width;
+            has_words = true;
+            continue;
+        }
+
+        if has_words {
+            line.push(' ');
+            line_width += 1;
+        }
+        for c in word.chars() {
+            let width = char_width(c);
+            if has_words && line_width + width > BODY_WIDTH {
+                lines.push(line);
+                line = rest_prefix.to_string();
+                line_width = display_width(rest_prefix);
+            }
+            line.push(c);
+            line_width += width;
+            has_words = true;
+        }
+    }
+
+    if has_words {
+        lines.push(line);
+    }
+}
+
+/// Check whether a word is a list marker on its own, e.g. `-` or `2.`
+fn is_marker(word: &str) -> bool {
+    matches!(word, "-" | "*" | "+" | "•" | "–")
+        || word
+            .strip_suffix(['.', ')'])
+            .is_some_and(|number| !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Columns a string takes up in a terminal
+fn display_width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+fn char_width(c: char) -> usize {
+    if is_wide(c) {
+        2
+    } else {
+        1
+    }
+}
+
+/// Check for the CJK and full-width characters terminals draw two columns wide
+fn is_wide(c: char) -> bool {
+    matches!(
+        c,
+        '\u{1100}'..='\u{115F}'
+            | '\u{2E80}'..='\u{A4CF}'
+            | '\u{AC00}'..='\u{D7A3}'
+            | '\u{F900}'..='\u{FAFF}'
+            | '\u{FE30}'..='\u{FE4F}'
+            | '\u{FF00}'..='\u{FF60}'
+            | '\u{FFE0}'..='\u{FFE6}'
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wraps_paragraphs_at_72_columns() {
+        let body = "This change moves the session refresh into a background task so that \
+                    requests no longer block while the token is renewed.";
+        let formatted = format_body(body);
+
+        assert!(formatted
+            .lines()
+            .all(|line| line.chars().count() <= BODY_WIDTH));
+        assert_eq!(
+            formatted.split_whitespace().collect::<Vec<_>>().join(" "),
+            body
+        );
+    }
+
+    #[test]
+    fn test_wraps_text_without_spaces() {
+        let body = "ログイン処理をバックグラウンドタスクに移動し、トークンの更新中にリクエストがブロックされないようにしました。";
+        let formatted = format_body(body);
+
+        assert!(formatted.lines().count() > 1);
+        assert!(formatted
+            .lines()
+            .all(|line| display_width(line) <= BODY_WIDTH));
+        assert_eq!(formatted.replace('\n', ""), body);
+    }
+
+    #[test]
+    fn test_normalizes_bullets_with_hanging_indent() {
+        let body = "* first point\n+ second point that is long enough that it has to wrap onto the next line\n• third";
+        assert_eq!(
+            format_body(body),
+            "- first point\n\
+             - second point that is long enough that it has to wrap onto the next\n  \
+             line\n\
+             - third"
+        );
+    }
+
+    #[test]
+    fn test_strips_markdown() {
+        let body = "## Summary\n\n**Why:** the [old client](https://example.com/x) leaked\n\n```\nlet x = 1;\n```";
+        assert_eq!(
+            format_body(body),
+            "Summary\n\nWhy: the old client (https://example.com/x) leaked\n\nlet x = 1;"
+        );
+    }
+
+    #[test]
+    fn test_keeps_trailers_and_subject() {
+        let message =
+            "feat: add login\n\n\n- adds   a form\n\nRefs: ABC-1\nSigned-off-by: A <a@example.com>";
+        assert_eq!(
+            format_message_body(message),
+            "feat: add login\n\n- adds a form\n\nRefs: ABC-1\nSigned-off-by: A <a@example.com>"
+        );
+        assert_eq!(format_message_body("fix: typo\n\n"), "fix: typo");
+    }
+
+    #[test]
+    fn test_bare_markers_stay_list_items() {
+        let body = "-
+starts on the next line
+
+the list markers - and 2. in prose wrap without starting a line";
+        let formatted = format_body(body);
+        assert_eq!(
+            formatted,
+            "- starts on the next line\n\nthe list markers - and 2. in prose wrap without starting a line"
+        );
+        assert_eq!(format_body(&formatted), formatted);
+
+        let prose = format!("{} b - after", "a ".repeat(35).trim_end());
+        let formatted = format_body(&prose);
+        assert!(formatted.lines().all(|line| !line.starts_with('-')));
+        assert_eq!(format_body(&formatted), formatted);
+    }
+}
@@ -0,0 +1,249 @@
+//! Cherry-picks whose message is adapted to the branch they land on
+//!
+//! A commit picked from another branch or fork keeps a message written for where it
+//! came from: scopes the target branch doesn't use, or no conventional format at
+//! all. The message is regenerated for the target and still records the original
+//! commit, as `git cherry-pick -x` does.
+
+use crate::commit::parse_commit_message;
+use crate::diff::get_commit_diff_from_repo;
+use crate::git::git_output;
+use crate::trailers::{append_trailers, message_trailers};
+use crate::types::CommittorError;
+use anyhow::{Context, Result};
+use git2::Repository;
+use std::path::Path;
+use std::process::Command;
+
+/// How many recent commits on the target branch its scopes are read from
+pub const SCOPE_HISTORY_COMMITS: usize = 200;
+
+/// A commit about to be cherry-picked onto the current branch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CherryPick {
+    pub commit: String,
+    /// Its message on the branch it came from
+    pub original: String,
+    pub diff: String,
+}
+
+impl CherryPick {
+    /// Get the message the picked commit ends up with
+    ///
+    /// The original's trailers are kept and the `(cherry picked from commit …)` line
+    /// that `git cherry-pick -x` writes is added, unless the message already has it.
+    pub fn final_message(&self, message: &str) -> String {
+        let message = append_trailers(message, &message_trailers(&self.original));
+        let line = cherry_picked_from(&self.commit);
+        if message.contains(&line) {
+            return message;
+        }
+        // Like -x, the line joins a trailer block rather than starting a paragraph
+        let separator = if message_trailers(&message).is_empty() {
+            "\n\n"
+        } else {
+            "\n"
+        };
+        format!("{message}{separator}{line}")
+    }
+}
+
+/// Get the line recording where a cherry-picked commit came from
+pub fn cherry_picked_from(commit: &str) -> String {
+    format!("(cherry picked from commit {commit})")
+}
+
+/// Find the commit to cherry-pick in the current repository
+pub fn find_commit(rev: &str) -> Result<CherryPick> {
+    let repo = Repository::open(".").context("Not in a git repository")?;
+    find_commit_from_repo(&repo, rev)
+}
+
+/// Find the commit to cherry-pick in a repository, refusing merge commits
+pub fn find_commit_from_repo(repo: &Repository, rev: &str) -> Result<CherryPick> {
+    let commit = repo
+        .revparse_single(rev)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|_| CommittorError::GitError(format!("{rev} is not a commit")))?;
+    if commit.parent_count() > 1 {
+        return Err(CommittorError::GitError(format!(
+            "{rev} is a merge commit; cherry-pick it with git cherry-pick -m"
+        ))
+        .into());
+    }
+
+    Ok(CherryPick {
+        commit: commit.id().to_string(),
+        original: commit.message().unwrap_or_default().trim_end().to_string(),
+        diff: get_commit_diff_from_repo(repo, &commit)?,
+    })
+}
+
+/// Get the scopes used by the current branch's recent commits, most used first
+pub fn branch_scopes() -> Result<Vec<String>> {
+    let repo = Repository::open(".").context("Not in a git repository")?;
+    branch_scopes_from_repo(&repo)
+}
+
+/// Get the scopes used by a repository's recent commits on HEAD, most used first
+pub fn branch_scopes_from_repo(repo: &Repository) -> Result<Vec<String>> {
+    let Ok(head) = repo.head().and_then(|head| head.peel_to_commit()) else {
+        return Ok(Vec::new());
+    };
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push(head.id())?;
+
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    for oid in revwalk.take(SCOPE_HISTORY_COMMITS) {
+        let commit = repo.find_commit(oid?)?;
+        let Ok(parsed) = parse_commit_message(commit.summary().unwrap_or_default()) else {
+            continue;
+        };
+        let Some(scope) = parsed.scope else {
+            continue;
+        };
+        match counts.iter_mut().find(|(known, _)| *known == scope) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((scope, 1)),
+        }
+    }
+    // Stable, so equally used scopes stay in order of most recent use
+    counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    Ok(counts.into_iter().map(|(scope, _)| scope).collect())
+}
+
+/// Cherry-pick a commit onto the current branch of the current repository with the message
+pub fn apply_cherry_pick(pick: &CherryPick, message: &str) -> Result<()> {
+    apply_cherry_pick_in(Path::new("."), pick, message)
+}
+
+/// Cherry-pick a commit onto the current branch with the message
+///
+/// When the pick stops on conflicts, the message is left where `git cherry-pick
+/// --continue` commits it from, and an error says how to go on.
+pub fn apply_cherry_pick_in(dir: &Path, pick: &CherryPick, message: &str) -> Result<()> {
+    let message = format!("{}\n", message.trim_end());
+    let output = Command::new("git")
+        .args(["cherry-pick", &pick.commit])
+        .current_dir(dir)
+        .env("GIT_EDITOR", "true")
+        .output()
+        .context("Failed to execute git cherry-pick")?;
+
+    if !output.status.success() {
+        if git_output(dir, &["rev-parse", "-q", "--verify", "CHERRY_PICK_HEAD"]).is_err() {
+            return Err(CommittorError::GitError(
+                String::from_utf8_lossy(&output.stderr).to_string(),
+            )
+            .into());
+        }
+        // Keep git's list of conflicts, which `committor continue` reads
+        let path = dir.join(git_output(dir, &["rev-parse", "--git-path", "MERGE_MSG"])?);
+        let prepared = std::fs::read_to_string(&path).unwrap_or_default();
+        let conflicts = prepared
+            .find("# Conflicts:")
+            .map_or("", |start| &prepared[start..]);
+        std::fs::write(&path, format!("{message}\n{conflicts}"))
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        return Err(CommittorError::GitError(format!(
+            "Cherry-picking {} stopped on conflicts; resolve and stage them, then run git cherry-pick --continue",
+            pick.commit.get(..7).unwrap_or(&pick.commit)
+        ))
+        .into());
+    }
+
+    let path = dir.join(git_output(
+        dir,
+        &["rev-parse", "--git-path", "COMMITTOR_CHERRY_PICK_MSG"],
+    )?);
+    std::fs::write(&path, &message)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    let amended = git_output(
+        dir,
+        &[
+            "commit",
+            "--amend",
+            "-q",
+            "--no-verify",
+            "-F",
+            &path.to_string_lossy(),
+        ],
+    );
+    let _ = std::fs::remove_file(&path);
+    amended.map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fixtures::{commit, git, init_repo};
+    use std::fs;
+
+    #[test]
+    fn test_final_message() {
+        let pick = CherryPick {
+            commit: "0123abcd".to_string(),
+            original: "fix(web): handle empty input\n\nRefs: ABC-1".to_string(),
+            diff: String::new(),
+        };
+        assert_eq!(
+            pick.final_message("fix(ui): handle empty input"),
+            "fix(ui): handle empty input\n\nRefs: ABC-1\n(cherry picked from commit 0123abcd)"
+        );
+
+        let pick = CherryPick {
+            original: "Handle empty input".to_string(),
+            ..pick
+        };
+        let message = pick.final_message("fix(ui): handle empty input");
+        assert_eq!(
+            message,
+            "fix(ui): handle empty input\n\n(cherry picked from commit 0123abcd)"
+        );
+        assert_eq!(pick.final_message(&message), message);
+    }
+
+    #[test]
+    fn test_branch_scopes() {
+        let temp_dir = init_repo();
+        let dir = temp_dir.path();
+        commit(dir, "a.txt", "a\n", "chore: initial");
+        commit(dir, "b.txt", "b", "feat(ui): add button");
+        commit(dir, "c.txt", "c", "fix(api): handle timeout");
+        commit(dir, "d.txt", "d", "fix(ui): align button");
+
+        let repo = Repository::open(dir).unwrap();
+        assert_eq!(branch_scopes_from_repo(&repo).unwrap(), vec!["ui", "api"]);
+    }
+
+    #[test]
+    fn test_apply_cherry_pick() {
+        let temp_dir = init_repo();
+        let dir = temp_dir.path();
+        commit(dir, "a.txt", "a\n", "chore: initial");
+        git(dir, &["checkout", "-q", "-b", "release"]);
+        git(dir, &["checkout", "-q", "main"]);
+        let picked = commit(dir, "b.txt", "b", "fix(web): handle empty input");
+        let conflicting = commit(dir, "a.txt", "main\n", "fix(web): use main");
+        git(dir, &["checkout", "-q", "release"]);
+        commit(dir, "a.txt", "release\n", "fix(ui): use release");
+
+        let repo = Repository::open(dir).unwrap();
+        let pick = find_commit_from_repo(&repo, &picked).unwrap();
+        assert_eq!(pick.original, "fix(web): handle empty input");
+        assert!(pick.diff.contains("b.txt"));
+        let message = pick.final_message("fix(ui): handle empty input");
+        apply_cherry_pick_in(dir, &pick, &message).unwrap();
+        assert_eq!(git(dir, &["log", "-1", "--format=%B"]), message);
+        assert_eq!(git(dir, &["log", "-1", "--format=%an"]), "Test User");
+
+        // A conflict leaves the message for git cherry-pick --continue
+        let pick = find_commit_from_repo(&repo, &conflicting).unwrap();
+        let error = apply_cherry_pick_in(dir, &pick, "fix(ui): use main")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("git cherry-pick --continue"), "{error}");
+        let prepared = fs::read_to_string(dir.join(".git/MERGE_MSG")).unwrap();
+        assert!(prepared.starts_with("fix(ui): use main\n\n# Conflicts:\n#\ta.txt"));
+    }
+}
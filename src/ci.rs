@@ -0,0 +1,387 @@
+//! Jobs and steps changed by staged CI configuration
+//!
+//! Raw workflow YAML is long and mostly indentation, so the model rarely names
+//! the workflow it touches. Jobs and steps are compared instead, and the patch
+//! is replaced with a summary such as ``changed job `lint` (added step `Run clippy`)``.
+
+use crate::diff::{get_staged_changes_from_repo, staged_file_versions_from_repo};
+use anyhow::{Context, Result};
+use git2::Repository;
+use serde_yaml::{Mapping, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Top-level GitLab CI keys that configure the pipeline rather than name a job
+const GITLAB_KEYWORDS: &[&str] = &[
+    "after_script",
+    "before_script",
+    "cache",
+    "default",
+    "image",
+    "include",
+    "services",
+    "stages",
+    "variables",
+    "workflow",
+];
+
+/// Note left in place of a summarized patch
+const SUMMARIZED_PATCH_NOTE: &str =
+    "(CI configuration, summarized under \"CI configuration changes\")\n";
+
+/// Which CI system a configuration file belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CiSystem {
+    GitHubActions,
+    GitLab,
+    CircleCi,
+}
+
+impl CiSystem {
+    /// Recognize a CI configuration file from its path
+    pub fn for_path(path: &str) -> Option<Self> {
+        let is_yaml = path.ends_with(".yml") || path.ends_with(".yaml");
+        if path.starts_with(".github/workflows/") && is_yaml {
+            Some(Self::GitHubActions)
+        } else if path == ".gitlab-ci.yml" || (path.starts_with(".gitlab/ci/") && is_yaml) {
+            Some(Self::GitLab)
+        } else if path == ".circleci/config.yml" {
+            Some(Self::CircleCi)
+        } else {
+            None
+        }
+    }
+}
+
+/// What a staged edit changed in one CI configuration file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CiChange {
+    pub path: String,
+    /// Workflow name from the file, for GitHub Actions workflows that set one
+    pub workflow: Option<String>,
+    /// Changes to jobs, steps and pipeline settings, e.g. ``added job `lint` ``
+    pub changes: Vec<String>,
+}
+
+impl fmt::Display for CiChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.workflow {
+            Some(workflow) => write!(f, "{} (workflow `{workflow}`): ", self.path)?,
+            None => write!(f, "{}: ", self.path)?,
+        }
+        if self.changes.is_empty() {
+            write!(f, "only comments or formatting changed")
+        } else {
+            write!(f, "{}", self.changes.join("; "))
+        }
+    }
+}
+
+/// Get the CI configuration changes staged in the repository in the current directory
+pub fn ci_changes() -> Result<Vec<CiChange>> {
+    let repo = Repository::open(".").context("Not in a git repository")?;
+    ci_changes_from_repo(&repo)
+}
+
+/// Get the CI configuration changes staged in a specific repository
+///
+/// Files that fail to parse on either side are left out, so their patch is sent as it is.
+pub fn ci_changes_from_repo(repo: &Repository) -> Result<Vec<CiChange>> {
+    let mut changes = Vec::new();
+    for change in get_staged_changes_from_repo(repo)? {
+        let Some(system) = CiSystem::for_path(&change.file_path) else {
+            continue;
+        };
+        let (old, new) = staged_file_versions_from_repo(repo, &change.file_path)?;
+        if let Some(ci_change) = compare_ci_config(
+            system,
+            &change.file_path,
+            old.as_deref().unwrap_or_default(),
+            new.as_deref().unwrap_or_default(),
+        ) {
+            changes.push(ci_change);
+        }
+    }
+    Ok(changes)
+}
+
+/// Compare two versions of a CI configuration file, `None` if either isn't valid YAML
+pub fn compare_ci_config(system: CiSystem, path: &str, old: &str, new: &str) -> Option<CiChange> {
+    let parse = |content: &str| -> Option<Mapping> {
+        match serde_yaml::from_str(content).ok()? {
+            Value::Mapping(mapping) => Some(mapping),
+            Value::Null => Some(Mapping::new()),
+            _ => None,
+        }
+    };
+    let (old, new) = (parse(old)?, parse(new)?);
+    let (old_jobs, old_settings) = split_jobs(system, &old);
+    let (new_jobs, new_settings) = split_jobs(system, &new);
+
+    let mut changes = Vec::new();
+    let settings = changed_keys(&old_settings, &new_settings);
+    if !settings.is_empty() {
+        changes.push(format!("changed {}", settings.join(", ")));
+    }
+    for (name, change) in compare_maps(&old_jobs, &new_jobs) {
+        changes.push(match change {
+            Change::Added => format!("added job `{name}`"),
+            Change::Removed => format!("removed job `{name}`"),
+            Change::Modified => {
+                let detail = job_changes(&old_jobs[&name], &new_jobs[&name]);
+                format!("changed job `{name}` ({})", detail.join(", "))
+            }
+        });
+    }
+
+    let workflow = match system {
+        CiSystem::GitHubActions => new.get("name").or(old.get("name")),
+        CiSystem::GitLab | CiSystem::CircleCi => None,
+    };
+    Some(CiChange {
+        path: path.to_string(),
+        workflow: workflow.and_then(Value::as_str).map(str::to_string),
+        changes,
+    })
+}
+
+/// Get the note that replaces each summarized file's patch, keyed by path
+///
+/// For [`crate::diff::replace_file_patches`].
+pub fn ci_patch_replacements(changes: &[CiChange]) -> Vec<(String, String)> {
+    changes
+        .iter()
+        .map(|change| (change.path.clone(), SUMMARIZED_PATCH_NOTE.to_string()))
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Change {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// Split a configuration into its jobs and its other top-level settings
+fn split_jobs(system: CiSystem, config: &Mapping) -> (BTreeMap<String, Value>, Mapping) {
+    let mut jobs = BTreeMap::new();
+    let mut settings = Mapping::new();
+    for (key, value) in config {
+        let Some(name) = key.as_str() else {
+            continue;
+        };
+        match system {
+            CiSystem::GitLab if !GITLAB_KEYWORDS.contains(&name) && value.is_mapping() => {
+                jobs.insert(name.to_string(), value.clone());
+            }
+            CiSystem::GitHubActions | CiSystem::CircleCi if name == "jobs" => {
+                let entries = value.as_mapping().into_iter().flatten();
+                jobs.extend(entries.filter_map(|(job, definition)| {
+                    Some((job.as_str()?.to_string(), definition.clone()))
+                }));
+            }
+            _ => {
+                settings.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    (jobs, settings)
+}
+
+/// Describe what changed inside a job: its steps where it has them, otherwise its keys
+fn job_changes(old: &Value, new: &Value) -> Vec<String> {
+    let mut changes = Vec::new();
+    let empty = Mapping::new();
+    let mut old_settings = old.as_mapping().unwrap_or(&empty).clone();
+    let mut new_settings = new.as_mapping().unwrap_or(&empty).clone();
+
+    let old_sequence = old_settings.remove("steps").unwrap_or_default();
+    let new_sequence = new_settings.remove("steps").unwrap_or_default();
+    let (old_steps, new_steps) = (labelled_steps(&old_sequence), labelled_steps(&new_sequence));
+    for (label, change) in compare_maps(&old_steps, &new_steps) {
+        changes.push(match change {
+            Change::Added => format!("added step `{label}`"),
+            Change::Removed => format!("removed step `{label}`"),
+            Change::Modified => format!("changed step `{label}`"),
+        });
+    }
+    if changes.is_empty() && old_sequence != new_sequence {
+        changes.push("reordered steps".to_string());
+    }
+
+    let keys = changed_keys(&old_settings, &new_settings);
+    if !keys.is_empty() {
+        changes.push(format!("changed {}", keys.join(", ")));
+    }
+    changes
+}
+
+/// Key each step by its name, action or first command, numbering repeats
+fn labelled_steps(steps: &Value) -> BTreeMap<String, Value> {
+    let mut labelled = BTreeMap::new();
+    for (i, step) in steps.as_sequence().into_iter().flatten().enumerate() {
+        let label = step_label(step).unwrap_or_else(|| format!("step {}", i + 1));
+        let mut key = label.clone();
+        let mut repeat = 1;
+        while labelled.contains_key(&key) {
+            repeat += 1;
+            key = format!("{label} #{repeat}");
+        }
+        labelled.insert(key, step.clone());
+    }
+    labelled
+}
+
+fn step_label(step: &Value) -> Option<String> {
+    let first_line = |text: &str| text.lines().next().unwrap_or_default().trim().to_string();
+    if let Some(step) = step.as_str() {
+        return Some(first_line(step));
+    }
+    let step = step.as_mapping()?;
+    for key in ["name", "uses", "run"] {
+        if let Some(value) = step.get(key).and_then(Value::as_str) {
+            return Some(first_line(value));
+        }
+    }
+    // CircleCI steps are one-key maps such as `run: {name: Test, command: ...}`
+    let (kind, body) = step.iter().next()?;
+    let kind = kind.as_str()?;
+    match body.get("name").and_then(Value::as_str) {
+        Some(name) => Some(format!("{kind}: {name}")),
+        None => Some(kind.to_string()),
+    }
+}
+
+fn compare_maps(
+    old: &BTreeMap<String, Value>,
+    new: &BTreeMap<String, Value>,
+) -> Vec<(String, Change)> {
+    let mut changes: Vec<(String, Change)> = new
+        .iter()
+        .filter_map(|(name, value)| match old.get(name) {
+            None => Some((name.clone(), Change::Added)),
+            Some(old_value) if old_value != value => Some((name.clone(), Change::Modified)),
+            Some(_) => None,
+        })
+        .collect();
+    changes.extend(
+        old.keys()
+            .filter(|name| !new.contains_key(*name))
+            .map(|name| (name.clone(), Change::Removed)),
+    );
+    changes
+}
+
+/// List the keys, as `` `key` ``, whose values differ between two mappings
+fn changed_keys(old: &Mapping, new: &Mapping) -> Vec<String> {
+    old.keys()
+        .chain(new.keys().filter(|key| !old.contains_key(*key)))
+        .filter(|key| old.get(*key) != new.get(*key))
+        .filter_map(|key| key.as_str().map(|key| format!("`{key}`")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD_WORKFLOW: &str = r#"
+name: CI
+on: [push]
+jobs:
+  test:
+    runs-on: ubuntu-latest
+    steps:
+      - uses: actions/checkout@v4
+      - name: Run tests
+        run: cargo test
+  docs:
+    runs-on: ubuntu-latest
+    steps:
+      - run: cargo doc
+"#;
+
+    const NEW_WORKFLOW: &str = r#"
+name: CI
+on: [push, pull_request]
+jobs:
+  test:
+    runs-on: ubuntu-latest
+    steps:
+      - uses: actions/checkout@v4
+      - name: Run tests
+        run: cargo test --workspace
+      - name: Run clippy
+        run: cargo clippy -- -D warnings
+  lint:
+    runs-on: ubuntu-latest
+    steps:
+      - run: cargo fmt --check
+"#;
+
+    #[test]
+    fn test_github_workflow_changes() {
+        let change = compare_ci_config(
+            CiSystem::GitHubActions,
+            ".github/workflows/ci.yml",
+            OLD_WORKFLOW,
+            NEW_WORKFLOW,
+        )
+        .unwrap();
+
+        assert_eq!(
+            change.to_string(),
+            ".github/workflows/ci.yml (workflow `CI`): changed `on`; \
+             added job `lint`; \
+             changed job `test` (added step `Run clippy`, changed step `Run tests`); \
+             removed job `docs`"
+        );
+    }
+
+    #[test]
+    fn test_gitlab_jobs_and_formatting_only() {
+        let old = "stages: [test]\nunit:\n  stage: test\n  script: [make test]\n";
+        let new = "stages: [test]\n# run the unit tests\nunit:\n  stage: test\n  script:\n    - make test\n";
+        let change = compare_ci_config(CiSystem::GitLab, ".gitlab-ci.yml", old, new).unwrap();
+        assert!(change.changes.is_empty());
+        assert_eq!(
+            change.to_string(),
+            ".gitlab-ci.yml: only comments or formatting changed"
+        );
+
+        assert!(compare_ci_config(CiSystem::GitLab, ".gitlab-ci.yml", old, "a: [").is_none());
+    }
+
+    #[test]
+    fn test_ci_system_for_path() {
+        assert_eq!(
+            CiSystem::for_path(".github/workflows/release.yaml"),
+            Some(CiSystem::GitHubActions)
+        );
+        assert_eq!(CiSystem::for_path(".gitlab-ci.yml"), Some(CiSystem::GitLab));
+        assert_eq!(
+            CiSystem::for_path(".circleci/config.yml"),
+            Some(CiSystem::CircleCi)
+        );
+        assert_eq!(CiSystem::for_path("docker-compose.yml"), None);
+    }
+
+    #[test]
+    fn test_ci_patch_replacements() {
+        let diff = "diff --git a/.github/workflows/ci.yml b/.github/workflows/ci.yml\n\
+                    @@ -1 +1 @@\n-on: [push]\n+on: [push, pull_request]\n\
+                    diff --git a/src/lib.rs b/src/lib.rs\n@@ -1 +1 @@\n+fn a() {}\n";
+        let changes = vec![CiChange {
+            path: ".github/workflows/ci.yml".to_string(),
+            workflow: None,
+            changes: vec!["changed `on`".to_string()],
+        }];
+
+        let summarized = crate::diff::replace_file_patches(diff, &ci_patch_replacements(&changes));
+        assert!(!summarized.contains("pull_request"));
+        assert!(summarized.contains(SUMMARIZED_PATCH_NOTE));
+        assert!(
+            summarized.ends_with("diff --git a/src/lib.rs b/src/lib.rs\n@@ -1 +1 @@\n+fn a() {}\n")
+        );
+    }
+}
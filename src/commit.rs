@@ -1,14 +1,52 @@
 //! Commit operations for generating conventional commit messages and executing git commits
 
-use crate::prompt::create_commit_prompt;
-use crate::providers::AIProvider;
-use crate::types::{CommittorError, ConventionalCommit};
+use crate::eval::similarity;
+use crate::feedback::Rating;
+use crate::filters::FilterPipeline;
+use crate::history::RecentHistory;
+use crate::i18n::Text;
+use crate::output::{item_label, separator, success_mark, warning_mark};
+use crate::paths::git_state_path;
+use crate::prompt::{create_commit_prompt_with_context, create_stash_prompt, CommitContext};
+use crate::providers::{provider_error, AIProvider, InterruptedResponse};
+use crate::ranking::rank_messages;
+use crate::rules::{ConventionalFormat, RuleSet, SubjectLength};
+use crate::secrets::detect_secrets;
+use crate::split::parse_selection;
+use crate::theme::{paint, paint_type, Element, OTHER_TYPE};
+use crate::timing;
+use crate::trailers::{clean_message_file, CleanupMode, MessageCleanup};
+use crate::types::{CommitType, CommittorError, ConventionalCommit, HeadState};
+use crate::validate;
+use crate::vendored::VendoredFile;
+use crate::vocabulary::Misspelling;
 use anyhow::{Context, Result};
 use colored::*;
+use futures::stream::{FuturesUnordered, StreamExt};
+use git2::Repository;
 use std::io::{self, Write};
 use std::process::Command;
+use std::sync::OnceLock;
 use std::time::Instant;
-use tracing::{info, warn};
+use tracing::{info, info_span, warn, Instrument};
+
+/// Minimum number of commits on a branch before they are used as prompt context
+const BRANCH_HISTORY_MIN_COMMITS: usize = 3;
+
+/// Maximum number of branch commit subjects included in the prompt
+const BRANCH_HISTORY_MAX_COMMITS: usize = 10;
+
+/// Branches tried, in order, as the base a feature branch diverged from
+const BASE_BRANCH_CANDIDATES: &[&str] = &[
+    "origin/HEAD",
+    "origin/main",
+    "origin/master",
+    "main",
+    "master",
+];
+
+/// Similarity to the previous subject at which a suggestion is flagged as a near-duplicate
+pub const PREVIOUS_SUBJECT_SIMILARITY_THRESHOLD: f64 = 0.9;
 
 /// Generate commit messages using AI
 pub async fn generate_commit_messages(
@@ -16,158 +54,1071 @@ pub async fn generate_commit_messages(
     provider: &dyn AIProvider,
     count: u8,
 ) -> Result<Vec<String>> {
+    generate_commit_messages_with_context(
+        diff,
+        provider,
+        count,
+        &CommitContext::default(),
+        &FilterPipeline::default(),
+    )
+    .await
+}
+
+/// Generate commit messages using AI with extra prompt context, passing them through the filters
+pub async fn generate_commit_messages_with_context(
+    diff: &str,
+    provider: &dyn AIProvider,
+    count: u8,
+    context: &CommitContext,
+    filters: &FilterPipeline,
+) -> Result<Vec<String>> {
+    let prompt = create_commit_prompt_with_context(diff, context);
+    generate_commit_messages_from_prompt(&prompt, diff, provider, count, filters).await
+}
+
+/// Generate commit messages from a prepared prompt
+///
+/// The diff never leaves the machine here; it is only used to rank the results.
+pub async fn generate_commit_messages_from_prompt(
+    prompt: &str,
+    diff: &str,
+    provider: &dyn AIProvider,
+    count: u8,
+    filters: &FilterPipeline,
+) -> Result<Vec<String>> {
+    let mut messages = Vec::new();
+    generate_messages_concurrently(prompt, provider, count, filters, |message| {
+        messages.push(message)
+    })
+    .await?;
+
+    info_span!(timing::RANKING).in_scope(|| rank_messages(&mut messages, diff));
+    Ok(messages)
+}
+
+/// Generate distinct valid messages with up to `count` requests in flight, passing each to `accept` as it arrives
+///
+/// Rejected output is retried until `count` messages were accepted or `count * 2`
+/// requests were made. Fails when no message was accepted at all.
+pub async fn generate_messages_concurrently(
+    prompt: &str,
+    provider: &dyn AIProvider,
+    count: u8,
+    filters: &FilterPipeline,
+    mut accept: impl FnMut(String),
+) -> Result<()> {
     info!(
         "Generating commit messages using provider: {}",
         provider.provider_name()
     );
 
     let start_time = Instant::now();
-    let prompt = create_commit_prompt(diff);
+    let count = count as usize;
+    let max_attempts = count * 2; // Allow more attempts than requested count
 
-    let mut messages = Vec::new();
+    let request = |attempt: usize| async move {
+        let response = provider
+            .generate_message(prompt)
+            .instrument(info_span!(timing::PROVIDER_CALL))
+            .await;
+        (attempt, response)
+    };
+    let mut in_flight = FuturesUnordered::new();
     let mut attempts = 0;
-    let max_attempts = count as usize * 2; // Allow more attempts than requested count
-
-    while messages.len() < count as usize && attempts < max_attempts {
+    while attempts < count {
         attempts += 1;
+        in_flight.push(request(attempts));
+    }
 
-        match provider.generate_message(&prompt).await {
+    let mut messages: Vec<String> = Vec::new();
+    let mut refusal = None;
+    let mut completed = 0;
+    let mut rejections = Vec::new();
+    let mut empty = 0;
+    while let Some((attempt, response)) = in_flight.next().await {
+        completed += 1;
+        let _validation = info_span!(timing::VALIDATION).entered();
+        match response {
             Ok(response) => {
-                let message = response.trim().to_string();
-                if !message.is_empty() && is_valid_commit_message(&message) {
-                    // Avoid duplicates
-                    if !messages.contains(&message) {
-                        messages.push(message);
+                // Asking again rarely changes a model's mind, so stop at the first refusal
+                if is_refusal(&response) {
+                    warn!("Model refused on attempt {}: {}", attempt, response);
+                    refusal = Some(response);
+                    break;
+                }
+                if response.trim().is_empty() {
+                    warn!("Empty response on attempt {}", attempt);
+                    empty += 1;
+                }
+                match accept_response(&response, filters, attempt) {
+                    Ok(message) if !messages.contains(&message) => {
+                        messages.push(message.clone());
+                        accept(message);
                     }
+                    Ok(_) => {}
+                    Err(reason) => rejections.push(Rejection {
+                        attempt,
+                        output: response,
+                        reason,
+                    }),
                 }
             }
             Err(e) => {
                 warn!(
                     "Failed to generate commit message (attempt {}): {}",
-                    attempts, e
+                    attempt, e
                 );
-                if attempts == 1 {
-                    // If first attempt fails, return the error
-                    return Err(CommittorError::AIProviderError(e.to_string()).into());
+                if let Some(interrupted) = e.downcast_ref::<InterruptedResponse>() {
+                    // Keep a whole subject that arrived before the cut; otherwise this
+                    // candidate alone is requested again below
+                    match salvage_subject(&interrupted.partial, filters, attempt) {
+                        Some(message) if !messages.contains(&message) => {
+                            messages.push(message.clone());
+                            accept(message);
+                        }
+                        _ => {}
+                    }
+                } else if let Some(CommittorError::OllamaUnavailable(_)) = e.downcast_ref() {
+                    return Err(e);
+                } else if completed == 1 {
+                    // If the first response is an error, return it
+                    return Err(provider_error(e));
                 }
-                // For subsequent attempts, just continue trying
+                // For later responses, just continue trying
             }
         }
+
+        if messages.len() >= count {
+            break;
+        }
+        if attempts < max_attempts && messages.len() + in_flight.len() < count {
+            attempts += 1;
+            in_flight.push(request(attempts));
+        }
     }
 
-    let generation_time = start_time.elapsed();
     info!(
         "Generated {} messages in {:?}",
         messages.len(),
-        generation_time
+        start_time.elapsed()
     );
 
     if messages.is_empty() {
+        if let Some(refusal) = refusal {
+            return Err(CommittorError::ModelRefusal(refusal_excerpt(&refusal)).into());
+        }
+        if explains_failures() {
+            display_rejections(&rejections);
+        }
+        let rejected = rejections.len();
+        if empty > 0 && empty == rejected {
+            return Err(CommittorError::AIProviderError(format!(
+                "the model returned {empty} empty responses"
+            ))
+            .into());
+        }
+        if rejected > 0 {
+            return Err(CommittorError::ValidationFailed(format!(
+                "{rejected} of {completed} generated messages broke the commit message rules"
+            ))
+            .into());
+        }
         return Err(CommittorError::AIProviderError(
             "Failed to generate any valid commit messages".to_string(),
         )
         .into());
     }
+    Ok(())
+}
 
+/// Turn replies to a prompt run elsewhere into distinct valid messages, for `import-response`
+///
+/// Each reply is checked as a provider's would be, and no usable reply fails the same way.
+pub fn accept_responses(responses: &[String], filters: &FilterPipeline) -> Result<Vec<String>> {
+    let mut messages: Vec<String> = Vec::new();
+    let mut refusal = None;
+    let mut rejections = Vec::new();
+    for (i, response) in responses.iter().enumerate() {
+        if is_refusal(response) {
+            refusal.get_or_insert(response);
+            continue;
+        }
+        match accept_response(response, filters, i + 1) {
+            Ok(message) if !messages.contains(&message) => messages.push(message),
+            Ok(_) => {}
+            Err(reason) => rejections.push(Rejection {
+                attempt: i + 1,
+                output: response.clone(),
+                reason,
+            }),
+        }
+    }
+
+    if messages.is_empty() {
+        if let Some(refusal) = refusal {
+            return Err(CommittorError::ModelRefusal(refusal_excerpt(refusal)).into());
+        }
+        if explains_failures() {
+            display_rejections(&rejections);
+        }
+        return Err(CommittorError::ValidationFailed(format!(
+            "none of the {} replies is a valid commit message",
+            responses.len()
+        ))
+        .into());
+    }
     Ok(messages)
 }
 
-/// Validate if a commit message follows conventional commit format
-pub fn is_valid_commit_message(message: &str) -> bool {
-    // Basic validation for conventional commit format
+/// Turn a model response into a commit message, or say why it has to be discarded
+fn accept_response(
+    response: &str,
+    filters: &FilterPipeline,
+    attempt: usize,
+) -> Result<String, String> {
+    if response.trim().is_empty() {
+        return Err("the response is empty".to_string());
+    }
+    let message = filters.run(response.trim()).map_err(|filter| {
+        warn!(
+            "Rejected commit message (attempt {}): {}",
+            attempt, response
+        );
+        format!("discarded by the {filter} filter")
+    })?;
+    // Models occasionally echo a secret from the diff back into the message
+    let secrets = detect_secrets(&message);
+    if !secrets.is_empty() {
+        warn!(
+            "Discarded commit message containing a secret ({}) on attempt {}",
+            secrets.join(", "),
+            attempt
+        );
+        return Err(format!("contains a secret ({})", secrets.join(", ")));
+    }
+    let subject = message.lines().next().unwrap_or_default();
+    let violations = subject_rules().check(subject);
+    if !violations.is_empty() {
+        let reasons: Vec<String> = violations.iter().map(ToString::to_string).collect();
+        return Err(reasons.join("; "));
+    }
+    Ok(message)
+}
+
+/// A model output that was discarded, and why
+#[derive(Debug, Clone)]
+struct Rejection {
+    attempt: usize,
+    output: String,
+    reason: String,
+}
+
+/// Most rejected outputs `--verbose` shows when no message was accepted
+const EXPLAINED_REJECTIONS: usize = 3;
+
+static EXPLAIN_FAILURES: OnceLock<bool> = OnceLock::new();
+
+/// Show the rejected outputs when generation fails, for the rest of the process; only the first call has any effect
+pub fn set_explain_failures(explain: bool) {
+    let _ = EXPLAIN_FAILURES.set(explain);
+}
+
+fn explains_failures() -> bool {
+    EXPLAIN_FAILURES.get().copied().unwrap_or(false)
+}
+
+/// Print the last few rejected outputs with the reason each was discarded, on stderr
+fn display_rejections(rejections: &[Rejection]) {
+    if rejections.is_empty() {
+        return;
+    }
+    let shown = &rejections[rejections.len().saturating_sub(EXPLAINED_REJECTIONS)..];
+    eprintln!(
+        "{}",
+        paint(
+            &format!(
+                "Rejected model output (last {} of {}):",
+                shown.len(),
+                rejections.len()
+            ),
+            Element::Warning
+        )
+    );
+    for rejection in shown {
+        let output = rejection.output.trim();
+        let output = if output.is_empty() {
+            "(empty)".to_string()
+        } else {
+            output.lines().collect::<Vec<_>>().join("\n    ")
+        };
+        eprintln!("  attempt {}: {}", rejection.attempt, output);
+        eprintln!(
+            "    {}",
+            paint(&format!("rejected: {}", rejection.reason), Element::Hint)
+        );
+    }
+}
+
+/// Get a valid subject from a response cut off mid-stream, if its first line arrived whole
+///
+/// The unfinished last line is dropped, and so is any body, since it may be missing lines.
+fn salvage_subject(partial: &str, filters: &FilterPipeline, attempt: usize) -> Option<String> {
+    let (complete, _) = partial.trim_start().rsplit_once('\n')?;
+    let message = accept_response(complete, filters, attempt).ok()?;
+    message.lines().next().map(str::to_string)
+}
+
+/// Generate a descriptive stash message for work-in-progress changes
+pub async fn generate_stash_message(diff: &str, provider: &dyn AIProvider) -> Result<String> {
+    let prompt = create_stash_prompt(diff);
+    let response = provider
+        .generate_message(&prompt)
+        .await
+        .map_err(provider_error)?;
+
+    let message = clean_single_line_response(&response);
+    if message.is_empty() {
+        return Err(CommittorError::AIProviderError(
+            "Failed to generate a stash message".to_string(),
+        )
+        .into());
+    }
+
+    Ok(message)
+}
+
+/// Reduce a model response to its first meaningful line without surrounding quotes
+fn clean_single_line_response(response: &str) -> String {
+    response
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or("")
+        .trim_matches(|c| c == '"' || c == '\'' || c == '`')
+        .trim_end_matches('.')
+        .trim()
+        .to_string()
+}
+
+/// Check whether a response is the model declining the request rather than a message
+pub fn is_refusal(response: &str) -> bool {
     let regex = regex::Regex::new(
-        r"^(feat|fix|docs|style|refactor|test|chore|perf|ci|build)(\(.+\))?: .+$",
+        r"(?i)^\W*(i'?m sorry|i am sorry|sorry, (but )?i|i apologi[sz]e|i can(no|')t|i'?m (unable|not able)|i am (unable|not able)|i won'?t|as an ai\b|unfortunately,? i)",
     )
     .unwrap();
-    regex.is_match(message) && message.len() <= 72
+    let policy = regex::Regex::new(r"(?i)\b(content|usage|safety) polic(y|ies)\b").unwrap();
+
+    let response = response.trim();
+    if is_valid_commit_message(response.lines().next().unwrap_or_default()) {
+        return false;
+    }
+    regex.is_match(response) || policy.is_match(response)
+}
+
+/// First sentence of a refusal, short enough for an error message
+fn refusal_excerpt(refusal: &str) -> String {
+    let refusal = refusal.trim();
+    let sentence = refusal
+        .find(['.', '!', '\n'])
+        .map_or(refusal, |end| &refusal[..=end])
+        .trim();
+    match sentence.char_indices().nth(160) {
+        Some((end, _)) => format!("{}...", &sentence[..end]),
+        None => sentence.to_string(),
+    }
+}
+
+/// Validate if a commit message follows conventional commit format
+///
+/// The subject may carry a `!` breaking marker and is limited to 72 characters, not bytes.
+pub fn is_valid_commit_message(message: &str) -> bool {
+    subject_rules().is_valid(message)
+}
+
+/// Rules every generated subject is held to, whatever the configuration
+fn subject_rules() -> RuleSet {
+    RuleSet::new().with(ConventionalFormat).with(SubjectLength {
+        max: validate::MAX_SUBJECT_LENGTH,
+    })
 }
 
 /// Parse a commit message into a ConventionalCommit struct
 pub fn parse_commit_message(message: &str) -> Result<ConventionalCommit> {
-    let regex = regex::Regex::new(
-        r"^(feat|fix|docs|style|refactor|test|chore|perf|ci|build)(\(([^)]+)\))?(!)?: (.+)$",
-    )
-    .unwrap();
+    let header = validate::parse_header(message).map_err(|e| {
+        CommittorError::InvalidCommitFormat(format!("Invalid conventional commit format: {e}"))
+    })?;
 
-    if let Some(captures) = regex.captures(message) {
-        let commit_type = match captures.get(1).unwrap().as_str() {
-            "feat" => crate::types::CommitType::Feat,
-            "fix" => crate::types::CommitType::Fix,
-            "docs" => crate::types::CommitType::Docs,
-            "style" => crate::types::CommitType::Style,
-            "refactor" => crate::types::CommitType::Refactor,
-            "test" => crate::types::CommitType::Test,
-            "chore" => crate::types::CommitType::Chore,
-            "perf" => crate::types::CommitType::Perf,
-            "ci" => crate::types::CommitType::Ci,
-            "build" => crate::types::CommitType::Build,
-            _ => {
-                return Err(
-                    CommittorError::InvalidCommitFormat("Unknown commit type".to_string()).into(),
-                )
-            }
-        };
+    let commit_type: CommitType = header.commit_type.parse()?;
+    let mut commit = ConventionalCommit::new(commit_type, header.description.to_string());
+    if let Some(scope) = header.scope {
+        commit = commit.with_scope(scope.to_string());
+    }
+    if header.breaking {
+        commit = commit.with_breaking();
+    }
 
-        let scope = captures.get(3).map(|m| m.as_str().to_string());
-        let breaking = captures.get(4).is_some();
-        let description = captures.get(5).unwrap().as_str().to_string();
+    Ok(commit)
+}
+
+/// Display commit message options to the user
+pub fn display_commit_options(messages: &[String]) {
+    display_commit_options_with_previous(messages, None);
+}
+
+/// Display commit message options, flagging those that repeat the previous subject
+pub fn display_commit_options_with_previous(messages: &[String], previous_subject: Option<&str>) {
+    display_commit_options_with_details(
+        messages,
+        previous_subject,
+        &RecentHistory::default(),
+        false,
+        false,
+    );
+}
 
-        let mut commit = ConventionalCommit::new(commit_type, description);
-        if let Some(scope) = scope {
-            commit = commit.with_scope(scope);
+/// Display commit message options, with each type's meaning under it when `verbose`
+///
+/// In verbose mode, options that disagree on the commit type are called out, so the
+/// choice between e.g. `fix` and `refactor` is made knowingly. Bodies are indented
+/// under their subject, or reduced to a line count when `collapsed`, for the picker
+/// to show one at a time with [`display_option_preview`]. Options repeating a subject
+/// of the recent history are marked.
+pub fn display_commit_options_with_details(
+    messages: &[String],
+    previous_subject: Option<&str>,
+    history: &RecentHistory,
+    verbose: bool,
+    collapsed: bool,
+) {
+    println!(
+        "{}",
+        paint(Text::GeneratedOptions.text(), Element::Heading).bold()
+    );
+    println!();
+
+    let types = option_types(messages);
+    let disagree = verbose && types_disagree(&types);
+    if disagree {
+        let names: Vec<String> = distinct_types(&types)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        let warning = format!(
+            "{} The options disagree on the type: {}",
+            warning_mark(),
+            names.join(", ")
+        );
+        println!("{}", paint(&warning, Element::Warning).bold());
+        println!();
+    }
+
+    if messages.len() >= GROUP_BY_TYPE_MIN_OPTIONS {
+        for (commit_type, indices) in group_by_type(&types) {
+            let heading = commit_type
+                .as_ref()
+                .map_or_else(|| OTHER_TYPE.to_string(), ToString::to_string);
+            println!("{}", paint_type(&heading, commit_type.as_ref()).bold());
+            for i in indices {
+                display_option(
+                    i,
+                    messages,
+                    &types,
+                    option_note(&messages[i], previous_subject, history),
+                    verbose,
+                    disagree,
+                    collapsed,
+                );
+            }
         }
-        if breaking {
-            commit = commit.with_breaking();
+    } else {
+        for i in 0..messages.len() {
+            display_option(
+                i,
+                messages,
+                &types,
+                option_note(&messages[i], previous_subject, history),
+                verbose,
+                disagree,
+                collapsed,
+            );
         }
+    }
+    println!();
+}
 
-        Ok(commit)
+/// Get the warning shown after an option that repeats the recent history, if any
+fn option_note(
+    message: &str,
+    previous_subject: Option<&str>,
+    history: &RecentHistory,
+) -> Option<Text> {
+    if history.repeats(message) {
+        Some(Text::RepeatsRecentCommit)
+    } else if previous_subject.is_some_and(|previous| is_near_duplicate(message, previous)) {
+        Some(Text::SimilarToPrevious)
     } else {
-        Err(
-            CommittorError::InvalidCommitFormat("Invalid conventional commit format".to_string())
-                .into(),
-        )
+        None
     }
 }
 
-/// Display commit message options to the user
-pub fn display_commit_options(messages: &[String]) {
-    println!("{}", "Generated commit message options:".green().bold());
+/// Show one numbered option, its body and, when `verbose`, its type's meaning
+fn display_option(
+    i: usize,
+    messages: &[String],
+    types: &[Option<CommitType>],
+    note: Option<Text>,
+    verbose: bool,
+    disagree: bool,
+    collapsed: bool,
+) {
+    let message = &messages[i];
+    let (subject, body) = split_body(message);
+    if let Some(note) = note {
+        println!(
+            "{} {} {}",
+            paint(&item_label(i + 1), Element::Number).bold(),
+            subject,
+            paint(note.text(), Element::Warning)
+        );
+    } else {
+        println!(
+            "{} {}",
+            paint(&item_label(i + 1), Element::Number).bold(),
+            subject
+        );
+    }
+
+    if !body.is_empty() {
+        if collapsed {
+            let hint = Text::CollapsedBody.format(&[&body.len(), &(i + 1)]);
+            println!("   {}", paint(&hint, Element::Hint));
+        } else {
+            for line in &body {
+                println!("   {line}");
+            }
+        }
+    }
+
+    if let (true, Some(commit_type)) = (verbose, &types[i]) {
+        let meaning = format!("   {commit_type}: {}", commit_type.description());
+        if disagree {
+            println!("{}", paint(&meaning, Element::Warning));
+        } else {
+            println!("{}", paint(&meaning, Element::Hint));
+        }
+    }
+}
+
+/// Options from this many on are shown grouped by commit type
+pub const GROUP_BY_TYPE_MIN_OPTIONS: usize = 5;
+
+/// Group option indices by commit type, in the order each type first appears
+///
+/// Options keep their numbers, so what is picked is unchanged by the grouping;
+/// options whose subject doesn't parse come last.
+pub fn group_by_type(types: &[Option<CommitType>]) -> Vec<(Option<CommitType>, Vec<usize>)> {
+    let mut groups: Vec<(Option<CommitType>, Vec<usize>)> = Vec::new();
+    for commit_type in distinct_types(types).into_iter().map(Some).chain([None]) {
+        let indices: Vec<usize> = (0..types.len())
+            .filter(|&i| types[i] == commit_type)
+            .collect();
+        if !indices.is_empty() {
+            groups.push((commit_type, indices));
+        }
+    }
+    groups
+}
+
+/// Show one option in full, subject and body, between separators
+pub fn display_option_preview(message: &str, n: usize) {
+    let separator = separator();
     println!();
+    println!("{}", paint(&item_label(n), Element::Number).bold());
+    if !separator.is_empty() {
+        println!("{}", paint(&separator, Element::Hint));
+    }
+    println!("{message}");
+    if !separator.is_empty() {
+        println!("{}", paint(&separator, Element::Hint));
+    }
+    println!();
+}
+
+/// Split a message into its subject and the body lines below the blank line after it
+fn split_body(message: &str) -> (&str, Vec<&str>) {
+    let mut lines = message.lines();
+    let subject = lines.next().unwrap_or_default();
+    let body: Vec<&str> = lines.skip_while(|line| line.trim().is_empty()).collect();
+    (subject, body)
+}
+
+/// Check whether any option has a body below its subject
+pub fn has_bodies(messages: &[String]) -> bool {
+    messages
+        .iter()
+        .any(|message| !split_body(message).1.is_empty())
+}
+
+/// Get the commit type of each option's subject, if it parses
+pub fn option_types(messages: &[String]) -> Vec<Option<CommitType>> {
+    messages
+        .iter()
+        .map(|message| {
+            let subject = message.lines().next().unwrap_or_default();
+            parse_commit_message(subject.trim())
+                .ok()
+                .map(|commit| commit.commit_type)
+        })
+        .collect()
+}
 
-    for (i, message) in messages.iter().enumerate() {
-        println!("{} {}", format!("{}.", i + 1).cyan().bold(), message);
+/// Check whether the options were given more than one commit type
+pub fn types_disagree(types: &[Option<CommitType>]) -> bool {
+    distinct_types(types).len() > 1
+}
+
+/// Get the distinct commit types in the order they first appear
+fn distinct_types(types: &[Option<CommitType>]) -> Vec<CommitType> {
+    let mut distinct: Vec<CommitType> = Vec::new();
+    for commit_type in types.iter().flatten() {
+        if !distinct.contains(commit_type) {
+            distinct.push(commit_type.clone());
+        }
+    }
+    distinct
+}
+
+/// Display likely misspellings of repository terms under the numbered options
+pub fn display_spelling_warnings(warnings: &[Vec<Misspelling>]) {
+    let mut any = false;
+    for (i, misspellings) in warnings.iter().enumerate() {
+        for misspelling in misspellings {
+            println!(
+                "{} {}",
+                paint(
+                    &format!("{} {}", warning_mark(), item_label(i + 1)),
+                    Element::Warning
+                )
+                .bold(),
+                paint(&misspelling.to_string(), Element::Warning)
+            );
+            any = true;
+        }
+    }
+    if any {
+        println!();
+    }
+}
+
+/// Warn about changed files that look vendored or minified, printing nothing when there are none
+pub fn display_vendored_warning(files: &[VendoredFile]) {
+    if files.is_empty() {
+        return;
+    }
+
+    println!(
+        "{} {}",
+        paint(warning_mark(), Element::Warning).bold(),
+        paint(Text::VendoredFilesWarning.text(), Element::Warning)
+    );
+    for file in files {
+        println!("  {}", paint(&file.to_string(), Element::Warning));
     }
     println!();
 }
 
-/// Prompt user to choose a commit message
-pub fn prompt_user_choice(count: usize) -> Result<Option<usize>> {
-    print!(
-        "{}",
-        format!("Choose an option (1-{count}, or 'q' to quit): ").yellow()
+/// Check whether a message's subject is nearly the same as a previous subject
+pub fn is_near_duplicate(message: &str, previous_subject: &str) -> bool {
+    let subject = message.lines().next().unwrap_or("");
+    similarity(subject, previous_subject) >= PREVIOUS_SUBJECT_SIMILARITY_THRESHOLD
+}
+
+/// A picked option, optionally with one of the suggested scopes swapped in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Choice {
+    pub index: usize,
+    pub scope: Option<usize>,
+}
+
+/// Keys used to pick a scope variant; `c`, `e`, `q` and `r` are left free for picker actions
+const SCOPE_KEYS: &[char] = &['a', 'b', 'd', 'f', 'g', 'h', 'i', 'j'];
+
+/// Maximum number of scope variants offered in the picker
+pub const MAX_SCOPE_SUGGESTIONS: usize = SCOPE_KEYS.len();
+
+/// Display the scope candidates that can be swapped into any option
+pub fn display_scope_options(scopes: &[String]) {
+    if scopes.is_empty() {
+        return;
+    }
+
+    let keys = scopes
+        .iter()
+        .zip(SCOPE_KEYS)
+        .map(|(scope, key)| {
+            format!(
+                "{} {scope}",
+                paint(&format!("{key})"), Element::Number).bold()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("  ");
+    println!("{} {keys}", paint("Scopes:", Element::Heading).bold());
+    println!();
+}
+
+/// Replace the scope of a conventional message, leaving other messages unchanged
+pub fn with_scope(message: &str, scope: &str) -> String {
+    let (subject, rest) = match message.split_once('\n') {
+        Some((subject, rest)) => (subject, Some(rest)),
+        None => (message, None),
+    };
+
+    let Ok(mut commit) = parse_commit_message(subject) else {
+        return message.to_string();
+    };
+    commit.scope = Some(scope.to_string());
+
+    match rest {
+        Some(rest) => format!("{commit}\n{rest}"),
+        None => commit.to_string(),
+    }
+}
+
+/// Parse picker input such as `2`, `2b` (option 2 with scope b) or `b` (option 1 with scope b)
+pub fn parse_choice(input: &str, count: usize, scope_count: usize) -> Option<Choice> {
+    let input = input.trim().to_lowercase();
+    let digits: String = input.chars().take_while(|c| c.is_ascii_digit()).collect();
+    let suffix = &input[digits.len()..];
+
+    let index = if digits.is_empty() {
+        0
+    } else {
+        match digits.parse::<usize>() {
+            Ok(n) if n >= 1 && n <= count => n - 1,
+            _ => return None,
+        }
+    };
+
+    let scope = match suffix.chars().collect::<Vec<_>>()[..] {
+        [] if digits.is_empty() => return None,
+        [] => None,
+        [key] => match SCOPE_KEYS[..scope_count.min(SCOPE_KEYS.len())]
+            .iter()
+            .position(|k| *k == key)
+        {
+            Some(position) => Some(position),
+            None => return None,
+        },
+        _ => return None,
+    };
+
+    Some(Choice { index, scope })
+}
+
+/// What the user asked for at the message picker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PickerAction {
+    /// Commit an option, optionally with a scope swapped in
+    Commit(Choice),
+    /// Open an option in the editor before committing it
+    Edit(usize),
+    /// Show an option's full message, body included
+    View(usize),
+    /// Rate an option thumbs up or down in the feedback log
+    Rate(usize, Rating),
+    /// Ask the model for a fresh set of options
+    Regenerate,
+    /// Wait for the options still being generated
+    More,
+    /// Choose staged files to leave out of the context, then regenerate
+    Exclude,
+    /// Leave without committing
+    Cancel,
+}
+
+/// What to do with a chosen message when the staged changes moved on since it was generated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StaleAction {
+    /// Generate new options for the changes staged now
+    Regenerate,
+    /// Commit the chosen message with the changes staged now
+    CommitAnyway,
+    /// Leave without committing
+    Cancel,
+}
+
+/// Parse the answer to the stale changes prompt; an empty answer regenerates
+pub fn parse_stale_action(input: &str) -> Option<StaleAction> {
+    match input.trim().to_lowercase().as_str() {
+        "" | "r" | "regenerate" => Some(StaleAction::Regenerate),
+        "a" | "anyway" => Some(StaleAction::CommitAnyway),
+        "q" | "quit" | "c" | "cancel" => Some(StaleAction::Cancel),
+        _ => None,
+    }
+}
+
+/// Warn that the staged changes moved on and ask what to do; end of input cancels
+pub fn prompt_stale_action() -> Result<StaleAction> {
+    println!(
+        "{} {}",
+        paint(warning_mark(), Element::Warning).bold(),
+        paint(Text::StagedChangesChanged.text(), Element::Warning)
     );
-    io::stdout().flush()?;
+    loop {
+        print!("{}", paint(Text::StalePrompt.text(), Element::Warning));
+        io::stdout().flush()?;
 
+        let Some(input) = read_input()? else {
+            println!();
+            return Ok(StaleAction::Cancel);
+        };
+        match parse_stale_action(&input) {
+            Some(action) => return Ok(action),
+            None => println!("{}", paint(Text::InvalidChoice.text(), Element::Error)),
+        }
+    }
+}
+
+/// Parse picker input: a choice such as `2b`, `e`/`e2` to edit, `v`/`v2` to view,
+/// `+2`/`-2` to rate, `r` to regenerate, `m` for more, `x` to leave files out or `c`/`q` to cancel
+pub fn parse_picker_action(input: &str, count: usize, scope_count: usize) -> Option<PickerAction> {
+    let input = input.trim().to_lowercase();
+    match input.as_str() {
+        "c" | "cancel" | "q" | "quit" => return Some(PickerAction::Cancel),
+        "r" | "regenerate" => return Some(PickerAction::Regenerate),
+        "m" | "more" => return Some(PickerAction::More),
+        "x" | "exclude" => return Some(PickerAction::Exclude),
+        "e" | "edit" => return Some(PickerAction::Edit(0)),
+        "v" | "view" => return Some(PickerAction::View(0)),
+        _ => {}
+    }
+
+    if let Some(number) = input.strip_prefix('e') {
+        return option_index(number, count).map(PickerAction::Edit);
+    }
+    if let Some(number) = input.strip_prefix('v') {
+        return option_index(number, count).map(PickerAction::View);
+    }
+    if let Some(number) = input.strip_prefix('+') {
+        return option_index(number, count).map(|index| PickerAction::Rate(index, Rating::Up));
+    }
+    if let Some(number) = input.strip_prefix('-') {
+        return option_index(number, count).map(|index| PickerAction::Rate(index, Rating::Down));
+    }
+
+    parse_choice(&input, count, scope_count).map(PickerAction::Commit)
+}
+
+/// Parse a 1-based option number into an index
+fn option_index(number: &str, count: usize) -> Option<usize> {
+    match number.trim().parse::<usize>() {
+        Ok(n) if n >= 1 && n <= count => Some(n - 1),
+        _ => None,
+    }
+}
+
+/// Read a line of user input, returning `None` at end of input
+///
+/// Reads interrupted by a signal are retried, so a stray signal doesn't abort the prompt.
+pub fn read_input() -> Result<Option<String>> {
     let mut input = String::new();
-    io::stdin().read_line(&mut input)?;
-    let input = input.trim();
+    loop {
+        match io::stdin().read_line(&mut input) {
+            Ok(0) => return Ok(None),
+            Ok(_) => return Ok(Some(input.trim().to_string())),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Prompt the user to pick, edit, view or regenerate a message; end of input cancels
+///
+/// Ratings are only accepted with `feedback`, when there is somewhere to record them.
+pub fn prompt_picker_action(
+    count: usize,
+    scope_count: usize,
+    has_bodies: bool,
+    feedback: bool,
+) -> Result<PickerAction> {
+    let scope_hint = if scope_count > 0 {
+        Text::PickerScopeHint.text()
+    } else {
+        ""
+    };
+    let view_hint = if has_bodies {
+        Text::PickerViewHint.text()
+    } else {
+        ""
+    };
+    let feedback_hint = if feedback {
+        Text::PickerFeedbackHint.text()
+    } else {
+        ""
+    };
+    let hint =
+        Text::PickerPrompt.format(&[&count, &format!("{scope_hint}{view_hint}{feedback_hint}")]);
+
+    loop {
+        print!("{}", paint(&hint, Element::Warning));
+        io::stdout().flush()?;
+
+        let Some(input) = read_input()? else {
+            println!();
+            return Ok(PickerAction::Cancel);
+        };
+        if input.is_empty() {
+            continue;
+        }
+
+        match parse_picker_action(&input, count, scope_count) {
+            Some(PickerAction::Rate(..)) if !feedback => {
+                println!("{}", paint(Text::InvalidChoice.text(), Element::Error))
+            }
+            Some(action) => return Ok(action),
+            None => println!("{}", paint(Text::InvalidChoice.text(), Element::Error)),
+        }
+    }
+}
+
+/// Toggle the files whose numbers were given in and out of the excluded set
+///
+/// Returns `None` when that would leave no file in the context.
+pub fn toggle_excluded(
+    paths: &[String],
+    excluded: &[String],
+    toggled: &[usize],
+) -> Option<Vec<String>> {
+    let mut excluded = excluded.to_vec();
+    for path in toggled.iter().filter_map(|&index| paths.get(index)) {
+        match excluded.iter().position(|excluded| excluded == path) {
+            Some(position) => {
+                excluded.remove(position);
+            }
+            None => excluded.push(path.clone()),
+        }
+    }
+    (paths.iter().any(|path| !excluded.contains(path))).then_some(excluded)
+}
+
+/// List the staged files and ask which to leave out of the context
+///
+/// Returns the new excluded set, or `None` when it is unchanged.
+pub fn prompt_excluded_files(paths: &[String], excluded: &[String]) -> Result<Option<Vec<String>>> {
+    println!();
+    for (i, path) in paths.iter().enumerate() {
+        if excluded.contains(path) {
+            println!(
+                "{} {}",
+                paint(&item_label(i + 1), Element::Hint),
+                paint(path, Element::Hint).strikethrough()
+            );
+        } else {
+            println!(
+                "{} {path}",
+                paint(&item_label(i + 1), Element::Number).bold()
+            );
+        }
+    }
+
+    loop {
+        print!(
+            "{}",
+            paint(Text::ExcludeFilesPrompt.text(), Element::Warning)
+        );
+        io::stdout().flush()?;
+
+        let Some(input) = read_input()? else {
+            println!();
+            return Ok(None);
+        };
+        if input.is_empty() {
+            return Ok(None);
+        }
 
-    if input.eq_ignore_ascii_case("q") || input.eq_ignore_ascii_case("quit") {
-        return Ok(None);
+        let Some(toggled) = parse_selection(&input, paths.len()) else {
+            println!("{}", paint(Text::InvalidSelection.text(), Element::Error));
+            continue;
+        };
+        match toggle_excluded(paths, excluded, &toggled) {
+            Some(updated) => return Ok(Some(updated)),
+            None => println!("{}", paint(Text::CannotExcludeAll.text(), Element::Error)),
+        }
     }
+}
+
+/// Prompt user to choose a commit message; end of input counts as quitting
+pub fn prompt_user_choice(count: usize) -> Result<Option<usize>> {
+    loop {
+        print!(
+            "{}",
+            paint(&Text::ChoosePrompt.format(&[&count]), Element::Warning)
+        );
+        io::stdout().flush()?;
 
-    match input.parse::<usize>() {
-        Ok(n) if n >= 1 && n <= count => Ok(Some(n - 1)),
-        _ => {
-            println!("{}", "Invalid choice. Please try again.".red());
-            prompt_user_choice(count)
+        let Some(input) = read_input()? else {
+            println!();
+            return Ok(None);
+        };
+        if input.eq_ignore_ascii_case("q") || input.eq_ignore_ascii_case("quit") {
+            return Ok(None);
         }
+
+        match input.parse::<usize>() {
+            Ok(n) if n >= 1 && n <= count => return Ok(Some(n - 1)),
+            _ => println!("{}", paint(Text::InvalidChoice.text(), Element::Error)),
+        }
+    }
+}
+
+/// Name of the file a message is edited in, kept in the git directory
+const EDIT_MESSAGE_FILE_NAME: &str = "COMMITTOR_EDITMSG";
+
+/// Open a message in the user's git editor, returning the edited message or `None` if emptied
+pub fn edit_message(message: &str) -> Result<Option<String>> {
+    let repo = Repository::discover(".").context("Not in a git repository")?;
+    let path = git_state_path(&repo, EDIT_MESSAGE_FILE_NAME);
+    // Committor strips the file itself, with the comment character git would use
+    let cleanup = MessageCleanup {
+        mode: CleanupMode::Strip,
+        ..MessageCleanup::from_git(message)
+    };
+    let help = format!(
+        "Edit the message; lines starting with '{}' are ignored.",
+        cleanup.comment_char
+    );
+    std::fs::write(&path, format!("{message}\n\n{}\n", cleanup.comment(&help)))
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+
+    let editor = Command::new("git")
+        .args(["var", "GIT_EDITOR"])
+        .output()
+        .context("Failed to find the git editor")?;
+    let editor = String::from_utf8_lossy(&editor.stdout).trim().to_string();
+    if editor.is_empty() {
+        return Err(CommittorError::ConfigError("No editor is configured".to_string()).into());
+    }
+
+    // Run through the shell, as git does, so editors configured with arguments work
+    let status = Command::new("sh")
+        .args(["-c", &format!("{editor} \"$@\""), "sh"])
+        .arg(&path)
+        .status()
+        .context("Failed to launch the editor")?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("The editor exited with {status}"));
     }
+
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let _ = std::fs::remove_file(&path);
+    Ok(clean_message_file(&content, &cleanup))
 }
 
 /// Execute a git commit with the given message
 pub fn commit_with_message(message: &str) -> Result<()> {
-    println!("{}", format!("Committing with message: {message}").green());
+    println!("{}", Text::CommittingWith.format(&[&message]).green());
 
     let output = Command::new("git")
         .args(["commit", "-m", message])
@@ -175,7 +1126,12 @@ pub fn commit_with_message(message: &str) -> Result<()> {
         .context("Failed to execute git commit")?;
 
     if output.status.success() {
-        println!("{}", "✓ Commit successful!".green().bold());
+        println!(
+            "{}",
+            format!("{} {}", success_mark(), Text::CommitSuccessful.text())
+                .green()
+                .bold()
+        );
 
         // Show commit hash if available
         if let Ok(hash_output) = Command::new("git")
@@ -186,7 +1142,7 @@ pub fn commit_with_message(message: &str) -> Result<()> {
                 let hash = String::from_utf8_lossy(&hash_output.stdout)
                     .trim()
                     .to_string();
-                println!("{}", format!("Commit hash: {hash}").cyan());
+                println!("{}", Text::CommitHash.format(&[&hash]).cyan());
             }
         }
     } else {
@@ -197,6 +1153,29 @@ pub fn commit_with_message(message: &str) -> Result<()> {
     Ok(())
 }
 
+/// Stash the working tree changes with the given message
+pub fn stash_with_message(message: &str) -> Result<()> {
+    println!("{}", format!("Stashing with message: {message}").green());
+
+    let output = Command::new("git")
+        .args(["stash", "push", "-m", message])
+        .output()
+        .context("Failed to execute git stash")?;
+
+    if output.status.success() {
+        println!(
+            "{}",
+            format!("{} Changes stashed!", success_mark())
+                .green()
+                .bold()
+        );
+        Ok(())
+    } else {
+        let error = String::from_utf8_lossy(&output.stderr);
+        Err(CommittorError::GitError(error.to_string()).into())
+    }
+}
+
 /// Check if git is available and we're in a git repository
 pub fn validate_git_environment() -> Result<()> {
     // Check if git is available
@@ -222,21 +1201,109 @@ pub fn validate_git_environment() -> Result<()> {
     Ok(())
 }
 
-/// Get the current git branch name
-pub fn get_current_branch() -> Result<String> {
+/// Check whether the current repository is bare (has no working tree or index)
+pub fn is_bare_repository() -> Result<bool> {
     let output = Command::new("git")
-        .args(["branch", "--show-current"])
+        .args(["rev-parse", "--is-bare-repository"])
         .output()
-        .context("Failed to get current branch")?;
+        .context("Failed to check repository type")?;
 
     if output.status.success() {
-        let branch = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        Ok(branch)
+        Ok(String::from_utf8_lossy(&output.stdout).trim() == "true")
     } else {
-        Ok("HEAD".to_string()) // Fallback for detached HEAD
+        Err(CommittorError::GitRepoNotFound.into())
+    }
+}
+
+/// Get the current git branch name
+pub fn get_current_branch() -> Result<String> {
+    match get_head_state()? {
+        HeadState::Branch(name) => Ok(name),
+        HeadState::Detached(_) => Ok("HEAD".to_string()), // Fallback for detached HEAD
     }
 }
 
+/// Determine whether HEAD is on a branch or detached in the current repository
+pub fn get_head_state() -> Result<HeadState> {
+    let repo = Repository::open(".").context("Not in a git repository")?;
+    get_head_state_from_repo(&repo)
+}
+
+/// Determine whether HEAD is on a branch or detached in a specific repository
+pub fn get_head_state_from_repo(repo: &Repository) -> Result<HeadState> {
+    if repo.head_detached()? {
+        let commit = repo.head()?.peel_to_commit()?;
+        let short_id = commit.as_object().short_id()?;
+        return Ok(HeadState::Detached(
+            short_id.as_str().unwrap_or_default().to_string(),
+        ));
+    }
+
+    // An unborn branch has no commit yet but HEAD still names it
+    match repo.head() {
+        Ok(head) => Ok(HeadState::Branch(
+            head.shorthand().unwrap_or("HEAD").to_string(),
+        )),
+        Err(_) => {
+            let head = repo.find_reference("HEAD")?;
+            let target = head.symbolic_target().unwrap_or("HEAD");
+            Ok(HeadState::Branch(
+                target.trim_start_matches("refs/heads/").to_string(),
+            ))
+        }
+    }
+}
+
+/// Get the subjects of the current branch's own commits when it has diverged significantly
+pub fn get_branch_history() -> Result<Vec<String>> {
+    let repo = Repository::discover(".").context("Not in a git repository")?;
+    get_branch_history_from_repo(&repo)
+}
+
+/// Get the subjects of a repository branch's own commits, newest first
+///
+/// Returns nothing when HEAD is detached, no base branch can be found, or the
+/// branch has fewer than a handful of commits of its own.
+pub fn get_branch_history_from_repo(repo: &Repository) -> Result<Vec<String>> {
+    if repo.head_detached()? {
+        return Ok(Vec::new());
+    }
+    let Ok(head) = repo.head().and_then(|head| head.peel_to_commit()) else {
+        return Ok(Vec::new());
+    };
+
+    let Some(merge_base) = BASE_BRANCH_CANDIDATES.iter().find_map(|candidate| {
+        let base = repo
+            .revparse_single(candidate)
+            .ok()?
+            .peel_to_commit()
+            .ok()?;
+        repo.merge_base(head.id(), base.id()).ok()
+    }) else {
+        return Ok(Vec::new());
+    };
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(git2::Sort::TOPOLOGICAL)?;
+    revwalk.push(head.id())?;
+    revwalk.hide(merge_base)?;
+
+    let mut subjects = Vec::new();
+    for oid in revwalk {
+        let commit = repo.find_commit(oid?)?;
+        if commit.parent_count() <= 1 {
+            subjects.push(commit.summary().unwrap_or_default().to_string());
+        }
+    }
+
+    if subjects.len() < BRANCH_HISTORY_MIN_COMMITS {
+        return Ok(Vec::new());
+    }
+
+    subjects.truncate(BRANCH_HISTORY_MAX_COMMITS);
+    Ok(subjects)
+}
+
 /// Get the last commit message
 pub fn get_last_commit_message() -> Result<String> {
     let output = Command::new("git")
@@ -267,22 +1334,70 @@ pub fn has_uncommitted_changes() -> Result<bool> {
     }
 }
 
+/// Default branch prefixes and the commit types they imply
+pub fn default_branch_type_mappings() -> Vec<(String, CommitType)> {
+    [
+        ("feature/", CommitType::Feat),
+        ("feat/", CommitType::Feat),
+        ("fix/", CommitType::Fix),
+        ("bugfix/", CommitType::Fix),
+        ("hotfix/", CommitType::Fix),
+        ("docs/", CommitType::Docs),
+        ("style/", CommitType::Style),
+        ("refactor/", CommitType::Refactor),
+        ("test/", CommitType::Test),
+        ("chore/", CommitType::Chore),
+        ("perf/", CommitType::Perf),
+        ("ci/", CommitType::Ci),
+        ("build/", CommitType::Build),
+    ]
+    .into_iter()
+    .map(|(prefix, commit_type)| (prefix.to_string(), commit_type))
+    .collect()
+}
+
+/// Infer a commit type from a branch name, preferring the longest matching prefix
+pub fn infer_type_from_branch(
+    branch: &str,
+    mappings: &[(String, CommitType)],
+) -> Option<CommitType> {
+    mappings
+        .iter()
+        .filter(|(prefix, _)| branch.starts_with(prefix.as_str()))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, commit_type)| commit_type.clone())
+}
+
 /// Enhance commit message with additional context
 pub fn enhance_commit_message(message: &str, branch: &str) -> String {
-    let mut enhanced = message.to_string();
+    enhance_commit_message_with_mappings(message, branch, &default_branch_type_mappings())
+}
 
-    // Add branch context for feature branches
-    if branch.starts_with("feature/") || branch.starts_with("feat/") {
-        if !enhanced.starts_with("feat") {
-            enhanced = format!("feat: {}", enhanced.trim_start_matches("feat: "));
-        }
-    } else if (branch.starts_with("fix/") || branch.starts_with("bugfix/"))
-        && !enhanced.starts_with("fix")
-    {
-        enhanced = format!("fix: {}", enhanced.trim_start_matches("fix: "));
+/// Prefix a type inferred from the branch, but only when the message has no type yet
+pub fn enhance_commit_message_with_mappings(
+    message: &str,
+    branch: &str,
+    mappings: &[(String, CommitType)],
+) -> String {
+    // A detached HEAD carries no branch naming convention to infer a type from
+    if branch.is_empty() || branch == "HEAD" {
+        return message.to_string();
+    }
+
+    if parse_commit_message(message).is_ok() || has_type_prefix(message) {
+        return message.to_string();
     }
 
-    enhanced
+    match infer_type_from_branch(branch, mappings) {
+        Some(commit_type) => format!("{commit_type}: {}", message.trim()),
+        None => message.to_string(),
+    }
+}
+
+/// Check whether a message already starts with something shaped like a type prefix
+fn has_type_prefix(message: &str) -> bool {
+    let regex = regex::Regex::new(r"^[A-Za-z]+(\([^)]*\))?!?: ").unwrap();
+    regex.is_match(message)
 }
 
 #[cfg(test)]
@@ -310,6 +1425,147 @@ mod tests {
         assert!(!is_valid_commit_message("feat:"));
         assert!(!is_valid_commit_message("feature: add something")); // wrong type
         assert!(!is_valid_commit_message(&"feat: ".repeat(100))); // too long
+        assert!(is_valid_commit_message("feat(api)!: drop the v1 endpoints"));
+        assert!(is_valid_commit_message(&format!(
+            "docs: {}",
+            "é".repeat(60)
+        )));
+    }
+
+    #[test]
+    fn test_is_refusal() {
+        assert!(is_refusal("I'm sorry, but I can't help with that request."));
+        assert!(is_refusal(
+            "I cannot write a commit message for this content."
+        ));
+        assert!(is_refusal(
+            "This diff appears to violate our content policy, so no message was written."
+        ));
+        assert!(!is_refusal("feat: add login form"));
+        assert!(!is_refusal("fix(policy): honour the content policy header"));
+        assert!(!is_refusal("Added a login form"));
+
+        assert_eq!(
+            refusal_excerpt("I'm sorry, I can't do that. Please remove the keys."),
+            "I'm sorry, I can't do that."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refusal_is_reported_separately() {
+        struct RefusingProvider;
+
+        #[async_trait::async_trait]
+        impl AIProvider for RefusingProvider {
+            async fn generate_message(&self, _prompt: &str) -> Result<String> {
+                Ok("I'm sorry, but I can't assist with that.".to_string())
+            }
+
+            fn provider_name(&self) -> &'static str {
+                "OpenAI"
+            }
+        }
+
+        let error = generate_commit_messages_from_prompt(
+            "prompt",
+            "diff",
+            &RefusingProvider,
+            3,
+            &FilterPipeline::default(),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(CommittorError::ModelRefusal(refusal)) if refusal == "I'm sorry, but I can't assist with that."
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_empty_responses_are_a_provider_error() {
+        struct EmptyProvider;
+
+        #[async_trait::async_trait]
+        impl AIProvider for EmptyProvider {
+            async fn generate_message(&self, _prompt: &str) -> Result<String> {
+                Ok(" \n".to_string())
+            }
+
+            fn provider_name(&self) -> &'static str {
+                "OpenAI"
+            }
+        }
+
+        let error = generate_commit_messages_from_prompt(
+            "prompt",
+            "diff",
+            &EmptyProvider,
+            2,
+            &FilterPipeline::default(),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(CommittorError::AIProviderError(reason)) if reason == "the model returned 4 empty responses"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_responses_are_salvaged_or_retried() {
+        struct InterruptingProvider(std::sync::Mutex<Vec<&'static str>>);
+
+        #[async_trait::async_trait]
+        impl AIProvider for InterruptingProvider {
+            async fn generate_message(&self, _prompt: &str) -> Result<String> {
+                let partial = self.0.lock().unwrap().remove(0);
+                if partial.ends_with('.') {
+                    return Ok(partial.to_string());
+                }
+                Err(InterruptedResponse {
+                    partial: partial.to_string(),
+                    reason: "connection reset".to_string(),
+                }
+                .into())
+            }
+
+            fn provider_name(&self) -> &'static str {
+                "Ollama"
+            }
+        }
+
+        // A whole subject is kept without its unfinished body
+        let provider = InterruptingProvider(std::sync::Mutex::new(vec![
+            "feat: add login form\n\nAdds the form and val",
+        ]));
+        let messages = generate_commit_messages_from_prompt(
+            "prompt",
+            "diff",
+            &provider,
+            1,
+            &FilterPipeline::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(messages, vec!["feat: add login form"]);
+
+        // A subject cut off mid-word is asked for again
+        let provider = InterruptingProvider(std::sync::Mutex::new(vec![
+            "feat: add login fo",
+            "fix: handle an empty password.",
+        ]));
+        let messages = generate_commit_messages_from_prompt(
+            "prompt",
+            "diff",
+            &provider,
+            1,
+            &FilterPipeline::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].starts_with("fix: handle an empty password"));
+        assert!(provider.0.lock().unwrap().is_empty());
     }
 
     #[test]
@@ -336,6 +1592,15 @@ mod tests {
         assert!(parse_commit_message("invalid message").is_err());
     }
 
+    #[test]
+    fn test_clean_single_line_response() {
+        assert_eq!(
+            clean_single_line_response("\n\"Rework parser error recovery.\"\nextra"),
+            "Rework parser error recovery"
+        );
+        assert_eq!(clean_single_line_response("   \n"), "");
+    }
+
     #[test]
     fn test_enhance_commit_message() {
         assert_eq!(
@@ -352,5 +1617,312 @@ mod tests {
             enhance_commit_message("feat: add new feature", "main"),
             "feat: add new feature"
         );
+
+        assert_eq!(
+            enhance_commit_message("add new feature", "HEAD"),
+            "add new feature"
+        );
+
+        // Messages that already carry a type are never re-typed
+        assert_eq!(
+            enhance_commit_message("docs: update README", "feature/user-auth"),
+            "docs: update README"
+        );
+        assert_eq!(
+            enhance_commit_message("Feature: add login", "fix/auth-bug"),
+            "Feature: add login"
+        );
+
+        assert_eq!(
+            enhance_commit_message("bump dependencies", "chore/deps"),
+            "chore: bump dependencies"
+        );
+        assert_eq!(
+            enhance_commit_message("update README", "main"),
+            "update README"
+        );
+    }
+
+    #[test]
+    fn test_infer_type_from_branch_with_custom_mappings() {
+        let mut mappings = default_branch_type_mappings();
+        mappings.push(("feature/docs-".to_string(), CommitType::Docs));
+
+        assert_eq!(
+            infer_type_from_branch("feature/docs-install", &mappings),
+            Some(CommitType::Docs)
+        );
+        assert_eq!(
+            infer_type_from_branch("feature/login", &mappings),
+            Some(CommitType::Feat)
+        );
+        assert_eq!(infer_type_from_branch("main", &mappings), None);
+
+        assert_eq!(
+            enhance_commit_message_with_mappings(
+                "speed up parser",
+                "speed/parser",
+                &[("speed/".to_string(), CommitType::Perf)]
+            ),
+            "perf: speed up parser"
+        );
+    }
+
+    #[test]
+    fn test_with_scope() {
+        assert_eq!(
+            with_scope("feat(auth): add login", "api"),
+            "feat(api): add login"
+        );
+        assert_eq!(
+            with_scope("fix!: stop crash\n\nbody", "cli"),
+            "fix(cli)!: stop crash\n\nbody"
+        );
+        assert_eq!(with_scope("not conventional", "cli"), "not conventional");
+    }
+
+    #[test]
+    fn test_parse_choice() {
+        assert_eq!(
+            parse_choice("2", 3, 2),
+            Some(Choice {
+                index: 1,
+                scope: None
+            })
+        );
+        assert_eq!(
+            parse_choice("2b", 3, 2),
+            Some(Choice {
+                index: 1,
+                scope: Some(1)
+            })
+        );
+        assert_eq!(
+            parse_choice("A", 3, 2),
+            Some(Choice {
+                index: 0,
+                scope: Some(0)
+            })
+        );
+        assert_eq!(parse_choice("c", 3, 2), None);
+        assert_eq!(parse_choice("4", 3, 2), None);
+        assert_eq!(parse_choice("1ab", 3, 2), None);
+        assert_eq!(parse_choice("", 3, 2), None);
+    }
+
+    #[test]
+    fn test_parse_picker_action() {
+        assert_eq!(
+            parse_picker_action(" 2b ", 3, 2),
+            Some(PickerAction::Commit(Choice {
+                index: 1,
+                scope: Some(1)
+            }))
+        );
+        assert_eq!(parse_picker_action("e", 3, 2), Some(PickerAction::Edit(0)));
+        assert_eq!(parse_picker_action("E3", 3, 2), Some(PickerAction::Edit(2)));
+        assert_eq!(parse_picker_action("e4", 3, 2), None);
+        assert_eq!(parse_picker_action("v", 3, 2), Some(PickerAction::View(0)));
+        assert_eq!(parse_picker_action("v2", 3, 2), Some(PickerAction::View(1)));
+        assert_eq!(parse_picker_action("v4", 3, 2), None);
+        assert_eq!(
+            parse_picker_action("+2", 3, 2),
+            Some(PickerAction::Rate(1, Rating::Up))
+        );
+        assert_eq!(
+            parse_picker_action("-1", 3, 2),
+            Some(PickerAction::Rate(0, Rating::Down))
+        );
+        assert_eq!(parse_picker_action("-4", 3, 2), None);
+        assert_eq!(
+            parse_picker_action("r", 3, 2),
+            Some(PickerAction::Regenerate)
+        );
+        assert_eq!(parse_picker_action("m", 3, 2), Some(PickerAction::More));
+        assert_eq!(parse_picker_action("x", 3, 2), Some(PickerAction::Exclude));
+        assert_eq!(parse_picker_action("c", 3, 2), Some(PickerAction::Cancel));
+        assert_eq!(
+            parse_picker_action("quit", 3, 2),
+            Some(PickerAction::Cancel)
+        );
+        assert_eq!(parse_picker_action("0", 3, 2), None);
+    }
+
+    #[test]
+    fn test_toggle_excluded() {
+        let paths: Vec<String> = ["src/lib.rs", "Cargo.lock", "README.md"]
+            .map(String::from)
+            .to_vec();
+
+        let excluded = toggle_excluded(&paths, &[], &[1]).unwrap();
+        assert_eq!(excluded, vec!["Cargo.lock"]);
+        // A listed file toggles back in while another goes out
+        assert_eq!(
+            toggle_excluded(&paths, &excluded, &[1, 2]).unwrap(),
+            vec!["README.md"]
+        );
+        assert_eq!(toggle_excluded(&paths, &excluded, &[0, 2]), None);
+    }
+
+    #[test]
+    fn test_parse_stale_action() {
+        assert_eq!(parse_stale_action(""), Some(StaleAction::Regenerate));
+        assert_eq!(parse_stale_action("R"), Some(StaleAction::Regenerate));
+        assert_eq!(parse_stale_action("a"), Some(StaleAction::CommitAnyway));
+        assert_eq!(parse_stale_action("quit"), Some(StaleAction::Cancel));
+        assert_eq!(parse_stale_action("y"), None);
+    }
+
+    #[test]
+    fn test_split_body() {
+        let message = "feat: add login\n\nAdds a form.\n\nRefs: ABC-1";
+        assert_eq!(
+            split_body(message),
+            ("feat: add login", vec!["Adds a form.", "", "Refs: ABC-1"])
+        );
+        assert_eq!(split_body("fix: typo"), ("fix: typo", vec![]));
+
+        assert!(has_bodies(&["fix: typo".to_string(), message.to_string()]));
+        assert!(!has_bodies(&["fix: typo".to_string()]));
+    }
+
+    #[test]
+    fn test_option_types() {
+        let messages = vec![
+            "fix(api): handle empty body".to_string(),
+            "refactor(api): extract body parser".to_string(),
+            "not conventional".to_string(),
+        ];
+        let types = option_types(&messages);
+        assert_eq!(
+            types,
+            vec![Some(CommitType::Fix), Some(CommitType::Refactor), None]
+        );
+        assert!(types_disagree(&types));
+
+        let agreeing = option_types(&messages[..1]);
+        assert!(!types_disagree(&agreeing));
+        assert!(!types_disagree(&[
+            None,
+            Some(CommitType::Fix),
+            Some(CommitType::Fix)
+        ]));
+    }
+
+    #[test]
+    fn test_group_by_type() {
+        let types = vec![
+            Some(CommitType::Fix),
+            None,
+            Some(CommitType::Feat),
+            Some(CommitType::Fix),
+            Some(CommitType::Feat),
+        ];
+        assert_eq!(
+            group_by_type(&types),
+            vec![
+                (Some(CommitType::Fix), vec![0, 3]),
+                (Some(CommitType::Feat), vec![2, 4]),
+                (None, vec![1]),
+            ]
+        );
+        assert!(group_by_type(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_is_near_duplicate() {
+        assert!(is_near_duplicate(
+            "fix(api): handle timeouts",
+            "fix(api): handle timeouts"
+        ));
+        assert!(is_near_duplicate(
+            "Fix(api): Handle timeouts\n\nbody text",
+            "fix(api): handle timeouts"
+        ));
+        assert!(!is_near_duplicate(
+            "fix(api): retry failed requests",
+            "fix(api): handle timeouts"
+        ));
+    }
+
+    #[test]
+    fn test_get_head_state_from_repo() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init(temp_dir.path()).unwrap();
+
+        // Unborn branch still reports its name
+        assert!(matches!(
+            get_head_state_from_repo(&repo).unwrap(),
+            HeadState::Branch(_)
+        ));
+
+        let signature = git2::Signature::now("Test User", "test@example.com").unwrap();
+        let tree_id = repo.index().unwrap().write_tree().unwrap();
+        let tree = repo.find_tree(tree_id).unwrap();
+        let commit_id = repo
+            .commit(Some("HEAD"), &signature, &signature, "Initial", &tree, &[])
+            .unwrap();
+
+        let state = get_head_state_from_repo(&repo).unwrap();
+        assert!(!state.is_detached());
+        assert!(state.branch().is_some());
+
+        repo.set_head_detached(commit_id).unwrap();
+        let state = get_head_state_from_repo(&repo).unwrap();
+        assert!(state.is_detached());
+        assert_eq!(state.branch(), None);
+        match state {
+            HeadState::Detached(sha) => assert!(commit_id.to_string().starts_with(&sha)),
+            _ => panic!("Expected detached HEAD"),
+        }
+    }
+
+    #[test]
+    fn test_get_branch_history_from_repo() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init(temp_dir.path()).unwrap();
+        let signature = git2::Signature::now("Test User", "test@example.com").unwrap();
+        let tree_id = repo.index().unwrap().write_tree().unwrap();
+        let tree = repo.find_tree(tree_id).unwrap();
+
+        let commit = |message: &str| {
+            let parent = repo.head().unwrap().peel_to_commit().unwrap();
+            repo.commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                message,
+                &tree,
+                &[&parent],
+            )
+            .unwrap()
+        };
+
+        let base = repo
+            .commit(None, &signature, &signature, "Initial", &tree, &[])
+            .unwrap();
+        let base = repo.find_commit(base).unwrap();
+        repo.branch("main", &base, true).unwrap();
+        repo.branch("feature/retries", &base, true).unwrap();
+        repo.set_head("refs/heads/feature/retries").unwrap();
+
+        // Too few commits of its own to be worth including
+        commit("feat(api): add http client");
+        commit("feat(api): add retry policy");
+        assert!(get_branch_history_from_repo(&repo).unwrap().is_empty());
+
+        commit("test(api): cover retry policy");
+        assert_eq!(
+            get_branch_history_from_repo(&repo).unwrap(),
+            vec![
+                "test(api): cover retry policy",
+                "feat(api): add retry policy",
+                "feat(api): add http client",
+            ]
+        );
+
+        // The base branch itself has no history of its own
+        repo.set_head("refs/heads/main").unwrap();
+        assert!(get_branch_history_from_repo(&repo).unwrap().is_empty());
     }
 }
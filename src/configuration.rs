@@ -0,0 +1,380 @@
+//! Checks and reports on the configuration committor runs with
+//!
+//! Settings are deserialized leniently so an old binary can read a newer file,
+//! which means a misspelt key is silently ignored at runtime. `config lint`
+//! reports those along with parse errors and bad values, each at its line in
+//! the file, and `config show --resolved` says where every effective value
+//! came from once the settings file and organization policy are merged.
+
+use crate::policy::Policy;
+use crate::providers::PROVIDER_NAMES;
+use crate::settings::Settings;
+use crate::theme;
+use crate::types::CommitType;
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Source label for values nothing configured
+pub const DEFAULT_SOURCE: &str = "default";
+
+/// Source label for values narrowed or added by the organization policy
+pub const POLICY_SOURCE: &str = "organization policy";
+
+/// A mistake found in a configuration file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigProblem {
+    /// The file, or URL, the problem was found in
+    pub file: String,
+    /// Line and column of the problem, counted from 1, when it can be placed
+    pub location: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.location {
+            Some((line, column)) => write!(f, "{}:{line}:{column}: {}", self.file, self.message),
+            None => write!(f, "{}: {}", self.file, self.message),
+        }
+    }
+}
+
+/// Check a `.committor.toml` for syntax errors, unknown keys and invalid values
+pub fn lint_settings(file: &str, content: &str) -> Vec<ConfigProblem> {
+    lint(file, content, |settings: &Settings| {
+        let mut problems = Vec::new();
+        for (prefix, commit_type) in &settings.branch_types {
+            if commit_type.parse::<CommitType>().is_err() {
+                problems.push((
+                    format!("branch_types.{prefix}"),
+                    format!("`{commit_type}` is not a commit type"),
+                ));
+            }
+        }
+        problems.extend(unknown_types(
+            "rules.allowed_types",
+            &settings.rules.allowed_types,
+        ));
+        if settings.rules.max_subject_length == 0 {
+            problems.push((
+                "rules.max_subject_length".to_string(),
+                "must be at least 1".to_string(),
+            ));
+        }
+        if !(0.0..=1.0).contains(&settings.gate.min_similarity) {
+            problems.push((
+                "gate.min_similarity".to_string(),
+                "must be between 0 and 1".to_string(),
+            ));
+        }
+        for (section, sampling) in [
+            ("generate", &settings.generate),
+            ("tag", &settings.tag),
+            ("review", &settings.review),
+        ] {
+            if sampling
+                .temperature
+                .is_some_and(|temperature| !(0.0..=2.0).contains(&temperature))
+            {
+                problems.push((
+                    format!("{section}.temperature"),
+                    "must be between 0 and 2".to_string(),
+                ));
+            }
+        }
+        problems.extend(theme::lint_theme(&settings.theme));
+        problems
+    })
+}
+
+/// Check an organization policy for syntax errors, unknown keys and invalid values
+pub fn lint_policy(file: &str, content: &str) -> Vec<ConfigProblem> {
+    lint(file, content, |policy: &Policy| {
+        let mut problems: Vec<(String, String)> = policy
+            .providers
+            .iter()
+            .filter(|provider| !PROVIDER_NAMES.contains(&provider.as_str()))
+            .map(|provider| {
+                (
+                    "providers".to_string(),
+                    format!(
+                        "`{provider}` is not a provider; use {}",
+                        PROVIDER_NAMES.join(" or ")
+                    ),
+                )
+            })
+            .collect();
+        problems.extend(unknown_types("types", &policy.types));
+        problems
+    })
+}
+
+fn unknown_types(key: &str, types: &[String]) -> Vec<(String, String)> {
+    types
+        .iter()
+        .filter(|commit_type| commit_type.parse::<CommitType>().is_err())
+        .map(|commit_type| {
+            (
+                key.to_string(),
+                format!("`{commit_type}` is not a commit type"),
+            )
+        })
+        .collect()
+}
+
+/// Deserialize a file, collecting ignored keys, then run value checks returning `(key, message)`
+fn lint<T: DeserializeOwned>(
+    file: &str,
+    content: &str,
+    check: impl Fn(&T) -> Vec<(String, String)>,
+) -> Vec<ConfigProblem> {
+    let problem = |key: &str, message: String| ConfigProblem {
+        file: file.to_string(),
+        location: locate_key(content, key),
+        message,
+    };
+
+    let mut unknown = Vec::new();
+    let parsed: Result<T, _> =
+        serde_ignored::deserialize(toml::Deserializer::new(content), |path| {
+            unknown.push(path.to_string())
+        });
+    let value = match parsed {
+        Ok(value) => value,
+        Err(e) => {
+            return vec![ConfigProblem {
+                file: file.to_string(),
+                location: e.span().map(|span| line_and_column(content, span.start)),
+                message: e.message().trim().to_string(),
+            }]
+        }
+    };
+
+    let mut problems: Vec<ConfigProblem> = unknown
+        .iter()
+        .map(|key| problem(key, format!("unknown key `{key}`")))
+        .collect();
+    problems.extend(
+        check(&value)
+            .into_iter()
+            .map(|(key, message)| problem(&key, format!("`{key}` {message}"))),
+    );
+    problems.sort_by_key(|problem| problem.location);
+    problems
+}
+
+/// Convert a byte offset into a line and column, both counted from 1
+fn line_and_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+/// Find the line defining a dotted key such as `rules.max_subject_length`
+///
+/// Keys inside inline tables or arrays are placed at the line of their outer key.
+fn locate_key(content: &str, key: &str) -> Option<(usize, usize)> {
+    let mut table = String::new();
+    for (index, line) in content.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let column = line.len() - trimmed.len() + 1;
+        if trimmed.starts_with('#') {
+            continue;
+        }
+        if trimmed.starts_with('[') {
+            let header = trimmed.split(']').next().unwrap_or_default();
+            table = dotted(header.trim_start_matches('['));
+            if table == key {
+                return Some((index + 1, column));
+            }
+            continue;
+        }
+
+        let Some((name, _)) = trimmed.split_once('=') else {
+            continue;
+        };
+        let name = dotted(name);
+        let full = if table.is_empty() {
+            name
+        } else {
+            format!("{table}.{name}")
+        };
+        if full == key || key.starts_with(&format!("{full}.")) {
+            return Some((index + 1, column));
+        }
+    }
+    None
+}
+
+/// Normalize a TOML key such as `branch_types . "spike/"` to `branch_types.spike/`
+fn dotted(key: &str) -> String {
+    key.split('.')
+        .map(|part| part.trim().trim_matches(['"', '\'']))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// A setting's effective value and where it came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedSetting {
+    /// Dotted key, e.g. `rules.max_subject_length`
+    pub key: String,
+    /// The value in TOML syntax
+    pub value: String,
+    /// The settings file, the organization policy or the built-in default
+    pub source: String,
+}
+
+/// Merge a settings file and a policy, labelling every effective value with its source
+///
+/// `file` is the settings file's name and content, when there is one.
+pub fn resolve_settings(
+    file: Option<(&str, &str)>,
+    policy: Option<&Policy>,
+) -> Result<Vec<ResolvedSetting>> {
+    let (configured, table) = match file {
+        Some((_, content)) => (Settings::parse(content)?, toml::from_str(content)?),
+        None => (Settings::default(), toml::Table::new()),
+    };
+    let mut effective = configured.clone();
+    if let Some(policy) = policy {
+        policy.apply(&mut effective);
+    }
+
+    let configured = flatten(toml::Value::try_from(&configured)?);
+    let in_file = flatten(toml::Value::Table(table));
+    let resolved = flatten(toml::Value::try_from(&effective)?)
+        .into_iter()
+        .map(|(key, value)| {
+            let source = if configured.get(&key) != Some(&value) {
+                POLICY_SOURCE
+            } else if in_file.contains_key(&key) {
+                file.map_or(DEFAULT_SOURCE, |(name, _)| name)
+            } else {
+                DEFAULT_SOURCE
+            };
+            ResolvedSetting {
+                key,
+                value: value.to_string(),
+                source: source.to_string(),
+            }
+        })
+        .collect();
+    Ok(resolved)
+}
+
+/// Flatten nested tables into dotted keys; arrays and empty tables stay whole
+fn flatten(value: toml::Value) -> BTreeMap<String, toml::Value> {
+    let mut flat = BTreeMap::new();
+    flatten_into(String::new(), value, &mut flat);
+    flat
+}
+
+fn flatten_into(prefix: String, value: toml::Value, flat: &mut BTreeMap<String, toml::Value>) {
+    match value {
+        toml::Value::Table(table) if !table.is_empty() => {
+            for (key, value) in table {
+                let key = if prefix.is_empty() {
+                    key
+                } else {
+                    format!("{prefix}.{key}")
+                };
+                flatten_into(key, value, flat);
+            }
+        }
+        value => {
+            flat.insert(prefix, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint_settings_reports_unknown_keys_and_values() {
+        let content = r#"
+[rules]
+max_subject_lenght = 50
+allowed_types = ["feat", "feature"]
+
+[branch_types]
+"spike/" = "spike"
+
+[gate]
+min_similarity = 1.5
+
+[tag]
+temperature = 3.0
+"#;
+        let problems: Vec<String> = lint_settings(".committor.toml", content)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            problems,
+            vec![
+                ".committor.toml:3:1: unknown key `rules.max_subject_lenght`",
+                ".committor.toml:4:1: `rules.allowed_types` `feature` is not a commit type",
+                ".committor.toml:7:1: `branch_types.spike/` `spike` is not a commit type",
+                ".committor.toml:10:1: `gate.min_similarity` must be between 0 and 1",
+                ".committor.toml:13:1: `tag.temperature` must be between 0 and 2",
+            ]
+        );
+
+        assert!(lint_settings(".committor.toml", "[rules]\nmax_subject_length = 50").is_empty());
+    }
+
+    #[test]
+    fn test_lint_settings_reports_parse_errors_at_their_location() {
+        let problems = lint_settings(
+            ".committor.toml",
+            "[rules]\nmax_subject_length = \"long\"\n",
+        );
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].location, Some((2, 22)));
+        assert!(problems[0].message.contains("invalid type"));
+    }
+
+    #[test]
+    fn test_lint_policy() {
+        let problems = lint_policy(
+            "policy.toml",
+            "providers = [\"ollama\", \"anthropic\"]\ntypes = [\"fix\"]\nttl = 5\n",
+        );
+        let messages: Vec<&str> = problems.iter().map(|p| p.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "`providers` `anthropic` is not a provider; use openai or ollama",
+                "unknown key `ttl`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_settings_labels_sources() {
+        let content = "[rules]\nmax_subject_length = 50\nallowed_scopes = [\"api\", \"web\"]\n";
+        let policy = Policy {
+            scopes: vec!["api".to_string()],
+            ..Policy::default()
+        };
+        let resolved = resolve_settings(Some((".committor.toml", content)), Some(&policy)).unwrap();
+        let find = |key: &str| resolved.iter().find(|setting| setting.key == key).unwrap();
+
+        assert_eq!(find("rules.max_subject_length").value, "50");
+        assert_eq!(find("rules.max_subject_length").source, ".committor.toml");
+        assert_eq!(find("rules.allowed_scopes").value, "[\"api\"]");
+        assert_eq!(find("rules.allowed_scopes").source, POLICY_SOURCE);
+        assert_eq!(find("hooks.timeout").source, DEFAULT_SOURCE);
+
+        let resolved = resolve_settings(None, None).unwrap();
+        assert!(resolved
+            .iter()
+            .all(|setting| setting.source == DEFAULT_SOURCE));
+    }
+}
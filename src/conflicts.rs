@@ -0,0 +1,247 @@
+//! Messages for commits whose conflicts were resolved mid-rebase or cherry-pick
+//!
+//! A rebase or cherry-pick that stops on a conflict commits the original message
+//! once it's continued, even when the resolution changed what the commit does.
+//! `committor continue` writes a message for the resolved change into the file
+//! git commits from, then continues the operation.
+
+use crate::git::git_output;
+use crate::trailers::{append_trailers, message_trailers};
+use crate::types::CommittorError;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A git operation that stops on conflicts and carries a commit's message over
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Rebase,
+    CherryPick,
+}
+
+impl Operation {
+    /// Get the git command that continues the operation
+    pub fn command(self) -> &'static str {
+        match self {
+            Self::Rebase => "rebase",
+            Self::CherryPick => "cherry-pick",
+        }
+    }
+
+    /// Get the ref naming the commit being replayed
+    fn head_ref(self) -> &'static str {
+        match self {
+            Self::Rebase => "REBASE_HEAD",
+            Self::CherryPick => "CHERRY_PICK_HEAD",
+        }
+    }
+
+    /// Get the file, relative to the git directory, whose message `--continue` commits
+    fn message_file(self) -> &'static str {
+        match self {
+            Self::Rebase => "rebase-merge/message",
+            Self::CherryPick => "MERGE_MSG",
+        }
+    }
+}
+
+/// A stopped commit whose conflicts have been resolved and staged
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolution {
+    pub operation: Operation,
+    /// The commit being replayed
+    pub commit: String,
+    /// Its message, which `--continue` would otherwise reuse
+    pub original: String,
+    /// Paths git reported conflicts in
+    pub conflicts: Vec<String>,
+    /// The staged change the commit will be made of
+    pub diff: String,
+}
+
+impl Resolution {
+    /// Get the message the commit ends up with, keeping the original's trailers
+    pub fn final_message(&self, message: &str) -> String {
+        append_trailers(message, &message_trailers(&self.original))
+    }
+}
+
+/// Find the resolved conflict of a rebase or cherry-pick stopped in the current repository
+pub fn find_resolution() -> Result<Resolution> {
+    find_resolution_in(Path::new("."))
+}
+
+/// Find the resolved conflict of a rebase or cherry-pick stopped in a repository
+///
+/// Fails when neither is in progress, when a path is still unmerged, or when the
+/// resolution leaves nothing to commit.
+pub fn find_resolution_in(dir: &Path) -> Result<Resolution> {
+    if git_path(dir, "rebase-apply")?.exists() {
+        return Err(CommittorError::GitError(
+            "Only rebases using the merge backend can be continued; run git rebase --continue"
+                .to_string(),
+        )
+        .into());
+    }
+    let operation = if git_path(dir, "rebase-merge")?.exists() {
+        Operation::Rebase
+    } else {
+        Operation::CherryPick
+    };
+    let Ok(commit) = git_output(dir, &["rev-parse", "-q", "--verify", operation.head_ref()]) else {
+        return Err(CommittorError::GitError(
+            "No rebase or cherry-pick is stopped on a conflict".to_string(),
+        )
+        .into());
+    };
+
+    let unmerged = git_output(dir, &["diff", "--name-only", "--diff-filter=U"])?;
+    if !unmerged.is_empty() {
+        let paths: Vec<&str> = unmerged.lines().collect();
+        return Err(CommittorError::GitError(format!(
+            "Resolve and stage the conflicts in {} first",
+            paths.join(", ")
+        ))
+        .into());
+    }
+
+    let diff = git_output(dir, &["diff", "--cached", "HEAD"])?;
+    if diff.is_empty() {
+        return Err(CommittorError::GitError(format!(
+            "The resolution leaves nothing to commit; run git {} --continue or --skip",
+            operation.command()
+        ))
+        .into());
+    }
+
+    let original = git_output(dir, &["log", "-1", "--format=%B", &commit])?;
+    let merge_message = std::fs::read_to_string(git_path(dir, "MERGE_MSG")?).unwrap_or_default();
+    Ok(Resolution {
+        operation,
+        commit,
+        original,
+        conflicts: parse_conflicts(&merge_message),
+        diff: format!("{diff}\n"),
+    })
+}
+
+/// Get the paths listed under `# Conflicts:` in the message git prepared
+pub fn parse_conflicts(merge_message: &str) -> Vec<String> {
+    merge_message
+        .lines()
+        .skip_while(|line| line.trim() != "# Conflicts:")
+        .skip(1)
+        .take_while(|line| line.starts_with('#'))
+        .filter_map(|line| line.strip_prefix("#\t"))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Continue the operation stopped in the current repository, committing with the message
+pub fn continue_with_message(resolution: &Resolution, message: &str) -> Result<()> {
+    continue_with_message_in(Path::new("."), resolution, message)
+}
+
+/// Continue a stopped operation, committing its resolution with the message
+///
+/// The message replaces the one git prepared and no editor is opened. A rebase may
+/// stop again on a later commit's conflict, which is reported as an error.
+pub fn continue_with_message_in(dir: &Path, resolution: &Resolution, message: &str) -> Result<()> {
+    let path = git_path(dir, resolution.operation.message_file())?;
+    std::fs::write(&path, format!("{}\n", message.trim_end()))
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+
+    let command = resolution.operation.command();
+    let output = Command::new("git")
+        .args([command, "--continue"])
+        .current_dir(dir)
+        .env("GIT_EDITOR", "true")
+        .output()
+        .with_context(|| format!("Failed to execute git {command} --continue"))?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(CommittorError::GitError(format!("{}\n{}", stdout.trim(), stderr.trim())).into())
+    }
+}
+
+/// Resolve a path inside the git directory, e.g. `MERGE_MSG`
+fn git_path(dir: &Path, name: &str) -> Result<PathBuf> {
+    Ok(dir.join(git_output(dir, &["rev-parse", "--git-path", name])?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fixtures::{commit, git, init_repo};
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// A repository whose `side` branch conflicts with `main` in `a.txt`
+    fn conflicting_repo() -> TempDir {
+        let temp_dir = init_repo();
+        let dir = temp_dir.path();
+        commit(dir, "a.txt", "a\n", "chore: initial");
+        git(dir, &["checkout", "-q", "-b", "side"]);
+        commit(dir, "a.txt", "side\n", "feat: use side\n\nRefs: ABC-1");
+        git(dir, &["checkout", "-q", "main"]);
+        commit(dir, "a.txt", "main\n", "feat: use main");
+        temp_dir
+    }
+
+    #[test]
+    fn test_parse_conflicts() {
+        let message = "feat: use side\n\n# Conflicts:\n#\ta.txt\n#\tsrc/b.rs\n";
+        assert_eq!(parse_conflicts(message), vec!["a.txt", "src/b.rs"]);
+        assert!(parse_conflicts("feat: use side\n").is_empty());
+    }
+
+    #[test]
+    fn test_continue_cherry_pick_with_message() {
+        let temp_dir = conflicting_repo();
+        let dir = temp_dir.path();
+        assert!(find_resolution_in(dir).is_err());
+
+        assert!(git_output(dir, &["cherry-pick", "side"]).is_err());
+        let error = find_resolution_in(dir).unwrap_err().to_string();
+        assert!(error.contains("a.txt"), "{error}");
+
+        fs::write(dir.join("a.txt"), "main and side\n").unwrap();
+        git(dir, &["add", "a.txt"]);
+        let resolution = find_resolution_in(dir).unwrap();
+        assert_eq!(resolution.operation, Operation::CherryPick);
+        assert_eq!(resolution.original, "feat: use side\n\nRefs: ABC-1");
+        assert_eq!(resolution.conflicts, vec!["a.txt"]);
+        assert!(resolution.diff.contains("+main and side"));
+
+        let message = resolution.final_message("feat: combine main and side");
+        continue_with_message_in(dir, &resolution, &message).unwrap();
+        assert_eq!(
+            git(dir, &["log", "-1", "--format=%B"]),
+            "feat: combine main and side\n\nRefs: ABC-1"
+        );
+    }
+
+    #[test]
+    fn test_continue_rebase_with_message() {
+        let temp_dir = conflicting_repo();
+        let dir = temp_dir.path();
+        git(dir, &["checkout", "-q", "side"]);
+        assert!(git_output(dir, &["rebase", "main"]).is_err());
+
+        fs::write(dir.join("a.txt"), "main and side\n").unwrap();
+        git(dir, &["add", "a.txt"]);
+        let resolution = find_resolution_in(dir).unwrap();
+        assert_eq!(resolution.operation, Operation::Rebase);
+
+        continue_with_message_in(dir, &resolution, "feat: combine main and side").unwrap();
+        assert_eq!(
+            git(dir, &["log", "--format=%s"]),
+            "feat: combine main and side\nfeat: use main\nchore: initial"
+        );
+        assert_eq!(git(dir, &["branch", "--show-current"]), "side");
+    }
+}
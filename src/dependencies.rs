@@ -0,0 +1,265 @@
+//! Dependencies added, removed or bumped by staged `Cargo.toml` and `package.json` edits
+//!
+//! A manifest diff is a few changed version strings with little context; listing
+//! the changes by name lets the model write `build(deps): bump reqwest to 0.12`.
+
+use crate::diff::{get_staged_changes_from_repo, staged_file_versions_from_repo};
+use anyhow::{Context, Result};
+use git2::Repository;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Cargo manifest tables that list dependencies
+const CARGO_TABLES: &[&str] = &["dependencies", "dev-dependencies", "build-dependencies"];
+
+/// npm manifest fields that list dependencies
+const NPM_FIELDS: &[&str] = &[
+    "dependencies",
+    "devDependencies",
+    "peerDependencies",
+    "optionalDependencies",
+];
+
+/// A dependency whose requirement changed between HEAD and the index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyChange {
+    /// Manifest the dependency is declared in
+    pub path: String,
+    pub name: String,
+    /// Requirement at HEAD, `None` when the dependency is new
+    pub old: Option<String>,
+    /// Staged requirement, `None` when the dependency is removed
+    pub new: Option<String>,
+}
+
+impl fmt::Display for DependencyChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.old, &self.new) {
+            (None, Some(new)) => write!(f, "add {} {new}", self.name),
+            (Some(_), None) => write!(f, "remove {}", self.name),
+            (Some(old), Some(new)) => write!(f, "bump {} from {old} to {new}", self.name),
+            (None, None) => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// Get the dependency changes staged in the repository in the current directory
+pub fn dependency_changes() -> Result<Vec<DependencyChange>> {
+    let repo = Repository::open(".").context("Not in a git repository")?;
+    dependency_changes_from_repo(&repo)
+}
+
+/// Get the dependency changes staged in a specific repository
+///
+/// Manifests that fail to parse on either side are skipped.
+pub fn dependency_changes_from_repo(repo: &Repository) -> Result<Vec<DependencyChange>> {
+    let mut changes = Vec::new();
+    for change in get_staged_changes_from_repo(repo)? {
+        let file_name = change.file_path.rsplit('/').next().unwrap_or_default();
+        if !matches!(file_name, "Cargo.toml" | "package.json") {
+            continue;
+        }
+
+        let (old, new) = staged_file_versions_from_repo(repo, &change.file_path)?;
+        let parse = |content: Option<String>| match content {
+            Some(content) => manifest_dependencies(file_name, &content),
+            None => Some(BTreeMap::new()),
+        };
+        if let (Some(old), Some(new)) = (parse(old), parse(new)) {
+            changes.extend(diff_dependencies(&change.file_path, &old, &new));
+        }
+    }
+    Ok(changes)
+}
+
+/// Read the dependency names and requirements from a manifest, `None` if it isn't valid
+pub fn manifest_dependencies(file_name: &str, content: &str) -> Option<BTreeMap<String, String>> {
+    match file_name {
+        "Cargo.toml" => cargo_dependencies(content),
+        "package.json" => npm_dependencies(content),
+        _ => None,
+    }
+}
+
+/// Compare dependency requirements of the manifest at `path`, in name order
+pub fn diff_dependencies(
+    path: &str,
+    old: &BTreeMap<String, String>,
+    new: &BTreeMap<String, String>,
+) -> Vec<DependencyChange> {
+    let mut names: Vec<&String> = old.keys().chain(new.keys()).collect();
+    names.sort();
+    names.dedup();
+
+    names
+        .into_iter()
+        .filter(|name| old.get(*name) != new.get(*name))
+        .map(|name| DependencyChange {
+            path: path.to_string(),
+            name: name.clone(),
+            old: old.get(name).cloned(),
+            new: new.get(name).cloned(),
+        })
+        .collect()
+}
+
+/// Collect dependencies from the top-level, workspace and per-target tables of a `Cargo.toml`
+fn cargo_dependencies(content: &str) -> Option<BTreeMap<String, String>> {
+    let manifest: toml::Table = toml::from_str(content).ok()?;
+    let mut parents = vec![&manifest];
+    parents.extend(manifest.get("workspace").and_then(toml::Value::as_table));
+    if let Some(targets) = manifest.get("target").and_then(toml::Value::as_table) {
+        parents.extend(targets.values().filter_map(toml::Value::as_table));
+    }
+    let tables = parents.into_iter().flat_map(|parent| {
+        CARGO_TABLES
+            .iter()
+            .filter_map(|name| parent.get(*name)?.as_table())
+    });
+
+    let mut dependencies = BTreeMap::new();
+    for table in tables {
+        for (name, spec) in table {
+            dependencies.insert(name.clone(), cargo_requirement(spec));
+        }
+    }
+    Some(dependencies)
+}
+
+/// Describe a Cargo dependency spec by its version, or its source when it has none
+fn cargo_requirement(spec: &toml::Value) -> String {
+    if let Some(version) = spec.as_str() {
+        return version.to_string();
+    }
+    let field = |key: &str| spec.get(key).and_then(toml::Value::as_str);
+    if let Some(version) = field("version") {
+        return version.to_string();
+    }
+    if spec.get("workspace").and_then(toml::Value::as_bool) == Some(true) {
+        return "workspace".to_string();
+    }
+    if let Some(git) = field("git") {
+        let reference = field("rev").or(field("tag")).or(field("branch"));
+        return match reference {
+            Some(reference) => format!("{git}#{reference}"),
+            None => git.to_string(),
+        };
+    }
+    field("path")
+        .map(|path| format!("path {path}"))
+        .unwrap_or_else(|| "*".to_string())
+}
+
+fn npm_dependencies(content: &str) -> Option<BTreeMap<String, String>> {
+    let manifest: serde_json::Value = serde_json::from_str(content).ok()?;
+    let mut dependencies = BTreeMap::new();
+    for field in NPM_FIELDS {
+        let Some(entries) = manifest.get(field).and_then(serde_json::Value::as_object) else {
+            continue;
+        };
+        for (name, version) in entries {
+            let version = version.as_str().unwrap_or("*");
+            dependencies.insert(name.clone(), version.to_string());
+        }
+    }
+    Some(dependencies)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cargo_dependency_changes() {
+        let old = manifest_dependencies(
+            "Cargo.toml",
+            r#"
+[dependencies]
+reqwest = { version = "0.11", features = ["json"] }
+log = "0.4"
+
+[target.'cfg(unix)'.dependencies]
+nix = { git = "https://github.com/nix-rust/nix", tag = "v0.28" }
+"#,
+        )
+        .unwrap();
+        let new = manifest_dependencies(
+            "Cargo.toml",
+            r#"
+[dependencies]
+reqwest = { version = "0.12", features = ["json"] }
+
+[dev-dependencies]
+tempfile = "3.8"
+
+[target.'cfg(unix)'.dependencies]
+nix = { git = "https://github.com/nix-rust/nix", tag = "v0.28" }
+"#,
+        )
+        .unwrap();
+
+        let changes: Vec<String> = diff_dependencies("Cargo.toml", &old, &new)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                "remove log",
+                "bump reqwest from 0.11 to 0.12",
+                "add tempfile 3.8"
+            ]
+        );
+        assert!(manifest_dependencies("Cargo.toml", "[dependencies").is_none());
+    }
+
+    #[test]
+    fn test_npm_dependency_changes() {
+        let old = manifest_dependencies(
+            "package.json",
+            r#"{"dependencies": {"react": "^18.2.0"}, "devDependencies": {"jest": "^29.0.0"}}"#,
+        )
+        .unwrap();
+        let new = manifest_dependencies(
+            "package.json",
+            r#"{"dependencies": {"react": "^19.0.0"}, "devDependencies": {"jest": "^29.0.0"}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            diff_dependencies("package.json", &old, &new),
+            vec![DependencyChange {
+                path: "package.json".to_string(),
+                name: "react".to_string(),
+                old: Some("^18.2.0".to_string()),
+                new: Some("^19.0.0".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_dependency_changes_from_repo() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let repo = Repository::init(dir.path())?;
+        let stage = |content: &str| -> Result<()> {
+            std::fs::write(dir.path().join("package.json"), content)?;
+            let mut index = repo.index()?;
+            index.add_path(std::path::Path::new("package.json"))?;
+            index.write()?;
+            Ok(())
+        };
+
+        stage(r#"{"dependencies": {"axios": "^1.6.0"}}"#)?;
+        let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+        let signature = git2::Signature::now("Test User", "test@example.com")?;
+        repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])?;
+
+        stage(r#"{"dependencies": {"axios": "^1.7.2"}}"#)?;
+        let changes: Vec<String> = dependency_changes_from_repo(&repo)?
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(changes, vec!["bump axios from ^1.6.0 to ^1.7.2"]);
+        Ok(())
+    }
+}
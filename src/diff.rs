@@ -1,9 +1,36 @@
 //! Git diff operations for analyzing staged changes
 
-use crate::types::{DiffChange, DiffChangeType};
+use crate::assets::format_size;
+use crate::batch::diff_hash;
+use crate::paths::git_state_path;
+use crate::state::write_atomic;
+use crate::timing;
+use crate::types::{CommittorError, DiffChange, DiffChangeType, DiffHunk};
 use anyhow::{Context, Result};
 use git2::{Delta, Repository};
-use std::path::Path;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use tracing::info_span;
+
+/// Name of the staged diff cache, kept in the git directory
+pub const DIFF_CACHE_FILE_NAME: &str = "committor-diff-cache.json";
+
+/// Size above which a file is summarized by its size instead of diffed
+///
+/// libgit2 checks the size before loading a blob, so files above it are never
+/// read into memory, however large they are.
+pub const LARGE_FILE_BYTES: u64 = 10 * 1024 * 1024;
+
+/// The staged diff and its per-file stats, computed together and cached between runs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StagedSnapshot {
+    /// HEAD, index checksum and committor version the snapshot was computed for
+    pub key: String,
+    pub diff: String,
+    pub changes: Vec<DiffChange>,
+}
 
 /// Get the staged diff from the current git repository
 pub fn get_staged_diff() -> Result<String> {
@@ -13,23 +40,608 @@ pub fn get_staged_diff() -> Result<String> {
 
 /// Get the staged diff from a specific git repository
 pub fn get_staged_diff_from_repo(repo: &Repository) -> Result<String> {
+    let mut diff_opts = diff_options();
+    diff_opts.include_untracked(false);
+    diff_opts.context_lines(3);
+
+    let diff = diff_head_to_index(repo, &mut diff_opts)?;
+
+    diff_to_text(&diff)
+}
+
+/// Get the staged diff of each changed file separately from the current git repository
+pub fn get_staged_file_diffs() -> Result<Vec<(String, String)>> {
+    let repo = Repository::open(".").context("Not in a git repository")?;
+    get_staged_file_diffs_from_repo(&repo)
+}
+
+/// Get the staged diff of each changed file separately from a specific git repository
+pub fn get_staged_file_diffs_from_repo(repo: &Repository) -> Result<Vec<(String, String)>> {
+    let mut diff_opts = diff_options();
+    diff_opts.include_untracked(false);
+    diff_opts.context_lines(3);
+
+    let diff = diff_head_to_index(repo, &mut diff_opts)?;
+
+    file_patches(&diff)
+}
+
+/// Get the staged changes split into hunks from the current git repository
+pub fn get_staged_hunks() -> Result<Vec<DiffHunk>> {
+    let repo = Repository::open(".").context("Not in a git repository")?;
+    get_staged_hunks_from_repo(&repo)
+}
+
+/// Get the staged changes split into hunks from a specific git repository
+///
+/// Files that have no textual hunks, or whose contents are not available locally,
+/// become a single whole-file entry.
+pub fn get_staged_hunks_from_repo(repo: &Repository) -> Result<Vec<DiffHunk>> {
+    let mut diff_opts = diff_options();
+    diff_opts.include_untracked(false);
+    diff_opts.context_lines(3);
+
+    let diff = diff_head_to_index(repo, &mut diff_opts)?;
+    let mut hunks = Vec::new();
+
+    for (idx, delta) in diff.deltas().enumerate() {
+        let file_path = delta_path(&delta);
+        let whole_file = DiffHunk {
+            file_path: file_path.clone(),
+            file_header: format!("diff --git a/{file_path} b/{file_path}\n"),
+            text: None,
+        };
+
+        let mut patch = match git2::Patch::from_diff(&diff, idx) {
+            Ok(Some(patch)) if !patch.delta().flags().is_binary() => patch,
+            Ok(_) => {
+                hunks.push(whole_file);
+                continue;
+            }
+            Err(e) if is_missing_object(&e) => {
+                hunks.push(whole_file);
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut file_header = String::new();
+        let mut file_hunks: Vec<String> = Vec::new();
+        patch.print(&mut |_delta, _hunk, line| {
+            let content = decode_text(line.content());
+            match line.origin() {
+                'F' => file_header.push_str(&content),
+                'H' => file_hunks.push(content.to_string()),
+                origin @ ('+' | '-' | ' ') => {
+                    if let Some(hunk) = file_hunks.last_mut() {
+                        hunk.push(origin);
+                        hunk.push_str(&content);
+                    }
+                }
+                // End-of-file newline markers carry their own text
+                _ => {
+                    if let Some(hunk) = file_hunks.last_mut() {
+                        hunk.push_str(&content);
+                    }
+                }
+            }
+            true
+        })?;
+
+        if file_hunks.is_empty() {
+            hunks.push(whole_file);
+            continue;
+        }
+
+        hunks.extend(file_hunks.into_iter().map(|text| DiffHunk {
+            file_path: file_path.clone(),
+            file_header: file_header.clone(),
+            text: Some(text),
+        }));
+    }
+
+    Ok(hunks)
+}
+
+/// Get a staged file's contents at HEAD and in the index, `None` where it doesn't exist or isn't UTF-8
+///
+/// Versions larger than [`LARGE_FILE_BYTES`] are `None` too, without being read.
+pub fn staged_file_versions_from_repo(
+    repo: &Repository,
+    path: &str,
+) -> Result<(Option<String>, Option<String>)> {
+    let odb = repo.odb()?;
+    let blob_text = |id: git2::Oid| -> Option<String> {
+        let (size, _) = odb.read_header(id).ok()?;
+        if size as u64 > LARGE_FILE_BYTES {
+            return None;
+        }
+        let blob = repo.find_blob(id).ok()?;
+        String::from_utf8(blob.content().to_vec()).ok()
+    };
+    let path = std::path::Path::new(path);
+
+    let head = repo
+        .head()
+        .ok()
+        .and_then(|head| head.peel_to_tree().ok())
+        .and_then(|tree| tree.get_path(path).ok())
+        .and_then(|entry| blob_text(entry.id()));
+    let staged = repo
+        .index()?
+        .get_path(path, 0)
+        .and_then(|entry| blob_text(entry.id));
+    Ok((head, staged))
+}
+
+/// Get diff options that skip loading files larger than [`LARGE_FILE_BYTES`]
+fn diff_options() -> git2::DiffOptions {
     let mut diff_opts = git2::DiffOptions::new();
+    diff_opts.max_size(LARGE_FILE_BYTES as i64);
+    diff_opts
+}
+
+/// Get the diff introduced by a commit, compared against its first parent
+pub fn get_commit_diff_from_repo(repo: &Repository, commit: &git2::Commit) -> Result<String> {
+    diff_to_text(&commit_diff(repo, commit)?)
+}
+
+fn commit_diff<'r>(repo: &'r Repository, commit: &git2::Commit) -> Result<git2::Diff<'r>> {
+    let mut diff_opts = diff_options();
+    diff_opts.context_lines(3);
+
+    let tree = commit.tree()?;
+    let parent_tree = if commit.parent_count() > 0 {
+        Some(commit.parent(0)?.tree()?)
+    } else {
+        None
+    };
+
+    Ok(repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut diff_opts))?)
+}
+
+/// Get all uncommitted changes to tracked files, staged or not, in the current repository
+pub fn get_working_tree_diff() -> Result<String> {
+    let repo = Repository::open(".").context("Not in a git repository")?;
+    get_working_tree_diff_from_repo(&repo)
+}
+
+/// Get all uncommitted changes to tracked files, staged or not, in a specific repository
+pub fn get_working_tree_diff_from_repo(repo: &Repository) -> Result<String> {
+    let _span = info_span!(timing::GIT_DIFF).entered();
+    diff_to_text(&working_tree_diff(repo)?)
+}
+
+/// Get structured information about all uncommitted changes to tracked files in the current repository
+pub fn get_working_tree_changes() -> Result<Vec<DiffChange>> {
+    let repo = Repository::open(".").context("Not in a git repository")?;
+    get_working_tree_changes_from_repo(&repo)
+}
+
+/// Get structured information about all uncommitted changes to tracked files in a specific repository
+pub fn get_working_tree_changes_from_repo(repo: &Repository) -> Result<Vec<DiffChange>> {
+    changes_in_diff(working_tree_diff(repo)?)
+}
+
+fn working_tree_diff(repo: &Repository) -> Result<git2::Diff<'_>> {
+    let mut diff_opts = diff_options();
     diff_opts.include_untracked(false);
     diff_opts.context_lines(3);
 
     let head_tree = repo.head()?.peel_to_tree()?;
-    let mut index = repo.index()?;
-    let _index_tree = repo.find_tree(index.write_tree()?)?;
+    Ok(repo.diff_tree_to_workdir_with_index(Some(&head_tree), Some(&mut diff_opts))?)
+}
+
+/// Get the diff for a revision range (`a..b`, `a...b`) or a single commit in the current repository
+pub fn get_range_diff(range: &str) -> Result<String> {
+    let repo = Repository::open(".").context("Not in a git repository")?;
+    get_range_diff_from_repo(&repo, range)
+}
+
+/// Get the diff for a revision range or a single commit in a specific repository
+///
+/// This works without a working tree or index, so it can be used against bare repositories.
+pub fn get_range_diff_from_repo(repo: &Repository, range: &str) -> Result<String> {
+    let _span = info_span!(timing::GIT_DIFF).entered();
+    diff_to_text(&range_diff(repo, range)?)
+}
+
+/// Get structured information about the changes in a revision range in the current repository
+pub fn get_range_changes(range: &str) -> Result<Vec<DiffChange>> {
+    let repo = Repository::open(".").context("Not in a git repository")?;
+    get_range_changes_from_repo(&repo, range)
+}
+
+/// Get structured information about the changes in a revision range in a specific repository
+pub fn get_range_changes_from_repo(repo: &Repository, range: &str) -> Result<Vec<DiffChange>> {
+    changes_in_diff(range_diff(repo, range)?)
+}
+
+fn range_diff<'r>(repo: &'r Repository, range: &str) -> Result<git2::Diff<'r>> {
+    let revspec = repo
+        .revparse(range)
+        .with_context(|| format!("Invalid revision range: {range}"))?;
+
+    let from = revspec
+        .from()
+        .with_context(|| format!("Invalid revision range: {range}"))?
+        .peel_to_commit()?;
+
+    if revspec.mode().contains(git2::RevparseMode::SINGLE) {
+        return commit_diff(repo, &from);
+    }
+
+    let to = revspec
+        .to()
+        .with_context(|| format!("Invalid revision range: {range}"))?
+        .peel_to_commit()?;
+
+    let base = if revspec.mode().contains(git2::RevparseMode::MERGE_BASE) {
+        repo.find_commit(repo.merge_base(from.id(), to.id())?)?
+    } else {
+        from
+    };
+
+    let mut diff_opts = diff_options();
+    diff_opts.context_lines(3);
+
+    Ok(repo.diff_tree_to_tree(Some(&base.tree()?), Some(&to.tree()?), Some(&mut diff_opts))?)
+}
+
+/// Diff the HEAD tree against the index
+fn diff_head_to_index<'r>(
+    repo: &'r Repository,
+    diff_opts: &mut git2::DiffOptions,
+) -> Result<git2::Diff<'r>> {
+    let head_tree = repo.head()?.peel_to_tree()?;
 
-    let diff = repo.diff_tree_to_index(Some(&head_tree), Some(&index), Some(&mut diff_opts))?;
+    let index = match repo.index() {
+        Ok(index) => index,
+        // Sparse indexes use extensions libgit2 cannot read, so load the entries through git
+        // and make them the repository's in-memory index for attribute lookups too
+        Err(e) if e.class() == git2::ErrorClass::Index => {
+            let mut index = index_from_git(repo)?;
+            repo.set_index(&mut index)?;
+            index
+        }
+        Err(e) => return Err(e.into()),
+    };
 
+    Ok(repo.diff_tree_to_index(Some(&head_tree), Some(&index), Some(diff_opts))?)
+}
+
+/// Build an in-memory index from `git ls-files`, which expands sparse directory entries
+fn index_from_git(repo: &Repository) -> Result<git2::Index> {
+    let dir = repo.workdir().unwrap_or_else(|| repo.path());
+    let output = std::process::Command::new("git")
+        .args(["ls-files", "--stage", "-z"])
+        .current_dir(dir)
+        .output()
+        .context("Failed to list index entries")?;
+
+    if !output.status.success() {
+        return Err(
+            CommittorError::GitError(String::from_utf8_lossy(&output.stderr).to_string()).into(),
+        );
+    }
+
+    let mut index = git2::Index::new()?;
+    for record in output.stdout.split(|b| *b == 0).filter(|r| !r.is_empty()) {
+        // Paths are kept as git's bytes, which need not be UTF-8
+        let Some(tab) = record.iter().position(|b| *b == b'\t') else {
+            continue;
+        };
+        let (Ok(meta), path) = (std::str::from_utf8(&record[..tab]), &record[tab + 1..]) else {
+            continue;
+        };
+        let mut fields = meta.split_whitespace();
+        let (Some(mode), Some(oid), Some(stage)) = (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        let stage: u16 = stage.parse().unwrap_or(0);
+
+        index.add(&git2::IndexEntry {
+            ctime: git2::IndexTime::new(0, 0),
+            mtime: git2::IndexTime::new(0, 0),
+            dev: 0,
+            ino: 0,
+            mode: u32::from_str_radix(mode, 8)?,
+            uid: 0,
+            gid: 0,
+            file_size: 0,
+            id: git2::Oid::from_str(oid)?,
+            flags: (path.len().min(0xfff) as u16) | (stage << 12),
+            flags_extended: 0,
+            path: path.to_vec(),
+        })?;
+    }
+
+    Ok(index)
+}
+
+/// Render a diff as patch text
+fn diff_to_text(diff: &git2::Diff) -> Result<String> {
     let mut diff_text = String::new();
-    diff.print(git2::DiffFormat::Patch, |_delta, _hunk, line| {
-        diff_text.push_str(std::str::from_utf8(line.content()).unwrap_or(""));
+    let result = diff.print(git2::DiffFormat::Patch, |delta, _hunk, line| {
+        push_patch_line(&mut diff_text, &delta, &line);
+        true
+    });
+
+    match result {
+        Ok(()) => Ok(diff_text),
+        // Partial clones may lack blob contents locally, so render file by file instead
+        Err(e) if is_missing_object(&e) => diff_to_text_per_file(diff),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Render a diff one file at a time, summarizing files whose contents are not available
+fn diff_to_text_per_file(diff: &git2::Diff) -> Result<String> {
+    Ok(file_patches(diff)?
+        .into_iter()
+        .map(|(_path, patch_text)| patch_text)
+        .collect())
+}
+
+/// Render each file of a diff as its own patch, keyed by path
+fn file_patches(diff: &git2::Diff) -> Result<Vec<(String, String)>> {
+    let mut patches = Vec::new();
+
+    for (idx, delta) in diff.deltas().enumerate() {
+        let path = delta_path(&delta);
+        let patch_text = match render_patch(diff, idx) {
+            Ok(Some(patch_text)) => patch_text,
+            Ok(None) => match large_file_size(&delta) {
+                Some(size) => format!("diff --git a/{path} b/{path}\n{}", large_file_note(size)),
+                None => format!(
+                    "diff --git a/{path} b/{path}\nBinary files a/{path} and b/{path} differ\n"
+                ),
+            },
+            Err(e) if is_missing_object(&e) => summarize_missing_delta(&delta),
+            Err(e) => return Err(e.into()),
+        };
+        patches.push((path, patch_text));
+    }
+
+    Ok(patches)
+}
+
+fn render_patch(diff: &git2::Diff, idx: usize) -> Result<Option<String>, git2::Error> {
+    let Some(mut patch) = git2::Patch::from_diff(diff, idx)? else {
+        return Ok(None);
+    };
+
+    let mut patch_text = String::new();
+    patch.print(&mut |delta, _hunk, line| {
+        push_patch_line(&mut patch_text, &delta, &line);
         true
     })?;
 
-    Ok(diff_text)
+    Ok(Some(patch_text))
+}
+
+/// Append a line of patch output, prefixing content lines with their `+`, `-` or space
+///
+/// libgit2 passes the origin separately from the content, unlike the headers. Files
+/// too large to diff show their size where git would say `Binary files differ`.
+fn push_patch_line(text: &mut String, delta: &git2::DiffDelta, line: &git2::DiffLine) {
+    match line.origin() {
+        origin @ ('+' | '-' | ' ') => text.push(origin),
+        'B' => {
+            if let Some(size) = large_file_size(delta) {
+                text.push_str(&large_file_note(size));
+                return;
+            }
+        }
+        _ => {}
+    }
+    text.push_str(&decode_text(line.content()));
+}
+
+/// Get the size of a file too large to diff, the larger of its two versions
+fn large_file_size(delta: &git2::DiffDelta) -> Option<u64> {
+    let size = delta.old_file().size().max(delta.new_file().size());
+    (size > LARGE_FILE_BYTES).then_some(size)
+}
+
+/// Describe a file that was too large to diff
+fn large_file_note(size: u64) -> String {
+    format!("(large file, {}, not diffed)\n", format_size(size as usize))
+}
+
+/// Count the lines added and removed in one delta of a diff
+///
+/// Partial clones may lack some blobs; those files count as unchanged lines.
+fn delta_line_stats(diff: &git2::Diff, idx: usize) -> Result<(usize, usize)> {
+    match git2::Patch::from_diff(diff, idx) {
+        Ok(Some(patch)) => match patch.line_stats() {
+            Ok((_context, additions, deletions)) => Ok((additions, deletions)),
+            Err(e) if is_missing_object(&e) => Ok((0, 0)),
+            Err(e) => Err(e.into()),
+        },
+        Ok(None) => Ok((0, 0)),
+        Err(e) if is_missing_object(&e) => Ok((0, 0)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Describe a change by path alone when its contents cannot be read
+fn summarize_missing_delta(delta: &git2::DiffDelta) -> String {
+    let path = delta_path(delta);
+    let change_type = change_type_for(delta.status());
+    format!("diff --git a/{path} b/{path}\n({change_type} file, contents not available locally)\n")
+}
+
+/// Check whether an error is caused by an object missing from the local object database
+fn is_missing_object(error: &git2::Error) -> bool {
+    error.code() == git2::ErrorCode::NotFound && error.class() == git2::ErrorClass::Odb
+}
+
+fn delta_path(delta: &git2::DiffDelta) -> String {
+    delta
+        .new_file()
+        .path_bytes()
+        .or_else(|| delta.old_file().path_bytes())
+        .map_or_else(
+            || "unknown".to_string(),
+            |path| decode_text(path).into_owned(),
+        )
+}
+
+/// Cut a line to at most `max_chars` characters, `None` when it already fits
+///
+/// The cut falls on a character boundary, so emoji or CJK text at the limit is never split.
+pub fn truncate_line(line: &str, max_chars: usize) -> Option<&str> {
+    line.char_indices()
+        .nth(max_chars)
+        .map(|(end, _)| &line[..end])
+}
+
+/// Get the path of each file in a patch from its `diff --git` header, after any rename
+pub fn diff_paths(diff: &str) -> Vec<String> {
+    diff.lines()
+        .filter_map(|line| line.strip_prefix("diff --git "))
+        .filter_map(|paths| paths.rsplit_once(" b/"))
+        .map(|(_, path)| path.to_string())
+        .collect()
+}
+
+/// Replace the body of some files' patches, keyed by path, keeping their `diff --git` headers
+pub fn replace_file_patches(diff: &str, replacements: &[(String, String)]) -> String {
+    let mut replaced = String::with_capacity(diff.len());
+    let mut skipping = false;
+    for line in diff.split_inclusive('\n') {
+        if let Some(paths) = line.strip_prefix("diff --git ") {
+            let path = paths.trim_end().rsplit_once(" b/").map(|(_, path)| path);
+            let replacement = replacements
+                .iter()
+                .find(|(replaced_path, _)| Some(replaced_path.as_str()) == path);
+            replaced.push_str(line);
+            if let Some((_, body)) = replacement {
+                replaced.push_str(body);
+            }
+            skipping = replacement.is_some();
+        } else if !skipping {
+            replaced.push_str(line);
+        }
+    }
+    replaced
+}
+
+/// Drop some files' patches from a diff, keyed by path, headers included
+pub fn remove_file_patches(diff: &str, paths: &[String]) -> String {
+    let mut kept = String::with_capacity(diff.len());
+    let mut skipping = false;
+    for line in diff.split_inclusive('\n') {
+        if let Some(header) = line.strip_prefix("diff --git ") {
+            let path = header.trim_end().rsplit_once(" b/").map(|(_, path)| path);
+            skipping = path.is_some_and(|path| paths.iter().any(|removed| removed == path));
+        }
+        if !skipping {
+            kept.push_str(line);
+        }
+    }
+    kept
+}
+
+/// Decode text from git, reading anything that isn't UTF-8 as Latin-1
+///
+/// Legacy encodings are not valid UTF-8, so lossy decoding would turn whole lines into
+/// replacement characters. Every byte is a Latin-1 character, so nothing is dropped.
+pub fn decode_text(bytes: &[u8]) -> Cow<'_, str> {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Cow::Borrowed(text),
+        Err(_) => Cow::Owned(bytes.iter().map(|&byte| char::from(byte)).collect()),
+    }
+}
+
+fn change_type_for(status: Delta) -> DiffChangeType {
+    match status {
+        Delta::Added => DiffChangeType::Added,
+        Delta::Deleted => DiffChangeType::Deleted,
+        Delta::Modified => DiffChangeType::Modified,
+        Delta::Renamed => DiffChangeType::Renamed,
+        Delta::Copied => DiffChangeType::Copied,
+        _ => DiffChangeType::Modified,
+    }
+}
+
+/// Get the staged diff and change stats from the current repository, reusing the cache
+pub fn get_staged_snapshot() -> Result<StagedSnapshot> {
+    let repo = Repository::open(".").context("Not in a git repository")?;
+    get_staged_snapshot_from_repo(&repo)
+}
+
+/// Get the staged diff and change stats, recomputing them only when HEAD or the index changed
+///
+/// The cache is best effort: a missing or unreadable cache file just means a recompute.
+pub fn get_staged_snapshot_from_repo(repo: &Repository) -> Result<StagedSnapshot> {
+    let _span = info_span!(timing::GIT_DIFF).entered();
+    let key = staged_snapshot_key(repo);
+    let cache_path = git_state_path(repo, DIFF_CACHE_FILE_NAME);
+
+    if let Some(key) = &key {
+        let cached = fs::read_to_string(&cache_path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<StagedSnapshot>(&content).ok());
+        if let Some(snapshot) = cached.filter(|snapshot| &snapshot.key == key) {
+            return Ok(snapshot);
+        }
+    }
+
+    let snapshot = StagedSnapshot {
+        key: key.clone().unwrap_or_default(),
+        diff: get_staged_diff_from_repo(repo)?,
+        changes: get_staged_changes_from_repo(repo)?,
+    };
+
+    if key.is_some() {
+        // The cache only saves time, so failing to write it isn't an error
+        if let Ok(json) = serde_json::to_string(&snapshot) {
+            let _ = write_atomic(&cache_path, json);
+        }
+    }
+
+    Ok(snapshot)
+}
+
+/// Get the staged diff if it no longer matches the one messages were generated for
+pub fn staged_diff_if_changed(generated_for: &str) -> Result<Option<String>> {
+    let repo = Repository::open(".").context("Not in a git repository")?;
+    staged_diff_if_changed_from_repo(&repo, generated_for)
+}
+
+/// Get the staged diff if it no longer matches the one messages were generated for
+///
+/// The index can change while the user is choosing a message, e.g. from another
+/// terminal or an editor, so the diff is hashed again just before committing.
+pub fn staged_diff_if_changed_from_repo(
+    repo: &Repository,
+    generated_for: &str,
+) -> Result<Option<String>> {
+    let current = get_staged_snapshot_from_repo(repo)?.diff;
+    Ok((diff_hash(&current)? != diff_hash(generated_for)?).then_some(current))
+}
+
+/// Identify the staged state by HEAD and the checksum git stores at the end of the index
+///
+/// With `index.skipHash`, which `feature.manyFiles` turns on, git writes zeros in
+/// place of the checksum, so there is no key and the diff is always recomputed.
+fn staged_snapshot_key(repo: &Repository) -> Option<String> {
+    const CHECKSUM_LENGTH: u64 = 20;
+
+    let head = repo.head().ok()?.target()?;
+    let mut index = fs::File::open(repo.path().join("index")).ok()?;
+    index.seek(SeekFrom::End(-(CHECKSUM_LENGTH as i64))).ok()?;
+    let mut checksum = Vec::with_capacity(CHECKSUM_LENGTH as usize);
+    index.read_to_end(&mut checksum).ok()?;
+    if checksum.iter().all(|&b| b == 0) {
+        return None;
+    }
+
+    let checksum: String = checksum.iter().map(|b| format!("{b:02x}")).collect();
+    Some(format!("{}:{head}:{checksum}", env!("CARGO_PKG_VERSION")))
 }
 
 /// Get structured information about staged changes
@@ -40,92 +652,39 @@ pub fn get_staged_changes() -> Result<Vec<DiffChange>> {
 
 /// Get structured information about staged changes from a specific repository
 pub fn get_staged_changes_from_repo(repo: &Repository) -> Result<Vec<DiffChange>> {
-    let mut diff_opts = git2::DiffOptions::new();
+    let mut diff_opts = diff_options();
     diff_opts.include_untracked(false);
 
-    let head_tree = repo.head()?.peel_to_tree()?;
-    let mut index = repo.index()?;
-    let _index_tree = repo.find_tree(index.write_tree()?)?;
-
-    let diff = repo.diff_tree_to_index(Some(&head_tree), Some(&index), Some(&mut diff_opts))?;
-
-    let mut changes = Vec::new();
-
-    diff.foreach(
-        &mut |delta, _progress| {
-            let file_path = delta
-                .new_file()
-                .path()
-                .or_else(|| delta.old_file().path())
-                .unwrap_or_else(|| Path::new("unknown"))
-                .to_string_lossy()
-                .to_string();
-
-            let change_type = match delta.status() {
-                Delta::Added => DiffChangeType::Added,
-                Delta::Deleted => DiffChangeType::Deleted,
-                Delta::Modified => DiffChangeType::Modified,
-                Delta::Renamed => DiffChangeType::Renamed,
-                Delta::Copied => DiffChangeType::Copied,
-                _ => DiffChangeType::Modified,
-            };
-
-            changes.push(DiffChange {
-                file_path,
-                change_type,
-                additions: 0, // Will be filled in the hunk callback
-                deletions: 0, // Will be filled in the hunk callback
-            });
+    changes_in_diff(diff_head_to_index(repo, &mut diff_opts)?)
+}
 
-            true
-        },
-        None,
-        None,
-        None,
-    )?;
-
-    // Get line statistics
-    let mut file_stats = std::collections::HashMap::new();
-
-    // First pass: initialize file stats
-    for change in &changes {
-        file_stats.insert(change.file_path.clone(), (0usize, 0usize));
-    }
-
-    // Second pass: count additions and deletions
-    diff.foreach(
-        &mut |_delta, _progress| true,
-        None,
-        None,
-        Some(&mut |delta, _hunk, line| {
-            let file_path = delta
-                .new_file()
-                .path()
-                .or_else(|| delta.old_file().path())
-                .unwrap_or_else(|| Path::new("unknown"))
-                .to_string_lossy()
-                .to_string();
-
-            if let Some((additions, deletions)) = file_stats.get_mut(&file_path) {
-                match line.origin() {
-                    '+' => *additions += 1,
-                    '-' => *deletions += 1,
-                    _ => {}
-                }
+/// List the files changed in a diff with their line counts, detecting renames and copies
+fn changes_in_diff(mut diff: git2::Diff) -> Result<Vec<DiffChange>> {
+    // Similarity reads whole blobs, so it's skipped when a large file would be compared,
+    // and partial clones missing some blobs just go without rename detection
+    if !diff.deltas().any(|delta| large_file_size(&delta).is_some()) {
+        let mut find_opts = git2::DiffFindOptions::new();
+        find_opts.renames(true).copies(true);
+        if let Err(e) = diff.find_similar(Some(&mut find_opts)) {
+            if !is_missing_object(&e) {
+                return Err(e.into());
             }
-            true
-        }),
-    )?;
-
-    // Update changes with line statistics
-    for change in &mut changes {
-        if let Some((additions, deletions)) = file_stats.get(&change.file_path) {
-            change.additions = *additions;
-            change.deletions = *deletions;
         }
     }
 
-    Ok(changes)
+    // One pass over the deltas, reading each file's line counts from its patch by index
+    diff.deltas()
+        .enumerate()
+        .map(|(idx, delta)| {
+            let (additions, deletions) = delta_line_stats(&diff, idx)?;
+            Ok(DiffChange {
+                file_path: delta_path(&delta),
+                change_type: change_type_for(delta.status()),
+                additions,
+                deletions,
+            })
+        })
+        .collect()
 }
 
 /// Check if there are any staged changes
@@ -135,19 +694,48 @@ pub fn has_staged_changes() -> Result<bool> {
 }
 
 /// Check if there are any staged changes in a specific repository
+///
+/// Shell prompts call this often, so it only compares the ids of the index's entries
+/// with HEAD's tree: no file contents are read and nothing is written to the object
+/// database.
 pub fn has_staged_changes_from_repo(repo: &Repository) -> Result<bool> {
-    let mut diff_opts = git2::DiffOptions::new();
+    let mut diff_opts = diff_options();
     diff_opts.include_untracked(false);
 
-    let head_tree = repo.head()?.peel_to_tree()?;
-    let mut index = repo.index()?;
-    let _index_tree = repo.find_tree(index.write_tree()?)?;
-
-    let diff = repo.diff_tree_to_index(Some(&head_tree), Some(&index), Some(&mut diff_opts))?;
+    let diff = diff_head_to_index(repo, &mut diff_opts)?;
 
     Ok(diff.deltas().len() > 0)
 }
 
+/// Changed files with their line counts and the totals across them, as `summary --json` prints
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DiffSummary {
+    /// See [`crate::schema`]
+    #[serde(
+        default = "crate::schema::current_version",
+        deserialize_with = "crate::schema::deserialize_version"
+    )]
+    pub schema_version: u32,
+    pub files: usize,
+    pub additions: usize,
+    pub deletions: usize,
+    pub changes: Vec<DiffChange>,
+}
+
+impl DiffSummary {
+    /// Total up a list of changes
+    pub fn new(changes: Vec<DiffChange>) -> Self {
+        Self {
+            schema_version: crate::schema::SCHEMA_VERSION,
+            files: changes.len(),
+            additions: changes.iter().map(|change| change.additions).sum(),
+            deletions: changes.iter().map(|change| change.deletions).sum(),
+            changes,
+        }
+    }
+}
+
 /// Get a summary of the staged changes
 pub fn get_diff_summary() -> Result<String> {
     let changes = get_staged_changes()?;
@@ -156,10 +744,18 @@ pub fn get_diff_summary() -> Result<String> {
         return Ok("No staged changes found.".to_string());
     }
 
+    Ok(format!(
+        "Staged changes ({} files):\n{}",
+        changes.len(),
+        format_diff_summary(&changes)
+    ))
+}
+
+/// List changed files one per line with their change type and line counts
+pub fn format_diff_summary(changes: &[DiffChange]) -> String {
     let mut summary = String::new();
-    summary.push_str(&format!("Staged changes ({} files):\n", changes.len()));
 
-    for change in &changes {
+    for change in changes {
         let stats = if change.additions > 0 || change.deletions > 0 {
             format!(" (+{}, -{})", change.additions, change.deletions)
         } else {
@@ -172,15 +768,93 @@ pub fn get_diff_summary() -> Result<String> {
         ));
     }
 
-    Ok(summary)
+    summary
+}
+
+/// Width of the `--stat` graph, as `git diff --stat` uses when not writing to a terminal
+pub const STAT_WIDTH: usize = 80;
+
+/// Render changes like `git diff --stat`: a line per file with a bar of `+` and `-`
+///
+/// Bars are scaled down when the largest change doesn't fit in `width` columns,
+/// and long paths are shortened from the front to `...`.
+pub fn format_diff_stat(changes: &[DiffChange], width: usize) -> String {
+    let max_name_width = width / 2;
+    let names: Vec<String> = changes
+        .iter()
+        .map(|change| shorten_path(&change.file_path, max_name_width))
+        .collect();
+    let name_width = names
+        .iter()
+        .map(|name| name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let max_change = changes
+        .iter()
+        .map(|change| change.additions + change.deletions)
+        .max()
+        .unwrap_or(0);
+    let count_width = max_change.to_string().len();
+    // " name | count " around the bar
+    let bar_width = width.saturating_sub(name_width + count_width + 5).max(1);
+
+    let scale = |n: usize| {
+        if n == 0 || max_change <= bar_width {
+            n
+        } else {
+            n * (bar_width - 1) / max_change + 1
+        }
+    };
+
+    let mut stat = String::new();
+    for (change, name) in changes.iter().zip(&names) {
+        let total = change.additions + change.deletions;
+        let bar_len = scale(total);
+        let plus = scale(change.additions).min(bar_len);
+        let line = format!(
+            " {name:<name_width$} | {total:>count_width$} {}{}",
+            "+".repeat(plus),
+            "-".repeat(bar_len - plus)
+        );
+        stat.push_str(line.trim_end());
+        stat.push('\n');
+    }
+
+    let additions: usize = changes.iter().map(|change| change.additions).sum();
+    let deletions: usize = changes.iter().map(|change| change.deletions).sum();
+    stat.push_str(&format!(
+        " {} file{} changed, {additions} insertion{}(+), {deletions} deletion{}(-)\n",
+        changes.len(),
+        plural(changes.len()),
+        plural(additions),
+        plural(deletions)
+    ));
+    stat
+}
+
+/// Shorten a path to `width` columns by replacing its start with `...`
+fn shorten_path(path: &str, width: usize) -> String {
+    let len = path.chars().count();
+    if len <= width || width <= 3 {
+        return path.to_string();
+    }
+    let tail: String = path.chars().skip(len - (width - 3)).collect();
+    format!("...{tail}")
+}
+
+fn plural(n: usize) -> &'static str {
+    if n == 1 {
+        ""
+    } else {
+        "s"
+    }
 }
 
 /// Filter diff text to remove sensitive information
 pub fn sanitize_diff(diff: &str) -> String {
-    let lines: Vec<&str> = diff.lines().collect();
-    let mut sanitized = String::new();
+    let mut sanitized = String::with_capacity(diff.len());
 
-    for line in lines {
+    for line in diff.lines() {
         // Skip lines that might contain sensitive information
         if line.contains("password")
             || line.contains("secret")
@@ -192,8 +866,8 @@ pub fn sanitize_diff(diff: &str) -> String {
         }
 
         // Limit line length to prevent extremely long lines
-        if line.len() > 200 {
-            sanitized.push_str(&line[..200]);
+        if let Some(truncated) = truncate_line(line, 200) {
+            sanitized.push_str(truncated);
             sanitized.push_str("... (truncated)\n");
         } else {
             sanitized.push_str(line);
@@ -207,37 +881,32 @@ pub fn sanitize_diff(diff: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::git::fixtures::{git, init_repo, init_repo_in};
     use std::fs;
+    use std::path::Path;
     use tempfile::TempDir;
 
     fn create_test_repo() -> Result<(TempDir, Repository)> {
-        let temp_dir = TempDir::new()?;
-        let repo = Repository::init(temp_dir.path())?;
-
-        // Create initial commit
-        let signature = git2::Signature::now("Test User", "test@example.com")?;
-        let tree_id = {
-            let mut index = repo.index()?;
-            index.write_tree()?
-        };
-
-        // Separate the tree creation from the commit to avoid borrow conflicts
-        let commit_result = {
-            let tree = repo.find_tree(tree_id)?;
-            repo.commit(
-                Some("HEAD"),
-                &signature,
-                &signature,
-                "Initial commit",
-                &tree,
-                &[],
-            )
-        };
-        commit_result?;
+        let temp_dir = init_repo();
+        git(
+            temp_dir.path(),
+            &["commit", "-q", "--allow-empty", "-m", "Initial commit"],
+        );
+        let repo = Repository::open(temp_dir.path())?;
 
         Ok((temp_dir, repo))
     }
 
+    #[test]
+    fn test_diff_paths() {
+        let diff = "diff --git a/src/old.rs b/src/new.rs\nsimilarity index 90%\n\
+                    diff --git a/migrations/1_init/up.sql b/migrations/1_init/up.sql\n+CREATE TABLE t ();\n";
+        assert_eq!(
+            diff_paths(diff),
+            vec!["src/new.rs", "migrations/1_init/up.sql"]
+        );
+    }
+
     #[test]
     fn test_no_staged_changes() -> Result<()> {
         let (_temp_dir, repo) = create_test_repo()?;
@@ -269,7 +938,318 @@ mod tests {
 
         let diff = get_staged_diff_from_repo(&repo)?;
         assert!(!diff.is_empty());
-        assert!(diff.contains("Hello, world!"));
+        assert!(diff.contains("\n+Hello, world!"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_large_files_are_summarized() -> Result<()> {
+        let (temp_dir, repo) = create_test_repo()?;
+        let line = "INSERT INTO t VALUES (1);\n";
+        let size = LARGE_FILE_BYTES as usize + line.len();
+        fs::write(
+            temp_dir.path().join("dump.sql"),
+            line.repeat(size / line.len()),
+        )?;
+        fs::write(temp_dir.path().join("a.txt"), "a\n")?;
+        let mut index = repo.index()?;
+        index.add_path(Path::new("dump.sql"))?;
+        index.add_path(Path::new("a.txt"))?;
+        index.write()?;
+
+        let diff = get_staged_diff_from_repo(&repo)?;
+        assert!(diff.contains("diff --git a/dump.sql b/dump.sql"));
+        assert!(diff.contains("(large file, 10.0 MiB, not diffed)\n"));
+        assert!(!diff.contains("INSERT INTO"));
+        assert!(diff.contains("+a\n"));
+
+        let changes = get_staged_changes_from_repo(&repo)?;
+        let dump = changes.iter().find(|c| c.file_path == "dump.sql").unwrap();
+        assert_eq!((dump.additions, dump.deletions), (0, 0));
+        assert_eq!(
+            staged_file_versions_from_repo(&repo, "dump.sql")?,
+            (None, None)
+        );
+
+        let patches = get_staged_file_diffs_from_repo(&repo)?;
+        assert!(patches
+            .iter()
+            .any(|(path, patch)| path == "dump.sql" && patch.contains("large file")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_has_staged_changes_after_unstaging() -> Result<()> {
+        let (temp_dir, repo) = create_test_repo()?;
+        fs::write(temp_dir.path().join("a.txt"), "a\n")?;
+        let mut index = repo.index()?;
+        index.add_path(Path::new("a.txt"))?;
+        index.write()?;
+        assert!(has_staged_changes_from_repo(&repo)?);
+
+        index.remove_path(Path::new("a.txt"))?;
+        index.write()?;
+        assert!(!has_staged_changes_from_repo(&repo)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_has_staged_changes_writes_no_objects() -> Result<()> {
+        let (temp_dir, repo) = create_test_repo()?;
+        fs::create_dir(temp_dir.path().join("src"))?;
+        fs::write(temp_dir.path().join("src/a.txt"), "a\n")?;
+        let mut index = repo.index()?;
+        index.add_path(Path::new("src/a.txt"))?;
+        index.write()?;
+
+        let count_objects = || -> Result<usize> {
+            let mut count = 0;
+            repo.odb()?.foreach(|_| {
+                count += 1;
+                true
+            })?;
+            Ok(count)
+        };
+        let before = count_objects()?;
+        assert!(has_staged_changes_from_repo(&repo)?);
+        assert_eq!(count_objects()?, before);
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_text() {
+        assert_eq!(decode_text(b"caf\xc3\xa9"), "café");
+        assert_eq!(decode_text(b"caf\xe9"), "café");
+    }
+
+    #[test]
+    fn test_staged_diff_keeps_legacy_encodings() -> Result<()> {
+        let (temp_dir, repo) = create_test_repo()?;
+
+        fs::write(temp_dir.path().join("menu.txt"), b"caf\xe9 cr\xe8me\n")?;
+        let mut index = repo.index()?;
+        index.add_path(Path::new("menu.txt"))?;
+        index.write()?;
+
+        let snapshot = get_staged_snapshot_from_repo(&repo)?;
+        assert!(snapshot.diff.contains("café crème"));
+        assert_eq!(snapshot.changes[0].additions, 1);
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_paths() -> Result<()> {
+        use std::os::unix::ffi::OsStrExt;
+
+        let (temp_dir, repo) = create_test_repo()?;
+        let name = std::ffi::OsStr::from_bytes(b"r\xe9sum\xe9.txt");
+        fs::write(temp_dir.path().join(name), "hello\n")?;
+        let mut index = repo.index()?;
+        index.add_path(Path::new(name))?;
+        index.write()?;
+
+        let snapshot = get_staged_snapshot_from_repo(&repo)?;
+        assert_eq!(snapshot.changes[0].file_path, "résumé.txt");
+        assert!(snapshot.diff.contains("hello"));
+
+        // The index read through git, as for sparse checkouts, keeps the path's bytes
+        let from_git = index_from_git(&repo)?;
+        assert!(from_git.iter().any(|entry| entry.path == name.as_bytes()));
+        assert!(from_git.get_path(Path::new(name), 0).is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_staged_snapshot_cache() -> Result<()> {
+        let (temp_dir, repo) = create_test_repo()?;
+
+        fs::write(temp_dir.path().join("a.txt"), "first file\n")?;
+        let mut index = repo.index()?;
+        index.add_path(Path::new("a.txt"))?;
+        index.write()?;
+
+        let snapshot = get_staged_snapshot_from_repo(&repo)?;
+        assert!(snapshot.diff.contains("first file"));
+        assert_eq!(snapshot.changes.len(), 1);
+        assert_eq!(snapshot.changes[0].additions, 1);
+
+        // An unchanged index is served from the cache without walking the diff again
+        let cache_path = repo.path().join(DIFF_CACHE_FILE_NAME);
+        let mut cached = snapshot.clone();
+        cached.diff = "cached diff".to_string();
+        fs::write(&cache_path, serde_json::to_string(&cached)?)?;
+        assert_eq!(get_staged_snapshot_from_repo(&repo)?.diff, "cached diff");
+
+        // Staging more changes the index checksum and invalidates the cache
+        fs::write(temp_dir.path().join("b.txt"), "second file\n")?;
+        index.add_path(Path::new("b.txt"))?;
+        index.write()?;
+
+        let snapshot = get_staged_snapshot_from_repo(&repo)?;
+        assert!(snapshot.diff.contains("second file"));
+        assert_eq!(snapshot.changes.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_staged_snapshot_cache_without_index_checksum() -> Result<()> {
+        const CHECKSUM_LENGTH: usize = 20;
+
+        let (temp_dir, repo) = create_test_repo()?;
+        repo.config()?.set_bool("index.skipHash", true)?;
+        fs::write(temp_dir.path().join("a.txt"), "first file\n")?;
+        let mut index = repo.index()?;
+        index.add_path(Path::new("a.txt"))?;
+        index.write()?;
+
+        // Git 2.40 and later leave the checksum zeroed under `index.skipHash`
+        let index_path = repo.path().join("index");
+        let mut content = fs::read(&index_path)?;
+        let start = content.len() - CHECKSUM_LENGTH;
+        content[start..].fill(0);
+        fs::write(&index_path, content)?;
+        assert_eq!(staged_snapshot_key(&repo), None);
+
+        let head = repo.head()?.target().unwrap();
+        let zeroed = format!(
+            "{}:{head}:{}",
+            env!("CARGO_PKG_VERSION"),
+            "0".repeat(CHECKSUM_LENGTH * 2)
+        );
+        let stale = StagedSnapshot {
+            key: zeroed,
+            diff: "cached diff".to_string(),
+            changes: Vec::new(),
+        };
+        fs::write(
+            repo.path().join(DIFF_CACHE_FILE_NAME),
+            serde_json::to_string(&stale)?,
+        )?;
+        let generated_for = get_staged_snapshot_from_repo(&repo)?.diff;
+        assert!(generated_for.contains("first file"));
+        assert_eq!(
+            staged_diff_if_changed_from_repo(&repo, "cached diff")?,
+            Some(generated_for)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_staged_diff_if_changed() -> Result<()> {
+        let (temp_dir, repo) = create_test_repo()?;
+
+        fs::write(temp_dir.path().join("a.txt"), "first file\n")?;
+        let mut index = repo.index()?;
+        index.add_path(Path::new("a.txt"))?;
+        index.write()?;
+        let generated_for = get_staged_snapshot_from_repo(&repo)?.diff;
+        assert_eq!(
+            staged_diff_if_changed_from_repo(&repo, &generated_for)?,
+            None
+        );
+
+        fs::write(temp_dir.path().join("a.txt"), "first file, edited\n")?;
+        index.add_path(Path::new("a.txt"))?;
+        index.write()?;
+        let current = staged_diff_if_changed_from_repo(&repo, &generated_for)?.unwrap();
+        assert!(current.contains("first file, edited"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_staged_file_diffs() -> Result<()> {
+        let (temp_dir, repo) = create_test_repo()?;
+
+        fs::write(temp_dir.path().join("a.txt"), "first file")?;
+        fs::write(temp_dir.path().join("b.txt"), "second file")?;
+        let mut index = repo.index()?;
+        index.add_path(Path::new("a.txt"))?;
+        index.add_path(Path::new("b.txt"))?;
+        index.write()?;
+
+        let diffs = get_staged_file_diffs_from_repo(&repo)?;
+        assert_eq!(diffs.len(), 2);
+        assert_eq!(diffs[0].0, "a.txt");
+        assert!(diffs[0].1.contains("first file"));
+        assert!(!diffs[0].1.contains("second file"));
+        assert_eq!(diffs[1].0, "b.txt");
+        assert!(diffs[1].1.contains("second file"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_staged_hunks() -> Result<()> {
+        let (temp_dir, repo) = create_test_repo()?;
+        let lines: Vec<String> = (1..=20).map(|n| format!("line {n}")).collect();
+        fs::write(temp_dir.path().join("a.txt"), lines.join("\n") + "\n")?;
+        let mut index = repo.index()?;
+        index.add_path(Path::new("a.txt"))?;
+        index.write()?;
+        let signature = git2::Signature::now("Test User", "test@example.com")?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+        let parent = repo.head()?.peel_to_commit()?;
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            "Add a",
+            &tree,
+            &[&parent],
+        )?;
+
+        let mut edited = lines.clone();
+        edited[1] = "line two".to_string();
+        edited[18] = "line nineteen".to_string();
+        fs::write(temp_dir.path().join("a.txt"), edited.join("\n"))?;
+        fs::write(temp_dir.path().join("empty.txt"), "")?;
+        index.add_path(Path::new("a.txt"))?;
+        index.add_path(Path::new("empty.txt"))?;
+        index.write()?;
+
+        let hunks = get_staged_hunks_from_repo(&repo)?;
+        assert_eq!(hunks.len(), 3);
+        assert!(hunks[0]
+            .file_header
+            .starts_with("diff --git a/a.txt b/a.txt"));
+        let first = hunks[0].text.as_ref().unwrap();
+        assert!(first.starts_with("@@"));
+        assert!(first.contains("-line 2\n+line two\n"));
+        let second = hunks[1].text.as_ref().unwrap();
+        assert!(second.contains("+line nineteen\n"));
+        assert!(second.contains("\\ No newline at end of file"));
+        assert_eq!(hunks[2].file_path, "empty.txt");
+        assert_eq!(hunks[2].text, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_working_tree_diff_includes_unstaged_changes() -> Result<()> {
+        let (temp_dir, repo) = create_test_repo()?;
+
+        fs::write(temp_dir.path().join("staged.txt"), "staged content")?;
+        let mut index = repo.index()?;
+        index.add_path(std::path::Path::new("staged.txt"))?;
+        index.write()?;
+
+        // Untracked files are not stashed by default, so they are left out
+        fs::write(temp_dir.path().join("untracked.txt"), "untracked content")?;
+
+        let diff = get_working_tree_diff_from_repo(&repo)?;
+        assert!(diff.contains("staged content"));
+        assert!(!diff.contains("untracked content"));
+
+        fs::write(temp_dir.path().join("staged.txt"), "edited later")?;
+        let diff = get_working_tree_diff_from_repo(&repo)?;
+        assert!(diff.contains("edited later"));
 
         Ok(())
     }
@@ -295,6 +1275,301 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_format_diff_summary() {
+        let changes = vec![
+            DiffChange {
+                file_path: "src/lib.rs".to_string(),
+                change_type: DiffChangeType::Modified,
+                additions: 3,
+                deletions: 1,
+            },
+            DiffChange {
+                file_path: "logo.png".to_string(),
+                change_type: DiffChangeType::Added,
+                additions: 0,
+                deletions: 0,
+            },
+        ];
+
+        assert_eq!(
+            format_diff_summary(&changes),
+            "  modified src/lib.rs (+3, -1)\n  added logo.png\n"
+        );
+    }
+
+    #[test]
+    fn test_format_diff_stat() {
+        let change = |file_path: &str, additions, deletions| DiffChange {
+            file_path: file_path.to_string(),
+            change_type: DiffChangeType::Modified,
+            additions,
+            deletions,
+        };
+
+        let changes = vec![change("src/lib.rs", 3, 1), change("README.md", 1, 0)];
+        assert_eq!(
+            format_diff_stat(&changes, STAT_WIDTH),
+            " src/lib.rs | 4 +++-\n README.md  | 1 +\n 2 files changed, 4 insertions(+), 1 deletion(-)\n"
+        );
+
+        // Bars scale to fit the width, and long paths lose their start
+        let changes = vec![
+            change(
+                "src/a/very/long/path/to/some/deeply/nested/module/file.rs",
+                300,
+                100,
+            ),
+            change("b.rs", 2, 0),
+        ];
+        let stat = format_diff_stat(&changes, 60);
+        assert!(
+            stat.lines().all(|line| line.chars().count() <= 60),
+            "{stat}"
+        );
+        assert!(stat.starts_with(" ...eeply/nested/module/file.rs | 400 "));
+        assert!(stat.contains("|   2 +\n"));
+    }
+
+    #[test]
+    fn test_remove_file_patches() {
+        let diff = "diff --git a/Cargo.lock b/Cargo.lock\n\
+                    @@ -1 +1 @@\n\
+                    -version = 1\n\
+                    +version = 2\n\
+                    diff --git a/src/lib.rs b/src/lib.rs\n\
+                    @@ -0,0 +1 @@\n\
+                    +pub fn login() {}\n";
+
+        let kept = remove_file_patches(diff, &["Cargo.lock".to_string()]);
+        assert_eq!(diff_paths(&kept), vec!["src/lib.rs"]);
+        assert!(kept.ends_with("+pub fn login() {}\n"));
+        assert_eq!(remove_file_patches(diff, &[]), diff);
+    }
+
+    #[test]
+    fn test_diff_summary_totals() {
+        let change = |file_path: &str, additions, deletions| DiffChange {
+            file_path: file_path.to_string(),
+            change_type: DiffChangeType::Modified,
+            additions,
+            deletions,
+        };
+        let summary = DiffSummary::new(vec![change("a.rs", 3, 1), change("b.rs", 2, 4)]);
+
+        assert_eq!(
+            (summary.files, summary.additions, summary.deletions),
+            (2, 5, 5)
+        );
+        let json = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["changes"][0]["change_type"], "modified");
+    }
+
+    #[test]
+    fn test_get_staged_changes_renamed_file() -> Result<()> {
+        let (temp_dir, _repo) = create_test_repo()?;
+        let dir = temp_dir.path();
+        let content: String = (1..=20).map(|i| format!("line {i}\n")).collect();
+        fs::write(dir.join("old.txt"), &content)?;
+        git(dir, &["add", "old.txt"]);
+        git(
+            dir,
+            &[
+                "-c",
+                "user.name=Test",
+                "-c",
+                "user.email=t@example.com",
+                "commit",
+                "-q",
+                "-m",
+                "Add old",
+            ],
+        );
+
+        git(dir, &["mv", "old.txt", "new.txt"]);
+        fs::write(dir.join("new.txt"), format!("{content}line 21\n"))?;
+        git(dir, &["add", "new.txt"]);
+
+        let repo = Repository::open(dir)?;
+        let changes = get_staged_changes_from_repo(&repo)?;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].file_path, "new.txt");
+        assert_eq!(changes[0].change_type, DiffChangeType::Renamed);
+        assert_eq!((changes[0].additions, changes[0].deletions), (1, 0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_staged_changes_copied_file() -> Result<()> {
+        let (temp_dir, _repo) = create_test_repo()?;
+        let dir = temp_dir.path();
+        let content: String = (1..=20).map(|i| format!("line {i}\n")).collect();
+        fs::write(dir.join("source.txt"), &content)?;
+        git(dir, &["add", "source.txt"]);
+        git(
+            dir,
+            &[
+                "-c",
+                "user.name=Test",
+                "-c",
+                "user.email=t@example.com",
+                "commit",
+                "-q",
+                "-m",
+                "Add source",
+            ],
+        );
+
+        // Copies are only detected from files that are modified in the same change
+        fs::write(
+            dir.join("source.txt"),
+            format!("{content}line 21\nline 22\n"),
+        )?;
+        fs::write(dir.join("copy.txt"), &content)?;
+        git(dir, &["add", "source.txt", "copy.txt"]);
+
+        let repo = Repository::open(dir)?;
+        let mut changes = get_staged_changes_from_repo(&repo)?;
+        changes.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].file_path, "copy.txt");
+        assert_eq!(changes[0].change_type, DiffChangeType::Copied);
+        assert_eq!((changes[0].additions, changes[0].deletions), (0, 0));
+        assert_eq!(changes[1].file_path, "source.txt");
+        assert_eq!(changes[1].change_type, DiffChangeType::Modified);
+        assert_eq!((changes[1].additions, changes[1].deletions), (2, 0));
+
+        Ok(())
+    }
+
+    /// Create a blobless partial clone whose HEAD blobs are not present locally
+    fn create_partial_clone() -> Result<(TempDir, std::path::PathBuf)> {
+        let temp_dir = TempDir::new()?;
+        let source = temp_dir.path().join("source");
+        let clone = temp_dir.path().join("clone");
+        fs::create_dir(&source)?;
+
+        init_repo_in(&source);
+        git(&source, &["config", "uploadpack.allowFilter", "true"]);
+        fs::write(source.join("a.txt"), "hello\n")?;
+        fs::write(source.join("b.txt"), "world\n")?;
+        git(&source, &["add", "."]);
+        git(&source, &["commit", "-q", "-m", "Initial commit"]);
+
+        let url = format!("file://{}", source.display());
+        git(
+            temp_dir.path(),
+            &[
+                "clone",
+                "-q",
+                "--filter=blob:none",
+                "--no-checkout",
+                &url,
+                "clone",
+            ],
+        );
+        git(&clone, &["read-tree", "HEAD"]);
+        // With the source gone the missing blobs can't be fetched lazily
+        fs::remove_dir_all(&source)?;
+
+        Ok((temp_dir, clone))
+    }
+
+    #[test]
+    fn test_partial_clone_with_missing_blobs() -> Result<()> {
+        let (_temp_dir, clone) = create_partial_clone()?;
+
+        fs::write(clone.join("a.txt"), "hello again\n")?;
+        fs::write(clone.join("c.txt"), "new file\n")?;
+        git(&clone, &["add", "a.txt", "c.txt"]);
+
+        let repo = Repository::open(&clone)?;
+        assert!(has_staged_changes_from_repo(&repo)?);
+
+        let diff = get_staged_diff_from_repo(&repo)?;
+        assert!(diff.contains("a.txt"));
+        assert!(diff.contains("contents not available locally"));
+        assert!(diff.contains("new file"));
+
+        let changes = get_staged_changes_from_repo(&repo)?;
+        assert_eq!(changes.len(), 2);
+        let added = changes.iter().find(|c| c.file_path == "c.txt").unwrap();
+        assert_eq!(added.change_type, DiffChangeType::Added);
+        assert_eq!(added.additions, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sparse_checkout() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let dir = temp_dir.path();
+
+        init_repo_in(dir);
+        fs::create_dir_all(dir.join("app"))?;
+        fs::create_dir_all(dir.join("docs"))?;
+        fs::write(dir.join("app/main.rs"), "fn main() {}\n")?;
+        fs::write(dir.join("docs/guide.md"), "# Guide\n")?;
+        git(dir, &["add", "."]);
+        git(dir, &["commit", "-q", "-m", "Initial commit"]);
+        git(
+            dir,
+            &["sparse-checkout", "init", "--cone", "--sparse-index"],
+        );
+        git(dir, &["sparse-checkout", "set", "app"]);
+
+        fs::write(dir.join("app/main.rs"), "fn main() { run(); }\n")?;
+        git(dir, &["add", "app/main.rs"]);
+
+        let repo = Repository::open(dir)?;
+        let diff = get_staged_diff_from_repo(&repo)?;
+        assert!(diff.contains("run();"));
+        assert!(!diff.contains("guide.md"));
+
+        let changes = get_staged_changes_from_repo(&repo)?;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].file_path, "app/main.rs");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_diff_in_bare_repository() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let work = temp_dir.path().join("work");
+        fs::create_dir(&work)?;
+
+        init_repo_in(&work);
+        fs::write(work.join("a.txt"), "first\n")?;
+        git(&work, &["add", "."]);
+        git(&work, &["commit", "-q", "-m", "Initial commit"]);
+        fs::write(work.join("a.txt"), "second\n")?;
+        git(&work, &["commit", "-q", "-am", "Update a"]);
+        fs::write(work.join("b.txt"), "third\n")?;
+        git(&work, &["add", "."]);
+        git(&work, &["commit", "-q", "-m", "Add b"]);
+        git(
+            temp_dir.path(),
+            &["clone", "-q", "--bare", "work", "bare.git"],
+        );
+
+        let repo = Repository::open(temp_dir.path().join("bare.git"))?;
+        assert!(repo.is_bare());
+
+        let diff = get_range_diff_from_repo(&repo, "HEAD~2..HEAD")?;
+        assert!(diff.contains("second"));
+        assert!(diff.contains("third"));
+
+        let diff = get_range_diff_from_repo(&repo, "HEAD")?;
+        assert!(diff.contains("third"));
+        assert!(!diff.contains("second"));
+
+        assert!(get_range_diff_from_repo(&repo, "nope..HEAD").is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_sanitize_diff() {
         let diff = r#"
@@ -313,4 +1588,17 @@ mod tests {
         assert!(sanitized.contains("normal line"));
         assert!(sanitized.contains("another normal line"));
     }
+
+    #[test]
+    fn test_truncate_line_keeps_characters_whole() {
+        assert_eq!(truncate_line("short", 10), None);
+        assert_eq!(truncate_line("exactly", 7), None);
+        assert_eq!(truncate_line("日本語のテキスト", 3), Some("日本語"));
+        assert_eq!(truncate_line("ab🎉cd", 3), Some("ab🎉"));
+
+        // A four-byte emoji straddling the 200th byte
+        let line = format!("+{}🎉🎉🎉", "x".repeat(198));
+        let sanitized = sanitize_diff(&line);
+        assert!(sanitized.starts_with(&format!("+{}🎉... (truncated)", "x".repeat(198))));
+    }
 }
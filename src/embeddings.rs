@@ -0,0 +1,312 @@
+//! Local index of commit diff embeddings used to find similar past commits
+
+use crate::eval::collect_history_samples;
+use crate::paths::git_state_path;
+use crate::privacy::{PrivacyFilter, PrivacyLevel};
+use crate::providers::AIProvider;
+use crate::state::{with_lock, write_atomic};
+use anyhow::{Context, Result};
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Name of the index file kept inside the git directory
+pub const INDEX_FILE_NAME: &str = "committor-embeddings.json";
+
+/// Characters of a diff sent to the embedding model
+pub const EMBEDDING_MAX_CHARS: usize = 8000;
+
+/// Embedding behaviour read from the `[embeddings]` settings section
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmbeddingSettings {
+    /// Look up similar past commits and show their messages to the model
+    pub enabled: bool,
+    /// Embedding model, defaulting to one suited to the configured provider
+    pub model: Option<String>,
+    /// Number of similar commits included in the prompt
+    pub examples: usize,
+}
+
+impl Default for EmbeddingSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            model: None,
+            examples: 3,
+        }
+    }
+}
+
+/// A past commit and the embedding of its diff
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub commit_id: String,
+    pub subject: String,
+    pub vector: Vec<f64>,
+}
+
+/// Embeddings of past commits, all produced by the same model
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EmbeddingIndex {
+    pub model: String,
+    pub entries: Vec<IndexEntry>,
+}
+
+impl EmbeddingIndex {
+    /// Load an index file, returning `None` when it doesn't exist yet
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read embedding index {}", path.display()))?;
+        let index = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse embedding index {}", path.display()))?;
+        Ok(Some(index))
+    }
+
+    /// Write the index file
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let content = serde_json::to_string(self)?;
+        write_atomic(path, content)
+            .with_context(|| format!("Failed to write embedding index {}", path.display()))
+    }
+
+    /// Add the entries of another index for the same model that this one lacks
+    pub fn merge(&mut self, other: EmbeddingIndex) {
+        if other.model != self.model {
+            return;
+        }
+        let known: HashSet<String> = self.entries.iter().map(|e| e.commit_id.clone()).collect();
+        self.entries.extend(
+            other
+                .entries
+                .into_iter()
+                .filter(|entry| !known.contains(&entry.commit_id)),
+        );
+    }
+
+    /// Get the entries most similar to the vector, best match first
+    pub fn most_similar(&self, vector: &[f64], count: usize) -> Vec<&IndexEntry> {
+        let mut scored: Vec<(f64, &IndexEntry)> = self
+            .entries
+            .iter()
+            .map(|entry| (cosine_similarity(vector, &entry.vector), entry))
+            .collect();
+
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored
+            .into_iter()
+            .take(count)
+            .map(|(_, entry)| entry)
+            .collect()
+    }
+}
+
+/// Cosine similarity of two vectors, 0.0 when they can't be compared
+pub fn cosine_similarity(a: &[f64], b: &[f64]) -> f64 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+
+    let dot: f64 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f64>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f64>().sqrt();
+
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+/// Get the path of the index file for a repository
+pub fn index_path_from_repo(repo: &Repository) -> PathBuf {
+    git_state_path(repo, INDEX_FILE_NAME)
+}
+
+/// Shorten a diff to the part sent to the embedding model
+fn embedding_text(diff: &str) -> String {
+    diff.chars().take(EMBEDDING_MAX_CHARS).collect()
+}
+
+/// Embed recent commits that are not yet in the index, returning how many were added
+///
+/// An index built with a different model can't be compared against, so it is
+/// replaced, as it is when `rebuild` is set.
+pub async fn update_index(
+    repo: &Repository,
+    provider: &dyn AIProvider,
+    model: &str,
+    privacy: PrivacyLevel,
+    last: usize,
+    rebuild: bool,
+) -> Result<usize> {
+    privacy.require_content("Indexing commits")?;
+    let path = index_path_from_repo(repo);
+    let mut index = match EmbeddingIndex::load(&path)? {
+        Some(index) if !rebuild && index.model == model => index,
+        _ => EmbeddingIndex {
+            model: model.to_string(),
+            entries: Vec::new(),
+        },
+    };
+
+    let known: HashSet<String> = index.entries.iter().map(|e| e.commit_id.clone()).collect();
+    let mut added = 0;
+    for sample in collect_history_samples(repo, last)? {
+        if known.contains(&sample.commit_id) {
+            continue;
+        }
+
+        // Diffs are filtered the same way as the diffs they will be compared against
+        let diff = PrivacyFilter::new(privacy)
+            .diff(&sample.diff)
+            .unwrap_or_default();
+        let vector = provider
+            .embed(model, &embedding_text(&diff))
+            .await
+            .with_context(|| format!("Failed to embed commit {}", sample.commit_id))?;
+
+        index.entries.push(IndexEntry {
+            commit_id: sample.commit_id,
+            subject: sample.subject,
+            vector,
+        });
+        added += 1;
+    }
+
+    // Another run may have indexed commits while this one was embedding; keep them
+    with_lock(&path, || {
+        if !rebuild {
+            if let Some(saved) = EmbeddingIndex::load(&path)? {
+                index.merge(saved);
+            }
+        }
+        index.save(&path)
+    })?;
+    Ok(added)
+}
+
+/// Find the subjects of past commits whose diffs are most similar to this one
+///
+/// Returns nothing when there is no index for the model yet; the lookup only
+/// adds context, so failures are logged rather than returned.
+pub async fn similar_commits(
+    provider: &dyn AIProvider,
+    settings: &EmbeddingSettings,
+    model: &str,
+    diff: &str,
+) -> Vec<String> {
+    let Ok(repo) = Repository::discover(".") else {
+        return Vec::new();
+    };
+
+    let index = match EmbeddingIndex::load(&index_path_from_repo(&repo)) {
+        Ok(Some(index)) if index.model == model => index,
+        Ok(_) => return Vec::new(),
+        Err(e) => {
+            warn!("Ignoring embedding index: {}", e);
+            return Vec::new();
+        }
+    };
+
+    match provider.embed(model, &embedding_text(diff)).await {
+        Ok(vector) => index
+            .most_similar(&vector, settings.examples)
+            .into_iter()
+            .map(|entry| entry.subject.clone())
+            .collect(),
+        Err(e) => {
+            warn!("Failed to embed diff: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn entry(subject: &str, vector: Vec<f64>) -> IndexEntry {
+        IndexEntry {
+            commit_id: subject.to_string(),
+            subject: subject.to_string(),
+            vector,
+        }
+    }
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-9);
+        assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]).abs() < 1e-9);
+        assert_eq!(cosine_similarity(&[1.0], &[1.0, 0.0]), 0.0);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn test_most_similar() {
+        let index = EmbeddingIndex {
+            model: "test".to_string(),
+            entries: vec![
+                entry("docs: update readme", vec![0.0, 1.0]),
+                entry("fix(api): handle timeouts", vec![1.0, 0.1]),
+                entry("fix(api): retry requests", vec![1.0, 0.3]),
+            ],
+        };
+
+        let subjects: Vec<&str> = index
+            .most_similar(&[1.0, 0.0], 2)
+            .iter()
+            .map(|e| e.subject.as_str())
+            .collect();
+        assert_eq!(
+            subjects,
+            vec!["fix(api): handle timeouts", "fix(api): retry requests"]
+        );
+    }
+
+    #[test]
+    fn test_index_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(INDEX_FILE_NAME);
+        assert_eq!(EmbeddingIndex::load(&path).unwrap(), None);
+
+        let index = EmbeddingIndex {
+            model: "nomic-embed-text".to_string(),
+            entries: vec![entry("feat: add login", vec![0.25, -0.5])],
+        };
+        index.save(&path).unwrap();
+        assert_eq!(EmbeddingIndex::load(&path).unwrap(), Some(index));
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut index = EmbeddingIndex {
+            model: "nomic-embed-text".to_string(),
+            entries: vec![entry("feat: add login", vec![1.0])],
+        };
+        let saved = EmbeddingIndex {
+            model: "nomic-embed-text".to_string(),
+            entries: vec![
+                entry("feat: add login", vec![1.0]),
+                entry("fix: handle timeouts", vec![0.5]),
+            ],
+        };
+        let other_model = EmbeddingIndex {
+            model: "text-embedding-3-small".to_string(),
+            entries: vec![entry("docs: update readme", vec![0.0])],
+        };
+
+        index.merge(saved);
+        index.merge(other_model);
+        let subjects: Vec<&str> = index.entries.iter().map(|e| e.subject.as_str()).collect();
+        assert_eq!(subjects, vec!["feat: add login", "fix: handle timeouts"]);
+    }
+}
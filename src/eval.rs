@@ -0,0 +1,309 @@
+//! Offline evaluation by replaying past commits through the current model and prompt
+
+use crate::commit::{is_valid_commit_message, parse_commit_message};
+use crate::diff::get_commit_diff_from_repo;
+use crate::postprocess::normalize_message;
+use crate::privacy::{PrivacyFilter, PrivacyLevel};
+use crate::prompt::{create_commit_prompt, PROMPT_VERSION};
+use crate::providers::AIProvider;
+use anyhow::Result;
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+/// A past commit used as an evaluation sample
+#[derive(Debug, Clone)]
+pub struct HistorySample {
+    pub commit_id: String,
+    pub subject: String,
+    pub diff: String,
+}
+
+/// Comparison of a generated subject against the subject that was actually committed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SampleScore {
+    pub commit_id: String,
+    pub actual: String,
+    pub generated: Option<String>,
+    pub valid: bool,
+    pub type_match: Option<bool>,
+    pub scope_match: Option<bool>,
+    pub similarity: f64,
+}
+
+/// Aggregated results of an evaluation run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalReport {
+    /// Version of the prompt templates the samples were generated with
+    pub prompt_version: u32,
+    pub samples: Vec<SampleScore>,
+    pub generated_count: usize,
+    pub valid_rate: f64,
+    pub type_match_rate: f64,
+    pub scope_match_rate: f64,
+    pub mean_similarity: f64,
+}
+
+impl EvalReport {
+    /// Build a report from individual sample scores
+    pub fn from_scores(samples: Vec<SampleScore>) -> Self {
+        let generated: Vec<&SampleScore> =
+            samples.iter().filter(|s| s.generated.is_some()).collect();
+        let generated_count = generated.len();
+
+        let valid_rate = ratio(
+            generated.iter().filter(|s| s.valid).count(),
+            generated_count,
+        );
+
+        let type_results: Vec<bool> = generated.iter().filter_map(|s| s.type_match).collect();
+        let type_match_rate = ratio(
+            type_results.iter().filter(|m| **m).count(),
+            type_results.len(),
+        );
+
+        let scope_results: Vec<bool> = generated.iter().filter_map(|s| s.scope_match).collect();
+        let scope_match_rate = ratio(
+            scope_results.iter().filter(|m| **m).count(),
+            scope_results.len(),
+        );
+
+        let mean_similarity = if generated_count == 0 {
+            0.0
+        } else {
+            generated.iter().map(|s| s.similarity).sum::<f64>() / generated_count as f64
+        };
+
+        Self {
+            prompt_version: PROMPT_VERSION,
+            samples,
+            generated_count,
+            valid_rate,
+            type_match_rate,
+            scope_match_rate,
+            mean_similarity,
+        }
+    }
+}
+
+/// Collect the most recent non-merge commits with their diffs, newest first
+pub fn collect_history_samples(repo: &Repository, last: usize) -> Result<Vec<HistorySample>> {
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push_head()?;
+    revwalk.set_sorting(git2::Sort::TIME)?;
+
+    let mut samples = Vec::new();
+    for oid in revwalk {
+        if samples.len() >= last {
+            break;
+        }
+
+        let commit = repo.find_commit(oid?)?;
+        // Merge commits have no single diff to replay
+        if commit.parent_count() > 1 {
+            continue;
+        }
+
+        let diff = get_commit_diff_from_repo(repo, &commit)?;
+        if diff.trim().is_empty() {
+            continue;
+        }
+
+        samples.push(HistorySample {
+            commit_id: commit.id().to_string(),
+            subject: commit.summary().unwrap_or_default().to_string(),
+            diff,
+        });
+    }
+
+    Ok(samples)
+}
+
+/// Score a generated subject against the actual one
+pub fn score_sample(commit_id: &str, actual: &str, generated: Option<&str>) -> SampleScore {
+    let Some(generated) = generated else {
+        return SampleScore {
+            commit_id: commit_id.to_string(),
+            actual: actual.to_string(),
+            generated: None,
+            valid: false,
+            type_match: None,
+            scope_match: None,
+            similarity: 0.0,
+        };
+    };
+
+    // Type and scope can only be compared when the actual subject is conventional
+    let (type_match, scope_match) = match (
+        parse_commit_message(actual),
+        parse_commit_message(generated),
+    ) {
+        (Ok(actual), Ok(generated)) => (
+            Some(actual.commit_type == generated.commit_type),
+            Some(actual.scope == generated.scope),
+        ),
+        (Ok(_), Err(_)) => (Some(false), Some(false)),
+        _ => (None, None),
+    };
+
+    SampleScore {
+        commit_id: commit_id.to_string(),
+        actual: actual.to_string(),
+        generated: Some(generated.to_string()),
+        valid: is_valid_commit_message(generated),
+        type_match,
+        scope_match,
+        similarity: similarity(actual, generated),
+    }
+}
+
+/// ROUGE-L style similarity (F-measure of the longest common word subsequence)
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let a_words = tokenize(a);
+    let b_words = tokenize(b);
+
+    if a_words.is_empty() || b_words.is_empty() {
+        return 0.0;
+    }
+
+    let lcs = longest_common_subsequence(&a_words, &b_words) as f64;
+    if lcs == 0.0 {
+        return 0.0;
+    }
+
+    let precision = lcs / b_words.len() as f64;
+    let recall = lcs / a_words.len() as f64;
+    2.0 * precision * recall / (precision + recall)
+}
+
+/// Replay past commits through the provider and score the results
+pub async fn run_eval(
+    repo: &Repository,
+    provider: &dyn AIProvider,
+    privacy: PrivacyLevel,
+    last: usize,
+) -> Result<EvalReport> {
+    privacy.require_content("Evaluation")?;
+    let samples = collect_history_samples(repo, last)?;
+    info!("Evaluating {} commits from history", samples.len());
+
+    let mut scores = Vec::with_capacity(samples.len());
+    for sample in &samples {
+        let mut filter = PrivacyFilter::new(privacy);
+        let prompt = create_commit_prompt(&filter.diff(&sample.diff).unwrap_or_default());
+        let generated = match provider.generate_message(&prompt).await {
+            Ok(response) => normalize_message(&filter.restore(&response), None)
+                .lines()
+                .next()
+                .map(str::to_string),
+            Err(e) => {
+                warn!("Generation failed for {}: {}", sample.commit_id, e);
+                None
+            }
+        };
+
+        scores.push(score_sample(
+            &sample.commit_id,
+            &sample.subject,
+            generated.as_deref(),
+        ));
+    }
+
+    Ok(EvalReport::from_scores(scores))
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect()
+}
+
+fn longest_common_subsequence(a: &[String], b: &[String]) -> usize {
+    let mut previous = vec![0; b.len() + 1];
+    let mut current = vec![0; b.len() + 1];
+
+    for a_word in a {
+        for (j, b_word) in b.iter().enumerate() {
+            current[j + 1] = if a_word == b_word {
+                previous[j] + 1
+            } else {
+                current[j].max(previous[j + 1])
+            };
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fixtures::{commit, init_repo};
+
+    #[test]
+    fn test_similarity() {
+        assert_eq!(similarity("feat: add login", "feat: add login"), 1.0);
+        assert_eq!(similarity("feat: add login", "docs: fix typo"), 0.0);
+        assert_eq!(similarity("", "feat: add login"), 0.0);
+
+        let partial = similarity("feat(auth): add login form", "feat(auth): add signup form");
+        assert!(partial > 0.5 && partial < 1.0);
+    }
+
+    #[test]
+    fn test_score_sample() {
+        let score = score_sample(
+            "abc",
+            "feat(auth): add login",
+            Some("feat(api): add login endpoint"),
+        );
+        assert_eq!(score.type_match, Some(true));
+        assert_eq!(score.scope_match, Some(false));
+        assert!(score.valid);
+
+        let score = score_sample("abc", "Initial commit", Some("feat: add readme"));
+        assert_eq!(score.type_match, None);
+
+        let score = score_sample("abc", "fix: resolve crash", None);
+        assert!(score.generated.is_none());
+    }
+
+    #[test]
+    fn test_report_rates() {
+        let report = EvalReport::from_scores(vec![
+            score_sample("a", "feat: add login", Some("feat: add login")),
+            score_sample("b", "fix: resolve crash", Some("feat: handle crash")),
+            score_sample("c", "docs: update readme", None),
+        ]);
+
+        assert_eq!(report.generated_count, 2);
+        assert_eq!(report.type_match_rate, 0.5);
+        assert_eq!(report.scope_match_rate, 1.0);
+        assert_eq!(report.valid_rate, 1.0);
+    }
+
+    #[test]
+    fn test_collect_history_samples() {
+        let temp_dir = init_repo();
+        let dir = temp_dir.path();
+        commit(dir, "a.txt", "one", "feat: add a");
+        commit(dir, "b.txt", "two", "fix: add b");
+        commit(dir, "c.txt", "three", "docs: add c");
+        let repo = Repository::open(dir).unwrap();
+
+        let samples = collect_history_samples(&repo, 2).unwrap();
+        assert_eq!(samples.len(), 2);
+        assert!(samples.iter().all(|s| !s.diff.is_empty()));
+        assert!(samples.iter().any(|s| s.subject == "docs: add c"));
+    }
+}
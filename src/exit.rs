@@ -0,0 +1,114 @@
+//! Exit statuses of the `committor` command
+//!
+//! Editor plugins and CI wrappers branch on why committor stopped: nothing staged
+//! asks the user to stage something, a provider failure suggests retrying or
+//! switching providers. Every subcommand reports the same outcome with the same
+//! status, and the values don't change between releases.
+
+use crate::types::CommittorError;
+
+/// How a command ended, as its process exit status
+///
+/// Returned as an error, a status ends the command without printing anything
+/// more, for outcomes the command has already explained.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[repr(u8)]
+pub enum ExitStatus {
+    #[error("success")]
+    Success = 0,
+    /// Any failure not covered below, such as bad arguments or configuration
+    #[error("failed")]
+    Failure = 1,
+    /// There were no changes to work on: nothing staged, or nothing to stash
+    #[error("no staged changes")]
+    NoStagedChanges = 2,
+    /// The provider failed, refused, timed out or rejected the credentials, or none is set up
+    #[error("provider error")]
+    Provider = 3,
+    /// A message broke the commit message rules, or no generated message passed them
+    #[error("validation failed")]
+    Validation = 4,
+    /// A git operation failed, or the directory isn't a repository
+    #[error("git error")]
+    Git = 5,
+    /// The user cancelled at a prompt
+    #[error("cancelled")]
+    Cancelled = 6,
+    /// Ctrl-C, reported the way shells report SIGINT
+    #[error("interrupted")]
+    Interrupted = 130,
+}
+
+impl ExitStatus {
+    /// Get the status a failed command exits with
+    pub fn of(error: &anyhow::Error) -> Self {
+        if let Some(status) = error.downcast_ref::<ExitStatus>() {
+            return *status;
+        }
+        match error.downcast_ref::<CommittorError>() {
+            Some(CommittorError::NoStagedChanges) => Self::NoStagedChanges,
+            Some(
+                CommittorError::AIProviderError(_)
+                | CommittorError::OllamaUnavailable(_)
+                | CommittorError::ModelRefusal(_)
+                | CommittorError::Timeout(_)
+                | CommittorError::RateLimited(_)
+                | CommittorError::ContextTooLarge(_),
+            ) => Self::Provider,
+            Some(
+                CommittorError::ValidationFailed(_)
+                | CommittorError::InvalidCommitFormat(_)
+                | CommittorError::SecretDetected(_)
+                | CommittorError::MissingTrailers(_),
+            ) => Self::Validation,
+            Some(CommittorError::GitRepoNotFound | CommittorError::GitError(_)) => Self::Git,
+            Some(CommittorError::ConfigError(_)) | None => Self::Failure,
+        }
+    }
+
+    /// Get the numeric exit status
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    /// Check whether the command already explained this outcome, so no error is printed
+    pub fn is_quiet(error: &anyhow::Error) -> bool {
+        error.is::<ExitStatus>()
+    }
+}
+
+impl From<ExitStatus> for std::process::ExitCode {
+    fn from(status: ExitStatus) -> Self {
+        Self::from(status.code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_exit_status_of_errors() {
+        let status = |error: CommittorError| ExitStatus::of(&error.into());
+        assert_eq!(status(CommittorError::NoStagedChanges).code(), 2);
+        assert_eq!(status(CommittorError::RateLimited(String::new())).code(), 3);
+        assert_eq!(
+            status(CommittorError::ValidationFailed(String::new())).code(),
+            4
+        );
+        assert_eq!(status(CommittorError::GitError(String::new())).code(), 5);
+        assert_eq!(status(CommittorError::ConfigError(String::new())).code(), 1);
+
+        // Context added on the way up doesn't hide the cause
+        let error = Err::<(), _>(CommittorError::GitRepoNotFound)
+            .context("Git environment validation failed")
+            .unwrap_err();
+        assert_eq!(ExitStatus::of(&error), ExitStatus::Git);
+
+        let cancelled = anyhow::Error::from(ExitStatus::Cancelled);
+        assert_eq!(ExitStatus::of(&cancelled).code(), 6);
+        assert!(ExitStatus::is_quiet(&cancelled));
+        assert!(!ExitStatus::is_quiet(&anyhow::anyhow!("boom")));
+    }
+}
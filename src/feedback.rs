@@ -0,0 +1,271 @@
+//! Ratings of suggestions kept locally, and the preferences they add up to
+//!
+//! With `--feedback`, the committed option and any option rated with `+N` or `-N`
+//! at the picker are appended to a log in the git directory. When guidance is on,
+//! traits the user keeps rejecting or accepting are turned into prompt guidelines.
+
+use crate::commit::parse_commit_message;
+use crate::paths::git_state_path;
+use crate::state::append_line;
+use anyhow::{Context, Result};
+use git2::Repository;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name of the feedback log file, kept in the git directory
+pub const FEEDBACK_FILE_NAME: &str = "committor-feedback.jsonl";
+
+/// Only this many of the latest ratings count, so preferences can change over time
+const RECENT_RATINGS: usize = 200;
+
+/// Ratings of suggestions with a trait needed before it becomes guidance
+const MIN_RATINGS: usize = 3;
+
+/// Feedback behaviour read from the `[feedback]` settings section
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeedbackSettings {
+    /// Add the preferences learned from past ratings to commit prompts
+    pub guidance: bool,
+}
+
+/// Whether the user liked a suggestion
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rating {
+    Up,
+    Down,
+}
+
+/// One rated suggestion
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeedbackRecord {
+    /// When the suggestion was rated, in seconds since the Unix epoch
+    pub timestamp: u64,
+    pub rating: Rating,
+    pub message: String,
+}
+
+impl FeedbackRecord {
+    /// Rate a suggestion now
+    pub fn new(message: &str, rating: Rating) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default();
+        Self {
+            timestamp,
+            rating,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Get the feedback log path for a repository
+pub fn feedback_path_from_repo(repo: &Repository) -> PathBuf {
+    git_state_path(repo, FEEDBACK_FILE_NAME)
+}
+
+/// Record a rating in the current repository's feedback log
+pub fn record(message: &str, rating: Rating) -> Result<()> {
+    let repo = Repository::discover(".").context("Not in a git repository")?;
+    append_record(
+        &feedback_path_from_repo(&repo),
+        &FeedbackRecord::new(message, rating),
+    )
+}
+
+/// Append a record to the feedback log, creating it if needed
+pub fn append_record(path: &Path, record: &FeedbackRecord) -> Result<()> {
+    append_line(path, &serde_json::to_string(record)?)
+        .with_context(|| format!("Failed to write feedback log {}", path.display()))
+}
+
+/// Read every record in the feedback log, oldest first
+pub fn read_records(path: &Path) -> Result<Vec<FeedbackRecord>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read feedback log {}", path.display()))?;
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).context("Invalid feedback log record"))
+        .collect()
+}
+
+/// Get the guidance learned from the current repository's feedback log
+///
+/// Empty when there is no log or it can't be read: guidance is never worth failing over.
+pub fn guidance() -> Vec<String> {
+    Repository::discover(".")
+        .ok()
+        .and_then(|repo| read_records(&feedback_path_from_repo(&repo)).ok())
+        .map(|records| guidance_from_records(&records))
+        .unwrap_or_default()
+}
+
+/// A trait of a suggestion the user may consistently reject or accept
+struct Trait {
+    /// Guideline given when suggestions with the trait are rejected
+    avoid: &'static str,
+    /// Guideline given when suggestions with the trait are accepted
+    prefer: &'static str,
+    has: fn(&str) -> bool,
+}
+
+const TRAITS: [Trait; 4] = [
+    Trait {
+        avoid: "Don't mention file names in the subject",
+        prefer: "Mention the main file name in the subject",
+        has: mentions_file_name,
+    },
+    Trait {
+        avoid: "Leave out the scope",
+        prefer: "Include a scope",
+        has: has_scope,
+    },
+    Trait {
+        avoid: "Keep the description well under 50 characters",
+        prefer: "Use the full 50 characters to be specific",
+        has: is_long,
+    },
+    Trait {
+        avoid: "Don't add a body below the subject",
+        prefer: "Add a short body below the subject",
+        has: has_body,
+    },
+];
+
+/// Turn ratings into guidelines for the traits the user clearly rejects or prefers
+///
+/// A trait counts when at least [`MIN_RATINGS`] suggestions with it got one rating,
+/// it is in at least half of those ratings and in under half as many of the others.
+pub fn guidance_from_records(records: &[FeedbackRecord]) -> Vec<String> {
+    let recent = &records[records.len().saturating_sub(RECENT_RATINGS)..];
+    let rated = |rating: Rating| -> Vec<&str> {
+        recent
+            .iter()
+            .filter(|record| record.rating == rating)
+            .map(|record| record.message.as_str())
+            .collect()
+    };
+    let (up, down) = (rated(Rating::Up), rated(Rating::Down));
+
+    let mut guidance = Vec::new();
+    for t in &TRAITS {
+        let up_count = up.iter().filter(|message| (t.has)(message)).count();
+        let down_count = down.iter().filter(|message| (t.has)(message)).count();
+        if stands_out(down_count, down.len(), up_count, up.len()) {
+            guidance.push(format!(
+                "{} (the user rejected {down_count} suggestions that did)",
+                t.avoid
+            ));
+        } else if stands_out(up_count, up.len(), down_count, down.len()) {
+            guidance.push(format!(
+                "{} (the user accepted {up_count} suggestions that did)",
+                t.prefer
+            ));
+        }
+    }
+    guidance
+}
+
+/// Check whether a trait is common in one set of ratings and rare in the other
+fn stands_out(count: usize, total: usize, other_count: usize, other_total: usize) -> bool {
+    if count < MIN_RATINGS || count * 2 < total {
+        return false;
+    }
+    // Compare shares without dividing: count/total > 2 * other_count/other_total
+    other_total == 0 || count * other_total > 2 * other_count * total
+}
+
+fn subject(message: &str) -> &str {
+    message.lines().next().unwrap_or_default().trim()
+}
+
+fn mentions_file_name(message: &str) -> bool {
+    let file_regex = Regex::new(r"\b[\w-]+\.[a-z][a-z0-9]{0,4}\b|\w/\w").unwrap();
+    file_regex.is_match(subject(message))
+}
+
+fn has_scope(message: &str) -> bool {
+    parse_commit_message(subject(message)).is_ok_and(|commit| commit.scope.is_some())
+}
+
+fn is_long(message: &str) -> bool {
+    parse_commit_message(subject(message))
+        .is_ok_and(|commit| commit.description.chars().count() > 40)
+}
+
+fn has_body(message: &str) -> bool {
+    message.lines().skip(1).any(|line| !line.trim().is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(ratings: &[(&str, Rating)]) -> Vec<FeedbackRecord> {
+        ratings
+            .iter()
+            .map(|(message, rating)| FeedbackRecord::new(message, *rating))
+            .collect()
+    }
+
+    #[test]
+    fn test_guidance_from_records() {
+        let records = records(&[
+            ("fix(api): handle timeouts in client.rs", Rating::Down),
+            ("docs: update README.md", Rating::Down),
+            ("refactor(ui): split src/app/view", Rating::Down),
+            ("fix(api): handle timeouts", Rating::Up),
+            ("docs: describe installation", Rating::Up),
+            ("refactor(ui): split the main view", Rating::Up),
+        ]);
+        assert_eq!(
+            guidance_from_records(&records),
+            vec!["Don't mention file names in the subject (the user rejected 3 suggestions that did)"]
+        );
+
+        // Two ratings aren't a pattern yet
+        assert!(guidance_from_records(&records[..2]).is_empty());
+        assert!(guidance_from_records(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_guidance_from_accepted_scopes() {
+        let records = records(&[
+            ("fix(api): handle timeouts", Rating::Up),
+            ("feat(auth): add login", Rating::Up),
+            ("docs(readme): describe setup", Rating::Up),
+            ("fix: handle timeouts", Rating::Down),
+        ]);
+        assert_eq!(
+            guidance_from_records(&records),
+            vec!["Include a scope (the user accepted 3 suggestions that did)"]
+        );
+    }
+
+    #[test]
+    fn test_append_and_read_records() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(FEEDBACK_FILE_NAME);
+        assert!(read_records(&path)?.is_empty());
+
+        let record = FeedbackRecord::new("fix: handle timeouts", Rating::Down);
+        append_record(&path, &record)?;
+        append_record(&path, &FeedbackRecord::new("fix(api): retry", Rating::Up))?;
+
+        let read = read_records(&path)?;
+        assert_eq!(read.len(), 2);
+        assert_eq!(read[0], record);
+        assert!(fs::read_to_string(&path)?.contains(r#""rating":"down""#));
+        Ok(())
+    }
+}
@@ -0,0 +1,337 @@
+//! The pipeline of filters every generated message passes through
+//!
+//! Each filter either rewrites a message or discards it. Which filters run, and in
+//! which order, is set by `pipeline` under `[filters]`; a new policy is a new
+//! [`MessageFilter`] and a [`FilterKind`] naming it, with no change to the
+//! generation code. After the configured filters, the message is always laid out
+//! by [`normalize_message`].
+
+use crate::commit::parse_commit_message;
+use crate::postprocess::{lowercase_type, normalize_message, strip_emojis};
+use crate::rules::{
+    apply_forbidden_words, correct_subject, recase_header, Case, RuleSet, Rules, Strictness,
+};
+use crate::settings::Settings;
+use crate::trailers::append_trailers;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+/// A step of the pipeline
+pub trait MessageFilter: Send + Sync {
+    /// Get the name the filter is logged under
+    fn name(&self) -> &'static str;
+
+    /// Rewrite a generated message, or return `None` to discard it
+    fn apply(&self, message: &str) -> Option<String>;
+}
+
+/// The filters that can be listed under `[filters]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FilterKind {
+    /// Remove emojis that aren't in `rules.allowed_emojis`
+    StripEmojis,
+    /// Lowercase the type and put the description and scope in the `rules` case styles
+    NormalizeCase,
+    /// Drop a scope that isn't in `rules.allowed_scopes`
+    ScopeAllowlist,
+    /// Remove or replace `rules.forbidden_words`
+    ForbiddenWords,
+    /// Append the `trailers.add` trailers, so the options show them
+    Trailers,
+}
+
+impl FilterKind {
+    /// The filters run when `[filters]` doesn't list any
+    pub const DEFAULT: [FilterKind; 3] = [
+        FilterKind::StripEmojis,
+        FilterKind::NormalizeCase,
+        FilterKind::ForbiddenWords,
+    ];
+}
+
+/// Which filters generated messages pass through, from the `[filters]` settings section
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilterSettings {
+    pub pipeline: Vec<FilterKind>,
+}
+
+impl Default for FilterSettings {
+    fn default() -> Self {
+        Self {
+            pipeline: FilterKind::DEFAULT.to_vec(),
+        }
+    }
+}
+
+/// Filters run one after another, stopping at the first that discards the message
+pub struct FilterPipeline {
+    filters: Vec<Box<dyn MessageFilter>>,
+}
+
+impl FilterPipeline {
+    /// Create a pipeline with no filters
+    pub fn new() -> Self {
+        Self {
+            filters: Vec::new(),
+        }
+    }
+
+    /// Add a filter to the end of the pipeline
+    pub fn with(mut self, filter: impl MessageFilter + 'static) -> Self {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    /// Build the pipeline the settings configure, ending with the message layout
+    pub fn from_settings(settings: &Settings) -> Self {
+        let rules = &settings.rules;
+        let mut pipeline = Self::new();
+        for kind in &settings.filters.pipeline {
+            pipeline = match kind {
+                FilterKind::StripEmojis => pipeline.with(StripEmojis {
+                    allowed: rules.allowed_emojis.clone(),
+                    strictness: rules.strictness,
+                }),
+                FilterKind::NormalizeCase => pipeline.with(NormalizeCase {
+                    rules: rules.clone(),
+                }),
+                FilterKind::ScopeAllowlist => pipeline.with(ScopeAllowlist {
+                    allowed: rules.allowed_scopes.clone(),
+                    required: rules.require_scope,
+                    strictness: rules.strictness,
+                }),
+                FilterKind::ForbiddenWords => pipeline.with(ForbiddenWords {
+                    rules: rules.clone(),
+                }),
+                FilterKind::Trailers => pipeline.with(Trailers {
+                    trailers: settings.trailers.add.clone(),
+                }),
+            };
+        }
+        pipeline.with(Layout {
+            subject_case: rules.subject_case,
+        })
+    }
+
+    /// Build the default pipeline for a set of rules
+    pub fn from_rules(rules: &Rules) -> Self {
+        Self::from_settings(&Settings {
+            rules: rules.clone(),
+            ..Settings::default()
+        })
+    }
+
+    /// Run a message through every filter, or return `None` once one discards it
+    pub fn apply(&self, message: &str) -> Option<String> {
+        self.run(message).ok()
+    }
+
+    /// Run a message through every filter, failing with the name of the one that discards it
+    pub fn run(&self, message: &str) -> Result<String, &'static str> {
+        let mut message = message.to_string();
+        for filter in &self.filters {
+            match filter.apply(&message) {
+                Some(filtered) => message = filtered,
+                None => {
+                    debug!("The {} filter discarded: {}", filter.name(), message);
+                    return Err(filter.name());
+                }
+            }
+        }
+        Ok(message)
+    }
+}
+
+impl Default for FilterPipeline {
+    fn default() -> Self {
+        Self::from_rules(&Rules::default())
+    }
+}
+
+struct StripEmojis {
+    allowed: Vec<String>,
+    strictness: Strictness,
+}
+
+impl MessageFilter for StripEmojis {
+    fn name(&self) -> &'static str {
+        "strip-emojis"
+    }
+
+    fn apply(&self, message: &str) -> Option<String> {
+        correct_subject(message, self.strictness, |subject| {
+            strip_emojis(subject, &self.allowed)
+        })
+    }
+}
+
+struct NormalizeCase {
+    rules: Rules,
+}
+
+impl MessageFilter for NormalizeCase {
+    fn name(&self) -> &'static str {
+        "normalize-case"
+    }
+
+    fn apply(&self, message: &str) -> Option<String> {
+        correct_subject(message, self.rules.strictness, |subject| {
+            recase_header(&lowercase_type(subject.trim()), &self.rules)
+        })
+    }
+}
+
+struct ScopeAllowlist {
+    allowed: Vec<String>,
+    required: bool,
+    strictness: Strictness,
+}
+
+impl MessageFilter for ScopeAllowlist {
+    fn name(&self) -> &'static str {
+        "scope-allowlist"
+    }
+
+    fn apply(&self, message: &str) -> Option<String> {
+        // Subjects that don't parse are left for the commit format check to discard
+        let subject = message.lines().next().unwrap_or_default();
+        let Ok(commit) = parse_commit_message(subject) else {
+            return Some(message.to_string());
+        };
+        match commit.scope {
+            Some(scope) if !self.allowed.is_empty() && !self.allowed.contains(&scope) => {
+                if self.required {
+                    return None;
+                }
+                correct_subject(message, self.strictness, |subject| {
+                    subject.trim().replacen(&format!("({scope})"), "", 1)
+                })
+            }
+            _ => Some(message.to_string()),
+        }
+    }
+}
+
+struct ForbiddenWords {
+    rules: Rules,
+}
+
+impl MessageFilter for ForbiddenWords {
+    fn name(&self) -> &'static str {
+        "forbidden-words"
+    }
+
+    fn apply(&self, message: &str) -> Option<String> {
+        apply_forbidden_words(message, &self.rules)
+    }
+}
+
+struct Trailers {
+    trailers: Vec<String>,
+}
+
+impl MessageFilter for Trailers {
+    fn name(&self) -> &'static str {
+        "trailers"
+    }
+
+    fn apply(&self, message: &str) -> Option<String> {
+        Some(append_trailers(message, &self.trailers))
+    }
+}
+
+/// Discards messages that break any of a set of rules
+pub struct Validate {
+    pub rules: RuleSet,
+}
+
+impl MessageFilter for Validate {
+    fn name(&self) -> &'static str {
+        "validate"
+    }
+
+    fn apply(&self, message: &str) -> Option<String> {
+        self.rules.is_valid(message).then(|| message.to_string())
+    }
+}
+
+struct Layout {
+    subject_case: Option<Case>,
+}
+
+impl MessageFilter for Layout {
+    fn name(&self) -> &'static str {
+        "layout"
+    }
+
+    fn apply(&self, message: &str) -> Option<String> {
+        Some(normalize_message(message, self.subject_case))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_configured_pipeline() {
+        let settings = Settings::parse(
+            r#"
+[rules]
+allowed_scopes = ["api", "cli"]
+forbidden_words = ["falcon"]
+strictness = "correct"
+
+[trailers]
+add = ["Reviewed-by-policy: ai-assisted"]
+
+[filters]
+pipeline = ["normalize-case", "scope-allowlist", "forbidden-words", "trailers"]
+"#,
+        )
+        .unwrap();
+        let pipeline = FilterPipeline::from_settings(&settings);
+
+        assert_eq!(
+            pipeline.apply("Feat(db): add falcon indexes").as_deref(),
+            Some("feat: add indexes\n\nReviewed-by-policy: ai-assisted")
+        );
+        assert_eq!(
+            pipeline.apply("fix(api): handle timeouts").as_deref(),
+            Some("fix(api): handle timeouts\n\nReviewed-by-policy: ai-assisted")
+        );
+
+        // Emojis are only stripped when the pipeline lists the filter
+        assert_eq!(
+            pipeline.apply("✨ feat: add login").as_deref(),
+            Some("✨ feat: add login\n\nReviewed-by-policy: ai-assisted")
+        );
+        assert_eq!(
+            FilterPipeline::default()
+                .apply("✨ feat: add login")
+                .as_deref(),
+            Some("feat: add login")
+        );
+    }
+
+    #[test]
+    fn test_custom_filter() {
+        struct NoWip;
+
+        impl MessageFilter for NoWip {
+            fn name(&self) -> &'static str {
+                "no-wip"
+            }
+
+            fn apply(&self, message: &str) -> Option<String> {
+                (!message.contains("wip")).then(|| message.to_string())
+            }
+        }
+
+        let pipeline = FilterPipeline::default().with(NoWip);
+        assert!(pipeline.apply("chore: wip").is_none());
+        assert!(pipeline.apply("chore: tidy up").is_some());
+    }
+}
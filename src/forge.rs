@@ -0,0 +1,113 @@
+//! Issue-closing references for the forge a repository is hosted on
+//!
+//! GitHub, GitLab and Gitea close an issue when a merged commit says so, but each
+//! accepts its own set of keywords: only GitLab understands `Implements`. The
+//! forge is recognised from the `origin` remote, and the issue number from a
+//! branch name such as `fix/123-login-crash`.
+
+use crate::types::CommitType;
+use serde::{Deserialize, Serialize};
+
+/// A code hosting service, which decides the issue-closing keywords
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Forge {
+    GitHub,
+    GitLab,
+    Gitea,
+}
+
+impl Forge {
+    /// Recognise the forge from a remote URL's host, `None` for hosts it can't tell
+    pub fn from_remote_url(url: &str) -> Option<Self> {
+        let host = crate::credentials::https_url(url)?
+            .trim_start_matches("https://")
+            .split('/')
+            .next()?
+            .to_ascii_lowercase();
+        if host == "github.com" || host.ends_with(".github.com") || host.contains("github") {
+            Some(Self::GitHub)
+        } else if host.contains("gitlab") {
+            Some(Self::GitLab)
+        } else if host.contains("gitea") || host.contains("forgejo") || host == "codeberg.org" {
+            Some(Self::Gitea)
+        } else {
+            None
+        }
+    }
+
+    /// Get the keyword that closes an issue from a commit of the given type
+    pub fn closing_keyword(self, commit_type: Option<&CommitType>) -> &'static str {
+        match (self, commit_type) {
+            (_, Some(CommitType::Fix)) => "Fixes",
+            (Self::GitLab, Some(CommitType::Feat)) => "Implements",
+            _ => "Closes",
+        }
+    }
+
+    /// Build the footer line closing an issue, e.g. `Fixes #123`
+    pub fn closing_reference(self, issue: u64, commit_type: Option<&CommitType>) -> String {
+        format!("{} #{issue}", self.closing_keyword(commit_type))
+    }
+}
+
+/// Find an issue number in a branch name, such as `fix/123-crash` or `issue-42`
+///
+/// The number has to start a path segment, so `release/1.2`, `feature/2fa` and
+/// tickets like `ABC-123` have none.
+pub fn issue_from_branch(branch: &str) -> Option<u64> {
+    let regex = regex::Regex::new(r"(?:^|/)(?i:issue-|gh-)?([0-9]+)(?:[/_-]|$)").unwrap();
+    regex.captures(branch)?.get(1)?.as_str().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_remote_url() {
+        assert_eq!(
+            Forge::from_remote_url("git@github.com:owner/repo.git"),
+            Some(Forge::GitHub)
+        );
+        assert_eq!(
+            Forge::from_remote_url("https://gitlab.example.com/team/app.git"),
+            Some(Forge::GitLab)
+        );
+        assert_eq!(
+            Forge::from_remote_url("https://codeberg.org/owner/repo.git"),
+            Some(Forge::Gitea)
+        );
+        assert_eq!(Forge::from_remote_url("git@git.example.com:app.git"), None);
+        assert_eq!(Forge::from_remote_url("/srv/git/app.git"), None);
+    }
+
+    #[test]
+    fn test_closing_reference() {
+        assert_eq!(
+            Forge::GitHub.closing_reference(123, Some(&CommitType::Fix)),
+            "Fixes #123"
+        );
+        assert_eq!(
+            Forge::GitHub.closing_reference(123, Some(&CommitType::Feat)),
+            "Closes #123"
+        );
+        assert_eq!(
+            Forge::GitLab.closing_reference(123, Some(&CommitType::Feat)),
+            "Implements #123"
+        );
+        assert_eq!(Forge::Gitea.closing_reference(7, None), "Closes #7");
+    }
+
+    #[test]
+    fn test_issue_from_branch() {
+        assert_eq!(issue_from_branch("fix/123-login-crash"), Some(123));
+        assert_eq!(issue_from_branch("42-add-search"), Some(42));
+        assert_eq!(issue_from_branch("feature/issue-7"), Some(7));
+        assert_eq!(issue_from_branch("gh-99"), Some(99));
+        assert_eq!(issue_from_branch("release/1.2"), None);
+        assert_eq!(issue_from_branch("feature/2fa"), None);
+        assert_eq!(issue_from_branch("feature/ABC-123-login"), None);
+        assert_eq!(issue_from_branch("main"), None);
+    }
+}
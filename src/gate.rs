@@ -0,0 +1,187 @@
+//! Pull request titles checked against the change they describe
+//!
+//! Squash merges turn the PR title into the commit subject, so a title that
+//! says `fix typo` over a new feature ends up in history. In CI, messages are
+//! generated for the PR's squashed diff and the title is compared with them.
+
+use crate::commit::parse_commit_message;
+use crate::eval::similarity;
+use crate::hooks::EnforcementMode;
+use crate::i18n::Text;
+use serde::{Deserialize, Serialize};
+
+/// Title checking read from the `[gate]` settings section
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GateSettings {
+    /// Whether a misleading title fails the check or is only reported
+    pub mode: EnforcementMode,
+    /// Word overlap with the closest generated subject below which a title looks unrelated
+    pub min_similarity: f64,
+}
+
+impl Default for GateSettings {
+    fn default() -> Self {
+        Self {
+            mode: EnforcementMode::default(),
+            min_similarity: 0.2,
+        }
+    }
+}
+
+/// Outcome of comparing a title with the subjects generated for its change
+#[derive(Debug, Clone, PartialEq)]
+pub struct TitleCheck {
+    pub title: String,
+    pub generated: Vec<String>,
+    /// Generated subject closest to the title
+    pub closest: Option<String>,
+    /// Word overlap between the title and the closest subject, from 0 to 1
+    pub similarity: f64,
+    /// Ways the title seems to misrepresent the change; empty when it looks right
+    pub problems: Vec<String>,
+}
+
+impl TitleCheck {
+    /// Check whether the title looks like a fair description of the change
+    pub fn passed(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Compare a title with the subjects generated for the same change
+///
+/// The title only has to agree with one of them: models word the same change in
+/// several ways, so a mismatch with every option is what suggests a misleading title.
+pub fn check_title(title: &str, generated: &[String], settings: &GateSettings) -> TitleCheck {
+    let title = title.trim();
+    let subjects: Vec<&str> = generated
+        .iter()
+        .map(|message| message.lines().next().unwrap_or_default().trim())
+        .collect();
+
+    let closest = subjects
+        .iter()
+        .map(|subject| {
+            (
+                *subject,
+                similarity(description(title), description(subject)),
+            )
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1));
+    let similarity = closest.map_or(0.0, |(_, score)| score);
+
+    let mut problems = Vec::new();
+    if let Ok(commit) = parse_commit_message(title) {
+        let generated_types: Vec<String> = subjects
+            .iter()
+            .filter_map(|subject| parse_commit_message(subject).ok())
+            .map(|generated| generated.commit_type.to_string())
+            .collect();
+        let title_type = commit.commit_type.to_string();
+        if !generated_types.is_empty() && !generated_types.contains(&title_type) {
+            problems.push(Text::GateTypeMismatch.format(&[&title_type, &generated_types[0]]));
+        }
+    }
+    if !subjects.is_empty() && similarity < settings.min_similarity {
+        problems.push(Text::GateLowSimilarity.format(&[
+            &format!("{similarity:.2}"),
+            &format!("{:.2}", settings.min_similarity),
+        ]));
+    }
+
+    TitleCheck {
+        title: title.to_string(),
+        generated: subjects.iter().map(ToString::to_string).collect(),
+        closest: closest.map(|(subject, _)| subject.to_string()),
+        similarity,
+        problems,
+    }
+}
+
+/// Render a check as a Markdown comment for CI to post on the pull request
+pub fn comment_markdown(check: &TitleCheck) -> String {
+    let mut comment = if check.passed() {
+        format!("{}\n", Text::GateCommentMatches.format(&[&check.title]))
+    } else {
+        let problems: String = check
+            .problems
+            .iter()
+            .map(|problem| format!("- {problem}\n"))
+            .collect();
+        format!(
+            "{}\n\n{problems}",
+            Text::GateCommentMismatch.format(&[&check.title])
+        )
+    };
+
+    if !check.generated.is_empty() {
+        comment.push_str(&format!("\n{}\n\n", Text::GateCommentGenerated.text()));
+        for subject in &check.generated {
+            comment.push_str(&format!("- `{subject}`\n"));
+        }
+    }
+    comment
+}
+
+/// Get the description of a conventional subject, or the whole text when it isn't one
+fn description(subject: &str) -> &str {
+    match subject.split_once(": ") {
+        Some((_, description)) if parse_commit_message(subject).is_ok() => description,
+        _ => subject,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generated() -> Vec<String> {
+        vec![
+            "feat(auth): add logout endpoint".to_string(),
+            "feat(session): allow users to log out".to_string(),
+        ]
+    }
+
+    #[test]
+    fn test_check_title_accepts_a_fair_title() {
+        let check = check_title(
+            "feat: add logout endpoint to auth",
+            &generated(),
+            &GateSettings::default(),
+        );
+        assert!(check.passed(), "{:?}", check.problems);
+        assert_eq!(
+            check.closest.as_deref(),
+            Some("feat(auth): add logout endpoint")
+        );
+
+        // A title that isn't conventional is only compared by its words
+        let check = check_title("Let users log out", &generated(), &GateSettings::default());
+        assert!(check.passed(), "{:?}", check.problems);
+    }
+
+    #[test]
+    fn test_check_title_flags_a_misleading_title() {
+        let check = check_title(
+            "docs: fix typo in readme",
+            &generated(),
+            &GateSettings::default(),
+        );
+        assert_eq!(check.problems.len(), 2);
+        assert!(check.problems[0].contains("type is `docs`"));
+        assert!(check.problems[0].contains("looks like `feat`"));
+        assert!(check.problems[1].contains("little in common"));
+
+        let comment = comment_markdown(&check);
+        assert!(comment.starts_with("⚠️ The title `docs: fix typo in readme`"));
+        assert!(comment.contains("- `feat(auth): add logout endpoint`\n"));
+    }
+
+    #[test]
+    fn test_check_title_without_generated_subjects() {
+        let check = check_title("fix: anything", &[], &GateSettings::default());
+        assert!(check.passed());
+        assert_eq!(check.closest, None);
+    }
+}
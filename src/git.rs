@@ -0,0 +1,73 @@
+//! Running the git command line, for the operations git2 doesn't cover
+//!
+//! Rebases, cherry-picks and ref maintenance go through `git` itself so hooks,
+//! sequencer state and the user's configuration behave as they would in a shell.
+
+use crate::types::CommittorError;
+use anyhow::{Context, Result};
+use std::path::Path;
+use std::process::Command;
+
+/// Run a git command in a directory and return its trimmed output
+pub(crate) fn git_output(dir: &Path, args: &[&str]) -> Result<String> {
+    git_output_with_index(dir, None, args)
+}
+
+/// Run a git command, optionally against an alternate index file, and return its output
+pub(crate) fn git_output_with_index(
+    dir: &Path,
+    index: Option<&Path>,
+    args: &[&str],
+) -> Result<String> {
+    let mut command = Command::new("git");
+    command.args(args).current_dir(dir);
+    if let Some(index) = index {
+        command.env("GIT_INDEX_FILE", index);
+    }
+
+    let output = command
+        .output()
+        .with_context(|| format!("Failed to execute git {}", args.join(" ")))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(CommittorError::GitError(String::from_utf8_lossy(&output.stderr).to_string()).into())
+    }
+}
+
+/// Repositories built with the git command line, for tests
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use super::git_output;
+    use std::fs;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    /// Run a git command, panicking if it fails
+    pub(crate) fn git(dir: &Path, args: &[&str]) -> String {
+        git_output(dir, args).unwrap()
+    }
+
+    /// Create a repository on `main` in an existing directory, with a committer set
+    pub(crate) fn init_repo_in(dir: &Path) {
+        git(dir, &["init", "-q", "-b", "main"]);
+        git(dir, &["config", "user.email", "test@example.com"]);
+        git(dir, &["config", "user.name", "Test User"]);
+    }
+
+    /// Create an empty repository on `main` in a temporary directory
+    pub(crate) fn init_repo() -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        init_repo_in(temp_dir.path());
+        temp_dir
+    }
+
+    /// Write a file and commit it, returning the new HEAD
+    pub(crate) fn commit(dir: &Path, file: &str, content: &str, message: &str) -> String {
+        fs::write(dir.join(file), content).unwrap();
+        git(dir, &["add", file]);
+        git(dir, &["commit", "-q", "-m", message]);
+        git(dir, &["rev-parse", "HEAD"])
+    }
+}
@@ -0,0 +1,166 @@
+//! Subjects of recent commits, for spotting suggestions that repeat history
+//!
+//! On repetitive repositories models tend to suggest a subject that was already
+//! used, word for word. The last [`RECENT_SUBJECTS`] subjects reachable from HEAD
+//! are cached in the git directory against HEAD, so the history is only walked
+//! again once a commit moves it; suggestions repeating one of them are moved
+//! after the others and marked in the picker.
+
+use crate::paths::git_state_path;
+use crate::state::write_atomic;
+use anyhow::Result;
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the history cache, kept in the git directory
+pub const HISTORY_CACHE_FILE_NAME: &str = "committor-history.json";
+
+/// Number of recent commit subjects suggestions are checked against
+pub const RECENT_SUBJECTS: usize = 50;
+
+/// Subjects of the commits before HEAD, newest first
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecentHistory {
+    /// Commit the subjects were read from; empty before the first commit
+    pub head: String,
+    pub subjects: Vec<String>,
+}
+
+impl RecentHistory {
+    /// Check whether a message's subject is exactly one of the recent subjects
+    ///
+    /// Case and surrounding whitespace are ignored.
+    pub fn repeats(&self, message: &str) -> bool {
+        let subject = message.lines().next().unwrap_or_default().trim();
+        !subject.is_empty()
+            && self
+                .subjects
+                .iter()
+                .any(|recent| recent.trim().eq_ignore_ascii_case(subject))
+    }
+
+    /// Move messages that repeat a recent subject after the rest, keeping each group's order
+    pub fn demote_repeats(&self, messages: Vec<String>) -> Vec<String> {
+        let (repeats, fresh): (Vec<_>, Vec<_>) = messages
+            .into_iter()
+            .partition(|message| self.repeats(message));
+        fresh.into_iter().chain(repeats).collect()
+    }
+}
+
+/// Get the path of the history cache for a repository
+pub fn history_cache_path_from_repo(repo: &Repository) -> PathBuf {
+    git_state_path(repo, HISTORY_CACHE_FILE_NAME)
+}
+
+/// Get the recent history of the repository containing the current directory
+pub fn recent_history() -> Result<RecentHistory> {
+    let repo = Repository::discover(".")?;
+    recent_history_from_repo(&repo)
+}
+
+/// Get a repository's recent history, from the cache when HEAD hasn't moved
+pub fn recent_history_from_repo(repo: &Repository) -> Result<RecentHistory> {
+    // Nothing is committed yet, so there is no history to repeat
+    let Ok(head) = repo.head().and_then(|head| head.peel_to_commit()) else {
+        return Ok(RecentHistory::default());
+    };
+
+    let path = history_cache_path_from_repo(repo);
+    let head_id = head.id().to_string();
+    if let Some(cached) = load_history(&path).filter(|history| history.head == head_id) {
+        return Ok(cached);
+    }
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push(head.id())?;
+    revwalk.simplify_first_parent()?;
+    let mut subjects = Vec::new();
+    for oid in revwalk {
+        let commit = repo.find_commit(oid?)?;
+        // Merge subjects are written by git, not suggested by a model
+        if commit.parent_count() > 1 {
+            continue;
+        }
+        subjects.push(commit.summary().unwrap_or_default().to_string());
+        if subjects.len() == RECENT_SUBJECTS {
+            break;
+        }
+    }
+
+    let history = RecentHistory {
+        head: head_id,
+        subjects,
+    };
+    // The cache only saves time; a read-only git directory is no reason to fail
+    let _ = write_atomic(&path, serde_json::to_string_pretty(&history)?);
+    Ok(history)
+}
+
+fn load_history(path: &Path) -> Option<RecentHistory> {
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(repo: &Repository, message: &str) {
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let signature = git2::Signature::now("Test User", "test@example.com").unwrap();
+        let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+        let parents: Vec<_> = parent.iter().collect();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_recent_history_demotes_repeats() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let repo = Repository::init(dir.path())?;
+        assert_eq!(recent_history_from_repo(&repo)?, RecentHistory::default());
+
+        commit(&repo, "chore: bump dependencies\n\nWeekly update.");
+        commit(&repo, "docs: fix typo");
+        let history = recent_history_from_repo(&repo)?;
+        assert_eq!(
+            history.subjects,
+            ["docs: fix typo", "chore: bump dependencies"]
+        );
+        assert!(history_cache_path_from_repo(&repo).exists());
+
+        let messages = vec![
+            "Chore: bump dependencies\n\nMonthly update.".to_string(),
+            "feat: add login".to_string(),
+            "docs: fix typo".to_string(),
+            "fix: handle timeouts".to_string(),
+        ];
+        assert_eq!(
+            history.demote_repeats(messages),
+            [
+                "feat: add login",
+                "fix: handle timeouts",
+                "Chore: bump dependencies\n\nMonthly update.",
+                "docs: fix typo",
+            ]
+        );
+
+        // A new commit moves HEAD past the cached history
+        commit(&repo, "feat: add login");
+        let history = recent_history_from_repo(&repo)?;
+        assert_eq!(history.subjects.len(), 3);
+        assert!(history.repeats("feat: add login"));
+        Ok(())
+    }
+}
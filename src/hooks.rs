@@ -0,0 +1,458 @@
+//! Git hook integrations
+
+use crate::rules::{lint_message, lint_trailers, Rules, Violation};
+use crate::trailers::{clean_message_file, CleanupMode, MessageCleanup};
+use crate::types::CommittorError;
+use anyhow::Result;
+use git2::{Oid, Repository};
+use serde::{Deserialize, Serialize};
+
+/// How a hook reacts to rule violations
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnforcementMode {
+    /// Reject the operation
+    #[default]
+    Enforce,
+    /// Report violations but let the operation continue
+    Warn,
+}
+
+/// Where a message came from, as git passes it to prepare-commit-msg hooks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommitSource {
+    /// `-m` or `-F`, also used by `git revert`
+    Message,
+    /// `-t` or `commit.template`
+    Template,
+    /// A merge, or a `MERGE_MSG` left behind by one
+    Merge,
+    /// A `SQUASH_MSG` from `git merge --squash`
+    Squash,
+    /// `-c`, `-C` or `--amend`, reusing an existing commit's message
+    Commit,
+}
+
+impl CommitSource {
+    /// Parse the source argument, returning `None` for sources git may add later
+    pub fn from_arg(arg: &str) -> Option<Self> {
+        match arg {
+            "message" => Some(Self::Message),
+            "template" => Some(Self::Template),
+            "merge" => Some(Self::Merge),
+            "squash" => Some(Self::Squash),
+            "commit" => Some(Self::Commit),
+            _ => None,
+        }
+    }
+}
+
+/// Hook behaviour read from the `[hooks]` settings section
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HookSettings {
+    pub pre_receive: EnforcementMode,
+    /// Commit sources prepare-commit-msg leaves alone because git already wrote the message
+    pub skip_sources: Vec<CommitSource>,
+    /// Seconds prepare-commit-msg may spend before giving up and leaving the message alone
+    pub timeout: u64,
+}
+
+impl Default for HookSettings {
+    fn default() -> Self {
+        Self {
+            pre_receive: EnforcementMode::default(),
+            skip_sources: vec![
+                CommitSource::Message,
+                CommitSource::Merge,
+                CommitSource::Squash,
+                CommitSource::Commit,
+            ],
+            timeout: 8,
+        }
+    }
+}
+
+impl HookSettings {
+    /// Whether prepare-commit-msg should generate suggestions for a commit source
+    ///
+    /// Plain `git commit` passes no source; unknown sources get suggestions too.
+    pub fn suggests_for(&self, source: Option<&str>) -> bool {
+        source
+            .and_then(CommitSource::from_arg)
+            .is_none_or(|source| !self.skip_sources.contains(&source))
+    }
+}
+
+/// A single ref update as passed to the pre-receive hook on stdin
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefUpdate {
+    pub old: Oid,
+    pub new: Oid,
+    pub refname: String,
+}
+
+/// Lint result for one pushed commit
+#[derive(Debug, Clone)]
+pub struct CommitLint {
+    pub commit_id: String,
+    pub refname: String,
+    pub subject: String,
+    pub violations: Vec<Violation>,
+}
+
+/// Heading of the commented-out block of suggestions added by prepare-commit-msg
+pub const SUGGESTIONS_HEADING: &str = "Suggested by committor (uncomment one to use it):";
+
+/// Combine the message file git passes to prepare-commit-msg with generated suggestions
+///
+/// A message already in the file (from `-m`, a template or a merge) is never
+/// touched: every suggestion is added as a comment. When the file holds only
+/// git's comments, the first suggestion becomes the message and the rest are
+/// added as comments. Comments use git's comment character, and none are added
+/// when `commit.cleanup` would keep them in the commit.
+pub fn merge_suggestions(
+    content: &str,
+    suggestions: &[String],
+    cleanup: &MessageCleanup,
+) -> String {
+    let Some((first, rest)) = suggestions.split_first() else {
+        return content.to_string();
+    };
+
+    // Git's own comments never count as a message, whatever the cleanup keeps
+    let stripped = MessageCleanup {
+        mode: CleanupMode::Strip,
+        ..*cleanup
+    };
+    let has_message = clean_message_file(content, &stripped).is_some();
+    let alternatives = match (cleanup.strips_comments(), has_message) {
+        (false, _) => &[][..],
+        (true, true) => suggestions,
+        (true, false) => rest,
+    };
+    if has_message && alternatives.is_empty() {
+        return content.to_string();
+    }
+
+    let lines: Vec<&str> = content.lines().collect();
+    let comments_start = trailing_comments_start(&lines, cleanup);
+    let mut merged: Vec<String> = Vec::new();
+
+    if has_message {
+        merged.extend(lines[..comments_start].iter().map(|line| line.to_string()));
+    } else {
+        merged.push(first.trim().to_string());
+    }
+
+    if !alternatives.is_empty() {
+        if merged.last().is_some_and(|line| !line.trim().is_empty()) {
+            merged.push(String::new());
+        }
+        merged.push(cleanup.comment(SUGGESTIONS_HEADING));
+        for (i, suggestion) in alternatives.iter().enumerate() {
+            if i > 0 {
+                merged.push(cleanup.comment(""));
+            }
+            merged.extend(
+                suggestion
+                    .trim()
+                    .lines()
+                    .map(|line| cleanup.comment(&format!("  {line}")).trim_end().to_string()),
+            );
+        }
+    }
+
+    // Keep git's own comments, including a scissors line and the diff below it
+    if comments_start < lines.len() {
+        merged.push(String::new());
+        merged.extend(lines[comments_start..].iter().map(|line| line.to_string()));
+    }
+
+    let mut merged = merged.join("\n");
+    merged.push('\n');
+    merged
+}
+
+/// Index where the closing block of comment and blank lines starts
+fn trailing_comments_start(lines: &[&str], cleanup: &MessageCleanup) -> usize {
+    // Everything below the scissors line belongs to git, diff included
+    let end = lines
+        .iter()
+        .position(|line| cleanup.is_scissors(line))
+        .unwrap_or(lines.len());
+
+    let mut start = end;
+    while start > 0 && (cleanup.is_comment(lines[start - 1]) || lines[start - 1].trim().is_empty())
+    {
+        start -= 1;
+    }
+    // Leave blank lines with the message rather than the comments
+    while start < end && lines[start].trim().is_empty() {
+        start += 1;
+    }
+    start
+}
+
+/// Parse the `<old> <new> <ref>` lines git passes to pre-receive hooks
+pub fn parse_pre_receive_input(input: &str) -> Result<Vec<RefUpdate>> {
+    let mut updates = Vec::new();
+
+    for line in input.lines().filter(|l| !l.trim().is_empty()) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [old, new, refname] = fields[..] else {
+            return Err(CommittorError::GitError(format!(
+                "Invalid pre-receive input line: {line}"
+            ))
+            .into());
+        };
+
+        updates.push(RefUpdate {
+            old: Oid::from_str(old)?,
+            new: Oid::from_str(new)?,
+            refname: refname.to_string(),
+        });
+    }
+
+    Ok(updates)
+}
+
+/// List the commits a ref update introduces, oldest first
+pub fn new_commits_for_update(repo: &Repository, update: &RefUpdate) -> Result<Vec<Oid>> {
+    // Deleting a ref introduces nothing
+    if update.new.is_zero() {
+        return Ok(Vec::new());
+    }
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE)?;
+    revwalk.push(update.new)?;
+
+    if update.old.is_zero() {
+        // A new ref: everything not already reachable from an existing ref is new
+        for reference in repo.references()? {
+            if let Some(target) = reference?.target() {
+                revwalk.hide(target)?;
+            }
+        }
+    } else {
+        revwalk.hide(update.old)?;
+    }
+
+    Ok(revwalk.collect::<Result<Vec<_>, _>>()?)
+}
+
+/// Lint every new non-merge commit introduced by the given ref updates
+pub fn lint_ref_updates(
+    repo: &Repository,
+    updates: &[RefUpdate],
+    rules: &Rules,
+    required_trailers: &[String],
+) -> Result<Vec<CommitLint>> {
+    let mut results = Vec::new();
+
+    for update in updates {
+        for oid in new_commits_for_update(repo, update)? {
+            let commit = repo.find_commit(oid)?;
+            // Merge commits carry git-generated messages
+            if commit.parent_count() > 1 {
+                continue;
+            }
+
+            let message = commit.message().unwrap_or_default();
+            let mut violations = lint_message(message, rules);
+            violations.extend(lint_trailers(message, required_trailers));
+            results.push(CommitLint {
+                commit_id: oid.to_string(),
+                refname: update.refname.clone(),
+                subject: commit.summary().unwrap_or_default().to_string(),
+                violations,
+            });
+        }
+    }
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn commit(repo: &Repository, message: &str) -> Oid {
+        let signature = git2::Signature::now("Test User", "test@example.com").unwrap();
+        let tree_id = repo.index().unwrap().write_tree().unwrap();
+        let tree = repo.find_tree(tree_id).unwrap();
+        let parents = match repo.head() {
+            Ok(head) => vec![head.peel_to_commit().unwrap()],
+            Err(_) => Vec::new(),
+        };
+        let parent_refs: Vec<&git2::Commit> = parents.iter().collect();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parent_refs,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_suggests_for_sources() {
+        let settings = HookSettings::default();
+        assert!(settings.suggests_for(None));
+        assert!(settings.suggests_for(Some("template")));
+        assert!(settings.suggests_for(Some("something-new")));
+        assert!(!settings.suggests_for(Some("merge")));
+        assert!(!settings.suggests_for(Some("message")));
+
+        let settings = HookSettings {
+            skip_sources: vec![CommitSource::Template],
+            ..HookSettings::default()
+        };
+        assert!(settings.suggests_for(Some("merge")));
+        assert!(!settings.suggests_for(Some("template")));
+    }
+
+    #[test]
+    fn test_merge_suggestions_fills_empty_message() {
+        let content =
+            "\n# Please enter the commit message for your changes.\n#\n# On branch main\n";
+        let suggestions = vec![
+            "feat: add login".to_string(),
+            "feat(auth): add login\n\nAdds a form".to_string(),
+        ];
+
+        let merged = merge_suggestions(content, &suggestions, &MessageCleanup::default());
+        assert_eq!(
+            merged,
+            "feat: add login\n\n\
+             # Suggested by committor (uncomment one to use it):\n\
+             #   feat(auth): add login\n\
+             #\n\
+             #   Adds a form\n\n\
+             # Please enter the commit message for your changes.\n#\n# On branch main\n"
+        );
+        assert_eq!(
+            clean_message_file(&merged, &MessageCleanup::default()).as_deref(),
+            Some("feat: add login")
+        );
+    }
+
+    #[test]
+    fn test_merge_suggestions_keeps_existing_message() {
+        let content = "Merge branch 'topic'\n\n# Conflicts:\n#\tsrc/lib.rs\n";
+        let suggestions = vec!["fix: resolve conflicts".to_string()];
+
+        let merged = merge_suggestions(content, &suggestions, &MessageCleanup::default());
+        assert!(merged.starts_with("Merge branch 'topic'\n\n# Suggested by committor"));
+        assert!(merged.contains("#   fix: resolve conflicts\n\n# Conflicts:\n#\tsrc/lib.rs\n"));
+        assert_eq!(
+            clean_message_file(&merged, &MessageCleanup::default()).as_deref(),
+            Some("Merge branch 'topic'")
+        );
+
+        assert_eq!(
+            merge_suggestions(content, &[], &MessageCleanup::default()),
+            content
+        );
+    }
+
+    #[test]
+    fn test_merge_suggestions_follows_git_comments() {
+        let suggestions = vec![
+            "feat: add login".to_string(),
+            "feat(auth): add login".to_string(),
+        ];
+
+        // With `core.commentChar=;` the suggestions are commented out with `;`
+        let content = "\n; Please enter the commit message for your changes.\n";
+        let cleanup = MessageCleanup::from_config(Some(";"), None, content);
+        let merged = merge_suggestions(content, &suggestions, &cleanup);
+        assert_eq!(
+            merged,
+            "feat: add login\n\n\
+             ; Suggested by committor (uncomment one to use it):\n\
+             ;   feat(auth): add login\n\n\
+             ; Please enter the commit message for your changes.\n"
+        );
+        assert_eq!(
+            clean_message_file(&merged, &cleanup).as_deref(),
+            Some("feat: add login")
+        );
+
+        // Git would keep comments in the commit, so no alternatives are added
+        let content = "\n# Lines starting with '#' will be kept.\n";
+        let cleanup = MessageCleanup::from_config(None, Some("whitespace"), content);
+        let merged = merge_suggestions(content, &suggestions, &cleanup);
+        assert_eq!(
+            merged,
+            "feat: add login\n\n# Lines starting with '#' will be kept.\n"
+        );
+        let content = "fix: typo\n";
+        assert_eq!(merge_suggestions(content, &suggestions, &cleanup), content);
+    }
+
+    #[test]
+    fn test_merge_suggestions_stays_above_scissors() {
+        let content = "\n# ------------------------ >8 ------------------------\n# Do not modify or remove the line above.\ndiff --git a/x b/x\n";
+        let merged = merge_suggestions(
+            content,
+            &["docs: update x".to_string()],
+            &MessageCleanup::default(),
+        );
+        assert!(merged.starts_with("docs: update x\n\n# ------------------------ >8"));
+        assert!(merged.ends_with("diff --git a/x b/x\n"));
+    }
+
+    #[test]
+    fn test_parse_pre_receive_input() {
+        let zero = Oid::zero().to_string();
+        let sha = "1234567890abcdef1234567890abcdef12345678";
+        let input = format!("{zero} {sha} refs/heads/main\n\n");
+
+        let updates = parse_pre_receive_input(&input).unwrap();
+        assert_eq!(updates.len(), 1);
+        assert!(updates[0].old.is_zero());
+        assert_eq!(updates[0].refname, "refs/heads/main");
+
+        assert!(parse_pre_receive_input("not enough fields").is_err());
+    }
+
+    #[test]
+    fn test_lint_ref_updates() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = Repository::init(temp_dir.path()).unwrap();
+
+        let base = commit(&repo, "feat: initial setup");
+        let good = commit(&repo, "fix(api): handle timeouts");
+        let bad = commit(&repo, "WIP stuff");
+
+        let updates = [RefUpdate {
+            old: base,
+            new: bad,
+            refname: "refs/heads/main".to_string(),
+        }];
+
+        let results = lint_ref_updates(&repo, &updates, &Rules::default(), &[]).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].commit_id, good.to_string());
+        assert!(results[0].violations.is_empty());
+        assert_eq!(results[1].commit_id, bad.to_string());
+        assert!(!results[1].violations.is_empty());
+
+        let required = vec!["Signed-off-by".to_string()];
+        let results = lint_ref_updates(&repo, &updates, &Rules::default(), &required).unwrap();
+        assert_eq!(results[0].violations[0].rule, "trailer-required");
+
+        let deletion = RefUpdate {
+            old: bad,
+            new: Oid::zero(),
+            refname: "refs/heads/old".to_string(),
+        };
+        assert!(new_commits_for_update(&repo, &deletion).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,710 @@
+//! Catalog of user-facing strings and the locale they are shown in
+//!
+//! The commit flow, `split`, `rewrite` and `gate` are translated; reports meant for
+//! tooling or sharing, such as `eval`, `audit` and `config`, stay in English.
+
+use std::env;
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+/// Languages the interface is translated into
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    English,
+    Japanese,
+    Chinese,
+    Spanish,
+}
+
+impl Locale {
+    /// Parse a locale tag such as `ja`, `zh_CN.UTF-8` or `es-MX`, ignoring region and encoding
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let language = tag.split(['_', '-', '.', '@']).next()?.to_ascii_lowercase();
+        match language.as_str() {
+            "en" | "c" | "posix" => Some(Self::English),
+            "ja" => Some(Self::Japanese),
+            "zh" => Some(Self::Chinese),
+            "es" => Some(Self::Spanish),
+            _ => None,
+        }
+    }
+
+    /// Pick the locale from `LC_ALL`, `LC_MESSAGES` or `LANG`, in that order of precedence
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| Self::from_tag(&value))
+            .unwrap_or_default()
+    }
+}
+
+static LOCALE: OnceLock<Locale> = OnceLock::new();
+
+/// Set the locale for the rest of the process; only the first call has any effect
+pub fn set_locale(locale: Locale) {
+    let _ = LOCALE.set(locale);
+}
+
+/// Get the current locale, English until one is set
+pub fn locale() -> Locale {
+    LOCALE.get().copied().unwrap_or_default()
+}
+
+/// A user-facing string; templates take their arguments in place of each `{}`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Text {
+    GeneratedOptions,
+    SimilarToPrevious,
+    RepeatsRecentCommit,
+    CollapsedBody,
+    PickerViewHint,
+    PickerPrompt,
+    PickerScopeHint,
+    PickerFeedbackHint,
+    FeedbackRecorded,
+    ChoosePrompt,
+    InvalidChoice,
+    PartialOptions,
+    ExcludeFilesPrompt,
+    ExcludedFilesNote,
+    CannotExcludeAll,
+    SelectCommitsPrompt,
+    InvalidSelection,
+    ConfirmCommits,
+    ConfirmRewrite,
+    CommittingWith,
+    CommitSuccessful,
+    CommitHash,
+    NoStagedChangesHint,
+    VendoredFilesWarning,
+    CommitCancelled,
+    EmptyMessageCancelled,
+    StagedChangesChanged,
+    StalePrompt,
+    WaitingForIndexLock,
+    Cancelled,
+    UpdateAvailable,
+    RewriteCancelled,
+    ProposedMessages,
+    RewriteBecomes,
+    RewriteUnchanged,
+    RewriteKept,
+    GateNoChanges,
+    GateTitleMatches,
+    GateTitleMismatch,
+    GateClosestSubject,
+    GateTypeMismatch,
+    GateLowSimilarity,
+    GateTitleRejected,
+    GateCommentMatches,
+    GateCommentMismatch,
+    GateCommentGenerated,
+    RewriteFailed,
+    RewriteDone,
+    ProposedCommits,
+    SplitCancelled,
+    SplitFailed,
+    SplitRestored,
+    SplitCommitted,
+    PerFileOptions,
+    QuickFallbackCommit,
+    QuickFallbackBreaksRules,
+    ErrGitRepoNotFound,
+    ErrNoStagedChanges,
+    ErrAIProvider,
+    ErrOllamaUnavailable,
+    ErrGit,
+    ErrInvalidCommitFormat,
+    ErrModelRefusal,
+    ErrConfig,
+    ErrSecretDetected,
+    ErrMissingTrailers,
+    ErrTimeout,
+    ErrRateLimited,
+    ErrContextTooLarge,
+    ErrValidationFailed,
+}
+
+impl Text {
+    /// Every string in the catalog
+    pub const ALL: &'static [Text] = &[
+        Self::GeneratedOptions,
+        Self::SimilarToPrevious,
+        Self::RepeatsRecentCommit,
+        Self::CollapsedBody,
+        Self::PickerViewHint,
+        Self::PickerPrompt,
+        Self::PickerScopeHint,
+        Self::PickerFeedbackHint,
+        Self::FeedbackRecorded,
+        Self::ChoosePrompt,
+        Self::InvalidChoice,
+        Self::PartialOptions,
+        Self::ExcludeFilesPrompt,
+        Self::ExcludedFilesNote,
+        Self::CannotExcludeAll,
+        Self::SelectCommitsPrompt,
+        Self::InvalidSelection,
+        Self::ConfirmCommits,
+        Self::ConfirmRewrite,
+        Self::CommittingWith,
+        Self::CommitSuccessful,
+        Self::CommitHash,
+        Self::NoStagedChangesHint,
+        Self::VendoredFilesWarning,
+        Self::CommitCancelled,
+        Self::EmptyMessageCancelled,
+        Self::StagedChangesChanged,
+        Self::StalePrompt,
+        Self::WaitingForIndexLock,
+        Self::Cancelled,
+        Self::UpdateAvailable,
+        Self::RewriteCancelled,
+        Self::ProposedMessages,
+        Self::RewriteBecomes,
+        Self::RewriteUnchanged,
+        Self::RewriteKept,
+        Self::GateNoChanges,
+        Self::GateTitleMatches,
+        Self::GateTitleMismatch,
+        Self::GateClosestSubject,
+        Self::GateTypeMismatch,
+        Self::GateLowSimilarity,
+        Self::GateTitleRejected,
+        Self::GateCommentMatches,
+        Self::GateCommentMismatch,
+        Self::GateCommentGenerated,
+        Self::RewriteFailed,
+        Self::RewriteDone,
+        Self::ProposedCommits,
+        Self::SplitCancelled,
+        Self::SplitFailed,
+        Self::SplitRestored,
+        Self::SplitCommitted,
+        Self::PerFileOptions,
+        Self::QuickFallbackCommit,
+        Self::QuickFallbackBreaksRules,
+        Self::ErrGitRepoNotFound,
+        Self::ErrNoStagedChanges,
+        Self::ErrAIProvider,
+        Self::ErrOllamaUnavailable,
+        Self::ErrGit,
+        Self::ErrInvalidCommitFormat,
+        Self::ErrModelRefusal,
+        Self::ErrConfig,
+        Self::ErrSecretDetected,
+        Self::ErrMissingTrailers,
+        Self::ErrTimeout,
+        Self::ErrRateLimited,
+        Self::ErrContextTooLarge,
+        Self::ErrValidationFailed,
+    ];
+
+    /// Get the string in the current locale
+    pub fn text(self) -> &'static str {
+        self.in_locale(locale())
+    }
+
+    /// Fill the template's `{}` placeholders, in the current locale
+    pub fn format(self, args: &[&dyn Display]) -> String {
+        fill(self.text(), args)
+    }
+
+    /// Get the string in a given locale
+    pub fn in_locale(self, locale: Locale) -> &'static str {
+        let [en, ja, zh, es] = self.translations();
+        match locale {
+            Locale::English => en,
+            Locale::Japanese => ja,
+            Locale::Chinese => zh,
+            Locale::Spanish => es,
+        }
+    }
+
+    /// English, Japanese, Chinese and Spanish, in that order
+    fn translations(self) -> [&'static str; 4] {
+        match self {
+            Self::GeneratedOptions => [
+                "Generated commit message options:",
+                "生成されたコミットメッセージの候補:",
+                "生成的提交信息选项：",
+                "Opciones de mensaje de commit generadas:",
+            ],
+            Self::SimilarToPrevious => [
+                "(similar to previous commit)",
+                "(直前のコミットと類似)",
+                "（与上一次提交相似）",
+                "(similar al commit anterior)",
+            ],
+            Self::RepeatsRecentCommit => [
+                "(repeats a recent commit)",
+                "(最近のコミットと同じ)",
+                "（与最近的提交重复）",
+                "(repite un commit reciente)",
+            ],
+            Self::CollapsedBody => [
+                "(+{} more lines, 'v{}' to view)",
+                "(ほか {} 行、'v{}' で表示)",
+                "（另有 {} 行，输入 'v{}' 查看）",
+                "(+{} líneas más, 'v{}' para verlas)",
+            ],
+            Self::PickerViewHint => [
+                ", 'v1' to view a body",
+                "、'v1' で本文を表示",
+                "，'v1' 查看正文",
+                ", 'v1' para ver un cuerpo",
+            ],
+            Self::PickerPrompt => [
+                "Choose an option (1-{}{}, 'e' to edit, 'r' to regenerate, 'x' to leave files out, 'c' to cancel): ",
+                "候補を選択してください (1-{}{}、'e' で編集、'r' で再生成、'x' でファイルを除外、'c' でキャンセル): ",
+                "请选择一个选项（1-{}{}，'e' 编辑，'r' 重新生成，'x' 排除文件，'c' 取消）：",
+                "Elige una opción (1-{}{}, 'e' para editar, 'r' para regenerar, 'x' para omitir archivos, 'c' para cancelar): ",
+            ],
+            Self::PickerScopeHint => [
+                ", add a scope key like '1a'",
+                "、'1a' のようにスコープキーを付けられます",
+                "，可加作用域键如 '1a'",
+                ", añade una clave de ámbito como '1a'",
+            ],
+            Self::PickerFeedbackHint => [
+                ", '+1'/'-1' to rate",
+                "、'+1'/'-1' で評価",
+                "，'+1'/'-1' 评价",
+                ", '+1'/'-1' para valorar",
+            ],
+            Self::FeedbackRecorded => [
+                "Rating of option {} saved",
+                "候補 {} の評価を保存しました",
+                "已保存选项 {} 的评价",
+                "Valoración de la opción {} guardada",
+            ],
+            Self::ChoosePrompt => [
+                "Choose an option (1-{}, or 'q' to quit): ",
+                "候補を選択してください (1-{}、'q' で終了): ",
+                "请选择一个选项（1-{}，或输入 'q' 退出）：",
+                "Elige una opción (1-{}, o 'q' para salir): ",
+            ],
+            Self::InvalidChoice => [
+                "Invalid choice. Please try again.",
+                "無効な選択です。もう一度入力してください。",
+                "无效的选择，请重试。",
+                "Opción no válida. Inténtalo de nuevo.",
+            ],
+            Self::PartialOptions => [
+                "{} of {} ready, press 'm' for more",
+                "{} / {} 件準備完了、'm' で残りを表示",
+                "已就绪 {} / {} 个，按 'm' 查看更多",
+                "{} de {} listas, pulsa 'm' para ver más",
+            ],
+            Self::ExcludeFilesPrompt => [
+                "Files to leave out of the context sent to the model (e.g. 1,3; listed ones toggle back in; Enter to keep): ",
+                "モデルに送るコンテキストから除外するファイル (例: 1,3。除外済みのものは戻ります。Enter で変更なし): ",
+                "要从发送给模型的上下文中排除的文件（例如 1,3；已排除的会恢复；按 Enter 保持不变）：",
+                "Archivos que omitir del contexto enviado al modelo (p. ej. 1,3; los ya omitidos vuelven; Enter para mantener): ",
+            ],
+            Self::ExcludedFilesNote => [
+                "Left out of the context (still staged): {}",
+                "コンテキストから除外 (ステージは維持): {}",
+                "已从上下文中排除（仍保持暂存）：{}",
+                "Omitidos del contexto (siguen preparados): {}",
+            ],
+            Self::CannotExcludeAll => [
+                "At least one file has to stay in the context.",
+                "少なくとも 1 つのファイルはコンテキストに残す必要があります。",
+                "至少要有一个文件保留在上下文中。",
+                "Al menos un archivo debe quedar en el contexto.",
+            ],
+            Self::SelectCommitsPrompt => [
+                "Select commits to create (e.g. 1,3 or 1-{} or 'all', 'q' to quit): ",
+                "作成するコミットを選択してください (例: 1,3、1-{}、'all'。'q' で終了): ",
+                "请选择要创建的提交（例如 1,3、1-{} 或 'all'，'q' 退出）：",
+                "Selecciona los commits a crear (p. ej. 1,3, 1-{} o 'all'; 'q' para salir): ",
+            ],
+            Self::InvalidSelection => [
+                "Invalid selection. Please try again.",
+                "無効な指定です。もう一度入力してください。",
+                "无效的选择范围，请重试。",
+                "Selección no válida. Inténtalo de nuevo.",
+            ],
+            Self::ConfirmCommits => [
+                "Create these commits? [y/N]: ",
+                "これらのコミットを作成しますか? [y/N]: ",
+                "是否创建这些提交？[y/N]：",
+                "¿Crear estos commits? [y/N]: ",
+            ],
+            Self::ConfirmRewrite => [
+                "Rewrite these commit messages? [y/N]: ",
+                "これらのコミットメッセージを書き換えますか? [y/N]: ",
+                "是否改写这些提交信息？[y/N]：",
+                "¿Reescribir estos mensajes de commit? [y/N]: ",
+            ],
+            Self::CommittingWith => [
+                "Committing with message: {}",
+                "次のメッセージでコミットします: {}",
+                "使用以下信息提交：{}",
+                "Haciendo commit con el mensaje: {}",
+            ],
+            Self::CommitSuccessful => [
+                "Commit successful!",
+                "コミットしました",
+                "提交成功！",
+                "¡Commit realizado!",
+            ],
+            Self::CommitHash => [
+                "Commit hash: {}",
+                "コミットハッシュ: {}",
+                "提交哈希：{}",
+                "Hash del commit: {}",
+            ],
+            Self::NoStagedChangesHint => [
+                "No staged changes found. Use 'git add' to stage changes first.",
+                "ステージされた変更がありません。先に 'git add' で変更をステージしてください。",
+                "没有已暂存的更改。请先使用 'git add' 暂存更改。",
+                "No hay cambios preparados. Usa 'git add' para prepararlos primero.",
+            ],
+            Self::VendoredFilesWarning => [
+                "These staged files look vendored or minified; their contents won't be sent, and you may not mean to commit them:",
+                "次のステージ済みファイルはベンダリングまたは圧縮されたコードのようです。内容は送信されません。コミットする意図があるか確認してください:",
+                "以下已暂存的文件看起来是第三方或压缩后的代码；其内容不会被发送，请确认是否确实要提交：",
+                "Estos archivos preparados parecen código de terceros o minificado; su contenido no se enviará y quizá no quieras hacer commit de ellos:",
+            ],
+            Self::CommitCancelled => [
+                "Commit cancelled.",
+                "コミットを中止しました。",
+                "已取消提交。",
+                "Commit cancelado.",
+            ],
+            Self::EmptyMessageCancelled => [
+                "Empty message, commit cancelled.",
+                "メッセージが空のため、コミットを中止しました。",
+                "信息为空，已取消提交。",
+                "Mensaje vacío, commit cancelado.",
+            ],
+            Self::StagedChangesChanged => [
+                "The staged changes have changed since these messages were generated.",
+                "メッセージの生成後にステージされた変更が変わりました。",
+                "生成这些信息后，暂存的更改已发生变化。",
+                "Los cambios preparados han cambiado desde que se generaron estos mensajes.",
+            ],
+            Self::StalePrompt => [
+                "[R]egenerate for the current changes, commit [a]nyway or [q]uit: ",
+                "現在の変更で[R]再生成、そのまま[a]コミット、[q]終了: ",
+                "[R]按当前更改重新生成，[a]仍然提交，[q]退出：",
+                "[R]egenerar para los cambios actuales, hacer commit de todos modos [a] o salir [q]: ",
+            ],
+            Self::WaitingForIndexLock => [
+                "Another git process is using the index; waiting up to {}s for {} to go away...",
+                "別の git プロセスがインデックスを使用中です。最大 {} 秒、{} が消えるのを待ちます...",
+                "另一个 git 进程正在使用索引；最多等待 {} 秒直到 {} 消失...",
+                "Otro proceso de git está usando el índice; esperando hasta {}s a que desaparezca {}...",
+            ],
+            Self::Cancelled => [
+                "Cancelled.",
+                "キャンセルしました。",
+                "已取消。",
+                "Cancelado.",
+            ],
+            Self::UpdateAvailable => [
+                "A new version of committor is available: {} (you have {})",
+                "committor の新しいバージョンがあります: {} (現在のバージョン: {})",
+                "committor 有新版本可用：{}（当前版本：{}）",
+                "Hay una nueva versión de committor disponible: {} (tienes la {})",
+            ],
+            Self::RewriteCancelled => [
+                "Rewrite cancelled.",
+                "書き換えを中止しました。",
+                "已取消改写。",
+                "Reescritura cancelada.",
+            ],
+            Self::ProposedMessages => [
+                "Proposed messages:",
+                "提案されたメッセージ:",
+                "建议的提交信息：",
+                "Mensajes propuestos:",
+            ],
+            Self::RewriteBecomes => [
+                "becomes",
+                "変更後",
+                "改为",
+                "pasa a",
+            ],
+            Self::RewriteUnchanged => [
+                "(unchanged)",
+                "(変更なし)",
+                "（未更改）",
+                "(sin cambios)",
+            ],
+            Self::RewriteKept => [
+                "(kept, no message generated)",
+                "(メッセージが生成されなかったため維持)",
+                "（保留原样，未生成信息）",
+                "(se mantiene, no se generó ningún mensaje)",
+            ],
+            Self::GateNoChanges => [
+                "No changes found to check the title against.",
+                "タイトルを確認する対象の変更が見つかりません。",
+                "没有找到可用于检查标题的更改。",
+                "No se encontraron cambios con los que comprobar el título.",
+            ],
+            Self::GateTitleMatches => [
+                "The title matches the change",
+                "タイトルは変更内容と一致しています",
+                "标题与更改相符",
+                "El título coincide con el cambio",
+            ],
+            Self::GateTitleMismatch => [
+                "The title \"{}\" may not describe this change:",
+                "タイトル「{}」はこの変更を表していない可能性があります:",
+                "标题“{}”可能没有描述此更改：",
+                "Puede que el título \"{}\" no describa este cambio:",
+            ],
+            Self::GateClosestSubject => [
+                "closest generated subject: {}",
+                "最も近い生成された件名: {}",
+                "最接近的生成标题：{}",
+                "asunto generado más parecido: {}",
+            ],
+            Self::GateTypeMismatch => [
+                "the title's type is `{}`, but the change looks like `{}`",
+                "タイトルの種類は `{}` ですが、変更は `{}` のようです",
+                "标题的类型是 `{}`，但更改看起来像 `{}`",
+                "el tipo del título es `{}`, pero el cambio parece `{}`",
+            ],
+            Self::GateLowSimilarity => [
+                "the title has little in common with a description of the change (similarity {}, at least {} expected)",
+                "タイトルは変更の説明とほとんど共通点がありません (類似度 {}、{} 以上が必要)",
+                "标题与更改的描述几乎没有共同之处（相似度 {}，至少应为 {}）",
+                "el título tiene poco en común con una descripción del cambio (similitud {}, se esperaba al menos {})",
+            ],
+            Self::GateTitleRejected => [
+                "The pull request title does not match its change",
+                "プルリクエストのタイトルが変更内容と一致しません",
+                "拉取请求的标题与其更改不符",
+                "El título de la pull request no coincide con su cambio",
+            ],
+            Self::GateCommentMatches => [
+                "✅ The title `{}` matches the change.",
+                "✅ タイトル `{}` は変更内容と一致しています。",
+                "✅ 标题 `{}` 与更改相符。",
+                "✅ El título `{}` coincide con el cambio.",
+            ],
+            Self::GateCommentMismatch => [
+                "⚠️ The title `{}` may not describe this change:",
+                "⚠️ タイトル `{}` はこの変更を表していない可能性があります:",
+                "⚠️ 标题 `{}` 可能没有描述此更改：",
+                "⚠️ Puede que el título `{}` no describa este cambio:",
+            ],
+            Self::GateCommentGenerated => [
+                "Subjects generated for the squashed diff:",
+                "スカッシュした差分から生成された件名:",
+                "为压缩后的差异生成的标题：",
+                "Asuntos generados para el diff combinado:",
+            ],
+            Self::RewriteFailed => [
+                "Rewrite failed, aborting the rebase...",
+                "書き換えに失敗したため、リベースを中止しています...",
+                "改写失败，正在中止变基...",
+                "La reescritura falló, abortando el rebase...",
+            ],
+            Self::RewriteDone => [
+                "Rewrote {} commit messages; `committor restore` undoes it",
+                "{} 件のコミットメッセージを書き換えました。`committor restore` で元に戻せます",
+                "已改写 {} 条提交信息；`committor restore` 可撤销",
+                "Se reescribieron {} mensajes de commit; `committor restore` lo deshace",
+            ],
+            Self::ProposedCommits => [
+                "Proposed commits:",
+                "提案されたコミット:",
+                "建议的提交：",
+                "Commits propuestos:",
+            ],
+            Self::SplitCancelled => [
+                "Split cancelled.",
+                "分割を中止しました。",
+                "已取消拆分。",
+                "División cancelada.",
+            ],
+            Self::SplitFailed => [
+                "Split failed, rolling back...",
+                "分割に失敗したため、元に戻しています...",
+                "拆分失败，正在回滚...",
+                "La división falló, revirtiendo...",
+            ],
+            Self::SplitRestored => [
+                "Restored the original HEAD and staged changes",
+                "元の HEAD とステージ済みの変更を復元しました",
+                "已恢复原来的 HEAD 和暂存的更改",
+                "Se restauraron el HEAD original y los cambios preparados",
+            ],
+            Self::SplitCommitted => [
+                "Committed:",
+                "コミットしました:",
+                "已提交：",
+                "Commit creado:",
+            ],
+            Self::PerFileOptions => [
+                "Generated per-file commit messages:",
+                "ファイルごとに生成されたコミットメッセージ:",
+                "按文件生成的提交信息：",
+                "Mensajes de commit generados por archivo:",
+            ],
+            Self::QuickFallbackCommit => [
+                "Committing a message written from the changed paths",
+                "変更されたパスから作成したメッセージでコミットします",
+                "正在使用根据更改路径编写的信息提交",
+                "Haciendo commit con un mensaje escrito a partir de las rutas cambiadas",
+            ],
+            Self::QuickFallbackBreaksRules => [
+                "the message written from the changed paths breaks the rules: {}",
+                "変更されたパスから作成したメッセージがルールに違反しています: {}",
+                "根据更改路径编写的信息违反了规则：{}",
+                "el mensaje escrito a partir de las rutas cambiadas incumple las reglas: {}",
+            ],
+            Self::ErrGitRepoNotFound => [
+                "Git repository not found",
+                "Git リポジトリが見つかりません",
+                "未找到 Git 仓库",
+                "No se encontró el repositorio Git",
+            ],
+            Self::ErrNoStagedChanges => [
+                "No staged changes found",
+                "ステージされた変更がありません",
+                "没有已暂存的更改",
+                "No hay cambios preparados",
+            ],
+            Self::ErrAIProvider => [
+                "AI Provider error: {}",
+                "AI プロバイダーのエラー: {}",
+                "AI 服务提供方错误：{}",
+                "Error del proveedor de IA: {}",
+            ],
+            Self::ErrOllamaUnavailable => [
+                "Ollama is not available at {}. Please make sure Ollama is running.",
+                "{} の Ollama に接続できません。Ollama が起動していることを確認してください。",
+                "无法连接 {} 上的 Ollama，请确认 Ollama 正在运行。",
+                "Ollama no está disponible en {}. Asegúrate de que Ollama esté en ejecución.",
+            ],
+            Self::ErrGit => [
+                "Git operation failed: {}",
+                "Git の操作に失敗しました: {}",
+                "Git 操作失败：{}",
+                "Falló la operación de Git: {}",
+            ],
+            Self::ErrInvalidCommitFormat => [
+                "Invalid commit message format: {}",
+                "コミットメッセージの形式が正しくありません: {}",
+                "提交信息格式无效：{}",
+                "Formato de mensaje de commit no válido: {}",
+            ],
+            Self::ErrModelRefusal => [
+                "The model refused to write a commit message (\"{}\"). If the diff holds sensitive content, try `--privacy redacted` or `--privacy paths-only`",
+                "モデルがコミットメッセージの作成を拒否しました（「{}」）。差分に機密性の高い内容が含まれる場合は `--privacy redacted` または `--privacy paths-only` を試してください",
+                "模型拒绝生成提交信息（“{}”）。如果差异包含敏感内容，请尝试 `--privacy redacted` 或 `--privacy paths-only`",
+                "El modelo se negó a escribir un mensaje de commit («{}»). Si el diff contiene contenido sensible, prueba `--privacy redacted` o `--privacy paths-only`",
+            ],
+            Self::ErrConfig => [
+                "Configuration error: {}",
+                "設定エラー: {}",
+                "配置错误：{}",
+                "Error de configuración: {}",
+            ],
+            Self::ErrSecretDetected => [
+                "Commit message contains a secret ({}); refusing to commit it",
+                "コミットメッセージに秘密情報 ({}) が含まれているため、コミットしません",
+                "提交信息包含敏感信息（{}），拒绝提交",
+                "El mensaje de commit contiene un secreto ({}); no se hará el commit",
+            ],
+            Self::ErrMissingTrailers => [
+                "Commit message is missing required trailers: {}",
+                "コミットメッセージに必須のトレーラーがありません: {}",
+                "提交信息缺少必需的 trailer：{}",
+                "Al mensaje de commit le faltan trailers obligatorios: {}",
+            ],
+            Self::ErrTimeout => [
+                "The provider didn't answer in time: {}",
+                "プロバイダーが時間内に応答しませんでした: {}",
+                "服务提供方未能及时响应：{}",
+                "El proveedor no respondió a tiempo: {}",
+            ],
+            Self::ErrRateLimited => [
+                "The provider is rate limiting requests, try again shortly: {}",
+                "プロバイダーがリクエストを制限しています。しばらくしてから再試行してください: {}",
+                "服务提供方正在限制请求频率，请稍后重试：{}",
+                "El proveedor está limitando las solicitudes, vuelve a intentarlo en breve: {}",
+            ],
+            Self::ErrContextTooLarge => [
+                "The prompt is larger than the model's context window; stage fewer changes or try `committor split`: {}",
+                "プロンプトがモデルのコンテキストウィンドウを超えています。ステージする変更を減らすか `committor split` を試してください: {}",
+                "提示词超出了模型的上下文窗口；请减少暂存的更改或尝试 `committor split`：{}",
+                "El prompt supera la ventana de contexto del modelo; prepara menos cambios o prueba `committor split`: {}",
+            ],
+            Self::ErrValidationFailed => [
+                "Validation failed: {}",
+                "検証に失敗しました: {}",
+                "校验失败：{}",
+                "La validación falló: {}",
+            ],
+        }
+    }
+}
+
+/// Replace each `{}` in a template with the next argument
+fn fill(template: &str, args: &[&dyn Display]) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut args = args.iter();
+    let mut parts = template.split("{}");
+    if let Some(first) = parts.next() {
+        output.push_str(first);
+    }
+    for part in parts {
+        if let Some(arg) = args.next() {
+            output.push_str(&arg.to_string());
+        }
+        output.push_str(part);
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale_from_tag() {
+        assert_eq!(Locale::from_tag("ja_JP.UTF-8"), Some(Locale::Japanese));
+        assert_eq!(Locale::from_tag("zh-TW"), Some(Locale::Chinese));
+        assert_eq!(Locale::from_tag("es"), Some(Locale::Spanish));
+        assert_eq!(Locale::from_tag("C.UTF-8"), Some(Locale::English));
+        assert_eq!(Locale::from_tag("fr_FR"), None);
+    }
+
+    #[test]
+    fn test_translations_keep_placeholders() {
+        for text in Text::ALL {
+            let [en, ja, zh, es] = text.translations();
+            for translation in [ja, zh, es] {
+                assert!(!translation.is_empty(), "{text:?}");
+                assert_eq!(
+                    translation.matches("{}").count(),
+                    en.matches("{}").count(),
+                    "{text:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_fill() {
+        assert_eq!(
+            fill(
+                Text::UpdateAvailable.in_locale(Locale::English),
+                &[&"0.2.0", &"0.1.0"]
+            ),
+            "A new version of committor is available: 0.2.0 (you have 0.1.0)"
+        );
+        assert_eq!(fill("Ollama at {}", &[]), "Ollama at ");
+    }
+}
@@ -0,0 +1,237 @@
+//! Git LFS pointer files described by the sizes of their objects
+//!
+//! With LFS, git stores a short pointer in place of a large file, so the patch
+//! of a replaced model or video is two hashes that tell the model nothing. The
+//! pointer patch is replaced with the sizes it records. Only the pointers in
+//! the diff are read; the objects themselves are never fetched or sent.
+
+use crate::assets::format_size;
+use std::fmt;
+
+/// First line of every LFS pointer file
+const POINTER_VERSION: &str = "version https://git-lfs.github.com/spec/v1";
+
+/// An LFS pointer file, as stored in git in place of the object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LfsPointer {
+    /// Object ID, e.g. `sha256:4d7a...`
+    pub oid: String,
+    /// Size of the object in bytes
+    pub size: usize,
+}
+
+/// Parse an LFS pointer, `None` if the text isn't one
+pub fn parse_pointer(text: &str) -> Option<LfsPointer> {
+    let mut lines = text.lines();
+    if lines.next()?.trim_end() != POINTER_VERSION {
+        return None;
+    }
+
+    let (mut oid, mut size) = (None, None);
+    for line in lines {
+        let (key, value) = line.trim_end().split_once(' ')?;
+        match key {
+            "oid" => oid = Some(value.to_string()),
+            "size" => size = Some(value.parse().ok()?),
+            _ => {}
+        }
+    }
+    Some(LfsPointer {
+        oid: oid?,
+        size: size?,
+    })
+}
+
+/// A file in a diff whose old or new version is an LFS pointer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LfsChange {
+    pub path: String,
+    /// The object before the change, `None` for new files and files moved into LFS
+    pub old: Option<LfsPointer>,
+    /// The object after the change, `None` for deleted files and files moved out of LFS
+    pub new: Option<LfsPointer>,
+}
+
+impl fmt::Display for LfsChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.old, &self.new) {
+            (Some(old), Some(new)) if old.oid == new.oid => write!(f, "LFS object unchanged"),
+            (Some(old), Some(new)) => write!(
+                f,
+                "LFS object replaced (size {}\u{2192}{})",
+                format_size(old.size),
+                format_size(new.size)
+            ),
+            (None, Some(new)) => write!(f, "LFS object added (size {})", format_size(new.size)),
+            (Some(old), None) => write!(f, "LFS object removed (size {})", format_size(old.size)),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// Find the files in a diff whose old or new version is an LFS pointer
+///
+/// A pointer is a few short lines, so its patch always holds all of it.
+pub fn detect_lfs_changes(diff: &str) -> Vec<LfsChange> {
+    let mut changes = Vec::new();
+    let mut current: Option<(String, String, String)> = None;
+    let mut finish = |file: Option<(String, String, String)>| {
+        if let Some((path, old, new)) = file {
+            let (old, new) = (parse_pointer(&old), parse_pointer(&new));
+            if old.is_some() || new.is_some() {
+                changes.push(LfsChange { path, old, new });
+            }
+        }
+    };
+
+    let mut in_hunk = false;
+    for line in diff.lines() {
+        if let Some(paths) = line.strip_prefix("diff --git ") {
+            finish(current.take());
+            let path = paths.rsplit_once(" b/").map_or(paths, |(_, path)| path);
+            current = Some((path.to_string(), String::new(), String::new()));
+            in_hunk = false;
+            continue;
+        }
+        let Some((_, old, new)) = current.as_mut() else {
+            continue;
+        };
+        if line.starts_with("@@") {
+            in_hunk = true;
+            continue;
+        }
+        if !in_hunk {
+            continue;
+        }
+        if let Some(content) = line.strip_prefix('-') {
+            push_line(old, content);
+        } else if let Some(content) = line.strip_prefix('+') {
+            push_line(new, content);
+        } else if let Some(content) = line.strip_prefix(' ') {
+            push_line(old, content);
+            push_line(new, content);
+        }
+    }
+    finish(current.take());
+    changes
+}
+
+fn push_line(text: &mut String, line: &str) {
+    text.push_str(line);
+    text.push('\n');
+}
+
+/// Get the note that replaces each LFS pointer's patch, keyed by path
+///
+/// For [`crate::diff::replace_file_patches`].
+pub fn lfs_patch_replacements(changes: &[LfsChange]) -> Vec<(String, String)> {
+    changes
+        .iter()
+        .map(|change| (change.path.clone(), format!("({change})\n")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD_OID: &str = "sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393";
+    const NEW_OID: &str = "sha256:9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+    fn pointer(oid: &str, size: usize) -> String {
+        format!("{POINTER_VERSION}\noid {oid}\nsize {size}\n")
+    }
+
+    #[test]
+    fn test_parse_pointer() {
+        assert_eq!(
+            parse_pointer(&pointer(OLD_OID, 12345)),
+            Some(LfsPointer {
+                oid: OLD_OID.to_string(),
+                size: 12345
+            })
+        );
+        assert_eq!(parse_pointer("version 1\noid x\nsize 1\n"), None);
+        assert_eq!(parse_pointer(&format!("{POINTER_VERSION}\nsize 1\n")), None);
+        assert_eq!(parse_pointer("fn main() {}\n"), None);
+    }
+
+    #[test]
+    fn test_detect_lfs_changes() {
+        let diff = format!(
+            "diff --git a/models/weights.bin b/models/weights.bin\n\
+             index 1111111..2222222 100644\n\
+             --- a/models/weights.bin\n\
+             +++ b/models/weights.bin\n\
+             @@ -1,3 +1,3 @@\n \
+             {POINTER_VERSION}\n\
+             -oid {OLD_OID}\n\
+             -size 1048576\n\
+             +oid {NEW_OID}\n\
+             +size 3145728\n\
+             diff --git a/assets/intro.mp4 b/assets/intro.mp4\n\
+             new file mode 100644\n\
+             --- /dev/null\n\
+             +++ b/assets/intro.mp4\n\
+             @@ -0,0 +1,3 @@\n\
+             +{POINTER_VERSION}\n\
+             +oid {NEW_OID}\n\
+             +size 2048\n\
+             diff --git a/src/main.rs b/src/main.rs\n\
+             --- a/src/main.rs\n\
+             +++ b/src/main.rs\n\
+             @@ -1 +1 @@\n\
+             -fn main() {{}}\n\
+             +fn main() {{ run() }}\n"
+        );
+
+        let changes = detect_lfs_changes(&diff);
+        let notes: Vec<(&str, String)> = changes
+            .iter()
+            .map(|change| (change.path.as_str(), change.to_string()))
+            .collect();
+        assert_eq!(
+            notes,
+            vec![
+                (
+                    "models/weights.bin",
+                    "LFS object replaced (size 1.0 MiB\u{2192}3.0 MiB)".to_string()
+                ),
+                (
+                    "assets/intro.mp4",
+                    "LFS object added (size 2.0 KiB)".to_string()
+                ),
+            ]
+        );
+
+        let replaced = crate::diff::replace_file_patches(&diff, &lfs_patch_replacements(&changes));
+        assert!(!replaced.contains(OLD_OID) && !replaced.contains(NEW_OID));
+        assert!(replaced.contains("+fn main() { run() }"));
+    }
+
+    #[test]
+    fn test_detect_lfs_changes_in_staged_diff() -> anyhow::Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let repo = git2::Repository::init(temp_dir.path())?;
+        let signature = git2::Signature::now("Test User", "test@example.com")?;
+        std::fs::write(temp_dir.path().join("video.mp4"), pointer(OLD_OID, 5000))?;
+        let mut index = repo.index()?;
+        index.add_path(std::path::Path::new("video.mp4"))?;
+        index.write()?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+        repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])?;
+
+        std::fs::write(temp_dir.path().join("video.mp4"), pointer(NEW_OID, 9000))?;
+        index.add_path(std::path::Path::new("video.mp4"))?;
+        index.write()?;
+
+        let diff = crate::diff::get_staged_diff_from_repo(&repo)?;
+        let changes = detect_lfs_changes(&diff);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(
+            changes[0].to_string(),
+            "LFS object replaced (size 4.9 KiB\u{2192}8.8 KiB)"
+        );
+        Ok(())
+    }
+}
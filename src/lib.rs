@@ -2,26 +2,235 @@
 //!
 //! This library provides the core functionality for analyzing git diffs and generating
 //! conventional commit messages using AI models.
+//!
+//! Everything but [`validate`] needs the default `cli` feature. With
+//! `default-features = false` the crate is only that module, built `no_std` with no
+//! dependencies, for tools that just parse and check commit messages.
+
+#![cfg_attr(not(any(feature = "cli", test)), no_std)]
+
+extern crate alloc;
 
+#[cfg(feature = "cli")]
+pub mod airgap;
+#[cfg(feature = "cli")]
+pub mod anonymize;
+#[cfg(feature = "cli")]
+pub mod assets;
+#[cfg(feature = "cli")]
+pub mod audit;
+#[cfg(feature = "cli")]
+pub mod backup;
+#[cfg(feature = "cli")]
+pub mod badge;
+#[cfg(feature = "cli")]
+pub mod base;
+#[cfg(feature = "cli")]
+pub mod batch;
+#[cfg(feature = "cli")]
+pub mod body;
+#[cfg(feature = "cli")]
+pub mod cherry_pick;
+#[cfg(feature = "cli")]
+pub mod ci;
+#[cfg(feature = "cli")]
 pub mod commit;
+#[cfg(feature = "cli")]
+pub mod configuration;
+#[cfg(feature = "cli")]
+pub mod conflicts;
+#[cfg(feature = "cli")]
+pub mod credentials;
+#[cfg(feature = "cli")]
+pub mod dependencies;
+#[cfg(feature = "cli")]
 pub mod diff;
+#[cfg(feature = "cli")]
+pub mod embeddings;
+#[cfg(feature = "cli")]
+pub mod eval;
+#[cfg(feature = "cli")]
+pub mod exit;
+#[cfg(feature = "cli")]
+pub mod feedback;
+#[cfg(feature = "cli")]
+pub mod filters;
+#[cfg(feature = "cli")]
+pub mod forge;
+#[cfg(feature = "cli")]
+pub mod gate;
+#[cfg(feature = "cli")]
+mod git;
+#[cfg(feature = "cli")]
+pub mod history;
+#[cfg(feature = "cli")]
+pub mod hooks;
+#[cfg(feature = "cli")]
+pub mod i18n;
+#[cfg(feature = "cli")]
+pub mod lfs;
+#[cfg(feature = "cli")]
+pub mod lock;
+#[cfg(feature = "cli")]
+pub mod migrations;
+#[cfg(feature = "cli")]
+pub mod notebooks;
+#[cfg(feature = "cli")]
+pub mod outcomes;
+#[cfg(feature = "cli")]
+pub mod output;
+#[cfg(feature = "cli")]
+pub mod paths;
+#[cfg(feature = "cli")]
+pub mod policy;
+#[cfg(feature = "cli")]
+pub mod postprocess;
+#[cfg(feature = "cli")]
+pub mod privacy;
+#[cfg(feature = "cli")]
+pub mod profile;
+#[cfg(feature = "cli")]
 pub mod prompt;
+#[cfg(feature = "cli")]
 pub mod providers;
+#[cfg(feature = "cli")]
+pub mod ranking;
+#[cfg(feature = "cli")]
+pub mod report;
+#[cfg(feature = "cli")]
+pub mod review;
+#[cfg(feature = "cli")]
+pub mod rewrite;
+#[cfg(feature = "cli")]
+pub mod rules;
+#[cfg(feature = "cli")]
+pub mod schema;
+#[cfg(feature = "cli")]
+pub mod secrets;
+#[cfg(feature = "cli")]
+pub mod session;
+#[cfg(feature = "cli")]
+pub mod settings;
+#[cfg(feature = "cli")]
+pub mod shell;
+#[cfg(feature = "cli")]
+pub mod split;
+#[cfg(feature = "cli")]
+pub mod state;
+#[cfg(feature = "cli")]
+pub mod symbols;
+#[cfg(feature = "cli")]
+pub mod tag;
+#[cfg(feature = "cli")]
+pub mod template;
+#[cfg(feature = "cli")]
+pub mod test_gap;
+#[cfg(feature = "cli")]
+pub mod textconv;
+#[cfg(feature = "cli")]
+pub mod theme;
+#[cfg(feature = "cli")]
+pub mod timing;
+#[cfg(feature = "cli")]
+pub mod trailers;
+#[cfg(feature = "cli")]
 pub mod types;
+#[cfg(feature = "cli")]
+pub mod update;
+pub mod validate;
+#[cfg(feature = "cli")]
+pub mod vendored;
+#[cfg(feature = "cli")]
+pub mod version;
+#[cfg(feature = "cli")]
+pub mod vocabulary;
 
+#[cfg(feature = "cli")]
 use anyhow::Result;
-use providers::{create_provider, AIProvider, ProviderConfig};
+#[cfg(feature = "cli")]
+use prompt::{PromptBudget, PromptStyle};
+#[cfg(feature = "cli")]
+use providers::{
+    create_provider_with_temperature, is_context_too_large, AIProvider, ProviderConfig,
+};
+#[cfg(feature = "cli")]
+use settings::Settings;
+#[cfg(feature = "cli")]
+use std::borrow::Cow;
+#[cfg(feature = "cli")]
 use std::env;
+#[cfg(feature = "cli")]
+use std::future::Future;
+#[cfg(feature = "cli")]
+use std::sync::{Arc, OnceLock};
+#[cfg(feature = "cli")]
 use std::time::Duration;
+#[cfg(feature = "cli")]
+use tracing::{info_span, warn, Instrument};
+
+/// Generated messages ready so far, with the requests still running for the rest
+#[cfg(feature = "cli")]
+#[derive(Debug)]
+pub struct PartialMessages {
+    /// Messages ready to show, best first
+    pub messages: Vec<String>,
+    /// How many messages were asked for
+    pub requested: usize,
+    pending: Option<PendingMessages>,
+}
+
+#[cfg(feature = "cli")]
+impl PartialMessages {
+    /// Check whether generation has finished, so no more messages will arrive
+    pub fn is_complete(&self) -> bool {
+        self.pending.is_none()
+    }
+}
+
+#[cfg(feature = "cli")]
+impl From<Vec<String>> for PartialMessages {
+    fn from(messages: Vec<String>) -> Self {
+        Self {
+            requested: messages.len(),
+            messages,
+            pending: None,
+        }
+    }
+}
+
+/// Requests still running after a latency budget ran out
+#[cfg(feature = "cli")]
+#[derive(Debug)]
+struct PendingMessages {
+    receiver: tokio::sync::mpsc::UnboundedReceiver<Result<String>>,
+    filter: privacy::PrivacyFilter,
+    /// The full diff, used only to rank the messages
+    diff: String,
+    /// Messages received so far, before restoring and formatting
+    raw: Vec<String>,
+}
 
 /// Main configuration for the committor
+#[cfg(feature = "cli")]
 pub struct Config {
     pub provider_config: ProviderConfig,
     pub count: u8,
     pub auto_commit: bool,
     pub show_diff: bool,
+    pub settings: Settings,
+    /// Output template applied to generated messages, e.g. `{type}({scope}): {subject}`
+    pub format: Option<String>,
+    /// How much of a change may be sent to the provider
+    pub privacy: privacy::PrivacyLevel,
+    /// Ask for a body after the subject line
+    pub with_body: bool,
+    /// Temperature the provider samples with; the model's default when unset
+    pub temperature: Option<f64>,
+    /// Rules added to the built-in ones; generated messages breaking them are discarded
+    pub custom_rules: rules::RuleSet,
 }
 
+#[cfg(feature = "cli")]
 impl Config {
     /// Create a new configuration with default OpenAI values
     pub fn new() -> Result<Self> {
@@ -33,6 +242,12 @@ impl Config {
             count: 3,
             auto_commit: false,
             show_diff: false,
+            settings: Settings::default(),
+            format: None,
+            privacy: privacy::PrivacyLevel::default(),
+            with_body: false,
+            temperature: None,
+            custom_rules: rules::RuleSet::new(),
         })
     }
 
@@ -49,6 +264,12 @@ impl Config {
             count,
             auto_commit,
             show_diff,
+            settings: Settings::default(),
+            format: None,
+            privacy: privacy::PrivacyLevel::default(),
+            with_body: false,
+            temperature: None,
+            custom_rules: rules::RuleSet::new(),
         }
     }
 
@@ -65,6 +286,12 @@ impl Config {
             count,
             auto_commit,
             show_diff,
+            settings: Settings::default(),
+            format: None,
+            privacy: privacy::PrivacyLevel::default(),
+            with_body: false,
+            temperature: None,
+            custom_rules: rules::RuleSet::new(),
         }
     }
 
@@ -82,46 +309,1593 @@ impl Config {
             count,
             auto_commit,
             show_diff,
+            settings: Settings::default(),
+            format: None,
+            privacy: privacy::PrivacyLevel::default(),
+            with_body: false,
+            temperature: None,
+            custom_rules: rules::RuleSet::new(),
         }
     }
+
+    /// Attach file-based settings to the configuration
+    pub fn with_settings(mut self, settings: Settings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Set the output template applied to generated messages
+    pub fn with_format(mut self, format: Option<String>) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set how much of a change may be sent to the provider
+    pub fn with_privacy(mut self, privacy: privacy::PrivacyLevel) -> Self {
+        self.privacy = privacy;
+        self
+    }
+
+    /// Ask for a body explaining the change after each subject line
+    pub fn with_body(mut self, with_body: bool) -> Self {
+        self.with_body = with_body;
+        self
+    }
+
+    /// Set the temperature the provider samples with
+    pub fn with_temperature(mut self, temperature: Option<f64>) -> Self {
+        self.temperature = temperature;
+        self
+    }
+
+    /// Register a validation rule of your own, checked after the built-in ones
+    pub fn with_rule(mut self, rule: impl rules::Rule + 'static) -> Self {
+        self.custom_rules = self.custom_rules.with(rule);
+        self
+    }
 }
 
 /// Main committor service
+///
+/// Cloning is cheap and clones share the provider, so one instance can serve many tasks.
+#[cfg(feature = "cli")]
+#[derive(Clone)]
 pub struct Committor {
-    config: Config,
-    provider: Box<dyn AIProvider>,
+    config: Arc<Config>,
+    provider: Arc<dyn AIProvider>,
+    /// Pooled HTTP client shared by the provider and health checks
+    http: reqwest::Client,
+    /// Model under `[overflow]` that prompts too long for the provider's go to instead
+    long_context: Option<Arc<dyn AIProvider>>,
+    /// Staged files left out of the context sent to the provider
+    excluded_paths: Vec<String>,
 }
 
+#[cfg(feature = "cli")]
 impl Committor {
     /// Create a new committor instance
     pub fn new(config: Config) -> Result<Self> {
-        let provider = create_provider(config.provider_config.clone())?;
-        Ok(Self { config, provider })
+        let http = config.provider_config.http_client()?;
+        Self::with_client(config, http)
+    }
+
+    /// Create a committor whose provider sends its requests through an existing HTTP client
+    ///
+    /// Fails when the provider configuration is incomplete, such as an empty API key.
+    pub fn with_client(config: Config, http: reqwest::Client) -> Result<Self> {
+        config.provider_config.validate()?;
+        config.privacy.check_provider(&config.provider_config)?;
+        if let Some(policy) = policy::current() {
+            policy.check(&config.provider_config, config.privacy)?;
+        }
+        let provider = create_checked_provider(&config, config.provider_config.clone(), &http)?;
+        let long_context = match &config.settings.overflow.model {
+            Some(model) => {
+                let provider_config = config.provider_config.with_model(model);
+                Some(Arc::from(create_checked_provider(
+                    &config,
+                    provider_config,
+                    &http,
+                )?))
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            config: Arc::new(config),
+            provider: Arc::from(provider),
+            http,
+            long_context,
+            excluded_paths: Vec::new(),
+        })
+    }
+
+    /// Create a committor that generates with a provider of your own, such as a wrapper or a test double
+    ///
+    /// The configured provider still stands for it in the privacy and policy checks.
+    pub fn with_provider(config: Config, provider: Arc<dyn AIProvider>) -> Result<Self> {
+        config.privacy.check_provider(&config.provider_config)?;
+        if let Some(policy) = policy::current() {
+            policy.check(&config.provider_config, config.privacy)?;
+        }
+        Ok(Self {
+            config: Arc::new(config),
+            provider,
+            http: reqwest::Client::new(),
+            long_context: None,
+            excluded_paths: Vec::new(),
+        })
+    }
+
+    /// Get a committor that leaves some staged files out of the context sent to the provider
+    ///
+    /// The files stay staged and are committed as usual; only their patches and their
+    /// entries in the file list are kept from the model, e.g. when one noisy file
+    /// drowns out the rest of the change.
+    pub fn excluding(&self, paths: Vec<String>) -> Self {
+        Self {
+            excluded_paths: paths,
+            ..self.clone()
+        }
+    }
+
+    /// Check that the provider can be reached; only Ollama has a health check
+    pub async fn check_provider_availability(&self) -> Result<bool> {
+        self.config
+            .provider_config
+            .check_availability(&self.http)
+            .await
     }
 
     /// Generate commit messages for the given diff
     pub async fn generate_commit_messages(&self, diff: &str) -> Result<Vec<String>> {
-        commit::generate_commit_messages(diff, &*self.provider, self.config.count).await
+        let changes = self.changes_for_prompt()?;
+        let messages = self
+            .generate_for_diff(diff, &changes, self.config.count)
+            .await?;
+        let messages = self.correct_spelling(messages);
+
+        messages
+            .iter()
+            .map(|message| self.format_message(message))
+            .collect()
+    }
+
+    /// Generate commit messages for the given diff without an async runtime of your own
+    ///
+    /// For build scripts and other synchronous code. Fails if called from inside a runtime.
+    ///
+    /// ```no_run
+    /// use committor::{Committor, Config};
+    ///
+    /// let committor = Committor::new(Config::new()?)?;
+    /// let messages = committor.generate_commit_messages_blocking("+fn login() {}")?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn generate_commit_messages_blocking(&self, diff: &str) -> Result<Vec<String>> {
+        block_on(self.generate_commit_messages(diff))
+    }
+
+    /// Remember the messages generated for a diff so a later run can commit from them
+    pub fn save_last_messages(&self, diff: &str, messages: &[String]) -> Result<()> {
+        let repo = git2::Repository::discover(".")?;
+        batch::save_last_batch_from_repo(&repo, diff, messages)
+    }
+
+    /// Get the messages last generated for exactly this diff, if any
+    pub fn last_messages(&self, diff: &str) -> Result<Option<Vec<String>>> {
+        let repo = git2::Repository::discover(".")?;
+        batch::load_last_batch_from_repo(&repo, diff)
+    }
+
+    /// Build the commit prompt for a diff to run on another machine, for `export-prompt`
+    ///
+    /// What reading the replies back needs is saved in the git directory, including
+    /// the values the privacy filter replaced, so they never leave this machine.
+    pub async fn export_prompt(&self, diff: &str) -> Result<String> {
+        let changes = self.changes_for_prompt()?;
+        let (prompt, filter) = self.prepare_prompt(diff, &changes).await;
+        let repo = git2::Repository::discover(".")?;
+        airgap::save_export_from_repo(&repo, diff, &filter)?;
+        Ok(prompt)
+    }
+
+    /// Turn replies to the prompt exported for this diff into commit messages
+    ///
+    /// They are checked, ranked and formatted as if the provider had sent them.
+    pub fn import_responses(&self, diff: &str, responses: &[String]) -> Result<Vec<String>> {
+        let repo = git2::Repository::discover(".")?;
+        let filter = airgap::load_export_from_repo(&repo, diff)?;
+        let mut messages = commit::accept_responses(responses, &self.message_filters())?;
+        info_span!(timing::RANKING).in_scope(|| ranking::rank_messages(&mut messages, diff));
+        self.finish_messages(&filter, &messages)
+    }
+
+    /// Generate commit messages, returning once all are ready or the budget runs out
+    ///
+    /// At least one message is always waited for. Requests still running when the
+    /// budget runs out carry on in the background; `wait_for_more` collects them.
+    pub async fn generate_commit_messages_within(
+        &self,
+        diff: &str,
+        budget: Duration,
+    ) -> Result<PartialMessages> {
+        let changes = self.changes_for_prompt()?;
+        let (prompt, filter) = self.prepare_prompt(diff, &changes).await;
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        let committor = self.clone();
+        let (task_diff, task_filter) = (diff.to_string(), filter.clone());
+        let count = self.config.count;
+        tokio::spawn(async move {
+            let result = committor
+                .generate_with_fallbacks(
+                    &prompt,
+                    &task_diff,
+                    &changes,
+                    &task_filter,
+                    count,
+                    |message| {
+                        let _ = sender.send(Ok(message));
+                    },
+                )
+                .await;
+            if let Err(e) = result {
+                let _ = sender.send(Err(e));
+            }
+        });
+
+        let mut partial = PartialMessages {
+            messages: Vec::new(),
+            requested: count as usize,
+            pending: Some(PendingMessages {
+                receiver,
+                filter,
+                diff: self.without_excluded(diff).into_owned(),
+                raw: Vec::new(),
+            }),
+        };
+        let deadline = tokio::time::Instant::now() + budget;
+        self.receive_messages(&mut partial, Some(deadline)).await?;
+        Ok(partial)
     }
 
-    /// Get the staged diff from the repository
+    /// Wait for the messages still being generated after `generate_commit_messages_within`
+    pub async fn wait_for_more(&self, partial: &mut PartialMessages) -> Result<()> {
+        self.receive_messages(partial, None).await
+    }
+
+    /// Generate one message for `commit --quick`, or write one from the changed paths
+    ///
+    /// The model gets the `[quick]` budget. When it fails, runs out of time or replies
+    /// with a message that breaks the rules, the message comes from
+    /// [`prompt::heuristic_message`] instead; only Ctrl-C ends the run. Returns the
+    /// message and whether the model wrote it.
+    pub async fn generate_quick_message(&self, diff: &str) -> Result<(String, bool)> {
+        let budget = Duration::from_secs(self.config.settings.quick.budget);
+        self.generate_quick_message_until(diff, tokio::time::Instant::now() + budget)
+            .await
+    }
+
+    /// Like [`Committor::generate_quick_message`], with a budget that started earlier,
+    /// e.g. before the provider was found
+    pub async fn generate_quick_message_until(
+        &self,
+        diff: &str,
+        deadline: tokio::time::Instant,
+    ) -> Result<(String, bool)> {
+        let rules = self.rule_set();
+        let committable = |message: &String| {
+            self.add_trailers(message)
+                .is_ok_and(|message| rules.is_valid(&message))
+        };
+        match tokio::time::timeout_at(deadline, self.generate_commit_messages(diff)).await {
+            Ok(Ok(messages)) => match messages.into_iter().find(committable) {
+                Some(message) => return Ok((message, true)),
+                None => warn!("The generated message breaks the commit message rules"),
+            },
+            Ok(Err(e)) if e.is::<exit::ExitStatus>() => return Err(e),
+            Ok(Err(e)) => warn!("Could not generate a message: {e:#}"),
+            Err(_) => warn!(
+                "No message was generated within {}s",
+                self.config.settings.quick.budget
+            ),
+        }
+        Ok((self.paths_message()?, false))
+    }
+
+    /// Write the message for the staged changes from their paths, without the model
+    ///
+    /// `commit --quick` falls back to it when no message can be generated in time.
+    pub fn paths_message(&self) -> Result<String> {
+        let mut changes = diff::get_staged_snapshot()?.changes;
+        changes.retain(|change| !self.excluded_paths.contains(&change.file_path));
+        let message =
+            prompt::heuristic_message(&changes, &self.config.settings.rules.allowed_scopes);
+        self.format_message(&message)
+    }
+
+    /// Receive generated messages until all arrived or, once there is one, the deadline passes
+    async fn receive_messages(
+        &self,
+        partial: &mut PartialMessages,
+        deadline: Option<tokio::time::Instant>,
+    ) -> Result<()> {
+        let Some(pending) = &mut partial.pending else {
+            return Ok(());
+        };
+
+        let mut finished = false;
+        while pending.raw.len() < partial.requested {
+            let received = match deadline {
+                Some(deadline) if !pending.raw.is_empty() => {
+                    match tokio::time::timeout_at(deadline, pending.receiver.recv()).await {
+                        Ok(received) => received,
+                        Err(_) => break,
+                    }
+                }
+                _ => pending.receiver.recv().await,
+            };
+            match received {
+                Some(Ok(message)) => pending.raw.push(message),
+                Some(Err(e)) if pending.raw.is_empty() => return Err(e),
+                Some(Err(_)) | None => {
+                    finished = true;
+                    break;
+                }
+            }
+        }
+
+        let mut raw = pending.raw.clone();
+        info_span!(timing::RANKING).in_scope(|| ranking::rank_messages(&mut raw, &pending.diff));
+        partial.messages = self.finish_messages(&pending.filter, &raw)?;
+        if finished || pending.raw.len() >= partial.requested {
+            partial.pending = None;
+        }
+        Ok(())
+    }
+
+    /// Generate messages for a diff through the configured privacy level
+    ///
+    /// The full diff stays local and is only used to rank the results.
+    async fn generate_for_diff(
+        &self,
+        diff: &str,
+        changes: &[types::DiffChange],
+        count: u8,
+    ) -> Result<Vec<String>> {
+        let (prompt, filter) = self.prepare_prompt(diff, changes).await;
+        let mut messages = Vec::new();
+        self.generate_with_fallbacks(&prompt, diff, changes, &filter, count, |message| {
+            messages.push(message)
+        })
+        .await?;
+
+        let included = self.without_excluded(diff);
+        info_span!(timing::RANKING).in_scope(|| ranking::rank_messages(&mut messages, &included));
+        Ok(self.restore_messages(&filter, &messages))
+    }
+
+    /// Generate messages from a commit prompt, trying again when it is too long for the model
+    ///
+    /// The long-context model under `[overflow]`, if one is set, gets the same prompt
+    /// first; then, unless turned off, the prompt is rebuilt in its compact form. Both
+    /// are built from the same diff, so `filter` restores the replies to either.
+    async fn generate_with_fallbacks(
+        &self,
+        prompt: &str,
+        diff: &str,
+        changes: &[types::DiffChange],
+        filter: &privacy::PrivacyFilter,
+        count: u8,
+        mut accept: impl FnMut(String),
+    ) -> Result<()> {
+        let pipeline = self.message_filters();
+        let overflow = &self.config.settings.overflow;
+        let error = match commit::generate_messages_concurrently(
+            prompt,
+            &*self.provider,
+            count,
+            &pipeline,
+            &mut accept,
+        )
+        .await
+        {
+            Err(e) if is_context_too_large(&e) => e,
+            result => return result,
+        };
+
+        let mut provider = &self.provider;
+        let error = match &self.long_context {
+            Some(long_context) => {
+                warn!(
+                    "The prompt is too long for {}, retrying with {}",
+                    self.config.provider_config.model(),
+                    overflow.model.as_deref().unwrap_or_default()
+                );
+                provider = long_context;
+                match commit::generate_messages_concurrently(
+                    prompt,
+                    &**long_context,
+                    count,
+                    &pipeline,
+                    &mut accept,
+                )
+                .await
+                {
+                    Err(e) if is_context_too_large(&e) => e,
+                    result => return result,
+                }
+            }
+            None => error,
+        };
+        if !overflow.compact {
+            return Err(error);
+        }
+
+        warn!("The prompt is too long for the model, retrying with a compact prompt");
+        let prompt = self.compact_prompt(diff, changes, &mut filter.clone());
+        commit::generate_messages_concurrently(&prompt, &**provider, count, &pipeline, accept).await
+    }
+
+    /// Build the commit prompt for a diff, with the privacy filter that undoes it on the replies
+    async fn prepare_prompt(
+        &self,
+        diff: &str,
+        changes: &[types::DiffChange],
+    ) -> (String, privacy::PrivacyFilter) {
+        let span = info_span!(timing::PROMPT_BUILD);
+        let included = self.without_excluded(diff);
+        let mut filter = privacy::PrivacyFilter::new(self.config.privacy);
+        let outgoing = filter.diff(&included);
+        let mut context = self
+            .commit_context(outgoing.as_deref())
+            .instrument(span.clone())
+            .await;
+        let mut replacements = bulk_patch_replacements(diff);
+        if outgoing.is_some() {
+            replacements.extend(span.in_scope(|| self.add_staged_context(diff, &mut context)));
+            // Step labels can be a line of a `run:` script, so they're filtered like the diff
+            context.ci_changes = context
+                .ci_changes
+                .iter()
+                .filter_map(|change| filter.context_line(change))
+                .collect();
+        }
+        if self.config.settings.migrations.enabled {
+            context.migration_files = migrations::migration_files(&diff::diff_paths(&included));
+        }
+        let prompt_diff = if replacements.is_empty() {
+            included
+        } else {
+            Cow::Owned(diff::replace_file_patches(&included, &replacements))
+        };
+        let builder = prompt::PromptBuilder::new()
+            .context(context)
+            .languages(&self.config.settings.language)
+            .casing(&self.config.settings.rules)
+            .with_body(self.config.with_body)
+            .symbols_in_body(self.config.settings.symbols.in_body)
+            .migrations(&self.config.settings.migrations);
+        let prompt = span.in_scope(|| filter.commit_prompt(&prompt_diff, changes, &builder));
+        (prompt, filter)
+    }
+
+    /// Build the compact form of the commit prompt, for when the full one overflowed the model's context window
+    ///
+    /// It has the concise style, a quarter of the diff lines and none of the extra context.
+    fn compact_prompt(
+        &self,
+        diff: &str,
+        changes: &[types::DiffChange],
+        filter: &mut privacy::PrivacyFilter,
+    ) -> String {
+        let _span = info_span!(timing::PROMPT_BUILD).entered();
+        let included = self.without_excluded(diff);
+        let replacements = bulk_patch_replacements(diff);
+        let prompt_diff = diff::replace_file_patches(&included, &replacements);
+        let builder = prompt::PromptBuilder::new()
+            .style(PromptStyle::Concise)
+            .budget(COMPACT_BUDGET)
+            .languages(&self.config.settings.language)
+            .casing(&self.config.settings.rules)
+            .with_body(self.config.with_body);
+        filter.commit_prompt(&prompt_diff, changes, &builder)
+    }
+
+    /// Cut unrequested bodies from generated messages and restore anything the privacy filter replaced
+    ///
+    /// The trailers filter runs before the body is cut, so its trailers are put back after.
+    fn restore_messages(
+        &self,
+        filter: &privacy::PrivacyFilter,
+        messages: &[String],
+    ) -> Vec<String> {
+        let settings = &self.config.settings;
+        let trailers = if settings
+            .filters
+            .pipeline
+            .contains(&filters::FilterKind::Trailers)
+        {
+            settings.trailers.add.as_slice()
+        } else {
+            &[]
+        };
+        messages
+            .iter()
+            .map(|message| {
+                // Without a body asked for, anything after the subject is the model explaining itself
+                let message = if self.config.with_body {
+                    message.as_str()
+                } else {
+                    message.lines().next().unwrap_or_default()
+                };
+                trailers::append_trailers(&filter.restore(message), trailers)
+            })
+            .collect()
+    }
+
+    /// Restore, spell-correct and format generated messages, as `generate_commit_messages` does
+    ///
+    /// Messages repeating a recent commit subject are moved after the others.
+    fn finish_messages(
+        &self,
+        filter: &privacy::PrivacyFilter,
+        messages: &[String],
+    ) -> Result<Vec<String>> {
+        let messages = self.correct_spelling(self.restore_messages(filter, messages));
+        let messages = messages
+            .iter()
+            .map(|message| self.format_message(message))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(self.recent_history().demote_repeats(messages))
+    }
+
+    /// Add the changed public symbols, dependencies and binary files, when the diff is the whole staged change
+    ///
+    /// These are read from the index, so a diff of a range or of one file gets none.
+    /// Returns replacements for the patches of CI configuration, notebooks and files
+    /// with a textconv diff driver, keyed by path.
+    fn add_staged_context(
+        &self,
+        diff: &str,
+        context: &mut prompt::CommitContext,
+    ) -> Vec<(String, String)> {
+        if !diff::get_staged_snapshot().is_ok_and(|snapshot| snapshot.diff == diff) {
+            return Vec::new();
+        }
+        match git2::Repository::open(".") {
+            Ok(repo) => self.add_repo_context(&repo, context),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Add the staged context of a repository, leaving out the excluded files
+    fn add_repo_context(
+        &self,
+        repo: &git2::Repository,
+        context: &mut prompt::CommitContext,
+    ) -> Vec<(String, String)> {
+        let included = |path: &String| !self.excluded_paths.contains(path);
+
+        if self.config.settings.symbols.enabled {
+            context.changed_symbols = symbols::changed_symbols_from_repo(repo)
+                .unwrap_or_default()
+                .iter()
+                .filter(|symbol| included(&symbol.path))
+                .map(ToString::to_string)
+                .collect();
+        }
+        context.dependency_changes = dependencies::dependency_changes_from_repo(repo)
+            .unwrap_or_default()
+            .iter()
+            .filter(|change| included(&change.path))
+            .map(ToString::to_string)
+            .collect();
+        let mut assets = assets::binary_assets_from_repo(repo).unwrap_or_default();
+        assets.retain(|asset| included(&asset.path));
+        context.binary_assets = assets::summarize_assets(&assets);
+
+        let mut ci_changes = ci::ci_changes_from_repo(repo).unwrap_or_default();
+        ci_changes.retain(|change| included(&change.path));
+        context.ci_changes = ci_changes.iter().map(ToString::to_string).collect();
+        // A textconv command the user configured wins over committor's own notebook cleanup
+        let mut replacements = textconv::textconv_patches_from_repo(repo).unwrap_or_default();
+        replacements.extend(notebooks::notebook_patches_from_repo(repo).unwrap_or_default());
+        replacements.extend(ci::ci_patch_replacements(&ci_changes));
+        replacements.retain(|(path, _)| included(path));
+        replacements
+    }
+
+    /// Get the staged change list when the privacy level sends it in place of the diff
+    fn changes_for_prompt(&self) -> Result<Vec<types::DiffChange>> {
+        if self.config.privacy.sends_content() {
+            return Ok(Vec::new());
+        }
+        let mut changes = diff::get_staged_snapshot()?.changes;
+        changes.retain(|change| !self.excluded_paths.contains(&change.file_path));
+        Ok(changes)
+    }
+
+    /// Drop the patches of the files left out of the context
+    fn without_excluded<'a>(&self, diff: &'a str) -> Cow<'a, str> {
+        if self.excluded_paths.is_empty() {
+            Cow::Borrowed(diff)
+        } else {
+            Cow::Owned(diff::remove_file_patches(diff, &self.excluded_paths))
+        }
+    }
+
+    /// Replace misspelled vocabulary terms when the spellcheck mode is `correct`
+    fn correct_spelling(&self, messages: Vec<String>) -> Vec<String> {
+        if self.config.settings.rules.spellcheck != vocabulary::SpellcheckMode::Correct {
+            return messages;
+        }
+
+        match vocabulary::build_vocabulary() {
+            Ok(vocabulary) => messages.iter().map(|m| vocabulary.correct(m)).collect(),
+            Err(_) => messages,
+        }
+    }
+
+    /// Find likely misspellings of repository terms in each message when the spellcheck mode is `warn`
+    pub fn spelling_warnings(&self, messages: &[String]) -> Vec<Vec<vocabulary::Misspelling>> {
+        if self.config.settings.rules.spellcheck != vocabulary::SpellcheckMode::Warn {
+            return vec![Vec::new(); messages.len()];
+        }
+
+        let vocabulary = vocabulary::build_vocabulary().unwrap_or_default();
+        messages
+            .iter()
+            .map(|message| vocabulary.misspellings(message))
+            .collect()
+    }
+
+    /// Gather the branch history, previous subject and similar past commits
+    ///
+    /// Similar commits are looked up by embedding the outgoing diff, so there are
+    /// none when the privacy level sends no contents.
+    async fn commit_context(&self, outgoing_diff: Option<&str>) -> prompt::CommitContext {
+        let embedding_settings = &self.config.settings.embeddings;
+        let similar_commits = match outgoing_diff {
+            Some(diff) if embedding_settings.enabled => {
+                embeddings::similar_commits(
+                    &*self.provider,
+                    embedding_settings,
+                    &self.embedding_model(),
+                    diff,
+                )
+                .await
+            }
+            _ => Vec::new(),
+        };
+
+        prompt::CommitContext {
+            branch_commits: commit::get_branch_history().unwrap_or_default(),
+            previous_subject: self.previous_commit_subject(),
+            similar_commits,
+            changed_symbols: Vec::new(),
+            dependency_changes: Vec::new(),
+            migration_files: Vec::new(),
+            ci_changes: Vec::new(),
+            binary_assets: Vec::new(),
+            feedback_guidance: if self.config.settings.feedback.guidance {
+                feedback::guidance()
+            } else {
+                Vec::new()
+            },
+        }
+    }
+
+    /// Get the configured embedding model, or the provider's default
+    fn embedding_model(&self) -> String {
+        self.config
+            .settings
+            .embeddings
+            .model
+            .clone()
+            .unwrap_or_else(|| {
+                self.config
+                    .provider_config
+                    .default_embedding_model()
+                    .to_string()
+            })
+    }
+
+    /// Embed recent commits into the local similar-commit index, returning how many were added
+    pub async fn update_embedding_index(&self, last: usize, rebuild: bool) -> Result<usize> {
+        let repo = git2::Repository::discover(".")?;
+        embeddings::update_index(
+            &repo,
+            &*self.provider,
+            &self.embedding_model(),
+            self.config.privacy,
+            last,
+            rebuild,
+        )
+        .await
+    }
+
+    /// Generate one commit message for each staged file
+    ///
+    /// A file no message could be generated for is recorded in `outcomes`, which
+    /// decides whether the run goes on without it.
+    pub async fn generate_file_suggestions(
+        &self,
+        outcomes: &mut outcomes::Outcomes,
+    ) -> Result<Vec<split::FileSuggestion>> {
+        let changes = self.changes_for_prompt()?;
+
+        let mut suggestions = Vec::new();
+        for (path, diff) in diff::get_staged_file_diffs()? {
+            let file_changes: Vec<types::DiffChange> = changes
+                .iter()
+                .filter(|change| change.file_path == path)
+                .cloned()
+                .collect();
+            let message = match self.generate_for_diff(&diff, &file_changes, 1).await {
+                Ok(messages) => self.format_message(&messages[0]),
+                Err(e) => Err(e),
+            };
+            match message {
+                Ok(message) => suggestions.push(split::FileSuggestion { path, message }),
+                Err(e) => outcomes.failed(path, e)?,
+            }
+        }
+
+        Ok(suggestions)
+    }
+
+    /// Commit a single staged file with the given message, adding the configured trailers
+    pub fn commit_file_with_message(&self, path: &str, message: &str) -> Result<()> {
+        let message = self.prepare_commit_message(message)?;
+        self.wait_for_index_lock()?;
+        split::commit_paths(&[path.to_string()], &message)
+    }
+
+    /// Ask the provider to group the staged hunks into a sequence of commits
+    ///
+    /// Fails when the message filters reject one of the planned messages.
+    pub async fn plan_split(&self, hunks: &[types::DiffHunk]) -> Result<Vec<split::PlannedCommit>> {
+        let mut filter = privacy::PrivacyFilter::new(self.config.privacy);
+        let outgoing = filter.split_hunks(hunks);
+        let prompt = prompt::create_split_prompt(&outgoing);
+        let mut response = String::new();
+        for attempt in 1..=2 {
+            response = self
+                .provider
+                .generate_message(&prompt)
+                .await
+                .map_err(providers::provider_error)?;
+            if !response.trim().is_empty() {
+                break;
+            }
+            warn!("Empty split plan on attempt {}", attempt);
+        }
+        if response.trim().is_empty() {
+            return Err(types::CommittorError::AIProviderError(
+                "The model returned an empty split plan twice".to_string(),
+            )
+            .into());
+        }
+
+        let mut plan = split::parse_split_plan(&response, hunks.len())?;
+        let pipeline = self.message_filters();
+        for commit in &mut plan {
+            let restored = filter.restore(&commit.message);
+            let message = pipeline.run(&restored).map_err(|name| {
+                types::CommittorError::InvalidCommitFormat(format!(
+                    "the {name} filter rejected the planned message \"{restored}\""
+                ))
+            })?;
+            commit.message = self.format_message(&message)?;
+        }
+
+        Ok(plan)
+    }
+
+    /// Suggest test cases when a change touches source files but no tests, `None` otherwise
+    pub async fn suggest_tests(
+        &self,
+        diff: &str,
+        changes: &[types::DiffChange],
+    ) -> Result<Option<test_gap::TestGap>> {
+        let sources = test_gap::untested_sources(changes);
+        if sources.is_empty() {
+            return Ok(None);
+        }
+
+        let mut filter = privacy::PrivacyFilter::new(self.config.privacy);
+        let outgoing = filter.diff(&self.without_excluded(diff));
+        let prompt = prompt::create_test_suggestions_prompt(outgoing.as_deref(), &sources);
+        let response = self
+            .provider
+            .generate_message(&prompt)
+            .await
+            .map_err(providers::provider_error)?;
+        let suggestions = test_gap::parse_test_suggestions(&response)
+            .iter()
+            .map(|suggestion| filter.restore(suggestion))
+            .collect();
+
+        Ok(Some(test_gap::TestGap {
+            sources,
+            suggestions,
+        }))
+    }
+
+    /// Ask the provider for reviewer-style comments on the staged hunks
+    pub async fn review_hunks(
+        &self,
+        hunks: &[types::DiffHunk],
+    ) -> Result<Vec<review::ReviewComment>> {
+        self.config
+            .privacy
+            .require_content("Reviewing the staged changes")?;
+        let mut filter = privacy::PrivacyFilter::new(self.config.privacy);
+        let outgoing = filter.split_hunks(hunks);
+        let prompt = prompt::create_review_prompt(&outgoing);
+        let response = self
+            .provider
+            .generate_message(&prompt)
+            .await
+            .map_err(providers::provider_error)?;
+
+        let mut comments = review::parse_review(&response, hunks.len());
+        for comment in &mut comments {
+            comment.text = filter.restore(&comment.text);
+        }
+        Ok(comments)
+    }
+
+    /// Create the commits of a split plan, adding the configured trailers
+    ///
+    /// Any failure rolls HEAD and the index back; after a split the original HEAD is
+    /// kept as a backup for `committor restore`.
+    pub fn execute_split(
+        &self,
+        plan: &[split::PlannedCommit],
+        hunks: &[types::DiffHunk],
+    ) -> Result<()> {
+        let mut plan = plan.to_vec();
+        for commit in &mut plan {
+            commit.message = self.prepare_commit_message(&commit.message)?;
+        }
+        self.wait_for_index_lock()?;
+        split::execute_plan(&plan, hunks)
+    }
+
+    /// Regenerate the message of every commit in `<base>..HEAD`, oldest first
+    ///
+    /// One message is generated per commit; a commit no valid message could be
+    /// generated for keeps its own. Other failures are recorded in `outcomes`, which
+    /// decides whether the commit keeps its message or the run ends.
+    pub async fn plan_rewrite(
+        &self,
+        range: &str,
+        outcomes: &mut outcomes::Outcomes,
+    ) -> Result<rewrite::RewritePlan> {
+        let repo = git2::Repository::open(".")?;
+        let mut plan = rewrite::collect_range_from_repo(&repo, range)?;
+        for commit in &mut plan.commits {
+            let changes = if self.config.privacy.sends_content() {
+                Vec::new()
+            } else {
+                diff::get_range_changes_from_repo(&repo, &commit.commit_id)?
+            };
+            let messages = match self.generate_for_diff(&commit.diff, &changes, 1).await {
+                Ok(messages) => self.correct_spelling(messages),
+                Err(e)
+                    if matches!(
+                        e.downcast_ref::<types::CommittorError>(),
+                        Some(types::CommittorError::ValidationFailed(_))
+                    ) =>
+                {
+                    warn!("Keeping the message of {}: {}", commit.commit_id, e);
+                    outcomes.skipped(&commit.commit_id, format!("kept its message: {e}"));
+                    continue;
+                }
+                Err(e) => {
+                    outcomes.failed(&commit.commit_id, e)?;
+                    continue;
+                }
+            };
+            if let Some(message) = messages.first() {
+                commit.message = Some(self.format_message(message)?);
+                outcomes.succeeded(&commit.commit_id);
+            }
+        }
+
+        Ok(plan)
+    }
+
+    /// Give the commits of a rewrite plan their new messages, backing up the original HEAD
+    ///
+    /// The configured trailers aren't added: the commits keep the trailers they had.
+    pub fn apply_rewrite(&self, plan: &rewrite::RewritePlan) -> Result<()> {
+        for message in plan.commits.iter().filter_map(|c| c.message.as_deref()) {
+            secrets::ensure_no_secrets(message)?;
+        }
+        self.wait_for_index_lock()?;
+        rewrite::apply_rewrite(plan)
+    }
+
+    /// Generate messages for the commit a stopped rebase or cherry-pick makes from its resolved conflicts
+    pub async fn generate_conflict_messages(
+        &self,
+        resolution: &conflicts::Resolution,
+    ) -> Result<Vec<String>> {
+        self.config
+            .privacy
+            .require_content("Generating a message for a conflict resolution")?;
+        let mut filter = privacy::PrivacyFilter::new(self.config.privacy);
+        let outgoing = filter.diff(&resolution.diff).unwrap_or_default();
+        let context = self.commit_context(Some(&outgoing)).await;
+        let prompt = prompt::PromptBuilder::new()
+            .context(context)
+            .languages(&self.config.settings.language)
+            .casing(&self.config.settings.rules)
+            .with_body(self.config.with_body)
+            .conflict_prompt(&resolution.original, &resolution.conflicts, &outgoing);
+        let messages = commit::generate_commit_messages_from_prompt(
+            &prompt,
+            &resolution.diff,
+            &*self.provider,
+            self.config.count,
+            &self.message_filters(),
+        )
+        .await?;
+        self.finish_messages(&filter, &messages)
+    }
+
+    /// Continue a stopped rebase or cherry-pick, committing its resolution with the message
+    ///
+    /// Like `apply_rewrite`, the commit keeps its original trailers instead of getting the configured ones.
+    pub fn continue_with_message(
+        &self,
+        resolution: &conflicts::Resolution,
+        message: &str,
+    ) -> Result<()> {
+        secrets::ensure_no_secrets(message)?;
+        self.wait_for_index_lock()?;
+        conflicts::continue_with_message(resolution, &resolution.final_message(message))
+    }
+
+    /// Generate messages for a commit being cherry-picked, following the current branch's conventions
+    ///
+    /// The scopes offered are the allowed ones when configured, otherwise those of the branch's history.
+    pub async fn generate_cherry_pick_messages(
+        &self,
+        pick: &cherry_pick::CherryPick,
+    ) -> Result<Vec<String>> {
+        self.config
+            .privacy
+            .require_content("Generating a message for a cherry-pick")?;
+        let mut filter = privacy::PrivacyFilter::new(self.config.privacy);
+        let outgoing = filter.diff(&pick.diff).unwrap_or_default();
+        let context = self.commit_context(Some(&outgoing)).await;
+        let scopes = match &self.config.settings.rules.allowed_scopes {
+            allowed if !allowed.is_empty() => allowed.clone(),
+            _ => cherry_pick::branch_scopes().unwrap_or_default(),
+        };
+        let prompt = prompt::PromptBuilder::new()
+            .context(context)
+            .languages(&self.config.settings.language)
+            .casing(&self.config.settings.rules)
+            .with_body(self.config.with_body)
+            .cherry_pick_prompt(&pick.original, &scopes, &outgoing);
+        let messages = commit::generate_commit_messages_from_prompt(
+            &prompt,
+            &pick.diff,
+            &*self.provider,
+            self.config.count,
+            &self.message_filters(),
+        )
+        .await?;
+        self.finish_messages(&filter, &messages)
+    }
+
+    /// Cherry-pick a commit onto the current branch with the message
+    ///
+    /// The commit keeps the original's trailers and records where it came from.
+    pub fn cherry_pick_with_message(
+        &self,
+        pick: &cherry_pick::CherryPick,
+        message: &str,
+    ) -> Result<()> {
+        secrets::ensure_no_secrets(message)?;
+        self.wait_for_index_lock()?;
+        cherry_pick::apply_cherry_pick(pick, &pick.final_message(message))
+    }
+
+    /// Lay out a message with the configured output template, if any
+    pub fn format_message(&self, message: &str) -> Result<String> {
+        match &self.config.format {
+            Some(format) => {
+                let ticket = commit::get_current_branch()
+                    .ok()
+                    .and_then(|branch| template::extract_ticket(&branch));
+                template::apply_template(format, message, ticket.as_deref())
+            }
+            None => Ok(message.to_string()),
+        }
+    }
+
+    /// Get the provider and model the messages are generated with
+    pub fn provider_config(&self) -> &ProviderConfig {
+        &self.config.provider_config
+    }
+
+    /// Get the subject of the commit at HEAD, if there is one
+    pub fn previous_commit_subject(&self) -> Option<String> {
+        commit::get_last_commit_message()
+            .ok()
+            .filter(|subject| !subject.is_empty())
+    }
+
+    /// Get the subjects of the recent commits, empty outside a repository or before the first commit
+    pub fn recent_history(&self) -> history::RecentHistory {
+        history::recent_history().unwrap_or_default()
+    }
+
+    /// Suggest scopes for the staged changes, limited to the allowed scopes if configured
+    pub fn suggest_scopes(&self) -> Result<Vec<String>> {
+        let changes = diff::get_staged_snapshot()?.changes;
+        let allowed = &self.config.settings.rules.allowed_scopes;
+
+        let scopes = prompt::suggest_scopes(&changes, usize::MAX)
+            .into_iter()
+            .filter(|scope| allowed.is_empty() || allowed.contains(scope))
+            .take(commit::MAX_SCOPE_SUGGESTIONS)
+            .collect();
+        Ok(scopes)
+    }
+
+    /// Get the staged diff from the repository, reusing the cached one if nothing changed
     pub fn get_staged_diff(&self) -> Result<String> {
-        diff::get_staged_diff()
+        Ok(diff::get_staged_snapshot()?.diff)
     }
 
-    /// Commit with the given message
+    /// Get the staged diff if it no longer matches the one messages were generated for
+    pub fn staged_diff_if_changed(&self, generated_for: &str) -> Result<Option<String>> {
+        diff::staged_diff_if_changed(generated_for)
+    }
+
+    /// Get the staged changes split into hunks
+    pub fn get_staged_hunks(&self) -> Result<Vec<types::DiffHunk>> {
+        diff::get_staged_hunks()
+    }
+
+    /// Get all uncommitted changes to tracked files
+    pub fn get_working_tree_diff(&self) -> Result<String> {
+        diff::get_working_tree_diff()
+    }
+
+    /// Generate a descriptive stash message for the given diff
+    pub async fn generate_stash_message(&self, diff: &str) -> Result<String> {
+        self.config
+            .privacy
+            .require_content("Generating a stash message")?;
+        // A checked-out LFS file differs from its pointer by its whole contents
+        let lfs_changes = lfs::detect_lfs_changes(diff);
+        let diff = diff::replace_file_patches(diff, &lfs::lfs_patch_replacements(&lfs_changes));
+        let mut filter = privacy::PrivacyFilter::new(self.config.privacy);
+        let outgoing = filter.diff(&diff).unwrap_or_default();
+
+        let message = commit::generate_stash_message(&outgoing, &*self.provider).await?;
+        Ok(filter.restore(&message))
+    }
+
+    /// Generate commit messages from a description of the change instead of a diff
+    ///
+    /// The description is sent as written, whatever the privacy level, and also ranks the results.
+    pub async fn generate_from_description(&self, description: &str) -> Result<Vec<String>> {
+        let context = self.commit_context(None).await;
+        let prompt = prompt::PromptBuilder::new()
+            .context(context)
+            .languages(&self.config.settings.language)
+            .casing(&self.config.settings.rules)
+            .with_body(self.config.with_body)
+            .description_prompt(description);
+        let messages = commit::generate_commit_messages_from_prompt(
+            &prompt,
+            description,
+            &*self.provider,
+            self.config.count,
+            &self.message_filters(),
+        )
+        .await?;
+        // Nothing was redacted, so the filter only cuts unrequested bodies
+        let filter = privacy::PrivacyFilter::new(self.config.privacy);
+        self.finish_messages(&filter, &messages)
+    }
+
+    /// Generate an annotated tag message from the commits since the previous tag
+    pub async fn generate_tag_message(&self, tag_name: &str) -> Result<String> {
+        let repo = git2::Repository::open(".")?;
+        let previous_tag = tag::find_previous_tag_from_repo(&repo)?;
+        let subjects = tag::commit_subjects_since_from_repo(&repo, previous_tag.as_deref())?;
+
+        if subjects.is_empty() {
+            return Err(anyhow::anyhow!(
+                "No commits found since {}",
+                previous_tag.as_deref().unwrap_or("the start of history")
+            ));
+        }
+
+        tag::generate_tag_message(
+            tag_name,
+            previous_tag.as_deref(),
+            &subjects,
+            &*self.provider,
+        )
+        .await
+    }
+
+    /// Get the diff for a revision range, usable in bare repositories
+    pub fn get_range_diff(&self, range: &str) -> Result<String> {
+        diff::get_range_diff(range)
+    }
+
+    /// Commit with the given message, adding the configured trailers
     pub fn commit_with_message(&self, message: &str) -> Result<()> {
-        commit::commit_with_message(message)
+        let message = self.prepare_commit_message(message)?;
+        self.wait_for_index_lock()?;
+        commit::commit_with_message(&message)
     }
-}
 
-impl Default for Config {
-    fn default() -> Self {
-        Self::new().unwrap_or_else(|_| Config {
-            provider_config: ProviderConfig::openai(String::new(), "gpt-4".to_string()),
-            count: 3,
-            auto_commit: false,
-            show_diff: false,
+    /// Wait, up to the configured timeout, for another git process to release the index
+    pub fn wait_for_index_lock(&self) -> Result<()> {
+        let timeout = Duration::from_secs(self.config.settings.index_lock.timeout);
+        lock::wait_for_index_lock(timeout)
+    }
+
+    /// Refuse messages that contain a secret, then add the configured trailers
+    fn prepare_commit_message(&self, message: &str) -> Result<String> {
+        secrets::ensure_no_secrets(message)?;
+        let message = self.add_trailers(message)?;
+        trailers::ensure_required_trailers(&message, &self.config.settings.trailers.required)?;
+        Ok(message)
+    }
+
+    /// Get the filters generated messages pass through, as the settings configure them
+    ///
+    /// Messages that break a custom rule are discarded last.
+    pub fn message_filters(&self) -> filters::FilterPipeline {
+        let pipeline = filters::FilterPipeline::from_settings(&self.config.settings);
+        if self.config.custom_rules.is_empty() {
+            return pipeline;
+        }
+        pipeline.with(filters::Validate {
+            rules: self.config.custom_rules.clone(),
         })
     }
+
+    /// Get the rules committed messages are checked against: the configured built-in ones and any custom ones
+    pub fn rule_set(&self) -> rules::RuleSet {
+        let mut rules = rules::RuleSet::from_settings(&self.config.settings);
+        rules.extend(&self.config.custom_rules);
+        rules
+    }
+
+    /// Append the configured static trailers, a required sign-off and, if enabled, a Gerrit Change-Id
+    pub fn add_trailers(&self, message: &str) -> Result<String> {
+        trailers::apply_trailer_settings(message, &self.config.settings.trailers)
+    }
+
+    /// Prefix a commit type inferred from the current branch when the message has none
+    pub fn enhance_commit_message(&self, message: &str) -> Result<String> {
+        let branch = commit::get_current_branch()?;
+        let mappings = self.config.settings.branch_type_mappings()?;
+        Ok(commit::enhance_commit_message_with_mappings(
+            message, &branch, &mappings,
+        ))
+    }
+
+    /// Replay the last `last` commits through the provider and score the results
+    pub async fn evaluate_history(&self, last: usize) -> Result<eval::EvalReport> {
+        let repo = git2::Repository::open(".")?;
+        eval::run_eval(&repo, &*self.provider, self.config.privacy, last).await
+    }
+
+    /// Generate messages for a pull request's diff and check its title against them
+    pub async fn check_title(&self, title: &str, diff: &str) -> Result<gate::TitleCheck> {
+        let messages = self.generate_commit_messages(diff).await?;
+        Ok(gate::check_title(
+            title,
+            &messages,
+            &self.config.settings.gate,
+        ))
+    }
+}
+
+/// How much of the diff the compact prompt tried after an overflow keeps
+#[cfg(feature = "cli")]
+const COMPACT_BUDGET: PromptBudget = PromptBudget {
+    max_lines: prompt::DEFAULT_MAX_DIFF_LINES / 4,
+    max_line_length: 100,
+};
+
+/// Get replacements for the patches of vendored code and Git LFS pointers, keyed by path
+#[cfg(feature = "cli")]
+fn bulk_patch_replacements(diff: &str) -> Vec<(String, String)> {
+    let mut replacements = vendored::vendored_patch_replacements(&vendored::detect_vendored(diff));
+    replacements.extend(lfs::lfs_patch_replacements(&lfs::detect_lfs_changes(diff)));
+    replacements
+}
+
+/// Create the provider for a configuration, with requests that leave the machine audited when enabled
+#[cfg(feature = "cli")]
+fn create_checked_provider(
+    config: &Config,
+    provider_config: ProviderConfig,
+    http: &reqwest::Client,
+) -> Result<Box<dyn AIProvider>> {
+    let model = provider_config.model().to_string();
+    let local = provider_config.is_local();
+    let provider =
+        create_provider_with_temperature(provider_config, http.clone(), config.temperature)?;
+
+    let audit = &config.settings.audit;
+    if audit.enabled && !local {
+        return Ok(Box::new(audit::AuditingProvider::new(
+            provider,
+            model,
+            config.privacy,
+            audit::audit_path(audit)?,
+        )));
+    }
+    Ok(provider)
+}
+
+/// Run a future to completion on the runtime kept for the blocking API
+///
+/// The runtime lives for the whole process so pooled HTTP connections stay usable between calls.
+#[cfg(feature = "cli")]
+fn block_on<T>(future: impl Future<Output = Result<T>>) -> Result<T> {
+    static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+
+    if tokio::runtime::Handle::try_current().is_ok() {
+        return Err(anyhow::anyhow!(
+            "Blocking calls can't be made from inside an async runtime; use the async method instead"
+        ));
+    }
+
+    let runtime = match RUNTIME.get() {
+        Some(runtime) => runtime,
+        None => {
+            let runtime = tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(1)
+                .thread_name("committor-blocking")
+                .enable_all()
+                .build()?;
+            RUNTIME.get_or_init(|| runtime)
+        }
+    };
+    runtime.block_on(future)
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_committor_clones_share_provider() -> Result<()> {
+        fn assert_shareable<T: Clone + Send + Sync + 'static>() {}
+        assert_shareable::<Committor>();
+
+        let config = Config::with_ollama(
+            "http://localhost:11434".to_string(),
+            "llama2".to_string(),
+            1,
+            false,
+            false,
+        );
+        let committor = Committor::new(config)?;
+        let clone = committor.clone();
+        assert!(Arc::ptr_eq(&committor.provider, &clone.provider));
+
+        let name = std::thread::spawn(move || clone.provider.provider_name())
+            .join()
+            .unwrap();
+        assert_eq!(name, committor.provider.provider_name());
+        Ok(())
+    }
+
+    #[test]
+    fn test_excluded_files_leave_the_staged_context() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let repo = git2::Repository::init(dir.path())?;
+        let stage = |files: &[(&str, &str)]| -> Result<()> {
+            let mut index = repo.index()?;
+            for (path, content) in files {
+                let full = dir.path().join(path);
+                std::fs::create_dir_all(full.parent().unwrap())?;
+                std::fs::write(full, content)?;
+                index.add_path(std::path::Path::new(path))?;
+            }
+            index.write()?;
+            Ok(())
+        };
+        let workflow = ".github/workflows/ci.yml";
+        stage(&[
+            ("Cargo.toml", "[dependencies]\nserde = \"1.0\"\n"),
+            (
+                workflow,
+                "jobs:\n  test:\n    steps:\n      - run: cargo test\n",
+            ),
+        ])?;
+        let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+        let signature = git2::Signature::now("Test User", "test@example.com")?;
+        repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])?;
+        stage(&[
+            ("Cargo.toml", "[dependencies]\nserde = \"1.1\"\n"),
+            (
+                workflow,
+                "jobs:\n  lint:\n    steps:\n      - run: cargo fmt --check\n",
+            ),
+        ])?;
+
+        let config = Config::with_ollama(
+            "http://localhost:11434".to_string(),
+            "llama2".to_string(),
+            1,
+            false,
+            false,
+        );
+        let committor = Committor::new(config)?;
+        let mut context = prompt::CommitContext::default();
+        let replacements = committor.add_repo_context(&repo, &mut context);
+        assert_eq!(context.dependency_changes, ["bump serde from 1.0 to 1.1"]);
+        assert_eq!(context.ci_changes.len(), 1);
+        assert_eq!(replacements.len(), 1);
+
+        let excluding = committor.excluding(vec!["Cargo.toml".to_string(), workflow.to_string()]);
+        let mut context = prompt::CommitContext::default();
+        let replacements = excluding.add_repo_context(&repo, &mut context);
+        assert!(context.dependency_changes.is_empty());
+        assert!(context.ci_changes.is_empty());
+        assert!(replacements.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_plan_split_rejects_filtered_messages() -> Result<()> {
+        struct PlanProvider;
+
+        #[async_trait::async_trait]
+        impl AIProvider for PlanProvider {
+            async fn generate_message(&self, _prompt: &str) -> Result<String> {
+                Ok("1 | feat: add falcon login\n2 | docs: describe login".to_string())
+            }
+
+            fn provider_name(&self) -> &'static str {
+                "Plan"
+            }
+        }
+
+        let hunks: Vec<types::DiffHunk> = ["src/login.rs", "README.md"]
+            .iter()
+            .map(|path| types::DiffHunk {
+                file_path: path.to_string(),
+                file_header: format!("diff --git a/{path} b/{path}"),
+                text: Some("@@ -1 +1 @@\n+login\n".to_string()),
+            })
+            .collect();
+        let mut settings = Settings::default();
+        settings.rules.forbidden_words = vec!["falcon".to_string()];
+        settings.rules.strictness = rules::Strictness::Reject;
+        let config = Config::with_ollama(
+            "http://localhost:11434".to_string(),
+            "llama2".to_string(),
+            1,
+            false,
+            false,
+        )
+        .with_settings(settings);
+        let committor = Committor::with_provider(config, Arc::new(PlanProvider))?;
+
+        let error = committor.plan_split(&hunks).await.unwrap_err();
+        assert!(error.to_string().contains("feat: add falcon login"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_trailers_filter_survives_the_subject_cut() -> Result<()> {
+        struct LoginProvider;
+
+        #[async_trait::async_trait]
+        impl AIProvider for LoginProvider {
+            async fn generate_message(&self, _prompt: &str) -> Result<String> {
+                Ok("feat: add login form".to_string())
+            }
+
+            fn provider_name(&self) -> &'static str {
+                "Login"
+            }
+        }
+
+        let settings = Settings::parse(
+            "[trailers]\nadd = [\"Reviewed-by-policy: ai-assisted\"]\n\n\
+             [filters]\npipeline = [\"normalize-case\", \"trailers\"]\n",
+        )?;
+        let config = Config::with_ollama(
+            "http://localhost:11434".to_string(),
+            "llama2".to_string(),
+            1,
+            false,
+            false,
+        )
+        .with_settings(settings);
+        let committor = Committor::with_provider(config, Arc::new(LoginProvider))?;
+
+        let messages = committor.generate_commit_messages("+fn login() {}").await?;
+        assert_eq!(
+            messages,
+            ["feat: add login form\n\nReviewed-by-policy: ai-assisted"]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_within_budget_returns_partial_results() -> Result<()> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        /// Answers each request more slowly than the one before
+        struct SlowingProvider(AtomicUsize);
+
+        #[async_trait::async_trait]
+        impl AIProvider for SlowingProvider {
+            async fn generate_message(&self, _prompt: &str) -> Result<String> {
+                let call = self.0.fetch_add(1, Ordering::SeqCst);
+                let (delay, message) = [
+                    (0, "feat: add login form"),
+                    (20, "fix: handle empty passwords"),
+                    (400, "docs: describe the login flow"),
+                ][call % 3];
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                Ok(message.to_string())
+            }
+
+            fn provider_name(&self) -> &'static str {
+                "Slow"
+            }
+        }
+
+        let config = Config::with_ollama(
+            "http://localhost:11434".to_string(),
+            "llama2".to_string(),
+            3,
+            false,
+            false,
+        );
+        let committor =
+            Committor::with_provider(config, Arc::new(SlowingProvider(AtomicUsize::new(0))))?;
+
+        let mut partial = committor
+            .generate_commit_messages_within("+fn login() {}", Duration::from_millis(150))
+            .await?;
+        assert_eq!(partial.messages.len(), 2);
+        assert_eq!(partial.requested, 3);
+        assert!(!partial.is_complete());
+
+        committor.wait_for_more(&mut partial).await?;
+        assert_eq!(partial.messages.len(), 3);
+        assert!(partial.is_complete());
+        assert!(partial
+            .messages
+            .contains(&"docs: describe the login flow".to_string()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_custom_rules_discard_generated_messages() -> Result<()> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        /// Leaves out the scope on every other answer
+        struct ScopeForgettingProvider(AtomicUsize);
+
+        #[async_trait::async_trait]
+        impl AIProvider for ScopeForgettingProvider {
+            async fn generate_message(&self, _prompt: &str) -> Result<String> {
+                let call = self.0.fetch_add(1, Ordering::SeqCst);
+                Ok(["feat: add login", "feat(auth): add login"][call % 2].to_string())
+            }
+
+            fn provider_name(&self) -> &'static str {
+                "Forgetful"
+            }
+        }
+
+        struct AuthScope;
+
+        impl rules::Rule for AuthScope {
+            fn check(&self, message: &str) -> Vec<rules::Violation> {
+                if message.starts_with("feat(auth)") {
+                    return Vec::new();
+                }
+                vec![rules::Violation::new("auth-scope", "Use the auth scope")]
+            }
+        }
+
+        let config = Config::with_ollama(
+            "http://localhost:11434".to_string(),
+            "llama2".to_string(),
+            1,
+            false,
+            false,
+        )
+        .with_rule(AuthScope);
+        let provider = Arc::new(ScopeForgettingProvider(AtomicUsize::new(0)));
+        let committor = Committor::with_provider(config, provider.clone())?;
+
+        let messages = committor.generate_commit_messages("+fn login() {}").await?;
+        assert_eq!(messages, vec!["feat(auth): add login"]);
+        assert_eq!(provider.0.load(Ordering::SeqCst), 2);
+        let broken = committor.rule_set().check("feat: add login");
+        assert_eq!(broken[0].rule, "auth-scope");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_overflowing_prompt_is_retried_compact() -> Result<()> {
+        use std::sync::Mutex;
+
+        /// Rejects prompts longer than a small context window, recording each one
+        struct SmallContextProvider(Mutex<Vec<String>>);
+
+        #[async_trait::async_trait]
+        impl AIProvider for SmallContextProvider {
+            async fn generate_message(&self, prompt: &str) -> Result<String> {
+                self.0.lock().unwrap().push(prompt.to_string());
+                if prompt.len() > 3_000 {
+                    return Err(types::CommittorError::ContextTooLarge(
+                        "context_length_exceeded".to_string(),
+                    )
+                    .into());
+                }
+                Ok("feat: add login".to_string())
+            }
+
+            fn provider_name(&self) -> &'static str {
+                "Small"
+            }
+        }
+
+        let diff: String = (0..100)
+            .map(|i| format!("+    let field_{i} = login_form.field({i});\n"))
+            .collect();
+        let config = |compact: bool| {
+            let mut settings = Settings::default();
+            settings.overflow.compact = compact;
+            Config::with_ollama(
+                "http://localhost:11434".to_string(),
+                "llama2".to_string(),
+                1,
+                false,
+                false,
+            )
+            .with_settings(settings)
+        };
+
+        let provider = Arc::new(SmallContextProvider(Mutex::new(Vec::new())));
+        let committor = Committor::with_provider(config(true), provider.clone())?;
+        let messages = committor.generate_commit_messages(&diff).await?;
+        assert_eq!(messages, vec!["feat: add login"]);
+        let prompts = provider.0.lock().unwrap().clone();
+        assert_eq!(prompts.len(), 2);
+        assert!(prompts[1].contains("field_24") && !prompts[1].contains("field_25"));
+
+        let provider = Arc::new(SmallContextProvider(Mutex::new(Vec::new())));
+        let committor = Committor::with_provider(config(false), provider)?;
+        let error = committor.generate_commit_messages(&diff).await.unwrap_err();
+        assert!(is_context_too_large(&error));
+        Ok(())
+    }
+
+    #[test]
+    fn test_committor_rejects_empty_api_key() {
+        let config = Config::with_openai(String::new(), "gpt-4".to_string(), 3, false, false);
+        let error = Committor::new(config).err().unwrap();
+        assert!(error.to_string().contains("API key"), "{error}");
+    }
 }
@@ -0,0 +1,114 @@
+//! Waiting for another git process to release the index
+//!
+//! git holds `.git/index.lock` while it writes the index, and an editor's git
+//! integration or a long `git add -p` can keep it for a while. Reading the staged
+//! changes doesn't need the lock, but a commit made meanwhile fails with git's
+//! "File exists" error, so commands that write wait for it first, up to a timeout.
+
+use crate::i18n::Text;
+use crate::types::CommittorError;
+use anyhow::{Context, Result};
+use colored::*;
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often the lock file is checked while waiting
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Index lock behaviour read from the `[index_lock]` settings section
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IndexLockSettings {
+    /// Seconds to wait for another git process to release the index before giving up
+    pub timeout: u64,
+}
+
+impl Default for IndexLockSettings {
+    fn default() -> Self {
+        Self { timeout: 10 }
+    }
+}
+
+/// Get the path of a repository's index lock; linked worktrees each have their own
+pub fn index_lock_path(repo: &Repository) -> PathBuf {
+    repo.path().join("index.lock")
+}
+
+/// Wait for the current repository's index lock to be released
+pub fn wait_for_index_lock(timeout: Duration) -> Result<()> {
+    let repo = Repository::open(".").context("Not in a git repository")?;
+    wait_for_index_lock_from_repo(&repo, timeout)
+}
+
+/// Wait for a repository's index lock to be released, failing once `timeout` passes
+pub fn wait_for_index_lock_from_repo(repo: &Repository, timeout: Duration) -> Result<()> {
+    let path = index_lock_path(repo);
+    if !path.exists() {
+        return Ok(());
+    }
+
+    println!(
+        "{}",
+        Text::WaitingForIndexLock
+            .format(&[&timeout.as_secs(), &path.display()])
+            .yellow()
+    );
+    if wait_for_removal(&path, timeout) {
+        return Ok(());
+    }
+    Err(CommittorError::GitError(format!(
+        "Another git process is still holding {} after {}s. If no git process is running, remove the file and try again, or raise `index_lock.timeout`.",
+        path.display(),
+        timeout.as_secs()
+    ))
+    .into())
+}
+
+/// Poll until a file is gone, returning whether it went before the timeout
+fn wait_for_removal(path: &Path, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    while path.exists() {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_wait_for_index_lock() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo = Repository::init(temp_dir.path())?;
+        let lock = index_lock_path(&repo);
+
+        // No lock, no wait
+        wait_for_index_lock_from_repo(&repo, Duration::ZERO)?;
+
+        fs::write(&lock, "")?;
+        let error = wait_for_index_lock_from_repo(&repo, Duration::ZERO).unwrap_err();
+        assert!(error.to_string().contains("index.lock"));
+
+        let releaser = {
+            let lock = lock.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(200));
+                fs::remove_file(lock).unwrap();
+            })
+        };
+        wait_for_index_lock_from_repo(&repo, Duration::from_secs(10))?;
+        releaser.join().unwrap();
+        assert!(!lock.exists());
+
+        Ok(())
+    }
+}
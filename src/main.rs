@@ -1,27 +1,62 @@
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 use colored::*;
-use committor::{commit, providers, Committor, Config};
+use committor::audit;
+use committor::backup;
+use committor::badge::{self, BadgeFormat};
+use committor::commit::{PickerAction, StaleAction};
+use committor::exit::ExitStatus;
+use committor::feedback::{self, Rating};
+use committor::hooks::{self, EnforcementMode};
+use committor::i18n::{self, Locale, Text};
+use committor::outcomes::Outcomes;
+use committor::privacy::PrivacyLevel;
+use committor::providers::SamplingSettings;
+use committor::report::{self, DateFormat};
+use committor::session::{self, Session};
+use committor::settings::{self, Settings};
+use committor::shell::{self, Shell};
+use committor::theme::{self, Element, Theme};
+use committor::timing::Timings;
+use committor::types::{CommittorError, DiffChange};
+use committor::{
+    cherry_pick, commit, configuration, conflicts, credentials, diff, gate, output, paths, policy,
+    providers, review, rewrite, rules, split, tag, test_gap, trailers, update, vendored, Committor,
+    Config, PartialMessages,
+};
 use std::env;
-use std::time::Duration;
+use std::fs;
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
 use tracing::{info, warn};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
 
-#[derive(Parser)]
+#[derive(Parser, Clone)]
 #[command(name = "committor")]
 #[command(about = "Generate conventional commit messages automatically based on git diff")]
-#[command(version = "0.1.0")]
+#[command(version)]
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
 
-    /// AI provider to use
-    #[arg(long, value_enum, default_value = "openai")]
-    provider: AIProviderType,
+    /// AI provider to use; when not set, OpenAI if an API key is set, otherwise a running Ollama
+    #[arg(long, value_enum, env = "COMMITTOR_PROVIDER")]
+    provider: Option<AIProviderType>,
 
     /// OpenAI API key (can also be set via OPENAI_API_KEY environment variable)
     #[arg(long, env = "OPENAI_API_KEY")]
     api_key: Option<String>,
 
+    /// OpenAI API base URL, e.g. for a proxy
+    #[arg(long, env = "OPENAI_BASE_URL")]
+    openai_url: Option<String>,
+
     /// Ollama base URL
     #[arg(long, default_value = "http://localhost:11434")]
     ollama_url: String,
@@ -30,24 +65,129 @@ struct Cli {
     #[arg(long, default_value = "30")]
     ollama_timeout: u64,
 
+    /// Seconds the commit picker waits for every option before showing the ones that are ready
+    #[arg(long, value_name = "SECS", env = "COMMITTOR_LATENCY_BUDGET")]
+    latency_budget: Option<u64>,
+
     /// Model to use for generation
-    #[arg(long, default_value = "llama2:7b")]
+    #[arg(long, env = "COMMITTOR_MODEL", default_value = "llama2:7b")]
     model: String,
 
     /// Maximum number of commit message options to generate
     #[arg(long, default_value = "3")]
     count: u8,
 
-    /// Automatically use the first generated commit message
-    #[arg(long, short = 'y')]
+    /// Automatically use the first generated commit message and skip confirmations
+    #[arg(long, short = 'y', visible_alias = "yes")]
     auto_commit: bool,
 
-    /// Show the git diff before generating commit message
+    /// Show the changes before generating: a per-file summary, or the whole patch with `--show-diff=full`
+    #[arg(
+        long,
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "summary"
+    )]
+    show_diff: Option<ShowDiffArg>,
+
+    /// Path to the repository to operate on (may be a bare repository)
+    #[arg(long)]
+    repo: Option<PathBuf>,
+
+    /// Revision range to use instead of staged changes (e.g. `main..feature`)
+    #[arg(long)]
+    range: Option<String>,
+
+    /// Output template, e.g. "{type}({scope}): {subject}\n\n{body}\n\nRefs: {ticket}"
+    #[arg(long, env = "COMMITTOR_FORMAT")]
+    format: Option<String>,
+
+    /// Ask for a body explaining why, below each subject line
+    #[arg(long, env = "COMMITTOR_WITH_BODY")]
+    with_body: bool,
+
+    /// When source files change without any test, list test cases to add next to the messages
+    #[arg(long, env = "COMMITTOR_SUGGEST_TESTS")]
+    suggest_tests: bool,
+
+    /// Record the committed option, and options rated with '+1'/'-1' in the picker, as feedback
+    #[arg(long, env = "COMMITTOR_FEEDBACK")]
+    feedback: bool,
+
+    /// Language of the subject's description, e.g. "German" (overrides the settings file)
+    #[arg(long, env = "COMMITTOR_SUBJECT_LANGUAGE")]
+    subject_language: Option<String>,
+
+    /// Language of the body, independent of the subject's (overrides the settings file)
+    #[arg(long, env = "COMMITTOR_BODY_LANGUAGE")]
+    body_language: Option<String>,
+
+    /// How much of the staged changes may be sent to the provider
+    #[arg(long, value_enum, env = "COMMITTOR_PRIVACY", default_value = "full")]
+    privacy: PrivacyArg,
+
+    /// Shorthand for `--privacy redacted`
+    #[arg(long, env = "COMMITTOR_ANONYMIZE", conflicts_with_all = ["paths_only", "privacy"])]
+    anonymize: bool,
+
+    /// Shorthand for `--privacy paths-only`
+    #[arg(long, env = "COMMITTOR_PATHS_ONLY", conflicts_with = "privacy")]
+    paths_only: bool,
+
+    /// Show what each option's commit type means and flag options that disagree on it,
+    /// and show the rejected model output when no valid message could be generated
+    #[arg(long, short = 'v')]
+    verbose: bool,
+
+    /// Skip the Ollama availability check before generating
+    #[arg(long, env = "COMMITTOR_NO_PREFLIGHT")]
+    no_preflight: bool,
+
+    /// Record every request sent to a provider off this machine in the audit log
+    #[arg(long, env = "COMMITTOR_AUDIT")]
+    audit: bool,
+
+    /// Don't check once a day whether a newer release is available
+    #[arg(long, env = "COMMITTOR_NO_UPDATE_CHECK")]
+    no_update_check: bool,
+
+    /// Print how long each phase took: git diff, prompt building, provider calls and validation
     #[arg(long)]
-    show_diff: bool,
+    timing: bool,
+
+    /// Screen-reader friendly output: no colour, symbols or box-drawing, with labelled lists
+    #[arg(long, env = "COMMITTOR_PLAIN")]
+    plain: bool,
+
+    /// Language of the interface; defaults to the one set by LC_ALL, LC_MESSAGES or LANG
+    #[arg(long, value_enum, env = "COMMITTOR_LOCALE")]
+    locale: Option<LocaleArg>,
+
+    /// How reports write dates: `locale`, `iso` or a pattern such as `%Y-%m-%d %H:%M`
+    #[arg(long, env = "COMMITTOR_DATE_FORMAT", default_value = "locale")]
+    date_format: DateFormat,
+}
+
+impl Cli {
+    /// Get the provider; one is detected before any command that needs it runs
+    fn provider(&self) -> AIProviderType {
+        self.provider.unwrap_or(AIProviderType::OpenAI)
+    }
+
+    /// Get the privacy level from `--privacy` or the shorthand flags, which can't be combined
+    fn privacy_level(&self) -> PrivacyLevel {
+        if self.paths_only {
+            PrivacyLevel::PathsOnly
+        } else if self.anonymize {
+            PrivacyLevel::Redacted
+        } else {
+            self.privacy.into()
+        }
+    }
 }
 
-#[derive(Clone, Debug, ValueEnum)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
 enum AIProviderType {
     #[value(name = "openai")]
     OpenAI,
@@ -60,35 +200,615 @@ enum Commands {
     /// Generate a commit message for staged changes
     Generate,
     /// Generate and commit in one step
-    Commit,
+    Commit {
+        /// Suggest a message per staged file and commit the selected ones separately
+        #[arg(long, conflicts_with = "use_last")]
+        per_file: bool,
+        /// Choose from the suggestions the last run made for these staged changes
+        #[arg(long)]
+        use_last: bool,
+        /// Commit the Nth suggestion, or the top-ranked one with `best`, without prompting
+        #[arg(long, value_name = "N|best", value_parser = parse_choose, conflicts_with = "per_file")]
+        choose: Option<usize>,
+        /// With --per-file, leave a file staged and go on when its message can't be generated or committed
+        #[arg(long, requires = "per_file")]
+        continue_on_error: bool,
+        /// Commit one message from the `[quick]` model without prompting, or one written from
+        /// the changed paths when it doesn't answer within the `[quick]` budget
+        #[arg(long, conflicts_with_all = ["per_file", "use_last", "choose"])]
+        quick: bool,
+    },
+    /// Split the staged changes into several commits, down to individual hunks
+    ///
+    /// The original HEAD is backed up first, for `committor restore`.
+    Split,
+    /// Regenerate the messages of existing commits and rewrite them with `git rebase -i`
+    ///
+    /// The original HEAD is backed up first, for `committor restore`.
+    Rewrite {
+        /// Commits to rewrite, ending at HEAD (e.g. `main..HEAD`)
+        #[arg(long)]
+        range: Option<String>,
+        /// Rewrite the commits since this branch; defaults to the remote's default branch
+        #[arg(long, conflicts_with = "range")]
+        base: Option<String>,
+        /// Only show the new messages
+        #[arg(long)]
+        dry_run: bool,
+        /// Keep the message of a commit whose new one can't be generated and go on
+        #[arg(long)]
+        continue_on_error: bool,
+    },
+    /// Cherry-pick a commit with its message rewritten for the current branch's conventions
+    ///
+    /// Scopes are mapped to the ones this branch uses, the original trailers are kept
+    /// and `(cherry picked from commit …)` is added.
+    CherryPick {
+        /// Commit to cherry-pick
+        commit: String,
+    },
+    /// Continue a rebase or cherry-pick stopped on conflicts, with a message for the resolved change
+    ///
+    /// Resolve and stage the conflicts first; the message is generated from the
+    /// original one and the diff after resolving.
+    Continue,
+    /// Move the current branch back to where it was before a split, rewrite or restore
+    Restore {
+        /// Backup to restore, as shown by `--list`; defaults to the newest
+        backup: Option<String>,
+        /// List the backups instead of restoring one
+        #[arg(long, conflicts_with = "backup")]
+        list: bool,
+    },
     /// Show the current git diff
-    Diff,
+    Diff {
+        /// Show a bar per file of lines added and removed instead of the patch
+        #[arg(long)]
+        stat: bool,
+    },
+    /// List the changed files with their line counts, without generating anything
+    Summary {
+        /// Print the summary as JSON
+        #[arg(long)]
+        json: bool,
+        /// Show a bar per file of lines added and removed, like `git diff --stat`
+        #[arg(long, conflicts_with = "json")]
+        stat: bool,
+    },
     /// List available models for the selected provider
     Models,
     /// Check if Ollama is available (only for Ollama provider)
     CheckOllama,
+    /// Comment on each hunk of a change the way a reviewer would, before committing it
+    ///
+    /// Points out likely bugs and missing tests; the comments are advice only.
+    Review {
+        /// Review the staged changes
+        #[arg(long, required = true)]
+        staged: bool,
+    },
+    /// Stash working tree changes under a generated, descriptive message
+    StashMsg {
+        /// Only print the generated message without stashing
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Generate commit messages from a description of the change instead of a diff
+    ///
+    /// Useful when there is no diff to read, e.g. for a patch someone else wrote.
+    From {
+        /// What the change does, in your own words; `-` reads it from stdin
+        description: String,
+    },
+    /// Generate an annotated tag message from the commits since the previous tag
+    Tag {
+        /// Name of the new tag (e.g. v1.4.0)
+        name: String,
+        /// Create the annotated tag at HEAD with the generated message
+        #[arg(long)]
+        create: bool,
+    },
+    /// Run as a git hook
+    Hook {
+        #[command(subcommand)]
+        hook: HookCommands,
+    },
+    /// Check that a pull request title describes its change, for pre-merge CI
+    ///
+    /// Messages are generated for the squashed diff of `<base>...HEAD` (or `--range`)
+    /// and the title is compared with them.
+    Gate {
+        /// Title of the pull request
+        #[arg(long, env = "PR_TITLE")]
+        title: String,
+        /// Branch the pull request merges into; defaults to the remote's default branch
+        #[arg(long)]
+        base: Option<String>,
+        /// Whether to fail or only warn when the title misrepresents the change (overrides the settings file)
+        #[arg(long, value_enum)]
+        mode: Option<HookMode>,
+        /// Write the result as a Markdown comment, for CI to post on the pull request
+        #[arg(long, value_name = "FILE")]
+        comment: Option<PathBuf>,
+    },
+    /// Replay past commits through the model and compare against the real subjects
+    Eval {
+        /// Number of recent commits to replay
+        #[arg(long, default_value = "200")]
+        last: usize,
+    },
+    /// Embed past commits into the local index used to find similar commits
+    Index {
+        /// Number of recent commits to index
+        #[arg(long, default_value = "500")]
+        last: usize,
+        /// Discard the existing index and embed every commit again
+        #[arg(long)]
+        rebuild: bool,
+    },
+    /// Write a README badge of the share of recent commits with a conventional subject
+    ///
+    /// Writes a shields.io endpoint file, or an SVG when the file ends in `.svg`.
+    Badge {
+        /// File to write the badge to, or `-` for standard output
+        file: PathBuf,
+        /// Number of recent commits to measure, not counting merges
+        #[arg(long, default_value = "100")]
+        last: usize,
+        /// Format to write, instead of the one the file's extension implies
+        #[arg(long, value_enum)]
+        format: Option<BadgeFormatArg>,
+        /// Text on the left of the badge
+        #[arg(long, default_value = badge::DEFAULT_LABEL)]
+        label: String,
+    },
+    /// Check a commit message against the repository's rules, without any AI
+    Lint {
+        /// File holding the message; reads stdin when omitted or `-`
+        file: Option<PathBuf>,
+    },
+    /// List the commit types, their meanings and the allowed scopes for this repository
+    Types,
+    /// Print a snippet that shows staged changes and the predicted commit type in the shell prompt
+    PromptHook {
+        /// Shell to print the snippet for
+        #[arg(value_enum)]
+        shell: ShellArg,
+    },
+    /// Print the indicator the prompt snippet shows, e.g. `+feat`; nothing when no changes are staged
+    PromptStatus,
+    /// Run as a hook of the pre-commit framework, declared in `.pre-commit-config.yaml`
+    ///
+    /// Never prompts or reads stdin. The prepare-commit-msg stage suggests messages and
+    /// always succeeds; the commit-msg stage adds trailers and fails on broken rules.
+    PreCommitHook {
+        /// Stage the hook is installed for
+        #[arg(value_enum)]
+        stage: PreCommitStageArg,
+        /// Path to the commit message file, which pre-commit passes last
+        file: PathBuf,
+        /// Where the message came from, for the prepare-commit-msg stage
+        #[arg(long, env = "PRE_COMMIT_COMMIT_MSG_SOURCE")]
+        source: Option<String>,
+        /// Seconds to wait for suggestions before leaving the message alone (overrides the settings file)
+        #[arg(long)]
+        timeout: Option<u64>,
+    },
+    /// Write the commit prompt for the staged changes to a file, to run the model on another machine
+    ExportPrompt {
+        /// File to write the prompt to, or `-` for standard output
+        file: PathBuf,
+    },
+    /// Read the model's replies to an exported prompt and show them as options, like `generate`
+    ImportResponse {
+        /// Files holding one reply each, or `-` for standard input
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Write the last `generate` or `commit` run to a file, as JSON for `.json` and Markdown otherwise
+    ExportSession {
+        /// File to write the session to
+        file: PathBuf,
+    },
+    /// Print where committor keeps its configuration, caches and per-repository files
+    Paths,
+    /// Choose a provider and model interactively and create a starter settings file
+    Init,
+    /// Inspect the log of requests sent off this machine
+    Audit {
+        #[command(subcommand)]
+        audit: AuditCommands,
+    },
+    /// Check or show the configuration committor runs with
+    Config {
+        #[command(subcommand)]
+        config: ConfigCommands,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+enum ConfigCommands {
+    /// Check the settings file and organization policy for unknown keys and invalid values
+    Lint,
+    /// Print the effective settings
+    Show {
+        /// Also list command-line and environment options, and where every value came from
+        #[arg(long)]
+        resolved: bool,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+enum AuditCommands {
+    /// Show the recorded requests, oldest first
+    Show {
+        /// Only show the most recent records
+        #[arg(long)]
+        last: Option<usize>,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+enum HookCommands {
+    /// Add the configured trailers to the message file git passes to commit-msg hooks
+    CommitMsg {
+        /// Path to the commit message file
+        file: PathBuf,
+        /// Always add a Gerrit Change-Id, even if the settings file doesn't enable it
+        #[arg(long)]
+        change_id: bool,
+    },
+    /// Suggest messages in the file git passes to prepare-commit-msg hooks
+    ///
+    /// A message that is already there is kept; suggestions are added as comments.
+    PrepareCommitMsg {
+        /// Path to the commit message file
+        file: PathBuf,
+        /// Where the message came from: message, template, merge, squash or commit
+        source: Option<String>,
+        /// The commit being amended or reused, for the commit source
+        sha: Option<String>,
+        /// Seconds to wait for suggestions before leaving the message alone (overrides the settings file)
+        #[arg(long)]
+        timeout: Option<u64>,
+    },
+    /// Validate pushed commit messages, reading `<old> <new> <ref>` lines from stdin
+    PreReceive {
+        /// Whether to reject the push or only warn (overrides the settings file)
+        #[arg(long, value_enum)]
+        mode: Option<HookMode>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum PreCommitStageArg {
+    /// Suggest messages in the editor, like `hook prepare-commit-msg`
+    PrepareCommitMsg,
+    /// Add trailers and check the message, like `hook commit-msg` followed by `lint`
+    CommitMsg,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ShowDiffArg {
+    /// Each changed file with its change type and line counts
+    Summary,
+    /// The whole patch
+    Full,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum PrivacyArg {
+    /// Send the diff, minus lines that look sensitive
+    Full,
+    /// Replace literals, emails, hostnames and IPs with placeholders
+    Redacted,
+    /// Send only file paths, change types and line counts
+    PathsOnly,
+    /// Send the diff, but only to Ollama on localhost
+    LocalOnly,
+}
+
+impl From<PrivacyArg> for PrivacyLevel {
+    fn from(privacy: PrivacyArg) -> Self {
+        match privacy {
+            PrivacyArg::Full => PrivacyLevel::Full,
+            PrivacyArg::Redacted => PrivacyLevel::Redacted,
+            PrivacyArg::PathsOnly => PrivacyLevel::PathsOnly,
+            PrivacyArg::LocalOnly => PrivacyLevel::LocalOnly,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum LocaleArg {
+    /// English
+    En,
+    /// Japanese
+    Ja,
+    /// Chinese
+    Zh,
+    /// Spanish
+    Es,
+}
+
+impl From<LocaleArg> for Locale {
+    fn from(locale: LocaleArg) -> Self {
+        match locale {
+            LocaleArg::En => Locale::English,
+            LocaleArg::Ja => Locale::Japanese,
+            LocaleArg::Zh => Locale::Chinese,
+            LocaleArg::Es => Locale::Spanish,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ShellArg {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl From<ShellArg> for Shell {
+    fn from(shell: ShellArg) -> Self {
+        match shell {
+            ShellArg::Bash => Shell::Bash,
+            ShellArg::Zsh => Shell::Zsh,
+            ShellArg::Fish => Shell::Fish,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum BadgeFormatArg {
+    /// A shields.io endpoint file
+    Json,
+    /// A badge image
+    Svg,
+}
+
+impl From<BadgeFormatArg> for BadgeFormat {
+    fn from(format: BadgeFormatArg) -> Self {
+        match format {
+            BadgeFormatArg::Json => BadgeFormat::Json,
+            BadgeFormatArg::Svg => BadgeFormat::Svg,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum HookMode {
+    Enforce,
+    Warn,
+}
+
+impl From<HookMode> for EnforcementMode {
+    fn from(mode: HookMode) -> Self {
+        match mode {
+            HookMode::Enforce => EnforcementMode::Enforce,
+            HookMode::Warn => EnforcementMode::Warn,
+        }
+    }
+}
+
+/// Exit cleanly on Ctrl-C, even while the picker is blocked reading input
+///
+/// The handler runs as its own task so a blocked prompt can't hold it up.
+fn spawn_interrupt_handler() {
+    tokio::spawn(async {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+        restore_terminal();
+        println!("{}", Text::Cancelled.text().yellow());
+        std::process::exit(ExitStatus::Interrupted.code().into());
+    });
+}
+
+/// Reset colours and show the cursor, then move past any half-written prompt line
+fn restore_terminal() {
+    let mut stdout = std::io::stdout();
+    if stdout.is_terminal() && !output::is_plain() {
+        let _ = write!(stdout, "\x1b[0m\x1b[?25h");
+    }
+    let _ = writeln!(stdout);
+    let _ = stdout.flush();
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitStatus::Success.into(),
+        Err(e) => {
+            if !ExitStatus::is_quiet(&e) {
+                eprintln!("Error: {e:?}");
+            }
+            ExitStatus::of(&e).into()
+        }
+    }
+}
+
+async fn run() -> Result<()> {
+    let started = Instant::now();
+    // `--version` also reports the build, the providers and the settings file, for bug reports
+    let long_version: &'static str =
+        committor::version::long_version(settings::settings_path().as_deref()).leak();
+    let matches = Cli::command().long_version(long_version).get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    // Always collected: the session export includes them even without --timing
+    let timings = Timings::new();
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_filter(LevelFilter::INFO))
+        .with(timings.layer())
+        .init();
+    i18n::set_locale(cli.locale.map_or_else(Locale::from_env, Locale::from));
+    report::set_date_format(cli.date_format.clone());
+    output::set_plain(cli.plain || output::is_dumb_terminal());
+    commit::set_explain_failures(cli.verbose);
+    spawn_interrupt_handler();
+
+    if let Some(repo) = &cli.repo {
+        env::set_current_dir(repo)
+            .with_context(|| format!("Failed to open repository at {}", repo.display()))?;
+    }
+
+    // Only commands that send changes to a provider wait for the policy URL; the rest,
+    // such as `prompt-status` on every prompt, use what is on disk and never fail on it
+    let generates = needs_provider(cli.command.as_ref()) || suggests_from_hook(&cli);
+    let policy = if generates {
+        policy::load(&reqwest::Client::new()).await
+    } else {
+        policy::load_offline()
+    };
+    match policy {
+        Ok(Some(policy)) => policy::set_policy(policy),
+        Ok(None) => {}
+        // `config lint` reports what is wrong with the policy itself
+        Err(_) if !generates => {}
+        // Nothing is generated without the policy, so the hook just makes no suggestions
+        Err(e) if suggests_from_hook(&cli) => {
+            eprintln!("{}", format!("committor: no suggestions ({e:#})").yellow());
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    }
+    // A broken settings file is reported by the command that reads it, or `config lint`
+    if let Ok(settings) = Settings::discover() {
+        theme::set_theme(Theme::from_settings(&settings.theme));
+    }
+
+    // Commands that read no changes work outside a repository, e.g. for packagers and CI
+    let reads_changes = !matches!(
+        cli.command,
+        Some(
+            Commands::Paths
+                | Commands::Types
+                | Commands::PromptHook { .. }
+                | Commands::Lint { .. }
+                | Commands::From { .. }
+                | Commands::Init
+                | Commands::Config { .. }
+        )
+    );
+    if reads_changes {
+        commit::validate_git_environment().context("Git environment validation failed")?;
+    }
+
+    let quick = matches!(cli.command, Some(Commands::Commit { quick: true, .. }));
+    let mut quick_deadline = None;
+    if quick {
+        // Quick mode answers within its budget, so a down provider fails the request instead
+        cli.count = 1;
+        cli.no_preflight = true;
+        let quick_settings = Settings::discover()
+            .map(|settings| settings.quick)
+            .unwrap_or_default();
+        if let Some(model) = quick_settings.model {
+            cli.model = model;
+        }
+        // The budget covers looking for a provider too
+        quick_deadline =
+            Some(tokio::time::Instant::now() + Duration::from_secs(quick_settings.budget));
+    }
 
-    let cli = Cli::parse();
+    if cli.provider.is_none() && needs_provider(cli.command.as_ref()) {
+        let detected = match quick_deadline {
+            // A probe that outlasts the budget counts as finding no provider
+            Some(deadline) => tokio::time::timeout_at(deadline, detect_provider(&cli))
+                .await
+                .unwrap_or(Ok(None))?,
+            None => detect_provider(&cli).await?,
+        };
+        match detected {
+            Some(provider) => cli.provider = Some(provider),
+            // Quick mode writes the message from the changed paths instead
+            None if quick => {}
+            None => return handle_first_run(&cli).await,
+        }
+    }
 
-    // Validate git environment first
-    commit::validate_git_environment().context("Git environment validation failed")?;
+    let update_check = spawn_update_check(&cli);
 
     match cli.command.clone().unwrap_or(Commands::Generate) {
         Commands::Generate => {
+            let range = cli.range.clone();
+            let privacy = cli.privacy_level();
+            let (committor, diff_content) =
+                start_committor(&cli, move || get_diff_content(range.as_deref(), privacy)).await?;
+            let result = handle_generate_command(&committor, &cli, diff_content).await;
+            finish_session(&timings, started);
+            result?;
+        }
+        Commands::Commit {
+            per_file,
+            use_last,
+            choose,
+            continue_on_error,
+            quick,
+        } => {
+            if quick {
+                // With no provider the committor only checks and formats the message,
+                // so Ollama's settings stand in as they do for the air-gapped commands
+                let deadline = cli.provider.and(quick_deadline);
+                cli.provider.get_or_insert(AIProviderType::Ollama);
+                let (committor, diff_content) =
+                    start_committor(&cli, || Ok(diff::get_staged_snapshot()?.diff)).await?;
+                let result =
+                    handle_quick_commit_command(&committor, &cli, diff_content, deadline).await;
+                finish_session(&timings, started);
+                result?;
+            } else if per_file {
+                let committor = create_committor(&cli).await?;
+                handle_per_file_commit_command(&committor, &cli, continue_on_error).await?;
+            } else {
+                let (committor, diff_content) =
+                    start_committor(&cli, || Ok(diff::get_staged_snapshot()?.diff)).await?;
+                let result =
+                    handle_commit_command(&committor, &cli, diff_content, use_last, choose).await;
+                finish_session(&timings, started);
+                result?;
+            }
+        }
+        Commands::Split => {
+            let committor = create_committor(&cli).await?;
+            handle_split_command(&committor, &cli).await?;
+        }
+        Commands::Rewrite {
+            range,
+            base,
+            dry_run,
+            continue_on_error,
+        } => {
+            let range = match range {
+                Some(range) => range,
+                None => format!("{}..HEAD", base_branch(base)?),
+            };
+            let committor = create_committor(&cli).await?;
+            let mut outcomes = Outcomes::new(continue_on_error);
+            let result =
+                handle_rewrite_command(&committor, &cli, &range, dry_run, &mut outcomes).await;
+            finish_outcomes(&outcomes, result, "rewritten")?;
+        }
+        Commands::CherryPick { commit } => {
+            let pick = cherry_pick::find_commit(&commit)?;
             let committor = create_committor(&cli).await?;
-            handle_generate_command(&committor, &cli).await?;
+            handle_cherry_pick_command(&committor, &cli, &pick).await?;
         }
-        Commands::Commit => {
+        Commands::Continue => {
+            // Checked before a provider is set up, as a forgotten `git add` is the common mistake
+            let resolution = conflicts::find_resolution()?;
             let committor = create_committor(&cli).await?;
-            handle_commit_command(&committor, &cli).await?;
+            handle_continue_command(&committor, &cli, &resolution).await?;
+        }
+        Commands::Restore { backup, list } => {
+            handle_restore_command(backup.as_deref(), list)?;
         }
-        Commands::Diff => {
-            handle_diff_command()?;
+        Commands::Diff { stat } => {
+            handle_diff_command(&cli, stat)?;
+        }
+        Commands::Summary { json, stat } => {
+            handle_summary_command(&cli, json, stat)?;
         }
         Commands::Models => {
             handle_models_command(&cli).await?;
@@ -96,148 +816,1390 @@ async fn main() -> Result<()> {
         Commands::CheckOllama => {
             handle_check_ollama_command(&cli).await?;
         }
+        Commands::Review { .. } => {
+            let committor = create_committor(&cli).await?;
+            handle_review_command(&committor).await?;
+        }
+        Commands::StashMsg { dry_run } => {
+            let committor = create_committor(&cli).await?;
+            handle_stash_msg_command(&committor, &cli, dry_run).await?;
+        }
+        Commands::From { description } => {
+            let committor = create_committor(&cli).await?;
+            handle_from_command(&committor, &cli, &description).await?;
+        }
+        Commands::Tag { name, create } => {
+            let committor = create_committor(&cli).await?;
+            handle_tag_command(&committor, &name, create).await?;
+        }
+        Commands::Hook { hook } => match hook {
+            HookCommands::CommitMsg { file, change_id } => {
+                handle_commit_msg_command(&file, change_id)?;
+            }
+            HookCommands::PrepareCommitMsg {
+                file,
+                source,
+                timeout,
+                ..
+            } => {
+                handle_prepare_commit_msg_command(&cli, &file, source.as_deref(), timeout).await?;
+            }
+            HookCommands::PreReceive { mode } => {
+                handle_pre_receive_command(mode.map(Into::into))?;
+            }
+        },
+        Commands::Gate {
+            title,
+            base,
+            mode,
+            comment,
+        } => {
+            let range = match &cli.range {
+                Some(range) => range.clone(),
+                None => format!("{}...HEAD", base_branch(base)?),
+            };
+            let privacy = cli.privacy_level();
+            let (committor, diff_content) =
+                start_committor(&cli, move || get_diff_content(Some(&range), privacy)).await?;
+            let mode = mode.map(Into::into);
+            handle_gate_command(&committor, &title, &diff_content, mode, comment.as_deref())
+                .await?;
+        }
+        Commands::Eval { last } => {
+            let committor = create_committor(&cli).await?;
+            handle_eval_command(&committor, last).await?;
+        }
+        Commands::Index { last, rebuild } => {
+            let committor = create_committor(&cli).await?;
+            handle_index_command(&committor, last, rebuild).await?;
+        }
+        Commands::Badge {
+            file,
+            last,
+            format,
+            label,
+        } => {
+            let format = format.map_or_else(|| BadgeFormat::from_path(&file), Into::into);
+            handle_badge_command(&file, last, format, &label)?;
+        }
+        Commands::Lint { file } => {
+            handle_lint_command(file.as_deref())?;
+        }
+        Commands::Types => {
+            handle_types_command()?;
+        }
+        Commands::PromptHook { shell } => {
+            print!("{}", shell::prompt_hook(shell.into()));
+        }
+        Commands::PromptStatus => {
+            handle_prompt_status_command()?;
+        }
+        Commands::PreCommitHook {
+            stage,
+            file,
+            source,
+            timeout,
+        } => match stage {
+            PreCommitStageArg::PrepareCommitMsg => {
+                handle_prepare_commit_msg_command(&cli, &file, source.as_deref(), timeout).await?;
+            }
+            PreCommitStageArg::CommitMsg => {
+                handle_pre_commit_msg_stage(&file)?;
+            }
+        },
+        Commands::ExportPrompt { file } => {
+            let (committor, diff_content) = start_offline_committor(&mut cli).await?;
+            handle_export_prompt_command(&committor, diff_content, &file).await?;
+        }
+        Commands::ImportResponse { files } => {
+            let (committor, diff_content) = start_offline_committor(&mut cli).await?;
+            handle_import_response_command(&committor, &cli, &diff_content, &files)?;
+        }
+        Commands::ExportSession { file } => {
+            handle_export_session_command(&file)?;
+        }
+        Commands::Paths => {
+            handle_paths_command()?;
+        }
+        Commands::Init => {
+            handle_init_command(&cli).await?;
+        }
+        Commands::Audit { audit } => match audit {
+            AuditCommands::Show { last } => {
+                handle_audit_show_command(last)?;
+            }
+        },
+        Commands::Config { config } => match config {
+            ConfigCommands::Lint => {
+                handle_config_lint_command().await?;
+            }
+            ConfigCommands::Show { resolved } => {
+                handle_config_show_command(resolved, &matches)?;
+            }
+        },
+    }
+
+    if cli.timing {
+        eprint!("{}", timings.report(started.elapsed()));
+    }
+
+    if let Some(update_check) = update_check {
+        if let Ok(Some(latest)) = update_check.await {
+            let current = env!("CARGO_PKG_VERSION");
+            eprintln!(
+                "{}",
+                Text::UpdateAvailable.format(&[&latest, &current]).yellow()
+            );
+        }
     }
 
     Ok(())
 }
 
-async fn create_committor(cli: &Cli) -> Result<Committor> {
-    let config = match cli.provider {
-        AIProviderType::OpenAI => {
-            let api_key = cli
-                .api_key
-                .clone()
-                .or_else(|| env::var("OPENAI_API_KEY").ok())
-                .context(
-                    "OpenAI API key not found. Set OPENAI_API_KEY environment variable or use --api-key",
-                )?;
-
-            Config::with_openai(
-                api_key,
-                cli.model.clone(),
-                cli.count,
-                cli.auto_commit,
-                cli.show_diff,
-            )
-        }
-        AIProviderType::Ollama => {
-            // Check if Ollama is available
-            if !providers::check_ollama_availability(&cli.ollama_url).await? {
-                return Err(anyhow::anyhow!(
-                    "Ollama is not available at {}. Please make sure Ollama is running.",
-                    cli.ollama_url
-                ));
+/// Whether the command is a prepare-commit-msg hook, which must never fail a commit
+fn suggests_from_hook(cli: &Cli) -> bool {
+    matches!(
+        cli.command,
+        Some(
+            Commands::Hook {
+                hook: HookCommands::PrepareCommitMsg { .. }
+            } | Commands::PreCommitHook {
+                stage: PreCommitStageArg::PrepareCommitMsg,
+                ..
             }
+        )
+    )
+}
 
-            Config::with_ollama_timeout(
-                cli.ollama_url.clone(),
-                cli.model.clone(),
-                Duration::from_secs(cli.ollama_timeout),
-                cli.count,
-                cli.auto_commit,
-                cli.show_diff,
-            )
-        }
-    };
+/// Start the daily update check alongside the command, for interactive use only
+///
+/// Hooks run unattended, often on servers, so they never reach out for updates, and
+/// neither does the shell prompt integration, which runs with every prompt.
+fn spawn_update_check(cli: &Cli) -> Option<tokio::task::JoinHandle<Option<String>>> {
+    let unattended = matches!(
+        cli.command,
+        Some(
+            Commands::Hook { .. }
+                | Commands::PreCommitHook { .. }
+                | Commands::PromptHook { .. }
+                | Commands::PromptStatus
+        )
+    );
+    if cli.no_update_check || unattended || !std::io::stderr().is_terminal() {
+        return None;
+    }
 
-    Committor::new(config)
+    Some(tokio::spawn(async {
+        update::check_for_update(&reqwest::Client::new(), env!("CARGO_PKG_VERSION")).await
+    }))
 }
 
-async fn handle_generate_command(committor: &Committor, cli: &Cli) -> Result<()> {
-    let diff_content = committor.get_staged_diff()?;
-    if diff_content.is_empty() {
-        println!(
-            "{}",
-            "No staged changes found. Use 'git add' to stage changes first.".yellow()
-        );
-        return Ok(());
+/// Check whether a command calls a provider
+fn needs_provider(command: Option<&Commands>) -> bool {
+    matches!(
+        command,
+        None | Some(
+            Commands::Generate
+                | Commands::Commit { .. }
+                | Commands::Split
+                | Commands::Rewrite { .. }
+                | Commands::CherryPick { .. }
+                | Commands::Continue
+                | Commands::Models
+                | Commands::Review { .. }
+                | Commands::StashMsg { .. }
+                | Commands::From { .. }
+                | Commands::Tag { .. }
+                | Commands::Gate { .. }
+                | Commands::Eval { .. }
+                | Commands::Index { .. }
+        )
+    )
+}
+
+/// Pick a provider when none was chosen: OpenAI when an API key is set, otherwise a running Ollama
+async fn detect_provider(cli: &Cli) -> Result<Option<AIProviderType>> {
+    if cli.api_key.is_some() {
+        return Ok(Some(AIProviderType::OpenAI));
+    }
+    let http = reqwest::Client::new();
+    if providers::check_ollama_availability_with_client(&http, &cli.ollama_url).await? {
+        return Ok(Some(AIProviderType::Ollama));
     }
+    Ok(None)
+}
 
-    if cli.show_diff {
-        println!("{}", "Current staged diff:".cyan().bold());
-        println!("{diff_content}");
-        println!("{}", "─".repeat(80).cyan());
+/// Explain how to get going when no provider could be found, offering to run `init`
+async fn handle_first_run(cli: &Cli) -> Result<()> {
+    println!("{}", "No AI provider is set up yet.".yellow().bold());
+    println!("committor needs an OpenAI API key or a running Ollama server:");
+    println!("  - OpenAI: export OPENAI_API_KEY=<your key>");
+    println!(
+        "  - Ollama: install it from https://ollama.com and start it (looked for it at {})",
+        cli.ollama_url
+    );
+
+    let interactive = std::io::stdin().is_terminal() && std::io::stdout().is_terminal();
+    if interactive && !cli.auto_commit && ask_yes_no("Set one up now with `committor init`?")? {
+        return handle_init_command(cli).await;
     }
 
-    info!("Generating commit messages...");
-    let messages = committor.generate_commit_messages(&diff_content).await?;
+    Err(CommittorError::AIProviderError(
+        "No AI provider configured. Run `committor init` to set one up.".to_string(),
+    )
+    .into())
+}
 
-    commit::display_commit_options(&messages);
+/// Ask a yes/no question, where pressing enter means yes and end of input means no
+fn ask_yes_no(question: &str) -> Result<bool> {
+    print!("{question} [Y/n]: ");
+    std::io::stdout().flush()?;
+    Ok(commit::read_input()?.is_some_and(|answer| {
+        answer.is_empty() || answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes")
+    }))
+}
 
-    if cli.auto_commit && !messages.is_empty() {
-        committor.commit_with_message(&messages[0])?;
+async fn handle_init_command(cli: &Cli) -> Result<()> {
+    let http = reqwest::Client::new();
+    let has_api_key = cli.api_key.is_some();
+    let ollama_running =
+        providers::check_ollama_availability_with_client(&http, &cli.ollama_url).await?;
+
+    println!("{}", "Which provider should committor use?".cyan().bold());
+    println!(
+        "  1. OpenAI {}",
+        if has_api_key {
+            "(API key found)".to_string()
+        } else {
+            "(needs an API key)".to_string()
+        }
+    );
+    println!(
+        "  2. Ollama {}",
+        if ollama_running {
+            format!("(running at {})", cli.ollama_url)
+        } else {
+            format!("(not running at {})", cli.ollama_url)
+        }
+    );
+    print!("Provider [1-2]: ");
+    std::io::stdout().flush()?;
+
+    let (provider, model) = match commit::read_input()?.as_deref() {
+        Some("1") => ("openai", "gpt-4".to_string()),
+        Some("2") => (
+            "ollama",
+            choose_ollama_model(&http, cli, ollama_running).await?,
+        ),
+        Some(other) => return Err(anyhow::anyhow!("Expected 1 or 2, got '{other}'")),
+        None => return Ok(()),
+    };
+
+    println!();
+    println!("{}", "Add these lines to your shell profile:".cyan().bold());
+    println!("  export COMMITTOR_PROVIDER={provider}");
+    println!("  export COMMITTOR_MODEL={model}");
+    if provider == "openai" && !has_api_key {
+        println!("  export OPENAI_API_KEY=<your key from https://platform.openai.com/api-keys>");
     }
 
-    Ok(())
+    create_starter_settings()
 }
 
-async fn handle_commit_command(committor: &Committor, cli: &Cli) -> Result<()> {
-    let diff_content = committor.get_staged_diff()?;
-    if diff_content.is_empty() {
+/// Let the user pick one of the installed Ollama models, suggesting one to pull when there are none
+async fn choose_ollama_model(
+    http: &reqwest::Client,
+    cli: &Cli,
+    ollama_running: bool,
+) -> Result<String> {
+    const SUGGESTED_MODEL: &str = "llama3.2";
+
+    if !ollama_running {
         println!(
             "{}",
-            "No staged changes found. Use 'git add' to stage changes first.".yellow()
+            "Install Ollama from https://ollama.com, start it, then pull a model:".yellow()
         );
-        return Ok(());
+        println!("  ollama pull {SUGGESTED_MODEL}");
+        return Ok(SUGGESTED_MODEL.to_string());
     }
 
-    if cli.show_diff {
-        println!("{}", "Current staged diff:".cyan().bold());
-        println!("{diff_content}");
-        println!("{}", "─".repeat(80).cyan());
+    let models = providers::get_ollama_models_with_client(http, &cli.ollama_url).await?;
+    if models.is_empty() {
+        println!("{}", "No models are installed yet. Pull one with:".yellow());
+        println!("  ollama pull {SUGGESTED_MODEL}");
+        return Ok(SUGGESTED_MODEL.to_string());
     }
 
-    info!("Generating commit messages...");
-    let messages = committor.generate_commit_messages(&diff_content).await?;
-
-    if cli.auto_commit && !messages.is_empty() {
-        committor.commit_with_message(&messages[0])?;
-    } else if !messages.is_empty() {
-        commit::display_commit_options(&messages);
-        let choice = commit::prompt_user_choice(messages.len())?;
-        if let Some(index) = choice {
-            committor.commit_with_message(&messages[index])?;
-        } else {
-            println!("{}", "Commit cancelled.".yellow());
-        }
-    } else {
-        warn!("No commit messages were generated");
+    println!("{}", "Which model?".cyan().bold());
+    for (i, model) in models.iter().enumerate() {
+        println!("  {} {model}", output::item_label(i + 1));
     }
+    print!("Model [1]: ");
+    std::io::stdout().flush()?;
 
-    Ok(())
+    let input = commit::read_input()?.unwrap_or_default();
+    let index = if input.is_empty() {
+        0
+    } else {
+        match input.parse::<usize>() {
+            Ok(n) if (1..=models.len()).contains(&n) => n - 1,
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Expected a number from 1 to {}",
+                    models.len()
+                ))
+            }
+        }
+    };
+    Ok(models[index].clone())
 }
 
-fn handle_diff_command() -> Result<()> {
-    use committor::diff;
+/// Offer to write the default settings to the repository's settings file, if it has none
+fn create_starter_settings() -> Result<()> {
+    let Some(path) = settings::settings_path() else {
+        return Ok(());
+    };
+    if path.exists() {
+        return Ok(());
+    }
 
-    let diff_content = diff::get_staged_diff()?;
-    if diff_content.is_empty() {
-        println!("{}", "No staged changes found.".yellow());
-    } else {
-        println!("{diff_content}");
+    println!();
+    if !ask_yes_no(&format!(
+        "Create {} with the default rules?",
+        path.display()
+    ))? {
+        return Ok(());
     }
+    fs::write(&path, toml::to_string_pretty(&Settings::default())?)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    println!(
+        "{}",
+        format!("{} Created {}", output::success_mark(), path.display()).green()
+    );
     Ok(())
 }
 
-async fn handle_models_command(cli: &Cli) -> Result<()> {
-    match cli.provider {
+async fn create_committor(cli: &Cli) -> Result<Committor> {
+    let (committor, ()) = start_committor(cli, || Ok(())).await?;
+    Ok(committor)
+}
+
+/// Create the committor while `prefetch` runs on a blocking thread
+///
+/// The settings load, the provider health check and the prefetch (usually the
+/// diff) all overlap, so Ollama's round trip no longer adds to startup time.
+async fn start_committor<T, F>(cli: &Cli, prefetch: F) -> Result<(Committor, T)>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    let config = match cli.provider() {
         AIProviderType::OpenAI => {
-            println!("{}", "Available OpenAI models:".green().bold());
-            let models = vec!["gpt-4", "gpt-4-turbo", "gpt-3.5-turbo", "gpt-3.5-turbo-16k"];
-            for model in models {
-                println!("  {model}");
-            }
-        }
-        AIProviderType::Ollama => {
-            if !providers::check_ollama_availability(&cli.ollama_url).await? {
-                return Err(anyhow::anyhow!(
-                    "Ollama is not available at {}. Please make sure Ollama is running.",
-                    cli.ollama_url
-                ));
-            }
+            let api_key = cli
+                .api_key
+                .clone()
+                .or_else(|| env::var("OPENAI_API_KEY").ok())
+                .or_else(|| {
+                    let url = cli.openai_url.as_deref().unwrap_or(credentials::OPENAI_API_URL);
+                    credentials::fill_credential(url).map(|credential| credential.password)
+                })
+                .context(
+                    "OpenAI API key not found. Set OPENAI_API_KEY environment variable, use --api-key or store it in git's credential helper",
+                )?;
+
+            let mut config = Config::with_openai(
+                api_key.clone(),
+                cli.model.clone(),
+                cli.count,
+                cli.auto_commit,
+                cli.show_diff.is_some(),
+            );
+            if let Some(base_url) = &cli.openai_url {
+                config.provider_config = providers::ProviderConfig::openai_with_url(
+                    api_key,
+                    cli.model.clone(),
+                    base_url.clone(),
+                );
+            }
+            config
+        }
+        AIProviderType::Ollama => Config::with_ollama_timeout(
+            cli.ollama_url.clone(),
+            cli.model.clone(),
+            Duration::from_secs(cli.ollama_timeout),
+            cli.count,
+            cli.auto_commit,
+            cli.show_diff.is_some(),
+        ),
+    };
+    let http = config.provider_config.http_client()?;
+
+    let preflight = async {
+        if cli.no_preflight {
+            return Ok(true);
+        }
+        config.provider_config.check_availability(&http).await
+    };
+    let (available, settings, prefetched) = tokio::join!(
+        preflight,
+        tokio::task::spawn_blocking(Settings::discover),
+        tokio::task::spawn_blocking(prefetch),
+    );
+
+    if !available? {
+        return Err(CommittorError::OllamaUnavailable(cli.ollama_url.clone()).into());
+    }
+
+    let mut settings = settings??;
+    settings.audit.enabled |= cli.audit;
+    if let Some(language) = &cli.subject_language {
+        settings.language.subject = Some(language.clone());
+    }
+    if let Some(language) = &cli.body_language {
+        settings.language.body = Some(language.clone());
+    }
+    let temperature = command_sampling(cli.command.as_ref(), &settings).temperature;
+
+    let committor = Committor::with_client(
+        config
+            .with_temperature(temperature)
+            .with_settings(settings)
+            .with_format(cli.format.clone())
+            .with_privacy(cli.privacy_level())
+            .with_body(cli.with_body),
+        http,
+    )?;
+
+    Ok((committor, prefetched??))
+}
+
+/// Create the committor for the air-gapped commands, which never contact a provider
+///
+/// The prompt doesn't depend on the provider, so Ollama's settings stand in: they
+/// need no API key, and the availability check is skipped.
+async fn start_offline_committor(cli: &mut Cli) -> Result<(Committor, String)> {
+    if cli.range.is_some() {
+        return Err(anyhow::anyhow!(
+            "Exported prompts describe the staged changes and cannot be used with --range"
+        ));
+    }
+    cli.provider = Some(AIProviderType::Ollama);
+    cli.no_preflight = true;
+    start_committor(cli, || Ok(diff::get_staged_snapshot()?.diff)).await
+}
+
+/// Get the settings section holding a command's sampling, `[generate]` unless it has its own
+fn command_sampling<'a>(
+    command: Option<&Commands>,
+    settings: &'a Settings,
+) -> &'a SamplingSettings {
+    match command {
+        Some(Commands::Tag { .. }) => &settings.tag,
+        Some(Commands::Review { .. }) => &settings.review,
+        _ => &settings.generate,
+    }
+}
+
+/// Get the branch given with `--base`, or the remote's default branch
+fn base_branch(base: Option<String>) -> Result<String> {
+    match base {
+        Some(base) => Ok(base),
+        None => committor::base::default_base_branch(),
+    }
+}
+
+/// Get the diff to work from: the requested range, or the staged changes
+fn get_diff_content(range: Option<&str>, privacy: PrivacyLevel) -> Result<String> {
+    match range {
+        Some(_) if !privacy.sends_content() => Err(anyhow::anyhow!(
+            "The paths-only privacy level works on staged changes and cannot be used with --range"
+        )),
+        Some(range) => diff::get_range_diff(range),
+        None if commit::is_bare_repository()? => Err(anyhow::anyhow!(
+            "Bare repositories have no staged changes. Use --range to select commits."
+        )),
+        None => Ok(diff::get_staged_snapshot()?.diff),
+    }
+}
+
+async fn handle_generate_command(
+    committor: &Committor,
+    cli: &Cli,
+    diff_content: String,
+) -> Result<()> {
+    if diff_content.is_empty() {
+        match &cli.range {
+            Some(range) => println!("{}", format!("No changes found in {range}.").yellow()),
+            None => println!("{}", Text::NoStagedChangesHint.text().yellow()),
+        }
+        return Err(ExitStatus::NoStagedChanges.into());
+    }
+
+    if let Some(mode) = cli.show_diff {
+        match &cli.range {
+            Some(range) => {
+                show_changes(mode, &format!("Changes in {range}:"), &diff_content, || {
+                    diff::get_range_changes(range)
+                })?
+            }
+            None => show_changes(mode, "Current staged changes:", &diff_content, || {
+                Ok(diff::get_staged_snapshot()?.changes)
+            })?,
+        }
+    }
+
+    commit::display_vendored_warning(&vendored::detect_vendored(&diff_content));
+    info!("Generating commit messages...");
+    let messages = committor.generate_commit_messages(&diff_content).await?;
+    if cli.suggest_tests {
+        let changes = match &cli.range {
+            Some(range) => diff::get_range_changes(range)?,
+            None => diff::get_staged_snapshot()?.changes,
+        };
+        display_test_suggestions(committor, &diff_content, &changes).await;
+    }
+    if cli.range.is_none() {
+        save_last_messages(committor, &diff_content, &messages);
+    }
+    let changes = match &cli.range {
+        Some(range) => diff::get_range_changes(range),
+        None => diff::get_staged_changes(),
+    };
+    start_session(committor, "generate", changes, &messages);
+
+    let previous_subject = committor.previous_commit_subject();
+    commit::display_commit_options_with_details(
+        &messages,
+        previous_subject.as_deref(),
+        &committor.recent_history(),
+        cli.verbose,
+        false,
+    );
+    commit::display_spelling_warnings(&committor.spelling_warnings(&messages));
+
+    if cli.auto_commit && !messages.is_empty() && cli.range.is_none() {
+        commit_chosen(committor, &messages[0])?;
+    }
+
+    Ok(())
+}
+
+/// Print suggested test cases for untested source changes; a failure only warns
+async fn display_test_suggestions(
+    committor: &Committor,
+    diff_content: &str,
+    changes: &[DiffChange],
+) {
+    match committor.suggest_tests(diff_content, changes).await {
+        Ok(Some(gap)) => test_gap::display_test_gap(&gap),
+        Ok(None) => {}
+        Err(e) => warn!("Could not suggest test cases: {e}"),
+    }
+}
+
+/// Print the changes about to be described, as a per-file summary or the whole patch
+fn show_changes(
+    mode: ShowDiffArg,
+    heading: &str,
+    diff_content: &str,
+    changes: impl FnOnce() -> Result<Vec<DiffChange>>,
+) -> Result<()> {
+    println!("{}", heading.cyan().bold());
+    match mode {
+        ShowDiffArg::Summary => print!("{}", diff::format_diff_summary(&changes()?)),
+        ShowDiffArg::Full => println!("{diff_content}"),
+    }
+    println!("{}", output::separator().cyan());
+    Ok(())
+}
+
+/// Parse `--choose`: a 1-based option number, or `best` for the top-ranked option
+fn parse_choose(value: &str) -> std::result::Result<usize, String> {
+    if value.eq_ignore_ascii_case("best") {
+        // Suggestions are ranked best first
+        return Ok(1);
+    }
+    match value.parse::<usize>() {
+        Ok(n) if n >= 1 => Ok(n),
+        _ => Err(format!(
+            "expected a positive number or 'best', got '{value}'"
+        )),
+    }
+}
+
+/// Start the session `export-session` writes out; failing to is not worth an error
+fn start_session(
+    committor: &Committor,
+    command: &str,
+    changes: Result<Vec<DiffChange>>,
+    messages: &[String],
+) {
+    let provider = committor.provider_config();
+    let started = changes.and_then(|changes| {
+        session::save_session(&Session::new(
+            command,
+            provider.name(),
+            provider.model(),
+            diff::DiffSummary::new(changes),
+            messages,
+        ))
+    });
+    if let Err(e) = started {
+        warn!("Could not save the session: {e}");
+    }
+}
+
+/// Change the session `export-session` writes out; failing to is not worth an error
+fn update_session(change: impl FnOnce(&mut Session)) {
+    if let Err(e) = session::update_session(change) {
+        warn!("Could not save the session: {e}");
+    }
+}
+
+/// Add the run's timings to the session it started, if it got that far
+fn finish_session(timings: &Timings, started: Instant) {
+    update_session(|session| {
+        if session.total_ms.is_none() {
+            session.finish(timings, started.elapsed());
+        }
+    });
+}
+
+/// Commit the chosen message and note it in the session
+fn commit_chosen(committor: &Committor, message: &str) -> Result<()> {
+    committor.commit_with_message(message)?;
+    update_session(|session| session.chosen = Some(message.to_string()));
+    Ok(())
+}
+
+/// Remember generated messages for `commit --use-last`; failing to is not worth an error
+fn save_last_messages(committor: &Committor, diff: &str, messages: &[String]) {
+    if let Err(e) = committor.save_last_messages(diff, messages) {
+        warn!("Could not save the generated messages: {}", e);
+    }
+}
+
+async fn handle_commit_command(
+    committor: &Committor,
+    cli: &Cli,
+    diff_content: String,
+    use_last: bool,
+    choose: Option<usize>,
+) -> Result<()> {
+    if cli.range.is_some() {
+        return Err(anyhow::anyhow!(
+            "The commit command works on staged changes and cannot be used with --range"
+        ));
+    }
+
+    if diff_content.is_empty() {
+        println!("{}", Text::NoStagedChangesHint.text().yellow());
+        return Err(ExitStatus::NoStagedChanges.into());
+    }
+
+    if let Some(mode) = cli.show_diff {
+        show_changes(mode, "Current staged changes:", &diff_content, || {
+            Ok(diff::get_staged_snapshot()?.changes)
+        })?;
+    }
+
+    commit::display_vendored_warning(&vendored::detect_vendored(&diff_content));
+    let interactive = choose.is_none() && !cli.auto_commit;
+    let options = if use_last {
+        committor
+            .last_messages(&diff_content)?
+            .context(
+                "No saved suggestions for the staged changes. Run `committor generate` first.",
+            )?
+            .into()
+    } else {
+        info!("Generating commit messages...");
+        let options = generate_options(committor, cli, &diff_content, interactive).await?;
+        save_last_messages(committor, &diff_content, &options.messages);
+        options
+    };
+    let messages = &options.messages;
+    start_session(committor, "commit", diff::get_staged_changes(), messages);
+    if cli.suggest_tests {
+        let changes = diff::get_staged_snapshot()?.changes;
+        display_test_suggestions(committor, &diff_content, &changes).await;
+    }
+
+    if messages.is_empty() {
+        warn!("No commit messages were generated");
+    } else if let Some(n) = choose {
+        let message = messages.get(n - 1).with_context(|| {
+            format!(
+                "Cannot choose option {n}: only {} messages were generated",
+                messages.len()
+            )
+        })?;
+        commit_chosen(committor, message)?;
+    } else if cli.auto_commit {
+        commit_chosen(committor, &messages[0])?;
+    } else {
+        pick_and_commit(committor, cli, &diff_content, options).await?;
+    }
+
+    Ok(())
+}
+
+/// Commit one generated message, or one written from the changed paths, without prompting
+///
+/// `deadline` ends the model's budget; without one no provider was found, so the
+/// message is written from the changed paths straight away.
+async fn handle_quick_commit_command(
+    committor: &Committor,
+    cli: &Cli,
+    diff_content: String,
+    deadline: Option<tokio::time::Instant>,
+) -> Result<()> {
+    if cli.range.is_some() {
+        return Err(anyhow::anyhow!(
+            "The commit command works on staged changes and cannot be used with --range"
+        ));
+    }
+
+    if diff_content.is_empty() {
+        println!("{}", Text::NoStagedChangesHint.text().yellow());
+        return Err(ExitStatus::NoStagedChanges.into());
+    }
+
+    let (message, generated) = match deadline {
+        Some(deadline) => {
+            committor
+                .generate_quick_message_until(&diff_content, deadline)
+                .await?
+        }
+        None => (committor.paths_message()?, false),
+    };
+    start_session(
+        committor,
+        "commit",
+        diff::get_staged_changes(),
+        std::slice::from_ref(&message),
+    );
+    if !generated {
+        let violations = committor
+            .rule_set()
+            .check(&committor.add_trailers(&message)?);
+        if !violations.is_empty() {
+            println!("{message}");
+            let reasons: Vec<String> = violations.into_iter().map(|v| v.message).collect();
+            return Err(CommittorError::ValidationFailed(
+                Text::QuickFallbackBreaksRules.format(&[&reasons.join("; ")]),
+            )
+            .into());
+        }
+        println!(
+            "{}",
+            theme::paint(Text::QuickFallbackCommit.text(), Element::Warning)
+        );
+    }
+    commit_chosen(committor, &message)
+}
+
+/// Generate the options to commit from; for the picker, only wait as long as the latency budget allows
+async fn generate_options(
+    committor: &Committor,
+    cli: &Cli,
+    diff_content: &str,
+    interactive: bool,
+) -> Result<PartialMessages> {
+    match cli.latency_budget {
+        Some(budget) if interactive => {
+            committor
+                .generate_commit_messages_within(diff_content, Duration::from_secs(budget))
+                .await
+        }
+        _ => Ok(committor
+            .generate_commit_messages(diff_content)
+            .await?
+            .into()),
+    }
+}
+
+/// Show the picker until the user commits, edits, or cancels, regenerating on request
+async fn pick_and_commit(
+    committor: &Committor,
+    cli: &Cli,
+    diff_content: &str,
+    mut options: PartialMessages,
+) -> Result<()> {
+    let mut diff_content = diff_content.to_string();
+    let previous_subject = committor.previous_commit_subject();
+    let history = committor.recent_history();
+    let scopes = committor.suggest_scopes()?;
+    // Staged files the user left out of the context; they are still committed
+    let mut excluded: Vec<String> = Vec::new();
+
+    loop {
+        let messages = &options.messages;
+        let has_bodies = commit::has_bodies(messages);
+        commit::display_commit_options_with_details(
+            messages,
+            previous_subject.as_deref(),
+            &history,
+            cli.verbose,
+            has_bodies,
+        );
+        commit::display_spelling_warnings(&committor.spelling_warnings(messages));
+        commit::display_scope_options(&scopes);
+        if !options.is_complete() {
+            let note = Text::PartialOptions.format(&[&messages.len(), &options.requested]);
+            println!("{}", note.dimmed());
+        }
+        if !excluded.is_empty() {
+            let note = Text::ExcludedFilesNote.format(&[&excluded.join(", ")]);
+            println!("{}", note.dimmed());
+        }
+
+        match commit::prompt_picker_action(messages.len(), scopes.len(), has_bodies, cli.feedback)?
+        {
+            PickerAction::Commit(choice) => {
+                let message = match choice.scope {
+                    Some(scope) => commit::with_scope(&messages[choice.index], &scopes[scope]),
+                    None => messages[choice.index].clone(),
+                };
+                match recheck_staged_changes(committor, &diff_content)? {
+                    StagedCheck::Commit => {
+                        if cli.feedback {
+                            record_feedback(&messages[choice.index], Rating::Up);
+                        }
+                        return commit_chosen(committor, &message);
+                    }
+                    StagedCheck::Regenerate(current) => diff_content = current,
+                }
+            }
+            PickerAction::Edit(index) => {
+                let Some(message) = commit::edit_message(&messages[index])? else {
+                    println!("{}", Text::EmptyMessageCancelled.text().yellow());
+                    return Err(ExitStatus::Cancelled.into());
+                };
+                match recheck_staged_changes(committor, &diff_content)? {
+                    StagedCheck::Commit => return commit_chosen(committor, &message),
+                    StagedCheck::Regenerate(current) => diff_content = current,
+                }
+            }
+            PickerAction::Regenerate => {}
+            PickerAction::More => {
+                if !options.is_complete() {
+                    info!("Waiting for the remaining options...");
+                    committor.wait_for_more(&mut options).await?;
+                    save_last_messages(committor, &diff_content, &options.messages);
+                    let messages = options.messages.clone();
+                    update_session(|session| {
+                        if let Some(round) = session.rounds.last_mut() {
+                            *round = messages;
+                        }
+                    });
+                }
+                continue;
+            }
+            PickerAction::View(index) => {
+                commit::display_option_preview(&messages[index], index + 1);
+                continue;
+            }
+            PickerAction::Rate(index, rating) => {
+                if record_feedback(&messages[index], rating) {
+                    let note = Text::FeedbackRecorded.format(&[&(index + 1)]);
+                    println!("{}", note.dimmed());
+                }
+                continue;
+            }
+            PickerAction::Exclude => {
+                let paths = diff::diff_paths(&diff_content);
+                match commit::prompt_excluded_files(&paths, &excluded)? {
+                    Some(updated) => excluded = updated,
+                    None => continue,
+                }
+            }
+            PickerAction::Cancel => {
+                println!("{}", Text::CommitCancelled.text().yellow());
+                return Err(ExitStatus::Cancelled.into());
+            }
+        }
+
+        // Reached on request, when files were left out, or when the changes staged since call for new options
+        info!("Regenerating commit messages...");
+        let context = committor.excluding(excluded.clone());
+        let regenerated = generate_options(&context, cli, &diff_content, true).await?;
+        if regenerated.messages.is_empty() {
+            warn!("No commit messages were generated; keeping the previous options");
+        } else {
+            save_last_messages(committor, &diff_content, &regenerated.messages);
+            let messages = regenerated.messages.clone();
+            update_session(|session| session.rounds.push(messages));
+            options = regenerated;
+        }
+    }
+}
+
+/// Record a rating in the feedback log, warning rather than failing when it can't be written
+fn record_feedback(message: &str, rating: Rating) -> bool {
+    match feedback::record(message, rating) {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("Failed to record feedback: {e:#}");
+            false
+        }
+    }
+}
+
+/// What to do with a chosen message once the staged changes have been checked again
+enum StagedCheck {
+    /// The changes are as generated for, or the user chose to commit anyway
+    Commit,
+    /// Generate new options for this diff, the one staged now
+    Regenerate(String),
+}
+
+/// Check that the staged changes still match the diff the options were generated for
+///
+/// Fails with the exit status to stop with when the user cancels or nothing is staged anymore.
+fn recheck_staged_changes(committor: &Committor, diff_content: &str) -> Result<StagedCheck> {
+    let Some(current) = committor.staged_diff_if_changed(diff_content)? else {
+        return Ok(StagedCheck::Commit);
+    };
+    Ok(match commit::prompt_stale_action()? {
+        StaleAction::CommitAnyway => StagedCheck::Commit,
+        StaleAction::Regenerate if current.is_empty() => {
+            println!("{}", Text::NoStagedChangesHint.text().yellow());
+            return Err(ExitStatus::NoStagedChanges.into());
+        }
+        StaleAction::Regenerate => StagedCheck::Regenerate(current),
+        StaleAction::Cancel => {
+            println!("{}", Text::CommitCancelled.text().yellow());
+            return Err(ExitStatus::Cancelled.into());
+        }
+    })
+}
+
+/// Print the summary of a run over many files or commits and end it with its status
+///
+/// The summary is only printed when an item failed or was skipped.
+fn finish_outcomes(outcomes: &Outcomes, result: Result<()>, succeeded_label: &str) -> Result<()> {
+    if outcomes.needs_summary() {
+        outcomes.display_summary(succeeded_label);
+    }
+    result?;
+    outcomes.finish()
+}
+
+async fn handle_per_file_commit_command(
+    committor: &Committor,
+    cli: &Cli,
+    continue_on_error: bool,
+) -> Result<()> {
+    if cli.range.is_some() {
+        return Err(anyhow::anyhow!(
+            "The commit command works on staged changes and cannot be used with --range"
+        ));
+    }
+
+    let mut outcomes = Outcomes::new(continue_on_error);
+    let result = commit_per_file(committor, cli, &mut outcomes).await;
+    finish_outcomes(&outcomes, result, "committed")
+}
+
+async fn commit_per_file(committor: &Committor, cli: &Cli, outcomes: &mut Outcomes) -> Result<()> {
+    info!("Generating per-file commit messages...");
+    let suggestions = committor.generate_file_suggestions(outcomes).await?;
+    if suggestions.is_empty() {
+        if !outcomes.items().is_empty() {
+            return Ok(());
+        }
+        println!("{}", Text::NoStagedChangesHint.text().yellow());
+        return Err(ExitStatus::NoStagedChanges.into());
+    }
+
+    let selected = if cli.auto_commit {
+        (0..suggestions.len()).collect()
+    } else {
+        split::display_file_suggestions(&suggestions);
+        match split::prompt_selection(suggestions.len())? {
+            Some(selected) => selected,
+            None => {
+                println!("{}", Text::CommitCancelled.text().yellow());
+                return Err(ExitStatus::Cancelled.into());
+            }
+        }
+    };
+
+    for (i, suggestion) in suggestions.iter().enumerate() {
+        if !selected.contains(&i) {
+            outcomes.skipped(&suggestion.path, "not selected");
+        }
+    }
+    let selected: Vec<_> = selected
+        .into_iter()
+        .map(|i| suggestions[i].clone())
+        .collect();
+    if !cli.auto_commit {
+        let hunks = committor.get_staged_hunks()?;
+        split::display_split_plan(&split::plan_for_files(&selected, &hunks), &hunks);
+        if !split::prompt_confirmation()? {
+            println!("{}", Text::CommitCancelled.text().yellow());
+            return Err(ExitStatus::Cancelled.into());
+        }
+    }
+
+    for suggestion in &selected {
+        match committor.commit_file_with_message(&suggestion.path, &suggestion.message) {
+            Ok(()) => outcomes.succeeded(&suggestion.path),
+            Err(e) => outcomes.failed(&suggestion.path, e)?,
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_split_command(committor: &Committor, cli: &Cli) -> Result<()> {
+    if cli.range.is_some() {
+        return Err(anyhow::anyhow!(
+            "The split command works on staged changes and cannot be used with --range"
+        ));
+    }
+
+    let hunks = committor.get_staged_hunks()?;
+    if hunks.is_empty() {
+        println!("{}", Text::NoStagedChangesHint.text().yellow());
+        return Err(ExitStatus::NoStagedChanges.into());
+    }
+
+    info!("Planning split of {} hunks...", hunks.len());
+    let plan = committor.plan_split(&hunks).await?;
+    split::display_split_plan(&plan, &hunks);
+
+    if !cli.auto_commit && !split::prompt_confirmation()? {
+        println!("{}", Text::SplitCancelled.text().yellow());
+        return Err(ExitStatus::Cancelled.into());
+    }
+    committor.execute_split(&plan, &hunks)
+}
+
+fn handle_restore_command(name: Option<&str>, list: bool) -> Result<()> {
+    let backups = backup::list_backups()?;
+    if backups.is_empty() {
+        println!("{}", "No backups to restore.".yellow());
+        return Ok(());
+    }
+
+    if list {
+        for backup in &backups {
+            println!(
+                "{}  {}  {} {}",
+                backup.short_name().cyan(),
+                report::format_date(backup.created).dimmed(),
+                &backup.commit[..7],
+                backup.subject
+            );
+        }
+        return Ok(());
+    }
+
+    let Some(target) = backup::find_backup(&backups, name) else {
+        return Err(anyhow::anyhow!(
+            "No backup named {}; `committor restore --list` shows them",
+            name.unwrap_or_default()
+        ));
+    };
+    let replaced = backup::restore_backup(target)?;
+    println!(
+        "{} {} {}",
+        format!("{} Restored", output::success_mark())
+            .green()
+            .bold(),
+        &target.commit[..7],
+        target.subject
+    );
+    if let Some(replaced) = replaced {
+        println!(
+            "{}",
+            format!(
+                "The previous HEAD is kept as backup {}",
+                replaced.short_name()
+            )
+            .dimmed()
+        );
+    }
+    Ok(())
+}
+
+async fn handle_rewrite_command(
+    committor: &Committor,
+    cli: &Cli,
+    range: &str,
+    dry_run: bool,
+    outcomes: &mut Outcomes,
+) -> Result<()> {
+    info!("Generating messages for the commits in {range}...");
+    let plan = committor.plan_rewrite(range, outcomes).await?;
+    rewrite::display_rewrite(&plan);
+
+    if dry_run {
+        return Ok(());
+    }
+    if !cli.auto_commit && !rewrite::prompt_confirmation()? {
+        println!("{}", Text::RewriteCancelled.text().yellow());
+        return Err(ExitStatus::Cancelled.into());
+    }
+    committor.apply_rewrite(&plan)
+}
+
+async fn handle_cherry_pick_command(
+    committor: &Committor,
+    cli: &Cli,
+    pick: &cherry_pick::CherryPick,
+) -> Result<()> {
+    info!("Generating messages for the cherry-picked commit...");
+    let messages = committor.generate_cherry_pick_messages(pick).await?;
+
+    let choice = if cli.auto_commit {
+        Some(0)
+    } else {
+        println!("{}", "Original message:".bold());
+        println!("{}", pick.original);
+        println!();
+        commit::display_commit_options_with_details(
+            &messages,
+            committor.previous_commit_subject().as_deref(),
+            &committor.recent_history(),
+            cli.verbose,
+            commit::has_bodies(&messages),
+        );
+        commit::display_spelling_warnings(&committor.spelling_warnings(&messages));
+        commit::prompt_user_choice(messages.len())?
+    };
+    let Some(choice) = choice else {
+        println!("{}", Text::CommitCancelled.text().yellow());
+        return Err(ExitStatus::Cancelled.into());
+    };
+    committor.cherry_pick_with_message(pick, &messages[choice])
+}
+
+async fn handle_continue_command(
+    committor: &Committor,
+    cli: &Cli,
+    resolution: &conflicts::Resolution,
+) -> Result<()> {
+    if !resolution.conflicts.is_empty() {
+        let note = format!("Resolved conflicts in {}", resolution.conflicts.join(", "));
+        println!("{}", note.dimmed());
+    }
+    info!("Generating messages for the resolved commit...");
+    let messages = committor.generate_conflict_messages(resolution).await?;
+
+    let choice = if cli.auto_commit {
+        Some(0)
+    } else {
+        println!("{}", "Original message:".bold());
+        println!("{}", resolution.original);
+        println!();
+        commit::display_commit_options_with_details(
+            &messages,
+            committor.previous_commit_subject().as_deref(),
+            &committor.recent_history(),
+            cli.verbose,
+            commit::has_bodies(&messages),
+        );
+        commit::display_spelling_warnings(&committor.spelling_warnings(&messages));
+        commit::prompt_user_choice(messages.len())?
+    };
+    let Some(choice) = choice else {
+        println!("{}", Text::CommitCancelled.text().yellow());
+        return Err(ExitStatus::Cancelled.into());
+    };
+    committor.continue_with_message(resolution, &messages[choice])
+}
+
+async fn handle_review_command(committor: &Committor) -> Result<()> {
+    let hunks = committor.get_staged_hunks()?;
+    if hunks.is_empty() {
+        println!("{}", Text::NoStagedChangesHint.text().yellow());
+        return Err(ExitStatus::NoStagedChanges.into());
+    }
+
+    info!("Reviewing {} hunks...", hunks.len());
+    let comments = committor.review_hunks(&hunks).await?;
+    review::display_review(&comments, &hunks);
+    Ok(())
+}
+
+async fn handle_stash_msg_command(committor: &Committor, cli: &Cli, dry_run: bool) -> Result<()> {
+    let diff_content = committor.get_working_tree_diff()?;
+    if diff_content.is_empty() {
+        println!("{}", "No local changes to stash.".yellow());
+        return Err(ExitStatus::NoStagedChanges.into());
+    }
+
+    if let Some(mode) = cli.show_diff {
+        show_changes(
+            mode,
+            "Current working tree changes:",
+            &diff_content,
+            diff::get_working_tree_changes,
+        )?;
+    }
+
+    info!("Generating stash message...");
+    let message = committor.generate_stash_message(&diff_content).await?;
+
+    if dry_run {
+        println!("{message}");
+    } else {
+        committor.wait_for_index_lock()?;
+        commit::stash_with_message(&message)?;
+    }
+
+    Ok(())
+}
+
+async fn handle_from_command(committor: &Committor, cli: &Cli, description: &str) -> Result<()> {
+    let description = if description == "-" {
+        let mut input = String::new();
+        std::io::stdin()
+            .read_to_string(&mut input)
+            .context("Failed to read the description from stdin")?;
+        input
+    } else {
+        description.to_string()
+    };
+    if description.trim().is_empty() {
+        return Err(anyhow::anyhow!("The description is empty"));
+    }
+
+    info!("Generating commit messages from the description...");
+    let messages = committor.generate_from_description(&description).await?;
+    commit::display_commit_options_with_details(
+        &messages,
+        committor.previous_commit_subject().as_deref(),
+        &committor.recent_history(),
+        cli.verbose,
+        false,
+    );
+    commit::display_spelling_warnings(&committor.spelling_warnings(&messages));
+
+    Ok(())
+}
+
+async fn handle_tag_command(committor: &Committor, name: &str, create: bool) -> Result<()> {
+    info!("Generating tag message...");
+    let message = committor.generate_tag_message(name).await?;
+
+    println!("{}", format!("Tag message for {name}:").green().bold());
+    println!();
+    println!("{message}");
+    println!();
+
+    if create {
+        tag::create_annotated_tag(name, &message)?;
+    }
+
+    Ok(())
+}
+
+fn handle_diff_command(cli: &Cli, stat: bool) -> Result<()> {
+    use committor::diff;
+
+    if stat {
+        let changes = match &cli.range {
+            Some(range) => diff::get_range_changes(range)?,
+            None => diff::get_staged_changes()?,
+        };
+        print_diff_stat(&changes);
+        return Ok(());
+    }
+
+    let diff_content = match &cli.range {
+        Some(range) => diff::get_range_diff(range)?,
+        None => diff::get_staged_diff()?,
+    };
+    if diff_content.is_empty() {
+        println!("{}", Text::ErrNoStagedChanges.text().yellow());
+    } else {
+        println!("{diff_content}");
+    }
+    Ok(())
+}
+
+fn handle_summary_command(cli: &Cli, json: bool, stat: bool) -> Result<()> {
+    if stat {
+        return handle_diff_command(cli, true);
+    }
+
+    let Some(range) = &cli.range else {
+        if json {
+            print_summary_json(diff::get_staged_changes()?)?;
+        } else {
+            print!("{}", diff::get_diff_summary()?);
+        }
+        return Ok(());
+    };
+
+    let changes = diff::get_range_changes(range)?;
+    if json {
+        print_summary_json(changes)?;
+    } else if changes.is_empty() {
+        println!("{}", format!("No changes found in {range}.").yellow());
+    } else {
+        print!(
+            "Changes in {range} ({} files):\n{}",
+            changes.len(),
+            diff::format_diff_summary(&changes)
+        );
+    }
+    Ok(())
+}
+
+/// Print the `--stat` graph with additions and deletions in their theme colors
+fn print_diff_stat(changes: &[DiffChange]) {
+    if changes.is_empty() {
+        println!("{}", Text::ErrNoStagedChanges.text().yellow());
+        return;
+    }
+
+    for line in diff::format_diff_stat(changes, diff::STAT_WIDTH).lines() {
+        match line.rsplit_once(' ') {
+            Some((head, bar))
+                if head.contains(" | ") && bar.chars().all(|c| c == '+' || c == '-') =>
+            {
+                let additions = bar.trim_end_matches('-');
+                let deletions = &bar[additions.len()..];
+                println!(
+                    "{head} {}{}",
+                    theme::paint(additions, Element::Added),
+                    theme::paint(deletions, Element::Deleted)
+                );
+            }
+            _ => println!("{line}"),
+        }
+    }
+}
+
+fn print_summary_json(changes: Vec<DiffChange>) -> Result<()> {
+    let summary = diff::DiffSummary::new(changes);
+    println!("{}", serde_json::to_string_pretty(&summary)?);
+    Ok(())
+}
+
+async fn handle_models_command(cli: &Cli) -> Result<()> {
+    match cli.provider() {
+        AIProviderType::OpenAI => {
+            println!("{}", "Available OpenAI models:".green().bold());
+            let models = vec!["gpt-4", "gpt-4-turbo", "gpt-3.5-turbo", "gpt-3.5-turbo-16k"];
+            for model in models {
+                println!("  {model}");
+            }
+        }
+        AIProviderType::Ollama => {
+            let http = reqwest::Client::new();
+            if !providers::check_ollama_availability_with_client(&http, &cli.ollama_url).await? {
+                return Err(CommittorError::OllamaUnavailable(cli.ollama_url.clone()).into());
+            }
 
             println!("{}", "Available Ollama models:".green().bold());
-            let models = providers::get_ollama_models(&cli.ollama_url).await?;
+            let models = providers::get_ollama_models_with_client(&http, &cli.ollama_url).await?;
             if models.is_empty() {
                 println!(
                     "  {}",
@@ -260,12 +2222,18 @@ async fn handle_check_ollama_command(cli: &Cli) -> Result<()> {
         format!("Checking Ollama availability at {}...", cli.ollama_url).cyan()
     );
 
-    match providers::check_ollama_availability(&cli.ollama_url).await {
+    let http = reqwest::Client::new();
+    match providers::check_ollama_availability_with_client(&http, &cli.ollama_url).await {
         Ok(true) => {
-            println!("{}", "✓ Ollama is available!".green().bold());
+            println!(
+                "{}",
+                format!("{} Ollama is available!", output::success_mark())
+                    .green()
+                    .bold()
+            );
 
             // Also show available models
-            match providers::get_ollama_models(&cli.ollama_url).await {
+            match providers::get_ollama_models_with_client(&http, &cli.ollama_url).await {
                 Ok(models) => {
                     if models.is_empty() {
                         println!(
@@ -286,16 +2254,647 @@ async fn handle_check_ollama_command(cli: &Cli) -> Result<()> {
             }
         }
         Ok(false) => {
-            println!("{}", "✗ Ollama is not available".red().bold());
+            println!(
+                "{}",
+                format!("{} Ollama is not available", output::failure_mark())
+                    .red()
+                    .bold()
+            );
             println!(
                 "{}",
                 "Make sure Ollama is running and accessible at the specified URL.".yellow()
             );
+            return Err(ExitStatus::Provider.into());
+        }
+        Err(e) => {
+            return Err(
+                CommittorError::AIProviderError(format!("Error checking Ollama: {e}")).into(),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_eval_command(committor: &Committor, last: usize) -> Result<()> {
+    println!(
+        "{}",
+        format!("Replaying up to {last} commits from history...").cyan()
+    );
+
+    let report = committor.evaluate_history(last).await?;
+
+    if report.samples.is_empty() {
+        println!("{}", "No commits with diffs found to evaluate.".yellow());
+        return Ok(());
+    }
+
+    println!("{}", "Evaluation results:".green().bold());
+    println!("  Prompt version:     {}", report.prompt_version);
+    println!(
+        "  Commits evaluated:  {}/{}",
+        report::format_count(report.generated_count as u64),
+        report::format_count(report.samples.len() as u64)
+    );
+    println!(
+        "  Valid format rate:  {}",
+        report::format_percent(report.valid_rate, 1)
+    );
+    println!(
+        "  Type match rate:    {}",
+        report::format_percent(report.type_match_rate, 1)
+    );
+    println!(
+        "  Scope match rate:   {}",
+        report::format_percent(report.scope_match_rate, 1)
+    );
+    println!(
+        "  Mean similarity:    {}",
+        report::format_decimal(report.mean_similarity, 3)
+    );
+
+    Ok(())
+}
+
+async fn handle_gate_command(
+    committor: &Committor,
+    title: &str,
+    diff_content: &str,
+    mode: Option<EnforcementMode>,
+    comment: Option<&Path>,
+) -> Result<()> {
+    if diff_content.is_empty() {
+        println!("{}", Text::GateNoChanges.text().yellow());
+        return Ok(());
+    }
+    let mode = mode.unwrap_or(Settings::discover()?.gate.mode);
+
+    info!("Generating commit messages for the pull request...");
+    let check = committor.check_title(title, diff_content).await?;
+    if let Some(path) = comment {
+        fs::write(path, gate::comment_markdown(&check))
+            .with_context(|| format!("Failed to write comment to {}", path.display()))?;
+    }
+
+    if check.passed() {
+        println!(
+            "{}",
+            format!(
+                "{} {}",
+                output::success_mark(),
+                Text::GateTitleMatches.text()
+            )
+            .green()
+        );
+        return Ok(());
+    }
+
+    println!(
+        "{} {}",
+        output::failure_mark().red().bold(),
+        Text::GateTitleMismatch.format(&[&check.title]).yellow()
+    );
+    for problem in &check.problems {
+        println!("    - {problem}");
+    }
+    if let Some(closest) = &check.closest {
+        println!(
+            "    {}",
+            Text::GateClosestSubject.format(&[closest]).dimmed()
+        );
+    }
+
+    match mode {
+        EnforcementMode::Enforce => {
+            Err(CommittorError::ValidationFailed(Text::GateTitleRejected.text().to_string()).into())
+        }
+        EnforcementMode::Warn => Ok(()),
+    }
+}
+
+async fn handle_index_command(committor: &Committor, last: usize, rebuild: bool) -> Result<()> {
+    println!(
+        "{}",
+        format!("Embedding up to {last} commits from history...").cyan()
+    );
+
+    let added = committor.update_embedding_index(last, rebuild).await?;
+    println!(
+        "{}",
+        format!("{} Indexed {added} new commit(s)", output::success_mark())
+            .green()
+            .bold()
+    );
+
+    Ok(())
+}
+
+fn handle_lint_command(file: Option<&Path>) -> Result<()> {
+    let content = match file {
+        Some(path) if path != Path::new("-") => fs::read_to_string(path)
+            .with_context(|| format!("Failed to read commit message from {}", path.display()))?,
+        _ => {
+            let mut content = String::new();
+            std::io::stdin()
+                .read_to_string(&mut content)
+                .context("Failed to read commit message from stdin")?;
+            content
+        }
+    };
+
+    let cleanup = trailers::MessageCleanup::from_git(&content);
+    let message = trailers::clean_message_file(&content, &cleanup).unwrap_or_default();
+    let settings = Settings::discover()?;
+    let violations = rules::RuleSet::from_settings(&settings).check(&message);
+
+    if violations.is_empty() {
+        println!(
+            "{}",
+            format!(
+                "{} Commit message follows the rules",
+                output::success_mark()
+            )
+            .green()
+        );
+        return Ok(());
+    }
+
+    for violation in &violations {
+        println!("{} {violation}", output::failure_mark().red().bold());
+    }
+    Err(CommittorError::ValidationFailed(format!(
+        "Commit message breaks {} rule(s)",
+        violations.len()
+    ))
+    .into())
+}
+
+fn handle_types_command() -> Result<()> {
+    println!("{}", Settings::discover()?.describe_conventions()?);
+    Ok(())
+}
+
+fn handle_prompt_status_command() -> Result<()> {
+    let mappings = Settings::discover()?.branch_type_mappings()?;
+    if let Some(status) = shell::prompt_status(&mappings)? {
+        println!("{status}");
+    }
+    Ok(())
+}
+
+async fn handle_config_lint_command() -> Result<()> {
+    let mut checked = Vec::new();
+    let mut problems = Vec::new();
+    if let Some(path) = settings::settings_path().filter(|path| path.exists()) {
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read settings from {}", path.display()))?;
+        let file = path.display().to_string();
+        problems.extend(configuration::lint_settings(&file, &content));
+        checked.push(file);
+    }
+    if let Some((source, content)) = policy::read_policy(&reqwest::Client::new()).await? {
+        problems.extend(configuration::lint_policy(&source, &content));
+        checked.push(source);
+    }
+
+    if checked.is_empty() {
+        println!(
+            "{}",
+            "No settings file or organization policy to check".yellow()
+        );
+        return Ok(());
+    }
+    if problems.is_empty() {
+        for file in &checked {
+            println!("{} {file}", output::success_mark().green());
+        }
+        return Ok(());
+    }
+
+    for problem in &problems {
+        println!("{} {problem}", output::failure_mark().red());
+    }
+    Err(anyhow::anyhow!(
+        "Found {} configuration problem(s)",
+        problems.len()
+    ))
+}
+
+fn handle_config_show_command(resolved: bool, matches: &ArgMatches) -> Result<()> {
+    if !resolved {
+        print!("{}", toml::to_string_pretty(&Settings::discover()?)?);
+        return Ok(());
+    }
+
+    let print_value = |key: &str, value: &str, source: &str| {
+        println!("{key} = {value} {}", format!("# {source}").dimmed());
+    };
+
+    println!("{}", "Command line and environment:".green().bold());
+    for (key, value, source) in resolved_arguments(matches) {
+        print_value(&key, &value, &source);
+    }
+
+    println!();
+    println!("{}", "Settings:".green().bold());
+    if let Some(source) = policy::policy_source() {
+        println!("{}", format!("Organization policy from {source}").dimmed());
+    }
+    let path = settings::settings_path().filter(|path| path.exists());
+    let content = path
+        .as_deref()
+        .map(fs::read_to_string)
+        .transpose()
+        .context("Failed to read the settings file")?;
+    let name = path.as_deref().map(|path| path.display().to_string());
+    let file = name.as_deref().zip(content.as_deref());
+    for setting in configuration::resolve_settings(file, policy::current())? {
+        print_value(&setting.key, &setting.value, &setting.source);
+    }
+    Ok(())
+}
+
+/// List the global options that have a value, with where each value came from
+///
+/// Flags left at their default are skipped, and the API key is never shown.
+fn resolved_arguments(matches: &ArgMatches) -> Vec<(String, String, String)> {
+    Cli::command()
+        .get_arguments()
+        .filter_map(|arg| {
+            let id = arg.get_id().as_str();
+            let long = arg.get_long()?;
+            let source = match matches.value_source(id)? {
+                ValueSource::DefaultValue if !arg.get_action().takes_values() => return None,
+                ValueSource::DefaultValue => "default".to_string(),
+                ValueSource::EnvVariable => match arg.get_env() {
+                    Some(name) => format!("environment ({})", name.to_string_lossy()),
+                    None => "environment".to_string(),
+                },
+                _ => "command line".to_string(),
+            };
+            let value = if id == "api_key" {
+                "(set)".to_string()
+            } else {
+                let values: Vec<String> = matches
+                    .get_raw(id)?
+                    .map(|value| value.to_string_lossy().into_owned())
+                    .collect();
+                values.join(",")
+            };
+            Some((format!("--{long}"), value, source))
+        })
+        .collect()
+}
+
+fn handle_badge_command(file: &Path, last: usize, format: BadgeFormat, label: &str) -> Result<()> {
+    let compliance = badge::compliance(last)?;
+    let badge = badge::render(&compliance, label, format)?;
+    if file == Path::new("-") {
+        print!("{badge}");
+        return Ok(());
+    }
+    fs::write(file, &badge).with_context(|| format!("Failed to write {}", file.display()))?;
+    println!(
+        "{}",
+        format!(
+            "{} of the last {} commits are conventional; badge written to {}",
+            compliance.conventional,
+            compliance.total,
+            file.display()
+        )
+        .green()
+    );
+    Ok(())
+}
+
+async fn handle_export_prompt_command(
+    committor: &Committor,
+    diff_content: String,
+    file: &Path,
+) -> Result<()> {
+    if diff_content.is_empty() {
+        println!("{}", Text::NoStagedChangesHint.text().yellow());
+        return Err(ExitStatus::NoStagedChanges.into());
+    }
+
+    let prompt = committor.export_prompt(&diff_content).await?;
+    if file == Path::new("-") {
+        println!("{prompt}");
+        return Ok(());
+    }
+    fs::write(file, format!("{prompt}\n"))
+        .with_context(|| format!("Failed to write {}", file.display()))?;
+    println!(
+        "{}",
+        format!("Prompt written to {}", file.display()).green()
+    );
+    println!("Run it on the model, save each reply to a file and read them back with `committor import-response <files>`.");
+    Ok(())
+}
+
+fn handle_import_response_command(
+    committor: &Committor,
+    cli: &Cli,
+    diff_content: &str,
+    files: &[PathBuf],
+) -> Result<()> {
+    if diff_content.is_empty() {
+        println!("{}", Text::NoStagedChangesHint.text().yellow());
+        return Err(ExitStatus::NoStagedChanges.into());
+    }
+
+    let responses = files
+        .iter()
+        .map(|file| {
+            if file == Path::new("-") {
+                let mut response = String::new();
+                std::io::stdin().read_to_string(&mut response)?;
+                Ok(response)
+            } else {
+                fs::read_to_string(file)
+                    .with_context(|| format!("Failed to read {}", file.display()))
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let messages = committor.import_responses(diff_content, &responses)?;
+    save_last_messages(committor, diff_content, &messages);
+
+    let previous_subject = committor.previous_commit_subject();
+    commit::display_commit_options_with_details(
+        &messages,
+        previous_subject.as_deref(),
+        &committor.recent_history(),
+        cli.verbose,
+        false,
+    );
+    commit::display_spelling_warnings(&committor.spelling_warnings(&messages));
+
+    if cli.auto_commit {
+        committor.commit_with_message(&messages[0])?;
+    }
+    Ok(())
+}
+
+fn handle_export_session_command(file: &Path) -> Result<()> {
+    let session = session::load_session()?
+        .context("No session to export. Run `committor generate` or `committor commit` first.")?;
+    let content = if file
+        .extension()
+        .is_some_and(|extension| extension == "json")
+    {
+        serde_json::to_string_pretty(&session)?
+    } else {
+        session.to_markdown()
+    };
+    fs::write(file, content).with_context(|| format!("Failed to write {}", file.display()))?;
+    println!(
+        "{}",
+        format!("Session written to {}", file.display()).green()
+    );
+    Ok(())
+}
+
+fn handle_paths_command() -> Result<()> {
+    let resolved = paths::ResolvedPaths::discover(&Settings::discover()?.audit);
+    let directories = [
+        ("Config directory", resolved.config_dir.as_deref()),
+        ("Cache directory", resolved.cache_dir.as_deref()),
+        ("Data directory", resolved.data_dir.as_deref()),
+    ];
+    for (label, path) in directories {
+        println!(
+            "{:<18} {}",
+            format!("{label}:").cyan(),
+            paths::display_path(path)
+        );
+    }
+    for (label, path) in resolved.user_files() {
+        println!("{:<18} {}", format!("{label}:").cyan(), path.display());
+    }
+
+    if resolved.repository_files.is_empty() {
+        println!("{}", "Not in a git repository".dimmed());
+        return Ok(());
+    }
+    println!();
+    println!("{}", "Repository files:".green().bold());
+    for (label, path) in &resolved.repository_files {
+        println!("{:<18} {}", format!("{label}:").cyan(), path.display());
+    }
+
+    Ok(())
+}
+
+fn handle_audit_show_command(last: Option<usize>) -> Result<()> {
+    let path = audit::audit_path(&Settings::discover()?.audit)?;
+    let records = audit::read_records(&path)?;
+    if records.is_empty() {
+        println!(
+            "{}",
+            format!("No requests recorded in {}", path.display()).yellow()
+        );
+        return Ok(());
+    }
+
+    let skip = last.map_or(0, |last| records.len().saturating_sub(last));
+    println!(
+        "{}",
+        format!("Audit log {}:", path.display()).green().bold()
+    );
+    for record in &records[skip..] {
+        println!(
+            "  {}  {:<7} {:<24} {:<10} {:<10} {:<4} {:>4} redaction(s) {:>9} bytes",
+            report::format_stored_date(&record.timestamp),
+            record.provider,
+            record.model,
+            record.operation,
+            record.privacy.to_string(),
+            record
+                .prompt_version
+                .map_or_else(String::new, |version| format!("v{version}")),
+            report::format_count(record.redactions as u64),
+            report::format_count(record.bytes as u64)
+        );
+    }
+
+    Ok(())
+}
+
+fn handle_commit_msg_command(file: &Path, change_id: bool) -> Result<()> {
+    let content = fs::read_to_string(file)
+        .with_context(|| format!("Failed to read commit message from {}", file.display()))?;
+
+    // An empty message aborts the commit; leave that to git
+    let cleanup = trailers::MessageCleanup::from_git(&content);
+    let Some(message) = trailers::clean_message_file(&content, &cleanup) else {
+        return Ok(());
+    };
+
+    let mut settings = Settings::discover()?.trailers;
+    settings.change_id |= change_id;
+
+    let message = trailers::apply_trailer_settings(&message, &settings)?;
+    trailers::ensure_required_trailers(&message, &settings.required)?;
+    fs::write(file, format!("{message}\n"))
+        .with_context(|| format!("Failed to write commit message to {}", file.display()))?;
+
+    Ok(())
+}
+
+/// Add the configured trailers to the message file, then check it against the rules
+fn handle_pre_commit_msg_stage(file: &Path) -> Result<()> {
+    handle_commit_msg_command(file, false)?;
+
+    let content = fs::read_to_string(file)
+        .with_context(|| format!("Failed to read commit message from {}", file.display()))?;
+    // An empty message aborts the commit; leave that to git
+    let cleanup = trailers::MessageCleanup::from_git(&content);
+    if trailers::clean_message_file(&content, &cleanup).is_none() {
+        return Ok(());
+    }
+    handle_lint_command(Some(file))
+}
+
+/// Add suggestions to the message file, leaving the commit untouched if anything goes wrong
+///
+/// A hook that fails or hangs would hold up the commit, so a broken settings file,
+/// a missing provider or a failed request only prints a warning, as does a policy
+/// that can't be loaded, before any of this runs. Generation runs
+/// on its own task, and once the time is up the process exits at once without a
+/// word, leaving the file as git wrote it, even while a git read is still running.
+async fn handle_prepare_commit_msg_command(
+    cli: &Cli,
+    file: &Path,
+    source: Option<&str>,
+    timeout: Option<u64>,
+) -> Result<()> {
+    // Generating reads the same settings, so a broken file means no suggestions
+    let settings = match Settings::discover() {
+        Ok(settings) => settings.hooks,
+        Err(e) => {
+            eprintln!("{}", format!("committor: no suggestions ({e:#})").yellow());
+            return Ok(());
         }
+    };
+    // Git already wrote the message for merges, squashes, `-m` and the like
+    if !settings.suggests_for(source) {
+        return Ok(());
+    }
+
+    let content = fs::read_to_string(file)
+        .with_context(|| format!("Failed to read commit message from {}", file.display()))?;
+
+    let budget = Duration::from_secs(timeout.unwrap_or(settings.timeout));
+    let mut generating = cli.clone();
+    let task = tokio::spawn(async move { hook_suggestions(&mut generating).await });
+    let generated = match tokio::time::timeout(budget, task).await {
+        Ok(joined) => joined
+            .map_err(anyhow::Error::from)
+            .and_then(|result| result),
+        // Reading the diff blocks and can't be cancelled, and the runtime would wait
+        // for it on the way out, so leave without it
+        Err(_) => std::process::exit(ExitStatus::Success.code().into()),
+    };
+    let suggestions = match generated {
+        Ok(suggestions) => suggestions,
         Err(e) => {
-            return Err(anyhow::anyhow!("Error checking Ollama: {}", e));
+            eprintln!("{}", format!("committor: no suggestions ({e:#})").yellow());
+            return Ok(());
         }
+    };
+
+    let cleanup = trailers::MessageCleanup::from_git(&content);
+    let merged = hooks::merge_suggestions(&content, &suggestions, &cleanup);
+    if merged != content {
+        fs::write(file, merged)
+            .with_context(|| format!("Failed to write commit message to {}", file.display()))?;
     }
 
     Ok(())
 }
+
+/// Generate suggestions for the staged changes, or none when no provider is set up
+async fn hook_suggestions(cli: &mut Cli) -> Result<Vec<String>> {
+    if cli.provider.is_none() {
+        match detect_provider(cli).await? {
+            Some(provider) => cli.provider = Some(provider),
+            None => return Ok(Vec::new()),
+        }
+    }
+
+    let (committor, diff_content) =
+        start_committor(cli, || Ok(diff::get_staged_snapshot()?.diff)).await?;
+    if diff_content.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    committor.generate_commit_messages(&diff_content).await
+}
+
+fn handle_pre_receive_command(mode: Option<EnforcementMode>) -> Result<()> {
+    let settings = Settings::discover()?;
+    let mode = mode.unwrap_or(settings.hooks.pre_receive);
+
+    let mut input = String::new();
+    std::io::stdin()
+        .read_to_string(&mut input)
+        .context("Failed to read ref updates from stdin")?;
+    let updates = hooks::parse_pre_receive_input(&input)?;
+
+    // Honour GIT_DIR and the quarantine object directories git sets up for receive hooks
+    let repo = git2::Repository::open_from_env().context("Not in a git repository")?;
+    let results = hooks::lint_ref_updates(
+        &repo,
+        &updates,
+        &settings.rules,
+        &settings.trailers.required,
+    )?;
+
+    let failures: Vec<_> = results
+        .iter()
+        .filter(|r| !r.violations.is_empty())
+        .collect();
+    if failures.is_empty() {
+        println!(
+            "{}",
+            format!(
+                "{} {} commit message(s) follow the rules",
+                output::success_mark(),
+                results.len()
+            )
+            .green()
+        );
+        return Ok(());
+    }
+
+    for failure in &failures {
+        println!(
+            "{} {} {}",
+            output::failure_mark().red().bold(),
+            failure.commit_id[..failure.commit_id.len().min(10)].yellow(),
+            failure.subject
+        );
+        println!("    {}", format!("on {}", failure.refname).dimmed());
+        for violation in &failure.violations {
+            println!("    - {violation}");
+        }
+    }
+
+    match mode {
+        EnforcementMode::Enforce => Err(CommittorError::ValidationFailed(format!(
+            "Push rejected: {} commit(s) do not follow the commit message rules",
+            failures.len()
+        ))
+        .into()),
+        EnforcementMode::Warn => {
+            println!(
+                "{}",
+                format!(
+                    "Warning: {} commit(s) do not follow the commit message rules",
+                    failures.len()
+                )
+                .yellow()
+            );
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! Database migrations among the changed files
+//!
+//! A migration diff is DDL the model tends to summarize as `update sql`; calling
+//! the files out asks for the schema change to be named, and lets a repository
+//! say which commit type its schema work goes under.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Directories whose files are migrations: diesel, sqlx, Prisma, Django, Rails and friends
+const MIGRATION_DIRS: &[&str] = &["migrations", "migration", "migrate"];
+
+/// Files that describe the whole schema and change along with migrations
+const SCHEMA_FILES: &[&str] = &["schema.prisma", "schema.rb", "structure.sql"];
+
+/// Migration handling read from the `[migrations]` settings section
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MigrationSettings {
+    /// Point out migration files in the prompt
+    pub enabled: bool,
+    /// Commit type schema work is committed under, e.g. `feat` or `chore`
+    pub commit_type: Option<String>,
+    /// Scope schema work is committed under, e.g. `db`
+    pub scope: Option<String>,
+}
+
+impl Default for MigrationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            commit_type: None,
+            scope: None,
+        }
+    }
+}
+
+impl MigrationSettings {
+    /// Describe how schema work should be classified, from the settings or the usual convention
+    pub fn classification(&self) -> String {
+        match (&self.commit_type, &self.scope) {
+            (Some(commit_type), Some(scope)) => {
+                format!("Use the `{commit_type}` type and `{scope}` scope for this schema work.")
+            }
+            (Some(commit_type), None) => {
+                format!("Use the `{commit_type}` type for this schema work.")
+            }
+            (None, scope) => {
+                let scope = scope
+                    .as_ref()
+                    .map(|scope| format!(" with the `{scope}` scope"))
+                    .unwrap_or_default();
+                format!(
+                    "Use `feat`{scope} when the schema supports new behaviour, `fix` when it corrects a schema bug, and `chore` for housekeeping such as squashing or reformatting migrations."
+                )
+            }
+        }
+    }
+}
+
+/// Check whether a path is a database migration or schema file
+pub fn is_migration_path(path: &str) -> bool {
+    let path = Path::new(path);
+    let is_sql = path.extension().is_some_and(|ext| ext == "sql");
+    let is_schema = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| SCHEMA_FILES.contains(&name));
+
+    let dirs: Vec<&str> = path
+        .parent()
+        .into_iter()
+        .flat_map(Path::iter)
+        .filter_map(|dir| dir.to_str())
+        .collect();
+    let in_migration_dir = dirs.iter().any(|dir| MIGRATION_DIRS.contains(dir));
+    let in_alembic_versions = dirs.contains(&"alembic") && dirs.contains(&"versions");
+
+    is_sql || is_schema || in_migration_dir || in_alembic_versions
+}
+
+/// Keep the migration and schema files from a list of changed paths
+pub fn migration_files(paths: &[String]) -> Vec<String> {
+    paths
+        .iter()
+        .filter(|path| is_migration_path(path))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_migration_path() {
+        for path in [
+            "migrations/2024-05-01-120000_create_users/up.sql",
+            "migrations/20240501120000_add_email.sql",
+            "prisma/migrations/20240501_init/migration.sql",
+            "db/migrate/20240501120000_add_index_to_users.rb",
+            "app/users/migrations/0002_profile.py",
+            "alembic/versions/3f2a_add_orders.py",
+            "prisma/schema.prisma",
+            "sql/V3__reports.sql",
+        ] {
+            assert!(is_migration_path(path), "{path}");
+        }
+
+        for path in ["src/migrate.rs", "docs/migrations.md", "src/db/pool.rs"] {
+            assert!(!is_migration_path(path), "{path}");
+        }
+    }
+
+    #[test]
+    fn test_classification() {
+        assert!(MigrationSettings::default()
+            .classification()
+            .contains("`chore` for housekeeping"));
+
+        let settings = MigrationSettings {
+            commit_type: Some("feat".to_string()),
+            scope: Some("db".to_string()),
+            ..MigrationSettings::default()
+        };
+        assert_eq!(
+            settings.classification(),
+            "Use the `feat` type and `db` scope for this schema work."
+        );
+    }
+}
@@ -0,0 +1,159 @@
+//! Jupyter notebook diffs reduced to their code and markdown
+//!
+//! A notebook is JSON holding cell outputs, execution counts and metadata next
+//! to the cells themselves; re-running one rewrites thousands of lines. The
+//! staged versions are read as cells instead and diffed as plain text.
+
+use crate::diff::{decode_text, get_staged_changes_from_repo, staged_file_versions_from_repo};
+use anyhow::{Context, Result};
+use git2::Repository;
+use serde_json::Value;
+
+/// Note opening a notebook's cleaned patch
+const CELLS_ONLY_NOTE: &str =
+    "(notebook cells only; outputs, execution counts and metadata left out)\n";
+
+/// Note standing in for a notebook patch whose cells didn't change
+const NO_CELL_CHANGES_NOTE: &str =
+    "(notebook: only outputs, execution counts or metadata changed)\n";
+
+/// Check whether a path is a Jupyter notebook
+pub fn is_notebook(path: &str) -> bool {
+    path.ends_with(".ipynb")
+}
+
+/// Get cleaned patches for the notebooks staged in the repository in the current directory
+pub fn notebook_patches() -> Result<Vec<(String, String)>> {
+    let repo = Repository::open(".").context("Not in a git repository")?;
+    notebook_patches_from_repo(&repo)
+}
+
+/// Get cleaned patches for the notebooks staged in a specific repository, keyed by path
+///
+/// Notebooks that fail to parse on either side are left out, so their patch is sent as it is.
+pub fn notebook_patches_from_repo(repo: &Repository) -> Result<Vec<(String, String)>> {
+    let mut patches = Vec::new();
+    for change in get_staged_changes_from_repo(repo)? {
+        if !is_notebook(&change.file_path) {
+            continue;
+        }
+        let (old, new) = staged_file_versions_from_repo(repo, &change.file_path)?;
+        if let Some(patch) = notebook_patch(old.as_deref(), new.as_deref())? {
+            patches.push((change.file_path, patch));
+        }
+    }
+    Ok(patches)
+}
+
+/// Diff the cells of two versions of a notebook, `None` if either isn't a valid notebook
+pub fn notebook_patch(old: Option<&str>, new: Option<&str>) -> Result<Option<String>> {
+    let cells = |content: Option<&str>| match content {
+        Some(content) => notebook_cells(content),
+        None => Some(String::new()),
+    };
+    let (Some(old), Some(new)) = (cells(old), cells(new)) else {
+        return Ok(None);
+    };
+    if old == new {
+        return Ok(Some(NO_CELL_CHANGES_NOTE.to_string()));
+    }
+
+    let mut diff_opts = git2::DiffOptions::new();
+    diff_opts.context_lines(3);
+    let mut patch = git2::Patch::from_buffers(
+        old.as_bytes(),
+        None,
+        new.as_bytes(),
+        None,
+        Some(&mut diff_opts),
+    )?;
+
+    let mut text = CELLS_ONLY_NOTE.to_string();
+    patch.print(&mut |_delta, _hunk, line| {
+        let content = decode_text(line.content());
+        match line.origin() {
+            'H' => text.push_str(&content),
+            origin @ ('+' | '-' | ' ') => {
+                text.push(origin);
+                text.push_str(&content);
+            }
+            _ => {}
+        }
+        true
+    })?;
+    Ok(Some(text))
+}
+
+/// Render a notebook's cells as text, each under a `# %% [type]` marker, `None` if it isn't a notebook
+pub fn notebook_cells(content: &str) -> Option<String> {
+    let notebook: Value = serde_json::from_str(content).ok()?;
+    let mut text = String::new();
+    for cell in notebook.get("cells")?.as_array()? {
+        let cell_type = cell
+            .get("cell_type")
+            .and_then(Value::as_str)
+            .unwrap_or("code");
+        text.push_str(&format!("# %% [{cell_type}]\n"));
+
+        // Sources are a string or a list of lines that keep their own newlines
+        match cell.get("source") {
+            Some(Value::String(source)) => text.push_str(source),
+            Some(Value::Array(lines)) => lines
+                .iter()
+                .filter_map(Value::as_str)
+                .for_each(|line| text.push_str(line)),
+            _ => {}
+        }
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+    }
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD_NOTEBOOK: &str = r##"{
+  "cells": [
+    {"cell_type": "markdown", "metadata": {}, "source": ["# Sales report\n"]},
+    {"cell_type": "code", "execution_count": 3, "metadata": {},
+     "outputs": [{"output_type": "stream", "text": ["42\n"]}],
+     "source": ["import pandas as pd\n", "df = pd.read_csv('sales.csv')\n", "df.sum()"]}
+  ],
+  "metadata": {"kernelspec": {"name": "python3"}},
+  "nbformat": 4,
+  "nbformat_minor": 5
+}"##;
+
+    #[test]
+    fn test_notebook_cells() {
+        assert_eq!(
+            notebook_cells(OLD_NOTEBOOK).unwrap(),
+            "# %% [markdown]\n# Sales report\n\
+             # %% [code]\nimport pandas as pd\ndf = pd.read_csv('sales.csv')\ndf.sum()\n"
+        );
+        assert!(notebook_cells("not json").is_none());
+    }
+
+    #[test]
+    fn test_notebook_patch_leaves_out_outputs() -> Result<()> {
+        let rerun = OLD_NOTEBOOK
+            .replace("\"execution_count\": 3", "\"execution_count\": 9")
+            .replace("42", "43");
+        assert_eq!(
+            notebook_patch(Some(OLD_NOTEBOOK), Some(&rerun))?.unwrap(),
+            NO_CELL_CHANGES_NOTE
+        );
+
+        let edited = rerun.replace("df.sum()", "df.groupby('region').sum()");
+        let patch = notebook_patch(Some(OLD_NOTEBOOK), Some(&edited))?.unwrap();
+        assert!(patch.starts_with(CELLS_ONLY_NOTE));
+        assert!(patch.contains("-df.sum()\n"));
+        assert!(patch.contains("+df.groupby('region').sum()\n"));
+        assert!(!patch.contains("execution_count"));
+        assert!(!patch.contains("43"));
+        Ok(())
+    }
+}
@@ -0,0 +1,152 @@
+//! What happened to each file or commit of a run over many of them
+//!
+//! `commit --per-file` and `rewrite` make one request per item. Without
+//! `--continue-on-error` the first failure ends the run as before; with it the item
+//! is recorded and the run goes on. Either way, a run where anything failed or was
+//! skipped ends with a summary of every item that didn't go through.
+
+use crate::exit::ExitStatus;
+use crate::types::CommittorError;
+use colored::Colorize;
+use tracing::warn;
+
+/// How one item of a run ended
+#[derive(Debug)]
+pub enum Outcome {
+    Succeeded,
+    Failed(anyhow::Error),
+    /// Left alone, with the reason
+    Skipped(String),
+}
+
+/// The outcome of every item of a run, in the order they were handled
+#[derive(Debug, Default)]
+pub struct Outcomes {
+    continue_on_error: bool,
+    items: Vec<(String, Outcome)>,
+}
+
+impl Outcomes {
+    pub fn new(continue_on_error: bool) -> Self {
+        Self {
+            continue_on_error,
+            items: Vec::new(),
+        }
+    }
+
+    pub fn succeeded(&mut self, item: impl Into<String>) {
+        self.items.push((item.into(), Outcome::Succeeded));
+    }
+
+    pub fn skipped(&mut self, item: impl Into<String>, reason: impl Into<String>) {
+        self.items
+            .push((item.into(), Outcome::Skipped(reason.into())));
+    }
+
+    /// Record a failed item, or hand the error back when it has to end the run
+    ///
+    /// Errors that would fail every later item too, such as Ollama not running or
+    /// Ctrl-C, end the run even with `--continue-on-error`.
+    pub fn failed(&mut self, item: impl Into<String>, error: anyhow::Error) -> anyhow::Result<()> {
+        let item = item.into();
+        if !self.continue_on_error || ends_run(&error) {
+            self.items
+                .push((item, Outcome::Failed(anyhow::anyhow!("{error:#}"))));
+            return Err(error);
+        }
+        warn!("Continuing after {}: {:#}", item, error);
+        self.items.push((item, Outcome::Failed(error)));
+        Ok(())
+    }
+
+    pub fn items(&self) -> &[(String, Outcome)] {
+        &self.items
+    }
+
+    /// Count the items that succeeded, failed and were skipped
+    pub fn counts(&self) -> (usize, usize, usize) {
+        self.items.iter().fold(
+            (0, 0, 0),
+            |(ok, failed, skipped), (_, outcome)| match outcome {
+                Outcome::Succeeded => (ok + 1, failed, skipped),
+                Outcome::Failed(_) => (ok, failed + 1, skipped),
+                Outcome::Skipped(_) => (ok, failed, skipped + 1),
+            },
+        )
+    }
+
+    /// Check whether anything didn't simply go through, which is worth a summary
+    pub fn needs_summary(&self) -> bool {
+        let (_, failed, skipped) = self.counts();
+        failed + skipped > 0
+    }
+
+    /// Print one line per item that failed or was skipped, and the totals
+    pub fn display_summary(&self, succeeded_label: &str) {
+        let (ok, failed, skipped) = self.counts();
+        println!();
+        println!(
+            "{}",
+            format!("{ok} {succeeded_label}, {failed} failed, {skipped} skipped").bold()
+        );
+        for (item, outcome) in &self.items {
+            match outcome {
+                Outcome::Succeeded => {}
+                Outcome::Failed(error) => {
+                    println!("  {} {}: {:#}", "failed".red(), item, error)
+                }
+                Outcome::Skipped(reason) => {
+                    println!("  {} {}: {}", "skipped".yellow(), item, reason.dimmed())
+                }
+            }
+        }
+    }
+
+    /// End the run with the status of the first failure, if anything failed
+    pub fn finish(&self) -> anyhow::Result<()> {
+        match self.items.iter().find_map(|(_, outcome)| match outcome {
+            Outcome::Failed(error) => Some(error),
+            _ => None,
+        }) {
+            Some(error) => Err(ExitStatus::of(error).into()),
+            None => Ok(()),
+        }
+    }
+}
+
+fn ends_run(error: &anyhow::Error) -> bool {
+    error.is::<ExitStatus>()
+        || matches!(
+            error.downcast_ref::<CommittorError>(),
+            Some(CommittorError::OllamaUnavailable(_))
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outcomes() {
+        let mut outcomes = Outcomes::new(true);
+        outcomes.succeeded("src/a.rs");
+        let empty = CommittorError::AIProviderError("empty response".to_string());
+        assert!(outcomes.failed("src/b.rs", empty.into()).is_ok());
+        outcomes.skipped("src/c.rs", "not selected");
+        assert_eq!(outcomes.counts(), (1, 1, 1));
+        assert!(outcomes.needs_summary());
+        let status = ExitStatus::of(&outcomes.finish().unwrap_err());
+        assert_eq!(status, ExitStatus::Provider);
+
+        // Ctrl-C ends the run regardless
+        assert!(outcomes
+            .failed("src/d.rs", ExitStatus::Interrupted.into())
+            .is_err());
+
+        let mut outcomes = Outcomes::new(false);
+        outcomes.succeeded("src/a.rs");
+        let rules = CommittorError::ValidationFailed("no message".to_string());
+        assert!(outcomes.failed("src/b.rs", rules.into()).is_err());
+        assert_eq!(outcomes.counts(), (1, 1, 0));
+    }
+}
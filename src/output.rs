@@ -0,0 +1,71 @@
+//! Output style: decorated for terminals, or plain for screen readers and dumb terminals
+//!
+//! Plain output has no colour, box-drawing or symbols; lists are numbered and
+//! status lines start with a word such as `OK:` so they read well aloud.
+
+use std::env;
+use std::sync::OnceLock;
+
+static PLAIN: OnceLock<bool> = OnceLock::new();
+
+/// Choose plain output for the rest of the process; only the first call has any effect
+pub fn set_plain(plain: bool) {
+    if PLAIN.set(plain).is_ok() && plain {
+        colored::control::set_override(false);
+    }
+}
+
+/// Check whether output is plain, decorated until set
+pub fn is_plain() -> bool {
+    PLAIN.get().copied().unwrap_or(false)
+}
+
+/// Check whether the terminal can't handle decorated output, as with `TERM=dumb`
+pub fn is_dumb_terminal() -> bool {
+    env::var("TERM").is_ok_and(|term| term == "dumb")
+}
+
+/// Prefix for a line reporting success
+pub fn success_mark() -> &'static str {
+    if is_plain() {
+        "OK:"
+    } else {
+        "✓"
+    }
+}
+
+/// Prefix for a line reporting failure
+pub fn failure_mark() -> &'static str {
+    if is_plain() {
+        "Failed:"
+    } else {
+        "✗"
+    }
+}
+
+/// Prefix for a warning line
+pub fn warning_mark() -> &'static str {
+    if is_plain() {
+        "Warning:"
+    } else {
+        "⚠"
+    }
+}
+
+/// Line separating a block of output such as a diff, empty in plain output
+pub fn separator() -> String {
+    if is_plain() {
+        String::new()
+    } else {
+        "─".repeat(80)
+    }
+}
+
+/// Label for the nth item of a numbered list, e.g. `2.` or `Option 2:`
+pub fn item_label(n: usize) -> String {
+    if is_plain() {
+        format!("Option {n}:")
+    } else {
+        format!("{n}.")
+    }
+}
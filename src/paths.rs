@@ -0,0 +1,157 @@
+//! Resolution of every file and directory committor reads or writes
+//!
+//! Per-repository state lives in the git directory so it never shows up as an
+//! untracked file; per-user files live in the platform's standard directories.
+
+use crate::airgap::EXPORT_FILE_NAME;
+use crate::audit::{audit_path_from_repo, AuditSettings};
+use crate::batch::LAST_BATCH_FILE_NAME;
+use crate::diff::DIFF_CACHE_FILE_NAME;
+use crate::embeddings::INDEX_FILE_NAME;
+use crate::feedback::FEEDBACK_FILE_NAME;
+use crate::history::HISTORY_CACHE_FILE_NAME;
+use crate::policy::{POLICY_CACHE_FILE_NAME, POLICY_FILE_NAME};
+use crate::profile::PROFILE_CACHE_FILE_NAME;
+use crate::session::SESSION_FILE_NAME;
+use crate::settings::SETTINGS_FILE_NAME;
+use crate::update::UPDATE_CHECK_FILE_NAME;
+use crate::vocabulary::VOCABULARY_CACHE_FILE_NAME;
+use directories::ProjectDirs;
+use git2::Repository;
+use std::path::{Path, PathBuf};
+
+/// Get the platform's per-user directories for committor
+fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("", "", "committor")
+}
+
+/// Get the per-user configuration directory
+pub fn config_dir() -> Option<PathBuf> {
+    project_dirs().map(|dirs| dirs.config_dir().to_path_buf())
+}
+
+/// Get the per-user cache directory
+pub fn cache_dir() -> Option<PathBuf> {
+    project_dirs().map(|dirs| dirs.cache_dir().to_path_buf())
+}
+
+/// Get the per-user data directory
+pub fn data_dir() -> Option<PathBuf> {
+    project_dirs().map(|dirs| dirs.data_dir().to_path_buf())
+}
+
+/// Get the path of a file in the per-user cache directory
+pub fn cache_file(name: &str) -> Option<PathBuf> {
+    cache_dir().map(|dir| dir.join(name))
+}
+
+/// Get the path of a state file kept in a repository's git directory
+pub fn git_state_path(repo: &Repository, name: &str) -> PathBuf {
+    repo.path().join(name)
+}
+
+/// Get the settings file for a repository, in the repository directory itself when bare
+pub fn settings_path_from_repo(repo: &Repository) -> PathBuf {
+    repo.workdir()
+        .unwrap_or_else(|| repo.path())
+        .join(SETTINGS_FILE_NAME)
+}
+
+/// Every location committor uses, as shown by `committor paths`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedPaths {
+    pub config_dir: Option<PathBuf>,
+    pub cache_dir: Option<PathBuf>,
+    pub data_dir: Option<PathBuf>,
+    /// Files kept for the current repository, labelled; empty outside a repository
+    pub repository_files: Vec<(&'static str, PathBuf)>,
+}
+
+impl ResolvedPaths {
+    /// Resolve every location, including the current repository's files if there is one
+    pub fn discover(audit: &AuditSettings) -> Self {
+        let repository_files = Repository::discover(".")
+            .map(|repo| repository_files(&repo, audit))
+            .unwrap_or_default();
+
+        Self {
+            config_dir: config_dir(),
+            cache_dir: cache_dir(),
+            data_dir: data_dir(),
+            repository_files,
+        }
+    }
+
+    /// Get the per-user files, labelled
+    pub fn user_files(&self) -> Vec<(&'static str, PathBuf)> {
+        let config_files = self
+            .config_dir
+            .iter()
+            .map(|dir| ("Organization policy", dir.join(POLICY_FILE_NAME)));
+        let cache_files = self.cache_dir.iter().flat_map(|dir| {
+            [
+                ("Update check", dir.join(UPDATE_CHECK_FILE_NAME)),
+                ("Policy cache", dir.join(POLICY_CACHE_FILE_NAME)),
+            ]
+        });
+        config_files.chain(cache_files).collect()
+    }
+}
+
+/// Get the files committor keeps for a repository, labelled
+pub fn repository_files(repo: &Repository, audit: &AuditSettings) -> Vec<(&'static str, PathBuf)> {
+    vec![
+        ("Settings", settings_path_from_repo(repo)),
+        ("Diff cache", git_state_path(repo, DIFF_CACHE_FILE_NAME)),
+        ("Last messages", git_state_path(repo, LAST_BATCH_FILE_NAME)),
+        ("Embeddings index", git_state_path(repo, INDEX_FILE_NAME)),
+        (
+            "Repository profile",
+            git_state_path(repo, PROFILE_CACHE_FILE_NAME),
+        ),
+        (
+            "Recent history",
+            git_state_path(repo, HISTORY_CACHE_FILE_NAME),
+        ),
+        (
+            "Vocabulary",
+            git_state_path(repo, VOCABULARY_CACHE_FILE_NAME),
+        ),
+        ("Last session", git_state_path(repo, SESSION_FILE_NAME)),
+        ("Exported prompt", git_state_path(repo, EXPORT_FILE_NAME)),
+        ("Feedback log", git_state_path(repo, FEEDBACK_FILE_NAME)),
+        ("Audit log", audit_path_from_repo(repo, audit)),
+    ]
+}
+
+/// Display a path, or `(unavailable)` when the platform has no such directory
+pub fn display_path(path: Option<&Path>) -> String {
+    path.map_or_else(
+        || "(unavailable)".to_string(),
+        |path| path.display().to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repository_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let files = repository_files(&repo, &AuditSettings::default());
+
+        assert_eq!(files[0].1, repo.workdir().unwrap().join(SETTINGS_FILE_NAME));
+        assert!(files[1..]
+            .iter()
+            .all(|(_, path)| path.starts_with(repo.path())));
+
+        let bare_dir = tempfile::tempdir().unwrap();
+        let bare = Repository::init_bare(bare_dir.path()).unwrap();
+        assert_eq!(
+            settings_path_from_repo(&bare),
+            bare.path().join(SETTINGS_FILE_NAME)
+        );
+    }
+}
@@ -0,0 +1,401 @@
+//! Organization policy that repository and user settings cannot weaken
+//!
+//! Companies rolling committor out need some rules to hold on every machine:
+//! which providers code may be sent to, how private prompts must be, which
+//! types and scopes messages may use and which trailers they carry. The policy
+//! is read from a shared path or fetched from a URL and cached, then laid over
+//! the settings so a `.committor.toml` can only narrow it.
+
+use crate::paths::{cache_file, config_dir};
+use crate::privacy::PrivacyLevel;
+use crate::providers::ProviderConfig;
+use crate::settings::Settings;
+use crate::state::write_atomic;
+use crate::types::CommittorError;
+use crate::update::now;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::warn;
+
+/// Environment variable naming the policy file or URL
+pub const POLICY_ENV: &str = "COMMITTOR_POLICY";
+
+/// Name of the policy file read from the user configuration directory when the variable is unset
+pub const POLICY_FILE_NAME: &str = "policy.toml";
+
+/// Name of the file caching a policy fetched from a URL, kept in the user cache directory
+pub const POLICY_CACHE_FILE_NAME: &str = "policy-cache.json";
+
+/// How long to wait for the policy URL
+const POLICY_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+static POLICY: OnceLock<Policy> = OnceLock::new();
+
+/// Rules set by an organization, each empty list allowing anything
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Policy {
+    /// Providers that may be used, e.g. `["ollama"]`
+    pub providers: Vec<String>,
+    /// Privacy levels that may be used
+    pub privacy: Vec<PrivacyLevel>,
+    /// Commit types messages may use
+    pub types: Vec<String>,
+    /// Scopes messages may use
+    pub scopes: Vec<String>,
+    /// Trailers appended to every committed message
+    pub trailers: Vec<String>,
+    /// Trailer keys every message must carry
+    pub required_trailers: Vec<String>,
+    /// Minutes a policy fetched from a URL is reused before it is fetched again
+    pub ttl_minutes: u64,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            providers: Vec::new(),
+            privacy: Vec::new(),
+            types: Vec::new(),
+            scopes: Vec::new(),
+            trailers: Vec::new(),
+            required_trailers: Vec::new(),
+            ttl_minutes: 60,
+        }
+    }
+}
+
+impl Policy {
+    /// Parse a policy from TOML content
+    pub fn parse(content: &str) -> Result<Self> {
+        toml::from_str(content).map_err(|e| {
+            CommittorError::ConfigError(format!("Invalid organization policy: {e}")).into()
+        })
+    }
+
+    /// Narrow settings to the policy, keeping anything stricter they already ask for
+    pub fn apply(&self, settings: &mut Settings) {
+        let rules = &mut settings.rules;
+        rules.allowed_types = narrow(&rules.allowed_types, &self.types);
+        rules.allowed_scopes = narrow(&rules.allowed_scopes, &self.scopes);
+
+        let trailers = &mut settings.trailers;
+        extend_unique(&mut trailers.add, &self.trailers);
+        extend_unique(&mut trailers.required, &self.required_trailers);
+    }
+
+    /// Check that a provider and privacy level are allowed
+    pub fn check(&self, provider: &ProviderConfig, privacy: PrivacyLevel) -> Result<()> {
+        let name = provider.name();
+        if !self.providers.is_empty() && !self.providers.iter().any(|allowed| allowed == name) {
+            return Err(CommittorError::ConfigError(format!(
+                "The organization policy does not allow the {name} provider; use one of: {}",
+                self.providers.join(", ")
+            ))
+            .into());
+        }
+
+        if !self.privacy.is_empty() && !self.privacy.contains(&privacy) {
+            let allowed: Vec<String> = self.privacy.iter().map(ToString::to_string).collect();
+            return Err(CommittorError::ConfigError(format!(
+                "The organization policy does not allow the {privacy} privacy level; use --privacy with one of: {}",
+                allowed.join(", ")
+            ))
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Keep the configured values the policy allows, or use the policy's when none are left
+///
+/// An empty list allows anything, so dropping every configured value must not widen it.
+fn narrow(configured: &[String], allowed: &[String]) -> Vec<String> {
+    if allowed.is_empty() {
+        return configured.to_vec();
+    }
+    let kept: Vec<String> = configured
+        .iter()
+        .filter(|value| allowed.contains(value))
+        .cloned()
+        .collect();
+    if kept.is_empty() {
+        allowed.to_vec()
+    } else {
+        kept
+    }
+}
+
+fn extend_unique(values: &mut Vec<String>, extra: &[String]) {
+    for value in extra {
+        if !values.contains(value) {
+            values.push(value.clone());
+        }
+    }
+}
+
+/// Set the policy for this process; later calls are ignored
+pub fn set_policy(policy: Policy) {
+    let _ = POLICY.set(policy);
+}
+
+/// Get the policy in force, if one was loaded
+pub fn current() -> Option<&'static Policy> {
+    POLICY.get()
+}
+
+/// Get the policy to load: the variable's path or URL, else the file in the config directory
+pub fn policy_source() -> Option<String> {
+    if let Some(source) = env::var(POLICY_ENV).ok().filter(|s| !s.trim().is_empty()) {
+        return Some(source);
+    }
+    policy_file()
+        .filter(|path| path.exists())
+        .map(|path| path.display().to_string())
+}
+
+/// Get the path of the policy file in the user configuration directory
+pub fn policy_file() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join(POLICY_FILE_NAME))
+}
+
+/// Load the organization policy, if one is configured
+pub async fn load(http: &reqwest::Client) -> Result<Option<Policy>> {
+    match read_policy(http).await? {
+        Some((_, content)) => Policy::parse(&content).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Read the configured policy file, returning where it came from and its content
+///
+/// A policy from a URL is reused from the cache until its TTL runs out. When it
+/// can't be fetched the cached copy is used however old it is; with no copy at
+/// all this fails rather than carrying on without the policy.
+pub async fn read_policy(http: &reqwest::Client) -> Result<Option<(String, String)>> {
+    let Some(source) = policy_source() else {
+        return Ok(None);
+    };
+    if !is_url(&source) {
+        return read_policy_file(source).map(Some);
+    }
+
+    let cache_path = cache_file(POLICY_CACHE_FILE_NAME);
+    let cached = cache_path
+        .as_deref()
+        .and_then(load_cached_policy)
+        .filter(|cached| cached.source == source);
+    if let Some(cached) = &cached {
+        let fresh =
+            Policy::parse(&cached.content).is_ok_and(|policy| cached.is_fresh(&policy, now()));
+        if fresh {
+            return Ok(Some((source, cached.content.clone())));
+        }
+    }
+
+    match fetch_policy(http, &source).await {
+        Ok(content) => {
+            // A broken policy isn't cached, so the next run fetches it again
+            if let (Some(path), Ok(_)) = (&cache_path, Policy::parse(&content)) {
+                let cached = CachedPolicy {
+                    source: source.clone(),
+                    fetched_at: now(),
+                    content: content.clone(),
+                };
+                if let Err(e) = save_cached_policy(path, &cached) {
+                    warn!("Failed to cache the organization policy: {e}");
+                }
+            }
+            Ok(Some((source, content)))
+        }
+        Err(e) => {
+            let Some(cached) = cached else {
+                return Err(e.context(format!(
+                    "Failed to fetch the organization policy from {source}"
+                )));
+            };
+            warn!("Failed to refresh the organization policy, using the cached copy: {e}");
+            Ok(Some((source, cached.content)))
+        }
+    }
+}
+
+/// Load the organization policy without going to the network
+///
+/// A policy from a URL comes from the cache however old it is, and is left out
+/// when nothing is cached, so commands that make no requests stay fast offline.
+pub fn load_offline() -> Result<Option<Policy>> {
+    let Some(source) = policy_source() else {
+        return Ok(None);
+    };
+    let content = if is_url(&source) {
+        let cached = cache_file(POLICY_CACHE_FILE_NAME)
+            .as_deref()
+            .and_then(load_cached_policy)
+            .filter(|cached| cached.source == source);
+        match cached {
+            Some(cached) => cached.content,
+            None => return Ok(None),
+        }
+    } else {
+        read_policy_file(source)?.1
+    };
+    Policy::parse(&content).map(Some)
+}
+
+fn read_policy_file(source: String) -> Result<(String, String)> {
+    let content = fs::read_to_string(&source)
+        .with_context(|| format!("Failed to read the organization policy from {source}"))?;
+    Ok((source, content))
+}
+
+fn is_url(source: &str) -> bool {
+    source.starts_with("https://") || source.starts_with("http://")
+}
+
+async fn fetch_policy(http: &reqwest::Client, url: &str) -> Result<String> {
+    Ok(http
+        .get(url)
+        .header(reqwest::header::USER_AGENT, "committor")
+        .timeout(POLICY_FETCH_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?)
+}
+
+/// A policy fetched from a URL, as kept in the cache
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedPolicy {
+    /// URL the policy was fetched from
+    pub source: String,
+    /// When it was fetched, in seconds since the Unix epoch
+    pub fetched_at: u64,
+    /// The policy file as fetched
+    pub content: String,
+}
+
+impl CachedPolicy {
+    /// Check whether the copy is within the policy's TTL at `now`
+    pub fn is_fresh(&self, policy: &Policy, now: u64) -> bool {
+        now.saturating_sub(self.fetched_at) < policy.ttl_minutes.saturating_mul(60)
+    }
+}
+
+fn load_cached_policy(path: &Path) -> Option<CachedPolicy> {
+    let content = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn save_cached_policy(path: &Path, cached: &CachedPolicy) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    write_atomic(path, serde_json::to_string(cached)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> Policy {
+        Policy::parse(
+            r#"
+providers = ["ollama"]
+privacy = ["redacted", "local-only"]
+types = ["feat", "fix", "chore"]
+scopes = ["api", "cli"]
+trailers = ["Reviewed-by-policy: ai-assisted"]
+required_trailers = ["Signed-off-by"]
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_apply_narrows_settings() {
+        let mut settings = Settings::parse(
+            r#"
+[rules]
+allowed_scopes = ["api", "web"]
+
+[trailers]
+required = ["Signed-off-by", "Change-Id"]
+"#,
+        )
+        .unwrap();
+        policy().apply(&mut settings);
+
+        assert_eq!(settings.rules.allowed_scopes, vec!["api"]);
+        assert_eq!(settings.rules.allowed_types, vec!["feat", "fix", "chore"]);
+        assert_eq!(
+            settings.trailers.add,
+            vec!["Reviewed-by-policy: ai-assisted"]
+        );
+        assert_eq!(
+            settings.trailers.required,
+            vec!["Signed-off-by", "Change-Id"]
+        );
+
+        // Repository scopes outside the policy can't turn into "any scope"
+        let mut settings = Settings::parse("[rules]\nallowed_scopes = [\"web\"]").unwrap();
+        policy().apply(&mut settings);
+        assert_eq!(settings.rules.allowed_scopes, vec!["api", "cli"]);
+
+        // An empty policy changes nothing
+        let mut settings = Settings::default();
+        Policy::default().apply(&mut settings);
+        assert_eq!(settings, Settings::default());
+    }
+
+    #[test]
+    fn test_check_provider_and_privacy() {
+        let ollama = ProviderConfig::ollama("http://localhost:11434".to_string(), "m".to_string());
+        let openai = ProviderConfig::openai("key".to_string(), "m".to_string());
+
+        assert!(policy().check(&ollama, PrivacyLevel::Redacted).is_ok());
+        let error = policy()
+            .check(&openai, PrivacyLevel::Redacted)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("does not allow the openai provider"));
+        let error = policy()
+            .check(&ollama, PrivacyLevel::Full)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("redacted, local-only"));
+
+        assert!(Policy::default().check(&openai, PrivacyLevel::Full).is_ok());
+    }
+
+    #[test]
+    fn test_cached_policy_freshness() {
+        let cached = CachedPolicy {
+            source: "https://example.com/policy.toml".to_string(),
+            fetched_at: 1_000,
+            content: String::new(),
+        };
+        let policy = Policy {
+            ttl_minutes: 10,
+            ..Policy::default()
+        };
+        assert!(cached.is_fresh(&policy, 1_000 + 599));
+        assert!(!cached.is_fresh(&policy, 1_000 + 600));
+        assert!(is_url(&cached.source));
+        assert!(!is_url("/etc/committor/policy.toml"));
+    }
+
+    #[test]
+    fn test_invalid_policy() {
+        assert!(Policy::parse("providers = \"ollama\"").is_err());
+        assert!(Policy::parse("privacy = [\"secret\"]").is_err());
+    }
+}
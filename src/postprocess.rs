@@ -0,0 +1,407 @@
+//! Deterministic clean-up of common slips in generated commit subjects
+
+use crate::body::format_message_body;
+use crate::rules::Case;
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Past-tense and third-person verbs models commonly open a description with
+const VERB_MAP: &[(&str, &str)] = &[
+    ("added", "add"),
+    ("adds", "add"),
+    ("adding", "add"),
+    ("fixed", "fix"),
+    ("fixes", "fix"),
+    ("fixing", "fix"),
+    ("updated", "update"),
+    ("updates", "update"),
+    ("updating", "update"),
+    ("removed", "remove"),
+    ("removes", "remove"),
+    ("removing", "remove"),
+    ("changed", "change"),
+    ("changes", "change"),
+    ("implemented", "implement"),
+    ("implements", "implement"),
+    ("refactored", "refactor"),
+    ("refactors", "refactor"),
+    ("improved", "improve"),
+    ("improves", "improve"),
+    ("renamed", "rename"),
+    ("renames", "rename"),
+    ("moved", "move"),
+    ("moves", "move"),
+    ("created", "create"),
+    ("creates", "create"),
+    ("deleted", "delete"),
+    ("deletes", "delete"),
+    ("replaced", "replace"),
+    ("replaces", "replace"),
+    ("resolved", "resolve"),
+    ("resolves", "resolve"),
+    ("simplified", "simplify"),
+    ("simplifies", "simplify"),
+    ("optimized", "optimize"),
+    ("optimizes", "optimize"),
+    ("bumped", "bump"),
+    ("bumps", "bump"),
+    ("introduced", "introduce"),
+    ("introduces", "introduce"),
+    ("handled", "handle"),
+    ("handles", "handle"),
+    ("documented", "document"),
+    ("documents", "document"),
+    ("cleaned", "clean"),
+    ("cleans", "clean"),
+    ("supported", "support"),
+    ("supports", "support"),
+];
+
+/// Fix the subject line of a generated message and lay out any body
+///
+/// The description starts in lowercase unless `subject_case` asks for sentence case.
+pub fn normalize_message(message: &str, subject_case: Option<Case>) -> String {
+    let message = message.trim();
+    match message.split_once('\n') {
+        Some((subject, body)) => format_message_body(&format!(
+            "{}\n{}",
+            normalize_subject(subject, subject_case),
+            body
+        )),
+        None => normalize_subject(message, subject_case),
+    }
+}
+
+/// Strip wrapping quotes and trailing periods, and put the description in imperative mood
+pub fn normalize_subject(subject: &str, subject_case: Option<Case>) -> String {
+    let subject = strip_wrapping_quotes(subject.trim())
+        .trim_end_matches('.')
+        .trim_end();
+
+    // Only the description after a `<type>(<scope>)!: ` header is rewritten
+    let header_len = header_regex().find(subject).map_or(0, |m| m.end());
+    let (header, description) = subject.split_at(header_len);
+    let description = normalize_description(description);
+
+    match subject_case {
+        Some(Case::Sentence) => format!("{header}{}", recase_word(&description, Case::Sentence)),
+        _ => format!("{header}{description}"),
+    }
+}
+
+/// Remove quotes wrapping the whole subject, leaving quotes that belong to it
+///
+/// A subject such as `` `a` and `b` `` starts and ends with a quote without being
+/// wrapped, so a pair is only removed when that quote appears nowhere between them.
+fn strip_wrapping_quotes(mut subject: &str) -> &str {
+    while let Some(quote) = subject
+        .chars()
+        .next()
+        .filter(|c| matches!(c, '"' | '\'' | '`'))
+    {
+        match subject[1..].strip_suffix(quote) {
+            Some(inner) if !inner.contains(quote) => subject = inner.trim(),
+            _ => break,
+        }
+    }
+    subject
+}
+
+/// Match a `<type>(<scope>)!: ` header
+fn header_regex() -> &'static Regex {
+    static HEADER: OnceLock<Regex> = OnceLock::new();
+    HEADER.get_or_init(|| Regex::new(r"^[A-Za-z]+(\([^)]*\))?!?: ").unwrap())
+}
+
+/// Put the first word of a subject's description in a case style
+pub fn recase_subject(subject: &str, case: Case) -> String {
+    let header_len = header_regex().find(subject).map_or(0, |m| m.end());
+    let (header, description) = subject.split_at(header_len);
+    format!("{header}{}", recase_word(description, case))
+}
+
+/// Put the scope of a `<type>(<scope>)!: ` header in a case style, e.g. `feat(API):` as `feat(api):`
+pub fn recase_scope(subject: &str, case: Case) -> String {
+    static SCOPE: OnceLock<Regex> = OnceLock::new();
+    let scope_regex = SCOPE.get_or_init(|| Regex::new(r"^([A-Za-z]+\()([^)]*)(\)!?: )").unwrap());
+    scope_regex
+        .replace(subject, |captures: &regex::Captures| {
+            let scope = match case {
+                Case::Lower => captures[2].to_lowercase(),
+                Case::Sentence => capitalize(&captures[2].to_lowercase()),
+            };
+            format!("{}{scope}{}", &captures[1], &captures[3])
+        })
+        .into_owned()
+}
+
+/// Recase the start of a text, leaving acronyms like "API" alone in lowercase style
+fn recase_word(text: &str, case: Case) -> String {
+    match case {
+        Case::Lower => {
+            let (first_word, rest) = text.split_at(text.find(' ').unwrap_or(text.len()));
+            format!("{}{rest}", lowercase_capitalized(first_word))
+        }
+        Case::Sentence => capitalize(text),
+    }
+}
+
+/// Uppercase the first character of a text
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Lowercase a capitalized first word and map it to its imperative form
+fn normalize_description(description: &str) -> String {
+    let (first_word, rest) = match description.find(' ') {
+        Some(index) => description.split_at(index),
+        None => (description, ""),
+    };
+
+    let first_word = lowercase_capitalized(first_word);
+    let first_word = imperative_form(&first_word).map_or(first_word.clone(), str::to_string);
+
+    format!("{first_word}{rest}")
+}
+
+/// Get the imperative form of a past-tense or third-person verb, e.g. `add` for `added`
+pub fn imperative_form(word: &str) -> Option<&'static str> {
+    VERB_MAP
+        .iter()
+        .find(|(from, _)| *from == word)
+        .map(|(_, to)| *to)
+}
+
+/// Lowercase a word like "Add" while leaving acronyms like "API" alone
+fn lowercase_capitalized(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) if first.is_uppercase() && chars.all(|c| !c.is_uppercase()) => {
+            word.to_lowercase()
+        }
+        _ => word.to_string(),
+    }
+}
+
+/// Lowercase the type in a `<type>(<scope>)!: ` header, e.g. `Feat:` becomes `feat:`
+pub fn lowercase_type(subject: &str) -> String {
+    static TYPE: OnceLock<Regex> = OnceLock::new();
+    let type_regex = TYPE.get_or_init(|| Regex::new(r"^([A-Za-z]+)((\([^)]*\))?!?: )").unwrap());
+    type_regex
+        .replace(subject, |captures: &regex::Captures| {
+            format!("{}{}", captures[1].to_lowercase(), &captures[2])
+        })
+        .into_owned()
+}
+
+/// Remove emojis and leading `:shortcode:` gitmojis that are not in the allowed list
+pub fn strip_emojis(subject: &str, allowed: &[String]) -> String {
+    let (shortcodes, rest) = split_leading_shortcodes(subject);
+    let mut parts: Vec<String> = shortcodes
+        .into_iter()
+        .filter(|code| allowed.iter().any(|a| a == code))
+        .map(str::to_string)
+        .collect();
+
+    let mut text = String::with_capacity(rest.len());
+    // Set once an emoji is removed, to whether whitespace surrounded it
+    let mut removed: Option<bool> = None;
+    for (cluster, is_emoji) in emoji_clusters(rest) {
+        if is_emoji && !allowed.contains(&cluster) {
+            let spaced = text.ends_with(char::is_whitespace);
+            text.truncate(text.trim_end().len());
+            removed = Some(removed.unwrap_or(false) || spaced);
+            continue;
+        }
+        // Only the whitespace around a removed emoji is collapsed, the rest is left as written
+        let cluster = match removed.take() {
+            Some(spaced) => {
+                let trimmed = cluster.trim_start();
+                let spaced = spaced || trimmed.len() < cluster.len();
+                if spaced && !text.is_empty() && !trimmed.is_empty() {
+                    text.push(' ');
+                }
+                trimmed
+            }
+            None => &cluster,
+        };
+        text.push_str(cluster);
+    }
+    parts.push(text.trim().to_string());
+    parts.retain(|part| !part.is_empty());
+    parts.join(" ")
+}
+
+/// Find emojis and leading `:shortcode:` gitmojis that are not in the allowed list
+pub fn unknown_emojis(subject: &str, allowed: &[String]) -> Vec<String> {
+    let (shortcodes, rest) = split_leading_shortcodes(subject);
+    shortcodes
+        .into_iter()
+        .map(str::to_string)
+        .chain(
+            emoji_clusters(rest)
+                .into_iter()
+                .filter(|(_, is_emoji)| *is_emoji)
+                .map(|(cluster, _)| cluster),
+        )
+        .filter(|emoji| !allowed.contains(emoji))
+        .collect()
+}
+
+/// Split `:shortcode:` gitmojis off the start of a subject
+fn split_leading_shortcodes(subject: &str) -> (Vec<&str>, &str) {
+    static SHORTCODE: OnceLock<Regex> = OnceLock::new();
+    let shortcode_regex = SHORTCODE.get_or_init(|| Regex::new(r"^\s*(:[a-z0-9_+-]+:)\s*").unwrap());
+    let mut shortcodes = Vec::new();
+    let mut rest = subject.trim_start();
+
+    while let Some(captures) = shortcode_regex.captures(rest) {
+        shortcodes.push(captures.get(1).unwrap().as_str());
+        rest = &rest[captures.get(0).unwrap().end()..];
+    }
+
+    (shortcodes, rest)
+}
+
+/// Split text into runs of emoji and non-emoji characters
+fn emoji_clusters(text: &str) -> Vec<(String, bool)> {
+    let mut clusters: Vec<(String, bool)> = Vec::new();
+    for c in text.chars() {
+        let emoji = is_emoji(c);
+        // Joiners, variation selectors and skin tones attach to the preceding emoji
+        let joins = matches!(c, '\u{200D}' | '\u{FE0F}' | '\u{1F3FB}'..='\u{1F3FF}');
+        match clusters.last_mut() {
+            Some((cluster, true)) if emoji || joins => cluster.push(c),
+            Some((cluster, false)) if !emoji => cluster.push(c),
+            _ => clusters.push((c.to_string(), emoji)),
+        }
+    }
+    clusters
+}
+
+/// Check whether a character is in one of the common emoji blocks
+fn is_emoji(c: char) -> bool {
+    matches!(
+        c,
+        '\u{1F000}'..='\u{1FAFF}' | '\u{2600}'..='\u{27BF}' | '\u{2B00}'..='\u{2BFF}'
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_subject() {
+        assert_eq!(
+            normalize_subject("feat(auth): Added JWT validation.", None),
+            "feat(auth): add JWT validation"
+        );
+        assert_eq!(
+            normalize_subject("\"fix: Fixes crash on startup\"", None),
+            "fix: fix crash on startup"
+        );
+        assert_eq!(
+            normalize_subject("'`feat: add login`'", None),
+            "feat: add login"
+        );
+        assert_eq!(
+            normalize_subject("feat: add `verify` helper to `Token`", None),
+            "feat: add `verify` helper to `Token`"
+        );
+        assert_eq!(
+            normalize_subject("fix: quote \"name\"", None),
+            "fix: quote \"name\""
+        );
+        assert_eq!(
+            normalize_subject("docs!: Updates README", None),
+            "docs!: update README"
+        );
+        assert_eq!(
+            normalize_subject("refactor: API cleanup", None),
+            "refactor: API cleanup"
+        );
+        assert_eq!(
+            normalize_subject("feat: add login", None),
+            "feat: add login"
+        );
+        assert_eq!(
+            normalize_subject("feat(auth): added JWT validation", Some(Case::Sentence)),
+            "feat(auth): Add JWT validation"
+        );
+    }
+
+    #[test]
+    fn test_lowercase_type() {
+        assert_eq!(lowercase_type("Feat: add login"), "feat: add login");
+        assert_eq!(
+            lowercase_type("FIX(API)!: Handle it"),
+            "fix(API)!: Handle it"
+        );
+        assert_eq!(lowercase_type("Add login"), "Add login");
+    }
+
+    #[test]
+    fn test_recase_subject_and_scope() {
+        assert_eq!(
+            recase_subject("feat(api): add login", Case::Sentence),
+            "feat(api): Add login"
+        );
+        assert_eq!(
+            recase_subject("feat: Add login", Case::Lower),
+            "feat: add login"
+        );
+        assert_eq!(
+            recase_subject("feat: API login", Case::Lower),
+            "feat: API login"
+        );
+        assert_eq!(
+            recase_scope("feat(API)!: add login", Case::Lower),
+            "feat(api)!: add login"
+        );
+        assert_eq!(
+            recase_scope("feat(auth): add login", Case::Sentence),
+            "feat(Auth): add login"
+        );
+        assert_eq!(
+            recase_scope("feat: add login", Case::Lower),
+            "feat: add login"
+        );
+    }
+
+    #[test]
+    fn test_strip_emojis() {
+        let allowed = vec!["🚀".to_string(), ":bug:".to_string()];
+
+        assert_eq!(strip_emojis("✨ feat: add login", &[]), "feat: add login");
+        assert_eq!(
+            strip_emojis(":sparkles: feat: add login ❤️", &allowed),
+            "feat: add login"
+        );
+        assert_eq!(
+            strip_emojis(":bug: fix: stop crash 🚀", &allowed),
+            ":bug: fix: stop crash 🚀"
+        );
+        assert_eq!(strip_emojis("feat: add ✨  login", &[]), "feat: add login");
+        // Whitespace away from an emoji is left as written
+        assert_eq!(strip_emojis("feat:  add x", &[]), "feat:  add x");
+
+        assert_eq!(
+            unknown_emojis(":sparkles: feat: add 👍🏽 and 🚀", &allowed),
+            vec![":sparkles:", "👍🏽"]
+        );
+        assert!(unknown_emojis("feat: add login", &allowed).is_empty());
+    }
+
+    #[test]
+    fn test_normalize_message_keeps_body() {
+        assert_eq!(
+            normalize_message("feat: Added login.\n\nUsers can Now sign in.", None),
+            "feat: add login\n\nUsers can Now sign in."
+        );
+    }
+}
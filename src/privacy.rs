@@ -0,0 +1,270 @@
+//! Privacy levels controlling what leaves the machine when a prompt is built
+
+use crate::anonymize::Anonymizer;
+use crate::prompt::{contains_sensitive_info, PromptBuilder};
+use crate::providers::ProviderConfig;
+use crate::split::hunk_line_counts;
+use crate::types::{CommittorError, DiffChange, DiffHunk};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// How much of a change may be sent to the provider
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PrivacyLevel {
+    /// Send the diff, minus lines that look sensitive
+    #[default]
+    Full,
+    /// Send the diff with literals, emails, hostnames and IPs replaced by placeholders
+    Redacted,
+    /// Send only file paths, change types and line counts
+    PathsOnly,
+    /// Send the diff, but only to a provider running on this machine
+    LocalOnly,
+}
+
+impl PrivacyLevel {
+    /// Check whether prompts at this level may include file contents
+    pub fn sends_content(self) -> bool {
+        self != Self::PathsOnly
+    }
+
+    /// Check that the provider is allowed at this level
+    pub fn check_provider(self, provider: &ProviderConfig) -> Result<()> {
+        if self != Self::LocalOnly || provider.is_local() {
+            return Ok(());
+        }
+
+        Err(CommittorError::ConfigError(
+            "The local-only privacy level needs an Ollama provider on localhost".to_string(),
+        )
+        .into())
+    }
+
+    /// Fail with a clear message when an operation needs file contents this level won't send
+    pub fn require_content(self, operation: &str) -> Result<()> {
+        if self.sends_content() {
+            return Ok(());
+        }
+
+        Err(CommittorError::ConfigError(format!(
+            "{operation} needs file contents, which the {self} privacy level never sends"
+        ))
+        .into())
+    }
+}
+
+impl fmt::Display for PrivacyLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Full => "full",
+            Self::Redacted => "redacted",
+            Self::PathsOnly => "paths-only",
+            Self::LocalOnly => "local-only",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Applies a privacy level to everything sent for one request, and undoes it on the reply
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PrivacyFilter {
+    level: PrivacyLevel,
+    anonymizer: Anonymizer,
+}
+
+impl PrivacyFilter {
+    /// Create a filter for a privacy level
+    pub fn new(level: PrivacyLevel) -> Self {
+        Self {
+            level,
+            anonymizer: Anonymizer::new(),
+        }
+    }
+
+    /// Get the privacy level the filter applies
+    pub fn level(&self) -> PrivacyLevel {
+        self.level
+    }
+
+    /// Get the diff as it may leave the machine, or `None` when no contents may
+    pub fn diff(&mut self, diff: &str) -> Option<String> {
+        match self.level {
+            PrivacyLevel::Full | PrivacyLevel::LocalOnly => Some(diff.to_string()),
+            PrivacyLevel::Redacted => Some(self.anonymizer.anonymize_diff(diff)),
+            PrivacyLevel::PathsOnly => None,
+        }
+    }
+
+    /// Get a line of extra context, such as a CI step, as it may leave the machine
+    ///
+    /// Lines that look sensitive are dropped at every level, as the diff's are, and
+    /// the rest are anonymized at the redacted level.
+    pub fn context_line(&mut self, line: &str) -> Option<String> {
+        if contains_sensitive_info(line) {
+            return None;
+        }
+        match self.level {
+            PrivacyLevel::Full | PrivacyLevel::LocalOnly => Some(line.to_string()),
+            PrivacyLevel::Redacted => Some(self.anonymizer.anonymize_text(line)),
+            PrivacyLevel::PathsOnly => None,
+        }
+    }
+
+    /// Build the commit prompt for a diff, using only the change list when no contents may leave
+    pub fn commit_prompt(
+        &mut self,
+        diff: &str,
+        changes: &[DiffChange],
+        builder: &PromptBuilder,
+    ) -> String {
+        match self.diff(diff) {
+            Some(outgoing) => builder.commit_prompt(&outgoing),
+            None => builder.paths_only_prompt(changes),
+        }
+    }
+
+    /// Get hunks as they may leave the machine, reduced to line counts when no contents may
+    pub fn split_hunks(&mut self, hunks: &[DiffHunk]) -> Vec<DiffHunk> {
+        hunks
+            .iter()
+            .map(|hunk| DiffHunk {
+                text: hunk.text.as_ref().map(|text| match self.diff(text) {
+                    Some(outgoing) => outgoing,
+                    None => {
+                        let (additions, deletions) = hunk_line_counts(text);
+                        format!("+{additions} -{deletions} lines (contents not shown)")
+                    }
+                }),
+                ..hunk.clone()
+            })
+            .collect()
+    }
+
+    /// Put back any values that were replaced with placeholders
+    pub fn restore(&self, text: &str) -> String {
+        self.anonymizer.restore(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ci::{compare_ci_config, CiSystem};
+    use crate::types::DiffChangeType;
+    use std::time::Duration;
+
+    const DIFF: &str = "diff --git a/src/vault.rs b/src/vault.rs\n\
+--- a/src/vault.rs\n\
++++ b/src/vault.rs\n\
+@@ -1 +1,2 @@\n\
++fn rotate_keys() { connect(\"vault.prod.internal\", \"ops@example.com\"); }\n";
+
+    fn changes() -> Vec<DiffChange> {
+        vec![DiffChange {
+            file_path: "src/vault.rs".to_string(),
+            change_type: DiffChangeType::Modified,
+            additions: 1,
+            deletions: 0,
+        }]
+    }
+
+    fn hunks() -> Vec<DiffHunk> {
+        vec![DiffHunk {
+            file_path: "src/vault.rs".to_string(),
+            file_header: "diff --git a/src/vault.rs b/src/vault.rs".to_string(),
+            text: Some(DIFF.lines().skip(3).collect::<Vec<_>>().join("\n")),
+        }]
+    }
+
+    #[test]
+    fn test_redacted_prompts_hide_literals() {
+        let mut filter = PrivacyFilter::new(PrivacyLevel::Redacted);
+        let prompt = filter.commit_prompt(DIFF, &changes(), &PromptBuilder::new());
+        let split = format!("{:?}", filter.split_hunks(&hunks()));
+
+        for text in [&prompt, &split] {
+            assert!(!text.contains("vault.prod.internal"));
+            assert!(!text.contains("ops@example.com"));
+        }
+        assert!(prompt.contains("rotate_keys"));
+        assert_eq!(
+            filter.restore("fix: connect to <STRING_1>"),
+            "fix: connect to vault.prod.internal"
+        );
+    }
+
+    #[test]
+    fn test_paths_only_prompts_hide_contents() {
+        let mut filter = PrivacyFilter::new(PrivacyLevel::PathsOnly);
+        let prompt = filter.commit_prompt(DIFF, &changes(), &PromptBuilder::new());
+        let split = format!("{:?}", filter.split_hunks(&hunks()));
+
+        for text in [&prompt, &split] {
+            assert!(!text.contains("rotate_keys"));
+            assert!(!text.contains("vault.prod.internal"));
+        }
+        assert!(prompt.contains("src/vault.rs (+1 -0)"));
+        assert!(split.contains("+1 -0 lines"));
+        assert_eq!(filter.diff(DIFF), None);
+        assert!(PrivacyLevel::PathsOnly
+            .require_content("Evaluation")
+            .is_err());
+    }
+
+    #[test]
+    fn test_ci_steps_are_filtered() {
+        let old = "jobs:\n  deploy:\n    steps:\n      - run: make build\n";
+        let new = "jobs:\n  deploy:\n    steps:\n      - run: make build\n      \
+                   - run: 'curl -H \"Authorization: Bearer ghp_abc123\" https://api.example.com'\n";
+        let secret =
+            compare_ci_config(CiSystem::GitHubActions, ".github/workflows/a.yml", old, new)
+                .unwrap()
+                .to_string();
+        let new = "jobs:\n  deploy:\n    steps:\n      - run: make build\n      \
+                   - run: scp dist deploy.prod.internal:/srv\n";
+        let host = compare_ci_config(CiSystem::GitHubActions, ".github/workflows/b.yml", old, new)
+            .unwrap()
+            .to_string();
+
+        for level in [PrivacyLevel::Full, PrivacyLevel::Redacted] {
+            assert_eq!(PrivacyFilter::new(level).context_line(&secret), None);
+        }
+        let mut filter = PrivacyFilter::new(PrivacyLevel::Redacted);
+        let redacted = filter.context_line(&host).unwrap();
+        assert!(!redacted.contains("deploy.prod.internal"));
+        assert!(redacted.contains("added step"));
+        assert_eq!(
+            PrivacyFilter::new(PrivacyLevel::Full).context_line(&host),
+            Some(host)
+        );
+    }
+
+    #[test]
+    fn test_full_and_local_only_send_diff() {
+        for level in [PrivacyLevel::Full, PrivacyLevel::LocalOnly] {
+            let mut filter = PrivacyFilter::new(level);
+            let prompt = filter.commit_prompt(DIFF, &changes(), &PromptBuilder::new());
+            assert!(prompt.contains("rotate_keys"));
+        }
+    }
+
+    #[test]
+    fn test_local_only_requires_local_provider() {
+        let local = ProviderConfig::ollama("http://localhost:11434".to_string(), "m".to_string());
+        let loopback = ProviderConfig::ollama_with_timeout(
+            "http://127.0.0.1:11434/".to_string(),
+            "m".to_string(),
+            Duration::from_secs(30),
+        );
+        let remote = ProviderConfig::ollama("http://gpu-box:11434".to_string(), "m".to_string());
+        let cloud = ProviderConfig::openai("key".to_string(), "gpt-4".to_string());
+
+        assert!(PrivacyLevel::LocalOnly.check_provider(&local).is_ok());
+        assert!(PrivacyLevel::LocalOnly.check_provider(&loopback).is_ok());
+        assert!(PrivacyLevel::LocalOnly.check_provider(&remote).is_err());
+        assert!(PrivacyLevel::LocalOnly.check_provider(&cloud).is_err());
+        assert!(PrivacyLevel::Full.check_provider(&cloud).is_ok());
+    }
+}
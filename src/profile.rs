@@ -0,0 +1,226 @@
+//! What the whole repository is, as opposed to what one change touches
+//!
+//! Changed files alone mislead: a YAML tweak would make a Rust project look
+//! like YAML. The profile is read from every file at HEAD and cached in the git
+//! directory against HEAD's tree, so only a new tree recomputes it.
+
+use crate::paths::git_state_path;
+use crate::prompt::{language_for_extension, project_type_for_files, RepositoryContext};
+use crate::state::write_atomic;
+use crate::types::DiffChange;
+use anyhow::Result;
+use git2::{ObjectType, Repository, Tree, TreeWalkMode, TreeWalkResult};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the profile cache, kept in the git directory
+pub const PROFILE_CACHE_FILE_NAME: &str = "committor-cache";
+
+/// Version of the profile's detection; cached profiles from another version are recomputed
+pub const PROFILE_VERSION: u32 = 2;
+
+/// Facts about the repository as a whole
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepoProfile {
+    /// [`PROFILE_VERSION`] the profile was worked out with
+    #[serde(default)]
+    pub version: u32,
+    /// Tree the profile was worked out from; empty before the first commit
+    pub tree: String,
+    /// Language with the most files, if any file is in a known language
+    pub language: Option<String>,
+    /// Kind of project, e.g. `Rust Workspace` or `Terraform Project`
+    #[serde(default)]
+    pub project_type: Option<String>,
+}
+
+impl RepoProfile {
+    /// Build the context for contextual prompts, taking the language from the whole tree
+    ///
+    /// Falls back to the changed files when the tree has no file in a known language.
+    pub fn repository_context(&self, changes: &[DiffChange]) -> RepositoryContext {
+        RepositoryContext {
+            language: self
+                .language
+                .clone()
+                .unwrap_or_else(|| RepositoryContext::detect_language(changes)),
+            project_type: self
+                .project_type
+                .clone()
+                .unwrap_or_else(|| RepositoryContext::detect_project_type(changes)),
+            files_changed: changes.len().to_string(),
+            ..RepositoryContext::new()
+        }
+    }
+}
+
+/// Get the path of the profile cache for a repository
+pub fn profile_cache_path_from_repo(repo: &Repository) -> PathBuf {
+    git_state_path(repo, PROFILE_CACHE_FILE_NAME)
+}
+
+/// Get the profile of the repository containing the current directory
+pub fn repo_profile() -> Result<RepoProfile> {
+    let repo = Repository::discover(".")?;
+    repo_profile_from_repo(&repo)
+}
+
+/// Get a repository's profile, from the cache when HEAD's tree hasn't changed
+pub fn repo_profile_from_repo(repo: &Repository) -> Result<RepoProfile> {
+    // Nothing is committed yet, so there is no tree to profile
+    let Ok(tree) = repo.head().and_then(|head| head.peel_to_tree()) else {
+        return Ok(RepoProfile::default());
+    };
+
+    let path = profile_cache_path_from_repo(repo);
+    let tree_id = tree.id().to_string();
+    if let Some(cached) = load_profile(&path)
+        .filter(|profile| profile.version == PROFILE_VERSION && profile.tree == tree_id)
+    {
+        return Ok(cached);
+    }
+
+    let files = tree_files(&tree)?;
+    let read_root_file = |name: &str| {
+        let blob = tree.get_path(Path::new(name)).ok()?.to_object(repo).ok()?;
+        let content = blob.as_blob()?.content();
+        Some(String::from_utf8_lossy(content).into_owned())
+    };
+    let file_refs: Vec<&str> = files.iter().map(String::as_str).collect();
+    let profile = RepoProfile {
+        version: PROFILE_VERSION,
+        tree: tree_id,
+        language: tree_language(&file_refs),
+        project_type: Some(project_type_for_files(&file_refs, read_root_file)),
+    };
+    // The cache only saves time; a read-only git directory is no reason to fail
+    let _ = write_atomic(&path, serde_json::to_string_pretty(&profile)?);
+    Ok(profile)
+}
+
+fn load_profile(path: &Path) -> Option<RepoProfile> {
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+/// List the path of every file in a tree
+fn tree_files(tree: &Tree) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+    tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+        if entry.kind() == Some(ObjectType::Blob) {
+            if let Some(name) = entry.name() {
+                files.push(format!("{root}{name}"));
+            }
+        }
+        TreeWalkResult::Ok
+    })?;
+    Ok(files)
+}
+
+/// Find the language most files are written in
+fn tree_language(files: &[&str]) -> Option<String> {
+    let mut counts: BTreeMap<&'static str, usize> = BTreeMap::new();
+    for file in files {
+        let language = Path::new(file)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(language_for_extension);
+        if let Some(language) = language {
+            *counts.entry(language).or_default() += 1;
+        }
+    }
+
+    // Ties go to the name that sorts first, so the answer is stable
+    counts
+        .into_iter()
+        .rev()
+        .max_by_key(|(_, count)| *count)
+        .map(|(language, _)| language.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fixtures::{git, init_repo};
+    use crate::types::DiffChangeType;
+
+    fn commit_files(dir: &Path, files: &[&str]) {
+        for file in files {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, "content\n").unwrap();
+        }
+        git(dir, &["add", "."]);
+        git(dir, &["commit", "-q", "-m", "init"]);
+    }
+
+    #[test]
+    fn test_language_comes_from_the_whole_tree() -> Result<()> {
+        let dir = init_repo();
+        let repo = Repository::open(dir.path())?;
+        assert_eq!(repo_profile_from_repo(&repo)?, RepoProfile::default());
+
+        commit_files(
+            dir.path(),
+            &[
+                "Cargo.toml",
+                "src/main.rs",
+                "src/lib.rs",
+                "src/cli/args.rs",
+                "ci.yml",
+                "build.py",
+            ],
+        );
+        let profile = repo_profile_from_repo(&repo)?;
+        assert_eq!(profile.language.as_deref(), Some("Rust"));
+        assert_eq!(profile.project_type.as_deref(), Some("Rust Project"));
+
+        let yaml_tweak = vec![DiffChange {
+            file_path: "ci.yml".to_string(),
+            change_type: DiffChangeType::Modified,
+            additions: 1,
+            deletions: 1,
+        }];
+        let context = profile.repository_context(&yaml_tweak);
+        assert_eq!(context.language, "Rust");
+        assert_eq!(context.project_type, "Rust Project");
+        assert_eq!(context.files_changed, "1");
+        Ok(())
+    }
+
+    #[test]
+    fn test_profile_is_cached_per_tree() -> Result<()> {
+        let dir = init_repo();
+        let repo = Repository::open(dir.path())?;
+        commit_files(dir.path(), &["main.go"]);
+
+        let profile = repo_profile_from_repo(&repo)?;
+        assert_eq!(profile.language.as_deref(), Some("Go"));
+
+        // A cache entry for the current tree is trusted as it is
+        let cached = RepoProfile {
+            language: Some("Zig".to_string()),
+            ..profile.clone()
+        };
+        let path = profile_cache_path_from_repo(&repo);
+        fs::write(&path, serde_json::to_string(&cached)?)?;
+        assert_eq!(repo_profile_from_repo(&repo)?, cached);
+
+        // One for another tree, or from another version of the detection, is recomputed
+        let stale = RepoProfile {
+            tree: "0".repeat(40),
+            ..cached.clone()
+        };
+        fs::write(&path, serde_json::to_string(&stale)?)?;
+        assert_eq!(repo_profile_from_repo(&repo)?, profile);
+
+        let old = RepoProfile {
+            version: 1,
+            ..cached
+        };
+        fs::write(&path, serde_json::to_string(&old)?)?;
+        assert_eq!(repo_profile_from_repo(&repo)?, profile);
+        Ok(())
+    }
+}
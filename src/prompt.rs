@@ -1,21 +1,440 @@
 //! Prompt generation for AI-powered commit message creation
 
-use crate::types::{CommitType, DiffChange};
+use crate::diff::truncate_line;
+use crate::migrations::MigrationSettings;
+use crate::rules::{Case, Rules};
+use crate::secrets::contains_secret;
+use crate::symbols::MAX_PROMPT_SYMBOLS;
+use crate::timing;
+use crate::types::{CommitType, DiffChange, DiffChangeType, DiffHunk, HeadState};
+use crate::validate::MAX_SUBJECT_LENGTH;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Version of the prompt templates, bumped whenever their wording changes
+///
+/// Saved suggestions from another version are discarded, and eval reports and
+/// audit records carry it so quality shifts can be traced to prompt changes.
+pub const PROMPT_VERSION: u32 = 1;
+
+/// Line put in place of a diff line that looked sensitive
+pub const REMOVED_LINE_NOTE: &str = "... (line with sensitive info removed)";
+
+/// Diff lines included in a prompt when the budget isn't set
+pub const DEFAULT_MAX_DIFF_LINES: usize = 100;
+
+/// Length a diff line is cut to when the budget isn't set
+pub const DEFAULT_MAX_LINE_LENGTH: usize = 150;
+
+/// How much guidance the commit prompt gives the model
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PromptStyle {
+    /// Describe every commit type and include worked examples
+    #[default]
+    Detailed,
+    /// List the commit types without descriptions or examples, for small context windows
+    Concise,
+}
 
-/// Create a detailed prompt for generating conventional commit messages
-pub fn create_commit_prompt(diff: &str) -> String {
-    let sanitized_diff = sanitize_diff_for_prompt(diff);
+/// How much of a diff may go into a prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PromptBudget {
+    /// Diff lines kept before the rest is cut off
+    pub max_lines: usize,
+    /// Length in characters each kept line is cut to
+    pub max_line_length: usize,
+}
 
-    format!(
-        r#"You are an expert software engineer who writes clear, concise conventional commit messages.
+impl Default for PromptBudget {
+    fn default() -> Self {
+        Self {
+            max_lines: DEFAULT_MAX_DIFF_LINES,
+            max_line_length: DEFAULT_MAX_LINE_LENGTH,
+        }
+    }
+}
 
-Based on the following git diff, generate a single conventional commit message that follows these rules:
+/// Languages read from the `[language]` settings section, English when unset
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LanguageSettings {
+    /// Language of the subject's description; the type and scope stay in English
+    pub subject: Option<String>,
+    /// Language of the body, independent of the subject's
+    pub body: Option<String>,
+}
+
+/// Builds the prompts sent to the provider
+///
+/// The free `create_*_prompt` functions use the default builder.
+///
+/// # Example
+///
+/// ```
+/// use committor::prompt::{PromptBudget, PromptBuilder, PromptStyle};
+///
+/// let prompt = PromptBuilder::new()
+///     .style(PromptStyle::Concise)
+///     .language("German")
+///     .budget(PromptBudget { max_lines: 40, max_line_length: 120 })
+///     .commit_prompt("+fn login() {}");
+/// assert!(prompt.contains("German"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PromptBuilder {
+    style: PromptStyle,
+    language: Option<String>,
+    body: bool,
+    body_language: Option<String>,
+    subject_case: Option<Case>,
+    scope_case: Option<Case>,
+    symbols_in_body: bool,
+    migrations: MigrationSettings,
+    budget: PromptBudget,
+    context: CommitContext,
+}
+
+impl PromptBuilder {
+    /// Create a builder with the default style and budget, writing in English
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how much guidance the prompt gives
+    pub fn style(mut self, style: PromptStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Ask for the description to be written in a language other than English
+    pub fn language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    /// Ask for a body explaining the change after the subject
+    pub fn with_body(mut self, body: bool) -> Self {
+        self.body = body;
+        self
+    }
+
+    /// Ask for the body to be written in a language other than English
+    ///
+    /// Independent of [`PromptBuilder::language`], so tooling can read English subjects
+    /// while people read bodies in their own language.
+    pub fn body_language(mut self, language: impl Into<String>) -> Self {
+        self.body_language = Some(language.into());
+        self
+    }
+
+    /// Ask for the changed public symbols to be listed as bullets in the body
+    pub fn symbols_in_body(mut self, symbols_in_body: bool) -> Self {
+        self.symbols_in_body = symbols_in_body;
+        self
+    }
+
+    /// Classify schema work by the `[migrations]` settings section
+    pub fn migrations(mut self, settings: &MigrationSettings) -> Self {
+        self.migrations = settings.clone();
+        self
+    }
+
+    /// Apply the subject and body languages from the `[language]` settings section
+    pub fn languages(mut self, settings: &LanguageSettings) -> Self {
+        self.language = settings.subject.clone();
+        self.body_language = settings.body.clone();
+        self
+    }
+
+    /// Ask for the description and scope case styles set in the `[rules]` settings section
+    pub fn casing(mut self, rules: &Rules) -> Self {
+        self.subject_case = rules.subject_case;
+        self.scope_case = rules.scope_case;
+        self
+    }
+
+    /// Set how much of the diff may go into the prompt
+    pub fn budget(mut self, budget: PromptBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Include branch history, the previous subject and similar commits
+    pub fn context(mut self, context: CommitContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Cut a diff to the budget and drop lines that look sensitive
+    pub fn sanitize(&self, diff: &str) -> String {
+        let _span = tracing::info_span!(timing::SANITIZE).entered();
+        // Sized for the budget rather than the diff, which may be far larger than what's kept
+        let budgeted_len = self.budget.max_lines.saturating_mul(
+            self.budget
+                .max_line_length
+                .saturating_add(TRUNCATED_LINE_NOTE.len()),
+        );
+        let mut sanitized = String::with_capacity(diff.len().min(budgeted_len));
+        let mut line_count = 0;
+
+        for line in diff.lines() {
+            if line_count >= self.budget.max_lines {
+                sanitized.push_str("... (diff truncated for brevity)\n");
+                break;
+            }
+
+            // Skip lines that might contain sensitive information
+            if contains_sensitive_info(line) {
+                sanitized.push_str(REMOVED_LINE_NOTE);
+                sanitized.push('\n');
+                continue;
+            }
+
+            // Truncate very long lines
+            if let Some(truncated) = truncate_line(line, self.budget.max_line_length) {
+                sanitized.push_str(truncated);
+                sanitized.push_str(TRUNCATED_LINE_NOTE);
+            } else {
+                sanitized.push_str(line);
+                sanitized.push('\n');
+            }
+
+            line_count += 1;
+        }
+
+        sanitized
+    }
+
+    /// Build the prompt for a single commit message from a diff
+    pub fn commit_prompt(&self, diff: &str) -> String {
+        let sanitized_diff = self.sanitize(diff);
+        let changes_section = format!("## Git Diff:\n```\n{sanitized_diff}\n```");
+        self.commit_prompt_for("git diff", &changes_section)
+    }
+
+    /// Build the prompt for a single commit message from file paths, change types and line counts
+    ///
+    /// No file contents are included, for repositories whose source must not leave the machine.
+    pub fn paths_only_prompt(&self, changes: &[DiffChange]) -> String {
+        let files = changes
+            .iter()
+            .map(|change| {
+                format!(
+                    "- {} {} (+{} -{})",
+                    change.change_type, change.file_path, change.additions, change.deletions
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let changes_section = format!("## Changed files:\n{files}");
+        self.commit_prompt_for(
+            "list of changed files (their contents are not available)",
+            &changes_section,
+        )
+    }
+
+    /// Build the prompt for a single commit message from a description of the change
+    ///
+    /// For when there is no diff to read, such as a patch someone else sent.
+    pub fn description_prompt(&self, description: &str) -> String {
+        let changes_section = format!("## Description of the change:\n{}", description.trim());
+        self.commit_prompt_for(
+            "description of a change, written by its author",
+            &changes_section,
+        )
+    }
+
+    /// Build the prompt for the message of a commit whose merge conflicts were resolved
+    ///
+    /// The original message is a starting point: the resolution may have changed what
+    /// the commit does, and the diff is what it does now.
+    pub fn conflict_prompt(&self, original: &str, conflicts: &[String], diff: &str) -> String {
+        let sanitized_diff = self.sanitize(diff);
+        let conflicts_section = if conflicts.is_empty() {
+            String::new()
+        } else {
+            let paths = conflicts
+                .iter()
+                .map(|path| format!("- {path}"))
+                .collect::<Vec<_>>()
+                .join("\n");
+            format!("## Conflicts were resolved in:\n{paths}\n\n")
+        };
+        let changes_section = format!(
+            "## Original commit message:\n{}\n\n{conflicts_section}## Git Diff after resolving:\n```\n{sanitized_diff}\n```",
+            original.trim()
+        );
+        self.commit_prompt_for(
+            "original commit message and the diff of the commit after its merge conflicts were resolved (keep the original meaning where the diff still matches it)",
+            &changes_section,
+        )
+    }
+
+    /// Build the prompt for the message of a commit cherry-picked onto the current branch
+    ///
+    /// The original message was written for another branch, so its scope is swapped
+    /// for the closest of the scopes this branch uses.
+    pub fn cherry_pick_prompt(&self, original: &str, scopes: &[String], diff: &str) -> String {
+        let sanitized_diff = self.sanitize(diff);
+        let scopes_section = if scopes.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "## Scopes used on this branch (use the closest one instead of a different scope):\n{}\n\n",
+                scopes.join(", ")
+            )
+        };
+        let changes_section = format!(
+            "## Original commit message:\n{}\n\n{scopes_section}## Git Diff:\n```\n{sanitized_diff}\n```",
+            original.trim()
+        );
+        self.commit_prompt_for(
+            "commit cherry-picked from another branch, with the message it had there (keep its meaning, but follow this branch's conventions)",
+            &changes_section,
+        )
+    }
+
+    /// Build the prompt asking for several commit message options at once
+    pub fn multiple_commit_prompt(&self, diff: &str, count: u8) -> String {
+        let sanitized_diff = self.sanitize(diff);
+        let types = self.types_section("Types:");
+        let language = self.language_guideline(7);
+
+        format!(
+            r#"You are an expert software engineer who writes clear, concise conventional commit messages.
+
+Based on the following git diff, generate {count} different conventional commit message options that follow these rules:
+
+## Format
+<type>(<scope>): <description>
+
+{types}
+## Guidelines:
+1. Each message under 50 characters
+2. Use imperative mood
+3. No period at the end
+4. Optional but useful scope
+5. Focus on WHAT changed
+6. Provide variety in scope and perspective
+{language}
+## Git Diff:
+```
+{sanitized_diff}
+```
+
+Generate {count} different conventional commit messages (one per line, no numbering or explanation):"#
+        )
+    }
+
+    /// Lay out the commit prompt around a description of the changes
+    fn commit_prompt_for(&self, source: &str, changes_section: &str) -> String {
+        let history_section = branch_history_section(&self.context.branch_commits);
+        let previous_section = previous_subject_section(self.context.previous_subject.as_deref());
+        let similar_section = similar_commits_section(&self.context.similar_commits);
+        let symbols_section = changed_symbols_section(&self.context.changed_symbols);
+        let dependencies_section = dependency_changes_section(&self.context.dependency_changes);
+        let migrations_section =
+            migration_files_section(&self.context.migration_files, &self.migrations);
+        let ci_section = ci_changes_section(&self.context.ci_changes);
+        let assets_section = binary_assets_section(&self.context.binary_assets);
+        let feedback_section = feedback_guidance_section(&self.context.feedback_guidance);
+        let types = self.types_section("Types (choose the most appropriate):");
+        let language = self.language_guideline(7);
+        let examples = match self.style {
+            PromptStyle::Detailed => COMMIT_EXAMPLES,
+            PromptStyle::Concise => "",
+        };
+        let request = if self.body {
+            "Generate ONE conventional commit message: the subject line, a blank line, then the body (no other explanation):"
+        } else {
+            "Generate ONE conventional commit message (only the message, no explanation):"
+        };
+
+        format!(
+            r#"You are an expert software engineer who writes clear, concise conventional commit messages.
+
+Based on the following {source}, generate a single conventional commit message that follows these rules:
 
 ## Format
 <type>(<scope>): <description>
 
-## Types (choose the most appropriate):
-- feat: A new feature for the user
+{types}
+## Guidelines:
+1. Keep the description under 50 characters
+2. Use imperative mood ("add" not "added" or "adds")
+3. No period at the end
+4. Make scope optional but useful (component, module, file area)
+5. Focus on WHAT changed, not HOW it was implemented
+6. If multiple changes, choose the most significant one
+{language}{examples}{similar_section}{history_section}{previous_section}{symbols_section}{dependencies_section}{migrations_section}{ci_section}{assets_section}{feedback_section}
+{changes_section}
+
+{request}"#
+        )
+    }
+
+    /// Render the commit types under a heading, with descriptions in the detailed style
+    fn types_section(&self, heading: &str) -> String {
+        match self.style {
+            PromptStyle::Detailed => format!("## {heading}\n{COMMIT_TYPE_DESCRIPTIONS}"),
+            PromptStyle::Concise => format!(
+                "## {heading}\nfeat, fix, docs, style, refactor, test, chore, perf, ci, build\n"
+            ),
+        }
+    }
+
+    /// Render the case, language and body guidelines numbered from `number`, empty when none apply
+    fn language_guideline(&self, number: usize) -> String {
+        let mut guidelines = Vec::new();
+        match self.subject_case {
+            Some(Case::Lower) => guidelines
+                .push("Start the description in lowercase, e.g. \"feat: add login\"".to_string()),
+            Some(Case::Sentence) => guidelines.push(
+                "Start the description with a capital letter, e.g. \"feat: Add login\"".to_string(),
+            ),
+            None => {}
+        }
+        match self.scope_case {
+            Some(Case::Lower) => guidelines
+                .push("Write the scope in lowercase, e.g. \"feat(auth): ...\"".to_string()),
+            Some(Case::Sentence) => guidelines.push(
+                "Start the scope with a capital letter, e.g. \"feat(Auth): ...\"".to_string(),
+            ),
+            None => {}
+        }
+        if let Some(language) = &self.language {
+            guidelines.push(format!(
+                "Write the description in {language}; keep the type and scope in English"
+            ));
+        }
+        if self.body {
+            guidelines.push(
+                "After the subject, add a blank line and a short body explaining why the change was made, wrapped at 72 columns".to_string(),
+            );
+            if self.symbols_in_body && !self.context.changed_symbols.is_empty() {
+                guidelines.push(
+                    "In the body, list each changed public symbol as a `- ` bullet, e.g. \"- add `TokenValidator::verify`\"".to_string(),
+                );
+            }
+            if let Some(language) = &self.body_language {
+                guidelines.push(format!(
+                    "Write the body in {language}, whatever language the subject is in"
+                ));
+            }
+        }
+
+        guidelines
+            .iter()
+            .enumerate()
+            .map(|(i, guideline)| format!("{}. {guideline}\n", number + i))
+            .collect()
+    }
+}
+
+/// Commit types with descriptions, as listed in the detailed prompt style
+const COMMIT_TYPE_DESCRIPTIONS: &str = "- feat: A new feature for the user
 - fix: A bug fix
 - docs: Documentation only changes
 - style: Changes that don't affect code meaning (formatting, missing semi-colons, etc.)
@@ -25,15 +444,10 @@ Based on the following git diff, generate a single conventional commit message t
 - perf: Code change that improves performance
 - ci: Changes to CI configuration files and scripts
 - build: Changes that affect the build system or external dependencies
+";
 
-## Guidelines:
-1. Keep the description under 50 characters
-2. Use imperative mood ("add" not "added" or "adds")
-3. No period at the end
-4. Make scope optional but useful (component, module, file area)
-5. Focus on WHAT changed, not HOW it was implemented
-6. If multiple changes, choose the most significant one
-
+/// Worked examples included in the detailed prompt style
+const COMMIT_EXAMPLES: &str = "
 ## Examples:
 - feat(auth): add JWT token validation
 - fix(database): resolve connection timeout
@@ -44,54 +458,432 @@ Based on the following git diff, generate a single conventional commit message t
 - perf(queries): optimize database indexes
 - ci(github): add automated testing
 - build(webpack): configure production build
+";
 
-## Git Diff:
-```
-{sanitized_diff}
-```
+/// Create a detailed prompt for generating conventional commit messages
+pub fn create_commit_prompt(diff: &str) -> String {
+    PromptBuilder::new().commit_prompt(diff)
+}
+
+/// Create a commit prompt that also carries history from the current branch
+pub fn create_commit_prompt_with_context(diff: &str, context: &CommitContext) -> String {
+    PromptBuilder::new()
+        .context(context.clone())
+        .commit_prompt(diff)
+}
+
+/// Create a commit prompt from file paths, change types and line counts alone
+///
+/// No file contents are included, for repositories whose source must not leave the machine.
+pub fn create_paths_only_prompt(changes: &[DiffChange], context: &CommitContext) -> String {
+    PromptBuilder::new()
+        .context(context.clone())
+        .paths_only_prompt(changes)
+}
+
+/// Render earlier branch commits as a prompt section, empty when there are none
+fn branch_history_section(branch_commits: &[String]) -> String {
+    if branch_commits.is_empty() {
+        return String::new();
+    }
+
+    let commits = branch_commits
+        .iter()
+        .map(|subject| format!("- {subject}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        r#"
+## Earlier commits on this branch (newest first):
+{commits}
+
+Continue this narrative: describe only what the new diff adds and do not repeat what these commits already said.
+"#
+    )
+}
+
+/// Render messages of similar past commits as style examples, empty when there are none
+fn similar_commits_section(similar_commits: &[String]) -> String {
+    if similar_commits.is_empty() {
+        return String::new();
+    }
+
+    let commits = similar_commits
+        .iter()
+        .map(|subject| format!("- {subject}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        r#"
+## Messages of similar past commits in this repository:
+{commits}
+
+Match their wording, type and scope conventions where they fit this change.
+"#
+    )
+}
+
+/// Render the changed public symbols as a prompt section, empty when there are none
+fn changed_symbols_section(changed_symbols: &[String]) -> String {
+    if changed_symbols.is_empty() {
+        return String::new();
+    }
+
+    let mut symbols = changed_symbols
+        .iter()
+        .take(MAX_PROMPT_SYMBOLS)
+        .map(|symbol| format!("- {symbol}"))
+        .collect::<Vec<_>>();
+    if changed_symbols.len() > MAX_PROMPT_SYMBOLS {
+        symbols.push(format!(
+            "- ... and {} more",
+            changed_symbols.len() - MAX_PROMPT_SYMBOLS
+        ));
+    }
+    let symbols = symbols.join("\n");
+
+    format!(
+        r#"
+## Changed public symbols:
+{symbols}
 
-Generate ONE conventional commit message (only the message, no explanation):"#
+Name the most significant of these in the description rather than a vague area such as "module".
+"#
     )
 }
 
+/// Render the staged dependency changes as a prompt section, empty when there are none
+fn dependency_changes_section(dependency_changes: &[String]) -> String {
+    if dependency_changes.is_empty() {
+        return String::new();
+    }
+
+    let changes = dependency_changes
+        .iter()
+        .map(|change| format!("- {change}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        r#"
+## Dependency changes:
+{changes}
+
+If updating dependencies is the main change, use the `build(deps)` type and scope and name the dependency and its new version, e.g. "build(deps): bump reqwest to 0.12".
+"#
+    )
+}
+
+/// Render the changed migration files with how to classify schema work, empty when there are none
+fn migration_files_section(migration_files: &[String], settings: &MigrationSettings) -> String {
+    if migration_files.is_empty() {
+        return String::new();
+    }
+
+    let files = migration_files
+        .iter()
+        .map(|file| format!("- {file}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let classification = settings.classification();
+
+    format!(
+        r#"
+## Database migrations:
+{files}
+
+Say what the migrations do to the schema (tables, columns, indexes, constraints) rather than just "update sql". {classification}
+"#
+    )
+}
+
+/// Render the CI configuration changes as a prompt section, empty when there are none
+fn ci_changes_section(ci_changes: &[String]) -> String {
+    if ci_changes.is_empty() {
+        return String::new();
+    }
+
+    let changes = ci_changes
+        .iter()
+        .map(|change| format!("- {change}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        r#"
+## CI configuration changes:
+{changes}
+
+If CI is the main change, use the `ci` type and name the affected workflow or job, e.g. "ci(release): add macOS build job".
+"#
+    )
+}
+
+/// Render the staged binary files as a prompt section, empty when there are none
+fn binary_assets_section(binary_assets: &[String]) -> String {
+    if binary_assets.is_empty() {
+        return String::new();
+    }
+
+    let assets = binary_assets
+        .iter()
+        .map(|asset| format!("- {asset}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        r#"
+## Binary files:
+{assets}
+
+Their contents can't be shown; describe them from their names, kinds and count, e.g. "feat(assets): add 3 onboarding illustrations".
+"#
+    )
+}
+
+/// Render the preferences learned from the user's ratings, empty when there are none
+fn feedback_guidance_section(feedback_guidance: &[String]) -> String {
+    if feedback_guidance.is_empty() {
+        return String::new();
+    }
+
+    let guidance = feedback_guidance
+        .iter()
+        .map(|guideline| format!("- {guideline}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        r#"
+## The user's preferences:
+{guidance}
+
+These come from suggestions the user accepted and rejected before; follow them unless they contradict the rules above.
+"#
+    )
+}
+
+/// Render the previous commit subject as a prompt section, empty when there is none
+fn previous_subject_section(previous_subject: Option<&str>) -> String {
+    match previous_subject {
+        Some(subject) if !subject.trim().is_empty() => format!(
+            r#"
+## Previous commit:
+{subject}
+
+Do not produce the same or a near-identical message; describe what is different about this change.
+"#
+        ),
+        _ => String::new(),
+    }
+}
+
 /// Create a prompt for generating multiple commit message options
 pub fn create_multiple_commit_prompt(diff: &str, count: u8) -> String {
+    PromptBuilder::new().multiple_commit_prompt(diff, count)
+}
+
+/// Create a prompt for describing work-in-progress changes as a stash message
+pub fn create_stash_prompt(diff: &str) -> String {
     let sanitized_diff = sanitize_diff_for_prompt(diff);
 
     format!(
-        r#"You are an expert software engineer who writes clear, concise conventional commit messages.
+        r#"You are an expert software engineer describing work-in-progress changes that are about to be stashed.
 
-Based on the following git diff, generate {count} different conventional commit message options that follow these rules:
-
-## Format
-<type>(<scope>): <description>
-
-## Types:
-- feat: A new feature
-- fix: A bug fix
-- docs: Documentation changes
-- style: Formatting changes
-- refactor: Code restructuring
-- test: Test additions/changes
-- chore: Maintenance tasks
-- perf: Performance improvements
-- ci: CI/CD changes
-- build: Build system changes
+Based on the following git diff, write a short stash message that will help the author recognize this work later.
 
 ## Guidelines:
-1. Each message under 50 characters
-2. Use imperative mood
-3. No period at the end
-4. Optional but useful scope
-5. Focus on WHAT changed
-6. Provide variety in scope and perspective
+1. Keep it under 60 characters
+2. Describe what the work is about, not the fact that it is unfinished
+3. Do not use a conventional commit type prefix
+4. No period at the end
 
 ## Git Diff:
 ```
 {sanitized_diff}
 ```
 
-Generate {count} different conventional commit messages (one per line, no numbering or explanation):"#
+Generate ONE stash message (only the message, no explanation):"#
+    )
+}
+
+/// Create a prompt for summarizing commits into an annotated tag message
+pub fn create_tag_prompt(
+    tag_name: &str,
+    previous_tag: Option<&str>,
+    subjects: &[String],
+) -> String {
+    let range = match previous_tag {
+        Some(previous) => format!("since {previous}"),
+        None => "since the start of the project".to_string(),
+    };
+    let commits = subjects
+        .iter()
+        .map(|subject| format!("- {subject}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        r#"You are an expert software engineer writing the annotation for the git tag {tag_name}.
+
+Summarize the following commits {range} into an annotated tag message.
+
+## Format
+<one-line summary of the release>
+
+<grouped bullet points of notable changes>
+
+## Guidelines:
+1. Keep the summary line under 72 characters
+2. Group related changes (features, fixes, other) and merge near-duplicates
+3. Leave out purely internal noise such as formatting or CI tweaks unless nothing else changed
+4. Plain text only, no markdown headings
+
+## Commits:
+{commits}
+
+Generate the tag message (only the message, no explanation):"#
+    )
+}
+
+/// Maximum number of lines shown for each hunk in the split prompt
+const SPLIT_HUNK_MAX_LINES: usize = 40;
+
+/// Create a prompt asking for test cases for source changes that came without tests
+///
+/// Without a diff, as at the `paths-only` privacy level, only the file names are given.
+pub fn create_test_suggestions_prompt(diff: Option<&str>, sources: &[String]) -> String {
+    let files = sources
+        .iter()
+        .map(|path| format!("- {path}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let diff_section = match diff {
+        Some(diff) => format!(
+            "\n\n## Git Diff:\n```\n{}\n```",
+            sanitize_diff_for_prompt(diff)
+        ),
+        None => String::new(),
+    };
+
+    format!(
+        r#"You are an experienced software engineer reviewing a change that modifies source files but adds or changes no tests.
+
+Suggest the test cases that would cover what this change does.
+
+## Guidelines:
+1. One test case per line, starting with "- "
+2. Name the behaviour to check, e.g. "- rejects an expired token with 401"
+3. Cover edge cases and error paths, not only the happy path
+4. At most 5 test cases, most important first
+
+## Changed source files:
+{files}{diff_section}
+
+Generate the test cases (only the lines, no explanation):"#
+    )
+}
+
+/// Maximum number of lines shown for each hunk in the review prompt
+const REVIEW_HUNK_MAX_LINES: usize = 80;
+
+/// Create a prompt asking for reviewer-style comments on each staged hunk
+pub fn create_review_prompt(hunks: &[DiffHunk]) -> String {
+    let hunks_text = hunks
+        .iter()
+        .enumerate()
+        .map(|(i, hunk)| {
+            let body = match &hunk.text {
+                Some(text) => {
+                    let lines: Vec<&str> = text.lines().take(REVIEW_HUNK_MAX_LINES).collect();
+                    sanitize_diff_for_prompt(&lines.join("\n"))
+                }
+                None => "(whole file change, contents not shown)".to_string(),
+            };
+            format!(
+                "### Hunk {} ({})\n{}",
+                i + 1,
+                hunk.file_path,
+                body.trim_end()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    format!(
+        r#"You are an experienced code reviewer looking over changes before they are committed.
+
+Comment on the numbered hunks below the way a careful reviewer would on a pull request.
+
+## Output format
+One line per comment:
+<hunk number> | <bug, test or note> | <comment>
+
+Example:
+2 | bug | `parse` panics when the header is empty; return an error instead
+3 | test | The new retry limit has no test
+
+## Guidelines:
+1. Use `bug` for likely defects: unhandled errors, off-by-one mistakes, races, resource leaks
+2. Use `test` for new or changed behaviour that no test in the change covers
+3. Use `note` sparingly, for anything else a reviewer would raise; skip style nitpicks
+4. Keep each comment to one sentence and say what to do about it
+5. Only comment when there is something worth saying; reply `none` if the change looks fine
+
+## Hunks:
+{hunks_text}
+
+Generate the review (only the comment lines, no explanation):"#
+    )
+}
+
+/// Create a prompt asking the model to group staged hunks into separate commits
+pub fn create_split_prompt(hunks: &[DiffHunk]) -> String {
+    let hunks_text = hunks
+        .iter()
+        .enumerate()
+        .map(|(i, hunk)| {
+            let body = match &hunk.text {
+                Some(text) => {
+                    let lines: Vec<&str> = text.lines().take(SPLIT_HUNK_MAX_LINES).collect();
+                    sanitize_diff_for_prompt(&lines.join("\n"))
+                }
+                None => "(whole file change, contents not shown)".to_string(),
+            };
+            format!(
+                "### Hunk {} ({})\n{}",
+                i + 1,
+                hunk.file_path,
+                body.trim_end()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    format!(
+        r#"You are an expert software engineer splitting staged changes into small, focused commits.
+
+Group the numbered hunks below into separate logical commits. Hunks from the same file may go to different commits when they are unrelated.
+
+## Output format
+One line per commit, in the order the commits should be made:
+<hunk numbers separated by commas> | <type>(<scope>): <description>
+
+Example:
+1, 3 | feat(api): add retry policy
+2 | docs(readme): document retries
+
+## Guidelines:
+1. Every hunk must appear in exactly one commit
+2. Use conventional commit messages under 72 characters
+3. Prefer fewer commits unless the changes are clearly unrelated
+
+## Hunks:
+{hunks_text}
+
+Generate the commit plan (only the plan lines, no explanation):"#
     )
 }
 
@@ -186,73 +978,94 @@ Generate the corrected conventional commit message:"#,
     )
 }
 
-/// Sanitize diff content for use in prompts
-fn sanitize_diff_for_prompt(diff: &str) -> String {
-    let lines: Vec<&str> = diff.lines().collect();
-    let mut sanitized = String::new();
-    let mut line_count = 0;
-    const MAX_LINES: usize = 100;
-    const MAX_LINE_LENGTH: usize = 150;
+/// Sanitize diff content for use in prompts, with the default budget
+pub fn sanitize_diff_for_prompt(diff: &str) -> String {
+    PromptBuilder::new().sanitize(diff)
+}
 
-    for line in lines {
-        if line_count >= MAX_LINES {
-            sanitized.push_str("... (diff truncated for brevity)\n");
-            break;
-        }
+/// Words marking a diff line as possibly sensitive, matched case-insensitively
+const SENSITIVE_PATTERNS: &[&str] = &[
+    "password",
+    "secret",
+    "token",
+    "api_key",
+    "private_key",
+    "auth_token",
+    "access_token",
+    "client_secret",
+    "client_id",
+    "database_url",
+    "connection_string",
+    "credentials",
+    "ssh_key",
+    "public_key",
+    "cert",
+    "certificate",
+    "bearer",
+    "authorization",
+    "x-api-key",
+    "x-auth-token",
+];
+
+/// Note ending a line that was cut to the budget's length
+const TRUNCATED_LINE_NOTE: &str = "... (line truncated)\n";
+
+/// The sensitive words as one case-insensitive pattern, compiled once
+fn sensitive_words() -> &'static Regex {
+    static WORDS: OnceLock<Regex> = OnceLock::new();
+    WORDS.get_or_init(|| {
+        let alternatives: Vec<String> = SENSITIVE_PATTERNS
+            .iter()
+            .map(|pattern| regex::escape(pattern))
+            .collect();
+        Regex::new(&format!("(?i){}", alternatives.join("|"))).unwrap()
+    })
+}
 
-        // Skip lines that might contain sensitive information
-        if contains_sensitive_info(line) {
-            sanitized.push_str("... (line with sensitive info removed)\n");
-            continue;
-        }
+/// Check if a line contains potentially sensitive information
+pub fn contains_sensitive_info(line: &str) -> bool {
+    sensitive_words().is_match(line) || contains_secret(line)
+}
 
-        // Truncate very long lines
-        if line.len() > MAX_LINE_LENGTH {
-            sanitized.push_str(&line[..MAX_LINE_LENGTH]);
-            sanitized.push_str("... (line truncated)\n");
-        } else {
-            sanitized.push_str(line);
-            sanitized.push('\n');
-        }
+/// Check whether a path looks like a test, by name or directory
+pub fn is_test_file(path: &str) -> bool {
+    path.contains("test")
+        || path.contains("spec")
+        || path.ends_with("_test.rs")
+        || path.ends_with(".test.js")
+        || path.ends_with(".spec.js")
+}
 
-        line_count += 1;
-    }
+/// Extensions of source files whose changes usually come with tests
+const SOURCE_EXTENSIONS: &[&str] = &[
+    "rs", "py", "js", "jsx", "ts", "tsx", "go", "java", "kt", "rb", "c", "cc", "cpp", "h", "hpp",
+    "cs", "swift", "php", "scala",
+];
+
+/// Check whether a path is program source that isn't itself a test
+pub fn is_source_file(path: &str) -> bool {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default();
+    SOURCE_EXTENSIONS.contains(&extension) && !is_test_file(path)
+}
 
-    sanitized
+/// Check whether a path is documentation
+pub fn is_doc_file(path: &str) -> bool {
+    path.contains("README")
+        || path.contains("CHANGELOG")
+        || path.ends_with(".md")
+        || path.contains("docs/")
+        || path.contains("documentation")
 }
 
-/// Check if a line contains potentially sensitive information
-fn contains_sensitive_info(line: &str) -> bool {
-    let line_lower = line.to_lowercase();
-
-    // Common patterns for sensitive information
-    let sensitive_patterns = [
-        "password",
-        "secret",
-        "token",
-        "api_key",
-        "private_key",
-        "auth_token",
-        "access_token",
-        "client_secret",
-        "client_id",
-        "database_url",
-        "connection_string",
-        "credentials",
-        "ssh_key",
-        "private_key",
-        "public_key",
-        "cert",
-        "certificate",
-        "bearer",
-        "authorization",
-        "x-api-key",
-        "x-auth-token",
-    ];
-
-    sensitive_patterns
-        .iter()
-        .any(|pattern| line_lower.contains(pattern))
+/// Check whether a path is CI configuration or a script it runs
+pub fn is_ci_file(path: &str) -> bool {
+    path.contains(".github/")
+        || path.contains(".gitlab-ci")
+        || path.contains("ci/")
+        || path.contains("scripts/")
 }
 
 /// Get commit type suggestions based on file changes
@@ -260,21 +1073,9 @@ pub fn suggest_commit_type(changes: &[DiffChange]) -> Vec<CommitType> {
     let mut suggestions = Vec::new();
 
     // Analyze file patterns to suggest appropriate types
-    let has_test_files = changes.iter().any(|c| {
-        c.file_path.contains("test")
-            || c.file_path.contains("spec")
-            || c.file_path.ends_with("_test.rs")
-            || c.file_path.ends_with(".test.js")
-            || c.file_path.ends_with(".spec.js")
-    });
+    let has_test_files = changes.iter().any(|c| is_test_file(&c.file_path));
 
-    let has_doc_files = changes.iter().any(|c| {
-        c.file_path.contains("README")
-            || c.file_path.contains("CHANGELOG")
-            || c.file_path.ends_with(".md")
-            || c.file_path.contains("docs/")
-            || c.file_path.contains("documentation")
-    });
+    let has_doc_files = changes.iter().any(|c| is_doc_file(&c.file_path));
 
     let has_config_files = changes.iter().any(|c| {
         c.file_path.contains("Cargo.toml")
@@ -286,12 +1087,7 @@ pub fn suggest_commit_type(changes: &[DiffChange]) -> Vec<CommitType> {
             || c.file_path.contains("Makefile")
     });
 
-    let has_ci_files = changes.iter().any(|c| {
-        c.file_path.contains(".github/")
-            || c.file_path.contains(".gitlab-ci")
-            || c.file_path.contains("ci/")
-            || c.file_path.contains("scripts/")
-    });
+    let has_ci_files = changes.iter().any(|c| is_ci_file(&c.file_path));
 
     // Suggest types based on file patterns
     if has_test_files {
@@ -316,6 +1112,123 @@ pub fn suggest_commit_type(changes: &[DiffChange]) -> Vec<CommitType> {
     suggestions
 }
 
+/// Directories that hold a project's sources rather than naming a component
+const SOURCE_ROOTS: &[&str] = &["src", "lib", "app", "pkg", "internal", "crates", "packages"];
+
+/// Suggest scopes from the changed files, most frequent first
+pub fn suggest_scopes(changes: &[DiffChange], max: usize) -> Vec<String> {
+    let mut counts: Vec<(String, usize)> = Vec::new();
+
+    for change in changes {
+        let components: Vec<&str> = change.file_path.split('/').collect();
+        let Some((file, dirs)) = components.split_last() else {
+            continue;
+        };
+
+        // Prefer the first directory below any source roots, falling back to the file stem
+        let scope = match dirs.iter().find(|dir| !SOURCE_ROOTS.contains(dir)) {
+            Some(dir) => dir.to_string(),
+            None => std::path::Path::new(file)
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or(file)
+                .to_string(),
+        };
+        let scope = scope.trim_start_matches('.').to_lowercase();
+        if scope.is_empty() {
+            continue;
+        }
+
+        match counts.iter_mut().find(|(s, _)| *s == scope) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((scope, 1)),
+        }
+    }
+
+    // Stable sort keeps first-seen order among equally common scopes
+    counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    counts.into_iter().take(max).map(|(s, _)| s).collect()
+}
+
+/// Write a subject from the changed paths alone, for when no model answers in time
+///
+/// The type is `test`, `docs` or `ci` when every file is one, `chore` otherwise; the
+/// scope is the most common one among `allowed_scopes`, if any are set.
+pub fn heuristic_message(changes: &[DiffChange], allowed_scopes: &[String]) -> String {
+    let all = |check: fn(&str) -> bool| changes.iter().all(|c| check(&c.file_path));
+    let commit_type = if changes.is_empty() {
+        CommitType::Chore
+    } else if all(is_test_file) {
+        CommitType::Test
+    } else if all(is_doc_file) {
+        CommitType::Docs
+    } else if all(is_ci_file) {
+        CommitType::Ci
+    } else {
+        CommitType::Chore
+    };
+
+    let scope = suggest_scopes(changes, usize::MAX)
+        .into_iter()
+        .find(|scope| allowed_scopes.is_empty() || allowed_scopes.contains(scope));
+    let header = match scope {
+        Some(scope) => format!("{commit_type}({scope})"),
+        None => commit_type.to_string(),
+    };
+
+    let verb = if changes
+        .iter()
+        .all(|c| c.change_type == DiffChangeType::Added)
+    {
+        "add"
+    } else if changes
+        .iter()
+        .all(|c| c.change_type == DiffChangeType::Deleted)
+    {
+        "remove"
+    } else {
+        "update"
+    };
+    let object = match changes {
+        [change] => change
+            .file_path
+            .rsplit('/')
+            .next()
+            .unwrap_or_default()
+            .to_string(),
+        _ => format!("{} files", changes.len()),
+    };
+    let subject = format!("{header}: {verb} {object}");
+    if subject.chars().count() <= MAX_SUBJECT_LENGTH {
+        subject
+    } else {
+        format!("{header}: {verb} 1 file")
+    }
+}
+
+/// Extra context included in the commit prompt
+#[derive(Debug, Clone, Default)]
+pub struct CommitContext {
+    /// Subjects of the commits already on the current branch, newest first
+    pub branch_commits: Vec<String>,
+    /// Subject of the commit at HEAD, which the new message should not repeat
+    pub previous_subject: Option<String>,
+    /// Subjects of past commits with similar diffs, used as style examples
+    pub similar_commits: Vec<String>,
+    /// Public functions and types the change adds, removes or modifies
+    pub changed_symbols: Vec<String>,
+    /// Dependencies the change adds, removes or bumps, e.g. `bump reqwest from 0.11 to 0.12`
+    pub dependency_changes: Vec<String>,
+    /// Database migration and schema files among the changed files
+    pub migration_files: Vec<String>,
+    /// Jobs and steps changed in CI configuration, whose patches are left out of the diff
+    pub ci_changes: Vec<String>,
+    /// Binary files grouped by kind and directory, with sizes and image dimensions
+    pub binary_assets: Vec<String>,
+    /// Preferences learned from the user's ratings of past suggestions
+    pub feedback_guidance: Vec<String>,
+}
+
 /// Repository context for better commit message generation
 #[derive(Debug, Clone)]
 pub struct RepositoryContext {
@@ -338,37 +1251,24 @@ impl RepositoryContext {
         }
     }
 
+    /// Set the branch from the HEAD state, surfacing the short SHA when detached
+    pub fn with_head_state(mut self, head: &HeadState) -> Self {
+        self.branch = head.to_string();
+        self
+    }
+
     /// Detect primary language from file extensions
+    ///
+    /// Only the changed files are seen; [`crate::profile`] works it out from the whole tree.
     pub fn detect_language(changes: &[DiffChange]) -> String {
         let mut language_counts = std::collections::HashMap::new();
 
         for change in changes {
             if let Some(ext) = std::path::Path::new(&change.file_path).extension() {
-                let lang = match ext.to_str() {
-                    Some("rs") => "Rust",
-                    Some("js") | Some("ts") => "JavaScript/TypeScript",
-                    Some("py") => "Python",
-                    Some("java") => "Java",
-                    Some("cpp") | Some("cc") | Some("cxx") => "C++",
-                    Some("c") | Some("h") => "C",
-                    Some("go") => "Go",
-                    Some("rb") => "Ruby",
-                    Some("php") => "PHP",
-                    Some("cs") => "C#",
-                    Some("kt") => "Kotlin",
-                    Some("swift") => "Swift",
-                    Some("dart") => "Dart",
-                    Some("scala") => "Scala",
-                    Some("clj") => "Clojure",
-                    Some("hs") => "Haskell",
-                    Some("elm") => "Elm",
-                    Some("ex") => "Elixir",
-                    Some("erl") => "Erlang",
-                    Some("nim") => "Nim",
-                    Some("zig") => "Zig",
-                    _ => "Other",
-                };
-
+                let lang = ext
+                    .to_str()
+                    .and_then(language_for_extension)
+                    .unwrap_or("Other");
                 *language_counts.entry(lang).or_insert(0) += 1;
             }
         }
@@ -381,38 +1281,155 @@ impl RepositoryContext {
     }
 
     /// Detect project type from file patterns
+    ///
+    /// Only the changed files are seen; [`crate::profile`] works it out from the whole tree.
     pub fn detect_project_type(changes: &[DiffChange]) -> String {
         let files: Vec<&str> = changes.iter().map(|c| c.file_path.as_str()).collect();
+        project_type_for_files(&files, |_| None)
+    }
+}
 
-        if files.iter().any(|f| f.contains("Cargo.toml")) {
-            "Rust Project"
-        } else if files.iter().any(|f| f.contains("package.json")) {
-            "Node.js Project"
-        } else if files
-            .iter()
-            .any(|f| f.contains("requirements.txt") || f.contains("setup.py"))
-        {
-            "Python Project"
-        } else if files
-            .iter()
-            .any(|f| f.contains("pom.xml") || f.contains("build.gradle"))
-        {
-            "Java Project"
-        } else if files.iter().any(|f| f.contains("go.mod")) {
-            "Go Project"
-        } else if files.iter().any(|f| f.contains("Gemfile")) {
-            "Ruby Project"
-        } else if files.iter().any(|f| f.contains("composer.json")) {
-            "PHP Project"
-        } else if files.iter().any(|f| f.contains("pubspec.yaml")) {
-            "Dart/Flutter Project"
-        } else if files.iter().any(|f| f.contains("Package.swift")) {
-            "Swift Project"
-        } else {
-            "Generic Project"
-        }
-        .to_string()
+/// Files at the root of a repository that hold several projects
+const MONOREPO_TOOL_FILES: &[&str] = &[
+    "lerna.json",
+    "nx.json",
+    "turbo.json",
+    "pnpm-workspace.yaml",
+    "rush.json",
+];
+
+/// Manifests that mark a project, with its ecosystem
+const PROJECT_MANIFESTS: &[(&str, &str)] = &[
+    ("Cargo.toml", "Rust"),
+    ("package.json", "Node.js"),
+    ("go.mod", "Go"),
+    ("pyproject.toml", "Python"),
+    ("setup.py", "Python"),
+    ("pom.xml", "Java"),
+    ("build.gradle", "Java"),
+    ("Gemfile", "Ruby"),
+    ("composer.json", "PHP"),
+    ("pubspec.yaml", "Dart/Flutter"),
+    ("Package.swift", "Swift"),
+];
+
+/// Detect the project type from file paths
+///
+/// `read_root_file` gives the contents of a file at the repository root, used to
+/// tell a Cargo or npm workspace from a single package; without it only paths count.
+pub fn project_type_for_files(
+    files: &[&str],
+    read_root_file: impl Fn(&str) -> Option<String>,
+) -> String {
+    let file_name = |f: &str| f.rsplit('/').next().unwrap_or(f).to_string();
+    let has_file_named = |name: &str| files.iter().any(|f| file_name(f) == name);
+
+    if files.iter().any(|f| MONOREPO_TOOL_FILES.contains(f)) {
+        return "Monorepo".to_string();
+    }
+    let ecosystems = subproject_ecosystems(files);
+    if ecosystems.len() > 1 {
+        return format!("Monorepo ({})", ecosystems.join(", "));
+    }
+
+    if read_root_file("Cargo.toml").is_some_and(|content| content.contains("[workspace]")) {
+        return "Rust Workspace".to_string();
+    }
+    if read_root_file("package.json").is_some_and(|content| content.contains("\"workspaces\"")) {
+        return "Node.js Workspace".to_string();
+    }
+    if files.contains(&"go.work") {
+        return "Go Workspace".to_string();
+    }
+
+    if has_file_named("AndroidManifest.xml") {
+        return "Android Project".to_string();
     }
+    if has_file_named("Podfile") || files.iter().any(|f| f.contains(".xcodeproj/")) {
+        return "iOS Project".to_string();
+    }
+    if files.iter().any(|f| f.ends_with(".tf")) {
+        return "Terraform Project".to_string();
+    }
+    if has_file_named("kustomization.yaml") || has_file_named("Chart.yaml") {
+        return "Kubernetes Manifests".to_string();
+    }
+
+    if files.iter().any(|f| f.contains("Cargo.toml")) {
+        "Rust Project"
+    } else if files.iter().any(|f| f.contains("package.json")) {
+        "Node.js Project"
+    } else if files
+        .iter()
+        .any(|f| f.contains("requirements.txt") || f.contains("setup.py"))
+    {
+        "Python Project"
+    } else if files
+        .iter()
+        .any(|f| f.contains("pom.xml") || f.contains("build.gradle"))
+    {
+        "Java Project"
+    } else if files.iter().any(|f| f.contains("go.mod")) {
+        "Go Project"
+    } else if files.iter().any(|f| f.contains("Gemfile")) {
+        "Ruby Project"
+    } else if files.iter().any(|f| f.contains("composer.json")) {
+        "PHP Project"
+    } else if files.iter().any(|f| f.contains("pubspec.yaml")) {
+        "Dart/Flutter Project"
+    } else if files.iter().any(|f| f.contains("Package.swift")) {
+        "Swift Project"
+    } else {
+        "Generic Project"
+    }
+    .to_string()
+}
+
+/// Ecosystems of the projects kept one or two directories below the root, in name order
+fn subproject_ecosystems(files: &[&str]) -> Vec<&'static str> {
+    let mut ecosystems: Vec<&'static str> = files
+        .iter()
+        .filter(|f| (1..=2).contains(&f.matches('/').count()))
+        .filter_map(|f| {
+            let name = f.rsplit('/').next()?;
+            PROJECT_MANIFESTS
+                .iter()
+                .find(|(manifest, _)| *manifest == name)
+                .map(|(_, ecosystem)| *ecosystem)
+        })
+        .collect();
+    ecosystems.sort_unstable();
+    ecosystems.dedup();
+    ecosystems
+}
+
+/// Get the programming language a file extension belongs to
+pub fn language_for_extension(extension: &str) -> Option<&'static str> {
+    let language = match extension {
+        "rs" => "Rust",
+        "js" | "ts" => "JavaScript/TypeScript",
+        "py" => "Python",
+        "java" => "Java",
+        "cpp" | "cc" | "cxx" => "C++",
+        "c" | "h" => "C",
+        "go" => "Go",
+        "rb" => "Ruby",
+        "php" => "PHP",
+        "cs" => "C#",
+        "kt" => "Kotlin",
+        "swift" => "Swift",
+        "dart" => "Dart",
+        "scala" => "Scala",
+        "clj" => "Clojure",
+        "hs" => "Haskell",
+        "elm" => "Elm",
+        "ex" => "Elixir",
+        "erl" => "Erlang",
+        "nim" => "Nim",
+        "zig" => "Zig",
+        _ => return None,
+    };
+    Some(language)
 }
 
 impl Default for RepositoryContext {
@@ -437,6 +1454,18 @@ mod tests {
         assert!(sanitized.contains("another line"));
     }
 
+    #[test]
+    fn test_sanitize_truncates_on_character_boundaries() {
+        let builder = PromptBuilder::new().budget(PromptBudget {
+            max_lines: 10,
+            max_line_length: 6,
+        });
+        assert_eq!(
+            builder.sanitize("+漢字👍🏽テキスト\n+short\n"),
+            format!("+漢字👍🏽テ{TRUNCATED_LINE_NOTE}+short\n")
+        );
+    }
+
     #[test]
     fn test_contains_sensitive_info() {
         assert!(contains_sensitive_info("password=secret123"));
@@ -473,6 +1502,72 @@ mod tests {
         assert!(suggestions.contains(&CommitType::Docs));
     }
 
+    #[test]
+    fn test_source_and_test_files() {
+        assert!(is_source_file("src/auth.rs"));
+        assert!(is_source_file("web/app.tsx"));
+        assert!(!is_source_file("src/auth_test.rs"));
+        assert!(!is_source_file("tests/integration.rs"));
+        assert!(!is_source_file("README.md"));
+        assert!(is_test_file("spec/user.spec.js"));
+    }
+
+    #[test]
+    fn test_suggest_scopes() {
+        let change = |path: &str| DiffChange {
+            file_path: path.to_string(),
+            change_type: DiffChangeType::Modified,
+            additions: 1,
+            deletions: 0,
+        };
+        let changes = vec![
+            change("src/auth/login.rs"),
+            change("src/api/routes.rs"),
+            change("src/auth/token.rs"),
+            change("src/main.rs"),
+            change("README.md"),
+            change(".github/workflows/ci.yml"),
+        ];
+
+        assert_eq!(
+            suggest_scopes(&changes, 4),
+            vec!["auth", "api", "main", "readme"]
+        );
+        assert_eq!(suggest_scopes(&changes, 10).last().unwrap(), "github");
+    }
+
+    #[test]
+    fn test_heuristic_message() {
+        let change = |path: &str, change_type| DiffChange {
+            file_path: path.to_string(),
+            change_type,
+            additions: 1,
+            deletions: 0,
+        };
+
+        assert_eq!(
+            heuristic_message(&[change("docs/setup.md", DiffChangeType::Added)], &[]),
+            "docs(docs): add setup.md"
+        );
+        let changes = [
+            change("src/auth/login.rs", DiffChangeType::Modified),
+            change("src/auth/token.rs", DiffChangeType::Added),
+            change("README.md", DiffChangeType::Modified),
+        ];
+        assert_eq!(
+            heuristic_message(&changes, &[]),
+            "chore(auth): update 3 files"
+        );
+        assert_eq!(
+            heuristic_message(&changes, &["readme".to_string()]),
+            "chore(readme): update 3 files"
+        );
+        assert_eq!(
+            heuristic_message(&changes, &["api".to_string()]),
+            "chore: update 3 files"
+        );
+    }
+
     #[test]
     fn test_detect_language() {
         let changes = vec![
@@ -517,6 +1612,54 @@ mod tests {
         assert_eq!(project_type, "Node.js Project");
     }
 
+    #[test]
+    fn test_project_type_for_files() {
+        let none = |_: &str| None;
+        assert_eq!(
+            project_type_for_files(&["backend/Cargo.toml", "web/package.json"], none),
+            "Monorepo (Node.js, Rust)"
+        );
+        assert_eq!(
+            project_type_for_files(&["nx.json", "apps/web/index.ts"], none),
+            "Monorepo"
+        );
+        assert_eq!(
+            project_type_for_files(&["Cargo.toml", "crates/core/Cargo.toml"], |name| {
+                (name == "Cargo.toml").then(|| "[workspace]\nmembers = [\"crates/*\"]".to_string())
+            }),
+            "Rust Workspace"
+        );
+        assert_eq!(
+            project_type_for_files(&["app/src/main/AndroidManifest.xml", "build.gradle"], none),
+            "Android Project"
+        );
+        assert_eq!(
+            project_type_for_files(&["App.xcodeproj/project.pbxproj"], none),
+            "iOS Project"
+        );
+        assert_eq!(
+            project_type_for_files(&["infra/main.tf"], none),
+            "Terraform Project"
+        );
+        assert_eq!(
+            project_type_for_files(&["deploy/kustomization.yaml", "deploy/app.yaml"], none),
+            "Kubernetes Manifests"
+        );
+        assert_eq!(
+            project_type_for_files(&["README.md"], none),
+            "Generic Project"
+        );
+    }
+
+    #[test]
+    fn test_contextual_prompt_with_detached_head() {
+        let context =
+            RepositoryContext::new().with_head_state(&HeadState::Detached("abc1234".to_string()));
+        let prompt = create_contextual_commit_prompt("+fn main() {}", &context);
+
+        assert!(prompt.contains("detached HEAD at abc1234"));
+    }
+
     #[test]
     fn test_create_commit_prompt() {
         let diff = "diff --git a/src/main.rs b/src/main.rs\n+fn new_function() {}";
@@ -529,6 +1672,225 @@ mod tests {
         assert!(prompt.contains(diff));
     }
 
+    #[test]
+    fn test_prompt_builder_options() {
+        let diff = "+fn new_function() {}\n+fn other_function() {}\n+fn third_function() {}";
+        let builder = PromptBuilder::new()
+            .style(PromptStyle::Concise)
+            .language("German")
+            .budget(PromptBudget {
+                max_lines: 2,
+                max_line_length: 12,
+            });
+
+        let prompt = builder.commit_prompt(diff);
+        assert!(!prompt.contains("## Examples:"));
+        assert!(!prompt.contains("A bug fix"));
+        assert!(prompt.contains("7. Write the description in German"));
+        assert!(prompt.contains("+fn new_func... (line truncated)"));
+        assert!(!prompt.contains("third"));
+        assert!(prompt.contains("(diff truncated for brevity)"));
+
+        let detailed = PromptBuilder::new().commit_prompt(diff);
+        assert!(detailed.contains("## Examples:"));
+        assert!(!detailed.contains("add a blank line and a short body"));
+        assert!(!detailed.contains("Write the description in"));
+        assert_eq!(detailed, create_commit_prompt(diff));
+    }
+
+    #[test]
+    fn test_body_language_is_independent_of_subject() {
+        let languages = LanguageSettings {
+            subject: None,
+            body: Some("Japanese".to_string()),
+        };
+        let prompt = PromptBuilder::new()
+            .languages(&languages)
+            .with_body(true)
+            .commit_prompt("+fn login() {}");
+        assert!(!prompt.contains("Write the description in"));
+        assert!(prompt.contains("7. After the subject, add a blank line"));
+        assert!(prompt.contains("8. Write the body in Japanese"));
+        assert!(prompt.contains("then the body"));
+
+        // No body was asked for, so its language doesn't matter
+        let prompt = PromptBuilder::new()
+            .language("English")
+            .body_language("Japanese")
+            .commit_prompt("+fn login() {}");
+        assert!(prompt.contains("7. Write the description in English"));
+        assert!(!prompt.contains("Japanese"));
+    }
+
+    #[test]
+    fn test_commit_prompt_with_branch_history() {
+        let diff = "+fn retry() {}";
+        assert!(!create_commit_prompt(diff).contains("Earlier commits on this branch"));
+
+        let context = CommitContext {
+            branch_commits: vec![
+                "feat(api): add retry policy".to_string(),
+                "feat(api): add http client".to_string(),
+            ],
+            ..CommitContext::default()
+        };
+        let prompt = create_commit_prompt_with_context(diff, &context);
+        assert!(prompt.contains("Earlier commits on this branch"));
+        assert!(prompt.contains("- feat(api): add retry policy"));
+        assert!(prompt.contains("- feat(api): add http client"));
+    }
+
+    #[test]
+    fn test_commit_prompt_with_previous_subject() {
+        let context = CommitContext {
+            previous_subject: Some("fix(api): handle timeouts".to_string()),
+            ..CommitContext::default()
+        };
+        let prompt = create_commit_prompt_with_context("+retry()", &context);
+        assert!(prompt.contains("## Previous commit:\nfix(api): handle timeouts"));
+        assert!(prompt.contains("near-identical"));
+    }
+
+    #[test]
+    fn test_commit_prompt_with_similar_commits() {
+        let context = CommitContext {
+            similar_commits: vec!["fix(api): retry on timeout".to_string()],
+            ..CommitContext::default()
+        };
+        let prompt = create_commit_prompt_with_context("+retry()", &context);
+        assert!(prompt.contains("similar past commits"));
+        assert!(prompt.contains("- fix(api): retry on timeout"));
+        assert!(!create_commit_prompt("+retry()").contains("similar past commits"));
+    }
+
+    #[test]
+    fn test_commit_prompt_with_changed_symbols() {
+        let context = CommitContext {
+            changed_symbols: vec!["added function `TokenValidator::verify`".to_string()],
+            ..CommitContext::default()
+        };
+        let prompt = PromptBuilder::new()
+            .context(context)
+            .with_body(true)
+            .symbols_in_body(true)
+            .commit_prompt("+pub fn verify() {}");
+        assert!(prompt
+            .contains("## Changed public symbols:\n- added function `TokenValidator::verify`"));
+        assert!(prompt.contains("list each changed public symbol"));
+        assert!(!create_commit_prompt("+retry()").contains("Changed public symbols"));
+    }
+
+    #[test]
+    fn test_commit_prompt_with_dependency_changes() {
+        let context = CommitContext {
+            dependency_changes: vec!["bump reqwest from 0.11 to 0.12".to_string()],
+            ..CommitContext::default()
+        };
+        let prompt = create_commit_prompt_with_context("-reqwest = \"0.11\"", &context);
+        assert!(prompt.contains("## Dependency changes:\n- bump reqwest from 0.11 to 0.12"));
+        assert!(prompt.contains("build(deps)"));
+    }
+
+    #[test]
+    fn test_commit_prompt_with_migration_files() {
+        let context = CommitContext {
+            migration_files: vec!["migrations/20240501_add_email/up.sql".to_string()],
+            ..CommitContext::default()
+        };
+        let settings = MigrationSettings {
+            commit_type: Some("chore".to_string()),
+            ..MigrationSettings::default()
+        };
+        let prompt = PromptBuilder::new()
+            .context(context)
+            .migrations(&settings)
+            .commit_prompt("+ALTER TABLE users ADD COLUMN email TEXT;");
+        assert!(prompt.contains("## Database migrations:\n- migrations/20240501_add_email/up.sql"));
+        assert!(prompt.contains("Use the `chore` type for this schema work."));
+    }
+
+    #[test]
+    fn test_commit_prompt_with_ci_changes() {
+        let context = CommitContext {
+            ci_changes: vec![
+                ".github/workflows/ci.yml (workflow `CI`): added job `lint`".to_string()
+            ],
+            ..CommitContext::default()
+        };
+        let prompt = create_commit_prompt_with_context("+lint:", &context);
+        assert!(prompt.contains("## CI configuration changes:\n- .github/workflows/ci.yml"));
+        assert!(prompt.contains("use the `ci` type"));
+    }
+
+    #[test]
+    fn test_commit_prompt_with_binary_assets() {
+        let context = CommitContext {
+            binary_assets: vec![
+                "added a PNG image in assets: logo.png (2.0 KiB, 64x64)".to_string()
+            ],
+            ..CommitContext::default()
+        };
+        let prompt = create_commit_prompt_with_context("Binary files differ", &context);
+        assert!(prompt.contains("## Binary files:\n- added a PNG image in assets"));
+    }
+
+    #[test]
+    fn test_paths_only_prompt() {
+        let changes = vec![DiffChange {
+            file_path: "src/auth.rs".to_string(),
+            change_type: crate::types::DiffChangeType::Modified,
+            additions: 12,
+            deletions: 3,
+        }];
+        let prompt = create_paths_only_prompt(&changes, &CommitContext::default());
+        assert!(prompt.contains("src/auth.rs (+12 -3)"));
+        assert!(prompt.contains("contents are not available"));
+        assert!(!prompt.contains("## Git Diff"));
+    }
+
+    #[test]
+    fn test_create_stash_prompt() {
+        let diff = "+fn half_finished_parser() {}";
+        let prompt = create_stash_prompt(diff);
+
+        assert!(prompt.contains("stash message"));
+        assert!(prompt.contains(diff));
+    }
+
+    #[test]
+    fn test_create_tag_prompt() {
+        let subjects = vec![
+            "feat(cli): add tag command".to_string(),
+            "fix(api): handle timeouts".to_string(),
+        ];
+        let prompt = create_tag_prompt("v1.4.0", Some("v1.3.0"), &subjects);
+
+        assert!(prompt.contains("v1.4.0"));
+        assert!(prompt.contains("since v1.3.0"));
+        assert!(prompt.contains("- feat(cli): add tag command"));
+        assert!(prompt.contains("- fix(api): handle timeouts"));
+    }
+
+    #[test]
+    fn test_create_split_prompt() {
+        let hunks = vec![
+            DiffHunk {
+                file_path: "src/api.rs".to_string(),
+                file_header: "diff --git a/src/api.rs b/src/api.rs\n".to_string(),
+                text: Some("@@ -1 +1 @@\n-old\n+new\n".to_string()),
+            },
+            DiffHunk {
+                file_path: "logo.png".to_string(),
+                file_header: "diff --git a/logo.png b/logo.png\n".to_string(),
+                text: None,
+            },
+        ];
+        let prompt = create_split_prompt(&hunks);
+
+        assert!(prompt.contains("### Hunk 1 (src/api.rs)\n@@ -1 +1 @@\n-old\n+new"));
+        assert!(prompt.contains("### Hunk 2 (logo.png)\n(whole file change"));
+    }
+
     #[test]
     fn test_create_analysis_prompt() {
         let message = "feat(auth): add JWT validation";
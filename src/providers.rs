@@ -1,14 +1,19 @@
 //! AI provider abstraction for different AI services
 
+use crate::types::CommittorError;
 use anyhow::Result;
 use async_trait::async_trait;
+use futures::StreamExt;
 use reqwest::Client as HttpClient;
 use rig::{
-    client::CompletionClient,
-    completion::Prompt,
+    agent::Agent,
+    client::{CompletionClient, EmbeddingsClient},
+    completion::{AssistantContent, Prompt},
+    embeddings::EmbeddingModel,
     providers::{ollama, openai},
+    streaming::StreamingPrompt,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
 /// Trait for AI providers that can generate commit messages
@@ -16,14 +21,83 @@ use std::time::Duration;
 pub trait AIProvider: Send + Sync {
     async fn generate_message(&self, prompt: &str) -> Result<String>;
     fn provider_name(&self) -> &'static str;
+
+    /// Embed text with the given embedding model, for similarity search
+    async fn embed(&self, _model: &str, _text: &str) -> Result<Vec<f64>> {
+        Err(anyhow::anyhow!(
+            "{} does not support embeddings",
+            self.provider_name()
+        ))
+    }
+}
+
+/// A streamed response that broke off before the model finished
+///
+/// Local servers drop connections mid-token often enough that the text received
+/// so far is kept, so a subject that already arrived whole can still be used.
+#[derive(Debug, thiserror::Error)]
+#[error("The response was cut off after {} characters: {reason}", .partial.chars().count())]
+pub struct InterruptedResponse {
+    /// Text received before the stream broke off
+    pub partial: String,
+    pub reason: String,
+}
+
+/// Describe a failed provider request with the `CommittorError` for its cause
+///
+/// Timeouts, rate limits and oversized prompts all reach us as HTTP or API errors
+/// that rig often flattens into strings, so the message is inspected as well.
+/// Errors that already say what went wrong pass through unchanged.
+pub fn provider_error(error: anyhow::Error) -> anyhow::Error {
+    if error.is::<CommittorError>() || error.is::<InterruptedResponse>() {
+        return error;
+    }
+
+    let message = error.to_string();
+    let details = format!("{error:#}").to_lowercase();
+    let timed_out = error.chain().any(|cause| {
+        cause
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(reqwest::Error::is_timeout)
+    });
+
+    if timed_out || details.contains("timed out") {
+        CommittorError::Timeout(message)
+    } else if details.contains("429")
+        || details.contains("rate limit")
+        || details.contains("too many requests")
+    {
+        CommittorError::RateLimited(message)
+    } else if details.contains("context_length_exceeded")
+        || details.contains("context length")
+        || details.contains("context window")
+    {
+        CommittorError::ContextTooLarge(message)
+    } else {
+        CommittorError::AIProviderError(message)
+    }
+    .into()
 }
 
+/// Check whether a request failed because the prompt didn't fit the model's context window
+pub fn is_context_too_large(error: &anyhow::Error) -> bool {
+    matches!(
+        error.downcast_ref::<CommittorError>(),
+        Some(CommittorError::ContextTooLarge(_))
+    )
+}
+
+/// Names of the supported providers, as given to `--provider`
+pub const PROVIDER_NAMES: [&str; 2] = ["openai", "ollama"];
+
 /// Configuration for different AI providers
 #[derive(Debug, Clone)]
 pub enum ProviderConfig {
     OpenAI {
         api_key: String,
         model: String,
+        /// API base URL, e.g. for a proxy; the public API when `None`
+        base_url: Option<String>,
     },
     Ollama {
         base_url: String,
@@ -35,7 +109,20 @@ pub enum ProviderConfig {
 impl ProviderConfig {
     /// Create an OpenAI provider configuration
     pub fn openai(api_key: String, model: String) -> Self {
-        Self::OpenAI { api_key, model }
+        Self::OpenAI {
+            api_key,
+            model,
+            base_url: None,
+        }
+    }
+
+    /// Create an OpenAI provider configuration against another API base URL
+    pub fn openai_with_url(api_key: String, model: String, base_url: String) -> Self {
+        Self::OpenAI {
+            api_key,
+            model,
+            base_url: Some(base_url),
+        }
     }
 
     /// Create an Ollama provider configuration
@@ -55,98 +142,378 @@ impl ProviderConfig {
             timeout,
         }
     }
+
+    /// Get the provider's name as given to `--provider`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::OpenAI { .. } => "openai",
+            Self::Ollama { .. } => "ollama",
+        }
+    }
+
+    /// Get the model used for generation
+    pub fn model(&self) -> &str {
+        match self {
+            Self::OpenAI { model, .. } | Self::Ollama { model, .. } => model,
+        }
+    }
+
+    /// Get the same provider configuration generating with another model
+    pub fn with_model(&self, model: &str) -> Self {
+        let mut config = self.clone();
+        match &mut config {
+            Self::OpenAI { model: current, .. } | Self::Ollama { model: current, .. } => {
+                *current = model.to_string();
+            }
+        }
+        config
+    }
+
+    /// Check that the configuration is usable, so mistakes surface before any request is made
+    pub fn validate(&self) -> Result<()> {
+        let problem = match self {
+            Self::OpenAI { api_key, .. } if api_key.trim().is_empty() => {
+                "The OpenAI API key is empty"
+            }
+            Self::Ollama { base_url, .. } if base_url.trim().is_empty() => {
+                "The Ollama base URL is empty"
+            }
+            _ if self.model().trim().is_empty() => "No model is set",
+            _ => return Ok(()),
+        };
+        Err(CommittorError::ConfigError(problem.to_string()).into())
+    }
+
+    /// Check whether the provider runs on this machine, so nothing leaves it
+    pub fn is_local(&self) -> bool {
+        let Self::Ollama { base_url, .. } = self else {
+            return false;
+        };
+
+        let host = base_url
+            .split("://")
+            .last()
+            .unwrap_or_default()
+            .split('/')
+            .next()
+            .unwrap_or_default();
+        let host = match host.rsplit_once(':') {
+            // Keep bracketed IPv6 addresses whole when they have no port
+            Some((host, port)) if port.chars().all(|c| c.is_ascii_digit()) => host,
+            _ => host,
+        };
+
+        matches!(host, "localhost" | "127.0.0.1" | "[::1]" | "::1")
+    }
+
+    /// Build the HTTP client shared by the provider and its health checks
+    ///
+    /// Ollama's request timeout is applied to the client, since rig has no per-request one.
+    pub fn http_client(&self) -> Result<HttpClient> {
+        let builder = HttpClient::builder();
+        let builder = match self {
+            Self::OpenAI { .. } => builder,
+            Self::Ollama { timeout, .. } => builder.timeout(*timeout),
+        };
+        Ok(builder.build()?)
+    }
+
+    /// Check that the provider can be reached; only Ollama has a health check
+    pub async fn check_availability(&self, http: &HttpClient) -> Result<bool> {
+        match self {
+            Self::Ollama { base_url, .. } => {
+                check_ollama_availability_with_client(http, base_url).await
+            }
+            Self::OpenAI { .. } => Ok(true),
+        }
+    }
+
+    /// Get the embedding model used when the settings don't name one
+    pub fn default_embedding_model(&self) -> &'static str {
+        match self {
+            Self::OpenAI { .. } => "text-embedding-3-small",
+            Self::Ollama { .. } => "nomic-embed-text",
+        }
+    }
 }
 
+/// Completion model behind an OpenAI agent
+type OpenAICompletionModel = <openai::Client as CompletionClient>::CompletionModel;
+
+/// Completion model behind an Ollama agent
+type OllamaCompletionModel = <ollama::Client as CompletionClient>::CompletionModel;
+
 /// OpenAI provider implementation
 pub struct OpenAIProvider {
     client: openai::Client,
-    model: String,
+    /// Agent built once and reused for every request
+    agent: Agent<OpenAICompletionModel>,
 }
 
 impl OpenAIProvider {
     pub fn new(api_key: String, model: String) -> Self {
         let client = openai::Client::new(&api_key);
-        Self { client, model }
+        let agent = client.agent(&model).build();
+        Self { client, agent }
+    }
+
+    /// Create a provider that sends its requests through a shared HTTP client
+    pub fn with_client(
+        api_key: String,
+        model: String,
+        base_url: Option<String>,
+        http: HttpClient,
+    ) -> Self {
+        let client = match base_url {
+            Some(base_url) => openai::Client::from_url(&api_key, &base_url),
+            None => openai::Client::new(&api_key),
+        }
+        .with_custom_client(http);
+        let agent = client.agent(&model).build();
+        Self { client, agent }
+    }
+
+    /// Sample with the given temperature instead of the model's default
+    pub fn with_temperature(mut self, temperature: Option<f64>) -> Self {
+        self.agent.temperature = temperature;
+        self
     }
 }
 
 #[async_trait]
 impl AIProvider for OpenAIProvider {
     async fn generate_message(&self, prompt: &str) -> Result<String> {
-        let agent = self.client.agent(&self.model).build();
-        let response = agent.prompt(prompt).await?;
+        let response = self.agent.prompt(prompt).await?;
         Ok(response.trim().to_string())
     }
 
     fn provider_name(&self) -> &'static str {
         "OpenAI"
     }
+
+    async fn embed(&self, model: &str, text: &str) -> Result<Vec<f64>> {
+        let embedding = self.client.embedding_model(model).embed_text(text).await?;
+        Ok(embedding.vec)
+    }
 }
 
 /// Ollama provider implementation
 pub struct OllamaProvider {
     client: ollama::Client,
-    model: String,
+    base_url: String,
+    /// Agent built once and reused for every request
+    agent: Agent<OllamaCompletionModel>,
 }
 
 impl OllamaProvider {
-    pub fn new(base_url: String, model: String, _timeout: Duration) -> Result<Self> {
+    pub fn new(base_url: String, model: String, timeout: Duration) -> Result<Self> {
+        let http = HttpClient::builder().timeout(timeout).build()?;
+        Self::with_client(base_url, model, http)
+    }
+
+    /// Create a provider that sends its requests through a shared HTTP client
+    pub fn with_client(base_url: String, model: String, http: HttpClient) -> Result<Self> {
         let client = if base_url == "http://localhost:11434" {
             ollama::Client::new()
         } else {
             ollama::Client::from_url(&base_url)
         };
 
-        Ok(Self { client, model })
+        let client = client.with_custom_client(http);
+        let agent = client.agent(&model).build();
+        Ok(Self {
+            client,
+            base_url,
+            agent,
+        })
     }
 
     pub fn with_default_url(model: String) -> Result<Self> {
+        let client = ollama::Client::new();
+        let agent = client.agent(&model).build();
         Ok(Self {
-            client: ollama::Client::new(),
-            model,
+            client,
+            base_url: "http://localhost:11434".to_string(),
+            agent,
         })
     }
+
+    /// Sample with the given temperature instead of the model's default
+    pub fn with_temperature(mut self, temperature: Option<f64>) -> Self {
+        self.agent.temperature = temperature;
+        self
+    }
 }
 
 #[async_trait]
 impl AIProvider for OllamaProvider {
     async fn generate_message(&self, prompt: &str) -> Result<String> {
-        let agent = self.client.agent(&self.model).build();
-        let response = agent.prompt(prompt).await?;
+        let mut stream = self
+            .agent
+            .stream_prompt(prompt)
+            .await
+            .map_err(|e| self.connection_error(e.into()))?;
+
+        let mut response = String::new();
+        while let Some(chunk) = stream.next().await {
+            match chunk {
+                Ok(AssistantContent::Text(text)) => response.push_str(&text.text),
+                Ok(_) => {}
+                Err(e) if response.is_empty() => return Err(self.connection_error(e.into())),
+                Err(e) => {
+                    return Err(InterruptedResponse {
+                        partial: response,
+                        reason: e.to_string(),
+                    }
+                    .into())
+                }
+            }
+        }
+
+        // Ollama marks its last chunk `done`; a stream that ends without one was cut off
+        if stream.response.is_none() {
+            return Err(InterruptedResponse {
+                partial: response,
+                reason: "the stream ended before the model finished".to_string(),
+            }
+            .into());
+        }
         Ok(response.trim().to_string())
     }
 
     fn provider_name(&self) -> &'static str {
         "Ollama"
     }
+
+    async fn embed(&self, model: &str, text: &str) -> Result<Vec<f64>> {
+        let embedding = self
+            .client
+            .embedding_model(model)
+            .embed_text(text)
+            .await
+            .map_err(|e| self.connection_error(e.into()))?;
+        Ok(embedding.vec)
+    }
+}
+
+impl OllamaProvider {
+    /// Report a failure to connect the same way the availability check does
+    fn connection_error(&self, error: anyhow::Error) -> anyhow::Error {
+        // rig flattens HTTP errors into strings, so reqwest's own message is all there is to go on
+        let refused = error.chain().any(|cause| {
+            cause
+                .downcast_ref::<reqwest::Error>()
+                .is_some_and(reqwest::Error::is_connect)
+                || cause.to_string().contains("error sending request")
+        });
+
+        if refused {
+            CommittorError::OllamaUnavailable(self.base_url.clone()).into()
+        } else {
+            error
+        }
+    }
 }
 
 /// Factory function to create AI providers
 pub fn create_provider(config: ProviderConfig) -> Result<Box<dyn AIProvider>> {
+    let http = config.http_client()?;
+    create_provider_with_client(config, http)
+}
+
+/// Create an AI provider that sends its requests through a shared HTTP client
+pub fn create_provider_with_client(
+    config: ProviderConfig,
+    http: HttpClient,
+) -> Result<Box<dyn AIProvider>> {
+    create_provider_with_temperature(config, http, None)
+}
+
+/// Create an AI provider sampling with a temperature, `None` for the model's default
+pub fn create_provider_with_temperature(
+    config: ProviderConfig,
+    http: HttpClient,
+    temperature: Option<f64>,
+) -> Result<Box<dyn AIProvider>> {
     match config {
-        ProviderConfig::OpenAI { api_key, model } => {
-            Ok(Box::new(OpenAIProvider::new(api_key, model)))
-        }
-        ProviderConfig::Ollama {
-            base_url,
+        ProviderConfig::OpenAI {
+            api_key,
             model,
-            timeout,
+            base_url,
+        } => Ok(Box::new(
+            OpenAIProvider::with_client(api_key, model, base_url, http)
+                .with_temperature(temperature),
+        )),
+        ProviderConfig::Ollama {
+            base_url, model, ..
         } => {
-            let provider = OllamaProvider::new(base_url, model, timeout)?;
+            let provider =
+                OllamaProvider::with_client(base_url, model, http)?.with_temperature(temperature);
             Ok(Box::new(provider))
         }
     }
 }
 
+/// Sampling read from a command's settings section, e.g. `[generate]`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SamplingSettings {
+    /// Temperature the command samples with; the model's default when unset
+    pub temperature: Option<f64>,
+}
+
+/// Retries for prompts the model's context window can't hold, from the `[overflow]` settings section
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OverflowSettings {
+    /// Model of the same provider with a larger context window, sent the prompt instead
+    pub model: Option<String>,
+    /// Rebuild the prompt in its compact form and try once more
+    pub compact: bool,
+}
+
+impl Default for OverflowSettings {
+    fn default() -> Self {
+        Self {
+            model: None,
+            compact: true,
+        }
+    }
+}
+
+/// `commit --quick`, from the `[quick]` settings section
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuickSettings {
+    /// Fastest model of the provider, used instead of `--model`
+    pub model: Option<String>,
+    /// Seconds to wait for the model before writing the message from the changed paths
+    pub budget: u64,
+}
+
+impl Default for QuickSettings {
+    fn default() -> Self {
+        Self {
+            model: None,
+            budget: 5,
+        }
+    }
+}
+
 /// Check if Ollama is available at the given URL
 pub async fn check_ollama_availability(base_url: &str) -> Result<bool> {
-    let client = HttpClient::builder()
-        .timeout(Duration::from_secs(5))
-        .build()?;
+    check_ollama_availability_with_client(&HttpClient::new(), base_url).await
+}
 
+/// Check if Ollama is available at the given URL, reusing an existing HTTP client
+pub async fn check_ollama_availability_with_client(
+    client: &HttpClient,
+    base_url: &str,
+) -> Result<bool> {
     let url = format!("{}/api/tags", base_url.trim_end_matches('/'));
+    let request = client.get(&url).timeout(Duration::from_secs(5));
 
-    match client.get(&url).send().await {
+    match request.send().await {
         Ok(response) => Ok(response.status().is_success()),
         Err(_) => Ok(false),
     }
@@ -154,12 +521,20 @@ pub async fn check_ollama_availability(base_url: &str) -> Result<bool> {
 
 /// Get available models from Ollama using /api/tags endpoint
 pub async fn get_ollama_models(base_url: &str) -> Result<Vec<String>> {
-    let client = HttpClient::builder()
-        .timeout(Duration::from_secs(10))
-        .build()?;
+    get_ollama_models_with_client(&HttpClient::new(), base_url).await
+}
 
+/// Get available models from Ollama, reusing an existing HTTP client
+pub async fn get_ollama_models_with_client(
+    client: &HttpClient,
+    base_url: &str,
+) -> Result<Vec<String>> {
     let url = format!("{}/api/tags", base_url.trim_end_matches('/'));
-    let response = client.get(&url).send().await?;
+    let response = client
+        .get(&url)
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await?;
 
     if !response.status().is_success() {
         return Err(anyhow::anyhow!(
@@ -192,9 +567,14 @@ mod tests {
     fn test_provider_config_creation() {
         let openai_config = ProviderConfig::openai("test-key".to_string(), "gpt-4".to_string());
         match openai_config {
-            ProviderConfig::OpenAI { api_key, model } => {
+            ProviderConfig::OpenAI {
+                api_key,
+                model,
+                base_url,
+            } => {
                 assert_eq!(api_key, "test-key");
                 assert_eq!(model, "gpt-4");
+                assert_eq!(base_url, None);
             }
             _ => panic!("Expected OpenAI config"),
         }
@@ -212,6 +592,23 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_validate_provider_config() {
+        assert!(
+            ProviderConfig::openai("sk-test".to_string(), "gpt-4".to_string())
+                .validate()
+                .is_ok()
+        );
+        assert!(ProviderConfig::openai(String::new(), "gpt-4".to_string())
+            .validate()
+            .is_err());
+        assert!(
+            ProviderConfig::ollama("http://localhost:11434".to_string(), " ".to_string())
+                .validate()
+                .is_err()
+        );
+    }
+
     #[test]
     fn test_ollama_provider_creation() {
         let provider = OllamaProvider::new(
@@ -222,6 +619,100 @@ mod tests {
         assert!(provider.is_ok());
     }
 
+    #[test]
+    fn test_provider_temperature() {
+        let provider = OllamaProvider::with_default_url("llama2".to_string())
+            .unwrap()
+            .with_temperature(Some(0.2));
+        assert_eq!(provider.agent.temperature, Some(0.2));
+
+        let provider = OpenAIProvider::new("test-key".to_string(), "gpt-4".to_string());
+        assert_eq!(provider.agent.temperature, None);
+        assert_eq!(
+            provider.with_temperature(Some(0.7)).agent.temperature,
+            Some(0.7)
+        );
+    }
+
+    #[test]
+    fn test_providers_share_http_client() {
+        let config = ProviderConfig::ollama_with_timeout(
+            "http://localhost:11434".to_string(),
+            "llama2".to_string(),
+            Duration::from_secs(5),
+        );
+        let http = config.http_client().unwrap();
+
+        let ollama = create_provider_with_client(config, http.clone()).unwrap();
+        let openai = create_provider_with_client(
+            ProviderConfig::openai("test-key".to_string(), "gpt-4".to_string()),
+            http,
+        )
+        .unwrap();
+        assert_eq!(ollama.provider_name(), "Ollama");
+        assert_eq!(openai.provider_name(), "OpenAI");
+    }
+
+    #[tokio::test]
+    async fn test_ollama_connection_errors_are_friendly() {
+        let provider = OllamaProvider::new(
+            "http://127.0.0.1:1".to_string(),
+            "llama2".to_string(),
+            Duration::from_secs(5),
+        )
+        .unwrap();
+
+        let error = provider.generate_message("hello").await.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<CommittorError>(),
+            Some(CommittorError::OllamaUnavailable(url)) if url == "http://127.0.0.1:1"
+        ));
+    }
+
+    #[test]
+    fn test_provider_error_classification() {
+        let classify = |message: &str| {
+            provider_error(anyhow::anyhow!(message.to_string()))
+                .downcast::<CommittorError>()
+                .unwrap()
+        };
+
+        assert!(matches!(
+            classify("HttpError: operation timed out"),
+            CommittorError::Timeout(_)
+        ));
+        assert!(matches!(
+            classify("ProviderError: 429 Too Many Requests"),
+            CommittorError::RateLimited(_)
+        ));
+        assert!(matches!(
+            classify(
+                "This model's maximum context length is 8192 tokens (context_length_exceeded)"
+            ),
+            CommittorError::ContextTooLarge(_)
+        ));
+        assert!(matches!(
+            classify("invalid api key"),
+            CommittorError::AIProviderError(_)
+        ));
+
+        // Errors that already say what went wrong are kept
+        let unavailable = CommittorError::OllamaUnavailable("http://localhost:11434".to_string());
+        assert!(matches!(
+            provider_error(unavailable.into()).downcast_ref::<CommittorError>(),
+            Some(CommittorError::OllamaUnavailable(_))
+        ));
+    }
+
+    #[test]
+    fn test_with_model() {
+        let config = ProviderConfig::openai("sk-test".to_string(), "gpt-4o-mini".to_string());
+        let long_context = config.with_model("gpt-4.1");
+        assert_eq!(long_context.model(), "gpt-4.1");
+        assert_eq!(long_context.name(), "openai");
+        assert_eq!(config.model(), "gpt-4o-mini");
+    }
+
     #[test]
     fn test_ollama_provider_with_default_url() {
         let provider = OllamaProvider::with_default_url("llama2".to_string());
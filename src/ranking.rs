@@ -0,0 +1,154 @@
+//! Local heuristic ranking of generated commit messages, needing no extra API calls
+
+use crate::commit::parse_commit_message;
+use regex::Regex;
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+/// Subject length the ranking prefers
+pub const IDEAL_SUBJECT_LENGTH: usize = 50;
+
+/// Scopes too broad to tell a reader where the change is
+const GENERIC_SCOPES: &[&str] = &[
+    "all", "app", "code", "core", "general", "misc", "project", "repo", "src",
+];
+
+/// Verbs that say little about what actually changed
+const VAGUE_VERBS: &[&str] = &["update", "change", "modify", "improve", "tweak", "adjust"];
+
+/// Sort messages best first, keeping the generated order between equal scores
+pub fn rank_messages(messages: &mut [String], diff: &str) {
+    let symbols = changed_symbols(diff);
+    let mut scored: Vec<(f64, String)> = messages
+        .iter()
+        .map(|message| (score_message(message, &symbols), message.clone()))
+        .collect();
+
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    for (slot, (_, message)) in messages.iter_mut().zip(scored) {
+        *slot = message;
+    }
+}
+
+/// Score a message on validity, scope, verb, length and mention of changed symbols
+pub fn score_message(message: &str, symbols: &HashSet<String>) -> f64 {
+    let subject = message.lines().next().unwrap_or_default().trim();
+    let Ok(commit) = parse_commit_message(subject) else {
+        return 0.0;
+    };
+
+    let mut score = 3.0;
+
+    score += match commit.scope.as_deref() {
+        Some(scope) if GENERIC_SCOPES.contains(&scope.to_lowercase().as_str()) => 0.25,
+        Some(_) => 1.0,
+        None => 0.0,
+    };
+
+    let verb = commit
+        .description
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    if !VAGUE_VERBS.contains(&verb.as_str()) {
+        score += 1.0;
+    }
+
+    let distance = subject.chars().count().abs_diff(IDEAL_SUBJECT_LENGTH);
+    score += 1.0 - (distance as f64 / IDEAL_SUBJECT_LENGTH as f64).min(1.0);
+
+    let mentions_symbol = commit
+        .description
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .any(|word| symbols.contains(&word.to_lowercase()));
+    if mentions_symbol {
+        score += 1.0;
+    }
+
+    score
+}
+
+/// Find the names of functions, types and modules defined in a piece of source
+pub fn defined_names(text: &str) -> Vec<String> {
+    static DEFINITION: OnceLock<Regex> = OnceLock::new();
+    let definition_regex = DEFINITION.get_or_init(|| {
+        Regex::new(
+            r"\b(?:fn|struct|enum|trait|mod|type|const|class|def|function|interface)\s+([A-Za-z_][A-Za-z0-9_]*)",
+        )
+        .unwrap()
+    });
+
+    definition_regex
+        .captures_iter(text)
+        .map(|captures| captures[1].to_string())
+        .collect()
+}
+
+/// Collect the names defined on changed lines and the names of changed files, lowercased
+pub fn changed_symbols(diff: &str) -> HashSet<String> {
+    let mut symbols = HashSet::new();
+    for line in diff.lines() {
+        if let Some(paths) = line.strip_prefix("diff --git ") {
+            for path in paths.split_whitespace() {
+                let stem = std::path::Path::new(path)
+                    .file_stem()
+                    .and_then(|stem| stem.to_str());
+                if let Some(stem) = stem {
+                    symbols.insert(stem.to_lowercase());
+                }
+            }
+            continue;
+        }
+
+        let changed = (line.starts_with('+') && !line.starts_with("+++"))
+            || (line.starts_with('-') && !line.starts_with("---"));
+        if changed {
+            symbols.extend(defined_names(line).iter().map(|name| name.to_lowercase()));
+        }
+    }
+
+    symbols
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIFF: &str = "diff --git a/src/retry.rs b/src/retry.rs\n+++ b/src/retry.rs\n+pub fn backoff_delay(attempt: u32) -> u64 {\n";
+
+    #[test]
+    fn test_changed_symbols() {
+        let symbols = changed_symbols(DIFF);
+        assert!(symbols.contains("retry"));
+        assert!(symbols.contains("backoff_delay"));
+        assert!(!symbols.contains("attempt"));
+    }
+
+    #[test]
+    fn test_score_message() {
+        let symbols = changed_symbols(DIFF);
+        let specific = score_message("feat(retry): add backoff_delay helper", &symbols);
+        let vague = score_message("chore(misc): update code", &symbols);
+        assert!(specific > vague);
+        assert_eq!(score_message("not a conventional commit", &symbols), 0.0);
+    }
+
+    #[test]
+    fn test_rank_messages() {
+        let mut messages = vec![
+            "invalid message".to_string(),
+            "chore: update stuff".to_string(),
+            "feat(retry): add backoff_delay for failed requests".to_string(),
+        ];
+        rank_messages(&mut messages, DIFF);
+        assert_eq!(
+            messages,
+            vec![
+                "feat(retry): add backoff_delay for failed requests",
+                "chore: update stuff",
+                "invalid message",
+            ]
+        );
+    }
+}
@@ -0,0 +1,338 @@
+//! Numbers and dates in reports, written the way the user's locale writes them
+//!
+//! `eval`, `audit show`, `restore --list`, the `--timing` breakdown and exported
+//! sessions all format through here, so `--locale` and `--date-format` apply to
+//! every report alike. Times are shown in UTC, which is how committor stores them.
+
+use crate::i18n::{self, Locale};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// How dates are written in reports
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum DateFormat {
+    /// The locale's own style, e.g. `Oct 15, 2025 12:34 UTC` or `2025年10月15日 12:34 UTC`
+    #[default]
+    Locale,
+    /// RFC 3339, e.g. `2025-10-15T12:34:56Z`
+    Iso,
+    /// A pattern of `%Y`, `%m`, `%d`, `%H`, `%M`, `%S`, `%b` and `%%`, e.g. `%d/%m/%Y`
+    Pattern(String),
+}
+
+impl FromStr for DateFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "locale" => Ok(Self::Locale),
+            "iso" => Ok(Self::Iso),
+            pattern if pattern.contains('%') => Ok(Self::Pattern(pattern.to_string())),
+            _ => Err(format!(
+                "expected `locale`, `iso` or a pattern such as `%Y-%m-%d %H:%M`, not `{format}`"
+            )),
+        }
+    }
+}
+
+static DATE_FORMAT: OnceLock<DateFormat> = OnceLock::new();
+
+/// Set how dates are written for the rest of the process; only the first call has any effect
+pub fn set_date_format(format: DateFormat) {
+    let _ = DATE_FORMAT.set(format);
+}
+
+/// Get how dates are written, in the locale's style until set
+pub fn date_format() -> &'static DateFormat {
+    DATE_FORMAT.get_or_init(DateFormat::default)
+}
+
+/// A UTC date and time split into its fields
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+impl DateTime {
+    /// Split seconds since the Unix epoch into a UTC date and time
+    pub fn from_timestamp(seconds: u64) -> Self {
+        let days = (seconds / 86_400) as i64;
+        let time = (seconds % 86_400) as u32;
+
+        // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let day_of_era = z.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+        let month = if month_index < 10 {
+            month_index + 3
+        } else {
+            month_index - 9
+        };
+
+        Self {
+            year: year_of_era + era * 400 + i64::from(month <= 2),
+            month: month as u32,
+            day: day as u32,
+            hour: time / 3_600,
+            minute: time % 3_600 / 60,
+            second: time % 60,
+        }
+    }
+
+    /// Get the seconds since the Unix epoch, `None` before it
+    pub fn timestamp(&self) -> Option<u64> {
+        // Days since 1970-01-01 from a civil date, the inverse of the above
+        let year = self.year - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year.rem_euclid(400);
+        let month = i64::from(self.month);
+        let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5
+            + i64::from(self.day)
+            - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146_097 + day_of_era - 719_468;
+
+        let seconds = days * 86_400
+            + i64::from(self.hour) * 3_600
+            + i64::from(self.minute) * 60
+            + i64::from(self.second);
+        u64::try_from(seconds).ok()
+    }
+
+    /// Parse an RFC 3339 UTC timestamp such as `2025-10-15T12:34:56Z`, as committor stores them
+    pub fn parse_rfc3339(timestamp: &str) -> Option<Self> {
+        let (date, time) = timestamp.strip_suffix('Z')?.split_once('T')?;
+        let mut date = date.splitn(3, '-');
+        let mut time = time.splitn(3, ':');
+        let parsed = Self {
+            year: date.next()?.parse().ok()?,
+            month: date.next()?.parse().ok()?,
+            day: date.next()?.parse().ok()?,
+            hour: time.next()?.parse().ok()?,
+            minute: time.next()?.parse().ok()?,
+            second: time.next()?.parse().ok()?,
+        };
+        let valid = (1..=12).contains(&parsed.month)
+            && (1..=31).contains(&parsed.day)
+            && parsed.hour < 24
+            && parsed.minute < 60
+            && parsed.second < 60;
+        valid.then_some(parsed)
+    }
+
+    /// Write as an RFC 3339 UTC timestamp
+    pub fn to_rfc3339(&self) -> String {
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+/// Write seconds since the Unix epoch as a date, in the current locale and date format
+pub fn format_date(seconds: u64) -> String {
+    format_date_in(seconds, i18n::locale(), date_format())
+}
+
+/// Rewrite a stored RFC 3339 timestamp in the current locale and date format, keeping it if it doesn't parse
+pub fn format_stored_date(timestamp: &str) -> String {
+    DateTime::parse_rfc3339(timestamp)
+        .and_then(|date| date.timestamp())
+        .map_or_else(|| timestamp.to_string(), format_date)
+}
+
+/// Write seconds since the Unix epoch as a date, in a given locale and date format
+pub fn format_date_in(seconds: u64, locale: Locale, format: &DateFormat) -> String {
+    let date = DateTime::from_timestamp(seconds);
+    let month = month_abbreviation(date.month, locale);
+    match format {
+        DateFormat::Iso => date.to_rfc3339(),
+        DateFormat::Locale => {
+            let (year, day, hour, minute) = (date.year, date.day, date.hour, date.minute);
+            match locale {
+                Locale::English => format!("{month} {day}, {year} {hour:02}:{minute:02} UTC"),
+                Locale::Japanese | Locale::Chinese => {
+                    format!("{year}年{}月{day}日 {hour:02}:{minute:02} UTC", date.month)
+                }
+                Locale::Spanish => format!("{day} {month} {year}, {hour:02}:{minute:02} UTC"),
+            }
+        }
+        DateFormat::Pattern(pattern) => {
+            let mut formatted = String::new();
+            let mut chars = pattern.chars();
+            while let Some(c) = chars.next() {
+                if c != '%' {
+                    formatted.push(c);
+                    continue;
+                }
+                match chars.next() {
+                    Some('Y') => formatted.push_str(&format!("{:04}", date.year)),
+                    Some('m') => formatted.push_str(&format!("{:02}", date.month)),
+                    Some('d') => formatted.push_str(&format!("{:02}", date.day)),
+                    Some('H') => formatted.push_str(&format!("{:02}", date.hour)),
+                    Some('M') => formatted.push_str(&format!("{:02}", date.minute)),
+                    Some('S') => formatted.push_str(&format!("{:02}", date.second)),
+                    Some('b') => formatted.push_str(&month),
+                    Some('%') => formatted.push('%'),
+                    Some(other) => {
+                        formatted.push('%');
+                        formatted.push(other);
+                    }
+                    None => formatted.push('%'),
+                }
+            }
+            formatted
+        }
+    }
+}
+
+fn month_abbreviation(month: u32, locale: Locale) -> String {
+    const ENGLISH: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    const SPANISH: [&str; 12] = [
+        "ene", "feb", "mar", "abr", "may", "jun", "jul", "ago", "sept", "oct", "nov", "dic",
+    ];
+    let index = month.clamp(1, 12) as usize - 1;
+    match locale {
+        Locale::English => ENGLISH[index].to_string(),
+        Locale::Japanese | Locale::Chinese => format!("{month}月"),
+        Locale::Spanish => SPANISH[index].to_string(),
+    }
+}
+
+/// Write a count with the current locale's digit grouping, e.g. `12,345` or `12.345`
+pub fn format_count(count: u64) -> String {
+    group_digits(&count.to_string(), i18n::locale())
+}
+
+/// Write a number with a fixed number of decimal places, e.g. `1,234.5` or `1234,5`
+pub fn format_decimal(value: f64, places: usize) -> String {
+    format_decimal_in(value, places, i18n::locale())
+}
+
+/// Write a number with a fixed number of decimal places in a given locale
+pub fn format_decimal_in(value: f64, places: usize, locale: Locale) -> String {
+    let formatted = format!("{:.places$}", value.abs());
+    let (integer, fraction) = formatted.split_once('.').unwrap_or((&formatted, ""));
+    let sign = if value < 0.0 && formatted.chars().any(|c| ('1'..='9').contains(&c)) {
+        "-"
+    } else {
+        ""
+    };
+    let mut written = format!("{sign}{}", group_digits(integer, locale));
+    if !fraction.is_empty() {
+        written.push(decimal_separator(locale));
+        written.push_str(fraction);
+    }
+    written
+}
+
+/// Write a ratio between 0 and 1 as a percentage, e.g. `95.5%` or `95,5 %`
+pub fn format_percent(ratio: f64, places: usize) -> String {
+    format_percent_in(ratio, places, i18n::locale())
+}
+
+/// Write a ratio between 0 and 1 as a percentage in a given locale
+pub fn format_percent_in(ratio: f64, places: usize, locale: Locale) -> String {
+    let number = format_decimal_in(ratio * 100.0, places, locale);
+    match locale {
+        Locale::Spanish => format!("{number}\u{a0}%"),
+        Locale::English | Locale::Japanese | Locale::Chinese => format!("{number}%"),
+    }
+}
+
+fn decimal_separator(locale: Locale) -> char {
+    match locale {
+        Locale::Spanish => ',',
+        Locale::English | Locale::Japanese | Locale::Chinese => '.',
+    }
+}
+
+/// Group the digits of an integer in threes; Spanish leaves four-digit numbers whole
+fn group_digits(digits: &str, locale: Locale) -> String {
+    let separator = match locale {
+        Locale::Spanish if digits.len() <= 4 => return digits.to_string(),
+        Locale::Spanish => '.',
+        Locale::English | Locale::Japanese | Locale::Chinese => ',',
+    };
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(separator);
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OCT_15_2025: u64 = 1_760_531_696;
+
+    #[test]
+    fn test_date_time_round_trip() {
+        for seconds in [0, 951_782_400, OCT_15_2025] {
+            let date = DateTime::from_timestamp(seconds);
+            assert_eq!(date.timestamp(), Some(seconds));
+            assert_eq!(DateTime::parse_rfc3339(&date.to_rfc3339()), Some(date));
+        }
+        assert_eq!(
+            DateTime::from_timestamp(OCT_15_2025).to_rfc3339(),
+            "2025-10-15T12:34:56Z"
+        );
+        assert_eq!(DateTime::parse_rfc3339("2025-13-01T00:00:00Z"), None);
+        assert_eq!(DateTime::parse_rfc3339("yesterday"), None);
+    }
+
+    #[test]
+    fn test_format_date_in() {
+        let locale = |locale| format_date_in(OCT_15_2025, locale, &DateFormat::Locale);
+        assert_eq!(locale(Locale::English), "Oct 15, 2025 12:34 UTC");
+        assert_eq!(locale(Locale::Japanese), "2025年10月15日 12:34 UTC");
+        assert_eq!(locale(Locale::Spanish), "15 oct 2025, 12:34 UTC");
+
+        assert_eq!(
+            format_date_in(OCT_15_2025, Locale::Spanish, &DateFormat::Iso),
+            "2025-10-15T12:34:56Z"
+        );
+        let pattern: DateFormat = "%d/%m/%Y %H:%M (%b) %% %q".parse().unwrap();
+        assert_eq!(
+            format_date_in(OCT_15_2025, Locale::English, &pattern),
+            "15/10/2025 12:34 (Oct) % %q"
+        );
+        assert!("yyyy-mm-dd".parse::<DateFormat>().is_err());
+    }
+
+    #[test]
+    fn test_format_numbers_in() {
+        assert_eq!(
+            format_decimal_in(1_234_567.25, 1, Locale::English),
+            "1,234,567.2"
+        );
+        assert_eq!(
+            format_decimal_in(1_234_567.25, 1, Locale::Spanish),
+            "1.234.567,2"
+        );
+        assert_eq!(format_decimal_in(1_234.5, 1, Locale::Spanish), "1234,5");
+        assert_eq!(format_decimal_in(999.96, 1, Locale::English), "1,000.0");
+        assert_eq!(format_decimal_in(-0.01, 1, Locale::English), "0.0");
+        assert_eq!(format_decimal_in(-12.5, 0, Locale::Japanese), "-12");
+        assert_eq!(group_digits("12345", Locale::Spanish), "12.345");
+
+        assert_eq!(format_percent_in(0.955, 1, Locale::English), "95.5%");
+        assert_eq!(format_percent_in(0.955, 1, Locale::Spanish), "95,5\u{a0}%");
+    }
+}
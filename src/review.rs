@@ -0,0 +1,184 @@
+//! Reviewer-style comments on staged hunks, for a self-review before committing
+//!
+//! The hunks are numbered in the prompt as they are for `split`, and the model
+//! answers with one comment per line naming the hunk it is about. Comments are
+//! advice only: nothing is blocked, and a reply with no comments is a clean review.
+
+use crate::types::DiffHunk;
+use colored::*;
+
+/// What a review comment is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentKind {
+    /// A likely defect, such as an unhandled error or an off-by-one
+    Bug,
+    /// Behaviour the change adds or alters without a test
+    Test,
+    /// Anything else worth a second look
+    Note,
+}
+
+impl CommentKind {
+    /// Read the kind the model gave, treating anything unrecognised as a note
+    pub fn parse(kind: &str) -> Self {
+        match kind.trim().to_ascii_lowercase().as_str() {
+            "bug" | "bugs" => Self::Bug,
+            "test" | "tests" => Self::Test,
+            _ => Self::Note,
+        }
+    }
+
+    /// Get the label shown before the comment
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Bug => "bug",
+            Self::Test => "test",
+            Self::Note => "note",
+        }
+    }
+}
+
+/// A comment on one staged hunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReviewComment {
+    /// Zero-based index into the staged hunks
+    pub hunk: usize,
+    pub kind: CommentKind,
+    pub text: String,
+}
+
+/// Parse the model's `<hunk> | <kind> | <comment>` lines
+///
+/// Lines that don't follow the format or name a hunk that doesn't exist are
+/// dropped, so a reply of `none` parses to no comments.
+pub fn parse_review(response: &str, hunk_count: usize) -> Vec<ReviewComment> {
+    response
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '|');
+            let hunk = parts
+                .next()?
+                .trim()
+                .trim_start_matches('#')
+                .parse::<usize>()
+                .ok()?;
+            let kind = CommentKind::parse(parts.next()?);
+            let text = parts.next()?.trim();
+            if hunk == 0 || hunk > hunk_count || text.is_empty() {
+                return None;
+            }
+            Some(ReviewComment {
+                hunk: hunk - 1,
+                kind,
+                text: text.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Render the comments grouped under the hunk they are about, in diff order
+pub fn render_review(comments: &[ReviewComment], hunks: &[DiffHunk]) -> String {
+    let mut sorted: Vec<&ReviewComment> = comments.iter().collect();
+    sorted.sort_by_key(|comment| comment.hunk);
+
+    let mut rendered = String::new();
+    let mut current = None;
+    for comment in sorted {
+        if current != Some(comment.hunk) {
+            if current.is_some() {
+                rendered.push('\n');
+            }
+            current = Some(comment.hunk);
+            let hunk = &hunks[comment.hunk];
+            let header = hunk
+                .text
+                .as_deref()
+                .and_then(|text| text.lines().next())
+                .unwrap_or("whole file");
+            rendered.push_str(&format!("{} {}\n", hunk.file_path.bold(), header.dimmed()));
+        }
+        let label = format!("{:<4}", comment.kind.label());
+        let label = match comment.kind {
+            CommentKind::Bug => label.red(),
+            CommentKind::Test => label.yellow(),
+            CommentKind::Note => label.normal(),
+        };
+        rendered.push_str(&format!("  {label} {}\n", comment.text));
+    }
+    rendered
+}
+
+/// Print the review, or say there was nothing to comment on
+pub fn display_review(comments: &[ReviewComment], hunks: &[DiffHunk]) {
+    if comments.is_empty() {
+        println!("{}", "No comments on the staged changes.".green());
+        return;
+    }
+    print!("{}", render_review(comments, hunks));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hunk(path: &str, text: &str) -> DiffHunk {
+        DiffHunk {
+            file_path: path.to_string(),
+            file_header: format!("diff --git a/{path} b/{path}\n"),
+            text: Some(text.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_parse_review() {
+        let response = "1 | bug | `unwrap` panics on an empty token\n\
+                        #2 | tests | Nothing tests logout\n\
+                        2 | style | Prefer `is_empty`\n\
+                        5 | bug | Out of range\n\
+                        none";
+        assert_eq!(
+            parse_review(response, 2),
+            vec![
+                ReviewComment {
+                    hunk: 0,
+                    kind: CommentKind::Bug,
+                    text: "`unwrap` panics on an empty token".to_string()
+                },
+                ReviewComment {
+                    hunk: 1,
+                    kind: CommentKind::Test,
+                    text: "Nothing tests logout".to_string()
+                },
+                ReviewComment {
+                    hunk: 1,
+                    kind: CommentKind::Note,
+                    text: "Prefer `is_empty`".to_string()
+                },
+            ]
+        );
+        assert!(parse_review("none", 2).is_empty());
+    }
+
+    #[test]
+    fn test_render_review() {
+        let hunks = vec![
+            hunk("src/auth.rs", "@@ -1,3 +1,4 @@ fn login\n+let a = 1;\n"),
+            hunk(
+                "src/session.rs",
+                "@@ -8,2 +8,5 @@ impl Session\n+fn logout() {}\n",
+            ),
+        ];
+        let comments = parse_review(
+            "2 | test | Nothing tests logout\n1 | bug | `a` is unused\n2 | note | Document it",
+            hunks.len(),
+        );
+        assert_eq!(
+            render_review(&comments, &hunks),
+            "src/auth.rs @@ -1,3 +1,4 @@ fn login\n  \
+             bug  `a` is unused\n\n\
+             src/session.rs @@ -8,2 +8,5 @@ impl Session\n  \
+             test Nothing tests logout\n  \
+             note Document it\n"
+        );
+    }
+}
@@ -0,0 +1,376 @@
+//! Regenerating the messages of a range of existing commits
+//!
+//! `rewrite` replays each commit of `<base>..HEAD` through the model, then applies
+//! the new messages with `git rebase -i`: the todo list is written by committor,
+//! each `pick` followed by an `exec` that amends the message, so trees, authors
+//! and dates are untouched. The original HEAD is backed up for `committor restore`.
+
+use crate::backup::{create_backup_in, drop_backup_in};
+use crate::commit::read_input;
+use crate::diff::get_commit_diff_from_repo;
+use crate::git::git_output;
+use crate::i18n::Text;
+use crate::output::{is_plain, success_mark};
+use crate::trailers::{append_trailers, message_trailers};
+use crate::types::CommittorError;
+use anyhow::{Context, Result};
+use colored::*;
+use git2::Repository;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::Command;
+
+/// Directory under `.git` holding the todo list and message files during a rewrite
+const REWRITE_DIR_NAME: &str = "committor-rewrite";
+
+/// A commit whose message is being regenerated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RewriteCommit {
+    pub commit_id: String,
+    /// The full message it was committed with
+    pub original: String,
+    /// The change the commit introduced
+    pub diff: String,
+    /// The regenerated message, `None` to keep the original
+    pub message: Option<String>,
+}
+
+impl RewriteCommit {
+    /// Get the message the commit ends up with, keeping the original's trailers
+    pub fn final_message(&self) -> Option<String> {
+        let message = self.message.as_ref()?;
+        Some(append_trailers(message, &message_trailers(&self.original)))
+    }
+}
+
+/// The commits of a range, oldest first, and the commit they sit on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RewritePlan {
+    pub base: String,
+    pub commits: Vec<RewriteCommit>,
+}
+
+/// Collect the commits of `<base>..HEAD` in the current repository
+pub fn collect_range(range: &str) -> Result<RewritePlan> {
+    let repo = Repository::open(".").context("Not in a git repository")?;
+    collect_range_from_repo(&repo, range)
+}
+
+/// Collect the commits of `<base>..HEAD` in a specific repository, oldest first
+///
+/// The range must end at HEAD and hold no merge commits, since a rebase would
+/// flatten them.
+pub fn collect_range_from_repo(repo: &Repository, range: &str) -> Result<RewritePlan> {
+    let invalid =
+        || CommittorError::GitError(format!("Expected a range like main..HEAD, got {range}"));
+    let revspec = repo.revparse(range).map_err(|_| invalid())?;
+    if !revspec.mode().contains(git2::RevparseMode::RANGE)
+        || revspec.mode().contains(git2::RevparseMode::MERGE_BASE)
+    {
+        return Err(invalid().into());
+    }
+    let (Some(from), Some(to)) = (revspec.from(), revspec.to()) else {
+        return Err(invalid().into());
+    };
+    let base = from.peel_to_commit()?.id();
+    let head = repo.head()?.peel_to_commit()?.id();
+    if to.peel_to_commit()?.id() != head {
+        return Err(CommittorError::GitError(format!(
+            "Only commits up to HEAD can be rewritten; use {}..HEAD",
+            range.split("..").next().unwrap_or_default()
+        ))
+        .into());
+    }
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE)?;
+    revwalk.push(head)?;
+    revwalk.hide(base)?;
+
+    let mut commits = Vec::new();
+    for oid in revwalk {
+        let commit = repo.find_commit(oid?)?;
+        if commit.parent_count() > 1 {
+            return Err(CommittorError::GitError(format!(
+                "{range} contains the merge commit {}; only linear history can be rewritten",
+                short_id(&commit.id().to_string())
+            ))
+            .into());
+        }
+        commits.push(RewriteCommit {
+            commit_id: commit.id().to_string(),
+            original: commit.message().unwrap_or_default().trim_end().to_string(),
+            diff: get_commit_diff_from_repo(repo, &commit)?,
+            message: None,
+        });
+    }
+
+    if commits.is_empty() {
+        return Err(anyhow::anyhow!("No commits found in {range}"));
+    }
+    Ok(RewritePlan {
+        base: base.to_string(),
+        commits,
+    })
+}
+
+/// Render each commit's subject before and after, noting the ones kept as they are
+pub fn render_rewrite(plan: &RewritePlan) -> String {
+    let arrow = if is_plain() {
+        Text::RewriteBecomes.text()
+    } else {
+        "\u{2192}"
+    };
+    let mut rendered = String::new();
+    for commit in &plan.commits {
+        let original = commit.original.lines().next().unwrap_or_default();
+        rendered.push_str(&format!("{} {original}\n", short_id(&commit.commit_id)));
+        match &commit.message {
+            Some(message) if message.lines().next() != Some(original) => {
+                let subject = message.lines().next().unwrap_or_default();
+                rendered.push_str(&format!("  {arrow} {}\n", subject.green()));
+            }
+            Some(_) => rendered.push_str(&format!("  {}\n", Text::RewriteUnchanged.text())),
+            None => rendered.push_str(&format!("  {}\n", Text::RewriteKept.text().yellow())),
+        }
+    }
+    rendered
+}
+
+/// Show the rewrite before it is applied
+pub fn display_rewrite(plan: &RewritePlan) {
+    println!("{}", Text::ProposedMessages.text().green().bold());
+    println!();
+    print!("{}", render_rewrite(plan));
+    println!();
+}
+
+/// Ask the user to confirm the rewrite
+pub fn prompt_confirmation() -> Result<bool> {
+    print!("{}", Text::ConfirmRewrite.text().yellow());
+    io::stdout().flush()?;
+
+    let input = read_input()?.unwrap_or_default();
+    Ok(input.eq_ignore_ascii_case("y") || input.eq_ignore_ascii_case("yes"))
+}
+
+/// Build the rebase todo list: each commit is picked, then amended with its message file
+///
+/// Commits keeping their message are picked alone.
+pub fn render_todo(plan: &RewritePlan, message_dir: &Path) -> String {
+    let mut todo = String::new();
+    for commit in &plan.commits {
+        let subject = commit.original.lines().next().unwrap_or_default();
+        todo.push_str(&format!("pick {} {subject}\n", commit.commit_id));
+        if commit.message.is_some() {
+            let file = message_dir.join(&commit.commit_id);
+            todo.push_str(&format!(
+                "exec git commit --amend --allow-empty --no-verify -q -F {}\n",
+                shell_quote(&file.to_string_lossy())
+            ));
+        }
+    }
+    todo
+}
+
+/// Apply a rewrite plan in the current repository
+pub fn apply_rewrite(plan: &RewritePlan) -> Result<()> {
+    apply_rewrite_in(Path::new("."), plan)
+}
+
+/// Apply a rewrite plan with an automated `git rebase -i`, aborting it if any step fails
+///
+/// HEAD is backed up first, and the backup is kept afterwards so `committor restore`
+/// can undo the rewrite.
+pub fn apply_rewrite_in(dir: &Path, plan: &RewritePlan) -> Result<()> {
+    let status = git_output(dir, &["status", "--porcelain", "--untracked-files=no"])?;
+    if !status.is_empty() {
+        return Err(CommittorError::GitError(
+            "The working tree has uncommitted changes; commit or stash them before rewriting"
+                .to_string(),
+        )
+        .into());
+    }
+
+    let backup = create_backup_in(dir)?.context("There is no HEAD to rewrite")?;
+
+    let work_dir = dir.join(git_output(
+        dir,
+        &["rev-parse", "--git-path", REWRITE_DIR_NAME],
+    )?);
+    fs::create_dir_all(&work_dir)?;
+    // The exec lines run from the top of the working tree, wherever committor was started
+    let work_dir = work_dir.canonicalize()?;
+    let result = run_rebase(dir, &work_dir, plan);
+    let _ = fs::remove_dir_all(&work_dir);
+
+    if let Err(e) = result {
+        println!("{}", Text::RewriteFailed.text().red().bold());
+        if git_output(dir, &["rebase", "--abort"]).is_ok() {
+            drop_backup_in(dir, &backup)?;
+            return Err(e);
+        }
+        return Err(e.context(format!("The original HEAD is kept at {}", backup.name)));
+    }
+
+    println!(
+        "{}",
+        format!(
+            "{} {}",
+            success_mark(),
+            Text::RewriteDone.format(&[&plan
+                .commits
+                .iter()
+                .filter(|c| c.message.is_some())
+                .count()])
+        )
+        .green()
+    );
+    Ok(())
+}
+
+/// Write the message files and todo list, then run the rebase with them
+fn run_rebase(dir: &Path, work_dir: &Path, plan: &RewritePlan) -> Result<()> {
+    for commit in &plan.commits {
+        if let Some(message) = commit.final_message() {
+            fs::write(work_dir.join(&commit.commit_id), format!("{message}\n"))?;
+        }
+    }
+    let todo_path = work_dir.join("todo");
+    fs::write(&todo_path, render_todo(plan, work_dir))?;
+
+    // Git runs the sequence editor through the shell with the todo file as its argument
+    let sequence_editor = format!("cp {}", shell_quote(&todo_path.to_string_lossy()));
+    let output = Command::new("git")
+        .args([
+            "rebase",
+            "-i",
+            "--no-autosquash",
+            "--keep-empty",
+            &plan.base,
+        ])
+        .current_dir(dir)
+        .env("GIT_SEQUENCE_EDITOR", sequence_editor)
+        .env("GIT_EDITOR", "true")
+        .output()
+        .context("Failed to execute git rebase")?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(CommittorError::GitError(String::from_utf8_lossy(&output.stderr).to_string()).into())
+    }
+}
+
+/// Quote a value for `sh`
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+fn short_id(commit_id: &str) -> &str {
+    &commit_id[..commit_id.len().min(7)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fixtures::{commit, git, init_repo};
+    use tempfile::TempDir;
+
+    fn repo_with_history() -> TempDir {
+        let temp_dir = init_repo();
+        for (file, message) in [
+            ("a.txt", "initial"),
+            ("b.txt", "wip"),
+            ("c.txt", "more stuff\n\nReviewed-by: Ann <ann@example.com>"),
+        ] {
+            commit(temp_dir.path(), file, file, message);
+        }
+        temp_dir
+    }
+
+    #[test]
+    fn test_collect_range() {
+        let temp_dir = repo_with_history();
+        let repo = Repository::open(temp_dir.path()).unwrap();
+
+        let plan = collect_range_from_repo(&repo, "HEAD~2..HEAD").unwrap();
+        let originals: Vec<&str> = plan.commits.iter().map(|c| c.original.as_str()).collect();
+        assert_eq!(
+            originals,
+            vec!["wip", "more stuff\n\nReviewed-by: Ann <ann@example.com>"]
+        );
+        assert!(plan.commits[0].diff.contains("+b.txt"));
+        assert_eq!(plan.base, git(temp_dir.path(), &["rev-parse", "HEAD~2"]));
+
+        assert!(collect_range_from_repo(&repo, "HEAD~2..HEAD~1").is_err());
+        assert!(collect_range_from_repo(&repo, "HEAD~2").is_err());
+        assert!(collect_range_from_repo(&repo, "HEAD..HEAD").is_err());
+    }
+
+    #[test]
+    fn test_render_todo() {
+        let commit = |id: &str, message: Option<&str>| RewriteCommit {
+            commit_id: id.to_string(),
+            original: format!("old {id}"),
+            diff: String::new(),
+            message: message.map(str::to_string),
+        };
+        let plan = RewritePlan {
+            base: "base".to_string(),
+            commits: vec![commit("aaa", Some("feat: add a")), commit("bbb", None)],
+        };
+
+        assert_eq!(
+            render_todo(&plan, Path::new("/tmp/it's")),
+            "pick aaa old aaa\n\
+             exec git commit --amend --allow-empty --no-verify -q -F '/tmp/it'\\''s/aaa'\n\
+             pick bbb old bbb\n"
+        );
+    }
+
+    #[test]
+    fn test_apply_rewrite() {
+        let temp_dir = repo_with_history();
+        let dir = temp_dir.path();
+        let repo = Repository::open(dir).unwrap();
+        let original_head = git(dir, &["rev-parse", "HEAD"]);
+        let original_tree = git(dir, &["rev-parse", "HEAD^{tree}"]);
+
+        let mut plan = collect_range_from_repo(&repo, "HEAD~2..HEAD").unwrap();
+        plan.commits[0].message = Some("feat: add b".to_string());
+        plan.commits[1].message = Some("feat: add c".to_string());
+        apply_rewrite_in(dir, &plan).unwrap();
+
+        assert_eq!(
+            git(dir, &["log", "--format=%B%x00"])
+                .split('\0')
+                .map(str::trim)
+                .filter(|m| !m.is_empty())
+                .collect::<Vec<_>>(),
+            vec![
+                "feat: add c\n\nReviewed-by: Ann <ann@example.com>",
+                "feat: add b",
+                "initial"
+            ]
+        );
+        assert_eq!(git(dir, &["rev-parse", "HEAD^{tree}"]), original_tree);
+        let backups = crate::backup::list_backups_in(dir).unwrap();
+        assert_eq!(backups[0].commit, original_head);
+        assert!(!dir.join(".git").join(REWRITE_DIR_NAME).exists());
+    }
+
+    #[test]
+    fn test_apply_rewrite_refuses_uncommitted_changes() {
+        let temp_dir = repo_with_history();
+        let dir = temp_dir.path();
+        let repo = Repository::open(dir).unwrap();
+        let mut plan = collect_range_from_repo(&repo, "HEAD~1..HEAD").unwrap();
+        plan.commits[0].message = Some("feat: add c".to_string());
+
+        fs::write(dir.join("a.txt"), "edited").unwrap();
+        assert!(apply_rewrite_in(dir, &plan).is_err());
+        assert_eq!(git(dir, &["log", "-1", "--format=%s"]), "more stuff");
+    }
+}
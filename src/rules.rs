@@ -0,0 +1,872 @@
+//! Rule-based validation of commit messages
+
+use crate::commit::parse_commit_message;
+use crate::postprocess::{
+    imperative_form, lowercase_type, recase_scope, recase_subject, strip_emojis, unknown_emojis,
+};
+use crate::settings::Settings;
+use crate::trailers::missing_trailers;
+use crate::validate;
+use crate::vocabulary::SpellcheckMode;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+
+/// Validation rules applied to commit messages
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rules {
+    /// Maximum length of the subject line
+    pub max_subject_length: usize,
+    /// Commit types that may be used; empty allows any type
+    pub allowed_types: Vec<String>,
+    /// Scopes that may be used; empty allows any scope
+    pub allowed_scopes: Vec<String>,
+    /// Whether every message must have a scope
+    pub require_scope: bool,
+    /// Emojis, or `:shortcode:` gitmojis, that may appear in a subject
+    pub allowed_emojis: Vec<String>,
+    /// How generated subjects with uppercase types or unknown emojis are handled
+    pub strictness: Strictness,
+    /// How generated messages are checked against the repository vocabulary
+    pub spellcheck: SpellcheckMode,
+    /// Terms such as codenames that must never appear in a message, matched case-insensitively
+    pub forbidden_words: Vec<String>,
+    /// Replacements used when correcting forbidden words; words without one are removed
+    pub forbidden_replacements: BTreeMap<String, String>,
+    /// Case the description starts in; either is accepted when unset
+    pub subject_case: Option<Case>,
+    /// Case scopes are written in; any is accepted when unset
+    pub scope_case: Option<Case>,
+    /// Whether descriptions must open with an imperative verb, `add` rather than `added`
+    pub imperative_mood: bool,
+}
+
+/// Case style of a description's first word or of a scope
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Case {
+    /// `feat(auth): add login`; acronyms like `API` starting a description are kept
+    Lower,
+    /// `feat(Auth): Add login`
+    Sentence,
+}
+
+/// How generated output that breaks the header rules is handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Strictness {
+    /// Lowercase the type and strip unknown emojis
+    #[default]
+    Correct,
+    /// Discard the output
+    Reject,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            max_subject_length: 72,
+            allowed_types: Vec::new(),
+            allowed_scopes: Vec::new(),
+            require_scope: false,
+            allowed_emojis: Vec::new(),
+            strictness: Strictness::default(),
+            spellcheck: SpellcheckMode::default(),
+            forbidden_words: Vec::new(),
+            forbidden_replacements: BTreeMap::new(),
+            subject_case: None,
+            scope_case: None,
+            imperative_mood: false,
+        }
+    }
+}
+
+/// A single rule broken by a commit message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Violation {
+    pub rule: String,
+    pub message: String,
+}
+
+impl Violation {
+    pub fn new(rule: &str, message: impl Into<String>) -> Self {
+        Self {
+            rule: rule.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.rule)
+    }
+}
+
+/// A check a commit message must pass
+///
+/// The built-in rules are configured from [`Rules`] and `[trailers]`; library
+/// embedders can add their own to a [`RuleSet`], or to a `Config` with `with_rule`.
+pub trait Rule: Send + Sync {
+    /// Check a message whose subject line isn't empty, returning every violation found
+    fn check(&self, message: &str) -> Vec<Violation>;
+}
+
+/// Rules a message is checked against, in order
+#[derive(Clone, Default)]
+pub struct RuleSet {
+    rules: Vec<Arc<dyn Rule>>,
+}
+
+impl RuleSet {
+    /// Create a set with no rules
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build the built-in rules that `[rules]` configures
+    pub fn from_rules(rules: &Rules) -> Self {
+        let emojis = rules.allowed_emojis.clone();
+        let mut set = Self::new()
+            .with(SubjectLength {
+                max: rules.max_subject_length,
+            })
+            .with(TypeRule {
+                allowed: rules.allowed_types.clone(),
+                emojis: emojis.clone(),
+            })
+            .with(EmojiRule {
+                allowed: emojis.clone(),
+            });
+        if let Some(case) = rules.subject_case {
+            set = set.with(DescriptionCase {
+                case,
+                emojis: emojis.clone(),
+            });
+        }
+        set = set.with(ScopeRule {
+            allowed: rules.allowed_scopes.clone(),
+            required: rules.require_scope,
+            case: rules.scope_case,
+            emojis: emojis.clone(),
+        });
+        if rules.imperative_mood {
+            set = set.with(ImperativeMood { emojis });
+        }
+        set.with(ForbiddenWordsRule {
+            words: rules.forbidden_words.clone(),
+        })
+        .with(BodySeparator)
+    }
+
+    /// Build the built-in rules the settings configure, required trailers included
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self::from_rules(&settings.rules).with(RequiredTrailers {
+            keys: settings.trailers.required.clone(),
+        })
+    }
+
+    /// Add a rule to the end of the set
+    pub fn with(mut self, rule: impl Rule + 'static) -> Self {
+        self.rules.push(Arc::new(rule));
+        self
+    }
+
+    /// Add every rule of another set to the end of this one
+    pub fn extend(&mut self, other: &RuleSet) {
+        self.rules.extend(other.rules.iter().cloned());
+    }
+
+    /// Check whether the set has no rules
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Check a full commit message, returning every violation found
+    pub fn check(&self, message: &str) -> Vec<Violation> {
+        let subject = message.lines().next().unwrap_or("").trim_end();
+        if subject.is_empty() {
+            return vec![Violation::new("subject-empty", "Subject line is empty")];
+        }
+        self.rules
+            .iter()
+            .flat_map(|rule| rule.check(message))
+            .collect()
+    }
+
+    /// Check whether a message breaks none of the rules
+    pub fn is_valid(&self, message: &str) -> bool {
+        self.check(message).is_empty()
+    }
+}
+
+impl fmt::Debug for RuleSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RuleSet({} rules)", self.rules.len())
+    }
+}
+
+/// Check a full commit message against the rules, returning every violation found
+pub fn lint_message(message: &str, rules: &Rules) -> Vec<Violation> {
+    RuleSet::from_rules(rules).check(message)
+}
+
+/// Report each required trailer the message doesn't carry
+pub fn lint_trailers(message: &str, required: &[String]) -> Vec<Violation> {
+    RequiredTrailers {
+        keys: required.to_vec(),
+    }
+    .check(message)
+}
+
+fn subject(message: &str) -> &str {
+    message.lines().next().unwrap_or("").trim_end()
+}
+
+/// Lowercase the type and strip unknown emojis, so each slip is reported by one rule only
+fn corrected_subject(message: &str, emojis: &[String]) -> String {
+    lowercase_type(&strip_emojis(subject(message), emojis))
+}
+
+/// The subject fits in `max_subject_length` characters
+pub struct SubjectLength {
+    pub max: usize,
+}
+
+impl Rule for SubjectLength {
+    fn check(&self, message: &str) -> Vec<Violation> {
+        let length = validate::subject_length(message);
+        if length <= self.max {
+            return Vec::new();
+        }
+        vec![Violation::new(
+            "subject-length",
+            format!("Subject is {length} characters, maximum is {}", self.max),
+        )]
+    }
+}
+
+/// The subject follows the conventional format with a lowercase type, one of `allowed` if set
+pub struct TypeRule {
+    pub allowed: Vec<String>,
+    pub emojis: Vec<String>,
+}
+
+impl Rule for TypeRule {
+    fn check(&self, message: &str) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        let subject = subject(message);
+        if lowercase_type(subject) != subject {
+            violations.push(Violation::new("type-case", "Commit type must be lowercase"));
+        }
+        match parse_commit_message(&corrected_subject(message, &self.emojis)) {
+            Ok(commit) => {
+                let commit_type = commit.commit_type.to_string();
+                if !self.allowed.is_empty() && !self.allowed.contains(&commit_type) {
+                    violations.push(Violation::new(
+                        "type-allowed",
+                        format!(
+                            "Type '{commit_type}' is not one of: {}",
+                            self.allowed.join(", ")
+                        ),
+                    ));
+                }
+            }
+            Err(_) => violations.push(Violation::new(
+                "conventional-format",
+                "Subject does not follow the <type>(<scope>): <description> format",
+            )),
+        }
+        violations
+    }
+}
+
+/// The whole message is a single conventional subject line, exactly as written
+///
+/// Unlike [`TypeRule`], nothing is corrected first; generated subjects are held to this.
+pub struct ConventionalFormat;
+
+impl Rule for ConventionalFormat {
+    fn check(&self, message: &str) -> Vec<Violation> {
+        match parse_commit_message(message) {
+            Ok(_) => Vec::new(),
+            Err(_) => vec![Violation::new(
+                "conventional-format",
+                "Subject does not follow the <type>(<scope>): <description> format",
+            )],
+        }
+    }
+}
+
+/// The subject has no emojis but the `allowed` ones
+pub struct EmojiRule {
+    pub allowed: Vec<String>,
+}
+
+impl Rule for EmojiRule {
+    fn check(&self, message: &str) -> Vec<Violation> {
+        let emojis = unknown_emojis(subject(message), &self.allowed);
+        if emojis.is_empty() {
+            return Vec::new();
+        }
+        vec![Violation::new(
+            "emoji",
+            format!("Subject contains unknown emojis: {}", emojis.join(" ")),
+        )]
+    }
+}
+
+/// The description starts in the configured case
+pub struct DescriptionCase {
+    pub case: Case,
+    pub emojis: Vec<String>,
+}
+
+impl Rule for DescriptionCase {
+    fn check(&self, message: &str) -> Vec<Violation> {
+        let corrected = corrected_subject(message, &self.emojis);
+        if recase_subject(&corrected, self.case) == corrected {
+            return Vec::new();
+        }
+        vec![Violation::new(
+            "subject-case",
+            match self.case {
+                Case::Lower => "Description must start in lowercase",
+                Case::Sentence => "Description must start with a capital letter",
+            },
+        )]
+    }
+}
+
+/// The scope is in the configured case and one of `allowed` if set, and present if `required`
+pub struct ScopeRule {
+    pub allowed: Vec<String>,
+    pub required: bool,
+    pub case: Option<Case>,
+    pub emojis: Vec<String>,
+}
+
+impl Rule for ScopeRule {
+    fn check(&self, message: &str) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        let corrected = corrected_subject(message, &self.emojis);
+        if let Some(case) = self.case {
+            if recase_scope(&corrected, case) != corrected {
+                violations.push(Violation::new(
+                    "scope-case",
+                    match case {
+                        Case::Lower => "Scope must be lowercase",
+                        Case::Sentence => "Scope must start with a capital letter",
+                    },
+                ));
+            }
+        }
+        // A subject that doesn't parse is reported by the type rule
+        let Ok(commit) = parse_commit_message(&corrected) else {
+            return violations;
+        };
+        match &commit.scope {
+            Some(scope) if !self.allowed.is_empty() && !self.allowed.contains(scope) => {
+                violations.push(Violation::new(
+                    "scope-allowed",
+                    format!("Scope '{scope}' is not one of: {}", self.allowed.join(", ")),
+                ));
+            }
+            None if self.required => {
+                violations.push(Violation::new("scope-required", "A scope is required"));
+            }
+            _ => {}
+        }
+        violations
+    }
+}
+
+/// The description opens with an imperative verb, `add` rather than `added` or `adds`
+pub struct ImperativeMood {
+    pub emojis: Vec<String>,
+}
+
+impl Rule for ImperativeMood {
+    fn check(&self, message: &str) -> Vec<Violation> {
+        let Ok(commit) = parse_commit_message(&corrected_subject(message, &self.emojis)) else {
+            return Vec::new();
+        };
+        let first_word = commit
+            .description
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        match imperative_form(&first_word) {
+            Some(imperative) => vec![Violation::new(
+                "imperative-mood",
+                format!("Description must start with '{imperative}', not '{first_word}'"),
+            )],
+            None => Vec::new(),
+        }
+    }
+}
+
+/// The message contains none of the forbidden words
+pub struct ForbiddenWordsRule {
+    pub words: Vec<String>,
+}
+
+impl Rule for ForbiddenWordsRule {
+    fn check(&self, message: &str) -> Vec<Violation> {
+        let forbidden = find_forbidden_words(message, &self.words);
+        if forbidden.is_empty() {
+            return Vec::new();
+        }
+        vec![Violation::new(
+            "forbidden-word",
+            format!("Message contains forbidden words: {}", forbidden.join(", ")),
+        )]
+    }
+}
+
+/// A blank line separates the subject from any body
+pub struct BodySeparator;
+
+impl Rule for BodySeparator {
+    fn check(&self, message: &str) -> Vec<Violation> {
+        if validate::body_separated(message) {
+            return Vec::new();
+        }
+        vec![Violation::new(
+            "body-separator",
+            "Subject must be separated from the body by a blank line",
+        )]
+    }
+}
+
+/// The message carries every required trailer
+pub struct RequiredTrailers {
+    pub keys: Vec<String>,
+}
+
+impl Rule for RequiredTrailers {
+    fn check(&self, message: &str) -> Vec<Violation> {
+        missing_trailers(message, &self.keys)
+            .into_iter()
+            .map(|key| {
+                Violation::new(
+                    "trailer-required",
+                    format!("Missing required trailer '{key}'"),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Apply the header rules to generated output, returning `None` when it must be rejected
+pub fn apply_header_rules(message: &str, rules: &Rules) -> Option<String> {
+    correct_subject(message, rules.strictness, |subject| {
+        recase_header(
+            &lowercase_type(&strip_emojis(subject, &rules.allowed_emojis)),
+            rules,
+        )
+    })
+}
+
+/// Correct the subject of a message, returning `None` when the strictness rejects any change
+pub fn correct_subject(
+    message: &str,
+    strictness: Strictness,
+    correct: impl FnOnce(&str) -> String,
+) -> Option<String> {
+    let (subject, body) = match message.split_once('\n') {
+        Some((subject, body)) => (subject, Some(body)),
+        None => (message, None),
+    };
+
+    let corrected = correct(subject);
+    if corrected == subject.trim() {
+        return Some(message.to_string());
+    }
+
+    match strictness {
+        Strictness::Reject => None,
+        Strictness::Correct => Some(match body {
+            Some(body) => format!("{corrected}\n{body}"),
+            None => corrected,
+        }),
+    }
+}
+
+/// Put the description and scope of a subject in the configured case styles
+pub fn recase_header(subject: &str, rules: &Rules) -> String {
+    let subject = match rules.subject_case {
+        Some(case) => recase_subject(subject, case),
+        None => subject.to_string(),
+    };
+    match rules.scope_case {
+        Some(case) => recase_scope(&subject, case),
+        None => subject,
+    }
+}
+
+/// Apply every rule that can correct generated output, returning `None` when it must be rejected
+pub fn apply_rules(message: &str, rules: &Rules) -> Option<String> {
+    let message = apply_header_rules(message, rules)?;
+    apply_forbidden_words(&message, rules)
+}
+
+/// Rewrite or reject a message that contains forbidden words
+pub fn apply_forbidden_words(message: &str, rules: &Rules) -> Option<String> {
+    let forbidden = find_forbidden_words(message, &rules.forbidden_words);
+    if forbidden.is_empty() {
+        return Some(message.to_string());
+    }
+    if rules.strictness == Strictness::Reject {
+        return None;
+    }
+
+    let rewritten = message
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let mut replaced = line.to_string();
+            for word in &forbidden {
+                let replacement = rules
+                    .forbidden_replacements
+                    .iter()
+                    .find(|(key, _)| key.eq_ignore_ascii_case(word))
+                    .map_or("", |(_, replacement)| replacement.as_str());
+                replaced = replace_forbidden_word(&replaced, word, replacement);
+            }
+            if replaced == line {
+                replaced
+            } else {
+                tidy_rewritten_line(&replaced, i == 0)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    // A message that was nothing but forbidden words can't be salvaged
+    let subject = rewritten.lines().next().unwrap_or("");
+    parse_commit_message(subject).ok().map(|_| rewritten)
+}
+
+/// Replace a forbidden word in one line, taking the spaces of a removed word with it
+///
+/// A word removed between others leaves one gap, and one at either end of the
+/// line leaves none, so spacing elsewhere on the line stays as written.
+fn replace_forbidden_word(line: &str, word: &str, replacement: &str) -> String {
+    let pattern = format!(
+        r"([ \t]*)(?:{})([ \t]*)",
+        forbidden_word_regex(word).as_str()
+    );
+    let regex = regex::Regex::new(&pattern).unwrap();
+    regex
+        .replace_all(line, |captures: &regex::Captures| {
+            let whole = captures.get(0).unwrap();
+            let (before, after) = (&captures[1], &captures[2]);
+            if !replacement.is_empty() {
+                format!("{before}{replacement}{after}")
+            } else if whole.start() == 0 {
+                // Keep the indentation of a line that started with the word
+                before.to_string()
+            } else if whole.end() == line.len() {
+                String::new()
+            } else if before.is_empty() {
+                after.to_string()
+            } else {
+                before.to_string()
+            }
+        })
+        .into_owned()
+}
+
+/// Tidy the header after a forbidden word was removed from it
+///
+/// A removed scope leaves `()` behind, and only that prefix is touched, never the description.
+fn tidy_rewritten_line(line: &str, is_header: bool) -> String {
+    if !is_header {
+        return line.to_string();
+    }
+    static EMPTY_SCOPE: OnceLock<regex::Regex> = OnceLock::new();
+    let empty_scope =
+        EMPTY_SCOPE.get_or_init(|| regex::Regex::new(r"^(\w+)(?:\(\s*\))?\s*(!?)\s*:").unwrap());
+    empty_scope.replace(line, "$1$2:").into_owned()
+}
+
+/// Find which forbidden words appear in a message as whole words
+pub fn find_forbidden_words(message: &str, forbidden_words: &[String]) -> Vec<String> {
+    forbidden_words
+        .iter()
+        .filter(|word| !word.trim().is_empty() && forbidden_word_regex(word).is_match(message))
+        .cloned()
+        .collect()
+}
+
+/// Build a case-insensitive whole-word pattern for a forbidden word
+///
+/// Word boundaries only go next to word characters: `C++` and `@internal` have
+/// none at their symbol ends, so `\b` there would never match.
+fn forbidden_word_regex(word: &str) -> regex::Regex {
+    let word = word.trim();
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    let start = if word.starts_with(is_word_char) {
+        r"\b"
+    } else {
+        ""
+    };
+    let end = if word.ends_with(is_word_char) {
+        r"\b"
+    } else {
+        ""
+    };
+    regex::Regex::new(&format!(r"(?i){start}{}{end}", regex::escape(word))).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_messages() {
+        let rules = Rules::default();
+        assert!(lint_message("feat(auth): add login", &rules).is_empty());
+        assert!(lint_message("fix: resolve crash\n\nLonger explanation.", &rules).is_empty());
+    }
+
+    #[test]
+    fn test_format_and_length_violations() {
+        let rules = Rules::default();
+
+        let violations = lint_message("Added some stuff", &rules);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].rule, "conventional-format");
+
+        let long = format!("feat: {}", "a".repeat(80));
+        let violations = lint_message(&long, &rules);
+        assert!(violations.iter().any(|v| v.rule == "subject-length"));
+
+        let violations = lint_message("", &rules);
+        assert_eq!(violations[0].rule, "subject-empty");
+
+        let violations = lint_message("feat: add login\nno blank line", &rules);
+        assert_eq!(violations[0].rule, "body-separator");
+    }
+
+    #[test]
+    fn test_type_case_and_emoji_rules() {
+        let rules = Rules::default();
+
+        let violations = lint_message("Feat: add login", &rules);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].rule, "type-case");
+
+        let violations = lint_message("✨ feat: add login", &rules);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].rule, "emoji");
+
+        let rules = Rules {
+            allowed_emojis: vec!["✨".to_string()],
+            ..Rules::default()
+        };
+        assert!(lint_message("feat: add login ✨", &rules).is_empty());
+    }
+
+    #[test]
+    fn test_apply_header_rules() {
+        let rules = Rules::default();
+        assert_eq!(
+            apply_header_rules("🎉 Feat: add login\n\nbody", &rules).as_deref(),
+            Some("feat: add login\n\nbody")
+        );
+        assert_eq!(
+            apply_header_rules("feat: add login", &rules).as_deref(),
+            Some("feat: add login")
+        );
+
+        let rules = Rules {
+            strictness: Strictness::Reject,
+            ..Rules::default()
+        };
+        assert_eq!(apply_header_rules("Feat: add login", &rules), None);
+        assert_eq!(
+            apply_header_rules("feat: add login", &rules).as_deref(),
+            Some("feat: add login")
+        );
+        assert_eq!(
+            apply_header_rules("feat:  add login", &rules).as_deref(),
+            Some("feat:  add login")
+        );
+    }
+
+    #[test]
+    fn test_case_rules() {
+        let rules = Rules {
+            subject_case: Some(Case::Sentence),
+            scope_case: Some(Case::Lower),
+            ..Rules::default()
+        };
+        assert!(lint_message("feat(auth): Add login", &rules).is_empty());
+
+        let violations = lint_message("feat(Auth): add login", &rules);
+        let broken: Vec<&str> = violations.iter().map(|v| v.rule.as_str()).collect();
+        assert_eq!(broken, vec!["subject-case", "scope-case"]);
+        assert_eq!(
+            apply_header_rules("feat(Auth): add login\n\nbody", &rules).as_deref(),
+            Some("feat(auth): Add login\n\nbody")
+        );
+
+        let rules = Rules {
+            subject_case: Some(Case::Lower),
+            strictness: Strictness::Reject,
+            ..Rules::default()
+        };
+        assert_eq!(
+            lint_message("feat: Add login", &rules)[0].rule,
+            "subject-case"
+        );
+        assert_eq!(apply_header_rules("feat: Add login", &rules), None);
+        assert!(lint_message("feat: Add login", &Rules::default()).is_empty());
+    }
+
+    #[test]
+    fn test_scope_rules() {
+        let rules = Rules {
+            allowed_scopes: vec!["api".to_string(), "cli".to_string()],
+            require_scope: true,
+            ..Rules::default()
+        };
+
+        assert!(lint_message("feat(api): add endpoint", &rules).is_empty());
+
+        let violations = lint_message("feat(db): add index", &rules);
+        assert_eq!(violations[0].rule, "scope-allowed");
+
+        let violations = lint_message("feat: add index", &rules);
+        assert_eq!(violations[0].rule, "scope-required");
+    }
+
+    #[test]
+    fn test_imperative_mood_rule() {
+        let rules = Rules {
+            imperative_mood: true,
+            ..Rules::default()
+        };
+        assert!(lint_message("feat: add login", &rules).is_empty());
+
+        let violations = lint_message("feat: Added login", &rules);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].rule, "imperative-mood");
+        assert!(violations[0].message.contains("'add', not 'added'"));
+        assert!(lint_message("feat: Added login", &Rules::default()).is_empty());
+    }
+
+    #[test]
+    fn test_custom_rules() {
+        struct TicketRule;
+
+        impl Rule for TicketRule {
+            fn check(&self, message: &str) -> Vec<Violation> {
+                if message.contains("JIRA-") {
+                    return Vec::new();
+                }
+                vec![Violation::new("ticket", "Reference a JIRA ticket")]
+            }
+        }
+
+        let settings = Settings {
+            trailers: crate::trailers::TrailerSettings {
+                required: vec!["Signed-off-by".to_string()],
+                ..Default::default()
+            },
+            ..Settings::default()
+        };
+        let rules = RuleSet::from_settings(&settings).with(TicketRule);
+        let broken: Vec<String> = rules
+            .check("fix: handle timeouts")
+            .into_iter()
+            .map(|v| v.rule)
+            .collect();
+        assert_eq!(broken, vec!["trailer-required", "ticket"]);
+        assert!(rules.is_valid("fix: handle timeouts\n\nRefs: JIRA-12\nSigned-off-by: A <a@b.c>"));
+        assert_eq!(rules.check("")[0].rule, "subject-empty");
+    }
+
+    #[test]
+    fn test_type_rules() {
+        let rules = Rules {
+            allowed_types: vec!["feat".to_string(), "fix".to_string()],
+            ..Rules::default()
+        };
+
+        assert!(lint_message("fix: handle empty input", &rules).is_empty());
+
+        let violations = lint_message("chore: bump deps", &rules);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].rule, "type-allowed");
+        assert!(violations[0].message.contains("feat, fix"));
+    }
+
+    #[test]
+    fn test_forbidden_words() {
+        let rules = Rules {
+            forbidden_words: vec!["Falcon".to_string(), "damn".to_string()],
+            forbidden_replacements: BTreeMap::from([(
+                "falcon".to_string(),
+                "renderer".to_string(),
+            )]),
+            ..Rules::default()
+        };
+
+        let violations = lint_message("fix(falcon): stop the damn crash", &rules);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].rule, "forbidden-word");
+        assert!(lint_message("feat: add falconry guide", &rules).is_empty());
+
+        assert_eq!(
+            apply_rules("fix(FALCON): stop the damn crash", &rules).as_deref(),
+            Some("fix(renderer): stop the crash")
+        );
+        // Only the lines a word was removed from are tidied
+        let rules = Rules {
+            forbidden_replacements: BTreeMap::new(),
+            ..rules
+        };
+        assert_eq!(
+            apply_rules(
+                "fix(falcon): stop the crash\n\n- call  init()\n  - damn retry()",
+                &rules
+            )
+            .as_deref(),
+            Some("fix: stop the crash\n\n- call  init()\n  - retry()")
+        );
+
+        // Only the removed word's spacing changes, never the rest of the header
+        assert_eq!(
+            apply_rules("feat: ratio a : b for falcon", &rules).as_deref(),
+            Some("feat: ratio a : b for")
+        );
+        assert_eq!(
+            apply_rules("fix(falcon)!: stop  the damn crash", &rules).as_deref(),
+            Some("fix!: stop  the crash")
+        );
+
+        let symbols = ["C++".to_string(), "@internal".to_string()];
+        assert_eq!(
+            find_forbidden_words("feat: port to C++ runtime", &symbols),
+            vec!["C++".to_string()]
+        );
+        assert_eq!(
+            find_forbidden_words("docs: drop the @internal marker", &symbols),
+            vec!["@internal".to_string()]
+        );
+        assert!(find_forbidden_words("feat: add internal cache", &symbols).is_empty());
+
+        let rules = Rules {
+            strictness: Strictness::Reject,
+            ..rules
+        };
+        assert_eq!(apply_rules("fix: stop the damn crash", &rules), None);
+        assert!(apply_rules("fix: stop the crash", &rules).is_some());
+    }
+}
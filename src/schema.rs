@@ -0,0 +1,132 @@
+//! Version of the JSON documents committor produces for other tools
+//!
+//! `summary --json` and the serialized library types are read by scripts and CI
+//! jobs, so their shape is a contract. Top-level documents carry a
+//! `schema_version`; fields are only added in a new version, and a reader refuses
+//! documents newer than it understands rather than misreading them.
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+
+/// Version of the JSON schema written by this release
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The version new documents are written with, for `#[serde(default)]`
+pub fn current_version() -> u32 {
+    SCHEMA_VERSION
+}
+
+/// Read a `schema_version`, rejecting versions this release doesn't know
+pub fn deserialize_version<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    let version = u32::deserialize(deserializer)?;
+    if version == 0 || version > SCHEMA_VERSION {
+        return Err(D::Error::custom(format!(
+            "unsupported schema version {version}, this release reads up to version {SCHEMA_VERSION}"
+        )));
+    }
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::DiffSummary;
+    use crate::types::{
+        CommitType, ConventionalCommit, DiffChange, DiffChangeType, GenerationResult,
+    };
+    use serde_json::json;
+    use std::time::Duration;
+
+    fn change() -> DiffChange {
+        DiffChange {
+            file_path: "src/auth.rs".to_string(),
+            change_type: DiffChangeType::Modified,
+            additions: 3,
+            deletions: 1,
+        }
+    }
+
+    #[test]
+    fn test_conventional_commit_schema() {
+        let commit = ConventionalCommit::new(CommitType::Feat, "add login".to_string())
+            .with_scope("auth".to_string());
+        let expected = json!({
+            "commit_type": "feat",
+            "scope": "auth",
+            "description": "add login",
+            "breaking": false
+        });
+
+        assert_eq!(serde_json::to_value(&commit).unwrap(), expected);
+        assert_eq!(
+            serde_json::from_value::<ConventionalCommit>(expected).unwrap(),
+            commit
+        );
+    }
+
+    #[test]
+    fn test_diff_summary_schema() {
+        let summary = DiffSummary::new(vec![change()]);
+        let expected = json!({
+            "schema_version": 1,
+            "files": 1,
+            "additions": 3,
+            "deletions": 1,
+            "changes": [{
+                "file_path": "src/auth.rs",
+                "change_type": "modified",
+                "additions": 3,
+                "deletions": 1
+            }]
+        });
+
+        assert_eq!(serde_json::to_value(&summary).unwrap(), expected);
+        assert_eq!(
+            serde_json::from_value::<DiffSummary>(expected).unwrap(),
+            summary
+        );
+    }
+
+    #[test]
+    fn test_generation_result_schema() {
+        let result = GenerationResult::new(
+            vec!["feat(auth): add login".to_string()],
+            "llama2:7b".to_string(),
+            Duration::from_millis(1500),
+        );
+        let expected = json!({
+            "schema_version": 1,
+            "messages": ["feat(auth): add login"],
+            "model_used": "llama2:7b",
+            "generation_time": {"secs": 1, "nanos": 500_000_000}
+        });
+
+        assert_eq!(serde_json::to_value(&result).unwrap(), expected);
+        assert_eq!(
+            serde_json::from_value::<GenerationResult>(expected).unwrap(),
+            result
+        );
+    }
+
+    #[test]
+    fn test_schema_rejects_unknown_fields_and_versions() {
+        let mut change = serde_json::to_value(change()).unwrap();
+        change["renamed_from"] = json!("src/login.rs");
+        assert!(serde_json::from_value::<DiffChange>(change).is_err());
+
+        let newer = json!({
+            "schema_version": SCHEMA_VERSION + 1,
+            "files": 0,
+            "additions": 0,
+            "deletions": 0,
+            "changes": []
+        });
+        let error = serde_json::from_value::<DiffSummary>(newer).unwrap_err();
+        assert!(error.to_string().contains("unsupported schema version 2"));
+
+        // Documents written before versioning are read as version 1
+        let unversioned = json!({"files": 0, "additions": 0, "deletions": 0, "changes": []});
+        let summary: DiffSummary = serde_json::from_value(unversioned).unwrap();
+        assert_eq!(summary.schema_version, 1);
+    }
+}
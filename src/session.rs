@@ -0,0 +1,262 @@
+//! The last `generate` or `commit` run, kept for `committor export-session`
+//!
+//! Each run starts a fresh session with the changed files and the options shown;
+//! regenerating adds a round, committing records the chosen message and the run's
+//! phase timings are added at the end, ready to share while trialling the tool.
+
+use crate::audit::{format_timestamp, now};
+use crate::diff::DiffSummary;
+use crate::paths::git_state_path;
+use crate::report::{format_count, format_decimal, format_stored_date};
+use crate::state::{with_lock, write_atomic};
+use crate::timing::Timings;
+use anyhow::{Context, Result};
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Name of the session file, kept in the git directory
+pub const SESSION_FILE_NAME: &str = "committor-session.json";
+
+/// One run of `generate` or `commit`, from the changes to the chosen message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    /// When the run started, as an RFC 3339 UTC timestamp
+    pub started: String,
+    pub command: String,
+    pub provider: String,
+    pub model: String,
+    pub summary: DiffSummary,
+    /// Options shown, one list per generation, regenerations included
+    pub rounds: Vec<Vec<String>>,
+    /// Message committed, `None` when the run ended without a commit
+    pub chosen: Option<String>,
+    pub timings: Vec<PhaseRecord>,
+    /// Wall-clock time of the whole run, once it ended
+    pub total_ms: Option<f64>,
+}
+
+/// Time spent in one phase of the run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PhaseRecord {
+    pub phase: String,
+    pub ms: f64,
+    pub runs: usize,
+}
+
+impl Session {
+    /// Start a session with the first options shown
+    pub fn new(
+        command: &str,
+        provider: &str,
+        model: &str,
+        summary: DiffSummary,
+        messages: &[String],
+    ) -> Self {
+        Self {
+            started: format_timestamp(now()),
+            command: command.to_string(),
+            provider: provider.to_string(),
+            model: model.to_string(),
+            summary,
+            rounds: vec![messages.to_vec()],
+            chosen: None,
+            timings: Vec::new(),
+            total_ms: None,
+        }
+    }
+
+    /// Record the phases that ran and the run's total time
+    pub fn finish(&mut self, timings: &Timings, total: Duration) {
+        self.timings = timings
+            .phases()
+            .into_iter()
+            .map(|(phase, timing)| PhaseRecord {
+                phase: phase.to_string(),
+                ms: milliseconds(timing.total),
+                runs: timing.count,
+            })
+            .collect();
+        self.total_ms = Some(milliseconds(total));
+    }
+
+    /// Render the session as a Markdown report
+    pub fn to_markdown(&self) -> String {
+        let mut report = String::from("# committor session\n\n");
+        let _ = writeln!(report, "- Command: `{}`", self.command);
+        let _ = writeln!(report, "- Started: {}", format_stored_date(&self.started));
+        let _ = writeln!(report, "- Model: {} ({})", self.model, self.provider);
+
+        let summary = &self.summary;
+        let _ = write!(
+            report,
+            "\n## Changes\n\n{} file{}, +{} -{}\n\n| File | Change | + | - |\n|------|--------|---|---|\n",
+            format_count(summary.files as u64),
+            if summary.files == 1 { "" } else { "s" },
+            format_count(summary.additions as u64),
+            format_count(summary.deletions as u64)
+        );
+        for change in &summary.changes {
+            let _ = writeln!(
+                report,
+                "| `{}` | {} | {} | {} |",
+                change.file_path,
+                change.change_type,
+                format_count(change.additions as u64),
+                format_count(change.deletions as u64)
+            );
+        }
+
+        report.push_str("\n## Options\n");
+        for (round, messages) in self.rounds.iter().enumerate() {
+            if self.rounds.len() > 1 {
+                let _ = write!(report, "\n### Round {}\n", round + 1);
+            }
+            report.push('\n');
+            for (i, message) in messages.iter().enumerate() {
+                let mut lines = message.lines();
+                let _ = writeln!(report, "{}. {}", i + 1, lines.next().unwrap_or_default());
+                for line in lines.filter(|line| !line.trim().is_empty()) {
+                    let _ = writeln!(report, "   {line}");
+                }
+            }
+        }
+
+        report.push_str("\n## Chosen\n\n");
+        match &self.chosen {
+            Some(message) => {
+                let _ = writeln!(report, "```\n{}\n```", message.trim_end());
+            }
+            None => report.push_str("Nothing was committed.\n"),
+        }
+
+        if let Some(total_ms) = self.total_ms {
+            report.push_str("\n## Timing\n\n| Phase | Time | Runs |\n|-------|------|------|\n");
+            for phase in &self.timings {
+                let _ = writeln!(
+                    report,
+                    "| {} | {} ms | {} |",
+                    phase.phase,
+                    format_decimal(phase.ms, 1),
+                    phase.runs
+                );
+            }
+            let _ = writeln!(report, "| total | {} ms | |", format_decimal(total_ms, 1));
+        }
+        report
+    }
+}
+
+fn milliseconds(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Get the path of the session file for a repository
+pub fn session_path_from_repo(repo: &Repository) -> PathBuf {
+    git_state_path(repo, SESSION_FILE_NAME)
+}
+
+/// Save a session, replacing the previous run's
+pub fn save_session_from_repo(repo: &Repository, session: &Session) -> Result<()> {
+    let path = session_path_from_repo(repo);
+    write_atomic(&path, serde_json::to_string_pretty(session)?)
+        .with_context(|| format!("Failed to save the session to {}", path.display()))
+}
+
+/// Load the last session, if there is one
+pub fn load_session_from_repo(repo: &Repository) -> Result<Option<Session>> {
+    let path = session_path_from_repo(repo);
+    let Ok(content) = fs::read_to_string(&path) else {
+        return Ok(None);
+    };
+    let session = serde_json::from_str(&content)
+        .with_context(|| format!("Invalid session in {}", path.display()))?;
+    Ok(Some(session))
+}
+
+/// Save a session in the current repository
+pub fn save_session(session: &Session) -> Result<()> {
+    let repo = Repository::discover(".").context("Not in a git repository")?;
+    save_session_from_repo(&repo, session)
+}
+
+/// Load the current repository's last session
+pub fn load_session() -> Result<Option<Session>> {
+    let repo = Repository::discover(".").context("Not in a git repository")?;
+    load_session_from_repo(&repo)
+}
+
+/// Change the current repository's last session, if there is one
+pub fn update_session(change: impl FnOnce(&mut Session)) -> Result<()> {
+    let repo = Repository::discover(".").context("Not in a git repository")?;
+    with_lock(&session_path_from_repo(&repo), || {
+        if let Some(mut session) = load_session_from_repo(&repo)? {
+            change(&mut session);
+            save_session_from_repo(&repo, &session)?;
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timing::PROVIDER_CALL;
+    use crate::types::{DiffChange, DiffChangeType};
+
+    fn session() -> Session {
+        let summary = DiffSummary::new(vec![DiffChange {
+            file_path: "src/auth.rs".to_string(),
+            change_type: DiffChangeType::Modified,
+            additions: 12,
+            deletions: 3,
+        }]);
+        Session::new(
+            "commit",
+            "ollama",
+            "llama2",
+            summary,
+            &["feat(auth): add logout".to_string()],
+        )
+    }
+
+    #[test]
+    fn test_session_round_trip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let repo = Repository::init(dir.path())?;
+        assert_eq!(load_session_from_repo(&repo)?, None);
+
+        let mut session = session();
+        session.chosen = Some("feat(auth): add logout".to_string());
+        save_session_from_repo(&repo, &session)?;
+        assert_eq!(load_session_from_repo(&repo)?, Some(session));
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_markdown() {
+        let mut session = session();
+        session.rounds.push(vec![
+            "feat(auth): add session logout\n\nEnd sessions on request.".to_string(),
+        ]);
+        assert!(!session.to_markdown().contains("## Timing"));
+
+        let timings = Timings::new();
+        timings.record(PROVIDER_CALL, Duration::from_millis(900));
+        session.finish(&timings, Duration::from_millis(1_000));
+        let report = session.to_markdown();
+
+        assert!(report.contains("- Model: llama2 (ollama)"));
+        assert!(report.contains("1 file, +12 -3"));
+        assert!(report.contains("| `src/auth.rs` | modified | 12 | 3 |"));
+        assert!(report.contains(
+            "### Round 2\n\n1. feat(auth): add session logout\n   End sessions on request.\n"
+        ));
+        assert!(report.contains("Nothing was committed."));
+        assert!(report.contains("| provider call | 900.0 ms | 1 |"));
+        assert!(report.contains("| total | 1,000.0 ms | |"));
+    }
+}
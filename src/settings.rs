@@ -0,0 +1,308 @@
+//! File-based settings loaded from a `.committor.toml` in the repository root
+
+use crate::audit::AuditSettings;
+use crate::commit::default_branch_type_mappings;
+use crate::embeddings::EmbeddingSettings;
+use crate::feedback::FeedbackSettings;
+use crate::filters::FilterSettings;
+use crate::gate::GateSettings;
+use crate::hooks::HookSettings;
+use crate::lock::IndexLockSettings;
+use crate::migrations::MigrationSettings;
+use crate::paths::settings_path_from_repo;
+use crate::policy;
+use crate::prompt::LanguageSettings;
+use crate::providers::{OverflowSettings, QuickSettings, SamplingSettings};
+use crate::rules::{Case, Rules};
+use crate::symbols::SymbolSettings;
+use crate::theme::ThemeSettings;
+use crate::trailers::TrailerSettings;
+use crate::types::{CommitType, CommittorError};
+use anyhow::{Context, Result};
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the per-repository settings file
+pub const SETTINGS_FILE_NAME: &str = ".committor.toml";
+
+/// Settings read from a `.committor.toml` file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Branch prefix to commit type mappings, e.g. `"spike/" = "chore"`
+    pub branch_types: BTreeMap<String, String>,
+    /// Commit message validation rules
+    pub rules: Rules,
+    /// Git hook behaviour
+    pub hooks: HookSettings,
+    /// Trailers appended to every committed message
+    pub trailers: TrailerSettings,
+    /// Similar-commit retrieval from the local embedding index
+    pub embeddings: EmbeddingSettings,
+    /// Audit log of requests sent off the machine
+    pub audit: AuditSettings,
+    /// Languages generated subjects and bodies are written in
+    pub language: LanguageSettings,
+    /// Changed public symbols listed in the prompt
+    pub symbols: SymbolSettings,
+    /// Database migrations called out in the prompt
+    pub migrations: MigrationSettings,
+    /// Pull request titles checked by `committor gate`
+    pub gate: GateSettings,
+    /// Waiting for another git process to release the index
+    pub index_lock: IndexLockSettings,
+    /// Sampling for commit messages, from `generate`, `commit` and the other message commands
+    pub generate: SamplingSettings,
+    /// Sampling for the release notes written by `committor tag`
+    pub tag: SamplingSettings,
+    /// Sampling for the comments written by `committor review`
+    pub review: SamplingSettings,
+    /// Preferences learned from rated suggestions
+    pub feedback: FeedbackSettings,
+    /// Retries when a prompt is too long for the model's context window
+    pub overflow: OverflowSettings,
+    /// Model and time budget of `commit --quick`
+    pub quick: QuickSettings,
+    /// Colors of commit types and interface elements
+    pub theme: ThemeSettings,
+    /// Filters every generated message passes through
+    pub filters: FilterSettings,
+}
+
+impl Settings {
+    /// Parse settings from TOML content
+    pub fn parse(content: &str) -> Result<Self> {
+        toml::from_str(content).map_err(|e| CommittorError::ConfigError(e.to_string()).into())
+    }
+
+    /// Load settings from a specific file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read settings from {}", path.display()))?;
+        Self::parse(&content)
+    }
+
+    /// Load settings for the current repository, falling back to defaults when no file exists
+    ///
+    /// The organization policy, when one is loaded, is applied on top.
+    pub fn discover() -> Result<Self> {
+        match settings_path() {
+            Some(path) if path.exists() => Self::load(&path),
+            _ => Ok(Self::default()),
+        }
+        .map(|mut settings| {
+            if let Some(policy) = policy::current() {
+                policy.apply(&mut settings);
+            }
+            settings
+        })
+    }
+
+    /// Get the built-in branch mappings extended and overridden by the configured ones
+    pub fn branch_type_mappings(&self) -> Result<Vec<(String, CommitType)>> {
+        let mut mappings: Vec<(String, CommitType)> = default_branch_type_mappings()
+            .into_iter()
+            .filter(|(prefix, _)| !self.branch_types.contains_key(prefix))
+            .collect();
+
+        for (prefix, commit_type) in &self.branch_types {
+            mappings.push((prefix.clone(), commit_type.parse()?));
+        }
+
+        Ok(mappings)
+    }
+
+    /// Render the commit conventions these settings enforce, for `committor types`
+    ///
+    /// Built from the same rules and branch mappings used for validation.
+    pub fn describe_conventions(&self) -> Result<String> {
+        let mappings = self.branch_type_mappings()?;
+        let mut lines = vec!["Commit types:".to_string()];
+        let allowed = &self.rules.allowed_types;
+        for commit_type in CommitType::all()
+            .into_iter()
+            .filter(|commit_type| allowed.is_empty() || allowed.contains(&commit_type.to_string()))
+        {
+            let prefixes: Vec<&str> = mappings
+                .iter()
+                .filter(|(_, mapped)| *mapped == commit_type)
+                .map(|(prefix, _)| prefix.as_str())
+                .collect();
+            let branches = if prefixes.is_empty() {
+                String::new()
+            } else {
+                format!(" (branches: {})", prefixes.join(", "))
+            };
+            lines.push(format!(
+                "  {:<9} {}{branches}",
+                commit_type.to_string(),
+                commit_type.description()
+            ));
+        }
+
+        let rules = &self.rules;
+        lines.push(String::new());
+        let scopes = if rules.allowed_scopes.is_empty() {
+            "any".to_string()
+        } else {
+            rules.allowed_scopes.join(", ")
+        };
+        let required = if rules.require_scope {
+            "required"
+        } else {
+            "optional"
+        };
+        lines.push(format!("Scopes ({required}): {scopes}"));
+        lines.push(format!(
+            "Subject length: at most {} characters",
+            rules.max_subject_length
+        ));
+        let case_name = |case: Option<Case>| match case {
+            Some(Case::Lower) => "lowercase",
+            Some(Case::Sentence) => "sentence case",
+            None => "any",
+        };
+        if rules.subject_case.is_some() || rules.scope_case.is_some() {
+            lines.push(format!(
+                "Case: descriptions {}, scopes {}",
+                case_name(rules.subject_case),
+                case_name(rules.scope_case)
+            ));
+        }
+        if !rules.forbidden_words.is_empty() {
+            lines.push(format!(
+                "Forbidden words: {}",
+                rules.forbidden_words.join(", ")
+            ));
+        }
+
+        Ok(lines.join("\n"))
+    }
+}
+
+/// Get the path of the settings file for the current repository
+///
+/// Bare repositories keep the file inside the git directory itself.
+pub fn settings_path() -> Option<PathBuf> {
+    let repo = Repository::discover(".").ok()?;
+    Some(settings_path_from_repo(&repo))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_branch_types() {
+        let settings = Settings::parse(
+            r#"
+[branch_types]
+"spike/" = "chore"
+"fix/" = "perf"
+"#,
+        )
+        .unwrap();
+
+        let mappings = settings.branch_type_mappings().unwrap();
+        assert!(mappings.contains(&("spike/".to_string(), CommitType::Chore)));
+        assert!(mappings.contains(&("fix/".to_string(), CommitType::Perf)));
+        assert!(!mappings.contains(&("fix/".to_string(), CommitType::Fix)));
+        assert!(mappings.contains(&("feature/".to_string(), CommitType::Feat)));
+    }
+
+    #[test]
+    fn test_parse_rules_and_hooks() {
+        let settings = Settings::parse(
+            r#"
+[rules]
+max_subject_length = 50
+allowed_scopes = ["api", "cli"]
+
+[hooks]
+pre_receive = "warn"
+skip_sources = ["merge", "squash"]
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(settings.rules.max_subject_length, 50);
+        assert_eq!(settings.rules.allowed_scopes, vec!["api", "cli"]);
+        assert!(!settings.rules.require_scope);
+        assert_eq!(
+            settings.hooks.pre_receive,
+            crate::hooks::EnforcementMode::Warn
+        );
+        assert!(settings.hooks.suggests_for(Some("message")));
+        assert!(!settings.hooks.suggests_for(Some("squash")));
+    }
+
+    #[test]
+    fn test_parse_trailers() {
+        let settings = Settings::parse(
+            r#"
+[trailers]
+add = ["Reviewed-by-policy: ai-assisted"]
+change_id = true
+required = ["Signed-off-by"]
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            settings.trailers.add,
+            vec!["Reviewed-by-policy: ai-assisted"]
+        );
+        assert!(settings.trailers.change_id);
+        assert_eq!(settings.trailers.required, vec!["Signed-off-by"]);
+    }
+
+    #[test]
+    fn test_parse_symbols() {
+        let settings = Settings::parse("[symbols]\nin_body = true").unwrap();
+        assert!(settings.symbols.enabled);
+        assert!(settings.symbols.in_body);
+        assert!(!Settings::default().symbols.in_body);
+    }
+
+    #[test]
+    fn test_describe_conventions() {
+        let settings = Settings::parse(
+            r#"
+[branch_types]
+"spike/" = "chore"
+
+[rules]
+allowed_scopes = ["api", "cli"]
+require_scope = true
+subject_case = "sentence"
+"#,
+        )
+        .unwrap();
+
+        let conventions = settings.describe_conventions().unwrap();
+        assert!(conventions.contains("  feat      A new feature (branches: feature/"));
+        assert!(conventions.contains("spike/"));
+        assert!(conventions.contains("  docs      Documentation only changes"));
+        assert!(conventions.contains("Scopes (required): api, cli"));
+        assert!(conventions.contains("Subject length: at most 72 characters"));
+        assert!(conventions.contains("Case: descriptions sentence case, scopes any"));
+        assert!(!conventions.contains("Forbidden words"));
+    }
+
+    #[test]
+    fn test_invalid_settings() {
+        assert!(Settings::parse("branch_types = 3").is_err());
+
+        let settings = Settings::parse("[branch_types]\n\"x/\" = \"feature\"").unwrap();
+        assert!(settings.branch_type_mappings().is_err());
+    }
+
+    #[test]
+    fn test_empty_settings() {
+        let settings = Settings::parse("").unwrap();
+        assert_eq!(settings, Settings::default());
+    }
+}
@@ -0,0 +1,142 @@
+//! Shell prompt integration showing staged changes and the commit type committor predicts
+//!
+//! Prompts redraw after every command, so the indicator comes from the heuristics
+//! and the cached staged snapshot only and never reaches a provider. The snippet
+//! printed by `committor prompt-hook` calls `committor prompt-status` each time.
+
+use crate::commit::{get_head_state_from_repo, infer_type_from_branch};
+use crate::diff::{get_staged_snapshot_from_repo, has_staged_changes_from_repo};
+use crate::prompt::suggest_commit_type;
+use crate::types::{CommitType, HeadState};
+use anyhow::{Context, Result};
+use git2::Repository;
+
+/// A shell `committor prompt-hook` can print a snippet for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+const BASH_SNIPPET: &str = r#"__committor_prompt() {
+    local status
+    status=$(committor prompt-status 2>/dev/null)
+    [ -n "$status" ] && printf '(%s) ' "$status"
+}
+case $PS1 in
+    *__committor_prompt*) ;;
+    *) PS1='$(__committor_prompt)'$PS1 ;;
+esac
+"#;
+
+const ZSH_SNIPPET: &str = r#"setopt PROMPT_SUBST
+__committor_prompt() {
+    local prompt_status
+    prompt_status=$(committor prompt-status 2>/dev/null)
+    [[ -n $prompt_status ]] && print -n "($prompt_status) "
+}
+if [[ $PROMPT != *__committor_prompt* ]]; then
+    PROMPT='$(__committor_prompt)'$PROMPT
+fi
+"#;
+
+const FISH_SNIPPET: &str = r#"function __committor_prompt
+    set -l prompt_status (committor prompt-status 2>/dev/null)
+    if test -n "$prompt_status"
+        printf '(%s) ' $prompt_status
+    end
+end
+if not functions -q __committor_original_prompt
+    functions -c fish_prompt __committor_original_prompt
+    function fish_prompt
+        __committor_prompt
+        __committor_original_prompt
+    end
+end
+"#;
+
+/// Get the snippet that adds the indicator to a shell's prompt
+///
+/// Sourcing it twice leaves the prompt as it was after the first time.
+pub fn prompt_hook(shell: Shell) -> &'static str {
+    match shell {
+        Shell::Bash => BASH_SNIPPET,
+        Shell::Zsh => ZSH_SNIPPET,
+        Shell::Fish => FISH_SNIPPET,
+    }
+}
+
+/// Get the prompt indicator for the repository in the current directory
+pub fn prompt_status(mappings: &[(String, CommitType)]) -> Result<Option<String>> {
+    let repo = Repository::open(".").context("Not in a git repository")?;
+    prompt_status_from_repo(&repo, mappings)
+}
+
+/// Get the prompt indicator for a repository, `None` when nothing is staged
+///
+/// The indicator is `+` followed by the predicted type, like `+feat`: the `+` is what
+/// git's own prompt shows for staged changes. The branch decides the type when it
+/// maps to one, otherwise the first type suggested for the changed files.
+pub fn prompt_status_from_repo(
+    repo: &Repository,
+    mappings: &[(String, CommitType)],
+) -> Result<Option<String>> {
+    if !has_staged_changes_from_repo(repo)? {
+        return Ok(None);
+    }
+
+    let from_branch = match get_head_state_from_repo(repo)? {
+        HeadState::Branch(branch) => infer_type_from_branch(&branch, mappings),
+        HeadState::Detached(_) => None,
+    };
+    let commit_type = match from_branch {
+        Some(commit_type) => commit_type,
+        None => {
+            let changes = get_staged_snapshot_from_repo(repo)?.changes;
+            // Never empty: without a telling file, feat is suggested first
+            suggest_commit_type(&changes)
+                .into_iter()
+                .next()
+                .unwrap_or(CommitType::Feat)
+        }
+    };
+    Ok(Some(format!("+{commit_type}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commit::default_branch_type_mappings;
+    use crate::git::fixtures::{commit, git, init_repo};
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_prompt_status() {
+        let temp_dir = init_repo();
+        let dir = temp_dir.path();
+        commit(dir, "a.txt", "a\n", "chore: initial");
+
+        let mappings = default_branch_type_mappings();
+        let status = |dir: &Path| {
+            let repo = Repository::open(dir).unwrap();
+            prompt_status_from_repo(&repo, &mappings).unwrap()
+        };
+        assert_eq!(status(dir), None);
+
+        fs::write(dir.join("README.md"), "# App\n").unwrap();
+        git(dir, &["add", "README.md"]);
+        assert_eq!(status(dir).as_deref(), Some("+docs"));
+
+        git(dir, &["checkout", "-q", "-b", "fix/login"]);
+        assert_eq!(status(dir).as_deref(), Some("+fix"));
+    }
+
+    #[test]
+    fn test_prompt_hook_calls_prompt_status() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            assert!(prompt_hook(shell).contains("committor prompt-status 2>/dev/null"));
+        }
+    }
+}
@@ -0,0 +1,694 @@
+//! Splitting staged changes into a sequence of smaller commits
+
+use crate::backup::{create_backup_in, drop_backup_in, Backup};
+use crate::commit::read_input;
+use crate::git::{git_output, git_output_with_index};
+use crate::i18n::Text;
+use crate::output::{is_plain, item_label, success_mark};
+use crate::postprocess::normalize_message;
+use crate::types::{CommittorError, DiffHunk};
+use anyhow::{Context, Result};
+use colored::*;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Name of the temporary index used while committing a subset of the staged files
+const SPLIT_INDEX_NAME: &str = "committor-split-index";
+
+/// Repository state captured before a split so it can be restored if a step fails
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// HEAD before the split, or `None` on an unborn branch
+    pub head: Option<String>,
+    /// Tree of the staged index before the split
+    pub tree: String,
+    /// Backup of HEAD taken for the split, if there was a HEAD to keep
+    pub backup: Option<Backup>,
+}
+
+/// A suggested commit for a single staged file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileSuggestion {
+    pub path: String,
+    pub message: String,
+}
+
+/// One commit of a split plan
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedCommit {
+    pub message: String,
+    /// Zero-based indices into the staged hunks
+    pub hunks: Vec<usize>,
+}
+
+/// Parse the model's `<hunks> | <message>` plan lines
+///
+/// Hunks claimed twice stay with their first commit, and hunks the model left out are
+/// added to the last commit so the plan always covers every staged change.
+pub fn parse_split_plan(response: &str, hunk_count: usize) -> Result<Vec<PlannedCommit>> {
+    let mut plan: Vec<PlannedCommit> = Vec::new();
+    let mut assigned = vec![false; hunk_count];
+
+    for line in response.lines() {
+        let Some((ids, message)) = line.split_once('|') else {
+            continue;
+        };
+
+        let mut hunks = Vec::new();
+        for id in ids
+            .split(',')
+            .filter_map(|id| id.trim().parse::<usize>().ok())
+        {
+            if id >= 1 && id <= hunk_count && !assigned[id - 1] {
+                assigned[id - 1] = true;
+                hunks.push(id - 1);
+            }
+        }
+
+        let message = normalize_message(message, None);
+        if !hunks.is_empty() && !message.is_empty() {
+            plan.push(PlannedCommit { message, hunks });
+        }
+    }
+
+    let Some(last) = plan.last_mut() else {
+        return Err(CommittorError::ValidationFailed(
+            "the response held no split plan".to_string(),
+        )
+        .into());
+    };
+    last.hunks
+        .extend((0..hunk_count).filter(|&index| !assigned[index]));
+
+    Ok(plan)
+}
+
+/// Display a split plan as a tree of commits, files and hunks
+pub fn display_split_plan(plan: &[PlannedCommit], hunks: &[DiffHunk]) {
+    println!("{}", Text::ProposedCommits.text().green().bold());
+    println!();
+    print!("{}", render_plan_tree(plan, hunks));
+    println!();
+}
+
+/// Build a plan that commits each suggested file with its own message
+pub fn plan_for_files(suggestions: &[FileSuggestion], hunks: &[DiffHunk]) -> Vec<PlannedCommit> {
+    suggestions
+        .iter()
+        .map(|suggestion| PlannedCommit {
+            message: suggestion.message.clone(),
+            hunks: (0..hunks.len())
+                .filter(|&index| hunks[index].file_path == suggestion.path)
+                .collect(),
+        })
+        .collect()
+}
+
+/// Render a plan as `commit → files → hunk summaries` with added and removed line counts
+///
+/// Plain output labels each level instead of drawing tree branches.
+pub fn render_plan_tree(plan: &[PlannedCommit], hunks: &[DiffHunk]) -> String {
+    let mut tree = String::new();
+
+    for (i, commit) in plan.iter().enumerate() {
+        tree.push_str(&format!(
+            "{}. {}\n",
+            i + 1,
+            commit.message.lines().next().unwrap_or_default()
+        ));
+
+        // Group the commit's hunks by file, keeping diff order
+        let mut files: Vec<(&str, Vec<&DiffHunk>)> = Vec::new();
+        let mut sorted = commit.hunks.clone();
+        sorted.sort_unstable();
+        for index in sorted {
+            let hunk = &hunks[index];
+            match files.iter_mut().find(|(path, _)| *path == hunk.file_path) {
+                Some((_, file_hunks)) => file_hunks.push(hunk),
+                None => files.push((&hunk.file_path, vec![hunk])),
+            }
+        }
+
+        for (file_index, (path, file_hunks)) in files.iter().enumerate() {
+            if is_plain() {
+                tree.push_str(&format!("   File: {path}\n"));
+                for (hunk_index, hunk) in file_hunks.iter().enumerate() {
+                    tree.push_str(&format!(
+                        "      Change {}: {}\n",
+                        hunk_index + 1,
+                        summarize_hunk(hunk)
+                    ));
+                }
+                continue;
+            }
+
+            let last_file = file_index + 1 == files.len();
+            let (branch, indent) = if last_file {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            tree.push_str(&format!("   {branch}{path}\n"));
+
+            for (hunk_index, hunk) in file_hunks.iter().enumerate() {
+                let branch = if hunk_index + 1 == file_hunks.len() {
+                    "└── "
+                } else {
+                    "├── "
+                };
+                tree.push_str(&format!("   {indent}{branch}{}\n", summarize_hunk(hunk)));
+            }
+        }
+    }
+
+    tree
+}
+
+/// Summarize a hunk by its header and line counts, e.g. `@@ -1,3 +1,4 @@ fn main (+1 -0)`
+fn summarize_hunk(hunk: &DiffHunk) -> String {
+    let Some(text) = &hunk.text else {
+        return "whole file".to_string();
+    };
+
+    let header = text.lines().next().unwrap_or_default().trim();
+    let (additions, deletions) = hunk_line_counts(text);
+    format!("{header} (+{additions} -{deletions})")
+}
+
+/// Count the added and removed lines of a hunk's text
+pub fn hunk_line_counts(text: &str) -> (usize, usize) {
+    text.lines()
+        .skip(1)
+        .fold((0, 0), |(additions, deletions), line| {
+            match line.chars().next() {
+                Some('+') => (additions + 1, deletions),
+                Some('-') => (additions, deletions + 1),
+                _ => (additions, deletions),
+            }
+        })
+}
+
+/// Ask the user to confirm the plan before any commits are made
+pub fn prompt_confirmation() -> Result<bool> {
+    print!("{}", Text::ConfirmCommits.text().yellow());
+    io::stdout().flush()?;
+
+    let input = read_input()?.unwrap_or_default();
+    Ok(input.eq_ignore_ascii_case("y") || input.eq_ignore_ascii_case("yes"))
+}
+
+/// Build the patch for a set of hunks, grouped per file in their original order
+fn build_patch(hunks: &[DiffHunk], indices: &[usize]) -> String {
+    let mut files: BTreeMap<usize, (&str, Vec<&str>)> = BTreeMap::new();
+    let mut sorted = indices.to_vec();
+    sorted.sort_unstable();
+
+    for index in sorted {
+        let hunk = &hunks[index];
+        let Some(text) = &hunk.text else {
+            continue;
+        };
+        // Key by the file's first hunk so files keep their diff order
+        let first = hunks
+            .iter()
+            .position(|h| h.file_path == hunk.file_path)
+            .unwrap_or(index);
+        files
+            .entry(first)
+            .or_insert_with(|| (&hunk.file_header, Vec::new()))
+            .1
+            .push(text);
+    }
+
+    files
+        .into_values()
+        .map(|(header, texts)| format!("{header}{}", texts.concat()))
+        .collect()
+}
+
+/// Capture HEAD and the staged tree, and back HEAD up
+pub fn take_snapshot(dir: &Path) -> Result<Snapshot> {
+    verify_no_conflicts(dir)?;
+
+    let head = git_output(dir, &["rev-parse", "--verify", "-q", "HEAD"]).ok();
+    let tree = git_output(dir, &["write-tree"])?;
+
+    let backup = create_backup_in(dir)?;
+
+    Ok(Snapshot { head, tree, backup })
+}
+
+/// Move HEAD back to the snapshot and restore the staged index, leaving the working tree alone
+pub fn restore_snapshot(dir: &Path, snapshot: &Snapshot) -> Result<()> {
+    match &snapshot.head {
+        Some(head) => git_output(dir, &["reset", "-q", "--soft", head])?,
+        None => git_output(dir, &["update-ref", "-d", "HEAD"])?,
+    };
+    git_output(dir, &["read-tree", &snapshot.tree])?;
+    Ok(())
+}
+
+/// Remove the snapshot's backup once HEAD is back where it was
+fn drop_snapshot(dir: &Path, snapshot: &Snapshot) -> Result<()> {
+    if let Some(backup) = &snapshot.backup {
+        drop_backup_in(dir, backup)?;
+    }
+    Ok(())
+}
+
+/// Fail if the index has unmerged entries
+fn verify_no_conflicts(dir: &Path) -> Result<()> {
+    let unmerged = git_output(dir, &["ls-files", "--unmerged"])?;
+    if unmerged.is_empty() {
+        Ok(())
+    } else {
+        Err(CommittorError::GitError(
+            "The index has unresolved conflicts; resolve them before splitting".to_string(),
+        )
+        .into())
+    }
+}
+
+/// Commit a split plan in the current repository
+pub fn execute_plan(plan: &[PlannedCommit], hunks: &[DiffHunk]) -> Result<()> {
+    execute_plan_in(Path::new("."), plan, hunks)
+}
+
+/// Commit a split plan, rolling HEAD and the index back to where they started if any step fails
+///
+/// The original HEAD is kept as a backup when the split succeeds, for `committor restore`.
+pub fn execute_plan_in(dir: &Path, plan: &[PlannedCommit], hunks: &[DiffHunk]) -> Result<()> {
+    let snapshot = take_snapshot(dir)?;
+
+    match commit_plan(dir, plan, hunks) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            println!("{}", Text::SplitFailed.text().red().bold());
+            if let Err(restore_error) = restore_snapshot(dir, &snapshot) {
+                let hint = match &snapshot.backup {
+                    Some(backup) => format!("; the original HEAD is kept at {}", backup.name),
+                    None => String::new(),
+                };
+                return Err(e.context(format!("Rollback also failed: {restore_error}{hint}")));
+            }
+            drop_snapshot(dir, &snapshot)?;
+            println!(
+                "{}",
+                format!("{} {}", success_mark(), Text::SplitRestored.text()).green()
+            );
+            Err(e)
+        }
+    }
+}
+
+/// Apply each commit's hunks to the index with `git apply --cached` and commit them
+///
+/// The index is reset to HEAD first, so when every hunk is committed the index ends up
+/// exactly as it was staged. Whole-file changes are staged from their recorded index entries.
+fn commit_plan(dir: &Path, plan: &[PlannedCommit], hunks: &[DiffHunk]) -> Result<()> {
+    // Record index entries for whole-file changes before the index is reset
+    let mut entries = BTreeMap::new();
+    for hunk in hunks.iter().filter(|h| h.text.is_none()) {
+        let entry = git_output(dir, &["ls-files", "--stage", "--", &hunk.file_path])?;
+        entries.insert(hunk.file_path.clone(), entry);
+    }
+
+    if git_output(dir, &["rev-parse", "--verify", "-q", "HEAD"]).is_ok() {
+        git_output(dir, &["read-tree", "HEAD"])?;
+    } else {
+        git_output(dir, &["read-tree", "--empty"])?;
+    }
+
+    for commit in plan {
+        let patch = build_patch(hunks, &commit.hunks);
+        if !patch.is_empty() {
+            apply_cached(dir, &patch)?;
+        }
+
+        for &index in &commit.hunks {
+            let hunk = &hunks[index];
+            if hunk.text.is_none() {
+                stage_entry(dir, None, &hunk.file_path, &entries[&hunk.file_path])?;
+            }
+        }
+
+        git_output(dir, &["commit", "-q", "-m", &commit.message])?;
+        verify_no_conflicts(dir)?;
+        println!(
+            "{} {}",
+            format!("{} {}", success_mark(), Text::SplitCommitted.text())
+                .green()
+                .bold(),
+            commit.message.lines().next().unwrap_or_default()
+        );
+    }
+
+    Ok(())
+}
+
+/// Apply a patch to the index only
+fn apply_cached(dir: &Path, patch: &str) -> Result<()> {
+    let mut child = Command::new("git")
+        .args(["apply", "--cached", "--whitespace=nowarn", "-"])
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to execute git apply")?;
+
+    child
+        .stdin
+        .take()
+        .context("Failed to open git apply input")?
+        .write_all(patch.as_bytes())?;
+    let output = child.wait_with_output()?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(CommittorError::GitError(String::from_utf8_lossy(&output.stderr).to_string()).into())
+    }
+}
+
+/// Display per-file suggestions as a numbered list
+pub fn display_file_suggestions(suggestions: &[FileSuggestion]) {
+    println!("{}", Text::PerFileOptions.text().green().bold());
+    println!();
+
+    for (i, suggestion) in suggestions.iter().enumerate() {
+        println!(
+            "{} {} {}",
+            item_label(i + 1).cyan().bold(),
+            suggestion.path.dimmed(),
+            suggestion.message
+        );
+    }
+    println!();
+}
+
+/// Parse a selection such as `1,3`, `2-4` or `all` into zero-based indices in input order
+pub fn parse_selection(input: &str, count: usize) -> Option<Vec<usize>> {
+    let input = input.trim();
+    if input.eq_ignore_ascii_case("all") || input == "*" {
+        return Some((0..count).collect());
+    }
+
+    let mut selected = Vec::new();
+    for part in input.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (start, end): (usize, usize) = match part.split_once('-') {
+            Some((start, end)) => (start.trim().parse().ok()?, end.trim().parse().ok()?),
+            None => {
+                let n = part.parse().ok()?;
+                (n, n)
+            }
+        };
+
+        if start < 1 || end > count || start > end {
+            return None;
+        }
+        for n in start..=end {
+            if !selected.contains(&(n - 1)) {
+                selected.push(n - 1);
+            }
+        }
+    }
+
+    (!selected.is_empty()).then_some(selected)
+}
+
+/// Prompt the user to pick which per-file commits to create
+pub fn prompt_selection(count: usize) -> Result<Option<Vec<usize>>> {
+    loop {
+        print!("{}", Text::SelectCommitsPrompt.format(&[&count]).yellow());
+        io::stdout().flush()?;
+
+        let Some(input) = read_input()? else {
+            println!();
+            return Ok(None);
+        };
+        if input.eq_ignore_ascii_case("q") || input.eq_ignore_ascii_case("quit") {
+            return Ok(None);
+        }
+
+        match parse_selection(&input, count) {
+            Some(selected) => return Ok(Some(selected)),
+            None => println!("{}", Text::InvalidSelection.text().red()),
+        }
+    }
+}
+
+/// Commit only the staged state of the given paths in the current repository
+pub fn commit_paths(paths: &[String], message: &str) -> Result<()> {
+    commit_paths_in(Path::new("."), paths, message)
+}
+
+/// Commit only the staged state of the given paths, leaving everything else staged
+///
+/// The commit is built in a temporary index seeded from HEAD, so other staged files and
+/// the working tree are untouched and the usual commit hooks still run.
+pub fn commit_paths_in(dir: &Path, paths: &[String], message: &str) -> Result<()> {
+    let index_path = git_output(dir, &["rev-parse", "--git-path", SPLIT_INDEX_NAME])?;
+    let index_path = dir.join(index_path);
+
+    let result = build_and_commit(dir, &index_path, paths, message);
+    // The temporary index is only needed for this one commit
+    let _ = fs::remove_file(&index_path);
+    result
+}
+
+fn build_and_commit(dir: &Path, index_path: &Path, paths: &[String], message: &str) -> Result<()> {
+    let index = Some(index_path);
+
+    if git_output(dir, &["rev-parse", "--verify", "-q", "HEAD"]).is_ok() {
+        git_output_with_index(dir, index, &["read-tree", "HEAD"])?;
+    } else {
+        git_output_with_index(dir, index, &["read-tree", "--empty"])?;
+    }
+
+    for path in paths {
+        let entry = git_output(dir, &["ls-files", "--stage", "--", path])?;
+        stage_entry(dir, index, path, &entry)?;
+    }
+
+    git_output_with_index(dir, index, &["commit", "-q", "-m", message])?;
+    println!(
+        "{} {}",
+        format!("{} {}", success_mark(), Text::SplitCommitted.text())
+            .green()
+            .bold(),
+        message.lines().next().unwrap_or_default()
+    );
+    Ok(())
+}
+
+/// Stage a path from its `git ls-files --stage` entry, or remove it when the entry is empty
+fn stage_entry(dir: &Path, index: Option<&Path>, path: &str, entry: &str) -> Result<()> {
+    match entry.split_once('\t') {
+        Some((meta, _)) => {
+            let fields: Vec<&str> = meta.split_whitespace().collect();
+            let [mode, oid, _stage] = fields[..] else {
+                return Err(CommittorError::GitError(format!(
+                    "Unexpected index entry for {path}: {entry}"
+                ))
+                .into());
+            };
+            let cacheinfo = format!("{mode},{oid},{path}");
+            git_output_with_index(
+                dir,
+                index,
+                &["update-index", "--add", "--cacheinfo", &cacheinfo],
+            )?;
+        }
+        // Not in the index: the file's deletion is what was staged
+        None => {
+            git_output_with_index(dir, index, &["update-index", "--force-remove", "--", path])?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fixtures::{commit, git, init_repo};
+
+    #[test]
+    fn test_parse_selection() {
+        assert_eq!(parse_selection("1,3", 3), Some(vec![0, 2]));
+        assert_eq!(parse_selection("3, 1-2", 3), Some(vec![2, 0, 1]));
+        assert_eq!(parse_selection("all", 2), Some(vec![0, 1]));
+        assert_eq!(parse_selection("4", 3), None);
+        assert_eq!(parse_selection("0", 3), None);
+        assert_eq!(parse_selection("x", 3), None);
+        assert_eq!(parse_selection("", 3), None);
+    }
+
+    #[test]
+    fn test_parse_split_plan() {
+        let response = "Here is the plan:\n1, 3 | Feat(api): Added retries.\n2,1 | docs: explain retries\n9 | chore: bogus";
+        let plan = parse_split_plan(response, 4).unwrap();
+
+        assert_eq!(plan.len(), 2);
+        assert_eq!(plan[0].message, "Feat(api): add retries");
+        assert_eq!(plan[0].hunks, vec![0, 2]);
+        // Hunk 1 is already taken and the unmentioned hunk 4 joins the last commit
+        assert_eq!(plan[1].hunks, vec![1, 3]);
+
+        assert!(parse_split_plan("no plan here", 2).is_err());
+    }
+
+    #[test]
+    fn test_render_plan_tree() {
+        let hunk = |path: &str, text: Option<&str>| DiffHunk {
+            file_path: path.to_string(),
+            file_header: format!("diff --git a/{path} b/{path}\n"),
+            text: text.map(str::to_string),
+        };
+        let hunks = vec![
+            hunk(
+                "src/api.rs",
+                Some("@@ -1,2 +1,3 @@ fn retry\n context\n+added\n+more\n-gone\n"),
+            ),
+            hunk("src/api.rs", Some("@@ -20 +21 @@\n-old\n+new\n")),
+            hunk("logo.png", None),
+        ];
+        let plan = vec![
+            PlannedCommit {
+                message: "feat(api): add retries".to_string(),
+                hunks: vec![1, 0],
+            },
+            PlannedCommit {
+                message: "chore: update logo".to_string(),
+                hunks: vec![2],
+            },
+        ];
+
+        assert_eq!(
+            render_plan_tree(&plan, &hunks),
+            "1. feat(api): add retries\n   └── src/api.rs\n       ├── @@ -1,2 +1,3 @@ fn retry (+2 -1)\n       └── @@ -20 +21 @@ (+1 -1)\n2. chore: update logo\n   └── logo.png\n       └── whole file\n"
+        );
+
+        let suggestions = vec![FileSuggestion {
+            path: "src/api.rs".to_string(),
+            message: "feat(api): add retries".to_string(),
+        }];
+        assert_eq!(plan_for_files(&suggestions, &hunks)[0].hunks, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_execute_plan_splits_hunks_of_one_file() {
+        let temp_dir = init_repo();
+        let dir = temp_dir.path();
+        let lines: Vec<String> = (1..=20).map(|n| format!("line {n}")).collect();
+        fs::write(dir.join("a.txt"), lines.join("\n") + "\n").unwrap();
+        git(dir, &["add", "."]);
+        git(dir, &["commit", "-q", "-m", "chore: initial"]);
+
+        let mut edited = lines.clone();
+        edited[1] = "line two".to_string();
+        edited[18] = "line nineteen".to_string();
+        fs::write(dir.join("a.txt"), edited.join("\n") + "\n").unwrap();
+        fs::write(dir.join("new.bin"), [0u8, 159, 146, 150]).unwrap();
+        git(dir, &["add", "."]);
+        let staged_tree = git(dir, &["write-tree"]);
+
+        let repo = git2::Repository::open(dir).unwrap();
+        let hunks = crate::diff::get_staged_hunks_from_repo(&repo).unwrap();
+        assert_eq!(hunks.len(), 3);
+
+        let plan = vec![
+            PlannedCommit {
+                message: "fix: rename line nineteen".to_string(),
+                hunks: vec![1],
+            },
+            PlannedCommit {
+                message: "feat: rename line two".to_string(),
+                hunks: vec![0, 2],
+            },
+        ];
+        execute_plan_in(dir, &plan, &hunks).unwrap();
+
+        assert_eq!(
+            git(dir, &["log", "--format=%s"]),
+            "feat: rename line two\nfix: rename line nineteen\nchore: initial"
+        );
+        let first = git(dir, &["show", "HEAD~1:a.txt"]);
+        assert!(first.contains("line 2\n") && first.contains("line nineteen"));
+        assert_eq!(git(dir, &["rev-parse", "HEAD^{tree}"]), staged_tree);
+        assert_eq!(git(dir, &["diff", "--cached", "--name-only"]), "");
+        let backups = crate::backup::list_backups_in(dir).unwrap();
+        assert_eq!(backups[0].commit, git(dir, &["rev-parse", "HEAD~2"]));
+    }
+
+    #[test]
+    fn test_execute_plan_rolls_back_on_failure() {
+        let temp_dir = init_repo();
+        let dir = temp_dir.path();
+        let original_head = commit(dir, "a.txt", "a\n", "chore: initial");
+
+        fs::write(dir.join("a.txt"), "a changed\n").unwrap();
+        fs::write(dir.join("b.txt"), "b\n").unwrap();
+        git(dir, &["add", "."]);
+        let staged_tree = git(dir, &["write-tree"]);
+
+        let repo = git2::Repository::open(dir).unwrap();
+        let hunks = crate::diff::get_staged_hunks_from_repo(&repo).unwrap();
+
+        // The second commit's patch cannot apply, so the first commit must be undone
+        let mut broken = hunks.clone();
+        broken[1].text = Some("@@ -1 +1 @@\n-not there\n+b\n".to_string());
+        let plan = vec![
+            PlannedCommit {
+                message: "fix: change a".to_string(),
+                hunks: vec![0],
+            },
+            PlannedCommit {
+                message: "feat: add b".to_string(),
+                hunks: vec![1],
+            },
+        ];
+        assert!(execute_plan_in(dir, &plan, &broken).is_err());
+
+        assert_eq!(git(dir, &["rev-parse", "HEAD"]), original_head);
+        assert_eq!(git(dir, &["write-tree"]), staged_tree);
+        assert_eq!(
+            fs::read_to_string(dir.join("a.txt")).unwrap(),
+            "a changed\n"
+        );
+        assert!(crate::backup::list_backups_in(dir).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_commit_paths_leaves_other_files_staged() {
+        let temp_dir = init_repo();
+        let dir = temp_dir.path();
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::write(dir.join("gone.txt"), "gone").unwrap();
+        git(dir, &["add", "."]);
+        git(dir, &["commit", "-q", "-m", "chore: initial"]);
+
+        fs::write(dir.join("a.txt"), "a changed").unwrap();
+        fs::write(dir.join("b.txt"), "b").unwrap();
+        git(dir, &["add", "a.txt", "b.txt"]);
+        git(dir, &["rm", "-q", "gone.txt"]);
+        // Unstaged edits must not leak into the split commits
+        fs::write(dir.join("a.txt"), "a changed again").unwrap();
+
+        commit_paths_in(dir, &["b.txt".to_string()], "feat: add b").unwrap();
+        commit_paths_in(dir, &["gone.txt".to_string()], "chore: remove gone").unwrap();
+
+        assert_eq!(
+            git(dir, &["log", "--format=%s"]),
+            "chore: remove gone\nfeat: add b\nchore: initial"
+        );
+        assert_eq!(git(dir, &["show", "HEAD~1:b.txt"]), "b");
+        assert!(git_output(dir, &["show", "HEAD:gone.txt"]).is_err());
+        assert_eq!(git(dir, &["diff", "--cached", "--name-only"]), "a.txt");
+        assert_eq!(git(dir, &["show", ":a.txt"]), "a changed");
+        assert!(!dir.join(".git").join(SPLIT_INDEX_NAME).exists());
+    }
+}
@@ -0,0 +1,142 @@
+//! Writing committor's state files safely while other runs use them too
+//!
+//! Developers run committor in several terminals at once, often in one repository.
+//! Files are replaced by renaming a finished temporary file over them, so readers
+//! see the old content or the new and never half of either, and read-modify-write
+//! updates and log appends hold an exclusive lock so no run's change is lost.
+
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Distinguishes the temporary files of threads writing the same file
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Replace a file's content in one step, by writing a temporary file and renaming it
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let temp_path = temp_path(path);
+    let written = File::create(&temp_path).and_then(|mut file| {
+        file.write_all(contents.as_ref())?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)
+    });
+    if written.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    written
+}
+
+/// Get a temporary path next to a file, unique to this process and write
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let unique = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    path.with_file_name(format!(".{name}.{}.{unique}.tmp", process::id()))
+}
+
+/// Get the lock file that guards updates to a file
+pub fn lock_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".lock");
+    path.with_file_name(name)
+}
+
+/// Run an update to a file while holding its lock, waiting for other runs to finish theirs
+///
+/// The lock is on a file next to it rather than the file itself, because the
+/// rename in [`write_atomic`] would leave a lock on the replaced file behind.
+pub fn with_lock<T>(path: &Path, update: impl FnOnce() -> Result<T>) -> Result<T> {
+    let lock_path = lock_path(path);
+    let lock = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .with_context(|| format!("Failed to open lock file {}", lock_path.display()))?;
+    lock.lock()
+        .with_context(|| format!("Failed to lock {}", lock_path.display()))?;
+    // Released when the file is closed, even if the update panics
+    update()
+}
+
+/// Append a line to a log file, creating it if needed, without interleaving with other runs
+pub fn append_line(path: &Path, line: &str) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.lock()?;
+    file.write_all(format!("{line}\n").as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_write_atomic() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("state.json");
+
+        write_atomic(&path, "first")?;
+        write_atomic(&path, "second")?;
+        assert_eq!(fs::read_to_string(&path)?, "second");
+        // No temporary file is left behind
+        assert_eq!(fs::read_dir(dir.path())?.count(), 1);
+
+        let missing = dir.path().join("missing").join("state.json");
+        assert!(write_atomic(&missing, "lost").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_concurrent_updates_are_not_lost() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("count");
+        write_atomic(&path, "0")?;
+
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let path = path.clone();
+                thread::spawn(move || {
+                    for _ in 0..10 {
+                        with_lock(&path, || {
+                            let count: usize = fs::read_to_string(&path)?.parse()?;
+                            write_atomic(&path, (count + 1).to_string())?;
+                            Ok(())
+                        })
+                        .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(fs::read_to_string(&path)?, "80");
+        Ok(())
+    }
+
+    #[test]
+    fn test_append_line() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("log.jsonl");
+        let line = "x".repeat(10_000);
+
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let (path, line) = (path.clone(), line.clone());
+                thread::spawn(move || append_line(&path, &line).unwrap())
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let content = fs::read_to_string(&path)?;
+        assert_eq!(content.lines().count(), 8);
+        assert!(content.lines().all(|written| written == line));
+        Ok(())
+    }
+}
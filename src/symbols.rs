@@ -0,0 +1,473 @@
+//! Public functions and types added, removed or changed by the staged Rust and TypeScript files
+//!
+//! Naming the symbols lets the model write `add TokenValidator::verify` rather
+//! than `update auth module`. Parsing needs the `symbols` feature; without it
+//! no symbols are found and prompts are unchanged.
+
+use crate::diff::{get_staged_changes_from_repo, staged_file_versions_from_repo};
+use anyhow::{Context, Result};
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+/// Most changed symbols listed in a prompt
+pub const MAX_PROMPT_SYMBOLS: usize = 20;
+
+/// Symbol extraction read from the `[symbols]` settings section
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SymbolSettings {
+    /// List the changed public symbols in the prompt
+    pub enabled: bool,
+    /// Ask for a bullet per changed symbol in generated bodies
+    pub in_body: bool,
+}
+
+impl Default for SymbolSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            in_body: false,
+        }
+    }
+}
+
+/// Languages symbols can be extracted from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceLanguage {
+    Rust,
+    TypeScript,
+    Tsx,
+}
+
+impl SourceLanguage {
+    /// Get the language of a file from its extension
+    pub fn for_path(path: &str) -> Option<Self> {
+        match Path::new(path).extension()?.to_str()? {
+            "rs" => Some(Self::Rust),
+            "ts" | "mts" | "cts" => Some(Self::TypeScript),
+            "tsx" => Some(Self::Tsx),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SymbolKind {
+    Function,
+    Type,
+}
+
+impl fmt::Display for SymbolKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Function => write!(f, "function"),
+            Self::Type => write!(f, "type"),
+        }
+    }
+}
+
+/// A public item in one version of a file, with its source text to spot changes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub kind: SymbolKind,
+    /// Qualified name, e.g. `TokenValidator::verify` or `Client.send`
+    pub name: String,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolChange {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// A public symbol the staged changes add, remove or modify
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedSymbol {
+    /// File the symbol is declared in
+    pub path: String,
+    pub change: SymbolChange,
+    pub kind: SymbolKind,
+    pub name: String,
+}
+
+impl fmt::Display for ChangedSymbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let change = match self.change {
+            SymbolChange::Added => "added",
+            SymbolChange::Removed => "removed",
+            SymbolChange::Modified => "changed",
+        };
+        write!(f, "{change} {} `{}`", self.kind, self.name)
+    }
+}
+
+/// Get the symbols changed by the staged files of the repository in the current directory
+pub fn changed_symbols() -> Result<Vec<ChangedSymbol>> {
+    let repo = Repository::open(".").context("Not in a git repository")?;
+    changed_symbols_from_repo(&repo)
+}
+
+/// Get the symbols changed by the staged files of a specific repository
+pub fn changed_symbols_from_repo(repo: &Repository) -> Result<Vec<ChangedSymbol>> {
+    if !cfg!(feature = "symbols") {
+        return Ok(Vec::new());
+    }
+
+    let mut changed = Vec::new();
+    for change in get_staged_changes_from_repo(repo)? {
+        let Some(language) = SourceLanguage::for_path(&change.file_path) else {
+            continue;
+        };
+        let (old, new) = staged_file_versions_from_repo(repo, &change.file_path)?;
+        changed.extend(diff_symbols(
+            &change.file_path,
+            &public_symbols(language, &old.unwrap_or_default()),
+            &public_symbols(language, &new.unwrap_or_default()),
+        ));
+    }
+    Ok(changed)
+}
+
+/// Compare the public symbols of two versions of the file at `path`
+pub fn diff_symbols(path: &str, old: &[Symbol], new: &[Symbol]) -> Vec<ChangedSymbol> {
+    let find = |symbols: &[Symbol], symbol: &Symbol| {
+        symbols
+            .iter()
+            .find(|other| other.kind == symbol.kind && other.name == symbol.name)
+            .map(|other| other.text == symbol.text)
+    };
+    let changed = |change, symbol: &Symbol| ChangedSymbol {
+        path: path.to_string(),
+        change,
+        kind: symbol.kind,
+        name: symbol.name.clone(),
+    };
+
+    let mut changes = Vec::new();
+    for symbol in new {
+        match find(old, symbol) {
+            None => changes.push(changed(SymbolChange::Added, symbol)),
+            Some(false) => changes.push(changed(SymbolChange::Modified, symbol)),
+            Some(true) => {}
+        }
+    }
+    for symbol in old {
+        if find(new, symbol).is_none() {
+            changes.push(changed(SymbolChange::Removed, symbol));
+        }
+    }
+    changes
+}
+
+/// List the public functions and types in a source file
+#[cfg(feature = "symbols")]
+pub fn public_symbols(language: SourceLanguage, source: &str) -> Vec<Symbol> {
+    let grammar = match language {
+        SourceLanguage::Rust => tree_sitter_rust::LANGUAGE,
+        SourceLanguage::TypeScript => tree_sitter_typescript::LANGUAGE_TYPESCRIPT,
+        SourceLanguage::Tsx => tree_sitter_typescript::LANGUAGE_TSX,
+    };
+    let mut parser = tree_sitter::Parser::new();
+    if parser.set_language(&grammar.into()).is_err() {
+        return Vec::new();
+    }
+    let Some(tree) = parser.parse(source, None) else {
+        return Vec::new();
+    };
+
+    let mut symbols = Vec::new();
+    match language {
+        SourceLanguage::Rust => parse::rust_items(tree.root_node(), source, "", &mut symbols),
+        SourceLanguage::TypeScript | SourceLanguage::Tsx => {
+            parse::typescript_exports(tree.root_node(), source, &mut symbols)
+        }
+    }
+    symbols
+}
+
+/// List the public functions and types in a source file; always empty without the `symbols` feature
+#[cfg(not(feature = "symbols"))]
+pub fn public_symbols(_language: SourceLanguage, _source: &str) -> Vec<Symbol> {
+    Vec::new()
+}
+
+#[cfg(feature = "symbols")]
+mod parse {
+    use super::{Symbol, SymbolKind};
+    use tree_sitter::Node;
+
+    fn text<'a>(node: Node, source: &'a str) -> &'a str {
+        node.utf8_text(source.as_bytes()).unwrap_or_default()
+    }
+
+    fn push(symbols: &mut Vec<Symbol>, kind: SymbolKind, name: String, node: Node, source: &str) {
+        symbols.push(Symbol {
+            kind,
+            name,
+            text: text(node, source).to_string(),
+        });
+    }
+
+    /// Check for a plain `pub`; `pub(crate)` and friends aren't part of the public API
+    fn is_pub(node: Node, source: &str) -> bool {
+        let mut cursor = node.walk();
+        let is_pub = node
+            .named_children(&mut cursor)
+            .any(|child| child.kind() == "visibility_modifier" && text(child, source) == "pub");
+        is_pub
+    }
+
+    /// Collect public items, descending into public modules and inherent impls
+    pub fn rust_items(node: Node, source: &str, prefix: &str, symbols: &mut Vec<Symbol>) {
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            let name = child
+                .child_by_field_name("name")
+                .map(|name| format!("{prefix}{}", text(name, source)));
+            match (child.kind(), name) {
+                ("function_item", Some(name)) if is_pub(child, source) => {
+                    push(symbols, SymbolKind::Function, name, child, source)
+                }
+                (
+                    "struct_item" | "enum_item" | "union_item" | "trait_item" | "type_item",
+                    Some(name),
+                ) if is_pub(child, source) => push(symbols, SymbolKind::Type, name, child, source),
+                ("mod_item", Some(name)) if is_pub(child, source) => {
+                    if let Some(body) = child.child_by_field_name("body") {
+                        rust_items(body, source, &format!("{name}::"), symbols);
+                    }
+                }
+                // Methods of trait impls follow the trait, so only inherent impls add API
+                ("impl_item", _) if child.child_by_field_name("trait").is_none() => {
+                    let (Some(self_type), Some(body)) = (
+                        child.child_by_field_name("type"),
+                        child.child_by_field_name("body"),
+                    ) else {
+                        continue;
+                    };
+                    let self_type = text(self_type, source);
+                    let self_type = self_type.split('<').next().unwrap_or(self_type).trim();
+                    rust_items(body, source, &format!("{prefix}{self_type}::"), symbols);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Collect exported declarations and the public methods of exported classes
+    pub fn typescript_exports(node: Node, source: &str, symbols: &mut Vec<Symbol>) {
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            if child.kind() != "export_statement" {
+                continue;
+            }
+            let Some(declaration) = child.child_by_field_name("declaration") else {
+                continue;
+            };
+            let name = declaration
+                .child_by_field_name("name")
+                .map(|name| text(name, source).to_string());
+
+            match (declaration.kind(), name) {
+                ("function_declaration" | "generator_function_declaration", Some(name)) => {
+                    push(symbols, SymbolKind::Function, name, declaration, source)
+                }
+                ("class_declaration" | "abstract_class_declaration", Some(name)) => {
+                    push(symbols, SymbolKind::Type, name.clone(), declaration, source);
+                    if let Some(body) = declaration.child_by_field_name("body") {
+                        class_methods(body, source, &name, symbols);
+                    }
+                }
+                (
+                    "interface_declaration" | "type_alias_declaration" | "enum_declaration",
+                    Some(name),
+                ) => push(symbols, SymbolKind::Type, name, declaration, source),
+                ("lexical_declaration", _) => {
+                    let mut cursor = declaration.walk();
+                    for declarator in declaration.named_children(&mut cursor) {
+                        let is_function =
+                            declarator
+                                .child_by_field_name("value")
+                                .is_some_and(|value| {
+                                    matches!(value.kind(), "arrow_function" | "function_expression")
+                                });
+                        if let (true, Some(name)) =
+                            (is_function, declarator.child_by_field_name("name"))
+                        {
+                            let name = text(name, source).to_string();
+                            push(symbols, SymbolKind::Function, name, declarator, source);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn class_methods(body: Node, source: &str, class: &str, symbols: &mut Vec<Symbol>) {
+        let mut cursor = body.walk();
+        for member in body.named_children(&mut cursor) {
+            if member.kind() != "method_definition" {
+                continue;
+            }
+            let mut member_cursor = member.walk();
+            let hidden = member.named_children(&mut member_cursor).any(|child| {
+                child.kind() == "accessibility_modifier"
+                    && matches!(text(child, source), "private" | "protected")
+            });
+            let Some(name) = member.child_by_field_name("name") else {
+                continue;
+            };
+            let name = text(name, source);
+            if hidden || name.starts_with('#') || name == "constructor" {
+                continue;
+            }
+            push(
+                symbols,
+                SymbolKind::Function,
+                format!("{class}.{name}"),
+                member,
+                source,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbol(kind: SymbolKind, name: &str, text: &str) -> Symbol {
+        Symbol {
+            kind,
+            name: name.to_string(),
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_diff_symbols() {
+        let old = vec![
+            symbol(SymbolKind::Function, "parse", "fn parse() {}"),
+            symbol(SymbolKind::Type, "Token", "struct Token;"),
+            symbol(SymbolKind::Function, "legacy", "fn legacy() {}"),
+        ];
+        let new = vec![
+            symbol(SymbolKind::Function, "parse", "fn parse(strict: bool) {}"),
+            symbol(SymbolKind::Type, "Token", "struct Token;"),
+            symbol(
+                SymbolKind::Function,
+                "TokenValidator::verify",
+                "fn verify() {}",
+            ),
+        ];
+
+        let changes: Vec<String> = diff_symbols("src/auth.rs", &old, &new)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                "changed function `parse`",
+                "added function `TokenValidator::verify`",
+                "removed function `legacy`",
+            ]
+        );
+    }
+
+    #[cfg(feature = "symbols")]
+    #[test]
+    fn test_rust_public_symbols() {
+        let source = r#"
+pub struct TokenValidator;
+struct Internal;
+pub(crate) fn helper() {}
+
+impl TokenValidator {
+    pub fn verify(&self) -> bool { true }
+    fn cache(&self) {}
+}
+
+impl Default for TokenValidator {
+    fn default() -> Self { Self }
+}
+
+pub mod claims {
+    pub enum Claim { Sub }
+}
+"#;
+        let names: Vec<String> = public_symbols(SourceLanguage::Rust, source)
+            .into_iter()
+            .map(|symbol| format!("{} {}", symbol.kind, symbol.name))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "type TokenValidator",
+                "function TokenValidator::verify",
+                "type claims::Claim",
+            ]
+        );
+    }
+
+    #[cfg(feature = "symbols")]
+    #[test]
+    fn test_typescript_public_symbols() {
+        let source = r#"
+export interface Session { id: string }
+export const refresh = async (s: Session) => s;
+export const TIMEOUT = 30;
+function internal() {}
+export class Client {
+  send(body: string) {}
+  private retry() {}
+}
+"#;
+        let names: Vec<String> = public_symbols(SourceLanguage::TypeScript, source)
+            .into_iter()
+            .map(|symbol| format!("{} {}", symbol.kind, symbol.name))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "type Session",
+                "function refresh",
+                "type Client",
+                "function Client.send",
+            ]
+        );
+    }
+
+    #[cfg(feature = "symbols")]
+    #[test]
+    fn test_changed_symbols_from_repo() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let repo = Repository::init(dir.path())?;
+        let file = dir.path().join("lib.rs");
+        let stage = |content: &str| -> Result<()> {
+            std::fs::write(&file, content)?;
+            let mut index = repo.index()?;
+            index.add_path(Path::new("lib.rs"))?;
+            index.write()?;
+            Ok(())
+        };
+
+        stage("pub fn parse() {}\n")?;
+        let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+        let signature = git2::Signature::now("Test User", "test@example.com")?;
+        repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])?;
+
+        stage("pub fn parse() {}\npub fn verify() {}\n")?;
+        let changes: Vec<String> = changed_symbols_from_repo(&repo)?
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(changes, vec!["added function `verify`"]);
+        Ok(())
+    }
+}
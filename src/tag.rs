@@ -0,0 +1,155 @@
+//! Annotated tag message generation from the commits since the previous tag
+
+use crate::body::format_message_body;
+use crate::output::success_mark;
+use crate::prompt::create_tag_prompt;
+use crate::providers::{provider_error, AIProvider};
+use crate::types::CommittorError;
+use anyhow::{Context, Result};
+use colored::*;
+use git2::{DescribeFormatOptions, DescribeOptions, Repository};
+use std::process::Command;
+
+/// Find the most recent tag reachable from HEAD, if any
+pub fn find_previous_tag_from_repo(repo: &Repository) -> Result<Option<String>> {
+    let mut describe_opts = DescribeOptions::new();
+    describe_opts.describe_tags();
+
+    let describe = match repo.describe(&describe_opts) {
+        Ok(describe) => describe,
+        // No tag reachable from HEAD
+        Err(e) if e.class() == git2::ErrorClass::Describe => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut format_opts = DescribeFormatOptions::new();
+    format_opts.abbreviated_size(0);
+    Ok(Some(describe.format(Some(&format_opts))?))
+}
+
+/// Get the subjects of the commits after `since` up to HEAD, newest first
+pub fn commit_subjects_since_from_repo(
+    repo: &Repository,
+    since: Option<&str>,
+) -> Result<Vec<String>> {
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(git2::Sort::TOPOLOGICAL)?;
+    revwalk.push_head()?;
+
+    if let Some(since) = since {
+        let since_commit = repo.revparse_single(since)?.peel_to_commit()?;
+        revwalk.hide(since_commit.id())?;
+    }
+
+    let mut subjects = Vec::new();
+    for oid in revwalk {
+        let commit = repo.find_commit(oid?)?;
+        if commit.parent_count() > 1 {
+            continue;
+        }
+        subjects.push(commit.summary().unwrap_or_default().to_string());
+    }
+
+    Ok(subjects)
+}
+
+/// Generate an annotated tag message summarizing the given commit subjects
+pub async fn generate_tag_message(
+    tag_name: &str,
+    previous_tag: Option<&str>,
+    subjects: &[String],
+    provider: &dyn AIProvider,
+) -> Result<String> {
+    let prompt = create_tag_prompt(tag_name, previous_tag, subjects);
+    let response = provider
+        .generate_message(&prompt)
+        .await
+        .map_err(provider_error)?;
+
+    let message = format_message_body(&response);
+    if message.is_empty() {
+        return Err(CommittorError::AIProviderError(
+            "Failed to generate a tag message".to_string(),
+        )
+        .into());
+    }
+
+    Ok(message)
+}
+
+/// Create an annotated tag at HEAD with the given message
+pub fn create_annotated_tag(tag_name: &str, message: &str) -> Result<()> {
+    println!("{}", format!("Creating annotated tag {tag_name}").green());
+
+    let output = Command::new("git")
+        .args(["tag", "-a", tag_name, "-m", message])
+        .output()
+        .context("Failed to execute git tag")?;
+
+    if output.status.success() {
+        println!(
+            "{}",
+            format!("{} Tag created!", success_mark()).green().bold()
+        );
+        Ok(())
+    } else {
+        let error = String::from_utf8_lossy(&output.stderr);
+        Err(CommittorError::GitError(error.to_string()).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn commit(repo: &Repository, message: &str) -> git2::Oid {
+        let signature = git2::Signature::now("Test User", "test@example.com").unwrap();
+        let tree_id = repo.index().unwrap().write_tree().unwrap();
+        let tree = repo.find_tree(tree_id).unwrap();
+        let parents = match repo.head() {
+            Ok(head) => vec![head.peel_to_commit().unwrap()],
+            Err(_) => Vec::new(),
+        };
+        let parent_refs: Vec<&git2::Commit> = parents.iter().collect();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parent_refs,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_previous_tag_and_subjects() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = Repository::init(temp_dir.path()).unwrap();
+
+        commit(&repo, "feat: initial release");
+        assert_eq!(find_previous_tag_from_repo(&repo).unwrap(), None);
+        assert_eq!(
+            commit_subjects_since_from_repo(&repo, None).unwrap(),
+            vec!["feat: initial release"]
+        );
+
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        let signature = git2::Signature::now("Test User", "test@example.com").unwrap();
+        repo.tag("v1.0.0", head.as_object(), &signature, "v1.0.0", false)
+            .unwrap();
+
+        commit(&repo, "fix(api): handle timeouts");
+        commit(&repo, "feat(cli): add tag command");
+
+        let previous = find_previous_tag_from_repo(&repo).unwrap();
+        assert_eq!(previous.as_deref(), Some("v1.0.0"));
+
+        let subjects = commit_subjects_since_from_repo(&repo, previous.as_deref()).unwrap();
+        assert_eq!(
+            subjects,
+            vec!["feat(cli): add tag command", "fix(api): handle timeouts"]
+        );
+    }
+}
@@ -0,0 +1,170 @@
+//! Output templates that lay out the final commit message from its parsed parts
+
+use crate::commit::parse_commit_message;
+use crate::types::{CommittorError, ConventionalCommit};
+use anyhow::Result;
+
+/// Placeholders that may appear in an output template
+pub const PLACEHOLDERS: &[&str] = &[
+    "type", "scope", "breaking", "subject", "header", "body", "ticket",
+];
+
+/// Find a ticket reference such as `ABC-123` in a branch name
+pub fn extract_ticket(branch: &str) -> Option<String> {
+    let regex = regex::Regex::new(r"[A-Z][A-Z0-9]+-[0-9]+").unwrap();
+    regex.find(branch).map(|m| m.as_str().to_string())
+}
+
+/// Render a message through a template, leaving messages that don't parse unchanged
+pub fn apply_template(template: &str, message: &str, ticket: Option<&str>) -> Result<String> {
+    let (subject, body) = match message.split_once('\n') {
+        Some((subject, body)) => (subject, body.trim()),
+        None => (message, ""),
+    };
+
+    match parse_commit_message(subject.trim()) {
+        Ok(commit) => render_template(template, &commit, body, ticket),
+        Err(_) => Ok(message.to_string()),
+    }
+}
+
+/// Render a template like `{type}({scope}): {subject}\n\n{body}` for a parsed commit
+///
+/// Lines whose placeholders all render empty are dropped, as are the parentheses
+/// around a missing `{scope}`, so optional parts disappear cleanly.
+pub fn render_template(
+    template: &str,
+    commit: &ConventionalCommit,
+    body: &str,
+    ticket: Option<&str>,
+) -> Result<String> {
+    let placeholder_regex = regex::Regex::new(r"\{([a-z_]+)\}").unwrap();
+    let template = template.replace("\\n", "\n");
+
+    let mut lines = Vec::new();
+    for line in template.split('\n') {
+        let mut any_placeholder = false;
+        let mut any_value = false;
+        let mut rendered = String::new();
+        let mut last = 0;
+
+        for captures in placeholder_regex.captures_iter(line) {
+            let whole = captures.get(0).unwrap();
+            let value = placeholder_value(&captures[1], commit, body, ticket)?;
+            any_placeholder = true;
+            any_value |= !value.is_empty();
+            rendered.push_str(&line[last..whole.start()]);
+            rendered.push_str(&value);
+            last = whole.end();
+
+            // A missing scope takes the parentheses around its placeholder with it
+            if &captures[1] == "scope"
+                && value.is_empty()
+                && rendered.ends_with('(')
+                && line[last..].starts_with(')')
+            {
+                rendered.pop();
+                last += 1;
+            }
+        }
+        rendered.push_str(&line[last..]);
+
+        if any_placeholder && !any_value {
+            continue;
+        }
+        lines.push(rendered.trim_end().to_string());
+    }
+
+    // Collapse the blank lines left behind by dropped sections
+    let mut output: Vec<String> = Vec::new();
+    for line in lines {
+        if line.is_empty() && output.last().is_none_or(|l| l.is_empty()) {
+            continue;
+        }
+        output.push(line);
+    }
+
+    Ok(output.join("\n").trim_end().to_string())
+}
+
+/// Get the value of a single placeholder
+fn placeholder_value(
+    name: &str,
+    commit: &ConventionalCommit,
+    body: &str,
+    ticket: Option<&str>,
+) -> Result<String> {
+    let value = match name {
+        "type" => commit.commit_type.to_string(),
+        "scope" => commit.scope.clone().unwrap_or_default(),
+        "breaking" => if commit.breaking { "!" } else { "" }.to_string(),
+        "subject" => commit.description.clone(),
+        "header" => commit.to_string(),
+        "body" => body.to_string(),
+        "ticket" => ticket.unwrap_or_default().to_string(),
+        _ => {
+            return Err(CommittorError::ConfigError(format!(
+                "Unknown placeholder {{{name}}} in format template, expected one of: {}",
+                PLACEHOLDERS.join(", ")
+            ))
+            .into())
+        }
+    };
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEMPLATE: &str = "{type}({scope}){breaking}: {subject}\\n\\n{body}\\n\\nRefs: {ticket}";
+
+    #[test]
+    fn test_apply_template() {
+        let message = apply_template(
+            TEMPLATE,
+            "feat(auth): add login\n\nUsers can sign in.",
+            Some("ABC-123"),
+        )
+        .unwrap();
+        assert_eq!(
+            message,
+            "feat(auth): add login\n\nUsers can sign in.\n\nRefs: ABC-123"
+        );
+    }
+
+    #[test]
+    fn test_apply_template_drops_empty_parts() {
+        let message = apply_template(TEMPLATE, "fix!: stop crash", None).unwrap();
+        assert_eq!(message, "fix!: stop crash");
+    }
+
+    #[test]
+    fn test_apply_template_keeps_literal_parentheses() {
+        let message = apply_template(
+            "{type}({scope}): {subject}\\n\\nFixes parse()",
+            "fix: stop crash",
+            None,
+        )
+        .unwrap();
+        assert_eq!(message, "fix: stop crash\n\nFixes parse()");
+    }
+
+    #[test]
+    fn test_apply_template_errors() {
+        assert!(apply_template("{kind}: {subject}", "feat: add login", None).is_err());
+        assert_eq!(
+            apply_template(TEMPLATE, "not conventional", None).unwrap(),
+            "not conventional"
+        );
+    }
+
+    #[test]
+    fn test_extract_ticket() {
+        assert_eq!(
+            extract_ticket("feature/ABC-123-login").as_deref(),
+            Some("ABC-123")
+        );
+        assert_eq!(extract_ticket("feature/login"), None);
+    }
+}
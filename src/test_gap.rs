@@ -0,0 +1,113 @@
+//! Test cases suggested for source changes that come without tests
+//!
+//! The files of a change are split into source and tests the same way commit type
+//! suggestions do it. When sources changed and no test did, the model is asked what
+//! to test. The suggestions are printed next to the messages, never put in one.
+
+use crate::prompt::{is_source_file, is_test_file};
+use crate::types::{DiffChange, DiffChangeType};
+use colored::*;
+
+/// Most test cases shown
+pub const MAX_TEST_SUGGESTIONS: usize = 5;
+
+/// Source files changed without any test changing, with the test cases suggested for them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestGap {
+    pub sources: Vec<String>,
+    pub suggestions: Vec<String>,
+}
+
+/// Get the source files of a change that has no test changes, empty when it has some
+///
+/// Deleted sources are left out, as removing code needs no new test.
+pub fn untested_sources(changes: &[DiffChange]) -> Vec<String> {
+    if changes.iter().any(|change| is_test_file(&change.file_path)) {
+        return Vec::new();
+    }
+    changes
+        .iter()
+        .filter(|change| change.change_type != DiffChangeType::Deleted)
+        .filter(|change| is_source_file(&change.file_path))
+        .map(|change| change.file_path.clone())
+        .collect()
+}
+
+/// Parse the model's `- <test case>` lines
+pub fn parse_test_suggestions(response: &str) -> Vec<String> {
+    response
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let case = line
+                .strip_prefix("- ")
+                .or_else(|| line.strip_prefix("* "))?
+                .trim();
+            (!case.is_empty()).then(|| case.to_string())
+        })
+        .take(MAX_TEST_SUGGESTIONS)
+        .collect()
+}
+
+/// Print the untested sources and the suggested test cases
+pub fn display_test_gap(gap: &TestGap) {
+    println!(
+        "{}",
+        format!(
+            "No test changes accompany these source changes: {}",
+            gap.sources.join(", ")
+        )
+        .yellow()
+    );
+    if !gap.suggestions.is_empty() {
+        println!("{}", "Suggested test cases:".bold());
+        for suggestion in &gap.suggestions {
+            println!("  - {suggestion}");
+        }
+    }
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(path: &str, change_type: DiffChangeType) -> DiffChange {
+        DiffChange {
+            file_path: path.to_string(),
+            change_type,
+            additions: 1,
+            deletions: 0,
+        }
+    }
+
+    #[test]
+    fn test_untested_sources() {
+        let changes = vec![
+            change("src/auth.rs", DiffChangeType::Modified),
+            change("src/legacy.rs", DiffChangeType::Deleted),
+            change("README.md", DiffChangeType::Modified),
+        ];
+        assert_eq!(untested_sources(&changes), vec!["src/auth.rs"]);
+
+        let mut with_tests = changes.clone();
+        with_tests.push(change("tests/auth.rs", DiffChangeType::Modified));
+        assert!(untested_sources(&with_tests).is_empty());
+        assert!(untested_sources(&changes[2..]).is_empty());
+    }
+
+    #[test]
+    fn test_parse_test_suggestions() {
+        let response = "Here are some cases:\n- rejects an expired token\n* accepts a fresh token\n-\n- a\n- b\n- c\n- d";
+        assert_eq!(
+            parse_test_suggestions(response),
+            vec![
+                "rejects an expired token",
+                "accepts a fresh token",
+                "a",
+                "b",
+                "c"
+            ]
+        );
+    }
+}
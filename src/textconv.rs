@@ -0,0 +1,137 @@
+//! Patches of files whose `.gitattributes` diff driver converts them to text
+//!
+//! Repositories keep Word documents, PDFs or spreadsheets readable in `git diff`
+//! with a driver such as `*.docx diff=word` and a `diff.word.textconv` command.
+//! libgit2 doesn't run textconv, so those files would reach the prompt as
+//! `Binary files differ`; their patches are taken from git itself instead.
+
+use crate::diff::{decode_text, get_staged_changes_from_repo};
+use crate::types::DiffChangeType;
+use anyhow::{Context, Result};
+use git2::{AttrCheckFlags, AttrValue, Repository};
+use std::path::Path;
+use std::process::Command;
+
+/// Get textconv patches for the files staged in the repository in the current directory
+pub fn textconv_patches() -> Result<Vec<(String, String)>> {
+    let repo = Repository::open(".").context("Not in a git repository")?;
+    textconv_patches_from_repo(&repo)
+}
+
+/// Get textconv patches for the files staged in a specific repository, keyed by path
+///
+/// Only files whose diff driver has a `textconv` command configured are included;
+/// the command is the one git would run for `git diff --cached`.
+pub fn textconv_patches_from_repo(repo: &Repository) -> Result<Vec<(String, String)>> {
+    let Some(workdir) = repo.workdir() else {
+        return Ok(Vec::new());
+    };
+    let config = repo.config()?;
+
+    let mut patches = Vec::new();
+    for change in get_staged_changes_from_repo(repo)? {
+        // Renamed files keep their old path in git's header, so they're left to libgit2
+        if change.change_type == DiffChangeType::Renamed {
+            continue;
+        }
+        let Some(driver) = diff_driver(repo, &change.file_path)? else {
+            continue;
+        };
+        if config
+            .get_string(&format!("diff.{driver}.textconv"))
+            .is_err()
+        {
+            continue;
+        }
+        if let Some(patch) = textconv_patch(workdir, &change.file_path, &driver)? {
+            patches.push((change.file_path, patch));
+        }
+    }
+    Ok(patches)
+}
+
+/// Get the name of the diff driver `.gitattributes` assigns to a path, if any
+pub fn diff_driver(repo: &Repository, path: &str) -> Result<Option<String>> {
+    let value = repo.get_attr(Path::new(path), "diff", AttrCheckFlags::INDEX_THEN_FILE)?;
+    Ok(match AttrValue::from_string(value) {
+        AttrValue::String(driver) => Some(driver.to_string()),
+        _ => None,
+    })
+}
+
+/// Run `git diff` with textconv for one staged file, returning the patch after its `diff --git` line
+fn textconv_patch(workdir: &Path, path: &str, driver: &str) -> Result<Option<String>> {
+    let output = Command::new("git")
+        .args([
+            "diff",
+            "--cached",
+            "--textconv",
+            "--no-color",
+            "--no-ext-diff",
+            "--",
+            path,
+        ])
+        .current_dir(workdir)
+        .output()
+        .context("Failed to run git diff")?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+
+    let patch = decode_text(&output.stdout);
+    let Some((_, body)) = patch.split_once('\n') else {
+        return Ok(None);
+    };
+    Ok(Some(format!(
+        "(converted to text by the `{driver}` diff driver)\n{body}"
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_textconv_patches() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo = Repository::init(temp_dir.path())?;
+        let signature = git2::Signature::now("Test User", "test@example.com")?;
+        let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+        repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])?;
+        let mut config = repo.config()?;
+        config.set_str("diff.shout.textconv", "tr a-z A-Z <")?;
+
+        fs::write(
+            temp_dir.path().join(".gitattributes"),
+            "*.doc diff=shout\n*.txt diff=plain\n*.bin -diff\n",
+        )?;
+        fs::write(temp_dir.path().join("notes.doc"), "hello\n")?;
+        fs::write(temp_dir.path().join("notes.txt"), "hello\n")?;
+        fs::write(temp_dir.path().join("data.bin"), "hello\n")?;
+        let mut index = repo.index()?;
+        for path in [".gitattributes", "notes.doc", "notes.txt", "data.bin"] {
+            index.add_path(Path::new(path))?;
+        }
+        index.write()?;
+
+        assert_eq!(diff_driver(&repo, "notes.doc")?.as_deref(), Some("shout"));
+        assert_eq!(diff_driver(&repo, "data.bin")?, None);
+
+        // Drivers without a textconv command, and `-diff`, are left to libgit2
+        let patches = textconv_patches_from_repo(&repo)?;
+        assert_eq!(patches.len(), 1);
+        let (path, patch) = &patches[0];
+        assert_eq!(path, "notes.doc");
+        assert!(patch.starts_with("(converted to text by the `shout` diff driver)\n"));
+        assert!(patch.contains("+HELLO"));
+        assert!(!patch.contains("diff --git"));
+
+        // An unconfigured driver still diffs as plain text
+        let diff = crate::diff::get_staged_diff_from_repo(&repo)?;
+        assert!(diff.contains("b/notes.txt\n") && diff.contains("+hello"));
+
+        Ok(())
+    }
+}
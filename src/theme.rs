@@ -0,0 +1,234 @@
+//! Colors of commit types and interface elements, customizable with a `[theme]` section
+//!
+//! The picker, the `--stat` graph and the grouped option headings all color text
+//! through [`paint`] and [`paint_type`], so one setting changes an element
+//! everywhere. A color can be any name `colored` knows, a `#rrggbb` hex code,
+//! `dimmed`, or `none` to leave the element uncolored.
+
+use crate::types::CommitType;
+use colored::{Color, ColoredString, Colorize};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// Colors read from the `[theme]` settings section, keyed by commit type or element name
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ThemeSettings {
+    pub colors: BTreeMap<String, String>,
+}
+
+/// A part of the interface with a color of its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Element {
+    /// Headings such as "Generated commit message options"
+    Heading,
+    /// Option numbers and picker keys
+    Number,
+    /// Warnings and questions
+    Warning,
+    /// Rejected input
+    Error,
+    /// Secondary text such as collapsed bodies and type meanings
+    Hint,
+    /// Additions in the `--stat` graph
+    Added,
+    /// Deletions in the `--stat` graph
+    Deleted,
+}
+
+impl Element {
+    /// Every element, for validating settings
+    pub const ALL: [Element; 7] = [
+        Element::Heading,
+        Element::Number,
+        Element::Warning,
+        Element::Error,
+        Element::Hint,
+        Element::Added,
+        Element::Deleted,
+    ];
+
+    /// Get the key that sets the element's color under `[theme]`
+    pub fn name(self) -> &'static str {
+        match self {
+            Element::Heading => "heading",
+            Element::Number => "number",
+            Element::Warning => "warning",
+            Element::Error => "error",
+            Element::Hint => "hint",
+            Element::Added => "added",
+            Element::Deleted => "deleted",
+        }
+    }
+
+    fn default_style(self) -> Style {
+        match self {
+            Element::Heading | Element::Added => Style::Color(Color::Green),
+            Element::Number => Style::Color(Color::Cyan),
+            Element::Warning => Style::Color(Color::Yellow),
+            Element::Error | Element::Deleted => Style::Color(Color::Red),
+            Element::Hint => Style::Dimmed,
+        }
+    }
+}
+
+/// Key under `[theme]` for options whose type couldn't be parsed
+pub const OTHER_TYPE: &str = "other";
+
+/// How an element is colored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Color(Color),
+    Dimmed,
+    /// Left as the terminal shows it
+    Plain,
+}
+
+impl FromStr for Style {
+    type Err = String;
+
+    fn from_str(style: &str) -> Result<Self, Self::Err> {
+        match style.trim().to_lowercase().as_str() {
+            "none" => Ok(Style::Plain),
+            "dimmed" => Ok(Style::Dimmed),
+            color => Color::from_str(color).map(Style::Color).map_err(|()| {
+                format!("`{style}` is not a color; use a color name, `#rrggbb`, `dimmed` or `none`")
+            }),
+        }
+    }
+}
+
+/// The styles the configured theme overrides, keyed like `[theme]`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Theme {
+    styles: BTreeMap<String, Style>,
+}
+
+impl Theme {
+    /// Build a theme from settings, skipping entries `config lint` reports as invalid
+    pub fn from_settings(settings: &ThemeSettings) -> Self {
+        let styles = settings
+            .colors
+            .iter()
+            .filter(|(key, _)| is_theme_key(key))
+            .filter_map(|(key, style)| Some((key.clone(), style.parse().ok()?)))
+            .collect();
+        Self { styles }
+    }
+
+    /// Get the style of an element
+    pub fn element(&self, element: Element) -> Style {
+        self.styles
+            .get(element.name())
+            .copied()
+            .unwrap_or_else(|| element.default_style())
+    }
+
+    /// Get the style of a commit type, or of options whose type couldn't be parsed
+    pub fn commit_type(&self, commit_type: Option<&CommitType>) -> Style {
+        let key = commit_type.map_or_else(|| OTHER_TYPE.to_string(), |t| t.to_string());
+        self.styles
+            .get(&key)
+            .copied()
+            .unwrap_or_else(|| match commit_type {
+                Some(commit_type) => Style::Color(commit_type.color()),
+                None => Style::Dimmed,
+            })
+    }
+}
+
+/// Check whether a key names a commit type or an element
+pub fn is_theme_key(key: &str) -> bool {
+    key == OTHER_TYPE
+        || key.parse::<CommitType>().is_ok()
+        || Element::ALL.iter().any(|element| element.name() == key)
+}
+
+/// Check the `[theme]` section for unknown keys and invalid colors
+pub fn lint_theme(settings: &ThemeSettings) -> Vec<(String, String)> {
+    let mut problems = Vec::new();
+    for (key, style) in &settings.colors {
+        let path = format!("theme.{key}");
+        if !is_theme_key(key) {
+            problems.push((path, format!("`{key}` is not a commit type or element")));
+        } else if let Err(e) = style.parse::<Style>() {
+            problems.push((path, e));
+        }
+    }
+    problems
+}
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// Set the theme for the rest of the process; only the first call has any effect
+pub fn set_theme(theme: Theme) {
+    let _ = THEME.set(theme);
+}
+
+fn theme() -> &'static Theme {
+    THEME.get_or_init(Theme::default)
+}
+
+/// Color text as an element of the current theme
+pub fn paint(text: &str, element: Element) -> ColoredString {
+    apply(text, theme().element(element))
+}
+
+/// Color text in its commit type's color in the current theme
+pub fn paint_type(text: &str, commit_type: Option<&CommitType>) -> ColoredString {
+    apply(text, theme().commit_type(commit_type))
+}
+
+fn apply(text: &str, style: Style) -> ColoredString {
+    match style {
+        Style::Color(color) => text.color(color),
+        Style::Dimmed => text.dimmed(),
+        Style::Plain => text.normal(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_from_settings() {
+        let settings = ThemeSettings {
+            colors: BTreeMap::from([
+                ("feat".to_string(), "bright blue".to_string()),
+                ("other".to_string(), "none".to_string()),
+                ("number".to_string(), "#ff8800".to_string()),
+                ("hint".to_string(), "sparkly".to_string()),
+                ("banner".to_string(), "red".to_string()),
+            ]),
+        };
+        let theme = Theme::from_settings(&settings);
+
+        assert_eq!(
+            theme.commit_type(Some(&CommitType::Feat)),
+            Style::Color(Color::BrightBlue)
+        );
+        assert_eq!(
+            theme.commit_type(Some(&CommitType::Fix)),
+            Style::Color(Color::Red)
+        );
+        assert_eq!(theme.commit_type(None), Style::Plain);
+        assert_eq!(
+            theme.element(Element::Number),
+            Style::Color(Color::TrueColor {
+                r: 0xff,
+                g: 0x88,
+                b: 0x00
+            })
+        );
+        // Invalid entries fall back to the default
+        assert_eq!(theme.element(Element::Hint), Style::Dimmed);
+
+        let problems = lint_theme(&settings);
+        assert_eq!(problems.len(), 2);
+        assert_eq!(problems[0].0, "theme.banner");
+        assert!(problems[1].1.contains("`sparkly` is not a color"));
+    }
+}
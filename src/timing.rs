@@ -0,0 +1,184 @@
+//! Per-phase timing collected from tracing spans, for `--timing`
+//!
+//! Each phase of a run (diffing, sanitizing, building the prompt, calling the
+//! provider, validating and ranking) runs inside a span named after it. The layer
+//! here records how long each span was open, so the report shows wall-clock time
+//! including any time spent waiting on the network.
+
+use crate::report::format_decimal;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::span::{Attributes, Id};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Span for reading the diff from git
+pub const GIT_DIFF: &str = "git_diff";
+/// Span for trimming and filtering the diff before it goes into a prompt
+pub const SANITIZE: &str = "sanitize";
+/// Span for building the prompt, including sanitizing and gathering context
+pub const PROMPT_BUILD: &str = "prompt_build";
+/// Span for one request to the provider
+pub const PROVIDER_CALL: &str = "provider_call";
+/// Span for checking a generated message against the rules
+pub const VALIDATION: &str = "validation";
+/// Span for ranking the accepted messages
+pub const RANKING: &str = "ranking";
+
+/// Phases in the order they run, with the label shown in the report
+const PHASES: &[(&str, &str)] = &[
+    (GIT_DIFF, "git diff"),
+    (SANITIZE, "sanitize"),
+    (PROMPT_BUILD, "prompt build"),
+    (PROVIDER_CALL, "provider call"),
+    (VALIDATION, "validation"),
+    (RANKING, "ranking"),
+];
+
+/// Total time and number of runs of one phase
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PhaseTiming {
+    pub total: Duration,
+    pub count: usize,
+}
+
+/// Timings shared between the layer and whoever prints the report
+#[derive(Debug, Clone, Default)]
+pub struct Timings {
+    phases: Arc<Mutex<BTreeMap<&'static str, PhaseTiming>>>,
+}
+
+impl Timings {
+    /// Create an empty set of timings
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get a layer that records phase spans into these timings
+    pub fn layer(&self) -> TimingLayer {
+        TimingLayer {
+            timings: self.clone(),
+        }
+    }
+
+    /// Add one run of a phase
+    pub fn record(&self, phase: &'static str, elapsed: Duration) {
+        if let Ok(mut phases) = self.phases.lock() {
+            let timing = phases.entry(phase).or_default();
+            timing.total += elapsed;
+            timing.count += 1;
+        }
+    }
+
+    /// Get the timing of a phase, if it ran
+    pub fn phase(&self, phase: &str) -> Option<PhaseTiming> {
+        self.phases.lock().ok()?.get(phase).copied()
+    }
+
+    /// Get the phases that ran, labelled, in the order they run
+    pub fn phases(&self) -> Vec<(&'static str, PhaseTiming)> {
+        PHASES
+            .iter()
+            .filter_map(|(phase, label)| Some((*label, self.phase(phase)?)))
+            .collect()
+    }
+
+    /// Render the phase breakdown, ending with the run's total wall-clock time
+    ///
+    /// The prompt build phase includes sanitizing, so phases don't add up to the total.
+    pub fn report(&self, total: Duration) -> String {
+        let mut report = String::from("Timing:\n");
+        for (label, timing) in self.phases() {
+            let _ = write!(
+                report,
+                "  {label:<14} {:>10}",
+                format_duration(timing.total)
+            );
+            if timing.count > 1 {
+                let _ = write!(report, " ({} runs)", timing.count);
+            }
+            report.push('\n');
+        }
+        let _ = writeln!(report, "  {:<14} {:>10}", "total", format_duration(total));
+        report
+    }
+}
+
+/// Records how long each phase span is open
+pub struct TimingLayer {
+    timings: Timings,
+}
+
+/// When a phase span was opened, kept in the span's extensions
+struct SpanStart(Instant);
+
+impl<S> Layer<S> for TimingLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let is_phase = PHASES
+            .iter()
+            .any(|(phase, _)| *phase == attrs.metadata().name());
+        if let (true, Some(span)) = (is_phase, ctx.span(id)) {
+            span.extensions_mut().insert(SpanStart(Instant::now()));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(start) = span.extensions().get::<SpanStart>().map(|start| start.0) else {
+            return;
+        };
+        let name = span.metadata().name();
+        if let Some((phase, _)) = PHASES.iter().find(|(phase, _)| *phase == name) {
+            self.timings.record(phase, start.elapsed());
+        }
+    }
+}
+
+/// Format a duration in milliseconds with one decimal place
+fn format_duration(duration: Duration) -> String {
+    format!("{} ms", format_decimal(duration.as_secs_f64() * 1000.0, 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_layer_records_phase_spans() {
+        let timings = Timings::new();
+        let subscriber = tracing_subscriber::registry().with(timings.layer());
+
+        tracing::subscriber::with_default(subscriber, || {
+            for _ in 0..2 {
+                let _span = tracing::info_span!(PROVIDER_CALL).entered();
+            }
+            let _span = tracing::info_span!("unrelated").entered();
+        });
+
+        assert_eq!(timings.phase(PROVIDER_CALL).unwrap().count, 2);
+        assert_eq!(timings.phase("unrelated"), None);
+    }
+
+    #[test]
+    fn test_report() {
+        let timings = Timings::new();
+        timings.record(GIT_DIFF, Duration::from_micros(12_340));
+        timings.record(PROVIDER_CALL, Duration::from_millis(900));
+        timings.record(PROVIDER_CALL, Duration::from_millis(1_100));
+
+        let report = timings.report(Duration::from_millis(2_050));
+        assert_eq!(
+            report,
+            "Timing:\n  git diff          12.3 ms\n  provider call  2,000.0 ms (2 runs)\n  total          2,050.0 ms\n"
+        );
+    }
+}
@@ -0,0 +1,498 @@
+//! Trailers appended to every committed message
+
+use crate::commit::parse_commit_message;
+use crate::forge::{issue_from_branch, Forge};
+use crate::git::git_output;
+use crate::types::CommittorError;
+use anyhow::Result;
+use git2::{ObjectType, Oid};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Trailer behaviour read from the `[trailers]` settings section
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrailerSettings {
+    /// Static trailers such as `Reviewed-by-policy: ai-assisted`
+    pub add: Vec<String>,
+    /// Add a Gerrit-compatible `Change-Id:` trailer
+    pub change_id: bool,
+    /// Trailer keys every message must carry, such as `Signed-off-by` for DCO projects
+    pub required: Vec<String>,
+    /// Close the issue numbered in the branch name, e.g. `Fixes #123` on `fix/123-crash`
+    pub close_issues: bool,
+    /// Forge whose closing keywords are used; detected from the `origin` remote when unset
+    pub forge: Option<Forge>,
+}
+
+/// Key of the Developer Certificate of Origin sign-off trailer
+pub const SIGN_OFF_KEY: &str = "Signed-off-by";
+
+/// Apply trailer settings to a message, keeping any Change-Id it already carries
+///
+/// An amended commit keeps its message, so reusing the existing Change-Id keeps
+/// the amended commit attached to the same Gerrit change.
+pub fn apply_trailer_settings(message: &str, settings: &TrailerSettings) -> Result<String> {
+    let mut message = message.to_string();
+    if settings.close_issues {
+        if let Some(reference) = closing_reference(&message, settings.forge) {
+            message = insert_footer_line(&message, &reference);
+        }
+    }
+    let mut message = append_trailers(&message, &settings.add);
+
+    if settings.change_id && change_id_enabled() && find_trailer(&message, "Change-Id").is_none() {
+        let change_id = generate_change_id(&message)?;
+        message = append_trailers(&message, &[format!("Change-Id: {change_id}")]);
+    }
+
+    // A sign-off is the only required trailer that can be filled in for the user
+    let needs_sign_off = settings
+        .required
+        .iter()
+        .any(|key| key.eq_ignore_ascii_case(SIGN_OFF_KEY));
+    if needs_sign_off && find_trailer(&message, SIGN_OFF_KEY).is_none() {
+        message = append_trailers(&message, &[sign_off_trailer()?]);
+    }
+
+    Ok(message)
+}
+
+/// Get the required trailer keys the message doesn't carry
+pub fn missing_trailers(message: &str, required: &[String]) -> Vec<String> {
+    required
+        .iter()
+        .filter(|key| find_trailer(message, key).is_none())
+        .cloned()
+        .collect()
+}
+
+/// Fail when the message is missing any required trailer, naming the missing keys
+pub fn ensure_required_trailers(message: &str, required: &[String]) -> Result<()> {
+    let missing = missing_trailers(message, required);
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    Err(CommittorError::MissingTrailers(missing.join(", ")).into())
+}
+
+/// Build the line closing the issue numbered in the current branch's name
+///
+/// `None` outside a branch, when the name has no issue number, or when the message
+/// already mentions the issue. Unrecognised forges get `Closes`, which all accept.
+fn closing_reference(message: &str, forge: Option<Forge>) -> Option<String> {
+    let branch = git_output(Path::new("."), &["symbolic-ref", "--short", "-q", "HEAD"]).ok()?;
+    let issue = issue_from_branch(&branch)?;
+    let mentioned = regex::Regex::new(&format!(r"#{issue}\b")).unwrap();
+    if mentioned.is_match(message) {
+        return None;
+    }
+
+    let forge = forge
+        .or_else(|| {
+            let url = git_output(Path::new("."), &["remote", "get-url", "origin"]).ok()?;
+            Forge::from_remote_url(&url)
+        })
+        .unwrap_or(Forge::GitHub);
+    let subject = message.lines().next().unwrap_or_default();
+    let commit_type = parse_commit_message(subject).ok().map(|c| c.commit_type);
+    Some(forge.closing_reference(issue, commit_type.as_ref()))
+}
+
+/// Add a line to the footer as its own paragraph, above any trailer block
+///
+/// Lines like `Closes #123` aren't `Key: value` trailers, so they stay out of the
+/// block to keep it readable by `git interpret-trailers`.
+pub fn insert_footer_line(message: &str, line: &str) -> String {
+    let message = message.trim_end();
+    match message.rsplit_once("\n\n") {
+        Some((above, block)) if !trailer_block(message).is_empty() => {
+            format!("{above}\n\n{line}\n\n{block}")
+        }
+        _ => format!("{message}\n\n{line}"),
+    }
+}
+
+/// Build a `Signed-off-by` trailer from the committer identity, as `git commit -s` does
+fn sign_off_trailer() -> Result<String> {
+    let ident = git_output(Path::new("."), &["var", "GIT_COMMITTER_IDENT"])?;
+    Ok(format!("{SIGN_OFF_KEY}: {}", strip_ident_date(&ident)))
+}
+
+/// Drop the timestamp and timezone git appends to an identity
+fn strip_ident_date(ident: &str) -> &str {
+    match ident.rfind('>') {
+        Some(end) => &ident[..=end],
+        None => ident.trim(),
+    }
+}
+
+/// Characters git picks the comment character from under `core.commentChar=auto`
+const AUTO_COMMENT_CHARS: &str = "#;@!$%^&|:";
+
+/// What git does to a message file before committing it, from `commit.cleanup`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CleanupMode {
+    /// Drop comment lines and surrounding blank lines, git's default
+    #[default]
+    Strip,
+    /// Keep comment lines, trimming surrounding blank lines only
+    Whitespace,
+    /// Keep the message as it is written
+    Verbatim,
+    /// Like `whitespace`, dropping everything below the scissors line
+    Scissors,
+}
+
+/// How git reads comments in a message file: its comment character and cleanup mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageCleanup {
+    /// First character of a comment line, `#` unless `core.commentChar` says otherwise
+    pub comment_char: char,
+    pub mode: CleanupMode,
+}
+
+impl Default for MessageCleanup {
+    fn default() -> Self {
+        Self {
+            comment_char: '#',
+            mode: CleanupMode::Strip,
+        }
+    }
+}
+
+impl MessageCleanup {
+    /// Read `core.commentChar` and `commit.cleanup` for a message file in the current repository
+    pub fn from_git(content: &str) -> Self {
+        let config = |key: &str| git_output(Path::new("."), &["config", key]).ok();
+        Self::from_config(
+            config("core.commentChar").as_deref(),
+            config("commit.cleanup").as_deref(),
+            content,
+        )
+    }
+
+    /// Work out the cleanup from configured values, `auto` reading the file itself
+    pub fn from_config(comment_char: Option<&str>, cleanup: Option<&str>, content: &str) -> Self {
+        let comment_char = match comment_char.map(str::trim) {
+            Some(value) if value.eq_ignore_ascii_case("auto") => auto_comment_char(content),
+            Some(value) => value.chars().next().unwrap_or('#'),
+            None => '#',
+        };
+        let mode = match cleanup.map(str::trim) {
+            Some("whitespace") => CleanupMode::Whitespace,
+            Some("verbatim") => CleanupMode::Verbatim,
+            Some("scissors") => CleanupMode::Scissors,
+            _ => CleanupMode::Strip,
+        };
+        Self { comment_char, mode }
+    }
+
+    /// Check whether git drops comment lines from the message
+    pub fn strips_comments(&self) -> bool {
+        self.mode == CleanupMode::Strip
+    }
+
+    /// Check whether a line starts with the comment character
+    pub fn is_comment(&self, line: &str) -> bool {
+        line.starts_with(self.comment_char)
+    }
+
+    /// Check whether a line is the scissors line `git commit -v` puts above the diff
+    pub fn is_scissors(&self, line: &str) -> bool {
+        is_scissors_line(line, self.comment_char)
+    }
+
+    /// Write text as a comment line
+    pub fn comment(&self, text: &str) -> String {
+        if text.is_empty() {
+            self.comment_char.to_string()
+        } else {
+            format!("{} {text}", self.comment_char)
+        }
+    }
+}
+
+fn is_scissors_line(line: &str, comment_char: char) -> bool {
+    line.strip_prefix(comment_char)
+        .is_some_and(|rest| rest.starts_with(" ------------------------ >8"))
+}
+
+/// Find the character git chose under `core.commentChar=auto`
+///
+/// Git takes the first candidate that starts no line of the message, then writes
+/// its own comments with it below the message, so a closing block of lines like
+/// `; text` gives the choice away. Without one, git's rule is applied to the file.
+fn auto_comment_char(content: &str) -> char {
+    let lines: Vec<&str> = content.lines().collect();
+    let candidates = || AUTO_COMMENT_CHARS.chars();
+    if let Some(c) = candidates().find(|&c| lines.iter().any(|line| is_scissors_line(line, c))) {
+        return c;
+    }
+
+    let looks_like_comment = |line: &str, c: char| {
+        line.strip_prefix(c)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with([' ', '\t']))
+    };
+    let last = lines.iter().rev().find(|line| !line.trim().is_empty());
+    candidates()
+        .find(|&c| last.is_some_and(|line| looks_like_comment(line, c)))
+        .or_else(|| candidates().find(|&c| !lines.iter().any(|line| line.starts_with(c))))
+        .unwrap_or('#')
+}
+
+/// Prepare a message file written by git for the commit-msg hook
+///
+/// Anything below the verbose scissors line is dropped, and comment lines too when
+/// git strips them, so the trailers land at the end of the message git will keep.
+/// Returns `None` when the message is empty, leaving git to abort the commit as usual.
+pub fn clean_message_file(content: &str, cleanup: &MessageCleanup) -> Option<String> {
+    let message = content
+        .lines()
+        .take_while(|line| !cleanup.is_scissors(line))
+        .filter(|line| !(cleanup.strips_comments() && cleanup.is_comment(line)))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let message = match cleanup.mode {
+        CleanupMode::Verbatim => message.trim_end(),
+        _ => message.trim(),
+    };
+    (!message.is_empty()).then(|| message.to_string())
+}
+
+/// Check Gerrit's `gerrit.createChangeId` switch, which defaults to on
+fn change_id_enabled() -> bool {
+    git_output(
+        Path::new("."),
+        &["config", "--bool", "gerrit.createChangeId"],
+    )
+    .map(|value| value != "false")
+    .unwrap_or(true)
+}
+
+/// Check whether a line looks like a `Key: value` trailer
+///
+/// The `(cherry picked from commit …)` line of `git cherry-pick -x` counts too, as
+/// it does for git.
+pub fn is_trailer_line(line: &str) -> bool {
+    let regex = regex::Regex::new(r"^[A-Za-z0-9][A-Za-z0-9-]*: \S").unwrap();
+    regex.is_match(line) || line.starts_with("(cherry picked from commit ")
+}
+
+/// Get the value of the first trailer with the given key
+pub fn find_trailer(message: &str, key: &str) -> Option<String> {
+    trailer_block(message).iter().find_map(|line| {
+        let (line_key, value) = line.split_once(": ")?;
+        line_key
+            .eq_ignore_ascii_case(key)
+            .then(|| value.trim().to_string())
+    })
+}
+
+/// Get the trailers at the end of a message
+pub fn message_trailers(message: &str) -> Vec<String> {
+    trailer_block(message)
+        .into_iter()
+        .map(str::to_string)
+        .collect()
+}
+
+/// Append trailers to a message, joining an existing trailer block and skipping duplicates
+pub fn append_trailers(message: &str, trailers: &[String]) -> String {
+    let message = message.trim_end();
+    let existing = trailer_block(message);
+    let new: Vec<&String> = trailers
+        .iter()
+        .filter(|trailer| !existing.contains(&trailer.trim()))
+        .collect();
+
+    if new.is_empty() {
+        return message.to_string();
+    }
+
+    let separator = if existing.is_empty() { "\n\n" } else { "\n" };
+    let new = new
+        .iter()
+        .map(|trailer| trailer.trim())
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!("{message}{separator}{new}")
+}
+
+/// Get the lines of the message's trailing trailer block, if it has one
+fn trailer_block(message: &str) -> Vec<&str> {
+    let message = message.trim_end();
+    let Some((_, last_paragraph)) = message.rsplit_once("\n\n") else {
+        // A subject line on its own is never a trailer block
+        return Vec::new();
+    };
+
+    let lines: Vec<&str> = last_paragraph.lines().collect();
+    if lines.iter().all(|line| is_trailer_line(line)) {
+        lines
+    } else {
+        Vec::new()
+    }
+}
+
+/// Generate a `Change-Id` the way Gerrit's commit-msg hook does
+///
+/// The id hashes the tree, parent, identities and message as a commit object would,
+/// so it is unique to this change but needs no extra state.
+pub fn generate_change_id(message: &str) -> Result<String> {
+    let tree = git_output(Path::new("."), &["write-tree"])?;
+    let parent = git_output(Path::new("."), &["rev-parse", "--verify", "-q", "HEAD^0"]).ok();
+    let author = git_output(Path::new("."), &["var", "GIT_AUTHOR_IDENT"])?;
+    let committer = git_output(Path::new("."), &["var", "GIT_COMMITTER_IDENT"])?;
+
+    let mut content = format!("tree {tree}\n");
+    if let Some(parent) = parent {
+        content.push_str(&format!("parent {parent}\n"));
+    }
+    content.push_str(&format!(
+        "author {author}\ncommitter {committer}\n\n{message}"
+    ));
+
+    let id = Oid::hash_object(ObjectType::Commit, content.as_bytes())?;
+    Ok(format!("I{id}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_trailers() {
+        let trailers = vec!["Reviewed-by-policy: ai-assisted".to_string()];
+
+        assert_eq!(
+            append_trailers("feat: add login", &trailers),
+            "feat: add login\n\nReviewed-by-policy: ai-assisted"
+        );
+        assert_eq!(
+            append_trailers("feat: add login\n\nRefs: ABC-1\n", &trailers),
+            "feat: add login\n\nRefs: ABC-1\nReviewed-by-policy: ai-assisted"
+        );
+        assert_eq!(
+            append_trailers(
+                "feat: add login\n\nReviewed-by-policy: ai-assisted",
+                &trailers
+            ),
+            "feat: add login\n\nReviewed-by-policy: ai-assisted"
+        );
+        assert_eq!(
+            append_trailers("feat: add login\n\nA body: with a colon. And more text", &trailers),
+            "feat: add login\n\nA body: with a colon. And more text\n\nReviewed-by-policy: ai-assisted"
+        );
+    }
+
+    #[test]
+    fn test_clean_message_file() {
+        let content = "feat: add login\n\n# Please enter the commit message\n# ------------------------ >8 ------------------------\ndiff --git a/x b/x\n";
+        let cleanup = MessageCleanup::default();
+        assert_eq!(
+            clean_message_file(content, &cleanup).as_deref(),
+            Some("feat: add login")
+        );
+        assert_eq!(clean_message_file("# only comments\n\n", &cleanup), None);
+
+        // Another comment character keeps lines starting with `#`
+        let content = "fix: follow up\n\n#123 follow-up\n; Please enter the commit message\n; ------------------------ >8 ------------------------\n; diff\n";
+        let cleanup = MessageCleanup::from_config(Some(";"), None, content);
+        assert_eq!(
+            clean_message_file(content, &cleanup).as_deref(),
+            Some("fix: follow up\n\n#123 follow-up")
+        );
+
+        // `whitespace` and `verbatim` keep comments, only the diff is cut
+        let content = "fix: follow up\n\n# kept\n";
+        for mode in ["whitespace", "verbatim"] {
+            let cleanup = MessageCleanup::from_config(None, Some(mode), content);
+            assert_eq!(
+                clean_message_file(content, &cleanup).as_deref(),
+                Some("fix: follow up\n\n# kept")
+            );
+        }
+    }
+
+    #[test]
+    fn test_auto_comment_char() {
+        let auto = |content: &str| MessageCleanup::from_config(Some("auto"), None, content);
+        // Git skips `#` when the message uses it and comments with the next candidate
+        let content = "fix: follow up\n\n#123 follow-up\n\n; Please enter the commit message\n;\n";
+        assert_eq!(auto(content).comment_char, ';');
+        assert_eq!(auto("feat: add login\n").comment_char, '#');
+        assert_eq!(auto("#1 first\n;2 second\n").comment_char, '@');
+        assert_eq!(
+            auto("fix: x\n@ ------------------------ >8 ------------------------\n# diff\n")
+                .comment_char,
+            '@'
+        );
+    }
+
+    #[test]
+    fn test_apply_trailer_settings_keeps_change_id() {
+        let settings = TrailerSettings {
+            change_id: true,
+            ..Default::default()
+        };
+        let message = "feat: add login\n\nChange-Id: I0123456789abcdef0123456789abcdef01234567";
+        assert_eq!(apply_trailer_settings(message, &settings).unwrap(), message);
+    }
+
+    #[test]
+    fn test_insert_footer_line() {
+        assert_eq!(
+            insert_footer_line("fix: handle empty input\n", "Fixes #12"),
+            "fix: handle empty input\n\nFixes #12"
+        );
+        assert_eq!(
+            insert_footer_line(
+                "fix: handle empty input\n\nBody text\n\nRefs: ABC-1",
+                "Fixes #12"
+            ),
+            "fix: handle empty input\n\nBody text\n\nFixes #12\n\nRefs: ABC-1"
+        );
+        assert_eq!(
+            find_trailer(
+                &insert_footer_line("fix: x\n\nRefs: ABC-1", "Fixes #12"),
+                "Refs"
+            )
+            .as_deref(),
+            Some("ABC-1")
+        );
+    }
+
+    #[test]
+    fn test_find_trailer() {
+        let message = "feat: add login\n\nBody text\n\nChange-Id: I123\nRefs: ABC-1";
+        assert_eq!(find_trailer(message, "change-id").as_deref(), Some("I123"));
+        assert_eq!(find_trailer(message, "Signed-off-by"), None);
+        assert_eq!(find_trailer("Change-Id: I123", "Change-Id"), None);
+        let picked = "fix: x\n\nRefs: ABC-1\n(cherry picked from commit 0123abcd)";
+        assert_eq!(find_trailer(picked, "Refs").as_deref(), Some("ABC-1"));
+    }
+
+    #[test]
+    fn test_missing_trailers() {
+        let required = vec![SIGN_OFF_KEY.to_string(), "CLA-Signed".to_string()];
+        let message = "feat: add login\n\nsigned-off-by: Test User <test@example.com>";
+
+        assert_eq!(missing_trailers(message, &required), vec!["CLA-Signed"]);
+        assert!(missing_trailers(message, &required[..1]).is_empty());
+        let error = ensure_required_trailers("feat: add login", &required)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Signed-off-by, CLA-Signed"));
+    }
+
+    #[test]
+    fn test_strip_ident_date() {
+        assert_eq!(
+            strip_ident_date("Test User <test@example.com> 1760531696 +0000"),
+            "Test User <test@example.com>"
+        );
+    }
+}
@@ -1,10 +1,13 @@
 //! Common types and data structures used throughout the committor application
 
+use crate::i18n::Text;
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::str::FromStr;
 
 /// Represents a conventional commit type
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum CommitType {
     Feat,
     Fix,
@@ -36,6 +39,28 @@ impl fmt::Display for CommitType {
     }
 }
 
+impl FromStr for CommitType {
+    type Err = CommittorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "feat" => Ok(CommitType::Feat),
+            "fix" => Ok(CommitType::Fix),
+            "docs" => Ok(CommitType::Docs),
+            "style" => Ok(CommitType::Style),
+            "refactor" => Ok(CommitType::Refactor),
+            "test" => Ok(CommitType::Test),
+            "chore" => Ok(CommitType::Chore),
+            "perf" => Ok(CommitType::Perf),
+            "ci" => Ok(CommitType::Ci),
+            "build" => Ok(CommitType::Build),
+            _ => Err(CommittorError::InvalidCommitFormat(format!(
+                "Unknown commit type: {s}"
+            ))),
+        }
+    }
+}
+
 impl CommitType {
     /// Get all available commit types
     pub fn all() -> Vec<CommitType> {
@@ -68,10 +93,28 @@ impl CommitType {
             CommitType::Build => "Changes that affect the build system or external dependencies",
         }
     }
+
+    /// Get the color the commit type is shown in when the `[theme]` section doesn't set one
+    pub fn color(&self) -> colored::Color {
+        use colored::Color;
+        match self {
+            CommitType::Feat => Color::Green,
+            CommitType::Fix => Color::Red,
+            CommitType::Docs => Color::Blue,
+            CommitType::Style => Color::Magenta,
+            CommitType::Refactor => Color::Cyan,
+            CommitType::Test => Color::Yellow,
+            CommitType::Chore => Color::White,
+            CommitType::Perf => Color::BrightGreen,
+            CommitType::Ci => Color::BrightBlue,
+            CommitType::Build => Color::BrightMagenta,
+        }
+    }
 }
 
 /// Represents a conventional commit message
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct ConventionalCommit {
     pub commit_type: CommitType,
     pub scope: Option<String>,
@@ -124,6 +167,7 @@ impl fmt::Display for ConventionalCommit {
 
 /// Represents a git diff change
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct DiffChange {
     pub file_path: String,
     pub change_type: DiffChangeType,
@@ -131,8 +175,19 @@ pub struct DiffChange {
     pub deletions: usize,
 }
 
+/// A single unit of a staged diff that can be committed on its own
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffHunk {
+    pub file_path: String,
+    /// The `diff --git` header lines for the file
+    pub file_header: String,
+    /// The `@@` hunk text, or `None` for changes without hunks (binary, mode-only, empty files)
+    pub text: Option<String>,
+}
+
 /// Type of change in a git diff
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum DiffChangeType {
     Added,
     Modified,
@@ -154,32 +209,112 @@ impl fmt::Display for DiffChangeType {
     }
 }
 
+/// Where HEAD currently points
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HeadState {
+    /// HEAD is attached to a named branch
+    Branch(String),
+    /// HEAD is detached at the given short commit SHA
+    Detached(String),
+}
+
+impl HeadState {
+    /// Get the branch name, if HEAD is attached to one
+    pub fn branch(&self) -> Option<&str> {
+        match self {
+            HeadState::Branch(name) => Some(name),
+            HeadState::Detached(_) => None,
+        }
+    }
+
+    /// Check whether HEAD is detached
+    pub fn is_detached(&self) -> bool {
+        matches!(self, HeadState::Detached(_))
+    }
+}
+
+impl fmt::Display for HeadState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeadState::Branch(name) => write!(f, "{name}"),
+            HeadState::Detached(sha) => write!(f, "detached HEAD at {sha}"),
+        }
+    }
+}
+
 /// Represents the result of generating commit messages
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct GenerationResult {
+    /// See [`crate::schema`]
+    #[serde(
+        default = "crate::schema::current_version",
+        deserialize_with = "crate::schema::deserialize_version"
+    )]
+    pub schema_version: u32,
     pub messages: Vec<String>,
     pub model_used: String,
     pub generation_time: std::time::Duration,
 }
 
+impl GenerationResult {
+    /// Create a result in the current schema version
+    pub fn new(
+        messages: Vec<String>,
+        model_used: String,
+        generation_time: std::time::Duration,
+    ) -> Self {
+        Self {
+            schema_version: crate::schema::SCHEMA_VERSION,
+            messages,
+            model_used,
+            generation_time,
+        }
+    }
+}
+
 /// Error types specific to committor
 #[derive(Debug, thiserror::Error)]
 pub enum CommittorError {
-    #[error("Git repository not found")]
+    #[error("{}", Text::ErrGitRepoNotFound.text())]
     GitRepoNotFound,
 
-    #[error("No staged changes found")]
+    #[error("{}", Text::ErrNoStagedChanges.text())]
     NoStagedChanges,
 
-    #[error("AI Provider error: {0}")]
+    #[error("{}", Text::ErrAIProvider.format(&[.0]))]
     AIProviderError(String),
 
-    #[error("Git operation failed: {0}")]
+    #[error("{}", Text::ErrOllamaUnavailable.format(&[.0]))]
+    OllamaUnavailable(String),
+
+    #[error("{}", Text::ErrGit.format(&[.0]))]
     GitError(String),
 
-    #[error("Invalid commit message format: {0}")]
+    #[error("{}", Text::ErrInvalidCommitFormat.format(&[.0]))]
     InvalidCommitFormat(String),
 
-    #[error("Configuration error: {0}")]
+    #[error("{}", Text::ErrModelRefusal.format(&[.0]))]
+    ModelRefusal(String),
+
+    #[error("{}", Text::ErrConfig.format(&[.0]))]
     ConfigError(String),
+
+    #[error("{}", Text::ErrSecretDetected.format(&[.0]))]
+    SecretDetected(String),
+
+    #[error("{}", Text::ErrMissingTrailers.format(&[.0]))]
+    MissingTrailers(String),
+
+    #[error("{}", Text::ErrTimeout.format(&[.0]))]
+    Timeout(String),
+
+    #[error("{}", Text::ErrRateLimited.format(&[.0]))]
+    RateLimited(String),
+
+    #[error("{}", Text::ErrContextTooLarge.format(&[.0]))]
+    ContextTooLarge(String),
+
+    #[error("{}", Text::ErrValidationFailed.format(&[.0]))]
+    ValidationFailed(String),
 }
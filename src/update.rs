@@ -0,0 +1,151 @@
+//! Once-a-day check for a newer committor release
+
+use crate::paths::cache_file;
+use crate::state::write_atomic;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Endpoint describing the latest published release
+pub const LATEST_RELEASE_URL: &str =
+    "https://api.github.com/repos/simonhdickson/committor/releases/latest";
+
+/// Name of the file recording when the last check ran, kept in the user cache directory
+pub const UPDATE_CHECK_FILE_NAME: &str = "update-check.json";
+
+/// How long a check result is reused before asking again
+pub const UPDATE_CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long to wait for the release endpoint; the check must never hold up a command
+const UPDATE_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The outcome of the last update check
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpdateCheck {
+    /// When the check ran, in seconds since the Unix epoch
+    pub checked_at: u64,
+    /// Latest released version, if the check succeeded
+    pub latest: Option<String>,
+}
+
+impl UpdateCheck {
+    /// Check whether this result is recent enough to reuse at `now`
+    pub fn is_fresh(&self, now: u64) -> bool {
+        now.saturating_sub(self.checked_at) < UPDATE_CHECK_INTERVAL.as_secs()
+    }
+}
+
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+}
+
+/// Get the newer release version if one exists, asking the release endpoint at most once a day
+///
+/// Failures are treated as "no update": a network problem is not worth a warning here.
+pub async fn check_for_update(http: &reqwest::Client, current: &str) -> Option<String> {
+    let path = cache_file(UPDATE_CHECK_FILE_NAME)?;
+    let now = now();
+
+    let check = match load_update_check(&path) {
+        Some(check) if check.is_fresh(now) => check,
+        _ => {
+            let check = UpdateCheck {
+                checked_at: now,
+                latest: fetch_latest_version(http).await.ok(),
+            };
+            // Record failed checks too, so an offline machine isn't retried on every run
+            let _ = save_update_check(&path, &check);
+            check
+        }
+    };
+
+    check
+        .latest
+        .filter(|latest| is_newer_version(latest, current))
+}
+
+/// Ask the release endpoint for the latest version
+pub async fn fetch_latest_version(http: &reqwest::Client) -> Result<String> {
+    let release: Release = http
+        .get(LATEST_RELEASE_URL)
+        .header(reqwest::header::USER_AGENT, "committor")
+        .timeout(UPDATE_CHECK_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(release.tag_name.trim_start_matches('v').to_string())
+}
+
+/// Check whether `latest` is a higher `major.minor.patch` version than `current`
+pub fn is_newer_version(latest: &str, current: &str) -> bool {
+    match (parse_version(latest), parse_version(current)) {
+        (Some(latest), Some(current)) => latest > current,
+        _ => false,
+    }
+}
+
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    // Pre-release and build suffixes are ignored
+    let version = version.trim().trim_start_matches('v');
+    let core = version.split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|part| part.parse::<u64>().ok());
+    let major = parts.next()??;
+    let minor = parts.next().unwrap_or(Some(0))?;
+    let patch = parts.next().unwrap_or(Some(0))?;
+    Some((major, minor, patch))
+}
+
+fn load_update_check(path: &Path) -> Option<UpdateCheck> {
+    let content = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn save_update_check(path: &Path, check: &UpdateCheck) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    write_atomic(path, serde_json::to_string(check)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_newer_version() {
+        assert!(is_newer_version("0.2.0", "0.1.0"));
+        assert!(is_newer_version("v1.0", "0.9.9"));
+        assert!(is_newer_version("0.1.10", "0.1.9"));
+        assert!(!is_newer_version("0.1.0", "0.1.0"));
+        assert!(!is_newer_version("0.1.0-rc.1", "0.1.0"));
+        assert!(!is_newer_version("nightly", "0.1.0"));
+    }
+
+    #[test]
+    fn test_update_check_freshness_and_round_trip() {
+        let check = UpdateCheck {
+            checked_at: 1_000,
+            latest: Some("0.2.0".to_string()),
+        };
+        assert!(check.is_fresh(1_000 + UPDATE_CHECK_INTERVAL.as_secs() - 1));
+        assert!(!check.is_fresh(1_000 + UPDATE_CHECK_INTERVAL.as_secs()));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join(UPDATE_CHECK_FILE_NAME);
+        save_update_check(&path, &check).unwrap();
+        assert_eq!(load_update_check(&path), Some(check));
+    }
+}
@@ -0,0 +1,226 @@
+//! Parsing and checking of conventional commit messages as plain strings
+//!
+//! Nothing here touches git, the filesystem or a provider, and only `core` and
+//! `alloc` are used, so the module builds on its own with `default-features =
+//! false`. The command-line tool's rules parse subjects with [`parse_header`] and
+//! measure them with [`subject_length`], so a commitlint-style tool or a bot built on
+//! this module accepts exactly the subjects committor does.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+/// The commit types a conventional subject can start with
+pub const TYPES: [&str; 10] = [
+    "feat", "fix", "docs", "style", "refactor", "test", "chore", "perf", "ci", "build",
+];
+
+/// Longest subject, in characters, [`check_message`] accepts by default
+pub const MAX_SUBJECT_LENGTH: usize = 72;
+
+/// The parts of a `<type>(<scope>)!: <description>` subject
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header<'a> {
+    pub commit_type: &'a str,
+    pub scope: Option<&'a str>,
+    pub breaking: bool,
+    pub description: &'a str,
+}
+
+impl fmt::Display for Header<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.commit_type)?;
+        if let Some(scope) = self.scope {
+            write!(f, "({scope})")?;
+        }
+        if self.breaking {
+            write!(f, "!")?;
+        }
+        write!(f, ": {}", self.description)
+    }
+}
+
+/// Why a subject isn't a conventional commit header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    /// The subject doesn't start with one of [`TYPES`]
+    UnknownType,
+    /// The scope is empty or its parenthesis isn't closed
+    InvalidScope,
+    /// The type, scope or `!` isn't followed by `: `
+    MissingSeparator,
+    /// Nothing follows the `: `
+    EmptyDescription,
+    /// The header runs over more than one line
+    LineBreak,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            ParseError::UnknownType => "the subject doesn't start with a known commit type",
+            ParseError::InvalidScope => "the scope is empty or not closed",
+            ParseError::MissingSeparator => "the type isn't followed by ': '",
+            ParseError::EmptyDescription => "the description is empty",
+            ParseError::LineBreak => "the header runs over more than one line",
+        };
+        f.write_str(reason)
+    }
+}
+
+/// Parse a `<type>(<scope>)!: <description>` header
+///
+/// The scope and `!` are optional. The header must be a single line; pass
+/// [`subject`] of a message to parse a message with a body.
+pub fn parse_header(header: &str) -> Result<Header<'_>, ParseError> {
+    let type_end = header
+        .find(['(', '!', ':'])
+        .ok_or(ParseError::MissingSeparator)?;
+    let commit_type = &header[..type_end];
+    if !TYPES.contains(&commit_type) {
+        return Err(ParseError::UnknownType);
+    }
+
+    let mut rest = &header[type_end..];
+    let mut scope = None;
+    if let Some(scoped) = rest.strip_prefix('(') {
+        let scope_end = scoped.find(')').ok_or(ParseError::InvalidScope)?;
+        if scope_end == 0 {
+            return Err(ParseError::InvalidScope);
+        }
+        scope = Some(&scoped[..scope_end]);
+        rest = &scoped[scope_end + 1..];
+    }
+    let breaking = rest.starts_with('!');
+    if breaking {
+        rest = &rest[1..];
+    }
+
+    let description = rest
+        .strip_prefix(": ")
+        .ok_or(ParseError::MissingSeparator)?;
+    if description.is_empty() {
+        return Err(ParseError::EmptyDescription);
+    }
+    if description.contains('\n') {
+        return Err(ParseError::LineBreak);
+    }
+
+    Ok(Header {
+        commit_type,
+        scope,
+        breaking,
+        description,
+    })
+}
+
+/// Get the first line of a message
+pub fn subject(message: &str) -> &str {
+    message.lines().next().unwrap_or_default()
+}
+
+/// Count the characters of a message's subject
+pub fn subject_length(message: &str) -> usize {
+    subject(message).chars().count()
+}
+
+/// Check that a body, if there is one, is separated from the subject by a blank line
+pub fn body_separated(message: &str) -> bool {
+    message
+        .lines()
+        .nth(1)
+        .is_none_or(|second_line| second_line.trim().is_empty())
+}
+
+/// A way a message breaks the conventional commit format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Problem {
+    /// The subject isn't a conventional header
+    Header(ParseError),
+    /// The subject is longer than allowed
+    SubjectTooLong { length: usize, max: usize },
+    /// The body starts on the line after the subject
+    MissingBlankLine,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Header(error) => write!(f, "Invalid conventional commit header: {error}"),
+            Problem::SubjectTooLong { length, max } => {
+                write!(f, "Subject is {length} characters, maximum is {max}")
+            }
+            Problem::MissingBlankLine => {
+                f.write_str("Subject must be separated from the body by a blank line")
+            }
+        }
+    }
+}
+
+/// Check a whole message, returning every problem found
+pub fn check_message(message: &str, max_subject_length: usize) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    if let Err(error) = parse_header(subject(message)) {
+        problems.push(Problem::Header(error));
+    }
+    let length = subject_length(message);
+    if length > max_subject_length {
+        problems.push(Problem::SubjectTooLong {
+            length,
+            max: max_subject_length,
+        });
+    }
+    if !body_separated(message) {
+        problems.push(Problem::MissingBlankLine);
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_parse_header() {
+        assert_eq!(
+            parse_header("feat(api)!: drop v1 endpoints"),
+            Ok(Header {
+                commit_type: "feat",
+                scope: Some("api"),
+                breaking: true,
+                description: "drop v1 endpoints",
+            })
+        );
+        let header = parse_header("fix: handle timeouts").unwrap();
+        assert_eq!(header.scope, None);
+        assert_eq!(header.to_string(), "fix: handle timeouts");
+
+        assert_eq!(parse_header("feature: x"), Err(ParseError::UnknownType));
+        assert_eq!(parse_header("Feat: x"), Err(ParseError::UnknownType));
+        assert_eq!(parse_header("feat(): x"), Err(ParseError::InvalidScope));
+        assert_eq!(parse_header("feat(api: x"), Err(ParseError::InvalidScope));
+        assert_eq!(parse_header("feat:x"), Err(ParseError::MissingSeparator));
+        assert_eq!(
+            parse_header("update readme"),
+            Err(ParseError::MissingSeparator)
+        );
+        assert_eq!(parse_header("docs: "), Err(ParseError::EmptyDescription));
+        assert_eq!(parse_header("docs: a\n\nb"), Err(ParseError::LineBreak));
+    }
+
+    #[test]
+    fn test_check_message() {
+        assert!(check_message("feat: add login\n\nWith OAuth.", MAX_SUBJECT_LENGTH).is_empty());
+        assert_eq!(
+            check_message("added login\nWith OAuth.", 10),
+            [
+                Problem::Header(ParseError::MissingSeparator),
+                Problem::SubjectTooLong {
+                    length: 11,
+                    max: 10
+                },
+                Problem::MissingBlankLine,
+            ]
+        );
+    }
+}
@@ -0,0 +1,158 @@
+//! Vendored and minified code among the changed files
+//!
+//! Third-party code copied into the tree or a minified bundle says nothing about
+//! the change and can fill the whole prompt, and it is often committed by
+//! accident. Such patches are sent as line counts and the user is warned.
+
+use std::fmt;
+
+/// Directories that hold third-party code
+const VENDOR_DIRS: &[&str] = &[
+    "vendor",
+    "vendored",
+    "node_modules",
+    "bower_components",
+    "third_party",
+    "third-party",
+];
+
+/// Added lines at least this long are taken to be minified
+pub const MINIFIED_LINE_LENGTH: usize = 1000;
+
+/// Why a file looks like it isn't the project's own source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VendoredReason {
+    /// Under a directory of third-party code such as `node_modules/`
+    VendorDirectory(String),
+    /// Named like a minified file or has very long added lines
+    Minified,
+}
+
+/// A changed file that looks vendored or minified
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VendoredFile {
+    pub path: String,
+    pub reason: VendoredReason,
+    pub additions: usize,
+    pub deletions: usize,
+}
+
+impl fmt::Display for VendoredFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.reason {
+            VendoredReason::VendorDirectory(dir) => write!(f, "{} (in `{dir}/`)", self.path),
+            VendoredReason::Minified => write!(f, "{} (minified)", self.path),
+        }
+    }
+}
+
+/// Find the files in a diff that look vendored or minified
+pub fn detect_vendored(diff: &str) -> Vec<VendoredFile> {
+    let mut files = Vec::new();
+    let mut current: Option<(String, usize, usize, usize)> = None;
+    let mut finish = |file: Option<(String, usize, usize, usize)>| {
+        if let Some((path, additions, deletions, longest)) = file {
+            if let Some(reason) = vendored_reason(&path, longest) {
+                files.push(VendoredFile {
+                    path,
+                    reason,
+                    additions,
+                    deletions,
+                });
+            }
+        }
+    };
+
+    for line in diff.lines() {
+        if let Some(paths) = line.strip_prefix("diff --git ") {
+            finish(current.take());
+            let path = paths.rsplit_once(" b/").map_or(paths, |(_, path)| path);
+            current = Some((path.to_string(), 0, 0, 0));
+            continue;
+        }
+        let Some((_, additions, deletions, longest)) = current.as_mut() else {
+            continue;
+        };
+        if line.starts_with('+') && !line.starts_with("+++") {
+            *additions += 1;
+            *longest = (*longest).max(line.len() - 1);
+        } else if line.starts_with('-') && !line.starts_with("---") {
+            *deletions += 1;
+        }
+    }
+    finish(current);
+    files
+}
+
+/// Get the note that replaces each vendored file's patch, keyed by path
+///
+/// For [`crate::diff::replace_file_patches`].
+pub fn vendored_patch_replacements(files: &[VendoredFile]) -> Vec<(String, String)> {
+    files
+        .iter()
+        .map(|file| {
+            let note = format!(
+                "(vendored or minified code, +{} -{} lines; contents not sent)\n",
+                file.additions, file.deletions
+            );
+            (file.path.clone(), note)
+        })
+        .collect()
+}
+
+fn vendored_reason(path: &str, longest_added_line: usize) -> Option<VendoredReason> {
+    let mut dirs = path.split('/').rev().skip(1);
+    if let Some(dir) = dirs.find(|dir| VENDOR_DIRS.contains(dir)) {
+        return Some(VendoredReason::VendorDirectory(dir.to_string()));
+    }
+
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    let minified_name = [".min.js", ".min.mjs", ".min.css"]
+        .iter()
+        .any(|suffix| file_name.ends_with(suffix));
+    if minified_name || longest_added_line >= MINIFIED_LINE_LENGTH {
+        return Some(VendoredReason::Minified);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_vendored() {
+        let bundle = format!("+{}\n", "var a=1;".repeat(200));
+        let diff = format!(
+            "diff --git a/node_modules/lodash/lodash.js b/node_modules/lodash/lodash.js\n\
+             +++ b/node_modules/lodash/lodash.js\n+module.exports = {{}};\n+var x;\n\
+             diff --git a/dist/app.js b/dist/app.js\n--- a/dist/app.js\n-old\n{bundle}\
+             diff --git a/src/lib.rs b/src/lib.rs\n+fn vendor() {{}}\n\
+             diff --git a/static/jquery.min.js b/static/jquery.min.js\n+!function(){{}}\n"
+        );
+
+        let files: Vec<String> = detect_vendored(&diff)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            files,
+            vec![
+                "node_modules/lodash/lodash.js (in `node_modules/`)",
+                "dist/app.js (minified)",
+                "static/jquery.min.js (minified)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_vendored_patch_replacements() {
+        let diff = "diff --git a/vendor/x.go b/vendor/x.go\n+package x\n+var Y = 1\n-var Y = 0\n";
+        let replacements = vendored_patch_replacements(&detect_vendored(diff));
+        assert_eq!(
+            crate::diff::replace_file_patches(diff, &replacements),
+            "diff --git a/vendor/x.go b/vendor/x.go\n\
+             (vendored or minified code, +2 -1 lines; contents not sent)\n"
+        );
+    }
+}
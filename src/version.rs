@@ -0,0 +1,57 @@
+//! Build and environment details shown by `committor --version`, for bug reports
+//!
+//! The commit, build date and features come from a module `build.rs` generates.
+
+use std::path::Path;
+
+include!(concat!(env!("OUT_DIR"), "/build_info.rs"));
+
+/// Providers messages can be generated with
+pub const PROVIDERS: [&str; 2] = ["openai", "ollama"];
+
+/// Describe the build, the providers and the settings file in use
+///
+/// `settings_path` is where the current repository's settings are read from, or
+/// `None` outside a repository.
+pub fn long_version(settings_path: Option<&Path>) -> String {
+    let features = if FEATURES.is_empty() {
+        "none".to_string()
+    } else {
+        FEATURES.join(", ")
+    };
+    let config = match settings_path {
+        Some(path) if path.exists() => path.display().to_string(),
+        Some(path) => format!("{} (not found)", path.display()),
+        None => "(not in a git repository)".to_string(),
+    };
+    format!(
+        "{} ({GIT_SHA} {BUILD_DATE})\nfeatures: {features}\nproviders: {}\nconfig: {config}",
+        env!("CARGO_PKG_VERSION"),
+        PROVIDERS.join(", "),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_long_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let settings = dir.path().join(".committor.toml");
+
+        let version = long_version(Some(&settings));
+        let lines: Vec<&str> = version.lines().collect();
+        assert!(lines[0].starts_with(env!("CARGO_PKG_VERSION")));
+        assert!(lines[1].contains("cli"));
+        assert_eq!(lines[2], "providers: openai, ollama");
+        assert_eq!(
+            lines[3],
+            format!("config: {} (not found)", settings.display())
+        );
+
+        std::fs::write(&settings, "").unwrap();
+        assert!(long_version(Some(&settings)).ends_with(&format!("config: {}", settings.display())));
+        assert!(long_version(None).ends_with("config: (not in a git repository)"));
+    }
+}
@@ -0,0 +1,419 @@
+//! Repository vocabulary used to catch misspelled product and module names
+//!
+//! Reading every source file at HEAD takes a while on a large repository, so the
+//! terms are cached in the git directory against HEAD's tree, like the profile.
+
+use crate::paths::git_state_path;
+use crate::ranking::defined_names;
+use crate::state::write_atomic;
+use crate::validate::{parse_header, subject};
+use anyhow::Result;
+use git2::{ObjectType, Repository, Tree, TreeWalkMode, TreeWalkResult};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Name of the vocabulary cache, kept in the git directory
+pub const VOCABULARY_CACHE_FILE_NAME: &str = "committor-vocabulary.json";
+
+/// Shortest word checked against the vocabulary; shorter words are too easily confused
+pub const MIN_TERM_LENGTH: usize = 5;
+
+/// Largest file read for identifiers when building the vocabulary
+const MAX_SOURCE_FILE_SIZE: usize = 100 * 1024;
+
+/// Extensions of files whose definitions are added to the vocabulary
+const SOURCE_EXTENSIONS: &[&str] = &[
+    "rs", "py", "js", "ts", "jsx", "tsx", "go", "java", "kt", "rb", "php", "cs", "swift", "c", "h",
+    "cpp", "scala",
+];
+
+/// How generated messages are checked against the repository vocabulary
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpellcheckMode {
+    /// Don't check spelling
+    Off,
+    /// Flag likely misspellings next to the suggestions
+    #[default]
+    Warn,
+    /// Replace likely misspellings with the closest vocabulary term
+    Correct,
+}
+
+/// A word in a message that looks like a misspelled vocabulary term
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Misspelling {
+    pub word: String,
+    pub suggestion: String,
+}
+
+impl fmt::Display for Misspelling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` looks like a misspelling of `{}`",
+            self.word, self.suggestion
+        )
+    }
+}
+
+/// Identifiers, file names and README terms known to the repository
+#[derive(Debug, Clone, Default)]
+pub struct Vocabulary {
+    /// Every known spelling, keyed by its lowercase form
+    terms: HashMap<String, HashSet<String>>,
+}
+
+impl Vocabulary {
+    /// Build a vocabulary from a list of terms
+    pub fn from_terms<I, S>(terms: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut vocabulary = Self::default();
+        for term in terms {
+            vocabulary.insert(term.as_ref());
+        }
+        vocabulary
+    }
+
+    /// Add a term, ignoring ones too short to check
+    pub fn insert(&mut self, term: &str) {
+        if term.chars().count() >= MIN_TERM_LENGTH {
+            self.terms
+                .entry(term.to_lowercase())
+                .or_default()
+                .insert(term.to_string());
+        }
+    }
+
+    /// Check whether the vocabulary has no terms
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Find the vocabulary term a word is most likely a misspelling of
+    ///
+    /// Words that are themselves known, or that only differ from a term by a
+    /// suffix (plurals, `handle`/`handler`), are not misspellings.
+    pub fn closest_term(&self, word: &str) -> Option<String> {
+        let lower = word.to_lowercase();
+        let length = lower.chars().count();
+        if length < MIN_TERM_LENGTH || self.terms.contains_key(&lower) {
+            return None;
+        }
+
+        let max_distance = if length >= 8 { 2 } else { 1 };
+        self.terms
+            .iter()
+            .filter(|(term, _)| !term.starts_with(&lower) && !lower.starts_with(term.as_str()))
+            .map(|(term, spellings)| (edit_distance(&lower, term), spellings))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .min_by_key(|(distance, spellings)| (*distance, spellings.iter().min().cloned()))
+            .and_then(|(_, spellings)| spellings.iter().min().cloned())
+    }
+
+    /// Find the words in a message that look like misspelled vocabulary terms
+    pub fn misspellings(&self, message: &str) -> Vec<Misspelling> {
+        let mut misspellings: Vec<Misspelling> = Vec::new();
+        for word in words(message) {
+            if misspellings.iter().any(|m| m.word == word) {
+                continue;
+            }
+            if let Some(suggestion) = self.closest_term(word) {
+                misspellings.push(Misspelling {
+                    word: word.to_string(),
+                    suggestion,
+                });
+            }
+        }
+        misspellings
+    }
+
+    /// Replace likely misspellings in a message with the closest vocabulary term
+    ///
+    /// Only words that look like identifiers or names are replaced, so plain English
+    /// close to a file name is left alone. The type and scope of a conventional
+    /// header are left to the header rules.
+    pub fn correct(&self, message: &str) -> String {
+        let first_line = subject(message);
+        let prefix_length = parse_header(first_line)
+            .map_or(0, |header| first_line.len() - header.description.len());
+        let (prefix, rest) = message.split_at(prefix_length);
+        let corrected = word_regex().replace_all(rest, |captures: &regex::Captures| {
+            let word = &captures[0];
+            if looks_like_identifier(word) {
+                self.closest_term(word).unwrap_or_else(|| word.to_string())
+            } else {
+                word.to_string()
+            }
+        });
+        format!("{prefix}{corrected}")
+    }
+
+    /// Get every spelling of every term, sorted
+    fn spellings(&self) -> Vec<String> {
+        let mut spellings: Vec<String> = self.terms.values().flatten().cloned().collect();
+        spellings.sort();
+        spellings
+    }
+}
+
+/// Vocabulary terms cached against the tree they were read from
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedVocabulary {
+    tree: String,
+    terms: Vec<String>,
+}
+
+/// Build the vocabulary for the current repository
+pub fn build_vocabulary() -> Result<Vocabulary> {
+    let repo = Repository::discover(".")?;
+    build_vocabulary_from_repo(&repo)
+}
+
+/// Get the path of the vocabulary cache for a repository
+pub fn vocabulary_cache_path_from_repo(repo: &Repository) -> PathBuf {
+    git_state_path(repo, VOCABULARY_CACHE_FILE_NAME)
+}
+
+/// Build a vocabulary from the files at HEAD: path components, defined names and README terms
+///
+/// Comes from the cache when HEAD's tree hasn't changed.
+pub fn build_vocabulary_from_repo(repo: &Repository) -> Result<Vocabulary> {
+    // An unborn branch has no files yet
+    let Ok(tree) = repo.head().and_then(|head| head.peel_to_tree()) else {
+        return Ok(Vocabulary::default());
+    };
+
+    let path = vocabulary_cache_path_from_repo(repo);
+    let tree_id = tree.id().to_string();
+    if let Some(cached) = load_vocabulary(&path).filter(|cached| cached.tree == tree_id) {
+        return Ok(Vocabulary::from_terms(cached.terms));
+    }
+
+    let vocabulary = tree_vocabulary(repo, &tree)?;
+    let cached = CachedVocabulary {
+        tree: tree_id,
+        terms: vocabulary.spellings(),
+    };
+    // The cache only saves time; a read-only git directory is no reason to fail
+    let _ = write_atomic(&path, serde_json::to_string(&cached)?);
+    Ok(vocabulary)
+}
+
+fn load_vocabulary(path: &Path) -> Option<CachedVocabulary> {
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+/// Read the terms of every file in a tree
+fn tree_vocabulary(repo: &Repository, tree: &Tree) -> Result<Vocabulary> {
+    let mut vocabulary = Vocabulary::default();
+    tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+        let Some(name) = entry.name() else {
+            return TreeWalkResult::Ok;
+        };
+        let path = Path::new(name);
+        if let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) {
+            vocabulary.insert(stem);
+        }
+
+        if entry.kind() != Some(ObjectType::Blob) {
+            return TreeWalkResult::Ok;
+        }
+
+        let is_readme = dir.is_empty() && name.to_lowercase().starts_with("readme");
+        let is_source = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| SOURCE_EXTENSIONS.contains(&ext));
+        if !is_readme && !is_source {
+            return TreeWalkResult::Ok;
+        }
+
+        let Ok(blob) = repo.find_blob(entry.id()) else {
+            return TreeWalkResult::Ok;
+        };
+        if blob.size() > MAX_SOURCE_FILE_SIZE || blob.is_binary() {
+            return TreeWalkResult::Ok;
+        }
+
+        let content = String::from_utf8_lossy(blob.content());
+        if is_readme {
+            for term in readme_terms(&content) {
+                vocabulary.insert(term);
+            }
+        } else {
+            for name in defined_names(&content) {
+                vocabulary.insert(&name);
+            }
+        }
+        TreeWalkResult::Ok
+    })?;
+
+    Ok(vocabulary)
+}
+
+/// Find product and proper names in a README: capitalized words not starting a sentence
+fn readme_terms(content: &str) -> Vec<&str> {
+    let mut terms = Vec::new();
+    for line in content.lines() {
+        let mut previous_ends_sentence = true;
+        for word in line.split_whitespace() {
+            let trimmed = word.trim_matches(|c: char| !c.is_alphanumeric());
+            if !previous_ends_sentence
+                && trimmed.chars().next().is_some_and(char::is_uppercase)
+                && trimmed.chars().any(char::is_lowercase)
+            {
+                terms.push(trimmed);
+            }
+            previous_ends_sentence = word.ends_with(['.', '!', '?', ':']) || word.starts_with('#');
+        }
+    }
+    terms
+}
+
+/// The words of a message, compiled once
+fn word_regex() -> &'static Regex {
+    static WORD: OnceLock<Regex> = OnceLock::new();
+    WORD.get_or_init(|| Regex::new(r"[A-Za-z][A-Za-z0-9_]*").unwrap())
+}
+
+/// Split a message into the words that are checked
+fn words(message: &str) -> Vec<&str> {
+    word_regex()
+        .find_iter(message)
+        .map(|m| m.as_str())
+        .collect()
+}
+
+/// Check whether a word is written like a name or identifier rather than plain English
+///
+/// That is, it has an underscore, a digit or a capital letter, as in `parse_args`,
+/// `oauth2`, `TokenValidator` or `Committor`.
+fn looks_like_identifier(word: &str) -> bool {
+    word.chars()
+        .any(|c| c == '_' || c.is_ascii_digit() || c.is_uppercase())
+}
+
+/// Levenshtein distance between two words
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vocabulary() -> Vocabulary {
+        Vocabulary::from_terms(["Committor", "rollback", "handler", "parse_commit_message"])
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("commitor", "committor"), 1);
+        assert_eq!(edit_distance("rollback", "rollback"), 0);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_misspellings() {
+        let vocabulary = vocabulary();
+        let misspellings = vocabulary
+            .misspellings("fix(cli): make Commitor handle rolback in parse_comit_message");
+        assert_eq!(
+            misspellings,
+            vec![
+                Misspelling {
+                    word: "Commitor".to_string(),
+                    suggestion: "Committor".to_string(),
+                },
+                Misspelling {
+                    word: "rolback".to_string(),
+                    suggestion: "rollback".to_string(),
+                },
+                Misspelling {
+                    word: "parse_comit_message".to_string(),
+                    suggestion: "parse_commit_message".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_correct() {
+        assert_eq!(
+            vocabulary().correct("feat: add Commitor rolback handler"),
+            "feat: add Committor rolback handler"
+        );
+        assert_eq!(
+            vocabulary().correct("fix(Commitor): call parse_comit_message"),
+            "fix(Commitor): call parse_commit_message"
+        );
+    }
+
+    #[test]
+    fn test_vocabulary_is_cached_per_tree() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let repo = Repository::init(dir.path())?;
+        assert!(build_vocabulary_from_repo(&repo)?.is_empty());
+
+        std::fs::write(
+            dir.path().join("rollback.rs"),
+            "pub fn TokenValidator() {}\n",
+        )?;
+        let mut index = repo.index()?;
+        index.add_path(Path::new("rollback.rs"))?;
+        index.write()?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+        let signature = git2::Signature::now("Test User", "test@example.com")?;
+        repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])?;
+
+        let vocabulary = build_vocabulary_from_repo(&repo)?;
+        assert_eq!(
+            vocabulary.closest_term("rolback").as_deref(),
+            Some("rollback")
+        );
+        let path = vocabulary_cache_path_from_repo(&repo);
+        assert!(path.exists());
+
+        // A cache entry for the current tree is trusted as it is
+        let cached = CachedVocabulary {
+            tree: tree.id().to_string(),
+            terms: vec!["Committor".to_string()],
+        };
+        std::fs::write(&path, serde_json::to_string(&cached)?)?;
+        let vocabulary = build_vocabulary_from_repo(&repo)?;
+        assert_eq!(vocabulary.closest_term("rolback"), None);
+        assert_eq!(
+            vocabulary.closest_term("Commitor").as_deref(),
+            Some("Committor")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_readme_terms() {
+        let terms =
+            readme_terms("# Committor\n\nGenerate messages with OpenAI or Ollama. Works offline.");
+        assert_eq!(terms, vec!["OpenAI", "Ollama"]);
+    }
+}
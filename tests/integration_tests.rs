@@ -2,8 +2,13 @@
 //!
 //! These tests verify the end-to-end functionality of committor,
 //! including CLI commands, git operations, and AI integration.
+//! They run the binary cargo built for the tests, so they work from any directory.
 
+mod support;
+
+use assert_cmd::cargo::cargo_bin_cmd;
 use git2::Repository;
+use predicates::prelude::*;
 use std::fs;
 use std::path::Path;
 use std::process::Command;
@@ -153,50 +158,87 @@ impl TestRepo {
     }
 }
 
+/// Build a command running the committor binary, without the caller's provider settings
+///
+/// The interface is pinned to English so assertions don't depend on the caller's locale.
+fn committor() -> assert_cmd::Command {
+    let mut command = cargo_bin_cmd!("committor");
+    command
+        .env("LC_ALL", "C")
+        .env_remove("OPENAI_API_KEY")
+        .env_remove("OPENAI_BASE_URL")
+        .env_remove("COMMITTOR_PROVIDER")
+        .env_remove("COMMITTOR_MODEL")
+        .env_remove("COMMITTOR_POLICY");
+    command
+}
+
 #[test]
 fn test_cli_help_command() {
-    let output = Command::new("cargo")
-        .args(["run", "--", "--help"])
-        .current_dir(".")
-        .output()
-        .expect("Failed to execute command");
-
-    assert!(output.status.success());
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    assert!(stdout.contains("Generate conventional commit messages"));
-    assert!(stdout.contains("Commands:"));
-    assert!(stdout.contains("generate"));
-    assert!(stdout.contains("commit"));
-    assert!(stdout.contains("diff"));
+    committor()
+        .arg("--help")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Generate conventional commit messages",
+        ))
+        .stdout(predicate::str::contains("Commands:"))
+        .stdout(predicate::str::contains("generate"))
+        .stdout(predicate::str::contains("commit"))
+        .stdout(predicate::str::contains("diff"));
 }
 
 #[test]
 fn test_cli_version_command() {
-    let output = Command::new("cargo")
-        .args(["run", "--", "--version"])
-        .current_dir(".")
-        .output()
-        .expect("Failed to execute command");
+    committor()
+        .arg("--version")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("committor"))
+        .stdout(predicate::str::contains(env!("CARGO_PKG_VERSION")))
+        .stdout(predicate::str::contains("providers: openai, ollama"))
+        .stdout(predicate::str::contains("config: "));
+}
+
+#[test]
+fn test_badge_command() {
+    let test_repo = TestRepo::new().expect("Failed to create test repo");
 
-    assert!(output.status.success());
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    assert!(stdout.contains("committor"));
-    assert!(stdout.contains("0.1.0"));
+    committor()
+        .arg("--repo")
+        .arg(test_repo.path())
+        .args(["--no-update-check", "badge", "-"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"schemaVersion\": 1"))
+        .stdout(predicate::str::contains("\"message\": \"0%\""))
+        .stdout(predicate::str::contains("\"color\": \"red\""));
+
+    let badge = test_repo.path().join("badge.svg");
+    committor()
+        .arg("--repo")
+        .arg(test_repo.path())
+        .args(["--no-update-check", "badge"])
+        .arg(&badge)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "0 of the last 1 commits are conventional",
+        ));
+    assert!(fs::read_to_string(badge).unwrap().starts_with("<svg"));
 }
 
 #[test]
 fn test_no_staged_changes() {
     let test_repo = TestRepo::new().expect("Failed to create test repo");
 
-    let output = Command::new("cargo")
-        .args(["run", "--", "diff"])
-        .current_dir(test_repo.path())
-        .output()
-        .expect("Failed to execute command");
-
     // Should succeed but indicate no changes
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    assert!(stdout.contains("No staged changes") || stdout.is_empty());
+    committor()
+        .arg("diff")
+        .current_dir(test_repo.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No staged changes").or(predicate::str::is_empty()));
 }
 
 #[test]
@@ -225,21 +267,12 @@ fn test_generate_command_without_api_key() {
         .add_file("test.rs", "fn main() { println!(\"Hello!\"); }")
         .expect("Failed to add file");
 
-    let output = Command::new("cargo")
-        .args(["run", "--", "generate"])
+    committor()
+        .args(["--provider", "openai", "generate"])
         .current_dir(test_repo.path())
-        .env_remove("OPENAI_API_KEY") // Ensure no API key
-        .output()
-        .expect("Failed to execute command");
-
-    // Should fail with API key error or other expected errors
-    let stderr = String::from_utf8_lossy(&output.stderr);
-    assert!(
-        stderr.contains("OpenAI API key")
-            || stderr.contains("API key")
-            || stderr.contains("No staged changes")
-            || !output.status.success()
-    );
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("OpenAI API key not found"));
 }
 
 #[test]
@@ -253,41 +286,440 @@ fn test_commit_command_without_api_key() {
         )
         .expect("Failed to add file");
 
-    let output = Command::new("cargo")
-        .args(["run", "--", "commit"])
+    committor()
+        .args(["--provider", "openai", "commit"])
         .current_dir(test_repo.path())
-        .env_remove("OPENAI_API_KEY") // Ensure no API key
-        .output()
-        .expect("Failed to execute command");
-
-    // Should fail with API key error or other expected errors
-    let stderr = String::from_utf8_lossy(&output.stderr);
-    assert!(
-        stderr.contains("OpenAI API key")
-            || stderr.contains("API key")
-            || stderr.contains("No staged changes")
-            || !output.status.success()
-    );
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("OpenAI API key not found"));
+    assert!(test_repo
+        .has_staged_changes()
+        .expect("Failed to check staged changes"));
 }
 
 #[test]
 fn test_invalid_git_repository() {
     let temp_dir = TempDir::new().expect("Failed to create temp dir");
 
-    let output = Command::new("cargo")
-        .args(["run", "--", "generate"])
+    // Should fail with git repository error
+    committor()
+        .arg("generate")
         .current_dir(temp_dir.path())
-        .output()
-        .expect("Failed to execute command");
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Git repository not found"));
+}
 
-    // Should fail with git repository error
-    let stderr = String::from_utf8_lossy(&output.stderr);
+#[test]
+fn test_bare_repository_range_diff() {
+    let test_repo = TestRepo::new().expect("Failed to create test repo");
+    test_repo
+        .add_file("server.rs", "fn handle_push() {}")
+        .expect("Failed to add file");
+
+    let git = |args: &[&str], dir: &Path| {
+        Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .expect("Failed to run git")
+    };
+    assert!(git(&["commit", "-q", "-m", "Add server"], test_repo.path())
+        .status
+        .success());
+
+    let bare_dir = TempDir::new().expect("Failed to create temp dir");
+    let bare_path = bare_dir.path().join("bare.git");
+    assert!(git(
+        &[
+            "clone",
+            "-q",
+            "--bare",
+            test_repo.path().to_str().unwrap(),
+            bare_path.to_str().unwrap(),
+        ],
+        bare_dir.path(),
+    )
+    .status
+    .success());
+
+    committor()
+        .arg("--repo")
+        .arg(&bare_path)
+        .args(["--range", "HEAD~1..HEAD", "diff"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("handle_push"));
+}
+
+#[test]
+fn test_summary_command_json() {
+    let test_repo = TestRepo::new().expect("Failed to create test repo");
+    test_repo
+        .add_file("src/lib.rs", "fn one() {}\nfn two() {}\n")
+        .expect("Failed to add file");
+
+    let output = committor()
+        .arg("--repo")
+        .arg(test_repo.path())
+        .args(["summary", "--json"])
+        .assert()
+        .success();
+    let summary: serde_json::Value =
+        serde_json::from_slice(&output.get_output().stdout).expect("Summary is not JSON");
+    assert_eq!(summary["schema_version"], committor::schema::SCHEMA_VERSION);
+    assert_eq!(summary["files"], 1);
+    assert_eq!(summary["additions"], 2);
+    assert_eq!(summary["changes"][0]["file_path"], "src/lib.rs");
+    assert_eq!(summary["changes"][0]["change_type"], "added");
+}
+
+#[test]
+fn test_prompt_status_without_provider() {
+    let test_repo = TestRepo::new().expect("Failed to create test repo");
+    let prompt_status = || {
+        committor()
+            .arg("--repo")
+            .arg(test_repo.path())
+            .arg("prompt-status")
+            .env_remove("OPENAI_API_KEY")
+            .assert()
+            .success()
+    };
+    prompt_status().stdout("");
+
+    test_repo
+        .add_file(
+            "docs/install.md",
+            "# Install
+",
+        )
+        .expect("Failed to add file");
+    prompt_status().stdout("+docs\n");
+}
+
+#[test]
+fn test_privacy_shorthands_conflict_with_privacy() {
+    for shorthand in ["--anonymize", "--paths-only"] {
+        committor()
+            .args(["--privacy", "local-only", shorthand, "generate"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
+    }
+}
+
+#[test]
+fn test_export_prompt_and_import_response() {
+    let test_repo = TestRepo::new().expect("Failed to create test repo");
+    test_repo
+        .add_file(
+            "src/vault.rs",
+            "pub fn connect() { open(\"vault.prod.internal\"); }\n",
+        )
+        .expect("Failed to add file");
+    let run = |args: &[&str]| {
+        committor()
+            .arg("--repo")
+            .arg(test_repo.path())
+            .args(["--no-update-check", "--privacy", "redacted"])
+            .args(args)
+            .env_remove("OPENAI_API_KEY")
+            .assert()
+    };
+
+    run(&["import-response", "-"])
+        .failure()
+        .stderr(predicate::str::contains("No prompt was exported"));
+
+    run(&["export-prompt", "prompt.txt"]).success();
+    let prompt = fs::read_to_string(test_repo.path().join("prompt.txt")).unwrap();
+    assert!(prompt.contains("src/vault.rs"));
+    assert!(prompt.contains("<STRING_1>"));
+    assert!(!prompt.contains("vault.prod.internal"));
+
+    // Replies are checked like a provider's: the one that isn't a commit message is dropped
+    fs::write(
+        test_repo.path().join("reply1.txt"),
+        "feat(vault): connect to <STRING_1>\n",
+    )
+    .unwrap();
+    fs::write(test_repo.path().join("reply2.txt"), "Sure! Here you go.\n").unwrap();
+    run(&["import-response", "reply1.txt", "reply2.txt"])
+        .success()
+        .stdout(predicate::str::contains(
+            "feat(vault): connect to vault.prod.internal",
+        ))
+        .stdout(predicate::str::contains("Sure!").not());
+
+    test_repo
+        .add_file("src/other.rs", "pub fn other() {}\n")
+        .expect("Failed to add file");
+    run(&["import-response", "reply1.txt"])
+        .failure()
+        .stderr(predicate::str::contains(
+            "Run `committor export-prompt` again",
+        ));
+}
+
+#[test]
+fn test_prepare_commit_msg_hook_gives_up_quietly() {
+    let test_repo = TestRepo::new().expect("Failed to create test repo");
+    test_repo
+        .add_file("src/lib.rs", "pub fn login() {}\n")
+        .expect("Failed to add file");
+    let message_file = test_repo.path().join("COMMIT_EDITMSG");
+    let content = "\n# Please enter the commit message for your changes.\n";
+    fs::write(&message_file, content).expect("Failed to write message file");
+
+    // Accepts connections but never answers, like a hung provider
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+    let ollama_url = format!("http://{}", listener.local_addr().unwrap());
+
+    let started = std::time::Instant::now();
+    committor()
+        .arg("--repo")
+        .arg(test_repo.path())
+        .args(["--provider", "ollama", "--ollama-url", &ollama_url])
+        .args(["hook", "prepare-commit-msg"])
+        .arg(&message_file)
+        .args(["--timeout", "1"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("committor:").not());
+
+    assert!(started.elapsed() < std::time::Duration::from_secs(10));
+    assert_eq!(fs::read_to_string(&message_file).unwrap(), content);
+}
+
+#[test]
+fn test_prepare_commit_msg_hook_survives_broken_settings() {
+    let test_repo = TestRepo::new().expect("Failed to create test repo");
+    fs::write(test_repo.path().join(".committor.toml"), "[hooks\n").unwrap();
+    let message_file = test_repo.path().join("COMMIT_EDITMSG");
+    let content = "\n# Please enter the commit message for your changes.\n";
+    fs::write(&message_file, content).expect("Failed to write message file");
+
+    committor()
+        .arg("--repo")
+        .arg(test_repo.path())
+        .args(["hook", "prepare-commit-msg"])
+        .arg(&message_file)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("committor: no suggestions"));
+    assert_eq!(fs::read_to_string(&message_file).unwrap(), content);
+}
+
+#[test]
+fn test_prepare_commit_msg_hook_survives_missing_policy() {
+    let test_repo = TestRepo::new().expect("Failed to create test repo");
+    let message_file = test_repo.path().join("COMMIT_EDITMSG");
+    let content = "\n# Please enter the commit message for your changes.\n";
+    fs::write(&message_file, content).expect("Failed to write message file");
+
+    for args in [
+        &["hook", "prepare-commit-msg"][..],
+        &["pre-commit-hook", "prepare-commit-msg"][..],
+    ] {
+        committor()
+            .env(
+                "COMMITTOR_POLICY",
+                test_repo.path().join("missing-policy.toml"),
+            )
+            .arg("--repo")
+            .arg(test_repo.path())
+            .args(args)
+            .arg(&message_file)
+            .assert()
+            .success()
+            .stderr(predicate::str::contains("committor: no suggestions"));
+        assert_eq!(fs::read_to_string(&message_file).unwrap(), content);
+    }
+}
+
+#[test]
+fn test_commit_msg_hook_adds_change_id() {
+    let test_repo = TestRepo::new().expect("Failed to create test repo");
+    let message_file = test_repo.path().join("COMMIT_EDITMSG");
+    fs::write(&message_file, "feat: add login\n").expect("Failed to write message file");
+
+    committor()
+        .arg("--repo")
+        .arg(test_repo.path())
+        .args(["hook", "commit-msg", "--change-id"])
+        .arg(&message_file)
+        .assert()
+        .success();
+
+    let message = fs::read_to_string(&message_file).unwrap();
+    assert!(message.starts_with("feat: add login\n"), "{message}");
+    assert!(message.contains("Change-Id: I"), "{message}");
+}
+
+#[test]
+fn test_commit_msg_hook_uses_the_comment_char() {
+    let test_repo = TestRepo::new().expect("Failed to create test repo");
+    test_repo
+        .repo
+        .config()
+        .unwrap()
+        .set_str("core.commentChar", ";")
+        .unwrap();
+    let message_file = test_repo.path().join("COMMIT_EDITMSG");
+    fs::write(
+        &message_file,
+        "fix: follow up\n\n#123 follow-up\n\n; Please enter the commit message\n",
+    )
+    .expect("Failed to write message file");
+
+    committor()
+        .arg("--repo")
+        .arg(test_repo.path())
+        .args(["hook", "commit-msg", "--change-id"])
+        .arg(&message_file)
+        .assert()
+        .success();
+
+    let message = fs::read_to_string(&message_file).unwrap();
     assert!(
-        stderr.contains("Not in a git repository")
-            || stderr.contains("git repository")
-            || stderr.contains("fatal: not a git repository")
-            || !output.status.success()
+        message.starts_with("fix: follow up\n\n#123 follow-up\n\nChange-Id: I"),
+        "{message}"
     );
+    assert!(!message.contains("Please enter"), "{message}");
+}
+
+#[test]
+fn test_pre_commit_hook_stages() {
+    let test_repo = TestRepo::new().expect("Failed to create test repo");
+    let message_file = test_repo.path().join("COMMIT_EDITMSG");
+    let pre_commit_hook = |stage: &str| {
+        let mut command = committor();
+        command
+            .env("PRE_COMMIT", "1")
+            .arg("--repo")
+            .arg(test_repo.path())
+            .args(["pre-commit-hook", stage])
+            .arg(&message_file);
+        command
+    };
+
+    // Git wrote the message for `-m`, so there is nothing to suggest and no provider is needed
+    fs::write(&message_file, "Added login.\n").expect("Failed to write message file");
+    pre_commit_hook("prepare-commit-msg")
+        .env("PRE_COMMIT_COMMIT_MSG_SOURCE", "message")
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(&message_file).unwrap(), "Added login.\n");
+
+    pre_commit_hook("commit-msg")
+        .assert()
+        .code(4)
+        .stdout(predicate::str::contains("conventional"));
+
+    fs::write(&message_file, "feat: add login\n").expect("Failed to write message file");
+    pre_commit_hook("commit-msg").assert().success();
+
+    // An empty message is left for git to abort
+    fs::write(&message_file, "# Please enter the commit message.\n").unwrap();
+    pre_commit_hook("commit-msg").assert().success();
+}
+
+#[test]
+fn test_lint_command_without_provider() {
+    let lint = |message: &str| committor().arg("lint").write_stdin(message).assert();
+
+    lint("feat: add login\n").success();
+    lint("Added login.\n")
+        .failure()
+        .stdout(predicate::str::contains("conventional"));
+}
+
+#[test]
+fn test_organization_policy() {
+    let test_repo = TestRepo::new().expect("Failed to create test repo");
+    test_repo
+        .add_file("src/lib.rs", "pub fn answer() -> u32 { 42 }\n")
+        .expect("Failed to add file");
+    let policy = test_repo.path().join("policy.toml");
+    fs::write(
+        &policy,
+        "providers = [\"ollama\"]\ntypes = [\"feat\", \"fix\"]\n",
+    )
+    .unwrap();
+    let with_policy = || {
+        let mut command = committor();
+        command
+            .env("COMMITTOR_POLICY", &policy)
+            .arg("--repo")
+            .arg(test_repo.path());
+        command
+    };
+
+    with_policy()
+        .arg("lint")
+        .write_stdin("chore: bump deps\n")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("type-allowed"));
+    with_policy()
+        .args(["--provider", "openai", "--api-key", "test-key", "generate"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "does not allow the openai provider",
+        ));
+}
+
+#[test]
+fn test_offline_commands_skip_the_policy_url() {
+    let test_repo = TestRepo::new().expect("Failed to create test repo");
+    let cache = TempDir::new().unwrap();
+    let offline = || {
+        let mut command = committor();
+        command
+            .env("COMMITTOR_POLICY", "http://127.0.0.1:9/policy.toml")
+            .env("XDG_CACHE_HOME", cache.path())
+            .arg("--repo")
+            .arg(test_repo.path());
+        command
+    };
+
+    offline().arg("types").assert().success();
+    offline()
+        .arg("lint")
+        .write_stdin("feat: add login\n")
+        .assert()
+        .success();
+    offline().arg("prompt-status").assert().success();
+}
+
+#[test]
+fn test_config_lint_and_show() {
+    let test_repo = TestRepo::new().expect("Failed to create test repo");
+    let settings = test_repo.path().join(".committor.toml");
+    let config = |args: &[&str]| {
+        committor()
+            .arg("--repo")
+            .arg(test_repo.path())
+            .args(args)
+            .assert()
+    };
+
+    fs::write(&settings, "[rules]\nmax_subject_length = 50\n").unwrap();
+    config(&["config", "lint"]).success();
+    config(&["--count", "2", "config", "show", "--resolved"])
+        .success()
+        .stdout(predicate::str::contains("--count = 2 # command line"))
+        .stdout(predicate::str::contains("rules.max_subject_length = 50 # "))
+        .stdout(predicate::str::contains("hooks.timeout = 8 # default"));
+
+    fs::write(&settings, "[rules]\nmax_subject_lenght = 50\n").unwrap();
+    config(&["config", "lint"])
+        .failure()
+        .stdout(predicate::str::contains(
+            ".committor.toml:2:1: unknown key `rules.max_subject_lenght`",
+        ));
 }
 
 #[test]
@@ -302,19 +734,7 @@ fn test_cli_argument_parsing() {
     ];
 
     for args in help_commands {
-        let output = Command::new("cargo")
-            .arg("run")
-            .arg("--")
-            .args(&args)
-            .current_dir(".")
-            .output()
-            .expect("Failed to execute command");
-
-        assert!(
-            output.status.success(),
-            "Help command failed: cargo run -- {}",
-            args.join(" ")
-        );
+        committor().args(&args).assert().success();
     }
 }
 
@@ -398,8 +818,8 @@ fn test_error_handling_for_invalid_options() {
         .expect("Failed to add file");
 
     // Test invalid count value
-    let output = Command::new("cargo")
-        .args(["run", "--", "generate", "--count", "0"])
+    let output = committor()
+        .args(["generate", "--count", "0"])
         .current_dir(test_repo.path())
         .output()
         .expect("Failed to execute command");
@@ -485,8 +905,8 @@ mod api_integration_tests {
             )
             .expect("Failed to add file");
 
-        let output = Command::new("cargo")
-            .args(["run", "--", "generate", "--count", "1"])
+        let output = committor()
+            .args(["generate", "--count", "1"])
             .current_dir(test_repo.path())
             .env("OPENAI_API_KEY", api_key)
             .output()
@@ -525,8 +945,8 @@ mod api_integration_tests {
             )
             .expect("Failed to add file");
 
-        let output = Command::new("cargo")
-            .args(["run", "--", "generate", "--count", "1"])
+        let output = committor()
+            .args(["generate", "--count", "1"])
             .current_dir(test_repo.path())
             .env("OPENAI_API_KEY", api_key)
             .output()
@@ -541,6 +961,765 @@ mod api_integration_tests {
     }
 }
 
+mod fake_provider_tests {
+    //! End-to-end runs against the local fake provider in `support`
+
+    use super::support::{FakeProvider, FAKE_MODEL};
+    use super::*;
+    use std::process::Output;
+
+    /// Run committor against a repository, feeding `input` to the picker
+    fn run_committor(repo: &TestRepo, args: &[&str], input: &str) -> Output {
+        committor()
+            .arg("--repo")
+            .arg(repo.path())
+            .args(["--model", FAKE_MODEL, "--no-update-check"])
+            .args(args)
+            .write_stdin(input)
+            .assert()
+            .success()
+            .get_output()
+            .clone()
+    }
+
+    fn head_message(repo: &TestRepo) -> String {
+        let head = repo.repo.head().unwrap().peel_to_commit().unwrap();
+        head.message().unwrap().trim().to_string()
+    }
+
+    #[test]
+    fn test_generate_with_fake_ollama() {
+        let provider = FakeProvider::start(&["feat: add greeting helper", "fix: trim names"]);
+        let test_repo = TestRepo::new().expect("Failed to create test repo");
+        test_repo
+            .add_file(
+                "src/greet.rs",
+                "pub fn greet(name: &str) -> String {\n    format!(\"Hi {name}\")\n}\n",
+            )
+            .expect("Failed to add file");
+
+        let output = run_committor(
+            &test_repo,
+            &[
+                "--provider",
+                "ollama",
+                "--ollama-url",
+                provider.ollama_url(),
+                "--count",
+                "2",
+                "generate",
+            ],
+            "",
+        );
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            stdout.contains("feat: add greeting helper"),
+            "stdout: {stdout}"
+        );
+        assert!(stdout.contains("fix: trim names"), "stdout: {stdout}");
+        let prompts = provider.prompts();
+        assert_eq!(prompts.len(), 2);
+        assert!(prompts[0].contains("src/greet.rs"));
+        assert_eq!(head_message(&test_repo), "Initial commit");
+    }
+
+    #[test]
+    fn test_generate_fails_validation_when_every_response_is_rejected() {
+        let provider = FakeProvider::start(&["Here is a commit message for your changes"]);
+        let test_repo = TestRepo::new().expect("Failed to create test repo");
+        test_repo
+            .add_file("src/greet.rs", "pub fn greet() {}\n")
+            .expect("Failed to add file");
+
+        // Wrappers tell rejected output apart from provider failures by the exit code
+        committor()
+            .arg("--repo")
+            .arg(test_repo.path())
+            .args(["--model", FAKE_MODEL, "--no-update-check"])
+            .args([
+                "--provider",
+                "ollama",
+                "--ollama-url",
+                provider.ollama_url(),
+            ])
+            .args(["--count", "1", "generate"])
+            .assert()
+            .code(4)
+            .stderr(predicate::str::contains("Validation failed"))
+            .stderr(predicate::str::contains("Rejected model output").not());
+        assert_eq!(provider.prompts().len(), 2);
+
+        // With --verbose the replies are shown with the rule each one broke
+        committor()
+            .arg("--repo")
+            .arg(test_repo.path())
+            .args(["--model", FAKE_MODEL, "--no-update-check", "--verbose"])
+            .args([
+                "--provider",
+                "ollama",
+                "--ollama-url",
+                provider.ollama_url(),
+            ])
+            .args(["--count", "1", "generate"])
+            .assert()
+            .code(4)
+            .stderr(predicate::str::contains(
+                "Rejected model output (last 2 of 2):",
+            ))
+            .stderr(predicate::str::contains(
+                "attempt 2: Here is a commit message for your changes",
+            ))
+            .stderr(predicate::str::contains("(conventional-format)"));
+    }
+
+    #[test]
+    fn test_per_file_commit_continues_past_empty_responses() {
+        let provider = FakeProvider::start(&["", "", "feat(b): add b"]);
+        let test_repo = TestRepo::new().expect("Failed to create test repo");
+        test_repo
+            .add_file("a.rs", "pub fn a() {}\n")
+            .expect("Failed to add file");
+        test_repo
+            .add_file("b.rs", "pub fn b() {}\n")
+            .expect("Failed to add file");
+
+        committor()
+            .arg("--repo")
+            .arg(test_repo.path())
+            .args(["--model", FAKE_MODEL, "--no-update-check"])
+            .args([
+                "--provider",
+                "ollama",
+                "--ollama-url",
+                provider.ollama_url(),
+            ])
+            .args(["-y", "commit", "--per-file", "--continue-on-error"])
+            .assert()
+            .code(3)
+            .stdout(predicate::str::contains("1 committed, 1 failed, 0 skipped"))
+            .stdout(predicate::str::contains(
+                "a.rs: AI Provider error: the model returned 2 empty responses",
+            ));
+
+        assert_eq!(head_message(&test_repo), "feat(b): add b");
+        let statuses = test_repo.repo.statuses(None).unwrap();
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(statuses.get(0).unwrap().path(), Some("a.rs"));
+    }
+
+    #[test]
+    fn test_quick_commit_falls_back_to_the_changed_paths() {
+        let provider = FakeProvider::start(&["feat(greet): add greet", "Added some stuff"]);
+        let test_repo = TestRepo::new().expect("Failed to create test repo");
+        let quick = || {
+            let mut command = committor();
+            command
+                .arg("--repo")
+                .arg(test_repo.path())
+                .args(["--model", FAKE_MODEL, "--no-update-check"])
+                .args([
+                    "--provider",
+                    "ollama",
+                    "--ollama-url",
+                    provider.ollama_url(),
+                ])
+                .args(["commit", "--quick"]);
+            command
+        };
+
+        test_repo
+            .add_file("greet.rs", "pub fn greet() {}\n")
+            .expect("Failed to add file");
+        quick().assert().success();
+        assert_eq!(head_message(&test_repo), "feat(greet): add greet");
+
+        // Every later response breaks the rules
+        test_repo
+            .add_file("docs/setup.md", "# Setup\n")
+            .expect("Failed to add file");
+        quick().assert().success().stdout(predicate::str::contains(
+            "Committing a message written from the changed paths",
+        ));
+        assert_eq!(head_message(&test_repo), "docs(docs): add setup.md");
+
+        // With no provider to be found the message comes from the paths straight away
+        test_repo
+            .add_file("docs/usage.md", "# Usage\n")
+            .expect("Failed to add file");
+        committor()
+            .arg("--repo")
+            .arg(test_repo.path())
+            .args(["--no-update-check", "--ollama-url", "http://127.0.0.1:9"])
+            .args(["commit", "--quick"])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(
+                "Committing a message written from the changed paths",
+            ));
+        assert_eq!(head_message(&test_repo), "docs(docs): add usage.md");
+    }
+
+    #[test]
+    fn test_exit_codes_for_nothing_staged_and_cancelling() {
+        let provider = FakeProvider::start(&["feat(greet): add greet"]);
+        let test_repo = TestRepo::new().expect("Failed to create test repo");
+        let run = |command: &str, input: &str| {
+            committor()
+                .arg("--repo")
+                .arg(test_repo.path())
+                .args(["--model", FAKE_MODEL, "--no-update-check"])
+                .args([
+                    "--provider",
+                    "ollama",
+                    "--ollama-url",
+                    provider.ollama_url(),
+                ])
+                .args(["--count", "1", command])
+                .write_stdin(input)
+                .assert()
+        };
+
+        run("generate", "")
+            .code(2)
+            .stdout(predicate::str::contains("No staged changes"))
+            .stderr(predicate::str::is_empty());
+
+        test_repo
+            .add_file("src/greet.rs", "pub fn greet() {}\n")
+            .expect("Failed to add file");
+        run("commit", "q\n")
+            .code(6)
+            .stderr(predicate::str::is_empty());
+        assert_eq!(head_message(&test_repo), "Initial commit");
+    }
+
+    #[test]
+    fn test_commit_leaves_excluded_files_out_of_the_context() {
+        let provider = FakeProvider::start(&["chore: update fixtures", "feat(greet): add greet"]);
+        let test_repo = TestRepo::new().expect("Failed to create test repo");
+        test_repo
+            .add_file("data/fixtures.json", "{\"users\": [\"fixture-user\"]}\n")
+            .expect("Failed to add file");
+        test_repo
+            .add_file("src/greet.rs", "pub fn greet() {}\n")
+            .expect("Failed to add file");
+
+        // Leave the first file out, then commit the regenerated option
+        run_committor(
+            &test_repo,
+            &[
+                "--provider",
+                "ollama",
+                "--ollama-url",
+                provider.ollama_url(),
+                "--count",
+                "1",
+                "commit",
+            ],
+            "x\n1\n1\n",
+        );
+
+        let prompts = provider.prompts();
+        assert_eq!(prompts.len(), 2);
+        assert!(prompts[0].contains("fixture-user"));
+        assert!(!prompts[1].contains("fixture-user"));
+        assert!(!prompts[1].contains("data/fixtures.json"));
+        assert!(prompts[1].contains("src/greet.rs"));
+
+        // The excluded file is still committed
+        assert_eq!(head_message(&test_repo), "feat(greet): add greet");
+        let head = test_repo.repo.head().unwrap().peel_to_tree().unwrap();
+        assert!(head.get_path(Path::new("data/fixtures.json")).is_ok());
+    }
+
+    #[test]
+    fn test_from_generates_messages_from_a_description() {
+        let provider = FakeProvider::start(&["feat(uploader): add retry logic"]);
+        let test_repo = TestRepo::new().expect("Failed to create test repo");
+
+        // Nothing is staged; the description is all there is to go on
+        let output = run_committor(
+            &test_repo,
+            &[
+                "--provider",
+                "ollama",
+                "--ollama-url",
+                provider.ollama_url(),
+                "--count",
+                "1",
+                "from",
+                "-",
+            ],
+            "added retry logic to the uploader\n",
+        );
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            stdout.contains("feat(uploader): add retry logic"),
+            "stdout: {stdout}"
+        );
+        let prompts = provider.prompts();
+        assert_eq!(prompts.len(), 1);
+        assert!(
+            prompts[0].contains("## Description of the change:\nadded retry logic to the uploader")
+        );
+        assert_eq!(head_message(&test_repo), "Initial commit");
+    }
+
+    #[test]
+    fn test_rewrite_regenerates_messages_and_restore_undoes_it() {
+        let provider = FakeProvider::start(&["feat(greet): add greet"]);
+        let test_repo = TestRepo::new().expect("Failed to create test repo");
+        test_repo
+            .add_file("src/greet.rs", "pub fn greet() {}\n")
+            .expect("Failed to add file");
+        let status = std::process::Command::new("git")
+            .args(["commit", "-q", "-m", "wip"])
+            .current_dir(test_repo.path())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let original = test_repo.repo.head().unwrap().target().unwrap();
+
+        let args = [
+            "--provider",
+            "ollama",
+            "--ollama-url",
+            provider.ollama_url(),
+            "rewrite",
+            "--range",
+            "HEAD~1..HEAD",
+        ];
+        let output = run_committor(&test_repo, &[&args[..], &["--dry-run"]].concat(), "");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            stdout.contains("feat(greet): add greet"),
+            "stdout: {stdout}"
+        );
+        assert_eq!(head_message(&test_repo), "wip");
+
+        run_committor(&test_repo, &args, "y\n");
+        assert_eq!(head_message(&test_repo), "feat(greet): add greet");
+
+        // The rewrite is undone from the backup taken before it
+        let output = run_committor(&test_repo, &["restore", "--list"], "");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert_eq!(stdout.lines().count(), 1, "stdout: {stdout}");
+        assert!(stdout.contains(" wip"), "stdout: {stdout}");
+        run_committor(&test_repo, &["restore"], "");
+        assert_eq!(test_repo.repo.head().unwrap().target().unwrap(), original);
+    }
+
+    #[test]
+    fn test_generate_suggests_tests_for_untested_sources() {
+        let provider = FakeProvider::start(&["feat(greet): add greet"]);
+        let test_repo = TestRepo::new().expect("Failed to create test repo");
+        test_repo
+            .add_file("src/greet.rs", "pub fn greet() {}\n")
+            .expect("Failed to add file");
+
+        let output = run_committor(
+            &test_repo,
+            &[
+                "--provider",
+                "ollama",
+                "--ollama-url",
+                provider.ollama_url(),
+                "--count",
+                "1",
+                "--suggest-tests",
+                "generate",
+            ],
+            "",
+        );
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            stdout.contains("No test changes accompany these source changes: src/greet.rs"),
+            "stdout: {stdout}"
+        );
+        let prompts = provider.prompts();
+        assert_eq!(prompts.len(), 2);
+        assert!(prompts[1].contains("## Changed source files:\n- src/greet.rs"));
+    }
+
+    #[test]
+    fn test_review_comments_on_staged_hunks() {
+        let provider = FakeProvider::start(&[
+            "1 | bug | `greet` ignores its name\n2 | test | Nothing tests greeting",
+        ]);
+        let test_repo = TestRepo::new().expect("Failed to create test repo");
+        test_repo
+            .add_file("src/greet.rs", "pub fn greet(name: &str) {}\n")
+            .expect("Failed to add file");
+
+        let output = run_committor(
+            &test_repo,
+            &[
+                "--provider",
+                "ollama",
+                "--ollama-url",
+                provider.ollama_url(),
+                "review",
+                "--staged",
+            ],
+            "",
+        );
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("src/greet.rs"), "stdout: {stdout}");
+        assert!(
+            stdout.contains("bug  `greet` ignores its name"),
+            "stdout: {stdout}"
+        );
+        // The only staged hunk is 1, so the comment on 2 is dropped
+        assert!(
+            !stdout.contains("Nothing tests greeting"),
+            "stdout: {stdout}"
+        );
+        assert!(provider.prompts()[0].contains("### Hunk 1 (src/greet.rs)"));
+    }
+
+    #[test]
+    fn test_cherry_pick_adapts_the_message() {
+        let provider = FakeProvider::start(&["feat(greet): add spanish greeting"]);
+        let test_repo = TestRepo::new().expect("Failed to create test repo");
+        let git = |args: &[&str]| {
+            let output = Command::new("git")
+                .args(args)
+                .current_dir(test_repo.path())
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "git {args:?} failed");
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+        git(&["checkout", "-q", "-b", "side"]);
+        test_repo
+            .add_file("hola.txt", "hola\n")
+            .expect("Failed to add file");
+        git(&["commit", "-q", "-m", "Add spanish greeting\n\nRefs: ABC-1"]);
+        let picked = git(&["rev-parse", "HEAD"]);
+        git(&["checkout", "-q", "-"]);
+
+        run_committor(
+            &test_repo,
+            &[
+                "--provider",
+                "ollama",
+                "--ollama-url",
+                provider.ollama_url(),
+                "-y",
+                "cherry-pick",
+                "side",
+            ],
+            "",
+        );
+
+        assert_eq!(
+            git(&["log", "-1", "--format=%B"]),
+            format!(
+                "feat(greet): add spanish greeting\n\nRefs: ABC-1\n(cherry picked from commit {picked})"
+            )
+        );
+        assert!(provider.prompts()[0].contains("Add spanish greeting"));
+    }
+
+    #[test]
+    fn test_continue_cherry_pick_with_generated_message() {
+        let provider = FakeProvider::start(&["feat(greet): greet in both languages"]);
+        let test_repo = TestRepo::new().expect("Failed to create test repo");
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .args(args)
+                .current_dir(test_repo.path())
+                .output()
+                .unwrap()
+                .status
+                .success()
+        };
+        let write = |content: &str| fs::write(test_repo.path().join("greet.txt"), content).unwrap();
+        assert!(git(&["checkout", "-q", "-b", "side"]));
+        write("hola\n");
+        assert!(git(&["add", "greet.txt"]));
+        assert!(git(&["commit", "-q", "-m", "feat: greet in spanish"]));
+        assert!(git(&["checkout", "-q", "-"]));
+        write("hello\n");
+        assert!(git(&["add", "greet.txt"]));
+        assert!(git(&["commit", "-q", "-m", "feat: greet in english"]));
+        assert!(!git(&["cherry-pick", "side"]));
+
+        write("hello\nhola\n");
+        assert!(git(&["add", "greet.txt"]));
+        run_committor(
+            &test_repo,
+            &[
+                "--provider",
+                "ollama",
+                "--ollama-url",
+                provider.ollama_url(),
+                "continue",
+            ],
+            "1\n",
+        );
+
+        assert_eq!(
+            head_message(&test_repo),
+            "feat(greet): greet in both languages"
+        );
+        let prompts = provider.prompts();
+        assert!(prompts[0].contains("feat: greet in spanish"));
+        assert!(prompts[0].contains("- greet.txt"));
+    }
+
+    #[test]
+    fn test_commit_picks_option_with_fake_ollama() {
+        let provider =
+            FakeProvider::start(&["feat(greet): add greet", "chore: update greet module"]);
+        let test_repo = TestRepo::new().expect("Failed to create test repo");
+        test_repo
+            .add_file("src/greet.rs", "pub fn greet() {}\n")
+            .expect("Failed to add file");
+
+        run_committor(
+            &test_repo,
+            &[
+                "--provider",
+                "ollama",
+                "--ollama-url",
+                provider.ollama_url(),
+                "--count",
+                "2",
+                "commit",
+            ],
+            "2\n",
+        );
+
+        assert_eq!(head_message(&test_repo), "chore: update greet module");
+        assert!(!test_repo.has_staged_changes().unwrap());
+    }
+
+    #[test]
+    fn test_commit_rechecks_changes_staged_while_picking() {
+        let provider = FakeProvider::start(&[
+            "feat(greet): add greet",
+            "feat(greet): add greet and farewell",
+        ]);
+        let test_repo = TestRepo::new().expect("Failed to create test repo");
+        test_repo
+            .add_file("src/greet.rs", "pub fn greet() {}\n")
+            .expect("Failed to add file");
+
+        // An editor that stages another file while the message is being edited
+        let editor =
+            "echo 'pub fn farewell() {}' > src/farewell.rs && git add src/farewell.rs && true";
+        let output = committor()
+            .arg("--repo")
+            .arg(test_repo.path())
+            .args(["--model", FAKE_MODEL, "--no-update-check"])
+            .args([
+                "--provider",
+                "ollama",
+                "--ollama-url",
+                provider.ollama_url(),
+            ])
+            .args(["--count", "1", "commit"])
+            .env("GIT_EDITOR", editor)
+            .write_stdin("e\nr\n1\n")
+            .assert()
+            .success()
+            .get_output()
+            .clone();
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("The staged changes have changed"));
+        assert_eq!(
+            head_message(&test_repo),
+            "feat(greet): add greet and farewell"
+        );
+        let prompts = provider.prompts();
+        assert_eq!(prompts.len(), 2);
+        assert!(prompts[1].contains("src/farewell.rs"));
+    }
+
+    #[test]
+    fn test_commit_waits_for_index_lock() {
+        let provider = FakeProvider::start(&["feat(greet): add greet"]);
+        let test_repo = TestRepo::new().expect("Failed to create test repo");
+        test_repo
+            .add_file("src/greet.rs", "pub fn greet() {}\n")
+            .expect("Failed to add file");
+        let lock = test_repo.path().join(".git/index.lock");
+        let args = [
+            "--provider",
+            "ollama",
+            "--ollama-url",
+            provider.ollama_url(),
+            "--count",
+            "1",
+            "commit",
+            "--choose",
+            "1",
+        ];
+
+        // Reads don't need the lock, and a lock that isn't released fails clearly
+        std::fs::write(&lock, "").unwrap();
+        std::fs::write(
+            test_repo.path().join(".committor.toml"),
+            "[index_lock]\ntimeout = 0\n",
+        )
+        .unwrap();
+        committor()
+            .arg("--repo")
+            .arg(test_repo.path())
+            .args(["--model", FAKE_MODEL, "--no-update-check"])
+            .args(args)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(
+                "Another git process is still holding",
+            ));
+
+        // A lock released while waiting lets the commit through
+        std::fs::remove_file(test_repo.path().join(".committor.toml")).unwrap();
+        let releaser = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(300));
+            std::fs::remove_file(lock).unwrap();
+        });
+        run_committor(&test_repo, &args, "");
+        releaser.join().unwrap();
+
+        assert_eq!(head_message(&test_repo), "feat(greet): add greet");
+    }
+
+    #[test]
+    fn test_commit_with_fake_openai() {
+        let provider = FakeProvider::start(&["docs: add user guide"]);
+        let test_repo = TestRepo::new().expect("Failed to create test repo");
+        test_repo
+            .add_file("docs/guide.md", "# User Guide\n")
+            .expect("Failed to add file");
+
+        let openai_url = provider.openai_url();
+        run_committor(
+            &test_repo,
+            &[
+                "--provider",
+                "openai",
+                "--api-key",
+                "test-key",
+                "--openai-url",
+                &openai_url,
+                "--count",
+                "1",
+                "commit",
+                "--choose",
+                "1",
+            ],
+            "",
+        );
+
+        assert_eq!(head_message(&test_repo), "docs: add user guide");
+        let requests = provider.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, "/v1/responses");
+        assert!(provider.prompts()[0].contains("docs/guide.md"));
+    }
+
+    #[test]
+    fn test_gate_checks_pr_title_with_fake_ollama() {
+        let provider = FakeProvider::start(&["feat(auth): add logout endpoint"]);
+        let test_repo = TestRepo::new().expect("Failed to create test repo");
+        test_repo
+            .add_file("src/auth.rs", "pub fn logout() {}\n")
+            .expect("Failed to add file");
+        let status = std::process::Command::new("git")
+            .args(["commit", "-q", "-m", "wip"])
+            .current_dir(test_repo.path())
+            .status()
+            .expect("Failed to run git commit");
+        assert!(status.success());
+
+        let gate = |title: &str, extra: &[&str]| {
+            committor()
+                .arg("--repo")
+                .arg(test_repo.path())
+                .args(["--model", FAKE_MODEL, "--no-update-check"])
+                .args([
+                    "--provider",
+                    "ollama",
+                    "--ollama-url",
+                    provider.ollama_url(),
+                ])
+                .args(["--count", "1", "gate", "--base", "HEAD~1", "--title", title])
+                .args(extra)
+                .assert()
+        };
+
+        gate("feat: add logout endpoint", &[])
+            .success()
+            .stdout(predicate::str::contains("The title matches the change"));
+        gate("docs: fix typo in readme", &[])
+            .failure()
+            .stdout(predicate::str::contains("looks like `feat`"));
+        gate("docs: fix typo in readme", &["--mode", "warn"]).success();
+        assert!(provider.prompts()[0].contains("src/auth.rs"));
+    }
+
+    #[test]
+    fn test_export_session_after_commit() {
+        let provider = FakeProvider::start(&["feat(greet): add greet", "fix: trim names"]);
+        let test_repo = TestRepo::new().expect("Failed to create test repo");
+        test_repo
+            .add_file("src/greet.rs", "pub fn greet() {}\n")
+            .expect("Failed to add file");
+        let export = |file: &str| {
+            committor()
+                .arg("--repo")
+                .arg(test_repo.path())
+                .args(["export-session", file])
+                .assert()
+        };
+
+        export("session.md")
+            .failure()
+            .stderr(predicate::str::contains("No session to export"));
+
+        run_committor(
+            &test_repo,
+            &[
+                "--provider",
+                "ollama",
+                "--ollama-url",
+                provider.ollama_url(),
+                "--count",
+                "2",
+                "commit",
+                "--choose",
+                "1",
+            ],
+            "",
+        );
+        let chosen = head_message(&test_repo);
+
+        export("session.md").success();
+        let markdown = std::fs::read_to_string(test_repo.path().join("session.md")).unwrap();
+        assert!(markdown.contains("| `src/greet.rs` | added | 1 | 0 |"));
+        assert!(markdown.contains(". feat(greet): add greet\n"));
+        assert!(markdown.contains(&format!("## Chosen\n\n```\n{chosen}\n```")));
+        assert!(markdown.contains("| provider call |"));
+
+        export("session.json").success();
+        let json: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(test_repo.path().join("session.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(json["command"], "commit");
+        assert_eq!(json["rounds"][0].as_array().unwrap().len(), 2);
+        assert_eq!(json["chosen"], chosen.as_str());
+    }
+}
+
 /// Test to ensure basic git operations perform reasonably
 #[test]
 fn test_performance_basic_operations() {
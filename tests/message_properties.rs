@@ -0,0 +1,125 @@
+//! Property tests for commit message parsing, validation and body layout
+//!
+//! Messages come from a model, so the parsers see every shape of text: unicode
+//! scopes, breaking markers, subjects just over the length limit. These check
+//! the invariants that must hold for all of them rather than a few examples.
+
+use committor::body::{format_body, format_message_body, BODY_WIDTH};
+use committor::commit::{is_valid_commit_message, parse_commit_message};
+use committor::types::{CommitType, ConventionalCommit};
+use proptest::prelude::*;
+
+/// Longest subject `is_valid_commit_message` accepts, in characters
+const MAX_SUBJECT_LENGTH: usize = 72;
+
+fn commit_type() -> impl Strategy<Value = CommitType> {
+    proptest::sample::select(CommitType::all())
+}
+
+/// Scopes: ASCII names, and any unicode apart from parentheses and line breaks
+fn scope() -> impl Strategy<Value = String> {
+    prop_oneof![
+        "[a-z][a-z0-9_-]{0,15}",
+        "[^()\\n\\r]{1,12}",
+        Just("ü".to_string()),
+        Just("認証".to_string()),
+    ]
+}
+
+/// Descriptions: one line that doesn't start with whitespace, of any length
+fn description() -> impl Strategy<Value = String> {
+    prop_oneof![
+        "[a-z][a-z ]{0,40}",
+        "[^\\s][^\\n]{0,100}",
+        "[a-zé ]{60,90}".prop_map(|text| format!("x{text}")),
+    ]
+}
+
+fn conventional_commit() -> impl Strategy<Value = ConventionalCommit> {
+    (
+        commit_type(),
+        proptest::option::of(scope()),
+        any::<bool>(),
+        description(),
+    )
+        .prop_map(|(commit_type, scope, breaking, description)| {
+            let mut commit = ConventionalCommit::new(commit_type, description);
+            if let Some(scope) = scope {
+                commit = commit.with_scope(scope);
+            }
+            if breaking {
+                commit = commit.with_breaking();
+            }
+            commit
+        })
+}
+
+proptest! {
+    #[test]
+    fn parse_reads_back_what_display_writes(commit in conventional_commit()) {
+        let parsed = parse_commit_message(&commit.to_string()).unwrap();
+        prop_assert_eq!(parsed, commit);
+    }
+
+    #[test]
+    fn valid_subjects_are_well_formed_and_short(commit in conventional_commit()) {
+        let subject = commit.to_string();
+        let short = subject.chars().count() <= MAX_SUBJECT_LENGTH;
+        prop_assert_eq!(is_valid_commit_message(&subject), short);
+    }
+
+    #[test]
+    fn valid_subjects_always_parse(message in any::<String>()) {
+        if is_valid_commit_message(&message) {
+            prop_assert!(parse_commit_message(&message).is_ok());
+            prop_assert!(message.chars().count() <= MAX_SUBJECT_LENGTH);
+        }
+    }
+
+    #[test]
+    fn unknown_types_are_rejected(
+        commit_type in "[a-z]{1,10}",
+        description in description(),
+    ) {
+        prop_assume!(commit_type.parse::<CommitType>().is_err());
+        let message = format!("{commit_type}: {description}");
+        prop_assert!(!is_valid_commit_message(&message));
+        prop_assert!(parse_commit_message(&message).is_err());
+    }
+
+    #[test]
+    fn multi_line_text_is_not_a_subject(commit in conventional_commit(), body in "[a-z ]{1,20}") {
+        let message = format!("{commit}\n\n{body}");
+        prop_assert!(!is_valid_commit_message(&message));
+        prop_assert!(parse_commit_message(&message).is_err());
+    }
+}
+
+proptest! {
+    // Body layout wraps every paragraph, so fewer cases keep this quick
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn formatting_keeps_the_subject(commit in conventional_commit(), body in "[^\\r]{0,300}") {
+        let subject = commit.to_string();
+        let formatted = format_message_body(&format!("{subject}\n\n{body}"));
+        prop_assert_eq!(formatted.lines().next().unwrap_or_default(), subject.trim());
+    }
+
+    #[test]
+    fn formatting_a_body_twice_changes_nothing(
+        words in proptest::collection::vec("[a-zA-Z0-9,.]{1,12}|- |\\n|\\n\\n", 0..80),
+    ) {
+        let body = words.join(" ");
+        let formatted = format_body(&body);
+        prop_assert_eq!(format_body(&formatted), formatted);
+    }
+
+    #[test]
+    fn formatted_prose_fits_the_width(words in proptest::collection::vec("[a-z]{1,20}", 1..60)) {
+        let formatted = format_body(&words.join(" "));
+        for line in formatted.lines() {
+            prop_assert!(line.chars().count() <= BODY_WIDTH, "{}", line);
+        }
+    }
+}
@@ -0,0 +1,342 @@
+//! Snapshot tests for every prompt template
+//!
+//! A wording change in a prompt shifts what every model writes, so each template
+//! is rendered for a set of representative diffs and compared with the reviewed
+//! copy under `tests/snapshots/`. Every snapshot starts with `PROMPT_VERSION`:
+//! a template change fails here until the new snapshots are accepted with
+//! `cargo insta review`, which is the time to bump the version too.
+
+use committor::migrations::MigrationSettings;
+use committor::prompt::{
+    create_analysis_prompt, create_contextual_commit_prompt, create_fix_commit_prompt,
+    create_paths_only_prompt, create_review_prompt, create_split_prompt, create_stash_prompt,
+    create_tag_prompt, create_test_suggestions_prompt, CommitContext, LanguageSettings,
+    PromptBudget, PromptBuilder, PromptStyle, RepositoryContext, PROMPT_VERSION,
+};
+use committor::rules::{Case, Rules};
+use committor::types::{DiffChange, DiffChangeType, DiffHunk};
+
+const FEATURE_DIFF: &str = "\
+diff --git a/src/auth.rs b/src/auth.rs
+index 3b18e51..a9c2f04 100644
+--- a/src/auth.rs
++++ b/src/auth.rs
+@@ -10,6 +10,12 @@ impl Session {
+     pub fn user(&self) -> &User {
+         &self.user
+     }
++
++    /// End the session and forget the token
++    pub fn logout(&mut self) {
++        self.expires_at = Instant::now();
++    }
+ }
+";
+
+const RENAME_DIFF: &str = "\
+diff --git a/src/util.rs b/src/paths.rs
+similarity index 92%
+rename from src/util.rs
+rename to src/paths.rs
+index 1a2b3c4..5d6e7f8 100644
+--- a/src/util.rs
++++ b/src/paths.rs
+@@ -1,4 +1,4 @@
+-//! Miscellaneous helpers
++//! Path helpers
+
+ pub fn normalize(path: &str) -> String {
+     path.replace('\\\\', \"/\")
+";
+
+const BINARY_DIFF: &str = "\
+diff --git a/assets/logo.png b/assets/logo.png
+new file mode 100644
+index 0000000..8f3a2b1
+Binary files /dev/null and b/assets/logo.png differ
+diff --git a/README.md b/README.md
+index 2e4f1c0..7b9d3a2 100644
+--- a/README.md
++++ b/README.md
+@@ -1,3 +1,5 @@
+ # Committor
++
++![logo](assets/logo.png)
+";
+
+const SECRETS_DIFF: &str = "\
+diff --git a/config/settings.py b/config/settings.py
+index 4c5d6e7..8f9a0b1 100644
+--- a/config/settings.py
++++ b/config/settings.py
+@@ -1,4 +1,6 @@
+ DEBUG = False
++DATABASE_PASSWORD = \"hunter2\"
++STRIPE_API_TOKEN = \"sk_live_51H8abcdefghijklmnop\"
+ ALLOWED_HOSTS = [\"example.com\"]
++TIMEOUT_SECONDS = 30
+";
+
+/// A generated file longer than the line budget, with one line over the length budget
+fn huge_file_diff() -> String {
+    let mut diff = String::from(
+        "diff --git a/src/generated.rs b/src/generated.rs\n\
+         new file mode 100644\n\
+         --- /dev/null\n\
+         +++ b/src/generated.rs\n\
+         @@ -0,0 +1,300 @@\n",
+    );
+    diff.push_str(&format!(
+        "+pub const TABLE: &str = \"{}\";\n",
+        "ab".repeat(120)
+    ));
+    for i in 0..300 {
+        diff.push_str(&format!("+pub const VALUE_{i}: u32 = {i};\n"));
+    }
+    diff
+}
+
+/// Diffs every diff-based template is rendered for
+fn diffs() -> Vec<(&'static str, String)> {
+    vec![
+        ("feature", FEATURE_DIFF.to_string()),
+        ("rename", RENAME_DIFF.to_string()),
+        ("binary", BINARY_DIFF.to_string()),
+        ("huge_file", huge_file_diff()),
+        ("secrets", SECRETS_DIFF.to_string()),
+    ]
+}
+
+/// Snapshot a prompt, headed by the prompt version it was written for
+fn assert_prompt(name: &str, prompt: &str) {
+    let snapshot = format!("prompt version {PROMPT_VERSION}\n\n{prompt}");
+    insta::assert_snapshot!(name, snapshot);
+}
+
+fn full_context() -> CommitContext {
+    CommitContext {
+        branch_commits: vec!["feat(auth): add session expiry".to_string()],
+        previous_subject: Some("feat(auth): add session expiry".to_string()),
+        similar_commits: vec!["feat(auth): add login endpoint".to_string()],
+        changed_symbols: vec!["added function `Session::logout`".to_string()],
+        dependency_changes: vec!["bump tokio from 1.37 to 1.38".to_string()],
+        migration_files: vec!["migrations/2024-05-01_sessions/up.sql".to_string()],
+        ci_changes: vec![".github/workflows/ci.yml (workflow `CI`): add job `lint`".to_string()],
+        binary_assets: vec!["added 1 PNG image in `assets/` (12.0 KB, 64x64)".to_string()],
+        feedback_guidance: vec![
+            "Include a scope (the user accepted 3 suggestions that did)".to_string()
+        ],
+    }
+}
+
+#[test]
+fn test_commit_prompt() {
+    for (name, diff) in diffs() {
+        assert_prompt(
+            &format!("commit_{name}"),
+            &PromptBuilder::new().commit_prompt(&diff),
+        );
+    }
+}
+
+#[test]
+fn test_commit_prompt_options() {
+    assert_prompt(
+        "commit_concise",
+        &PromptBuilder::new()
+            .style(PromptStyle::Concise)
+            .commit_prompt(FEATURE_DIFF),
+    );
+    assert_prompt(
+        "commit_with_body_and_languages",
+        &PromptBuilder::new()
+            .with_body(true)
+            .languages(&LanguageSettings {
+                subject: Some("German".to_string()),
+                body: Some("Japanese".to_string()),
+            })
+            .commit_prompt(FEATURE_DIFF),
+    );
+    assert_prompt(
+        "commit_sentence_case",
+        &PromptBuilder::new()
+            .casing(&Rules {
+                subject_case: Some(Case::Sentence),
+                scope_case: Some(Case::Lower),
+                ..Rules::default()
+            })
+            .commit_prompt(FEATURE_DIFF),
+    );
+    assert_prompt(
+        "commit_small_budget",
+        &PromptBuilder::new()
+            .budget(PromptBudget {
+                max_lines: 20,
+                max_line_length: 60,
+            })
+            .commit_prompt(&huge_file_diff()),
+    );
+    assert_prompt(
+        "commit_full_context",
+        &PromptBuilder::new()
+            .with_body(true)
+            .symbols_in_body(true)
+            .migrations(&MigrationSettings {
+                scope: Some("db".to_string()),
+                ..MigrationSettings::default()
+            })
+            .context(full_context())
+            .commit_prompt(FEATURE_DIFF),
+    );
+}
+
+#[test]
+fn test_multiple_commit_prompt() {
+    for (name, diff) in diffs() {
+        assert_prompt(
+            &format!("multiple_{name}"),
+            &PromptBuilder::new().multiple_commit_prompt(&diff, 3),
+        );
+    }
+}
+
+#[test]
+fn test_paths_only_prompt() {
+    let changes = vec![
+        DiffChange {
+            file_path: "src/paths.rs".to_string(),
+            change_type: DiffChangeType::Renamed,
+            additions: 1,
+            deletions: 1,
+        },
+        DiffChange {
+            file_path: "assets/logo.png".to_string(),
+            change_type: DiffChangeType::Added,
+            additions: 0,
+            deletions: 0,
+        },
+    ];
+    assert_prompt(
+        "paths_only",
+        &create_paths_only_prompt(&changes, &CommitContext::default()),
+    );
+}
+
+#[test]
+fn test_description_prompt() {
+    assert_prompt(
+        "description",
+        &PromptBuilder::new().description_prompt("added retry logic to the uploader\n"),
+    );
+}
+
+#[test]
+fn test_conflict_prompt() {
+    assert_prompt(
+        "conflict",
+        &PromptBuilder::new().conflict_prompt(
+            "feat(auth): add JWT validation\n\nRefs: ABC-1\n",
+            &["src/auth.rs".to_string()],
+            FEATURE_DIFF,
+        ),
+    );
+}
+
+#[test]
+fn test_cherry_pick_prompt() {
+    assert_prompt(
+        "cherry_pick",
+        &PromptBuilder::new().cherry_pick_prompt(
+            "feat(session): add logout\n\nRefs: ABC-1\n",
+            &["auth".to_string(), "api".to_string()],
+            FEATURE_DIFF,
+        ),
+    );
+}
+
+#[test]
+fn test_stash_prompt() {
+    assert_prompt("stash_feature", &create_stash_prompt(FEATURE_DIFF));
+    assert_prompt("stash_secrets", &create_stash_prompt(SECRETS_DIFF));
+}
+
+#[test]
+fn test_tag_prompt() {
+    let subjects = vec![
+        "feat(auth): add logout".to_string(),
+        "fix(paths): normalize separators".to_string(),
+    ];
+    assert_prompt(
+        "tag_with_previous",
+        &create_tag_prompt("v1.2.0", Some("v1.1.0"), &subjects),
+    );
+    assert_prompt("tag_first", &create_tag_prompt("v0.1.0", None, &subjects));
+}
+
+fn sample_hunks() -> Vec<DiffHunk> {
+    vec![
+        DiffHunk {
+            file_path: "src/auth.rs".to_string(),
+            file_header: "diff --git a/src/auth.rs b/src/auth.rs".to_string(),
+            text: Some("@@ -10,3 +10,7 @@\n+    pub fn logout(&mut self) {}\n".to_string()),
+        },
+        DiffHunk {
+            file_path: "assets/logo.png".to_string(),
+            file_header: "diff --git a/assets/logo.png b/assets/logo.png".to_string(),
+            text: None,
+        },
+    ]
+}
+
+#[test]
+fn test_split_prompt() {
+    assert_prompt("split", &create_split_prompt(&sample_hunks()));
+}
+
+#[test]
+fn test_test_suggestions_prompt() {
+    let sources = vec!["src/auth.rs".to_string()];
+    assert_prompt(
+        "test_suggestions",
+        &create_test_suggestions_prompt(Some(FEATURE_DIFF), &sources),
+    );
+    assert_prompt(
+        "test_suggestions_paths_only",
+        &create_test_suggestions_prompt(None, &sources),
+    );
+}
+
+#[test]
+fn test_review_prompt() {
+    assert_prompt("review", &create_review_prompt(&sample_hunks()));
+}
+
+#[test]
+fn test_analysis_and_fix_prompts() {
+    assert_prompt("analysis", &create_analysis_prompt("Added logout."));
+    assert_prompt(
+        "fix",
+        &create_fix_commit_prompt(
+            "Added logout.",
+            &[
+                "Missing commit type".to_string(),
+                "Ends with a period".to_string(),
+            ],
+        ),
+    );
+}
+
+#[test]
+fn test_contextual_commit_prompt() {
+    let context = RepositoryContext {
+        language: "Rust".to_string(),
+        project_type: "Library".to_string(),
+        branch: "feature/logout".to_string(),
+        files_changed: "src/auth.rs".to_string(),
+        recent_commits: vec!["feat(auth): add session expiry".to_string()],
+    };
+    assert_prompt(
+        "contextual_feature",
+        &create_contextual_commit_prompt(FEATURE_DIFF, &context),
+    );
+}
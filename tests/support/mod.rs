@@ -0,0 +1,202 @@
+//! A local stand-in for the OpenAI and Ollama APIs
+//!
+//! Integration tests point committor at this server instead of a real provider,
+//! so the whole generate, pick and commit flow runs without keys or a network.
+//! Each chat request is answered with the next scripted message, and every
+//! request is recorded so tests can check what was sent.
+
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Model name the server reports and answers as
+pub const FAKE_MODEL: &str = "fake-model";
+
+/// A request the server received
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub body: String,
+}
+
+impl RecordedRequest {
+    /// Get the text of the last message in a chat request
+    pub fn prompt(&self) -> Option<String> {
+        let body: Value = serde_json::from_str(&self.body).ok()?;
+        // Ollama sends `messages`, the OpenAI Responses API sends `input`
+        let messages = body.get("messages").or_else(|| body.get("input"))?;
+        let message = messages.as_array()?.last()?;
+        match message.get("content")? {
+            Value::String(content) => Some(content.clone()),
+            // OpenAI content is often a list of typed parts
+            Value::Array(parts) => Some(
+                parts
+                    .iter()
+                    .filter_map(|part| part.get("text").and_then(Value::as_str))
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    responses: VecDeque<String>,
+    last_response: String,
+    requests: Vec<RecordedRequest>,
+}
+
+impl State {
+    /// Take the next scripted response, repeating the last one once the script runs out
+    fn next_response(&mut self) -> String {
+        if let Some(response) = self.responses.pop_front() {
+            self.last_response = response;
+        }
+        self.last_response.clone()
+    }
+}
+
+/// A fake provider listening on a local port for as long as the test runs
+pub struct FakeProvider {
+    url: String,
+    state: Arc<Mutex<State>>,
+}
+
+impl FakeProvider {
+    /// Start a server that answers chat requests with the given messages in order
+    pub fn start(responses: &[&str]) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind the fake provider");
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let state = Arc::new(Mutex::new(State {
+            responses: responses.iter().map(ToString::to_string).collect(),
+            ..State::default()
+        }));
+
+        let server_state = Arc::clone(&state);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let state = Arc::clone(&server_state);
+                thread::spawn(move || serve_connection(stream, &state));
+            }
+        });
+
+        Self { url, state }
+    }
+
+    /// Base URL to pass as `--ollama-url`
+    pub fn ollama_url(&self) -> &str {
+        &self.url
+    }
+
+    /// Base URL to pass as `--openai-url`
+    pub fn openai_url(&self) -> String {
+        format!("{}/v1", self.url)
+    }
+
+    /// Get every request received so far
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// Get the prompts of the chat requests received so far
+    pub fn prompts(&self) -> Vec<String> {
+        self.requests()
+            .iter()
+            .filter(|request| is_chat(&request.path))
+            .filter_map(RecordedRequest::prompt)
+            .collect()
+    }
+}
+
+fn is_chat(path: &str) -> bool {
+    path == "/api/chat" || path.ends_with("/responses")
+}
+
+/// Answer requests on one connection until the client closes it
+fn serve_connection(stream: TcpStream, state: &Mutex<State>) {
+    let mut reader = BufReader::new(stream.try_clone().expect("Failed to clone the stream"));
+    let mut writer = stream;
+    while let Some(request) = read_request(&mut reader) {
+        let (status, body) = respond(&request, state);
+        state.lock().unwrap().requests.push(request);
+
+        let response = format!(
+            "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        );
+        if writer.write_all(response.as_bytes()).is_err() {
+            return;
+        }
+    }
+}
+
+/// Read one HTTP/1.1 request, `None` once the connection is closed
+fn read_request(reader: &mut BufReader<TcpStream>) -> Option<RecordedRequest> {
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).ok()? == 0 {
+        return None;
+    }
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let path = parts.next()?.to_string();
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).ok()?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().ok()?;
+            }
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).ok()?;
+    Some(RecordedRequest {
+        method,
+        path,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
+
+/// Build the status line and JSON body for a request
+fn respond(request: &RecordedRequest, state: &Mutex<State>) -> (&'static str, String) {
+    let path = request.path.as_str();
+    let body = match (request.method.as_str(), path) {
+        ("GET", "/api/tags") => json!({ "models": [{ "name": FAKE_MODEL }] }),
+        ("POST", "/api/chat") => json!({
+            "model": FAKE_MODEL,
+            "created_at": "2024-01-01T00:00:00Z",
+            "message": { "role": "assistant", "content": state.lock().unwrap().next_response() },
+            "done": true,
+        }),
+        ("POST", _) if path.ends_with("/responses") => json!({
+            "id": "resp_fake",
+            "object": "response",
+            "created_at": 0,
+            "status": "completed",
+            "model": FAKE_MODEL,
+            "output": [{
+                "type": "message",
+                "id": "msg_fake",
+                "role": "assistant",
+                "status": "completed",
+                "content": [{ "type": "output_text", "text": state.lock().unwrap().next_response() }],
+            }],
+            "tools": [],
+            "metadata": {},
+        }),
+        _ => return ("404 Not Found", json!({ "error": "not found" }).to_string()),
+    };
+    ("200 OK", body.to_string())
+}